@@ -1,10 +1,16 @@
 extern crate dnsproto;
-use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
 
+use dnsproto::dnsname::{CompressionMap, DNSName};
 use dnsproto::edns::EDNS;
 use dnsproto::message::Message;
 use dnsproto::meta::{DNSClass, DNSType, Header, Question, ResourceRecord};
-use dnsproto::qtype::{DnsTypeA, DnsTypeNS, DnsTypeOpt};
+use dnsproto::qtype::DnsTypeNS;
+use dnsproto::zone::{ZoneReader, ZoneTextReader};
+use std::fmt::Write;
+
+// records of the synthetic zone used by zone parsing benchmark
+const ZONE_RECORDS: usize = 10_000;
 
 fn dns_parse_message(c: &mut Criterion) {
     let message = [
@@ -46,6 +52,45 @@ fn dns_parse_message(c: &mut Criterion) {
     });
 }
 
+// packets captured in message.rs tests
+fn dns_parse_captured_message(c: &mut Criterion) {
+    let query: &[u8] = &[
+        0x2b, 0x01, 0x01, 0x20, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01, 0x03, 0x63, 0x6f,
+        0x6d, 0x00, 0x00, 0x02, 0x00, 0x01, 0x00, 0x00, 0x29, 0x10, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00,
+    ];
+    let nxdomain: &[u8] = &[
+        0x8e, 0xd0, 0x81, 0x83, 0x00, 0x01, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x04, 0x50, 0x41,
+        0x55, 0x4c, 0x00, 0x00, 0x01, 0x00, 0x01, 0x00, 0x00, 0x06, 0x00, 0x01, 0x00, 0x00, 0x2a,
+        0x30, 0x00, 0x40, 0x01, 0x61, 0x0c, 0x72, 0x6f, 0x6f, 0x74, 0x2d, 0x73, 0x65, 0x72, 0x76,
+        0x65, 0x72, 0x73, 0x03, 0x6e, 0x65, 0x74, 0x00, 0x05, 0x6e, 0x73, 0x74, 0x6c, 0x64, 0x0c,
+        0x76, 0x65, 0x72, 0x69, 0x73, 0x69, 0x67, 0x6e, 0x2d, 0x67, 0x72, 0x73, 0x03, 0x63, 0x6f,
+        0x6d, 0x00, 0x78, 0x68, 0x7a, 0x68, 0x00, 0x00, 0x07, 0x08, 0x00, 0x00, 0x03, 0x84, 0x00,
+        0x09, 0x3a, 0x80, 0x00, 0x01, 0x51, 0x80,
+    ];
+    let ns_answer: &[u8] = &[
+        202, 177, 129, 0, 0, 1, 0, 4, 0, 0, 0, 1, 6, 103, 111, 111, 103, 108, 101, 3, 99, 111, 109,
+        0, 0, 2, 0, 1, 192, 12, 0, 2, 0, 1, 0, 0, 39, 16, 0, 6, 3, 110, 115, 49, 192, 12, 192, 12,
+        0, 2, 0, 1, 0, 0, 39, 16, 0, 6, 3, 110, 115, 50, 192, 12, 192, 12, 0, 2, 0, 1, 0, 0, 39,
+        16, 0, 6, 3, 110, 115, 51, 192, 12, 192, 12, 0, 2, 0, 1, 0, 0, 39, 16, 0, 6, 3, 110, 115,
+        52, 192, 12, 0, 0, 41, 4, 219, 0, 0, 0, 0, 0, 0,
+    ];
+    let mut group = c.benchmark_group("parse_captured_message");
+    for (name, packet) in [
+        ("query", query),
+        ("nxdomain", nxdomain),
+        ("ns_answer", ns_answer),
+    ]
+    .iter()
+    {
+        group.throughput(Throughput::Bytes(packet.len() as u64));
+        group.bench_function(*name, |b| {
+            b.iter(|| Message::parse_dns_message(black_box(packet)))
+        });
+    }
+    group.finish();
+}
+
 fn dns_encode_answer_message(c: &mut Criterion) {
     let mut header = Header::new();
     header.set_id(0xcab1);
@@ -74,8 +119,9 @@ fn dns_encode_answer_message(c: &mut Criterion) {
     }
     message.header.set_qr(true);
     message.header.set_rd(true);
-    c.bench_function("encode_answer_message", |b| {
-        b.iter(|| Message::encode(&mut message))
+    c.bench_function("encode_answer_message", |b| b.iter(|| message.encode(true)));
+    c.bench_function("encode_answer_message_uncompressed", |b| {
+        b.iter(|| message.encode_uncompressed(true))
     });
 }
 
@@ -92,14 +138,92 @@ fn dns_encode_question_message(c: &mut Criterion) {
     message.header.set_qr(false);
     message.header.set_rd(true);
     c.bench_function("encode_question_message", |b| {
-        b.iter(|| Message::encode(&mut message))
+        b.iter(|| message.encode(true))
+    });
+}
+
+// deep names sharing suffixes in different cases, every label of every name is looked
+// up in the compression map, which used to clone all labels of the rest suffix
+fn dns_encode_deep_names_message(c: &mut Criterion) {
+    let mut message = Message::new_with_header(Header::new());
+    message
+        .set_question(Question::new("a.b.c.d.e.example.com.", DNSType::NS, DNSClass::IN).unwrap());
+    for index in 0..32 {
+        let ns = if index % 2 == 0 {
+            format!("ns{}.a.b.c.d.e.example.com.", index)
+        } else {
+            format!("NS{}.A.B.C.D.E.Example.COM.", index)
+        };
+        let answer = ResourceRecord::new(
+            "a.b.c.d.e.example.com.",
+            DNSType::NS,
+            DNSClass::IN,
+            10000,
+            Some(Box::new(DnsTypeNS::new(&ns).unwrap())),
+        )
+        .unwrap();
+        message.append_answer(answer);
+    }
+    message.header.set_qr(true);
+    c.bench_function("encode_deep_names_message", |b| {
+        b.iter(|| message.encode(true))
+    });
+}
+
+fn dns_name_to_binary(c: &mut Criterion) {
+    let zone = DNSName::new("google.com.", None).unwrap();
+    let name = DNSName::new("ns1.google.com.", None).unwrap();
+    let mut compression = CompressionMap::new();
+    // warm the compression map as the question has been encoded
+    zone.to_binary(Some((&mut compression, 12)));
+    c.bench_function("dnsname_to_binary_with_compression", |b| {
+        b.iter(|| name.to_binary(Some((&mut compression, 64))))
+    });
+    c.bench_function("dnsname_to_binary", |b| b.iter(|| name.to_binary(None)));
+}
+
+fn synthetic_zone(records: usize) -> String {
+    let mut zone = String::from(
+        "$ORIGIN example.com.\n$TTL 3600\n@ IN SOA ns.example.com. admin.example.com. ( 1 7200 3600 1209600 3600 )\n",
+    );
+    for index in 0..records {
+        let written = match index % 3 {
+            0 => writeln!(
+                zone,
+                "host{} IN A 192.0.{}.{}",
+                index,
+                index / 256 % 256,
+                index % 256
+            ),
+            1 => writeln!(zone, "host{} 300 IN MX 10 mail{}", index, index),
+            _ => writeln!(zone, "host{} IN TXT \"v=spf1 -all\" ; comment", index),
+        };
+        written.unwrap();
+    }
+    zone
+}
+
+fn zone_parse_throughput(c: &mut Criterion) {
+    let zone = synthetic_zone(ZONE_RECORDS);
+    let mut group = c.benchmark_group("zone_parse");
+    group.throughput(Throughput::Elements(ZONE_RECORDS as u64 + 1));
+    group.bench_function("zone_text_reader", |b| {
+        b.iter(|| {
+            let reader = ZoneReader::new(ZoneTextReader::new(&zone), None);
+            reader.filter(|rr| rr.is_ok()).count()
+        })
     });
+    group.finish();
 }
 
 criterion_group!(
     codec_benches,
     dns_parse_message,
+    dns_parse_captured_message,
     dns_encode_question_message,
-    dns_encode_answer_message
+    dns_encode_answer_message,
+    dns_encode_deep_names_message,
+    dns_name_to_binary
 );
-criterion_main!(codec_benches);
+criterion_group!(zone_benches, zone_parse_throughput);
+criterion_main!(codec_benches, zone_benches);
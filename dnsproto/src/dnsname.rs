@@ -7,12 +7,18 @@ use nom::lib::std::collections::HashMap;
 use nom::lib::std::fmt::Formatter;
 use otterlib::errors::DNSProtoErr;
 use std::borrow::Cow;
+use std::cmp::Ordering;
+use std::collections::hash_map::DefaultHasher;
 use std::fmt::Display;
+use std::hash::{Hash, Hasher};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
 use std::ops::Add;
 use std::str::FromStr;
 
-use crate::utils::is_fqdn;
+use crate::utils::{is_fqdn, to_ascii_domain, to_unicode_label};
 
+// labels compare and hash case-insensitively, so names that differ only in case are
+// the same map key.
 #[derive(Debug, PartialEq, Eq, Clone, Hash)]
 pub struct DNSName {
     pub labels: Vec<Label>,
@@ -21,14 +27,22 @@ pub struct DNSName {
 impl DNSName {
     // new a DNSName from domain str, if domain is fqdn then default_original can be set to None,
     // else must set default_original , otherwise a parse error will return .
+    // non-ascii labels(U-labels) will be converted to punycode A-labels.
     pub fn new(domain: &str, default_original: Option<&str>) -> Result<DNSName, DNSProtoErr> {
-        let mut domain = Cow::from(domain);
+        let mut domain = to_ascii_domain(domain)?;
         if domain.is_empty() || domain.eq(".") {
             return Ok(DNSName::default());
         }
         if !is_fqdn(domain.as_ref()) {
             match default_original {
-                Some(val) => *domain.to_mut() += val,
+                Some(val) => {
+                    let origin = to_ascii_domain(val)?;
+                    let domain = domain.to_mut();
+                    if origin.as_ref() != "." {
+                        domain.push('.');
+                    }
+                    *domain += origin.as_ref();
+                }
                 _ => {
                     return Err(DNSProtoErr::ParseDNSFromStrError(format!(
                         "domain: {} has no default original",
@@ -86,6 +100,22 @@ impl DNSName {
     pub fn label_count(&self) -> usize {
         self.labels.len()
     }
+    /// parent return the name without its leftmost label, root has no parent.
+    pub fn parent(&self) -> Option<DNSName> {
+        if self.labels.is_empty() {
+            return None;
+        }
+        Some(DNSName {
+            labels: self.labels[1..].to_vec(),
+        })
+    }
+    /// iter_suffixes yield the name itself and then each shorter suffix down to root,
+    /// for example a.b.c. yield a.b.c. b.c. c. and .
+    pub fn iter_suffixes(&self) -> impl Iterator<Item = DNSName> + '_ {
+        (0..=self.labels.len()).map(move |index| DNSName {
+            labels: self.labels[index..].to_vec(),
+        })
+    }
     pub fn is_part_of(&self, dname: &DNSName) -> bool {
         if self.label_count() < dname.label_count() {
             return false;
@@ -126,47 +156,46 @@ impl DNSName {
         (true, us_counter - zone_counter)
     }
 
-    pub fn to_binary(
-        &self,
-        compression: Option<(&mut HashMap<Vec<Label>, usize>, usize)>,
-    ) -> Vec<u8> {
+    pub fn to_binary(&self, compression: Option<(&mut CompressionMap, usize)>) -> Vec<u8> {
         let mut binary_store: Vec<u8> = vec![];
-        let mut index = 0;
-        let mut with_pointer = false;
-        let mut current_offset = 0;
         match compression {
             Some((store, offset)) => {
-                // Todo replace with hash u64
-                // let mut cal_last = 0;
-                // let hash_val = self.labels.iter().rev().map(|label|{
-                //     cal_last = calculate_hash(label)  + cal_last;
-                //     cal_last
-                // }).collect::<Vec<u64>>().iter().rev().collect::<Vec<u64>>();
-                for label in self.labels.iter() {
-                    #[allow(clippy::map_clone)]
-                    let current_key = self.labels[index..]
-                        .iter()
-                        .map(|x| x.clone())
-                        .collect::<Vec<Label>>();
-                    index += 1;
-                    match store.get(&current_key) {
-                        Some(location) => {
-                            let pointer = (*location) | 0xc000;
-                            binary_store.push((pointer >> 8) as u8);
-                            binary_store.push((pointer & 0x00ff) as u8);
-                            with_pointer = true;
-                            break;
-                        }
-                        _ => {
-                            binary_store.push(label.len() as u8);
-                            binary_store.extend_from_slice(label.as_bytes());
-                            store.insert(current_key, offset + current_offset);
-                            current_offset = current_offset + label.len() + 1;
+                // walk the known suffixes from the root, `matched` is where the longest known
+                // suffix start and `pointer` is the longest one which can be pointed to
+                let (mut matched, mut parent, mut pointer) = (self.labels.len(), ROOT_SUFFIX, None);
+                for (index, label) in self.labels.iter().enumerate().rev() {
+                    match store.find(label, parent) {
+                        Some(suffix) => {
+                            matched = index;
+                            parent = suffix;
+                            if let Some(location) = store.offset(suffix) {
+                                pointer = Some((index, location));
+                            }
                         }
+                        None => break,
                     }
                 }
-                if !with_pointer {
-                    binary_store.push(0x00);
+                let end = pointer.map_or(self.labels.len(), |(index, _)| index);
+                for label in self.labels[..end].iter() {
+                    binary_store.push(label.len() as u8);
+                    binary_store.extend_from_slice(label.as_bytes());
+                }
+                // record the new suffixes, each one refers to the suffix after it
+                let mut current_offset: usize = self.labels[..matched]
+                    .iter()
+                    .map(|label| label.len() + 1)
+                    .sum();
+                for label in self.labels[..matched].iter().rev() {
+                    current_offset -= label.len() + 1;
+                    parent = store.insert(label, parent, offset + current_offset);
+                }
+                match pointer {
+                    Some((_, location)) => {
+                        let pointer = location | 0xc000;
+                        binary_store.push((pointer >> 8) as u8);
+                        binary_store.push((pointer & 0x00ff) as u8);
+                    }
+                    None => binary_store.push(0x00),
                 }
             }
             _ => {
@@ -183,16 +212,134 @@ impl DNSName {
     pub fn append(&mut self, domain: &DNSName) {
         self.labels.extend_from_slice(domain.labels.as_slice());
     }
+
+    /// from_ipv4_ptr return the in-addr.arpa name of the address,
+    /// for example 192.0.2.1 will be 1.2.0.192.in-addr.arpa.
+    pub fn from_ipv4_ptr(addr: Ipv4Addr) -> DNSName {
+        let mut labels: Vec<String> = addr.octets().iter().rev().map(|o| o.to_string()).collect();
+        labels.push("in-addr".to_owned());
+        labels.push("arpa".to_owned());
+        DNSName::from_label_strs(labels)
+    }
+
+    /// from_ipv6_ptr return the nibble reversed ip6.arpa name of the address,
+    /// for example 2001:db8::1 will be 1.0.0.0...8.b.d.0.1.0.0.2.ip6.arpa.
+    pub fn from_ipv6_ptr(addr: Ipv6Addr) -> DNSName {
+        let mut labels = Vec::with_capacity(34);
+        for octet in addr.octets().iter().rev() {
+            labels.push(format!("{:x}", octet & 0x0f));
+            labels.push(format!("{:x}", octet >> 4));
+        }
+        labels.push("ip6".to_owned());
+        labels.push("arpa".to_owned());
+        DNSName::from_label_strs(labels)
+    }
+
+    fn from_label_strs(labels: Vec<String>) -> DNSName {
+        DNSName {
+            labels: labels
+                .iter()
+                .map(|label| Label::from_str(label.as_str()).unwrap())
+                .collect(),
+        }
+    }
+
+    /// to_ip_addr return the address of a well-formed reverse name, a full in-addr.arpa name
+    /// has four decimal octets and a full ip6.arpa name has 32 hex nibbles.
+    /// return None for any other names.
+    pub fn to_ip_addr(&self) -> Option<IpAddr> {
+        let labels: Vec<String> = self
+            .labels
+            .iter()
+            .map(|label| label.to_string().to_lowercase())
+            .collect();
+        let count = labels.len();
+        if count < 2 || labels[count - 1] != "arpa" {
+            return None;
+        }
+        match labels[count - 2].as_str() {
+            "in-addr" if count == 6 => {
+                let mut octets = [0u8; 4];
+                for (index, label) in labels[..4].iter().rev().enumerate() {
+                    let valid = !label.is_empty()
+                        && label.len() <= 3
+                        && label.bytes().all(|c| c.is_ascii_digit())
+                        && (label == "0" || !label.starts_with('0'));
+                    if !valid {
+                        return None;
+                    }
+                    octets[index] = label.parse::<u8>().ok()?;
+                }
+                Some(IpAddr::V4(Ipv4Addr::from(octets)))
+            }
+            "ip6" if count == 34 => {
+                let mut octets = [0u8; 16];
+                for (index, label) in labels[..32].iter().rev().enumerate() {
+                    if label.len() != 1 {
+                        return None;
+                    }
+                    let nibble = u8::from_str_radix(label.as_str(), 16).ok()?;
+                    if index % 2 == 0 {
+                        octets[index / 2] = nibble << 4;
+                    } else {
+                        octets[index / 2] |= nibble;
+                    }
+                }
+                Some(IpAddr::V6(Ipv6Addr::from(octets)))
+            }
+            _ => None,
+        }
+    }
+
+    /// canonical_cmp compare two names in dnssec canonical order(rfc4034 section 6.1):
+    /// labels are compared from the rightmost one as lowercase byte strings.
+    pub fn canonical_cmp(&self, other: &DNSName) -> Ordering {
+        for (left, right) in self.labels.iter().rev().zip(other.labels.iter().rev()) {
+            let left = left.as_bytes().iter().map(|c| c.to_ascii_lowercase());
+            let right = right.as_bytes().iter().map(|c| c.to_ascii_lowercase());
+            match left.cmp(right) {
+                Ordering::Equal => continue,
+                result => return result,
+            }
+        }
+        self.label_count().cmp(&other.label_count())
+    }
+
+    /// to_canonical_binary return the uncompressed wire format with lowercase labels.
+    pub fn to_canonical_binary(&self) -> Vec<u8> {
+        let mut binary = self.to_binary(None);
+        binary.make_ascii_lowercase();
+        binary
+    }
+}
+
+impl Ord for DNSName {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.canonical_cmp(other)
+    }
+}
+
+impl PartialOrd for DNSName {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.canonical_cmp(other))
+    }
 }
 
 impl Display for DNSName {
+    /// the alternate flag `{:#}` render the punycode A-labels back to U-labels.
     fn fmt(&self, format: &mut Formatter<'_>) -> std::fmt::Result {
         let mut name = String::new();
         if self.labels.is_empty() {
             return write!(format, ".");
         }
         for label in &self.labels {
-            name.push_str(label.to_string().as_str());
+            let label = label.to_string();
+            // the idna decoding is only paid by the alternate form
+            let u_label = match format.alternate() {
+                true => to_unicode_label(label.as_str()),
+                false => None,
+            };
+            name.push_str(u_label.as_deref().unwrap_or(label.as_str()));
             name.push('.');
         }
         write!(format, "{}", name)
@@ -204,74 +351,221 @@ impl Default for DNSName {
         DNSName { labels: Vec::new() }
     }
 }
-// update to return how many byte processed
+// max compression pointers followed in a name, stop the pointer loops
+const MAX_POINTER_HOPS: usize = 64;
+// max length of a name in wire format
+pub(crate) const MAX_NAME_WIRE_LENGTH: usize = 255;
+// offsets from here can not be pointed by the 14 bits of a compression pointer
+const MAX_POINTER_OFFSET: usize = 0x4000;
+
+// the parent of the single label suffixes
+const ROOT_SUFFIX: usize = usize::MAX;
+
+/// CompressionMap keep the message offsets of the name suffixes already encoded for name
+/// compression(rfc1035 4.1.4). a suffix is keyed by the hash of its first label and the
+/// index of the suffix after it, so finding a suffix never clones labels. labels compare
+/// case-insensitively and a hash hit is verified with the kept label.
+#[derive(Debug, Default, Clone)]
+pub struct CompressionMap {
+    // (hash of the first label, index of the rest suffix) => index of the suffix
+    index: HashMap<(u64, usize), usize>,
+    // the first label and the offset of each suffix, suffixes beyond the pointer range
+    // are kept without offset so longer names can still refer to them
+    suffixes: Vec<(Label, Option<usize>)>,
+}
+
+impl CompressionMap {
+    pub fn new() -> CompressionMap {
+        CompressionMap::default()
+    }
+
+    fn key(label: &Label, parent: usize) -> (u64, usize) {
+        let mut hasher = DefaultHasher::new();
+        label.hash(&mut hasher);
+        (hasher.finish(), parent)
+    }
+
+    /// find return the index of the suffix made of the label and the parent suffix.
+    fn find(&self, label: &Label, parent: usize) -> Option<usize> {
+        let suffix = *self.index.get(&CompressionMap::key(label, parent))?;
+        match self.suffixes.get(suffix) {
+            Some((known, _)) if known == label => Some(suffix),
+            _ => None,
+        }
+    }
+
+    fn offset(&self, suffix: usize) -> Option<usize> {
+        self.suffixes.get(suffix).and_then(|(_, offset)| *offset)
+    }
+
+    /// insert record the suffix at the message offset and return its index.
+    fn insert(&mut self, label: &Label, parent: usize, offset: usize) -> usize {
+        let suffix = self.suffixes.len();
+        // a pointer only has 14 bits for the offset
+        let offset = if offset < MAX_POINTER_OFFSET {
+            Some(offset)
+        } else {
+            None
+        };
+        // a hash collision keep the first suffix, the new one is just not compressed
+        self.index
+            .entry(CompressionMap::key(label, parent))
+            .or_insert(suffix);
+        self.suffixes.push((label.clone(), offset));
+        suffix
+    }
+}
+
+/// parse_name parse a name from input, compression pointers are resolved against the
+/// original message. return the rest of input after the name.
 pub fn parse_name<'a>(input: &'a [u8], original: &'_ [u8]) -> IResult<&'a [u8], DNSName> {
-    let original_len = original.len();
-    let mut shift: usize = 0;
     let mut labels = vec![];
-    let input_len = input.len();
+    let mut wire_length = 1;
+    let (rest, _) = parse_labels(input, original, &mut labels, &mut wire_length, 0)?;
+    Ok((rest, DNSName { labels }))
+}
+
+fn parse_labels<'a>(
+    input: &'a [u8],
+    original: &'_ [u8],
+    labels: &mut Vec<Label>,
+    wire_length: &mut usize,
+    hops: usize,
+) -> IResult<&'a [u8], ()> {
+    let mut shift: usize = 0;
     loop {
-        if shift >= 256 {
-            break;
-        }
-        if shift + 1 > input_len {
-            return Err(Incomplete(Needed::Unknown));
-        }
-        let size_or_pointer = input[shift] as usize;
+        let size_or_pointer = match input.get(shift) {
+            Some(value) => *value as usize,
+            None => return Err(Incomplete(Needed::Unknown)),
+        };
         if size_or_pointer == 0 {
-            shift += 1;
-            break;
+            return Ok((&input[shift + 1..], ()));
         }
-        // only 00(normal name) / 11(pointer) and 01(edns?) is used right now
-        match size_or_pointer >> 6 & 0xff {
+        // only 00(normal label) and 11(pointer) is used
+        match size_or_pointer >> 6 {
             0 => {
-                if (size_or_pointer + shift + 1) >= 256 {
+                let end = shift + 1 + size_or_pointer;
+                *wire_length += size_or_pointer + 1;
+                if end > input.len() || *wire_length > MAX_NAME_WIRE_LENGTH {
                     return Err(Incomplete(Needed::Unknown));
                 }
-                if shift + 2 > input_len || shift + 2 + size_or_pointer > input_len {
-                    return Err(Incomplete(Needed::Unknown));
-                }
-                let label = &input[shift + 1..shift + 1 + size_or_pointer];
-                // match std::str::from_utf8(&label) {
-                //     Ok(label) => labels.push(Label(label)),
-                //     _ => return Err(Incomplete(Needed::Unknown)),
-                // }
-                labels.push(Label::from_vec(label.to_vec()));
-                shift = shift + size_or_pointer + 1;
+                labels.push(Label::from_vec(input[shift + 1..end].to_vec()));
+                shift = end;
             }
             3 => {
-                let pointer =
-                    ((((input[shift] as u16) << 8) | input[shift + 1] as u16) & 0x0fff) as usize;
-                let mut stop = pointer;
-                loop {
-                    if original[stop] == 0 {
-                        break;
-                    }
-                    if stop >= original_len {
-                        return Err(Incomplete(Needed::Unknown));
-                    }
-                    stop += 1;
+                if hops >= MAX_POINTER_HOPS || shift + 1 >= input.len() {
+                    return Err(Incomplete(Needed::Unknown));
                 }
-                let new_input = &original[pointer..=stop];
-                match parse_name(new_input, original) {
-                    Ok(ref mut dname) => labels.append(&mut dname.1.labels),
-                    Err(_) => return Err(Incomplete(Needed::Unknown)),
-                };
-                shift += 2;
-                return Ok((&input[shift..], DNSName { labels }));
+                let pointer = ((size_or_pointer & 0x3f) << 8) | input[shift + 1] as usize;
+                if pointer >= original.len() {
+                    return Err(Incomplete(Needed::Unknown));
+                }
+                parse_labels(
+                    &original[pointer..],
+                    original,
+                    labels,
+                    wire_length,
+                    hops + 1,
+                )
+                // the error borrows the original message, not the input
+                .map_err(|_| Incomplete(Needed::Unknown))?;
+                return Ok((&input[shift + 2..], ()));
             }
             _ => return Err(Incomplete(Needed::Unknown)),
         }
     }
-    Ok((&input[shift..], DNSName { labels }))
 }
 #[cfg(test)]
 mod dnsname {
-    use crate::dnsname::{parse_name, DNSName};
+    use crate::dnsname::{parse_name, CompressionMap, DNSName};
     use crate::label::Label;
+    use otterlib::errors::DNSProtoErr;
+    use std::cmp::Ordering;
+    use std::collections::hash_map::DefaultHasher;
     use std::collections::HashMap;
+    use std::hash::{Hash, Hasher};
+    use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
     use std::str::FromStr;
 
+    #[test]
+    fn test_dns_name_idn() {
+        let name = DNSName::new("www.中文.example.", None).unwrap();
+        assert_eq!(
+            name,
+            DNSName::new("www.xn--fiq228c.example.", None).unwrap()
+        );
+        assert_eq!(name.to_string(), "www.xn--fiq228c.example.");
+        assert_eq!(format!("{:#}", name), "www.中文.example.");
+
+        let name = DNSName::new("www", Some("中文.example.")).unwrap();
+        assert_eq!(name.to_string(), "www.xn--fiq228c.example.");
+
+        match DNSName::new("\u{301}abc.example.", None) {
+            Err(DNSProtoErr::ParseZoneDataErr(_)) => {}
+            _ => assert!(
+                false,
+                "invalid idn label should return parse zone data error"
+            ),
+        }
+    }
+
+    #[test]
+    fn test_reverse_name_ipv4() {
+        let addr = Ipv4Addr::new(192, 0, 2, 1);
+        let name = DNSName::from_ipv4_ptr(addr);
+        assert_eq!(name.to_string(), "1.2.0.192.in-addr.arpa.");
+        assert_eq!(name.to_ip_addr(), Some(IpAddr::V4(addr)));
+        assert_eq!(
+            DNSName::new("10.2.0.192.IN-ADDR.ARPA.", None)
+                .unwrap()
+                .to_ip_addr(),
+            Some(IpAddr::V4(Ipv4Addr::new(192, 0, 2, 10)))
+        );
+        for name in &[
+            // the /24 zone apex itself is not an address
+            "2.0.192.in-addr.arpa.",
+            "256.2.0.192.in-addr.arpa.",
+            "01.2.0.192.in-addr.arpa.",
+            "1a.2.0.192.in-addr.arpa.",
+            "1.1.2.0.192.in-addr.arpa.",
+            "1.2.0.192.example.com.",
+        ] {
+            assert_eq!(DNSName::new(name, None).unwrap().to_ip_addr(), None);
+        }
+    }
+
+    #[test]
+    fn test_reverse_name_ipv6() {
+        let addr = Ipv6Addr::from_str("2001:db8:0:1::abcd").unwrap();
+        let name = DNSName::from_ipv6_ptr(addr);
+        assert_eq!(
+            name.to_string(),
+            "d.c.b.a.0.0.0.0.0.0.0.0.0.0.0.0.1.0.0.0.0.0.0.0.8.b.d.0.1.0.0.2.ip6.arpa."
+        );
+        assert_eq!(name.to_ip_addr(), Some(IpAddr::V6(addr)));
+
+        let prefix = "1.0.0.0.0.0.0.0.8.b.d.0.1.0.0.2.ip6.arpa.";
+        // the /64 zone apex itself is not an address
+        assert_eq!(DNSName::new(prefix, None).unwrap().to_ip_addr(), None);
+        let malformed = vec![
+            format!("D.C.B.A.0.0.0.0.0.0.0.0.0.0.0.{}", prefix),
+            format!("g.c.b.a.0.0.0.0.0.0.0.0.0.0.0.0.{}", prefix),
+            format!("dc.b.a.0.0.0.0.0.0.0.0.0.0.0.0.0.{}", prefix),
+            format!("0.d.c.b.a.0.0.0.0.0.0.0.0.0.0.0.0.{}", prefix),
+        ];
+        for name in malformed {
+            assert_eq!(
+                DNSName::new(name.as_str(), None).unwrap().to_ip_addr(),
+                None
+            );
+        }
+        let upper = format!("D.C.B.A.0.0.0.0.0.0.0.0.0.0.0.0.{}", prefix.to_uppercase());
+        assert_eq!(
+            DNSName::new(upper.as_str(), None).unwrap().to_ip_addr(),
+            Some(IpAddr::V6(addr))
+        );
+    }
+
     #[test]
     fn test_dns_name_method() {
         let mut dname = DNSName::new("www.google.com.", None).unwrap();
@@ -396,6 +690,38 @@ mod dnsname {
         assert_eq!(dname.1.to_string(), String::from("www.google.com."));
     }
 
+    #[test]
+    fn test_dns_name_parse_malformed() {
+        let cases: Vec<(&[u8], &[u8])> = vec![
+            // truncated pointer
+            (&[0xc0], &[0x00]),
+            // pointer out of the original message
+            (&[0xc0, 0x20], &[0x00]),
+            (&[0x03, 0x77, 0x77, 0x77, 0xc0, 0x0c], &[]),
+            // pointer to itself and pointers to each other
+            (&[0xc0, 0x00], &[0xc0, 0x00]),
+            (&[0xc0, 0x00], &[0xc0, 0x02, 0xc0, 0x00]),
+            // pointer to a name without the root label
+            (&[0xc0, 0x00], &[0x03, 0x77]),
+            // reserved label type
+            (&[0x40, 0x00], &[]),
+            (&[0x80, 0x00], &[]),
+        ];
+        for (input, original) in cases {
+            assert!(parse_name(input, original).is_err(), "{:?}", input);
+        }
+        // name longer than 255 bytes
+        let mut raw = vec![];
+        for _ in 0..4 {
+            raw.push(63);
+            raw.extend_from_slice(&[0x61; 63]);
+        }
+        raw.push(0);
+        assert!(parse_name(&raw, &[]).is_err());
+        let raw = &raw[64..];
+        assert_eq!(parse_name(raw, &[]).unwrap().1.label_count(), 3);
+    }
+
     #[test]
     fn test_encode_dnsname() {
         // let mut compression: HashMap<String, usize> = HashMap::new();
@@ -424,8 +750,10 @@ mod dnsname {
                 Err(err) => assert!(false, format!("should return name success: {:?}", err)),
             }
         }
-        let mut compression: HashMap<Vec<Label>, usize> = HashMap::new();
-        compression.insert(vec![Label::from_str("com").unwrap()], 10);
+        let mut compression = CompressionMap::new();
+        DNSName::new("com.", None)
+            .unwrap()
+            .to_binary(Some((&mut compression, 10)));
         let cases = vec![
             (
                 "www.baidu.com.",
@@ -444,6 +772,40 @@ mod dnsname {
         }
     }
 
+    #[test]
+    fn test_to_binary_compression_case() {
+        let to_binary = |name: &str, compression: &mut CompressionMap, offset: usize| {
+            DNSName::new(name, None)
+                .unwrap()
+                .to_binary(Some((compression, offset)))
+        };
+        let mut compression = CompressionMap::new();
+        assert_eq!(
+            to_binary("www.example.com.", &mut compression, 12).len(),
+            17
+        );
+        // the known suffix matches case-insensitively, new labels keep their case
+        assert_eq!(
+            to_binary("Mail.EXAMPLE.Com.", &mut compression, 40),
+            vec![4, b'M', b'a', b'i', b'l', 0xc0, 16]
+        );
+        assert_eq!(
+            to_binary("MAIL.example.com.", &mut compression, 60),
+            vec![0xc0, 40]
+        );
+        // suffixes beyond the pointer range are not pointed to, but names below them
+        // still point to the closest suffix in range
+        assert_eq!(
+            to_binary("a.b.example.com.", &mut compression, 0x4000),
+            vec![1, b'a', 1, b'b', 0xc0, 16]
+        );
+        assert_eq!(
+            to_binary("c.A.B.example.com.", &mut compression, 0x4100),
+            vec![1, b'c', 1, b'A', 1, b'B', 0xc0, 16]
+        );
+        assert_eq!(to_binary(".", &mut compression, 0x4200), vec![0]);
+    }
+
     #[test]
     fn test_make_relative() {
         let mut dnsname = DNSName::new("www.baidu.com.", None).unwrap();
@@ -479,4 +841,67 @@ mod dnsname {
             ]
         );
     }
+
+    #[test]
+    fn test_canonical_order() {
+        // https://tools.ietf.org/html/rfc4034#section-6.1
+        let ordered = vec![
+            "example.",
+            "a.example.",
+            "yljkjljk.a.example.",
+            "Z.a.example.",
+            "zABC.a.EXAMPLE.",
+            "z.example.",
+            "*.z.example.",
+        ];
+        let mut names: Vec<DNSName> = ordered
+            .iter()
+            .rev()
+            .map(|name| DNSName::new(name, None).unwrap())
+            .collect();
+        names.sort();
+        let names: Vec<String> = names.iter().map(|name| name.to_string()).collect();
+        assert_eq!(names, ordered);
+        assert_eq!(
+            DNSName::new("Www.Example.COM.", None)
+                .unwrap()
+                .to_canonical_binary(),
+            b"\x03www\x07example\x03com\x00".to_vec()
+        );
+    }
+
+    #[test]
+    fn test_dns_name_hash_and_eq() {
+        let mut hasher = DefaultHasher::new();
+        let lower = DNSName::new("www.example.com.", None).unwrap();
+        let upper = DNSName::new("WWW.Example.COM.", None).unwrap();
+        assert_eq!(lower, upper);
+        assert_eq!(lower.cmp(&upper), Ordering::Equal);
+        lower.hash(&mut hasher);
+        let lower_hash = hasher.finish();
+        let mut hasher = DefaultHasher::new();
+        upper.hash(&mut hasher);
+        assert_eq!(lower_hash, hasher.finish());
+
+        let mut zones = HashMap::new();
+        zones.insert(lower, 1);
+        assert_eq!(zones.get(&upper), Some(&1));
+        assert_ne!(
+            DNSName::new("ab.c.", None).unwrap(),
+            DNSName::new("a.bc.", None).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_dns_name_suffixes() {
+        let name = DNSName::new("a.b.c.", None).unwrap();
+        let suffixes: Vec<String> = name.iter_suffixes().map(|n| n.to_string()).collect();
+        assert_eq!(suffixes, vec!["a.b.c.", "b.c.", "c.", "."]);
+        assert_eq!(name.parent().unwrap().to_string(), "b.c.");
+        assert_eq!(DNSName::default().parent(), None);
+        assert_eq!(
+            DNSName::default().iter_suffixes().collect::<Vec<DNSName>>(),
+            vec![DNSName::default()]
+        );
+    }
 }
@@ -1,10 +1,8 @@
 // http://www.networksorcery.com/enp/protocol/dns.htm
-use crate::dnsname::DNSName;
-use crate::label::Label;
+use crate::dnsname::{CompressionMap, DNSName};
 use crate::meta::DNSType;
-use crate::qtype::DNSWireFrame;
+use crate::qtype::{DNSWireFrame, DnsTypeOpt, EDNSOptionCode};
 use byteorder::{BigEndian, WriteBytesExt};
-use nom::lib::std::collections::HashMap;
 use otterlib::errors::DNSProtoErr;
 use std::io::{Cursor, Write};
 
@@ -30,7 +28,7 @@ impl EDNS {
         EDNS {
             name: DNSName::new(".", None).unwrap(),
             qtype: DNSType::OPT,
-            payload_size: 1243,
+            payload_size: 1232,
             extension: 0,
             version: 0,
             do_bit: false,
@@ -44,18 +42,30 @@ impl EDNS {
     pub fn set_payload_size(&mut self, size: u16) {
         self.payload_size = size
     }
+    pub fn payload_size(&self) -> u16 {
+        self.payload_size
+    }
+    pub fn do_bit(&self) -> bool {
+        self.do_bit
+    }
 
     pub fn encode<'a>(
         &self,
         cursor: &'a mut Cursor<Vec<u8>>,
-        _compression: Option<&mut HashMap<Vec<Label>, usize>>,
+        _compression: Option<&mut CompressionMap>,
     ) -> Result<&'a mut Cursor<Vec<u8>>, DNSProtoErr> {
         cursor.write_u8(0)?; // root
-        cursor.write_u16::<BigEndian>(self.qtype as u16)?;
+        cursor.write_u16::<BigEndian>(self.qtype.into())?;
         cursor.write_u16::<BigEndian>(self.payload_size)?;
         cursor.write_u8(self.extension)?;
         cursor.write_u8(self.version)?;
         cursor.write_u16::<BigEndian>((self.do_bit as u16) << 15)?;
+        // the raw rdata holds all options while data is only the first one
+        if let Some(rdata) = self.raw_data.as_ref() {
+            cursor.write_u16::<BigEndian>(rdata.len() as u16)?;
+            cursor.write_all(rdata.as_slice())?;
+            return Ok(cursor);
+        }
         if self.data.is_none() {
             cursor.write_u16::<BigEndian>(0)?;
             Ok(cursor)
@@ -75,6 +85,60 @@ impl EDNS {
     pub fn set_rdata(&mut self, rdata: &[u8]) {
         self.raw_data = Some(rdata.to_vec());
     }
+
+    /// cookie return the data of the COOKIE option(rfc7873) in the OPT rdata, which is the
+    /// client cookie and an optional server cookie.
+    pub fn cookie(&self) -> Option<&[u8]> {
+        self.option(EDNSOptionCode::Cookie)
+    }
+
+    /// extended_error return the info code and the extra text of the EXTENDED-ERROR
+    /// option(rfc8914) in the OPT rdata.
+    pub fn extended_error(&self) -> Option<(u16, String)> {
+        let data = self.option(EDNSOptionCode::ExtendedError)?;
+        let info_code = ((*data.get(0)? as u16) << 8) + *data.get(1)? as u16;
+        Some((info_code, String::from_utf8_lossy(&data[2..]).into_owned()))
+    }
+
+    /// add_extended_error append an EXTENDED-ERROR option with the info code and the extra
+    /// text to the options.
+    pub fn add_extended_error(&mut self, info_code: u16, text: &str) {
+        let mut rdata = self.raw_data.take().unwrap_or_default();
+        rdata.extend_from_slice(&(EDNSOptionCode::ExtendedError as u16).to_be_bytes());
+        rdata.extend_from_slice(&(2 + text.len() as u16).to_be_bytes());
+        rdata.extend_from_slice(&info_code.to_be_bytes());
+        rdata.extend_from_slice(text.as_bytes());
+        self.raw_data = Some(rdata);
+    }
+
+    // option return the data of the first option with the code in the OPT rdata.
+    fn option(&self, option_code: EDNSOptionCode) -> Option<&[u8]> {
+        let mut rdata = self.raw_data.as_deref()?;
+        while rdata.len() >= 4 {
+            let code = ((rdata[0] as u16) << 8) + rdata[1] as u16;
+            let length = ((rdata[2] as usize) << 8) + rdata[3] as usize;
+            let data = rdata.get(4..4 + length)?;
+            if code == option_code as u16 {
+                return Some(data);
+            }
+            rdata = &rdata[4 + length..];
+        }
+        None
+    }
+
+    /// set_cookie replace the options with a COOKIE option.
+    pub fn set_cookie(&mut self, cookie: &[u8]) -> Result<(), DNSProtoErr> {
+        let opt = DnsTypeOpt::new_cookie(cookie)?;
+        self.raw_data = Some(opt.encode(None)?);
+        self.data = Some(Box::new(opt));
+        Ok(())
+    }
+
+    /// clear_options remove all options from the OPT rdata.
+    pub fn clear_options(&mut self) {
+        self.raw_data = None;
+        self.data = None;
+    }
 }
 
 impl PartialEq for EDNS {
@@ -15,6 +15,7 @@ pub struct Label(Vec<u8>);
 
 impl Hash for Label {
     fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.len().hash(state);
         for i in self.0.iter() {
             if *i >= 65 && *i <= 90 {
                 (*i + 32u8).hash(state);
@@ -112,20 +113,10 @@ impl Label {
         self.0.is_empty()
     }
 
-    fn compare_label(mut left: u8, mut right: u8) -> Ordering {
-        if (0x61..=0x7A).contains(&left) {
-            left -= 0x20;
-        }
-        if (0x61..=0x7A).contains(&right) {
-            right -= 0x20;
-        }
-        if left < right {
-            return Ordering::Less;
-        }
-        if left > right {
-            return Ordering::Greater;
-        }
-        Ordering::Equal
+    // compare_label compare the bytes as lowercase, labels are sorted in the dnssec
+    // canonical order(rfc4034 section 6.1) even with the chars between 'Z' and 'a'.
+    fn compare_label(left: u8, right: u8) -> Ordering {
+        left.to_ascii_lowercase().cmp(&right.to_ascii_lowercase())
     }
 }
 
@@ -201,6 +192,11 @@ mod label {
         assert_eq!(label.to_string(), "hello");
         let label2 = Label::from_str("heLLo").unwrap();
         assert_eq!(label == label2, true);
+        // '_' is between 'Z' and 'a', lowercase it sorts before the letters
+        let underscore = Label::from_str("_tcp").unwrap();
+        assert!(underscore < Label::from_str("Alpha").unwrap());
+        assert!(underscore > Label::from_str("9").unwrap());
+        assert_eq!(label.cmp(&label2), Ordering::Equal);
     }
     #[test]
     fn test_abnormal_label_fn() {
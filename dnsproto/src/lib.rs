@@ -1,7 +1,3 @@
-// Strum contains all the trait definitions
-extern crate strum;
-#[macro_use]
-extern crate strum_macros;
 extern crate itertools;
 extern crate regex;
 #[macro_use]
@@ -21,6 +17,7 @@ pub mod meta;
 pub mod qtype;
 pub mod record;
 pub mod utils;
+pub mod xfr;
 pub mod zone;
 
 pub fn version() -> &'static str {
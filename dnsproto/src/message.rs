@@ -1,14 +1,12 @@
 // http://www.networksorcery.com/enp/protocol/dns.htm
-use crate::dnsname::{parse_name, DNSName};
+use crate::dnsname::{parse_name, CompressionMap, DNSName, MAX_NAME_WIRE_LENGTH};
 use crate::edns::EDNS;
 use crate::label::Label;
-use crate::meta::{DNSClass, DNSType, RRSet};
+use crate::meta::{DNSClass, DNSType, RRSet, RecordOrder};
 use crate::meta::{Header, OpCode, Question, RCode, ResourceRecord};
-use crate::qtype::{decode_message_data, DnsTypeTXT};
+use crate::qtype::{decode_message_data, DNSWireFrame, DnsTypeSOA, DnsTypeTXT};
 use nom::number::complete::{be_u16, be_u32};
 use otterlib::errors::DNSProtoErr;
-use std::collections::HashMap;
-use std::convert::TryFrom;
 use std::io::Cursor;
 // use std::str::FromStr;
 
@@ -18,7 +16,10 @@ pub struct Message {
     questions: Vec<Question>,
     answers: Vec<Record>,
     authorities: Vec<Record>,
+    // records of the additional section without the OPT record
     additional: Vec<Record>,
+    // the OPT record, always encoded at the end of the additional section
+    edns: Option<EDNS>,
 }
 
 impl Message {
@@ -30,6 +31,36 @@ impl Message {
             answers: vec![],
             authorities: vec![],
             additional: vec![],
+            edns: None,
+        }
+    }
+
+    /// from_sections build the parsed message, the first OPT record of the additional
+    /// section is kept as the edns of the message and the others are dropped.
+    fn from_sections(
+        header: Header,
+        questions: Vec<Question>,
+        answers: Vec<Record>,
+        authorities: Vec<Record>,
+        records: Vec<Record>,
+    ) -> Message {
+        let mut additional = Vec::with_capacity(records.len());
+        let mut edns = None;
+        for record in records {
+            match record {
+                Record::EDNSRecord(opt) => {
+                    edns.get_or_insert(opt);
+                }
+                record => additional.push(record),
+            }
+        }
+        Message {
+            header,
+            questions,
+            answers,
+            authorities,
+            additional,
+            edns,
         }
     }
 
@@ -52,7 +83,99 @@ impl Message {
         }
         Ok((&self.questions[0].q_name, &self.questions[0].q_type))
     }
+    /// answers return the resource records in the answer section
+    pub fn answers(&self) -> Vec<&ResourceRecord> {
+        self.answers
+            .iter()
+            .filter_map(|record| match record {
+                Record::AnswerRecord(answer) => Some(answer),
+                _ => None,
+            })
+            .collect()
+    }
+    /// answers_of_type return the (owner, ttl, rdata) of the answers with the rdata of
+    /// the concrete type, the other answers are skipped.
+    ///
+    /// ```
+    /// use dnsproto::message::Message;
+    /// use dnsproto::meta::{DNSClass, DNSType, Header, ResourceRecord};
+    /// use dnsproto::qtype::{DNSWireFrame, DnsTypeA, DnsTypeCNAME};
+    ///
+    /// let mut message = Message::new_with_header(Header::new());
+    /// let records: Vec<(&str, DNSType, Box<dyn DNSWireFrame>)> = vec![
+    ///     (
+    ///         "www.example.com.",
+    ///         DNSType::CNAME,
+    ///         Box::new(DnsTypeCNAME::new("example.com.").unwrap()),
+    ///     ),
+    ///     (
+    ///         "example.com.",
+    ///         DNSType::A,
+    ///         Box::new(DnsTypeA::new("192.0.2.1").unwrap()),
+    ///     ),
+    /// ];
+    /// for (name, qtype, data) in records {
+    ///     let rr = ResourceRecord::new(name, qtype, DNSClass::IN, 300, Some(data)).unwrap();
+    ///     message.append_answer(rr);
+    /// }
+    /// let answers: Vec<_> = message.answers_of_type::<DnsTypeA>().collect();
+    /// assert_eq!(answers.len(), 1);
+    /// let (owner, ttl, a) = answers[0];
+    /// assert_eq!(owner.to_string(), "example.com.");
+    /// assert_eq!(ttl, 300);
+    /// assert_eq!(a.address().to_string(), "192.0.2.1");
+    /// ```
+    pub fn answers_of_type<T: DNSWireFrame + 'static>(
+        &self,
+    ) -> impl Iterator<Item = (&DNSName, u32, &T)> {
+        self.answers.iter().filter_map(|record| match record {
+            Record::AnswerRecord(rr) => rr
+                .rdata_as::<T>()
+                .map(|data| (rr.get_dname(), rr.get_ttl(), data)),
+            _ => None,
+        })
+    }
+    /// authorities return the resource records in the authority section
+    pub fn authorities(&self) -> Vec<&ResourceRecord> {
+        self.authorities
+            .iter()
+            .filter_map(|record| match record {
+                Record::AnswerRecord(authority) => Some(authority),
+                _ => None,
+            })
+            .collect()
+    }
+    /// additionals return the resource records in the additional section without the OPT
+    pub fn additionals(&self) -> Vec<&ResourceRecord> {
+        self.additional
+            .iter()
+            .filter_map(|record| match record {
+                Record::AnswerRecord(additional) => Some(additional),
+                _ => None,
+            })
+            .collect()
+    }
+    /// edns return the OPT record in the additional section
+    pub fn edns(&self) -> Option<&EDNS> {
+        self.edns.as_ref()
+    }
+    /// parse_query_header_and_question parse the query without decoding the records, the
+    /// question name borrows the input and only the OPT record is fully decoded.
+    pub fn parse_query_header_and_question(message: &[u8]) -> Result<QueryView, DNSProtoErr> {
+        QueryView::parse(message)
+    }
+    /// from_query_view build a query message with the question and the OPT record of the
+    /// view, the header keeps the counters of the raw query.
+    pub fn from_query_view(view: &QueryView) -> Message {
+        let mut message = Message::new_with_header(view.header);
+        if let Some(question) = view.question() {
+            message.questions.push(question.to_question());
+        }
+        message.edns = view.edns().cloned();
+        message
+    }
     pub fn parse_dns_message(message: &[u8]) -> Result<Message, DNSProtoErr> {
+        check_section_sizes(message)?;
         match parse_message(message, message) {
             Ok(val) => Ok(val.1),
             Err(_) => Err(DNSProtoErr::PacketParseError),
@@ -65,22 +188,17 @@ impl Message {
             answers: vec![],
             authorities: vec![],
             additional: vec![],
+            edns: None,
         }
     }
+    /// new_tc_message_from_build_message clear the answer, authority and additional
+    /// sections and set the tc flag, the question and the OPT record are kept.
     pub fn new_tc_message_from_build_message(message: &mut Message) -> &mut Message {
         message.header.tc = true;
         message.answers.clear();
         message.authorities.clear();
-        message.header.answer_count = 0;
-        message.header.answer_count = 0;
-        let mut additional = vec![];
-        for item in message.additional.iter() {
-            if let Record::EDNSRecord(edns) = item {
-                additional.push(Record::EDNSRecord(edns.clone()));
-            }
-        }
-        message.header.additional_count = additional.len() as u16;
-        message.additional = additional;
+        message.additional.clear();
+        message.update_counts();
         message
     }
 
@@ -91,9 +209,100 @@ impl Message {
         header.aa = false;
         header.ra = false;
         header.r_code = rcode;
+        // sections are empty, counters will be updated when appending records
+        header.question_count = 0;
+        header.answer_count = 0;
+        header.ns_count = 0;
+        header.additional_count = 0;
         Message::new_with_header(header)
     }
 
+    /// new_format_error_message return a FormatError response for a raw query which can not
+    /// be parsed, only the header of the query is used. return None when even the header
+    /// is missing or the raw message is a response.
+    pub fn new_format_error_message(raw: &[u8]) -> Option<Message> {
+        let header = match parse_header_frame(raw) {
+            Ok((_, header)) if !header.qr => header,
+            _ => return None,
+        };
+        Some(Message::new_message_with_rcode(
+            &Message::new_with_header(header),
+            RCode::FormatError,
+        ))
+    }
+
+    /// new_not_implemented_message return a NotImplemented response with the opcode and
+    /// question of the query message.
+    pub fn new_not_implemented_message(q_message: &Message) -> Message {
+        let mut message = Message::new_message_with_rcode(q_message, RCode::NotImplemented);
+        if let Some(question) = q_message.questions.first() {
+            message.set_question(question.clone());
+        }
+        message
+    }
+
+    /// new_server_failure_message return a ServerFailure response with the opcode and
+    /// question of the query message.
+    pub fn new_server_failure_message(q_message: &Message) -> Message {
+        let mut message = Message::new_message_with_rcode(q_message, RCode::ServerFailure);
+        if let Some(question) = q_message.questions.first() {
+            message.set_question(question.clone());
+        }
+        message
+    }
+
+    /// new_refused_message return a Refused response with the question of the query.
+    pub fn new_refused_message(q_message: &Message) -> Message {
+        let mut message = Message::new_message_with_rcode(q_message, RCode::Refused);
+        if let Some(question) = q_message.questions.first() {
+            message.set_question(question.clone());
+        }
+        message
+    }
+
+    /// new_transfer_message return an authoritative response with the question of the
+    /// query, the zone transfer records are appended to its answer section.
+    pub fn new_transfer_message(q_message: &Message) -> Message {
+        let mut message = Message::new_message_with_rcode(q_message, RCode::NoError);
+        message.header.aa = true;
+        if let Some(question) = q_message.questions.first() {
+            message.set_question(question.clone());
+        }
+        message
+    }
+
+    /// new_axfr_query return the query requesting the full transfer of the zone, the id
+    /// is random.
+    pub fn new_axfr_query(zone: &DNSName) -> Message {
+        Message::new_xfr_query(zone, DNSType::AXFR)
+    }
+
+    /// new_ixfr_query return the query requesting the changes of the zone since the
+    /// current soa, the soa is sent in the authority section(rfc1995 section 3).
+    pub fn new_ixfr_query(zone: &DNSName, current_soa: &DnsTypeSOA) -> Message {
+        let mut message = Message::new_xfr_query(zone, DNSType::IXFR);
+        message.append_authority(ResourceRecord {
+            name: zone.clone(),
+            qtype: DNSType::SOA,
+            qclass: DNSClass::IN,
+            ttl: 0,
+            data: Some(Box::new(current_soa.clone())),
+            provenance: Default::default(),
+        });
+        message
+    }
+
+    fn new_xfr_query(zone: &DNSName, qtype: DNSType) -> Message {
+        let mut message = Message::new_with_header(Header::new());
+        message.header.set_random_id();
+        message.set_question(Question {
+            q_name: zone.clone(),
+            q_type: qtype,
+            q_class: DNSClass::IN,
+        });
+        message
+    }
+
     /// new_message_from_query parse message and return a message
     /// return a message and bool(when true means something wrong and need terminate)
     pub fn new_message_from_query(
@@ -103,27 +312,39 @@ impl Message {
     ) -> (Message, u16, bool) {
         let mut terminator = false;
         let mut message = Message::new_message_with_rcode(q_message, RCode::NoError);
-        let mut max_size = max_edns_size;
-        for additional in q_message.additional.iter() {
-            match additional {
-                Record::EDNSRecord(edns) => {
-                    if edns.version != 0 {
-                        message.header.r_code = RCode::BadVersion;
-                        terminator = true;
-                        break;
-                    } else {
-                        let mut edns = edns.clone();
-                        if max_edns_size < edns.payload_size {
-                            edns.payload_size = max_edns_size;
-                            max_size = max_edns_size;
-                        } else {
-                            max_size = edns.payload_size
+        // udp responses to queries without edns are limited to 512 bytes(rfc1035 4.2.1)
+        let mut max_size = 512;
+        if let Some(edns) = q_message.edns() {
+            if edns.version != 0 {
+                // rfc6891: response BADVERS with an OPT of the version we support
+                let mut opt = EDNS::new();
+                opt.set_payload_size(max_edns_size);
+                message.header.r_code = RCode::BadVersion;
+                message.append_edns(opt);
+                terminator = true;
+            } else {
+                let mut edns = edns.clone();
+                // options of the query are not echoed back
+                edns.clear_options();
+                if q_message.questions.is_empty() {
+                    if let Some(cookie) = q_message.edns().and_then(|edns| edns.cookie()) {
+                        if edns.set_cookie(cookie).is_err() {
+                            message.header.r_code = RCode::FormatError;
                         }
-                        message.additional.push(Record::EDNSRecord(edns));
                     }
                 }
-                _ => {}
+                // the response never exceed the server limit even if the client
+                // advertise more, sizes below 512 are treated as 512(rfc6891 6.2.5)
+                max_size = edns.payload_size.min(max_edns_size).max(512);
+                edns.payload_size = max_size;
+                message.append_edns(edns);
+            }
+        }
+        if terminator {
+            if let Some(question) = q_message.questions.first() {
+                message.set_question(question.clone());
             }
+            return (message, max_size, terminator);
         }
 
         if q_message.header.r_code != RCode::NoError {
@@ -131,14 +352,22 @@ impl Message {
             return (message, max_size, true);
         }
 
-        if q_message.questions.len() != 1 {
-            message.header.r_code = RCode::ServerFailure;
+        // rfc7873 section 5.4: a query without question but with a COOKIE option asks
+        // for a server cookie, the response only has the cookie.
+        if q_message.questions.is_empty() {
+            let has_cookie = q_message.edns().and_then(|edns| edns.cookie()).is_some();
+            if !has_cookie {
+                message.header.r_code = RCode::FormatError;
+            }
+            return (message, max_size, true);
+        }
+        if q_message.questions.len() > 1 {
+            message.header.r_code = RCode::FormatError;
             return (message, max_size, true);
         }
 
         let question = &q_message.questions[0];
-        message.questions = vec![question.clone()];
-        message.header.question_count = 1;
+        message.set_question(question.clone());
         if question.q_class != DNSClass::IN {
             if question.q_class == DNSClass::CH && question.q_type == DNSType::TXT {
                 let record = Record::AnswerRecord(
@@ -166,9 +395,6 @@ impl Message {
             return (message, max_size, true);
         }
 
-        let question = q_message.questions[0].clone();
-        message.questions.push(question);
-
         (message, max_size, terminator)
     }
     pub fn set_nxdomain(&mut self) {
@@ -178,22 +404,41 @@ impl Message {
         self.header.r_code = RCode::ServerFailure;
     }
 
+    /// update_additional replace the records of the additional section, the OPT record is
+    /// kept.
     pub fn update_additional(&mut self, rrset: &RRSet) {
-        let additional = rrset.to_records();
-        self.header.additional_count = additional.len() as u16;
-        self.additional = additional;
+        self.additional = rrset.to_records(RecordOrder::Stable);
+        self.update_counts();
     }
     pub fn update_answer(&mut self, rrset: Vec<Record>) {
-        self.header.answer_count = rrset.len() as u16;
         self.answers = rrset;
+        self.update_counts();
     }
     pub fn update_authority(&mut self, rrset: &RRSet) {
-        let ns_list = rrset.to_records();
-        self.header.ns_count = ns_list.len() as u16;
-        self.authorities = ns_list;
+        self.authorities = rrset.to_records(RecordOrder::Stable);
+        self.update_counts();
+    }
+
+    /// update_counts set the section counters of the header from the sections, the OPT
+    /// record is counted in the additional section.
+    fn update_counts(&mut self) {
+        self.header.question_count = self.questions.len() as u16;
+        self.header.answer_count = self.answers.len() as u16;
+        self.header.ns_count = self.authorities.len() as u16;
+        self.header.additional_count =
+            self.additional.len() as u16 + u16::from(self.edns.is_some());
     }
 
     pub fn encode(&mut self, from_udp: bool) -> Result<Vec<u8>, DNSProtoErr> {
+        self.encode_message(from_udp, true)
+    }
+
+    /// encode_uncompressed encode the message without name compression
+    pub fn encode_uncompressed(&mut self, from_udp: bool) -> Result<Vec<u8>, DNSProtoErr> {
+        self.encode_message(from_udp, false)
+    }
+
+    fn encode_message(&mut self, from_udp: bool, compress: bool) -> Result<Vec<u8>, DNSProtoErr> {
         let buffer: Vec<u8> = {
             if self.header.qr {
                 Vec::with_capacity(256)
@@ -203,21 +448,35 @@ impl Message {
         };
 
         let cursor = &mut Cursor::new(buffer);
-        let compression = &mut HashMap::new();
+        let mut compression = if compress {
+            Some(CompressionMap::new())
+        } else {
+            None
+        };
+        // the upper 8 bits of extended rcode is carried by the OPT record
+        let rcode: u8 = self.header.r_code.into();
+        if let Some(edns) = self.edns.as_mut() {
+            edns.extension = rcode >> 4;
+        }
+        self.update_counts();
         let mut cursor = self.header.encode(cursor)?;
         for question in self.questions.as_slice() {
-            cursor = question.encode(cursor, Some(compression))?;
-            break;
+            cursor = question.encode(cursor, compression.as_mut())?;
         }
         for answer in self.answers.as_mut_slice() {
-            cursor = answer.encode(cursor, Some(compression))?;
+            cursor = answer.encode(cursor, compression.as_mut())?;
         }
         for ns_record in self.authorities.as_mut_slice() {
-            cursor = ns_record.encode(cursor, Some(compression))?;
+            cursor = ns_record.encode(cursor, compression.as_mut())?;
         }
-        // Opt is ends type not answer type
         for additional in self.additional.as_mut_slice() {
-            cursor = additional.encode(cursor, Some(compression))?
+            cursor = additional.encode(cursor, compression.as_mut())?
+        }
+        // the OPT record is the last one of the message
+        if let Some(edns) = self.edns.as_ref() {
+            cursor = edns
+                .encode(cursor, compression.as_mut())
+                .map_err(|_| DNSProtoErr::PacketSerializeError)?;
         }
         let result = cursor.get_ref().clone();
         // for tcp connection
@@ -244,31 +503,55 @@ impl Message {
 
     pub fn append_answer(&mut self, answer: ResourceRecord) {
         self.answers.push(Record::AnswerRecord(answer));
-        self.header.answer_count = self.answers.len() as u16;
+        self.update_counts();
     }
     pub fn append_additional(&mut self, additional: ResourceRecord) {
         self.additional.push(Record::AnswerRecord(additional));
-        self.header.additional_count = self.additional.len() as u16;
+        self.update_counts();
+    }
+    /// cap_ttl lower the ttl of all records great than max_ttl, the OPT record is skipped.
+    pub fn cap_ttl(&mut self, max_ttl: u32) {
+        let sections = self
+            .answers
+            .iter_mut()
+            .chain(self.authorities.iter_mut())
+            .chain(self.additional.iter_mut());
+        for record in sections {
+            if let Record::AnswerRecord(rr) = record {
+                if rr.get_ttl() > max_ttl {
+                    rr.set_ttl(max_ttl);
+                }
+            }
+        }
+    }
+    /// add_extended_error add the EXTENDED-ERROR option to the OPT record, messages without
+    /// the OPT record(the client does not support edns) are not changed.
+    pub fn add_extended_error(&mut self, info_code: u16, text: &str) {
+        if let Some(edns) = self.edns.as_mut() {
+            edns.add_extended_error(info_code, text);
+        }
     }
+    /// append_edns set the OPT record of the message, a message has one OPT record at most
+    /// so the previous one is replaced.
     pub fn append_edns(&mut self, edns: EDNS) {
-        self.additional.push(Record::EDNSRecord(edns));
-        self.header.additional_count = self.additional.len() as u16;
+        self.edns = Some(edns);
+        self.update_counts();
     }
     pub fn append_authority(&mut self, answer: ResourceRecord) {
         self.authorities.push(Record::AnswerRecord(answer));
-        self.header.ns_count = self.authorities.len() as u16;
+        self.update_counts();
     }
 }
 
-named!(parse_question<&[u8], Question>,
+named_args!(parse_question<'a>(original: &[u8])<&'a [u8], Question>,
     do_parse!(
-        name: call!(parse_name, &[]) >>
+        name: call!(parse_name, original) >>
         qtype: be_u16 >>
         qclass: be_u16 >>
         (Question {
             q_name: name,
-            q_type: DNSType::try_from(qtype).unwrap(),
-            q_class: DNSClass::try_from(qclass).unwrap(),
+            q_type: DNSType::from(qtype),
+            q_class: DNSClass::from(qclass),
         })
     )
 );
@@ -283,7 +566,7 @@ impl Record {
     fn encode<'a>(
         &self,
         cursor: &'a mut Cursor<Vec<u8>>,
-        compression: Option<&mut HashMap<Vec<Label>, usize>>,
+        compression: Option<&mut CompressionMap>,
     ) -> Result<&'a mut Cursor<Vec<u8>>, DNSProtoErr> {
         match self {
             Record::AnswerRecord(answer) => {
@@ -301,6 +584,120 @@ impl Record {
     }
 }
 
+/// QueryView is a query parsed for answering, only the header, the first question and
+/// the OPT record are decoded and the other records are skipped.
+#[derive(Debug)]
+pub struct QueryView<'a> {
+    pub header: Header,
+    question: Option<QuestionView<'a>>,
+    edns: Option<EDNS>,
+}
+
+/// QuestionView is a question with the name borrowed from the raw message.
+#[derive(Debug, Clone, Copy)]
+pub struct QuestionView<'a> {
+    name: &'a [u8],
+    q_type: DNSType,
+    q_class: DNSClass,
+}
+
+impl<'a> QuestionView<'a> {
+    /// labels return the labels of the name in wire format without the length bytes.
+    pub fn labels(&self) -> impl Iterator<Item = &'a [u8]> {
+        let name = self.name;
+        let mut offset = 0;
+        std::iter::from_fn(move || {
+            let length = *name.get(offset)? as usize;
+            let label = name.get(offset + 1..offset + 1 + length)?;
+            offset += length + 1;
+            match length {
+                0 => None,
+                _ => Some(label),
+            }
+        })
+    }
+    pub fn q_type(&self) -> DNSType {
+        self.q_type
+    }
+    pub fn q_class(&self) -> DNSClass {
+        self.q_class
+    }
+    pub fn to_dnsname(&self) -> DNSName {
+        DNSName {
+            labels: self
+                .labels()
+                .map(|label| Label::from_vec(label.to_vec()))
+                .collect(),
+        }
+    }
+    pub fn to_question(&self) -> Question {
+        Question {
+            q_name: self.to_dnsname(),
+            q_type: self.q_type,
+            q_class: self.q_class,
+        }
+    }
+}
+
+impl<'a> QueryView<'a> {
+    pub fn question(&self) -> Option<&QuestionView<'a>> {
+        self.question.as_ref()
+    }
+    pub fn edns(&self) -> Option<&EDNS> {
+        self.edns.as_ref()
+    }
+
+    fn parse(message: &'a [u8]) -> Result<QueryView<'a>, DNSProtoErr> {
+        check_section_sizes(message)?;
+        let header = match parse_header_frame(message) {
+            Ok((_, header)) => header,
+            Err(_) => return Err(DNSProtoErr::PacketParseError),
+        };
+        let be_u16_at = |offset: usize| match message.get(offset..offset + 2) {
+            Some(value) => Ok(((value[0] as u16) << 8) | value[1] as u16),
+            None => Err(DNSProtoErr::PacketParseError),
+        };
+        let mut offset = 12;
+        let mut question = None;
+        for index in 0..header.question_count {
+            let end = skip_name(message, offset)?;
+            if index == 0 {
+                let name = &message[offset..end];
+                // the first question has nothing before it to point to
+                if name.len() > MAX_NAME_WIRE_LENGTH || name.last() != Some(&0) {
+                    return Err(DNSProtoErr::PacketParseError);
+                }
+                question = Some(QuestionView {
+                    name,
+                    q_type: DNSType::from(be_u16_at(end)?),
+                    q_class: DNSClass::from(be_u16_at(end + 2)?),
+                });
+            }
+            offset = end + 4;
+        }
+        for _ in 0..header.answer_count as usize + header.ns_count as usize {
+            offset = skip_record(message, offset)?;
+        }
+        let mut edns = None;
+        for _ in 0..header.additional_count {
+            let end = skip_record(message, offset)?;
+            if edns.is_none() && be_u16_at(skip_name(message, offset)?)? == u16::from(DNSType::OPT)
+            {
+                match parse_answer(&message[offset..end], message) {
+                    Ok((_, Record::EDNSRecord(opt))) => edns = Some(opt),
+                    _ => return Err(DNSProtoErr::PacketParseError),
+                }
+            }
+            offset = end;
+        }
+        Ok(QueryView {
+            header,
+            question,
+            edns,
+        })
+    }
+}
+
 named_args!(parse_answer<'a>(original: &[u8])<&'a [u8], Record>,
     do_parse!(
         name: call!(parse_name, original)>>
@@ -314,10 +711,10 @@ named_args!(parse_answer<'a>(original: &[u8])<&'a [u8], Record>,
                 name,
                 qtype: DNSType::OPT,
                 payload_size: qclass,
-                extension: (ttl & 0xff000000 >> 24 )as u8,
-                version: (ttl & 0x00ff0000 >> 16 )as u8,
-                do_bit: (ttl & 0x0000ff00 >> 15) == 1,
-                raw_data: None,
+                extension: ((ttl & 0xff000000) >> 24) as u8,
+                version: ((ttl & 0x00ff0000) >> 16) as u8,
+                do_bit: ((ttl & 0x00008000) >> 15) == 1,
+                raw_data: if data_length != 0 { Some(data.to_vec()) } else { None },
                 data: {
                     if data_length != 0 {
                         match decode_message_data(data, original, DNSType::OPT){
@@ -330,7 +727,7 @@ named_args!(parse_answer<'a>(original: &[u8])<&'a [u8], Record>,
                 },
             }),
             false => {
-                let qtype = DNSType::try_from(qtype).unwrap();
+                let qtype = DNSType::from(qtype);
                 Record::AnswerRecord(ResourceRecord{
                     data:   {
                         match decode_message_data(data, original, qtype){
@@ -340,8 +737,10 @@ named_args!(parse_answer<'a>(original: &[u8])<&'a [u8], Record>,
                     },
                     name,
                     qtype,
-                    qclass: DNSClass::try_from(qclass).unwrap(),
+                    qclass: DNSClass::from(qclass),
                     ttl,
+                    // set by the storage when a transfer or an update is applied
+                    provenance: Default::default(),
                 })
             }
         }) >>
@@ -383,34 +782,102 @@ named!(parse_header_frame<&[u8], Header>,
     )
 );
 
+// the smallest question(root name, type, class) and resource record(root name, type,
+// class, ttl, rdlength) in wire format
+const MIN_QUESTION_SIZE: usize = 5;
+const MIN_RECORD_SIZE: usize = 11;
+
+/// check_section_sizes walk the sections claimed by the header without decoding them and
+/// return ExcessiveRecords when the counters or a rdata length do not fit in the remaining
+/// bytes, so no memory is reserved for bogus counters.
+fn check_section_sizes(message: &[u8]) -> Result<(), DNSProtoErr> {
+    if message.len() < 12 {
+        // leave it to the header parser
+        return Ok(());
+    }
+    let count = |index: usize| ((message[index] as usize) << 8) | message[index + 1] as usize;
+    let questions = count(4);
+    let records = count(6) + count(8) + count(10);
+    if questions * MIN_QUESTION_SIZE + records * MIN_RECORD_SIZE > message.len() - 12 {
+        return Err(DNSProtoErr::ExcessiveRecords);
+    }
+    let mut offset = 12;
+    for index in 0..questions + records {
+        offset = skip_name(message, offset)?;
+        if index < questions {
+            offset += 4;
+            continue;
+        }
+        if offset + 10 > message.len() {
+            return Err(DNSProtoErr::ExcessiveRecords);
+        }
+        let rdlength = ((message[offset + 8] as usize) << 8) | message[offset + 9] as usize;
+        offset += 10;
+        if rdlength > message.len() - offset {
+            return Err(DNSProtoErr::ExcessiveRecords);
+        }
+        offset += rdlength;
+    }
+    if offset > message.len() {
+        return Err(DNSProtoErr::ExcessiveRecords);
+    }
+    Ok(())
+}
+
+/// skip_name return the offset after the wire format name start at offset, a compression
+/// pointer ends the name and is not followed.
+fn skip_name(message: &[u8], mut offset: usize) -> Result<usize, DNSProtoErr> {
+    loop {
+        let length = match message.get(offset) {
+            Some(length) => *length as usize,
+            None => return Err(DNSProtoErr::ExcessiveRecords),
+        };
+        match length & 0xc0 {
+            0x00 if length == 0 => return Ok(offset + 1),
+            0x00 => offset += length + 1,
+            0xc0 => return Ok(offset + 2),
+            _ => return Err(DNSProtoErr::PacketParseError),
+        }
+    }
+}
+
+/// skip_record return the offset after the resource record start at offset.
+fn skip_record(message: &[u8], offset: usize) -> Result<usize, DNSProtoErr> {
+    let offset = skip_name(message, offset)?;
+    match message.get(offset + 8..offset + 10) {
+        Some(rdlength) => Ok(offset + 10 + (((rdlength[0] as usize) << 8) | rdlength[1] as usize)),
+        None => Err(DNSProtoErr::PacketParseError),
+    }
+}
+
 named_args!(parse_message<'a>(original: &[u8])<&'a [u8], Message>,
     do_parse!(
         header:  parse_header_frame >>
-        questions: many_m_n!(header.question_count as usize,header.question_count as usize, parse_question) >>
+        questions: many_m_n!(header.question_count as usize,header.question_count as usize, call!(parse_question, original)) >>
         answers: many_m_n!(header.answer_count as usize,header.answer_count as usize, call!(parse_answer,original)) >>
         authorities: many_m_n!(header.ns_count as usize,header.ns_count as usize, call!(parse_answer,original))>>
         additional:  many_m_n!(header.additional_count as usize,header.additional_count as usize, call!(parse_answer, original)) >>
-        (Message{
-            header,
-            questions,
-            answers,
-            authorities,
-            additional,
-        })
+        (Message::from_sections(header, questions, answers, authorities, additional))
     )
 );
 
 #[cfg(test)]
 mod message {
-    use crate::dnsname::DNSName;
+    use crate::dnsname::{CompressionMap, DNSName};
     use crate::edns::EDNS;
     use crate::label::Label;
     use crate::message::{
         parse_answer, parse_header_frame, parse_message, parse_question, Message, Record,
     };
-    use crate::meta::{DNSClass, DNSType, Header, OpCode, Question, RCode, ResourceRecord};
-    use crate::qtype::{DnsTypeA, DnsTypeNS};
-    use std::collections::HashMap;
+    use crate::meta::{
+        DNSClass, DNSType, Header, OpCode, Provenance, Question, RCode, RRSet, ResourceRecord,
+    };
+    use crate::qtype::{
+        DNSWireFrame, DnsTypeA, DnsTypeCNAME, DnsTypeMX, DnsTypeNS, DnsTypeSOA, DnsTypeSRV,
+    };
+    use otterlib::errors::DNSProtoErr;
+    use std::alloc::{GlobalAlloc, Layout, System};
+    use std::cell::Cell;
     use std::io::Cursor;
     use std::str::FromStr;
 
@@ -484,7 +951,7 @@ mod message {
             0x63, 0x6f, 0x6d, 0x00, 0x00, 0x01, 0x00, 0x01,
         ];
 
-        let result = parse_question(&a);
+        let result = parse_question(&a, &a);
         let question = Question {
             q_name: DNSName {
                 labels: vec![
@@ -546,6 +1013,7 @@ mod message {
             qclass: DNSClass::IN,
             ttl: 64,
             data: Some(Box::new(DnsTypeA::from_str("69.171.228.20").unwrap())),
+            provenance: Provenance::File,
         });
         assert_eq!(result, a.unwrap().1);
 
@@ -566,6 +1034,7 @@ mod message {
             qclass: DNSClass::IN,
             ttl: 156585,
             data: Some(Box::new(DnsTypeA::from_str("69.171.228.20").unwrap())),
+            provenance: Provenance::File,
         });
         assert_eq!(result, a.unwrap().1);
 
@@ -586,6 +1055,52 @@ mod message {
         assert_eq!(result, a.unwrap().1);
     }
 
+    #[test]
+    fn test_parse_excessive_records() {
+        for packet in [
+            // 30 bytes packet claims 65535 answers
+            vec![
+                0xa4, 0xac, 0x01, 0x00, 0x00, 0x01, 0xff, 0xff, 0x00, 0x00, 0x00, 0x00, 0x03, 0x63,
+                0x6f, 0x6d, 0x00, 0x00, 0x01, 0x00, 0x01, 0xc0, 0x0c, 0x00, 0x01, 0x00, 0x01, 0x00,
+                0x00, 0x00,
+            ],
+            // counters fit but the rdata length is larger than the packet
+            vec![
+                0xa4, 0xac, 0x01, 0x00, 0x00, 0x01, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x03, 0x63,
+                0x6f, 0x6d, 0x00, 0x00, 0x01, 0x00, 0x01, 0xc0, 0x0c, 0x00, 0x01, 0x00, 0x01, 0x00,
+                0x00, 0x00, 0x40, 0xff, 0xff, 0x01, 0x02, 0x03, 0x04,
+            ],
+            // counters fit but the question name runs out of the packet
+            vec![
+                0xa4, 0xac, 0x01, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x3f, 0x61,
+                0x61, 0x61, 0x61, 0x61, 0x61, 0x61, 0x61, 0x61,
+            ],
+            // inflated additional counter behind a valid query
+            vec![
+                0xa4, 0xac, 0x01, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x02, 0x03, 0x63,
+                0x6f, 0x6d, 0x00, 0x00, 0x01, 0x00, 0x01, 0x00, 0x00, 0x29, 0x10, 0x00, 0x00, 0x00,
+                0x00, 0x00, 0x00, 0x00,
+            ],
+        ]
+        .iter()
+        {
+            assert_eq!(
+                Message::parse_dns_message(packet).unwrap_err(),
+                DNSProtoErr::ExcessiveRecords
+            );
+            assert!(Message::new_format_error_message(packet).is_some());
+        }
+        let mut message = Message::new_format_error_message(&[
+            0xa4, 0xac, 0x01, 0x00, 0x00, 0x01, 0xff, 0xff, 0x00, 0x00, 0x00, 0x00,
+        ])
+        .unwrap();
+        assert_eq!(message.header.id(), 0xa4ac);
+        assert_eq!(message.header.rcode(), RCode::FormatError);
+        let encoded = message.encode(true).unwrap();
+        assert_eq!(&encoded[4..12], &[0u8; 8]);
+        assert!(Message::new_format_error_message(&[0xa4, 0xac]).is_none());
+    }
+
     #[test]
     fn test_decode_incorrect_packet() {
         let a = [];
@@ -604,9 +1119,85 @@ mod message {
             0xa4, 0xac, 0x01, 0x20, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00,
             0x29, 0x10, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
         ];
+        // the packet is parsed, and answered with FORMERR by new_message_from_query
         let result = Message::parse_dns_message(&a);
-        //TODO:  without a question is correct packets?
         assert_eq!(result.is_err(), false);
+
+        // header claims more records than the packet can hold
+        let a = [
+            0xa4, 0xac, 0x01, 0x20, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x00, 0x00,
+            0x01, 0x00, 0x01,
+        ];
+        assert!(Message::parse_dns_message(&a).is_err());
+
+        assert_eq!(
+            Message::parse_dns_message(&a).unwrap_err(),
+            DNSProtoErr::ExcessiveRecords
+        );
+
+        // question name is a pointer to itself
+        let a = [
+            0xa4, 0xac, 0x01, 0x20, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xc0, 0x0c,
+            0x00, 0x01, 0x00, 0x01,
+        ];
+        assert!(Message::parse_dns_message(&a).is_err());
+
+        // answer name is a pointer out of the packet
+        let a = [
+            0xa4, 0xac, 0x81, 0x80, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0xc0, 0xff,
+            0x00, 0x01, 0x00, 0x01, 0x00, 0x00, 0x00, 0x40, 0x00, 0x04, 0x45, 0xab, 0xe4, 0x14,
+        ];
+        assert!(Message::parse_dns_message(&a).is_err());
+    }
+
+    #[test]
+    fn test_new_not_implemented_message() {
+        // query example.com. A
+        let mut query = [
+            0x12, 0x34, 0x01, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x07, 0x65,
+            0x78, 0x61, 0x6d, 0x70, 0x6c, 0x65, 0x03, 0x63, 0x6f, 0x6d, 0x00, 0x00, 0x01, 0x00,
+            0x01,
+        ];
+        for opcode in 0u8..16 {
+            query[2] = (query[2] & 0x87) | (opcode << 3);
+            let parsed = Message::parse_dns_message(&query).unwrap();
+            let parsed_opcode: u8 = parsed.header.op_code().into();
+            assert_eq!(parsed_opcode, opcode);
+            let mut message = Message::new_not_implemented_message(&parsed);
+            let response = Message::parse_dns_message(&message.encode(true).unwrap()).unwrap();
+            assert!(!response.is_query());
+            assert_eq!(response.header.op_code(), parsed.header.op_code());
+            assert_eq!(response.header.rcode(), RCode::NotImplemented);
+            assert_eq!(response.questions, parsed.questions);
+        }
+    }
+
+    #[test]
+    fn test_decode_compressed_question() {
+        // question name point to the previous question
+        let a = [
+            0xa4, 0xac, 0x01, 0x20, 0x00, 0x02, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x03, 0x63,
+            0x6f, 0x6d, 0x00, 0x00, 0x01, 0x00, 0x01, 0xc0, 0x0c, 0x00, 0x1c, 0x00, 0x01,
+        ];
+        let message = Message::parse_dns_message(&a).unwrap();
+        assert_eq!(message.questions.len(), 2);
+        assert_eq!(message.questions[1].q_name.to_string(), "com.");
+    }
+
+    #[test]
+    fn test_reencode_parsed_message() {
+        let message = [
+            202u8, 177, 129, 0, 0, 1, 0, 4, 0, 0, 0, 1, 6, 103, 111, 111, 103, 108, 101, 3, 99,
+            111, 109, 0, 0, 2, 0, 1, 192, 12, 0, 2, 0, 1, 0, 0, 39, 16, 0, 6, 3, 110, 115, 49, 192,
+            12, 192, 12, 0, 2, 0, 1, 0, 0, 39, 16, 0, 6, 3, 110, 115, 50, 192, 12, 192, 12, 0, 2,
+            0, 1, 0, 0, 39, 16, 0, 6, 3, 110, 115, 51, 192, 12, 192, 12, 0, 2, 0, 1, 0, 0, 39, 16,
+            0, 6, 3, 110, 115, 52, 192, 12, 0, 0, 41, 4, 219, 0, 0, 0, 0, 0, 0,
+        ];
+        let mut parsed = Message::parse_dns_message(&message).unwrap();
+        let encoded = parsed.encode(true).unwrap();
+        assert_eq!(encoded, message.to_vec());
+        let reparsed = Message::parse_dns_message(&encoded).unwrap();
+        assert_eq!(reparsed, parsed);
     }
 
     #[test]
@@ -682,9 +1273,10 @@ mod message {
         let ref mut cursor = Cursor::new(vec![]);
         match header.encode(cursor) {
             Ok(_offset) => {
+                // the ad bit(0x20) of the fourth byte is encoded
                 assert_eq!(
                     cursor.get_ref().clone(),
-                    vec![0x2b, 0x01, 0x01, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01]
+                    vec![0x2b, 0x01, 0x01, 0x20, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01]
                 );
             }
             Err(err) => {
@@ -718,8 +1310,10 @@ mod message {
             q_class: DNSClass::IN,
         };
 
-        let mut compression = HashMap::new();
-        compression.insert(vec![Label::from_str("com").unwrap()], 2usize);
+        let mut compression = CompressionMap::new();
+        DNSName::new("com.", None)
+            .unwrap()
+            .to_binary(Some((&mut compression, 2)));
         let compression = Some(&mut compression);
         let ref mut cursor = Cursor::new(vec![]);
         match question.encode(cursor, compression) {
@@ -746,6 +1340,7 @@ mod message {
             qtype: DNSType::NS,
             qclass: DNSClass::IN,
             data: Some(Box::new(nsdata)),
+            provenance: Provenance::File,
         };
 
         let ref mut cursor = Cursor::new(vec![]);
@@ -762,14 +1357,10 @@ mod message {
             Err(e) => assert!(false, format!("error: {}", e.to_string())),
         }
 
-        let ref mut compression = HashMap::new();
-        compression.insert(
-            vec![
-                Label::from_str("gtld-servers").unwrap(),
-                Label::from_str("net").unwrap(),
-            ],
-            2usize,
-        );
+        let ref mut compression = CompressionMap::new();
+        DNSName::new("gtld-servers.net.", None)
+            .unwrap()
+            .to_binary(Some((compression, 2)));
         let ref mut cursor = Cursor::new(vec![]);
         match answer.encode(cursor, Some(compression)) {
             Ok(cursor) => {
@@ -816,7 +1407,9 @@ mod message {
         header.rd = true;
         // serialize a question
         let question = Question::new("google.com.", DNSType::NS, DNSClass::IN).unwrap();
-        let edns = EDNS::new();
+        // the payload size of the captured message in test_decode_dns_message
+        let mut edns = EDNS::new();
+        edns.set_payload_size(1243);
         let mut message = Message::new_with_header(header);
         message.set_question(question);
         message.append_edns(edns);
@@ -878,6 +1471,196 @@ mod message {
         }
     }
 
+    // CountingAllocator count the allocations of the current thread, so tests running
+    // on other threads do not disturb the numbers.
+    struct CountingAllocator;
+
+    thread_local! {
+        static ALLOCATIONS: Cell<usize> = Cell::new(0);
+    }
+
+    unsafe impl GlobalAlloc for CountingAllocator {
+        unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+            let _ = ALLOCATIONS.try_with(|count| count.set(count.get() + 1));
+            System.alloc(layout)
+        }
+        unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+            System.dealloc(ptr, layout)
+        }
+    }
+
+    #[global_allocator]
+    static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+    fn count_allocations<T>(run: impl FnOnce() -> T) -> usize {
+        let before = ALLOCATIONS.with(|count| count.get());
+        let result = run();
+        let after = ALLOCATIONS.with(|count| count.get());
+        drop(result);
+        after - before
+    }
+
+    #[test]
+    fn test_parse_query_header_and_question() {
+        let mut query = Message::new_with_header(Header::new());
+        query.set_question(Question::new("www.example.com.", DNSType::AAAA, DNSClass::IN).unwrap());
+        // a known answer is skipped without decoding
+        query.append_answer(
+            ResourceRecord::new(
+                "www.example.com.",
+                DNSType::A,
+                DNSClass::IN,
+                300,
+                Some(Box::new(DnsTypeA::new("192.0.2.1").unwrap())),
+            )
+            .unwrap(),
+        );
+        let mut edns = EDNS::new();
+        edns.set_payload_size(1232);
+        query.append_edns(edns);
+        let data = query.encode(true).unwrap();
+
+        let view = Message::parse_query_header_and_question(&data).unwrap();
+        assert_eq!(view.header, query.header);
+        let question = view.question().unwrap();
+        let labels: Vec<&[u8]> = question.labels().collect();
+        assert_eq!(labels, vec![&b"www"[..], &b"example"[..], &b"com"[..]]);
+        assert_eq!(question.q_type(), DNSType::AAAA);
+        assert_eq!(question.q_class(), DNSClass::IN);
+        assert_eq!(
+            question.to_dnsname(),
+            DNSName::new("www.example.com.", None).unwrap()
+        );
+        assert_eq!(view.edns().unwrap().payload_size(), 1232);
+
+        let message = Message::from_query_view(&view);
+        let full = Message::parse_dns_message(&data).unwrap();
+        assert_eq!(message.questions, full.questions);
+        assert_eq!(message.edns().unwrap().payload_size(), 1232);
+        assert!(message.answers.is_empty());
+
+        let full_allocations = count_allocations(|| Message::parse_dns_message(&data).unwrap());
+        let view_allocations =
+            count_allocations(|| Message::parse_query_header_and_question(&data).unwrap());
+        assert!(full_allocations > 0);
+        assert!(view_allocations * 2 <= full_allocations);
+
+        // a pointer in the first question is invalid
+        let pointer = [0u8, 1, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0xc0, 0x0c, 0, 1, 0, 1];
+        assert!(Message::parse_query_header_and_question(&pointer).is_err());
+        assert!(Message::parse_query_header_and_question(&data[..10]).is_err());
+    }
+
+    // assert_roundtrip decode the encoded message and check every section is unchanged,
+    // both with and without name compression.
+    fn assert_roundtrip(message: &mut Message) {
+        for compressed in &[true, false] {
+            let data = match compressed {
+                true => message.encode(true).unwrap(),
+                false => message.encode_uncompressed(true).unwrap(),
+            };
+            let decoded = Message::parse_dns_message(&data).unwrap();
+            assert_eq!(decoded.header, message.header);
+            assert_eq!(decoded.questions, message.questions);
+            assert_eq!(decoded.answers, message.answers);
+            assert_eq!(decoded.authorities, message.authorities);
+            assert_eq!(decoded.additional, message.additional);
+            assert_eq!(decoded.edns, message.edns);
+        }
+    }
+
+    #[test]
+    fn test_encode_message_cross_section() {
+        let mut header = Header::new();
+        header.aa = true;
+        header.tc = true;
+        header.ad = true;
+        header.cd = true;
+        let mut message = Message::new_with_header(header);
+        message.set_question(Question::new("www.example.com.", DNSType::A, DNSClass::IN).unwrap());
+        let records: Vec<(&str, DNSType, Box<dyn DNSWireFrame>)> = vec![
+            (
+                "www.example.com.",
+                DNSType::CNAME,
+                Box::new(DnsTypeCNAME::new("web.example.com.").unwrap()),
+            ),
+            (
+                "web.example.com.",
+                DNSType::A,
+                Box::new(DnsTypeA::new("192.0.2.1").unwrap()),
+            ),
+            (
+                "example.com.",
+                DNSType::MX,
+                Box::new(DnsTypeMX::new(10, "mail.example.com.").unwrap()),
+            ),
+            (
+                "_sip._tcp.example.com.",
+                DNSType::SRV,
+                Box::new(DnsTypeSRV::new(10, 60, 5060, "sip.example.com.").unwrap()),
+            ),
+        ];
+        for (owner, dtype, data) in records {
+            message.append_answer(
+                ResourceRecord::new(owner, dtype, DNSClass::IN, 300, Some(data)).unwrap(),
+            );
+        }
+        message.append_authority(
+            ResourceRecord::new(
+                "example.com.",
+                DNSType::NS,
+                DNSClass::IN,
+                3600,
+                Some(Box::new(DnsTypeNS::new("ns1.example.com.").unwrap())),
+            )
+            .unwrap(),
+        );
+        message.append_authority(
+            ResourceRecord::new(
+                "example.com.",
+                DNSType::SOA,
+                DNSClass::IN,
+                3600,
+                Some(Box::new(
+                    DnsTypeSOA::new(
+                        "ns1.example.com.",
+                        "admin.example.com.",
+                        2021010101,
+                        7200,
+                        3600,
+                        1209600,
+                        300,
+                    )
+                    .unwrap(),
+                )),
+            )
+            .unwrap(),
+        );
+        message.append_additional(
+            ResourceRecord::new(
+                "ns1.example.com.",
+                DNSType::A,
+                DNSClass::IN,
+                3600,
+                Some(Box::new(DnsTypeA::new("192.0.2.53").unwrap())),
+            )
+            .unwrap(),
+        );
+        message.append_edns(EDNS::new());
+        assert_roundtrip(&mut message);
+
+        // aa and tc in the first flags byte, ad and cd in the second
+        let data = message.encode(true).unwrap();
+        assert_eq!(data[2], 0b0000_0110);
+        assert_eq!(data[3], 0b0011_0000);
+        assert!(data.len() < message.encode_uncompressed(true).unwrap().len());
+    }
+
+    #[test]
+    fn test_encode_message_roundtrip() {
+        assert_roundtrip(&mut get_message());
+    }
+
     #[test]
     fn test_decode_dns_message() {
         let message = [
@@ -894,6 +1677,7 @@ mod message {
                 assert_eq!(decoded_message.questions, message_s.questions);
                 assert_eq!(decoded_message.answers, message_s.answers);
                 assert_eq!(decoded_message.additional, message_s.additional);
+                assert_eq!(decoded_message.edns, message_s.edns);
             }
             _ => {
                 assert!(false);
@@ -901,6 +1685,16 @@ mod message {
         }
     }
 
+    #[test]
+    fn test_encode_uncompressed_message() {
+        let mut message = get_message();
+        let compressed = message.encode(true).unwrap();
+        let uncompressed = message.encode_uncompressed(true).unwrap();
+        assert!(uncompressed.len() > compressed.len());
+        let decoded = Message::parse_dns_message(&uncompressed).unwrap();
+        assert_eq!(decoded.answers, message.answers);
+    }
+
     #[test]
     fn test_new_tc_message_from_build_message() {
         let mut message_s = get_message();
@@ -909,6 +1703,237 @@ mod message {
         assert_eq!(tc_message.questions.len(), 1);
         assert_eq!(tc_message.answers.len(), 0);
         assert_eq!(tc_message.authorities.len(), 0);
-        assert_eq!(tc_message.additional.len(), 1);
+        assert_eq!(tc_message.additional.len(), 0);
+        assert!(tc_message.edns.is_some());
+        assert_eq!(
+            (
+                tc_message.header.answer_count,
+                tc_message.header.ns_count,
+                tc_message.header.additional_count
+            ),
+            (0, 0, 1)
+        );
+    }
+
+    #[test]
+    fn test_tc_message_counts_match_sections() {
+        let mut message = get_message();
+        message.append_authority(
+            ResourceRecord::new(
+                "google.com.",
+                DNSType::NS,
+                DNSClass::IN,
+                10000,
+                Some(Box::new(DnsTypeNS::new("ns1.google.com.").unwrap())),
+            )
+            .unwrap(),
+        );
+        let data = Message::new_tc_message_from_build_message(&mut message)
+            .encode(true)
+            .unwrap();
+        // qdcount 1, ancount 0, nscount 0, arcount 1 for the OPT record
+        assert_eq!(data[4..12], [0, 1, 0, 0, 0, 0, 0, 1]);
+        let decoded = Message::parse_dns_message(&data).unwrap();
+        assert!(decoded.header.tc);
+        assert!(decoded.edns().is_some());
+        assert!(decoded.additionals().is_empty());
+    }
+
+    #[test]
+    fn test_update_additional_after_append_edns() {
+        let mut message = get_message();
+        let mut rrset = RRSet::default();
+        for ip in ["192.0.2.1", "192.0.2.2"].iter() {
+            rrset.add(
+                ResourceRecord::new(
+                    "ns1.google.com.",
+                    DNSType::A,
+                    DNSClass::IN,
+                    300,
+                    Some(Box::new(DnsTypeA::new(ip).unwrap())),
+                )
+                .unwrap(),
+            );
+        }
+        message.update_additional(&rrset);
+        assert_eq!(message.header.additional_count, 3);
+        assert!(message.edns().is_some());
+        // the OPT record is encoded after the additional records and counted once
+        message.append_edns(EDNS::new());
+        let data = message.encode(true).unwrap();
+        assert_eq!(data[10..12], [0, 3]);
+        assert_eq!(data[data.len() - 11..data.len() - 8], [0, 0, 41]);
+        let decoded = Message::parse_dns_message(&data).unwrap();
+        assert_eq!(decoded.additionals().len(), 2);
+        assert_eq!(decoded.edns, message.edns);
+        message.append_additional(
+            ResourceRecord::new(
+                "ns2.google.com.",
+                DNSType::A,
+                DNSClass::IN,
+                300,
+                Some(Box::new(DnsTypeA::new("192.0.2.3").unwrap())),
+            )
+            .unwrap(),
+        );
+        assert_eq!(message.header.additional_count, 4);
+        assert_roundtrip(&mut message);
+    }
+
+    #[test]
+    fn test_new_message_from_query_with_bad_edns_version() {
+        // query example.com. A with OPT version = 1
+        let query = [
+            0x12, 0x34, 0x01, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01, 0x07, 0x65,
+            0x78, 0x61, 0x6d, 0x70, 0x6c, 0x65, 0x03, 0x63, 0x6f, 0x6d, 0x00, 0x00, 0x01, 0x00,
+            0x01, 0x00, 0x00, 0x29, 0x10, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00,
+        ];
+        let query = Message::parse_dns_message(&query).unwrap();
+        let (mut message, _, terminator) = Message::new_message_from_query(&query, true, 1232);
+        assert!(terminator);
+        let response = message.encode(true).unwrap();
+        // header rcode only hold the lower 4 bits of BADVERS(16)
+        assert_eq!(response[3] & 0x0f, 0);
+        let response = Message::parse_dns_message(&response).unwrap();
+        assert_eq!(response.header.id, 0x1234);
+        assert_eq!(response.questions, query.questions);
+        assert_eq!(response.answers.len(), 0);
+        assert_eq!(response.additional.len(), 0);
+        let edns = response.edns().expect("additional should be an OPT record");
+        assert_eq!(edns.version, 0);
+        assert_eq!(edns.extension, 1);
+        assert_eq!(edns.payload_size, 1232);
+    }
+
+    #[test]
+    fn test_new_message_from_query_max_size() {
+        for (edns, server, max_size) in [
+            (None, 1232, 512),
+            (Some(4096), 1232, 1232),
+            (Some(1232), 1232, 1232),
+            (Some(1024), 1232, 1024),
+            (Some(100), 1232, 512),
+            (Some(4096), 0, 512),
+        ]
+        .iter()
+        {
+            let mut query = Message::new_with_header(Header::new());
+            query.set_question(Question::new("example.com.", DNSType::A, DNSClass::IN).unwrap());
+            if let Some(payload_size) = edns {
+                let mut opt = EDNS::new();
+                opt.set_payload_size(*payload_size);
+                query.append_edns(opt);
+            }
+            let (message, size, _) = Message::new_message_from_query(&query, true, *server);
+            assert_eq!(size, *max_size, "{:?} {}", edns, server);
+            if edns.is_some() {
+                assert_eq!(message.edns().unwrap().payload_size(), *max_size);
+            }
+        }
+    }
+
+    #[test]
+    fn test_add_extended_error() {
+        let mut message = Message::new_with_header(Header::new());
+        message.set_question(Question::new("example.com.", DNSType::A, DNSClass::IN).unwrap());
+        // no OPT record, no option
+        message.add_extended_error(0, "loop detected");
+        assert!(message.edns().is_none());
+
+        let mut edns = EDNS::new();
+        edns.set_cookie(&[1, 2, 3, 4, 5, 6, 7, 8]).unwrap();
+        message.append_edns(edns);
+        message.add_extended_error(0, "loop detected");
+        let encoded = message.encode(true).unwrap();
+        let parsed = Message::parse_dns_message(&encoded).unwrap();
+        let edns = parsed.edns().unwrap();
+        assert_eq!(edns.cookie(), Some(&[1u8, 2, 3, 4, 5, 6, 7, 8][..]));
+        assert_eq!(
+            edns.extended_error(),
+            Some((0, "loop detected".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_new_message_from_query_question_count() {
+        // header and OPT without question
+        let query = [
+            0xa4, 0xac, 0x01, 0x20, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00,
+            0x29, 0x10, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        ];
+        let query = Message::parse_dns_message(&query).unwrap();
+        let (mut message, _, terminator) = Message::new_message_from_query(&query, true, 1232);
+        assert!(terminator);
+        let response = Message::parse_dns_message(&message.encode(true).unwrap()).unwrap();
+        assert_eq!(response.header.rcode(), RCode::FormatError);
+        assert!(response.questions.is_empty());
+
+        // cookie only query with the client cookie
+        let query = [
+            0xa4, 0xac, 0x01, 0x20, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00,
+            0x29, 0x10, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x0c, 0x00, 0x0a, 0x00, 0x08, 0x01,
+            0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08,
+        ];
+        let query = Message::parse_dns_message(&query).unwrap();
+        let (mut message, _, terminator) = Message::new_message_from_query(&query, true, 1232);
+        assert!(terminator);
+        let encoded = message.encode(true).unwrap();
+        let response = Message::parse_dns_message(&encoded).unwrap();
+        assert_eq!(response.header.rcode(), RCode::NoError);
+        assert!(response.questions.is_empty());
+        assert!(response.answers.is_empty());
+        assert_eq!(
+            response.edns().unwrap().cookie(),
+            Some(&[1u8, 2, 3, 4, 5, 6, 7, 8][..])
+        );
+
+        // malformed cookie
+        let query = [
+            0xa4, 0xac, 0x01, 0x20, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00,
+            0x29, 0x10, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x08, 0x00, 0x0a, 0x00, 0x04, 0x01,
+            0x02, 0x03, 0x04,
+        ];
+        let query = Message::parse_dns_message(&query).unwrap();
+        let (message, _, _) = Message::new_message_from_query(&query, true, 1232);
+        assert_eq!(message.header.rcode(), RCode::FormatError);
+
+        // two questions: example.com. A and example.com. AAAA
+        let query = [
+            0x12, 0x34, 0x01, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x07, 0x65,
+            0x78, 0x61, 0x6d, 0x70, 0x6c, 0x65, 0x03, 0x63, 0x6f, 0x6d, 0x00, 0x00, 0x01, 0x00,
+            0x01, 0xc0, 0x0c, 0x00, 0x1c, 0x00, 0x01,
+        ];
+        let query = Message::parse_dns_message(&query).unwrap();
+        assert_eq!(query.questions.len(), 2);
+        let (message, _, terminator) = Message::new_message_from_query(&query, true, 1232);
+        assert!(terminator);
+        assert_eq!(message.header.rcode(), RCode::FormatError);
+    }
+
+    fn build_query(domain: &str, q_type: DNSType, q_class: DNSClass) -> Message {
+        let mut message = Message::new_with_header(Header::new());
+        message.set_question(Question::new(domain, q_type, q_class).unwrap());
+        message
+    }
+
+    #[test]
+    fn test_new_message_from_query_question_section() {
+        let query = build_query("google.com.", DNSType::NS, DNSClass::IN);
+        let (mut message, _, terminator) = Message::new_message_from_query(&query, true, 1232);
+        assert!(!terminator);
+        assert_eq!(message.questions, query.questions);
+        assert_eq!(message.header.question_count, 1);
+
+        let tc_message = Message::new_tc_message_from_build_message(&mut message);
+        assert_eq!(tc_message.questions, query.questions);
+        assert_eq!(tc_message.header.question_count, 1);
+
+        let query = build_query("version.bind.", DNSType::TXT, DNSClass::CH);
+        let (mut message, _, terminator) = Message::new_message_from_query(&query, true, 1232);
+        assert!(terminator);
+        assert_eq!(message.questions, query.questions);
+        let response = Message::parse_dns_message(&message.encode(true).unwrap()).unwrap();
+        assert_eq!(response.questions, query.questions);
+        assert_eq!(response.answers.len(), 1);
     }
 }
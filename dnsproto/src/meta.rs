@@ -1,8 +1,7 @@
 // http://www.networksorcery.com/enp/protocol/dns.htm
-use crate::dnsname::DNSName;
+use crate::dnsname::{CompressionMap, DNSName};
 use crate::qtype::DNSWireFrame;
 use byteorder::{BigEndian, WriteBytesExt};
-use nom::lib::std::collections::HashMap;
 use rand::Rng;
 use std::io::{Cursor, Write};
 
@@ -65,15 +64,42 @@ impl Header {
     pub fn set_rcode(&mut self, rcode: RCode) {
         self.r_code = rcode;
     }
+    pub fn rcode(&self) -> RCode {
+        self.r_code
+    }
     pub fn set_aa(&mut self, aa: bool) {
         self.aa = aa;
     }
+    pub fn aa(&self) -> bool {
+        self.aa
+    }
+    pub fn op_code(&self) -> OpCode {
+        self.op_code
+    }
+    pub fn set_op_code(&mut self, op_code: OpCode) {
+        self.op_code = op_code;
+    }
+    pub fn id(&self) -> u16 {
+        self.id
+    }
     pub fn set_id(&mut self, id: u16) {
         self.id = id
     }
+    pub fn question_count(&self) -> u16 {
+        self.question_count
+    }
+    pub fn tc(&self) -> bool {
+        self.tc
+    }
     pub fn set_rd(&mut self, rd: bool) {
         self.rd = rd;
     }
+    pub fn rd(&self) -> bool {
+        self.rd
+    }
+    pub fn ra(&self) -> bool {
+        self.ra
+    }
     pub fn set_qr(&mut self, qr: bool) {
         self.qr = qr;
     }
@@ -97,13 +123,16 @@ impl Header {
         let opcode: u8 = self.op_code.into();
         h0 |= (opcode) << 3;
         h0 |= (self.aa as u8) << 2;
-        h0 |= (self.aa as u8) << 1;
+        h0 |= (self.tc as u8) << 1;
         h0 |= self.rd as u8;
 
         let mut h1 = (self.ra as u8) << 7;
         let rcode: u8 = self.r_code.into();
         h1 |= (self.z as u8) << 6;
-        h1 |= rcode;
+        h1 |= (self.ad as u8) << 5;
+        h1 |= (self.cd as u8) << 4;
+        // the upper bits of extended rcode is carried by OPT record
+        h1 |= rcode & 0x0f;
         cursor.write_u8(h0)?;
         cursor.write_u8(h1)?;
         cursor.write_u16::<BigEndian>(self.question_count)?;
@@ -149,7 +178,7 @@ impl Question {
     pub fn encode<'a>(
         &self,
         cursor: &'a mut Cursor<Vec<u8>>,
-        compression: Option<&mut HashMap<Vec<Label>, usize>>,
+        compression: Option<&mut CompressionMap>,
     ) -> Result<&'a mut Cursor<Vec<u8>>, DNSProtoErr> {
         let frame = {
             match compression {
@@ -166,8 +195,8 @@ impl Question {
             // }
         };
         cursor.write_all(frame.as_slice())?;
-        cursor.write_u16::<BigEndian>(self.q_type as u16)?;
-        cursor.write_u16::<BigEndian>(self.q_class as u16)?;
+        cursor.write_u16::<BigEndian>(self.q_type.into())?;
+        cursor.write_u16::<BigEndian>(u16::from(self.q_class))?;
         Ok(cursor)
     }
 }
@@ -199,6 +228,50 @@ pub struct ResourceRecord {
     pub(crate) qclass: DNSClass,
     pub(crate) ttl: u32,
     pub(crate) data: Option<Box<dyn DNSWireFrame>>,
+    // where the record come from, never encoded
+    pub(crate) provenance: Provenance,
+}
+
+/// Provenance tell how a record get into the zone, it is kept in memory only and does
+/// not affect the wire format or the record equality.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Provenance {
+    /// loaded from the zone file
+    #[default]
+    File,
+    /// added by a dynamic update
+    Update,
+    /// received by a zone transfer
+    Transfer,
+}
+
+impl Display for Provenance {
+    fn fmt(&self, formatter: &mut Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Provenance::File => "file",
+            Provenance::Update => "update",
+            Provenance::Transfer => "transfer",
+        };
+        write!(formatter, "{}", name)
+    }
+}
+
+/// the record is serialized as {"name", "ttl", "class", "type", "rdata", "provenance"}
+/// with the rdata in presentation format.
+#[cfg(feature = "serde")]
+impl serde::Serialize for ResourceRecord {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct;
+        let mut state = serializer.serialize_struct("ResourceRecord", 6)?;
+        state.serialize_field("name", &self.name.to_string())?;
+        state.serialize_field("ttl", &self.ttl)?;
+        state.serialize_field("class", &self.qclass.to_string())?;
+        state.serialize_field("type", &self.qtype.to_string())?;
+        let rdata = self.data.as_ref().map(|data| data.to_string());
+        state.serialize_field("rdata", &rdata.unwrap_or_default())?;
+        state.serialize_field("provenance", &self.provenance.to_string())?;
+        state.end()
+    }
 }
 
 impl Display for ResourceRecord {
@@ -243,6 +316,7 @@ impl ResourceRecord {
             qclass,
             ttl,
             data,
+            provenance: Provenance::File,
         })
     }
     #[inline]
@@ -252,22 +326,62 @@ impl ResourceRecord {
     pub fn get_ttl(&self) -> u32 {
         self.ttl
     }
+    pub fn get_class(&self) -> DNSClass {
+        self.qclass
+    }
+    pub fn set_ttl(&mut self, ttl: u32) {
+        self.ttl = ttl;
+    }
+    /// provenance return where the record come from.
+    pub fn provenance(&self) -> Provenance {
+        self.provenance
+    }
+    pub fn set_provenance(&mut self, provenance: Provenance) {
+        self.provenance = provenance;
+    }
     pub fn get_data(&self) -> &Option<Box<dyn DNSWireFrame>> {
         &self.data
     }
+    /// rdata_as return the rdata as the concrete type, None when the record has no rdata
+    /// or the rdata is another type.
+    ///
+    /// ```
+    /// use dnsproto::meta::{DNSClass, DNSType, ResourceRecord};
+    /// use dnsproto::qtype::{DnsTypeA, DnsTypeMX};
+    ///
+    /// let data = Box::new(DnsTypeA::new("192.0.2.1").unwrap());
+    /// let rr = ResourceRecord::new("example.com.", DNSType::A, DNSClass::IN, 300, Some(data))
+    ///     .unwrap();
+    /// assert_eq!(
+    ///     rr.rdata_as::<DnsTypeA>().map(|a| a.address()),
+    ///     Some("192.0.2.1".parse().unwrap())
+    /// );
+    /// assert!(rr.rdata_as::<DnsTypeMX>().is_none());
+    /// ```
+    pub fn rdata_as<T: DNSWireFrame + 'static>(&self) -> Option<&T> {
+        self.data
+            .as_ref()
+            .and_then(|data| data.as_any().downcast_ref::<T>())
+    }
+    pub fn set_data(&mut self, data: Option<Box<dyn DNSWireFrame>>) {
+        self.data = data;
+    }
     pub fn get_label_count(&self) -> usize {
         self.name.label_count()
     }
     pub fn get_dname(&self) -> &DNSName {
         &self.name
     }
+    pub fn set_dname(&mut self, name: DNSName) {
+        self.name = name;
+    }
     pub fn get_label_iter(&self) -> Iter<'_, Label> {
         self.name.labels.iter()
     }
     pub fn encode<'a>(
         &self,
         cursor: &'a mut Cursor<Vec<u8>>,
-        compression: Option<&mut HashMap<Vec<Label>, usize>>,
+        compression: Option<&mut CompressionMap>,
     ) -> Result<&'a mut Cursor<Vec<u8>>, DNSProtoErr> {
         let offset = cursor.position();
         if self.data.is_none() {
@@ -278,16 +392,17 @@ impl ResourceRecord {
             _ => (self.name.to_binary(None), None),
         };
         cursor.write_all(frame.as_slice())?;
-        cursor.write_u16::<BigEndian>(self.qtype as u16)?;
-        cursor.write_u16::<BigEndian>(self.qclass as u16)?;
+        cursor.write_u16::<BigEndian>(self.qtype.into())?;
+        cursor.write_u16::<BigEndian>(u16::from(self.qclass))?;
         cursor.write_u32::<BigEndian>(self.ttl)?;
 
+        // the rdata starts after the 2 bytes rdlength
         let encoded = match compression {
             Some(cp) => self
                 .data
                 .as_ref()
                 .unwrap()
-                .encode(Some((cp, cursor.position() as usize))),
+                .encode(Some((cp, cursor.position() as usize + 2))),
             _ => self.data.as_ref().unwrap().encode(None),
         };
         if encoded.is_err() {
@@ -299,12 +414,78 @@ impl ResourceRecord {
         cursor.write_all(data.as_slice())?;
         Ok(cursor)
     }
+
+    /// encode_canonical return the uncompressed wire format in dnssec canonical form
+    /// (rfc4034 section 6.2), the owner name and the domain names embedded in the rdata of
+    /// NS CNAME PTR MX SRV SOA RRSIG are lowercased.
+    pub fn encode_canonical(&self) -> Result<Vec<u8>, DNSProtoErr> {
+        let rdata = self.canonical_rdata()?;
+        let mut result = self.name.to_canonical_binary();
+        result.extend_from_slice(&u16::from(self.qtype).to_be_bytes());
+        result.extend_from_slice(&u16::from(self.qclass).to_be_bytes());
+        result.extend_from_slice(&self.ttl.to_be_bytes());
+        result.extend_from_slice(&(rdata.len() as u16).to_be_bytes());
+        result.extend_from_slice(rdata.as_slice());
+        Ok(result)
+    }
+
+    /// canonical_rdata return the uncompressed rdata in dnssec canonical form, the records
+    /// of a set are sorted by it(rfc4034 section 6.3).
+    pub fn canonical_rdata(&self) -> Result<Vec<u8>, DNSProtoErr> {
+        let data = self
+            .data
+            .as_ref()
+            .ok_or(DNSProtoErr::PacketSerializeError)?;
+        let mut rdata = data.encode(None)?;
+        let lowercased = match self.qtype {
+            DNSType::NS | DNSType::CNAME | DNSType::PTR => lowercase_name(&mut rdata, 0),
+            DNSType::MX => lowercase_name(&mut rdata, 2),
+            DNSType::SRV => lowercase_name(&mut rdata, 6),
+            DNSType::SOA => {
+                lowercase_name(&mut rdata, 0).and_then(|end| lowercase_name(&mut rdata, end))
+            }
+            DNSType::RRSIG => lowercase_name(&mut rdata, 18),
+            _ => Some(0),
+        };
+        if lowercased.is_none() {
+            return Err(DNSProtoErr::PacketSerializeError);
+        }
+        Ok(rdata)
+    }
+}
+
+// lowercase_name lowercase the uncompressed name start from offset of the rdata,
+// return the offset after the name or None if the name is truncated.
+fn lowercase_name(rdata: &mut [u8], mut offset: usize) -> Option<usize> {
+    loop {
+        let length = *rdata.get(offset)? as usize;
+        if length == 0 {
+            return Some(offset + 1);
+        }
+        let end = offset + 1 + length;
+        rdata.get_mut(offset + 1..end)?.make_ascii_lowercase();
+        offset = end;
+    }
+}
+
+/// RecordOrder is the order of the records taken out of a RRSet, chosen by the caller.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecordOrder {
+    /// the order the records are added to the set
+    Stable,
+    /// the stable order rotated left by the offset, answers spread the load this way
+    Rotated(usize),
+    /// sorted by the canonical rdata(rfc4034 section 6.3), zone transfers and dumped
+    /// zone files must not change when the zone does not change
+    Canonical,
 }
 
 #[derive(Debug, Default, Clone)]
 pub struct RRSet {
     content: Vec<ResourceRecord>,
     signatures: Vec<ResourceRecord>,
+    // the smallest ttl of the records, each record keeps the ttl it is loaded with and
+    // is written to the wire with it(a ttl of 0 is kept as well)
     ttl: u32,
 }
 
@@ -324,6 +505,10 @@ impl RRSet {
     pub fn set_ttl(&mut self, ttl: u32) {
         self.ttl = ttl;
     }
+    /// ttl return the smallest ttl of the records in the set.
+    pub fn ttl(&self) -> u32 {
+        self.ttl
+    }
     pub fn clear(&mut self) {
         self.signatures.clear();
         self.signatures.clear();
@@ -334,22 +519,84 @@ impl RRSet {
     pub fn content(&self) -> &Vec<ResourceRecord> {
         self.content.as_ref()
     }
+    pub fn content_mut(&mut self) -> &mut Vec<ResourceRecord> {
+        self.content.as_mut()
+    }
+    pub fn signatures(&self) -> &Vec<ResourceRecord> {
+        self.signatures.as_ref()
+    }
+    /// iter_as return the rdata of the records in the set as the concrete type, records
+    /// with other rdata are skipped.
+    ///
+    /// ```
+    /// use dnsproto::meta::{DNSClass, DNSType, RRSet, ResourceRecord};
+    /// use dnsproto::qtype::DnsTypeMX;
+    ///
+    /// let mut rrset = RRSet::default();
+    /// for (preference, exchange) in vec![(10, "mx1.example.com."), (20, "mx2.example.com.")] {
+    ///     let data = Box::new(DnsTypeMX::new(preference, exchange).unwrap());
+    ///     let rr = ResourceRecord::new("example.com.", DNSType::MX, DNSClass::IN, 300, Some(data));
+    ///     rrset.add(rr.unwrap());
+    /// }
+    /// let preferences: Vec<u16> = rrset.iter_as::<DnsTypeMX>().map(|mx| mx.preference()).collect();
+    /// assert_eq!(preferences, vec![10, 20]);
+    /// ```
+    pub fn iter_as<T: DNSWireFrame + 'static>(&self) -> impl Iterator<Item = &T> {
+        self.content.iter().filter_map(|rr| rr.rdata_as::<T>())
+    }
     pub fn add(&mut self, rr: ResourceRecord) {
-        self.ttl = rr.ttl;
         if rr.qtype == DNSType::RRSIG {
             self.signatures.push(rr);
             return;
         }
+        self.ttl = match self.content.is_empty() {
+            true => rr.ttl,
+            false => self.ttl.min(rr.ttl),
+        };
         self.content.push(rr);
     }
 
-    // Bug found !!!!
-    pub fn to_records(&self) -> Vec<Record> {
-        let mut result = vec![];
-        for item in self.content().iter() {
-            result.push(Record::AnswerRecord(item.clone()))
+    /// with_owner return a copy of the set with the owner of all records replaced, used
+    /// when a wildcard is expanded to the query name. the rdata is copied as is so the
+    /// labels field of the signatures still tells it is a wildcard expansion(rfc4035 5.3.4).
+    pub fn with_owner(&self, owner: &DNSName) -> RRSet {
+        let mut rrset = self.clone();
+        for rr in rrset.content.iter_mut().chain(rrset.signatures.iter_mut()) {
+            rr.set_dname(owner.clone());
         }
-        result
+        rrset
+    }
+
+    /// ordered return the records of the set in the order, the signatures follow the
+    /// records they cover and are never rotated.
+    pub fn ordered(&self, order: RecordOrder) -> Vec<&ResourceRecord> {
+        let mut content: Vec<&ResourceRecord> = self.content.iter().collect();
+        let mut signatures: Vec<&ResourceRecord> = self.signatures.iter().collect();
+        match order {
+            RecordOrder::Stable => {}
+            RecordOrder::Rotated(offset) => {
+                if !content.is_empty() {
+                    let offset = offset % content.len();
+                    content.rotate_left(offset);
+                }
+            }
+            RecordOrder::Canonical => {
+                // records fail to encode are kept at the end in the stable order
+                content.sort_by_cached_key(|rr| rr.canonical_rdata().map_err(|_| ()));
+                signatures.sort_by_cached_key(|rr| rr.canonical_rdata().map_err(|_| ()));
+            }
+        }
+        content.extend(signatures);
+        content
+    }
+
+    /// to_records return the records of the set in the order without the signatures.
+    pub fn to_records(&self, order: RecordOrder) -> Vec<Record> {
+        self.ordered(order)
+            .into_iter()
+            .take(self.content.len())
+            .map(|rr| Record::AnswerRecord(rr.clone()))
+            .collect()
     }
 }
 
@@ -361,6 +608,8 @@ pub enum OpCode {
     Reserved,
     Notify,
     Update,
+    // opcode 6-15 are not assigned
+    Unassigned(u8),
 }
 impl From<u8> for OpCode {
     fn from(opcode: u8) -> Self {
@@ -371,7 +620,7 @@ impl From<u8> for OpCode {
             3 => OpCode::Reserved,
             4 => OpCode::Notify,
             5 => OpCode::Update,
-            _ => OpCode::Reserved,
+            _ => OpCode::Unassigned(opcode & 0x0f),
         }
     }
 }
@@ -385,6 +634,7 @@ impl Into<u8> for OpCode {
             OpCode::Reserved => 3,
             OpCode::Notify => 4,
             OpCode::Update => 5,
+            OpCode::Unassigned(opcode) => opcode & 0x0f,
         }
     }
 }
@@ -450,23 +700,73 @@ use crate::label::Label;
 use crate::message::Record;
 use nom::lib::std::fmt::{Display, Formatter};
 use nom::lib::std::slice::Iter;
-use num_enum::{IntoPrimitive, TryFromPrimitive};
 use otterlib::errors::DNSProtoErr;
 use std::fmt;
+use std::str::FromStr;
+
+/// mnemonic is the presentation name of a type or class, the variant name if not given.
+macro_rules! mnemonic {
+    ($name:ident) => {
+        stringify!($name)
+    };
+    ($name:ident, $mnemonic:literal) => {
+        $mnemonic
+    };
+}
+
+/// dns_classes define the DNSClass enum and the conversions between the class, its code
+/// and its mnemonic, codes without a variant are kept in DNSClass::Unknown.
+macro_rules! dns_classes {
+    ($($name:ident = $code:literal,)*) => {
+        /// https://tools.ietf.org/html/rfc1035#section-3.2.4
+        /// specify the class of the dns record data
+        #[derive(Debug, PartialEq, Copy, Clone, Eq, Hash)]
+        pub enum DNSClass {
+            $($name,)*
+            // a class without mnemonic, written as CLASS1234(rfc3597)
+            Unknown(u16),
+        }
 
-/// https://tools.ietf.org/html/rfc1035#section-3.2.4
-/// specify the class of the dns record data
-#[derive(Debug, PartialEq, Copy, Clone, IntoPrimitive, TryFromPrimitive)]
-#[repr(u16)]
-pub enum DNSClass {
-    #[num_enum(default)]
-    IN = 1,
-    // 1 the Internet
-    CS,
-    // 2 the CSNET class
-    CH,
-    // 3 the CHAOS class
-    HS, // 4 Hesiod
+        impl DNSClass {
+            /// all classes with a mnemonic
+            pub const ALL: &'static [DNSClass] = &[$(DNSClass::$name,)*];
+
+            /// mnemonic return the presentation name, None for unknown classes.
+            pub fn mnemonic(&self) -> Option<&'static str> {
+                match self {
+                    $(DNSClass::$name => Some(stringify!($name)),)*
+                    DNSClass::Unknown(_) => None,
+                }
+            }
+        }
+
+        impl From<u16> for DNSClass {
+            fn from(code: u16) -> Self {
+                match code {
+                    $($code => DNSClass::$name,)*
+                    _ => DNSClass::Unknown(code),
+                }
+            }
+        }
+
+        impl From<DNSClass> for u16 {
+            fn from(class: DNSClass) -> Self {
+                match class {
+                    $(DNSClass::$name => $code,)*
+                    DNSClass::Unknown(code) => code,
+                }
+            }
+        }
+    };
+}
+
+dns_classes! {
+    IN = 1,   // the Internet
+    CS = 2,   // the CSNET class
+    CH = 3,   // the CHAOS class
+    HS = 4,   // Hesiod
+    NONE = 254, // rfc2136: delete the rrset in update
+    ANY = 255,  // rfc1035: any class, also delete all rrsets in update
 }
 
 impl Default for DNSClass {
@@ -474,17 +774,90 @@ impl Default for DNSClass {
         DNSClass::IN
     }
 }
+
+/// unknown classes are written as CLASS1234(rfc3597)
 impl Display for DNSClass {
     fn fmt(&self, formatter: &mut Formatter<'_>) -> fmt::Result {
-        write!(formatter, "{:?}", self)
+        match self.mnemonic() {
+            Some(mnemonic) => formatter.write_str(mnemonic),
+            None => write!(formatter, "CLASS{}", u16::from(*self)),
+        }
     }
 }
 
-#[derive(Debug, PartialEq, Copy, Clone, IntoPrimitive, FromPrimitive, Eq, Hash, EnumString)]
-#[repr(u16)]
-pub enum DNSType {
-    #[num_enum(default)]
-    Unknown = 0,
+impl FromStr for DNSClass {
+    type Err = DNSProtoErr;
+
+    /// from_str parse the class mnemonic(case insensitive) or the CLASS1234 form.
+    fn from_str(class_str: &str) -> Result<Self, Self::Err> {
+        let mnemonic = class_str.to_uppercase();
+        if let Some(class) = DNSClass::ALL
+            .iter()
+            .find(|class| class.mnemonic() == Some(mnemonic.as_str()))
+        {
+            return Ok(*class);
+        }
+        generic_code(class_str, "CLASS")
+            .map(DNSClass::from)
+            .ok_or_else(|| DNSProtoErr::ValidTypeErr(format!("unknown class {}", class_str)))
+    }
+}
+
+/// generic_code parse the code of the rfc3597 form like TYPE1234 or CLASS1234.
+fn generic_code(value: &str, prefix: &str) -> Option<u16> {
+    let head = value.get(..prefix.len())?;
+    let code = &value[prefix.len()..];
+    if !head.eq_ignore_ascii_case(prefix) || !code.bytes().all(|byte| byte.is_ascii_digit()) {
+        return None;
+    }
+    code.parse::<u16>().ok()
+}
+
+/// dns_types define the DNSType enum and the conversions between the type, its code and
+/// its mnemonic, codes without a variant are kept in DNSType::Unknown.
+macro_rules! dns_types {
+    ($($name:ident = $code:literal $(=> $mnemonic:literal)?,)*) => {
+        #[derive(Debug, PartialEq, Copy, Clone, Eq, Hash)]
+        pub enum DNSType {
+            $($name,)*
+            // a type without codec, the type code is kept to echo it back
+            Unknown(u16),
+        }
+
+        impl DNSType {
+            /// all types with a mnemonic
+            pub const ALL: &'static [DNSType] = &[$(DNSType::$name,)*];
+
+            /// mnemonic return the presentation name, None for unknown types.
+            pub fn mnemonic(&self) -> Option<&'static str> {
+                match self {
+                    $(DNSType::$name => Some(mnemonic!($name $(, $mnemonic)?)),)*
+                    DNSType::Unknown(_) => None,
+                }
+            }
+        }
+
+        impl From<u16> for DNSType {
+            fn from(code: u16) -> Self {
+                match code {
+                    $($code => DNSType::$name,)*
+                    _ => DNSType::Unknown(code),
+                }
+            }
+        }
+
+        impl From<DNSType> for u16 {
+            fn from(dtype: DNSType) -> Self {
+                match dtype {
+                    $(DNSType::$name => $code,)*
+                    DNSType::Unknown(code) => code,
+                }
+            }
+        }
+    };
+}
+
+dns_types! {
     A = 1,
     NS = 2,
     CNAME = 5,
@@ -493,6 +866,8 @@ pub enum DNSType {
     HINFO = 13,
     MX = 15,
     TXT = 16,
+    RP = 17,
+    AFSDB = 18,
     AAAA = 28,
     LOC = 29,
     SRV = 33,
@@ -501,11 +876,23 @@ pub enum DNSType {
     RRSIG = 46,
     NSEC = 47,
     DNSKEY = 48,
+    DHCID = 49,
     NSEC3 = 50,
     NSEC3PARAM = 51,
+    TLSA = 52,
+    SMIMEA = 53,
+    CDS = 59,
+    CDNSKEY = 60,
+    OPENPGPKEY = 61,
+    CSYNC = 62,
+    ZONEMD = 63,
+    SPF = 99,
+    EUI48 = 108,
+    EUI64 = 109,
+    URI = 256,
     IXFR = 251,
     AXFR = 252,
-    Any = 255, // Rfc1035: return all records of all types known to the dns server
+    Any = 255 => "ANY", // Rfc1035: return all records of all types known to the dns server
 }
 
 impl Default for DNSType {
@@ -514,63 +901,148 @@ impl Default for DNSType {
     }
 }
 
+/// unknown types are written as TYPE1234(rfc3597)
 impl fmt::Display for DNSType {
     fn fmt(&self, formatter: &mut Formatter<'_>) -> fmt::Result {
-        write!(formatter, "{:?}", self)
+        match self.mnemonic() {
+            Some(mnemonic) => formatter.write_str(mnemonic),
+            None => write!(formatter, "TYPE{}", u16::from(*self)),
+        }
     }
 }
 
-impl DNSType {
-    #[allow(clippy::should_implement_trait)]
-    pub fn from_str(type_str: &str) -> Option<DNSType> {
-        let str = type_str.to_uppercase();
-        match str.as_str() {
-            "A" => Some(DNSType::A),
-            "NS" => Some(DNSType::NS),
-            "CNAME" => Some(DNSType::CNAME),
-            "SOA" => Some(DNSType::SOA),
-            "PTR" => Some(DNSType::PTR),
-            "HINFO" => Some(DNSType::HINFO),
-            "MX" => Some(DNSType::MX),
-            "TXT" => Some(DNSType::TXT),
-            "AAAA" => Some(DNSType::AAAA),
-            "LOC" => Some(DNSType::LOC),
-            "SRV" => Some(DNSType::SRV),
-            "OPT" => Some(DNSType::OPT),
-            "DS" => Some(DNSType::DS),
-            "RRSIG" => Some(DNSType::RRSIG),
-            "NSEC" => Some(DNSType::NSEC),
-            "DNSKEY" => Some(DNSType::DNSKEY),
-            "NSEC3" => Some(DNSType::NSEC3),
-            "NSEC3PARAM" => Some(DNSType::NSEC3PARAM),
-            "AXFR" => Some(DNSType::AXFR),
-            "ANY" => Some(DNSType::Any),
-            _ => None,
+impl FromStr for DNSType {
+    type Err = DNSProtoErr;
+
+    /// from_str parse the type mnemonic(case insensitive) or the TYPE1234 form.
+    fn from_str(type_str: &str) -> Result<Self, Self::Err> {
+        let mnemonic = type_str.to_uppercase();
+        if let Some(dtype) = DNSType::ALL
+            .iter()
+            .find(|dtype| dtype.mnemonic() == Some(mnemonic.as_str()))
+        {
+            return Ok(*dtype);
         }
+        generic_code(type_str, "TYPE")
+            .map(DNSType::from)
+            .ok_or_else(|| DNSProtoErr::ValidTypeErr(format!("unknown type {}", type_str)))
     }
+}
+
+impl DNSType {
+    /// from_u16 return the type of the code, None if the type has no mnemonic.
     pub fn from_u16(number: u16) -> Option<DNSType> {
-        match number {
-            1 => Some(DNSType::A),
-            2 => Some(DNSType::NS),
-            5 => Some(DNSType::CNAME),
-            6 => Some(DNSType::SOA),
-            12 => Some(DNSType::PTR),
-            13 => Some(DNSType::HINFO),
-            15 => Some(DNSType::MX),
-            16 => Some(DNSType::TXT),
-            28 => Some(DNSType::AAAA),
-            29 => Some(DNSType::LOC),
-            33 => Some(DNSType::SRV),
-            41 => Some(DNSType::OPT),
-            43 => Some(DNSType::DS),
-            46 => Some(DNSType::RRSIG),
-            47 => Some(DNSType::NSEC),
-            48 => Some(DNSType::DNSKEY),
-            50 => Some(DNSType::NSEC3),
-            51 => Some(DNSType::NSEC3PARAM),
-            252 => Some(DNSType::AXFR),
-            255 => Some(DNSType::Any),
-            _ => None,
+        match DNSType::from(number) {
+            DNSType::Unknown(_) => None,
+            dtype => Some(dtype),
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_dns_type_round_trip() {
+        for dtype in DNSType::ALL.iter() {
+            let text = dtype.to_string();
+            assert_eq!(Some(text.as_str()), dtype.mnemonic());
+            assert_eq!(text.parse::<DNSType>().unwrap(), *dtype);
+            assert_eq!(text.to_lowercase().parse::<DNSType>().unwrap(), *dtype);
+            assert_eq!(DNSType::from(u16::from(*dtype)), *dtype);
+            // the generic form is accepted for known types too
+            let generic = format!("TYPE{}", u16::from(*dtype));
+            assert_eq!(generic.parse::<DNSType>().unwrap(), *dtype);
+        }
+        assert_eq!(DNSType::Any.to_string(), "ANY");
+        for code in [0u16, 3, 65, 65280, 65535].iter() {
+            let dtype = DNSType::from(*code);
+            assert_eq!(dtype, DNSType::Unknown(*code));
+            assert_eq!(dtype.mnemonic(), None);
+            assert_eq!(dtype.to_string(), format!("TYPE{}", code));
+            assert_eq!(dtype.to_string().parse::<DNSType>().unwrap(), dtype);
+            assert_eq!(DNSType::from_u16(*code), None);
+        }
+        assert_eq!(
+            "type65280".parse::<DNSType>().unwrap(),
+            DNSType::Unknown(65280)
+        );
+        for invalid in [
+            "",
+            "TYPE",
+            "TYPE65536",
+            "TYPE-1",
+            "TYPE+1",
+            "TYPEA",
+            "A1",
+            "CLASS1",
+        ]
+        .iter()
+        {
+            assert!(invalid.parse::<DNSType>().is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_dns_class_round_trip() {
+        for class in DNSClass::ALL.iter() {
+            let text = class.to_string();
+            assert_eq!(Some(text.as_str()), class.mnemonic());
+            assert_eq!(text.parse::<DNSClass>().unwrap(), *class);
+            assert_eq!(text.to_lowercase().parse::<DNSClass>().unwrap(), *class);
+            assert_eq!(DNSClass::from(u16::from(*class)), *class);
+            let generic = format!("CLASS{}", u16::from(*class));
+            assert_eq!(generic.parse::<DNSClass>().unwrap(), *class);
+        }
+        assert_eq!(u16::from(DNSClass::NONE), 254);
+        assert_eq!(u16::from(DNSClass::ANY), 255);
+        for code in [0u16, 5, 253, 65280, 65535].iter() {
+            let class = DNSClass::from(*code);
+            assert_eq!(class, DNSClass::Unknown(*code));
+            assert_eq!(class.to_string(), format!("CLASS{}", code));
+            assert_eq!(class.to_string().parse::<DNSClass>().unwrap(), class);
+        }
+        for invalid in ["", "CLASS", "CLASS65536", "TYPE1", "INET"].iter() {
+            assert!(invalid.parse::<DNSClass>().is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_rrset_record_order() {
+        let mut rrset = RRSet::default();
+        for address in ["192.0.2.3", "192.0.2.1", "192.0.2.2"].iter() {
+            let line = format!("www.example.com. 3600 IN A {}", address);
+            rrset.add(ResourceRecord::from_zone_data(&line, None, None, None, None).unwrap());
+        }
+        let addresses = |order: RecordOrder| -> Vec<String> {
+            rrset
+                .to_records(order)
+                .iter()
+                .map(|record| match record {
+                    Record::AnswerRecord(rr) => rr.get_data().as_ref().unwrap().to_string(),
+                    _ => unreachable!(),
+                })
+                .collect()
+        };
+        assert_eq!(
+            addresses(RecordOrder::Stable),
+            vec!["192.0.2.3", "192.0.2.1", "192.0.2.2"]
+        );
+        assert_eq!(
+            addresses(RecordOrder::Rotated(1)),
+            vec!["192.0.2.1", "192.0.2.2", "192.0.2.3"]
+        );
+        assert_eq!(
+            addresses(RecordOrder::Rotated(5)),
+            vec!["192.0.2.2", "192.0.2.3", "192.0.2.1"]
+        );
+        assert_eq!(
+            addresses(RecordOrder::Canonical),
+            vec!["192.0.2.1", "192.0.2.2", "192.0.2.3"]
+        );
+        assert!(RRSet::default()
+            .to_records(RecordOrder::Rotated(3))
+            .is_empty());
+    }
+}
@@ -13,6 +13,9 @@ impl DnsTypeA {
     pub fn new(ip: &str) -> Result<Self, DNSProtoErr> {
         Ok(DnsTypeA(Ipv4Addr::from_str(ip)?))
     }
+    pub fn address(&self) -> Ipv4Addr {
+        self.0
+    }
     pub fn decode(data: &[u8], _: Option<&[u8]>) -> Result<Self, DNSProtoErr> {
         if data.len() < 4 {
             return Err(DNSProtoErr::PacketParseError);
@@ -13,6 +13,9 @@ impl DnsTypeAAAA {
     pub fn new(ip: &str) -> Result<Self, DNSProtoErr> {
         Ok(DnsTypeAAAA(Ipv6Addr::from_str(ip)?))
     }
+    pub fn address(&self) -> Ipv6Addr {
+        self.0
+    }
     pub fn decode(data: &[u8], _: Option<&[u8]>) -> Result<Self, DNSProtoErr> {
         if data.len() < 16 {
             return Err(DNSProtoErr::PacketParseError);
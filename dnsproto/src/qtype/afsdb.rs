@@ -0,0 +1,121 @@
+use crate::dnsname::{parse_name, DNSName};
+use crate::meta::DNSType;
+use crate::qtype::helper::{not_space, parse_target_name};
+use crate::qtype::{CompressionType, DNSWireFrame};
+use nom::character::complete::{digit1, multispace0};
+use nom::number::complete::be_u16;
+use otterlib::errors::DNSProtoErr;
+use std::any::Any;
+use std::fmt::{self, Formatter};
+use std::str::FromStr;
+
+// https://tools.ietf.org/html/rfc1183#section-1
+// +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+// |                    SUBTYPE                    |
+// +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+// /                   HOSTNAME                    /
+// +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+// the hostname is never compressed on encode(rfc3597 section 4).
+// example.com.  3600  IN  AFSDB  1 afsdb.example.com.
+#[derive(Debug, PartialEq, Clone)]
+pub struct DnsTypeAFSDB {
+    subtype: u16,
+    hostname: DNSName,
+}
+
+named_args!(parse_afsdb<'a>(original: &[u8])<DnsTypeAFSDB>,
+    do_parse!(
+        subtype: be_u16>>
+        hostname: call!(parse_name, original)>>
+        (DnsTypeAFSDB{
+            subtype,
+            hostname,
+        }
+    )
+));
+
+impl DnsTypeAFSDB {
+    pub fn new(subtype: u16, hostname: &str) -> Result<Self, DNSProtoErr> {
+        Ok(DnsTypeAFSDB {
+            subtype,
+            hostname: DNSName::new(hostname, None)?,
+        })
+    }
+    pub fn subtype(&self) -> u16 {
+        self.subtype
+    }
+    pub fn hostname(&self) -> &DNSName {
+        &self.hostname
+    }
+    pub fn decode(data: &[u8], original: Option<&[u8]>) -> Result<Self, DNSProtoErr> {
+        match parse_afsdb(data, original.unwrap_or(&[])) {
+            Ok((_, afsdb)) => Ok(afsdb),
+            Err(_err) => Err(DNSProtoErr::PacketParseError),
+        }
+    }
+    /// from_str parse the subtype and hostname, a relative hostname will be
+    /// qualified with the default original.
+    pub fn from_str(str: &str, default_original: Option<&str>) -> Result<Self, DNSProtoErr> {
+        let (rest, _) = multispace0(str)?;
+        let (rest, subtype) = digit1(rest)?;
+        let subtype = u16::from_str(subtype)?;
+        let (rest, _) = multispace0(rest)?;
+        let (rest, hostname) = not_space(rest)?;
+        Ok(DnsTypeAFSDB {
+            subtype,
+            hostname: parse_target_name(hostname, rest, default_original)?,
+        })
+    }
+}
+
+impl fmt::Display for DnsTypeAFSDB {
+    fn fmt(&self, format: &mut Formatter<'_>) -> fmt::Result {
+        write!(format, "{} {}", self.subtype, self.hostname)
+    }
+}
+
+impl DNSWireFrame for DnsTypeAFSDB {
+    fn get_type(&self) -> DNSType {
+        DNSType::AFSDB
+    }
+
+    fn encode(&self, _: CompressionType) -> Result<Vec<u8>, DNSProtoErr> {
+        let mut data = vec![];
+        data.extend_from_slice(&self.subtype.to_be_bytes());
+        data.extend_from_slice(self.hostname.to_binary(None).as_slice());
+        Ok(data)
+    }
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn clone_box(&self) -> Box<dyn DNSWireFrame> {
+        Box::new(self.clone())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::qtype::afsdb::DnsTypeAFSDB;
+    use crate::qtype::DNSWireFrame;
+
+    #[test]
+    fn afsdb_round_trip() {
+        let afsdb_str = "1 afsdb.example.com.";
+        let afsdb_struct = DnsTypeAFSDB::new(1, "afsdb.example.com.").unwrap();
+        let bin_arr = b"\x00\x01\x05afsdb\x07example\x03com\x00";
+        assert_eq!(
+            DnsTypeAFSDB::from_str(afsdb_str, None).unwrap(),
+            afsdb_struct
+        );
+        assert_eq!(
+            DnsTypeAFSDB::from_str("1 afsdb", Some("example.com.")).unwrap(),
+            afsdb_struct
+        );
+        assert_eq!(afsdb_struct.to_string(), afsdb_str);
+        assert_eq!(DnsTypeAFSDB::decode(bin_arr, None).unwrap(), afsdb_struct);
+        assert_eq!(afsdb_struct.encode(None).unwrap(), bin_arr.to_vec());
+        assert!(DnsTypeAFSDB::from_str("1 192.0.2.1", None).is_err());
+        assert!(DnsTypeAFSDB::decode(&bin_arr[..8], None).is_err());
+    }
+}
@@ -0,0 +1,98 @@
+use crate::meta::DNSType;
+use crate::qtype::dnskey::DnsTypeDNSKEY;
+use crate::qtype::ds::AlgorithemType;
+use crate::qtype::{CompressionType, DNSWireFrame};
+use otterlib::errors::DNSProtoErr;
+use std::any::Any;
+use std::fmt::{self, Formatter};
+
+// https://tools.ietf.org/html/rfc7344#section-3.2
+// the child zone publish CDNSKEY with the same rdata format as DNSKEY, the parent
+// poll it and compute the DS records of the delegation.
+// example.com.  3600  IN  CDNSKEY  257 3 13 mdsswUyr3DPW132mOi8V9xESWE8jTo0dxCjjnopKl+GqJxpVXckHAeF+KkxLbxILfDLUT0rAK9iUzy1L53eKGQ==
+#[derive(Debug, PartialEq, Clone)]
+pub struct DnsTypeCDNSKEY(DnsTypeDNSKEY);
+
+impl DnsTypeCDNSKEY {
+    pub fn new(
+        flags: u16,
+        algorithem_type: AlgorithemType,
+        public_key: String, // base64
+    ) -> Result<Self, DNSProtoErr> {
+        Ok(DnsTypeCDNSKEY(DnsTypeDNSKEY::new(
+            flags,
+            algorithem_type,
+            public_key,
+        )?))
+    }
+    pub fn dnskey(&self) -> &DnsTypeDNSKEY {
+        &self.0
+    }
+    pub fn decode(data: &[u8], original: Option<&[u8]>) -> Result<Self, DNSProtoErr> {
+        DnsTypeDNSKEY::decode(data, original).map(DnsTypeCDNSKEY)
+    }
+    pub fn from_str(str: &str) -> Result<Self, DNSProtoErr> {
+        DnsTypeDNSKEY::from_str(str).map(DnsTypeCDNSKEY)
+    }
+}
+
+impl From<DnsTypeDNSKEY> for DnsTypeCDNSKEY {
+    fn from(dnskey: DnsTypeDNSKEY) -> Self {
+        DnsTypeCDNSKEY(dnskey)
+    }
+}
+
+impl fmt::Display for DnsTypeCDNSKEY {
+    fn fmt(&self, format: &mut Formatter<'_>) -> fmt::Result {
+        self.0.fmt(format)
+    }
+}
+
+impl DNSWireFrame for DnsTypeCDNSKEY {
+    fn get_type(&self) -> DNSType {
+        DNSType::CDNSKEY
+    }
+
+    fn encode(&self, compression: CompressionType) -> Result<Vec<u8>, DNSProtoErr> {
+        self.0.encode(compression)
+    }
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn clone_box(&self) -> Box<dyn DNSWireFrame> {
+        Box::new(self.clone())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::meta::DNSType;
+    use crate::qtype::cdnskey::DnsTypeCDNSKEY;
+    use crate::qtype::ds::AlgorithemType;
+    use crate::qtype::DNSWireFrame;
+
+    #[test]
+    fn cdnskey_round_trip() {
+        let cdnskey_str = "257 3 13 AwEAAQ==";
+        let cdnskey_struct = DnsTypeCDNSKEY::new(
+            257,
+            AlgorithemType::ECDSACurveP256SHA256,
+            "AwEAAQ==".to_owned(),
+        )
+        .unwrap();
+        let bin_arr = [0x01, 0x01, 0x03, 0x0d, 0x03, 0x01, 0x00, 0x01];
+        assert_eq!(
+            DnsTypeCDNSKEY::from_str(cdnskey_str).unwrap(),
+            cdnskey_struct
+        );
+        assert_eq!(cdnskey_struct.to_string(), cdnskey_str);
+        assert_eq!(
+            DnsTypeCDNSKEY::decode(&bin_arr, None).unwrap(),
+            cdnskey_struct
+        );
+        assert_eq!(cdnskey_struct.encode(None).unwrap(), bin_arr.to_vec());
+        assert_eq!(cdnskey_struct.get_type(), DNSType::CDNSKEY);
+        assert_eq!(cdnskey_struct.dnskey().get_type(), DNSType::DNSKEY);
+    }
+}
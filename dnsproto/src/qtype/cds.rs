@@ -0,0 +1,106 @@
+use crate::meta::DNSType;
+use crate::qtype::ds::{AlgorithemType, DigestType, DnsTypeDS};
+use crate::qtype::{CompressionType, DNSWireFrame};
+use otterlib::errors::DNSProtoErr;
+use std::any::Any;
+use std::fmt::{self, Formatter};
+
+// https://tools.ietf.org/html/rfc7344#section-3.1
+// the child zone publish CDS with the same rdata format as DS, the parent
+// poll it to update the DS records of the delegation.
+// example.com.  3600  IN  CDS  30909 8 2 E2D3C916F6DEEAC73294E8268FB5885044A833FC5459588F4A9184CFC41A5766
+#[derive(Debug, PartialEq, Clone)]
+pub struct DnsTypeCDS(DnsTypeDS);
+
+impl DnsTypeCDS {
+    pub fn new(
+        key_tag: u16,
+        algorithm_type: AlgorithemType,
+        digest_type: DigestType,
+        digest: String,
+    ) -> Result<Self, DNSProtoErr> {
+        Ok(DnsTypeCDS(DnsTypeDS::new(
+            key_tag,
+            algorithm_type,
+            digest_type,
+            digest,
+        )?))
+    }
+    pub fn ds(&self) -> &DnsTypeDS {
+        &self.0
+    }
+    pub fn decode(data: &[u8], original: Option<&[u8]>) -> Result<Self, DNSProtoErr> {
+        DnsTypeDS::decode(data, original).map(DnsTypeCDS)
+    }
+    pub fn from_str(str: &str) -> Result<Self, DNSProtoErr> {
+        DnsTypeDS::from_str(str).map(DnsTypeCDS)
+    }
+}
+
+impl From<DnsTypeDS> for DnsTypeCDS {
+    fn from(ds: DnsTypeDS) -> Self {
+        DnsTypeCDS(ds)
+    }
+}
+
+impl fmt::Display for DnsTypeCDS {
+    fn fmt(&self, format: &mut Formatter<'_>) -> fmt::Result {
+        self.0.fmt(format)
+    }
+}
+
+impl DNSWireFrame for DnsTypeCDS {
+    fn get_type(&self) -> DNSType {
+        DNSType::CDS
+    }
+
+    fn encode(&self, compression: CompressionType) -> Result<Vec<u8>, DNSProtoErr> {
+        self.0.encode(compression)
+    }
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn clone_box(&self) -> Box<dyn DNSWireFrame> {
+        Box::new(self.clone())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::meta::DNSType;
+    use crate::qtype::cds::DnsTypeCDS;
+    use crate::qtype::ds::{AlgorithemType, DigestType, DnsTypeDS};
+    use crate::qtype::DNSWireFrame;
+
+    #[test]
+    fn cds_round_trip() {
+        let cds_str = "30909 8 2 E2D3C916F6DEEAC73294E8268FB5885044A833FC5459588F4A9184CFC41A5766";
+        let cds_struct = DnsTypeCDS::new(
+            30909,
+            AlgorithemType::RSASHA256,
+            DigestType::SHA256,
+            "E2D3C916F6DEEAC73294E8268FB5885044A833FC5459588F4A9184CFC41A5766".to_owned(),
+        )
+        .unwrap();
+        let bin_arr = [
+            0x78, 0xbd, 0x08, 0x02, 0xe2, 0xd3, 0xc9, 0x16, 0xf6, 0xde, 0xea, 0xc7, 0x32, 0x94,
+            0xe8, 0x26, 0x8f, 0xb5, 0x88, 0x50, 0x44, 0xa8, 0x33, 0xfc, 0x54, 0x59, 0x58, 0x8f,
+            0x4a, 0x91, 0x84, 0xcf, 0xc4, 0x1a, 0x57, 0x66,
+        ];
+        assert_eq!(DnsTypeCDS::from_str(cds_str).unwrap(), cds_struct);
+        assert_eq!(cds_struct.to_string(), cds_str);
+        assert_eq!(DnsTypeCDS::decode(&bin_arr, None).unwrap(), cds_struct);
+        assert_eq!(cds_struct.encode(None).unwrap(), bin_arr.to_vec());
+        assert_eq!(cds_struct.get_type(), DNSType::CDS);
+        assert_eq!(
+            DnsTypeDS::decode(&bin_arr, None)
+                .unwrap()
+                .encode(None)
+                .unwrap(),
+            cds_struct.encode(None).unwrap(),
+            "cds should be wire identical with ds"
+        );
+        assert_eq!(cds_struct.ds().get_type(), DNSType::DS);
+    }
+}
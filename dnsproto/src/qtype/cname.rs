@@ -1,4 +1,4 @@
-use crate::dnsname::parse_name;
+use crate::dnsname::{parse_name, DNSName};
 use crate::meta::DNSType;
 use crate::qtype::{CompressionType, DNSWireFrame, DnsTypeNS};
 use otterlib::errors::DNSProtoErr;
@@ -15,6 +15,10 @@ impl DnsTypeCNAME {
     pub fn from_str(a_str: &str, default_original: Option<&str>) -> Result<Self, DNSProtoErr> {
         Ok(DnsTypeCNAME(DnsTypeNS::from_str(a_str, default_original)?))
     }
+    /// name return the canonical name the alias points to.
+    pub fn name(&self) -> &DNSName {
+        &self.0.name
+    }
     pub fn decode(data: &[u8], original: Option<&[u8]>) -> Result<Self, DNSProtoErr> {
         match parse_name(data, original.unwrap_or(&[])) {
             Ok((_, name)) => Ok(DnsTypeCNAME(DnsTypeNS { name })),
@@ -47,10 +51,8 @@ impl DNSWireFrame for DnsTypeCNAME {
 }
 #[cfg(test)]
 mod test {
-    use crate::label::Label;
+    use crate::dnsname::{CompressionMap, DNSName};
     use crate::qtype::{DNSWireFrame, DnsTypeCNAME};
-    use std::collections::HashMap;
-    use std::str::FromStr;
     #[test]
     fn test_cname_decode() {
         let non_compression_vec: Vec<u8> = vec![
@@ -74,14 +76,10 @@ mod test {
                 assert!(false);
             }
         }
-        let mut compression_map = HashMap::new();
-        compression_map.insert(
-            vec![
-                Label::from_str("gtld-servers").unwrap(),
-                Label::from_str("net").unwrap(),
-            ],
-            23,
-        );
+        let mut compression_map = CompressionMap::new();
+        DNSName::new("gtld-servers.net.", None)
+            .unwrap()
+            .to_binary(Some((&mut compression_map, 23)));
         let compression_vec: Vec<u8> = vec![1, 102, 192, 23];
         match cname.encode(Some((&mut compression_map, 30))) {
             Ok(ns_data) => assert_eq!(ns_data, compression_vec),
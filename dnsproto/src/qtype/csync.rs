@@ -0,0 +1,155 @@
+use crate::meta::DNSType;
+use crate::qtype::helper::TypeBitMap;
+use crate::qtype::{CompressionType, DNSWireFrame};
+use nom::character::complete::{digit1, multispace0};
+use nom::combinator::rest;
+use nom::number::complete::{be_u16, be_u32};
+use otterlib::errors::DNSProtoErr;
+use std::any::Any;
+use std::fmt::{self, Formatter};
+use std::str::FromStr;
+
+// https://tools.ietf.org/html/rfc7477#section-2.1.1
+// 1 1 1 1 1 1 1 1 1 1 2 2 2 2 2 2 2 2 2 2 3 3
+// 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |                          SOA Serial                           |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |       Flags                   |            Type Bit Map       /
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// /                     Type Bit Map (continued)                  /
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// example.com.  3600  IN  CSYNC  66 3 A NS AAAA
+
+/// the parent can process the csync record without waiting the child soa serial
+pub const CSYNC_IMMEDIATE: u16 = 0x01;
+/// the parent must check the child soa serial is great than the csync serial
+pub const CSYNC_SOAMINIMUM: u16 = 0x02;
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct DnsTypeCSYNC {
+    serial: u32,
+    flags: u16,
+    bitmaps: TypeBitMap,
+}
+
+named!(parse_csync<&[u8], DnsTypeCSYNC>,
+    do_parse!(
+        serial: be_u32>>
+        flags: be_u16>>
+        bitmaps: map_res!(rest, TypeBitMap::decode)>>
+        (DnsTypeCSYNC{
+            serial,
+            flags,
+            bitmaps,
+        }
+    )
+));
+
+impl DnsTypeCSYNC {
+    pub fn new(serial: u32, flags: u16, type_arr: &[DNSType]) -> Self {
+        DnsTypeCSYNC {
+            serial,
+            flags,
+            bitmaps: TypeBitMap::from_types(type_arr),
+        }
+    }
+    pub fn serial(&self) -> u32 {
+        self.serial
+    }
+    pub fn flags(&self) -> u16 {
+        self.flags
+    }
+    pub fn immediate(&self) -> bool {
+        self.flags & CSYNC_IMMEDIATE == CSYNC_IMMEDIATE
+    }
+    pub fn soa_minimum(&self) -> bool {
+        self.flags & CSYNC_SOAMINIMUM == CSYNC_SOAMINIMUM
+    }
+    pub fn bitmaps(&self) -> &TypeBitMap {
+        &self.bitmaps
+    }
+    pub fn decode(data: &[u8], _: Option<&[u8]>) -> Result<Self, DNSProtoErr> {
+        match parse_csync(data) {
+            Ok((_, csync)) => Ok(csync),
+            Err(_err) => Err(DNSProtoErr::PacketParseError),
+        }
+    }
+    // 66 3 A NS AAAA
+    pub fn from_str(str: &str) -> Result<Self, DNSProtoErr> {
+        let (rest, _) = multispace0(str)?;
+        let (rest, serial) = digit1(rest)?;
+        let serial = u32::from_str(serial)?;
+        let (rest, _) = multispace0(rest)?;
+        let (rest, flags) = digit1(rest)?;
+        let flags = u16::from_str(flags)?;
+        Ok(DnsTypeCSYNC {
+            serial,
+            flags,
+            bitmaps: TypeBitMap::from_str(rest)?,
+        })
+    }
+}
+
+impl fmt::Display for DnsTypeCSYNC {
+    fn fmt(&self, format: &mut Formatter<'_>) -> fmt::Result {
+        write!(format, "{} {}", self.serial, self.flags)?;
+        if !self.bitmaps.is_empty() {
+            write!(format, " {}", self.bitmaps)?;
+        }
+        Ok(())
+    }
+}
+
+impl DNSWireFrame for DnsTypeCSYNC {
+    fn get_type(&self) -> DNSType {
+        DNSType::CSYNC
+    }
+
+    fn encode(&self, _: CompressionType) -> Result<Vec<u8>, DNSProtoErr> {
+        let mut data = vec![];
+        data.extend_from_slice(&self.serial.to_be_bytes());
+        data.extend_from_slice(&self.flags.to_be_bytes());
+        data.extend_from_slice(self.bitmaps.encode().as_slice());
+        Ok(data)
+    }
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn clone_box(&self) -> Box<dyn DNSWireFrame> {
+        Box::new(self.clone())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::meta::DNSType;
+    use crate::qtype::csync::DnsTypeCSYNC;
+    use crate::qtype::DNSWireFrame;
+
+    #[test]
+    fn csync_round_trip() {
+        let csync_str = "66 3 A NS AAAA";
+        let csync_struct = DnsTypeCSYNC::new(66, 3, &[DNSType::A, DNSType::NS, DNSType::AAAA]);
+        let bin_arr = [
+            0x00, 0x00, 0x00, 0x42, 0x00, 0x03, 0x00, 0x04, 0x60, 0x00, 0x00, 0x08,
+        ];
+        assert_eq!(DnsTypeCSYNC::from_str(csync_str).unwrap(), csync_struct);
+        assert_eq!(csync_struct.to_string(), csync_str);
+        assert_eq!(DnsTypeCSYNC::decode(&bin_arr, None).unwrap(), csync_struct);
+        assert_eq!(csync_struct.encode(None).unwrap(), bin_arr.to_vec());
+        assert!(csync_struct.immediate());
+        assert!(csync_struct.soa_minimum());
+
+        let empty = DnsTypeCSYNC::from_str("1 0").unwrap();
+        assert!(!empty.immediate());
+        assert_eq!(empty.to_string(), "1 0");
+        assert_eq!(
+            DnsTypeCSYNC::decode(&empty.encode(None).unwrap(), None).unwrap(),
+            empty
+        );
+        assert!(DnsTypeCSYNC::decode(&[0x00, 0x00, 0x00, 0x42, 0x00], None).is_err());
+        assert!(DnsTypeCSYNC::from_str("66 3 A BAD").is_err());
+    }
+}
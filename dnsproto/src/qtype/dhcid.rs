@@ -0,0 +1,112 @@
+use crate::meta::DNSType;
+use crate::qtype::{CompressionType, DNSWireFrame};
+use otterlib::errors::DNSProtoErr;
+use std::any::Any;
+use std::fmt::{self, Formatter};
+
+// identifier type(2 octets), digest type(1 octet) and at least one octet of digest
+const DHCID_MIN_SIZE: usize = 4;
+
+// https://tools.ietf.org/html/rfc4701#section-3.1
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |     Identifier Type Code      |  Digest Type  |               /
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+               /
+// /                            Digest                             /
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// the rdata is presented as a single base64 blob.
+// chi.example.com.  3600  IN  DHCID  ( AAIBY2/AuCccgoJbsaxcQc9TUapptP69lOjxfNuVAA2kjEA= )
+#[derive(Debug, PartialEq, Clone)]
+pub struct DnsTypeDHCID {
+    data: Vec<u8>,
+}
+
+impl DnsTypeDHCID {
+    pub fn new(data: Vec<u8>) -> Result<Self, DNSProtoErr> {
+        if data.len() < DHCID_MIN_SIZE {
+            return Err(DNSProtoErr::GeneralErr(format!(
+                "dhcid rdata size {} is less than {}",
+                data.len(),
+                DHCID_MIN_SIZE
+            )));
+        }
+        Ok(DnsTypeDHCID { data })
+    }
+    pub fn identifier_type(&self) -> u16 {
+        ((self.data[0] as u16) << 8) + self.data[1] as u16
+    }
+    pub fn digest_type(&self) -> u8 {
+        self.data[2]
+    }
+    pub fn digest(&self) -> &[u8] {
+        &self.data[3..]
+    }
+    pub fn decode(data: &[u8], _: Option<&[u8]>) -> Result<Self, DNSProtoErr> {
+        DnsTypeDHCID::new(data.to_vec()).map_err(|_| DNSProtoErr::PacketParseError)
+    }
+    // the base64 blob may be split by spaces
+    pub fn from_str(str: &str) -> Result<Self, DNSProtoErr> {
+        let blob: String = str.split_whitespace().collect();
+        match base64::decode(blob.as_str()) {
+            Ok(data) => DnsTypeDHCID::new(data),
+            Err(err) => Err(DNSProtoErr::ParseDNSFromStrError(format!(
+                "decode dhcid base64 fail: {}",
+                err.to_string()
+            ))),
+        }
+    }
+}
+
+impl fmt::Display for DnsTypeDHCID {
+    fn fmt(&self, format: &mut Formatter<'_>) -> fmt::Result {
+        write!(format, "{}", base64::encode(&self.data))
+    }
+}
+
+impl DNSWireFrame for DnsTypeDHCID {
+    fn get_type(&self) -> DNSType {
+        DNSType::DHCID
+    }
+
+    fn encode(&self, _: CompressionType) -> Result<Vec<u8>, DNSProtoErr> {
+        Ok(self.data.clone())
+    }
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn clone_box(&self) -> Box<dyn DNSWireFrame> {
+        Box::new(self.clone())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::meta::DNSType;
+    use crate::qtype::dhcid::DnsTypeDHCID;
+    use crate::qtype::{decode_dns_data_from_string, DNSWireFrame};
+
+    #[test]
+    fn dhcid_round_trip() {
+        // https://tools.ietf.org/html/rfc4701#section-3.6.1
+        let dhcid_str = "AAIBY2/AuCccgoJbsaxcQc9TUapptP69lOjxfNuVAA2kjEA=";
+        let dhcid_struct = DnsTypeDHCID::from_str(dhcid_str).unwrap();
+        assert_eq!(dhcid_struct.identifier_type(), 2);
+        assert_eq!(dhcid_struct.digest_type(), 1);
+        assert_eq!(dhcid_struct.digest().len(), 32);
+        assert_eq!(dhcid_struct.to_string(), dhcid_str);
+        let bin_arr = dhcid_struct.encode(None).unwrap();
+        assert_eq!(bin_arr.len(), 35);
+        assert_eq!(DnsTypeDHCID::decode(&bin_arr, None).unwrap(), dhcid_struct);
+        assert!(DnsTypeDHCID::decode(&bin_arr[..3], None).is_err());
+
+        let decoded = decode_dns_data_from_string(
+            "( AAIBY2/AuCccgoJbsaxcQc9TUapptP69lOjx fNuVAA2kjEA= )",
+            DNSType::DHCID,
+            None,
+        )
+        .unwrap();
+        assert_eq!(decoded.to_string(), dhcid_str);
+        assert!(DnsTypeDHCID::from_str("AAIBY2/AuCccgoJb!").is_err());
+        assert!(DnsTypeDHCID::from_str("AAIB").is_err());
+    }
+}
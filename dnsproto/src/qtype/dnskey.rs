@@ -73,6 +73,37 @@ impl DnsTypeDNSKEY {
             public_key: public_key.to_vec(),
         }
     }
+    pub fn flags(&self) -> u16 {
+        self.flags
+    }
+    pub fn algorithm(&self) -> u8 {
+        self.algorithem_type as u8
+    }
+    pub fn public_key(&self) -> &[u8] {
+        self.public_key.as_slice()
+    }
+    /// is_zone_key return true when the zone key flag is set, only zone keys can verify
+    /// the rrsig records of the zone(rfc4034 section 2.1.1).
+    pub fn is_zone_key(&self) -> bool {
+        self.flags & 0x0100 != 0
+    }
+    /// key_tag calculate the key tag of the key from the wire format rdata(rfc4034
+    /// appendix B), the rrsig records refer to their signing key by it.
+    pub fn key_tag(&self) -> u16 {
+        let mut rdata = self.flags.to_be_bytes().to_vec();
+        rdata.push(self.protocol_type);
+        rdata.push(self.algorithem_type as u8);
+        rdata.extend_from_slice(self.public_key.as_slice());
+        let mut accumulator: u32 = 0;
+        for (index, byte) in rdata.iter().enumerate() {
+            accumulator += match index & 1 {
+                0 => (*byte as u32) << 8,
+                _ => *byte as u32,
+            };
+        }
+        accumulator += (accumulator >> 16) & 0xffff;
+        (accumulator & 0xffff) as u16
+    }
     pub fn decode(data: &[u8], _: Option<&[u8]>) -> Result<Self, DNSProtoErr> {
         match parse_dnskey(data, data.len()) {
             Ok((_, mx)) => Ok(mx),
@@ -95,7 +126,8 @@ impl DnsTypeDNSKEY {
         let (rest, _) = multispace0(rest)?;
         let (rest, algorithm_type) = digit1(rest)?;
         let algorithm_type = u8::from_str(algorithm_type)?;
-        let (pk, _) = multispace0(rest)?;
+        // the public key may be split by whitespace
+        let pk: String = rest.split_whitespace().collect();
         if protocol_type != 3 {
             return Err(DNSProtoErr::GeneralErr(format!(
                 "unknown protocol number: {} for dnskey",
@@ -234,6 +266,15 @@ mod test {
         }
     }
 
+    #[test]
+    fn dnskey_key_tag() {
+        let keys = get_example_dnskey();
+        assert_eq!(keys[0].0.key_tag(), 38388);
+        assert_eq!(keys[1].0.key_tag(), 57724);
+        assert!(keys[0].0.is_zone_key());
+        assert_eq!(keys[1].0.algorithm(), 8);
+    }
+
     #[test]
     fn dnskey_binary_serialize() {
         for tc in &get_example_dnskey() {
@@ -52,7 +52,7 @@ impl AlgorithemType {
             3 => AlgorithemType::DSA,
             5 => AlgorithemType::RSASHA1,
             6 => AlgorithemType::DSANSEC3SHA1,
-            7 => AlgorithemType::DSA,
+            7 => AlgorithemType::RSASHA1NSEC3SHA1,
             8 => AlgorithemType::RSASHA256,
             10 => AlgorithemType::RSASHA512,
             12 => AlgorithemType::GOST,
@@ -156,12 +156,13 @@ impl DnsTypeDS {
         let (rest, _) = multispace0(rest)?;
         let (rest, digest_type) = digit1(rest)?;
         let digest_type = u8::from_str(digest_type)?;
-        let (digest, _) = multispace0(rest)?;
+        // the digest may be split by whitespace
+        let digest: String = rest.split_whitespace().collect();
         Ok(DnsTypeDS::new_from_raw(
             key_tag,
             algorithm_type,
             digest_type,
-            string_to_hex_u8(digest)?.as_slice(),
+            string_to_hex_u8(digest.as_str())?.as_slice(),
         ))
     }
 }
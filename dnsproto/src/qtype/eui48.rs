@@ -0,0 +1,88 @@
+use crate::meta::DNSType;
+use crate::qtype::helper::{eui_to_string, parse_eui};
+use crate::qtype::{CompressionType, DNSWireFrame};
+use otterlib::errors::DNSProtoErr;
+use std::any::Any;
+use std::convert::TryInto;
+use std::fmt::{self, Formatter};
+
+const EUI48_SIZE: usize = 6;
+
+// https://tools.ietf.org/html/rfc7043#section-3.1
+// +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+// |          EUI-48 Address                       |
+// |                                               |
+// |                                               |
+// +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+// host.example.  3600  IN  EUI48  00-00-5e-00-53-2a
+#[derive(Debug, PartialEq, Clone)]
+pub struct DnsTypeEUI48 {
+    address: [u8; EUI48_SIZE],
+}
+
+impl DnsTypeEUI48 {
+    pub fn new(address: [u8; EUI48_SIZE]) -> Self {
+        DnsTypeEUI48 { address }
+    }
+    pub fn address(&self) -> &[u8] {
+        &self.address
+    }
+    pub fn decode(data: &[u8], _: Option<&[u8]>) -> Result<Self, DNSProtoErr> {
+        match data.try_into() {
+            Ok(address) => Ok(DnsTypeEUI48 { address }),
+            Err(_) => Err(DNSProtoErr::PacketParseError),
+        }
+    }
+    pub fn from_str(str: &str) -> Result<Self, DNSProtoErr> {
+        let address = parse_eui(str, EUI48_SIZE)?;
+        match address.as_slice().try_into() {
+            Ok(address) => Ok(DnsTypeEUI48 { address }),
+            Err(_) => Err(DNSProtoErr::ParseDNSFromStrError(format!(
+                "invalid eui48: {}",
+                str
+            ))),
+        }
+    }
+}
+
+impl fmt::Display for DnsTypeEUI48 {
+    fn fmt(&self, format: &mut Formatter<'_>) -> fmt::Result {
+        write!(format, "{}", eui_to_string(&self.address))
+    }
+}
+
+impl DNSWireFrame for DnsTypeEUI48 {
+    fn get_type(&self) -> DNSType {
+        DNSType::EUI48
+    }
+
+    fn encode(&self, _: CompressionType) -> Result<Vec<u8>, DNSProtoErr> {
+        Ok(self.address.to_vec())
+    }
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn clone_box(&self) -> Box<dyn DNSWireFrame> {
+        Box::new(self.clone())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::qtype::eui48::DnsTypeEUI48;
+    use crate::qtype::DNSWireFrame;
+
+    #[test]
+    fn eui48_round_trip() {
+        let eui_str = "00-00-5e-00-53-2a";
+        let bin_arr = [0x00, 0x00, 0x5e, 0x00, 0x53, 0x2a];
+        let eui_struct = DnsTypeEUI48::new(bin_arr);
+        assert_eq!(DnsTypeEUI48::from_str(eui_str).unwrap(), eui_struct);
+        assert_eq!(eui_struct.to_string(), eui_str);
+        assert_eq!(DnsTypeEUI48::decode(&bin_arr, None).unwrap(), eui_struct);
+        assert_eq!(eui_struct.encode(None).unwrap(), bin_arr.to_vec());
+        assert!(DnsTypeEUI48::decode(&bin_arr[1..], None).is_err());
+        assert!(DnsTypeEUI48::from_str("00-00-5e-ef-10-00-00-2a").is_err());
+    }
+}
@@ -0,0 +1,89 @@
+use crate::meta::DNSType;
+use crate::qtype::helper::{eui_to_string, parse_eui};
+use crate::qtype::{CompressionType, DNSWireFrame};
+use otterlib::errors::DNSProtoErr;
+use std::any::Any;
+use std::convert::TryInto;
+use std::fmt::{self, Formatter};
+
+const EUI64_SIZE: usize = 8;
+
+// https://tools.ietf.org/html/rfc7043#section-4.1
+// +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+// |          EUI-64 Address                       |
+// |                                               |
+// |                                               |
+// |                                               |
+// +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+// host.example.  3600  IN  EUI64  00-00-5e-ef-10-00-00-2a
+#[derive(Debug, PartialEq, Clone)]
+pub struct DnsTypeEUI64 {
+    address: [u8; EUI64_SIZE],
+}
+
+impl DnsTypeEUI64 {
+    pub fn new(address: [u8; EUI64_SIZE]) -> Self {
+        DnsTypeEUI64 { address }
+    }
+    pub fn address(&self) -> &[u8] {
+        &self.address
+    }
+    pub fn decode(data: &[u8], _: Option<&[u8]>) -> Result<Self, DNSProtoErr> {
+        match data.try_into() {
+            Ok(address) => Ok(DnsTypeEUI64 { address }),
+            Err(_) => Err(DNSProtoErr::PacketParseError),
+        }
+    }
+    pub fn from_str(str: &str) -> Result<Self, DNSProtoErr> {
+        let address = parse_eui(str, EUI64_SIZE)?;
+        match address.as_slice().try_into() {
+            Ok(address) => Ok(DnsTypeEUI64 { address }),
+            Err(_) => Err(DNSProtoErr::ParseDNSFromStrError(format!(
+                "invalid eui64: {}",
+                str
+            ))),
+        }
+    }
+}
+
+impl fmt::Display for DnsTypeEUI64 {
+    fn fmt(&self, format: &mut Formatter<'_>) -> fmt::Result {
+        write!(format, "{}", eui_to_string(&self.address))
+    }
+}
+
+impl DNSWireFrame for DnsTypeEUI64 {
+    fn get_type(&self) -> DNSType {
+        DNSType::EUI64
+    }
+
+    fn encode(&self, _: CompressionType) -> Result<Vec<u8>, DNSProtoErr> {
+        Ok(self.address.to_vec())
+    }
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn clone_box(&self) -> Box<dyn DNSWireFrame> {
+        Box::new(self.clone())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::qtype::eui64::DnsTypeEUI64;
+    use crate::qtype::DNSWireFrame;
+
+    #[test]
+    fn eui64_round_trip() {
+        let eui_str = "00-00-5e-ef-10-00-00-2a";
+        let bin_arr = [0x00, 0x00, 0x5e, 0xef, 0x10, 0x00, 0x00, 0x2a];
+        let eui_struct = DnsTypeEUI64::new(bin_arr);
+        assert_eq!(DnsTypeEUI64::from_str(eui_str).unwrap(), eui_struct);
+        assert_eq!(eui_struct.to_string(), eui_str);
+        assert_eq!(DnsTypeEUI64::decode(&bin_arr, None).unwrap(), eui_struct);
+        assert_eq!(eui_struct.encode(None).unwrap(), bin_arr.to_vec());
+        assert!(DnsTypeEUI64::decode(&bin_arr[1..], None).is_err());
+        assert!(DnsTypeEUI64::from_str("00-00-5e-00-53-2a").is_err());
+    }
+}
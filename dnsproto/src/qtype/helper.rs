@@ -1,11 +1,14 @@
 use crate::dnsname::DNSName;
 use crate::meta::DNSType;
 use crate::qtype::ds::DigestType;
+use crate::utils::is_fqdn;
 use itertools::enumerate;
 use nom::bytes::complete::is_not;
 use nom::error::Error;
 use otterlib::errors::DNSProtoErr;
-use std::fmt::Write;
+use std::fmt::{self, Write};
+use std::net::IpAddr;
+use std::str::FromStr;
 
 pub fn not_space(str: &str) -> Result<(&str, &str), DNSProtoErr> {
     match is_not::<_, _, Error<&str>>(" \t\r\n")(str) {
@@ -14,6 +17,49 @@ pub fn not_space(str: &str) -> Result<(&str, &str), DNSProtoErr> {
     }
 }
 
+/// parse the target name of rdata(MX exchange, SRV target), a relative target is qualified
+/// with the default original and `@` stands for the default original itself.
+/// the target must be a domain name, an ip address literal is not allowed.
+pub fn parse_target_name(
+    target: &str,
+    rest: &str,
+    default_original: Option<&str>,
+) -> Result<DNSName, DNSProtoErr> {
+    if !rest.trim().is_empty() {
+        return Err(DNSProtoErr::ParseDNSFromStrError(format!(
+            "unexpected data after target {}: {}",
+            target, rest
+        )));
+    }
+    if target.is_empty() {
+        return Err(DNSProtoErr::ParseDNSFromStrError(
+            "target name is missing".to_owned(),
+        ));
+    }
+    if target.trim_end_matches('.').parse::<IpAddr>().is_ok() {
+        return Err(DNSProtoErr::ParseDNSFromStrError(format!(
+            "target must be a domain name not an ip address: {}",
+            target
+        )));
+    }
+    parse_domain_name(target, default_original)
+}
+
+/// parse_domain_name parse a name embedded in rdata, `@` stands for the default original and
+/// a relative name is qualified with it. a relative name without default original is an error.
+pub fn parse_domain_name(
+    name: &str,
+    default_original: Option<&str>,
+) -> Result<DNSName, DNSProtoErr> {
+    let name = name.trim();
+    match default_original {
+        Some(origin) if name == "@" => DNSName::new(origin, None),
+        Some(_) => DNSName::new(name, default_original),
+        None if name == "@" || !is_fqdn(name) => Err(DNSProtoErr::NoOriginDomainErr),
+        None => DNSName::new(name, None),
+    }
+}
+
 pub fn hex_u8_to_string(input: &[u8]) -> String {
     let mut result = String::with_capacity(2 * input.len());
     for &byte in input {
@@ -23,6 +69,12 @@ pub fn hex_u8_to_string(input: &[u8]) -> String {
 }
 
 pub fn string_to_hex_u8(input: &str) -> Result<Vec<u8>, DNSProtoErr> {
+    if input.len() % 2 != 0 || !input.is_ascii() {
+        return Err(DNSProtoErr::GeneralErr(format!(
+            "ds digest string to hex u8 fail : {}",
+            input
+        )));
+    }
     (0..input.len())
         .step_by(2)
         .map(|i| match u8::from_str_radix(&input[i..i + 2], 16) {
@@ -35,127 +87,168 @@ pub fn string_to_hex_u8(input: &str) -> Result<Vec<u8>, DNSProtoErr> {
         .collect()
 }
 
-pub fn nsec_bitmaps_to_string(input: &[u8]) -> Result<String, DNSProtoErr> {
-    decode_nsec_from_bits(input).map(|result| {
-        result
+/// parse_eui parse the hyphen separated hex address(rfc7043 section 3.2), `00-00-5e-00-53-2a`
+/// for eui48 and `00-00-5e-ef-10-00-00-2a` for eui64, each group must be exactly two hex digits.
+pub fn parse_eui(input: &str, size: usize) -> Result<Vec<u8>, DNSProtoErr> {
+    let err = || DNSProtoErr::ParseDNSFromStrError(format!("invalid eui{}: {}", size * 8, input));
+    let groups: Vec<&str> = input.trim().split('-').collect();
+    if groups.len() != size {
+        return Err(err());
+    }
+    groups
+        .iter()
+        .map(|group| {
+            if group.len() != 2 || !group.chars().all(|c| c.is_ascii_hexdigit()) {
+                return Err(err());
+            }
+            u8::from_str_radix(group, 16).map_err(|_| err())
+        })
+        .collect()
+}
+
+/// eui_to_string format the address as lowercase hyphen separated hex.
+pub fn eui_to_string(input: &[u8]) -> String {
+    input
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect::<Vec<String>>()
+        .join("-")
+}
+
+/// TypeBitMap is the type bit maps field of nsec and nsec3 records(rfc4034 section 4.1.2),
+/// the type codes are kept sorted and unique.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct TypeBitMap {
+    types: Vec<u16>,
+}
+
+impl TypeBitMap {
+    pub fn from_types(types: &[DNSType]) -> TypeBitMap {
+        let codes: Vec<u16> = types.iter().map(|dtype| u16::from(*dtype)).collect();
+        TypeBitMap::from_codes(&codes)
+    }
+
+    pub fn from_codes(codes: &[u16]) -> TypeBitMap {
+        let mut types = codes.to_vec();
+        types.sort_unstable();
+        types.dedup();
+        TypeBitMap { types }
+    }
+
+    /// to_types return the known types in the bitmap, use codes() to get all types.
+    pub fn to_types(&self) -> Vec<DNSType> {
+        self.types
             .iter()
-            .map(|v| {
-                DNSType::from_u16(*v)
-                    .unwrap_or(DNSType::Unknown)
-                    .to_string()
-            })
-            .collect::<Vec<String>>()
-            .join(" ")
-    })
-}
-
-pub fn decode_nsec_from_bits(input: &[u8]) -> Result<Vec<u16>, DNSProtoErr> {
-    let mut result = Vec::new();
-    let message_size = input.len();
-    let mut offset = 0;
-    let mut length: usize;
-    let mut window: u8;
-    let mut last_window = -1;
-    while offset < message_size {
-        if offset + 2 > message_size {
-            return Err(DNSProtoErr::GeneralErr(
-                "nsec block unpack overflow".to_string(),
-            ));
-        }
-        window = input[offset];
-        length = input[offset + 1] as usize;
-        offset += 2;
-        if window as isize <= last_window {
-            return Err(DNSProtoErr::GeneralErr(
-                "nsec block unpack out of order".to_string(),
-            ));
-        }
-        if length == 0 {
-            return Err(DNSProtoErr::GeneralErr("nsec block is empty".to_string()));
-        }
-        if length > 32 {
-            return Err(DNSProtoErr::GeneralErr("nsec block too long".to_string()));
-        }
-        if offset + length > message_size {
-            return Err(DNSProtoErr::GeneralErr(
-                "nsec block unpack overflow".to_string(),
-            ));
-        }
-        for (index, block) in enumerate(input[offset..offset + length].iter()) {
-            let index = index as u16;
-            if block & 0x80 == 0x80 {
-                result.push(window as u16 * 256 + index * 8)
+            .map(|code| DNSType::from(*code))
+            .filter(|dtype| !matches!(dtype, DNSType::Unknown(_)))
+            .collect()
+    }
+
+    pub fn codes(&self) -> &[u16] {
+        self.types.as_slice()
+    }
+
+    pub fn contains(&self, code: u16) -> bool {
+        self.types.binary_search(&code).is_ok()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.types.is_empty()
+    }
+
+    /// encode write one window block(window, length, bitmap) for each 256 types window
+    /// which has at least one type.
+    pub fn encode(&self) -> Vec<u8> {
+        let mut result = vec![];
+        let mut block_start = 0;
+        let mut last_window = None;
+        for code in self.types.iter() {
+            let window = (code >> 8) as u8;
+            let offset = (code & 0xff) as usize;
+            if last_window != Some(window) {
+                block_start = result.len();
+                result.extend_from_slice(&[window, 0]);
+                last_window = Some(window);
             }
-            if block & 0x40 == 0x40 {
-                result.push(window as u16 * 256 + index * 8 + 1)
+            let length = offset / 8 + 1;
+            if length > result[block_start + 1] as usize {
+                result[block_start + 1] = length as u8;
+                result.resize(block_start + 2 + length, 0);
             }
-            if block & 0x20 == 0x20 {
-                result.push(window as u16 * 256 + index * 8 + 2)
+            result[block_start + 2 + offset / 8] |= 0x80 >> (offset % 8);
+        }
+        result
+    }
+
+    pub fn decode(input: &[u8]) -> Result<TypeBitMap, DNSProtoErr> {
+        let mut types = Vec::new();
+        let message_size = input.len();
+        let mut offset = 0;
+        let mut last_window = -1;
+        while offset < message_size {
+            if offset + 2 > message_size {
+                return Err(DNSProtoErr::GeneralErr(
+                    "nsec block unpack overflow".to_string(),
+                ));
             }
-            if block & 0x10 == 0x10 {
-                result.push(window as u16 * 256 + index * 8 + 3)
+            let window = input[offset];
+            let length = input[offset + 1] as usize;
+            offset += 2;
+            if window as isize <= last_window {
+                return Err(DNSProtoErr::GeneralErr(
+                    "nsec block unpack out of order".to_string(),
+                ));
             }
-            if block & 0x8 == 0x8 {
-                result.push(window as u16 * 256 + index * 8 + 4)
+            if length == 0 {
+                return Err(DNSProtoErr::GeneralErr("nsec block is empty".to_string()));
             }
-            if block & 0x4 == 0x4 {
-                result.push(window as u16 * 256 + index * 8 + 5)
+            if length > 32 {
+                return Err(DNSProtoErr::GeneralErr("nsec block too long".to_string()));
             }
-            if block & 0x2 == 0x2 {
-                result.push(window as u16 * 256 + index * 8 + 6)
+            if offset + length > message_size {
+                return Err(DNSProtoErr::GeneralErr(
+                    "nsec block unpack overflow".to_string(),
+                ));
             }
-            if block & 0x1 == 0x1 {
-                result.push(window as u16 * 256 + index * 8 + 7)
+            for (index, block) in enumerate(input[offset..offset + length].iter()) {
+                for bit in 0..8u16 {
+                    if block & (0x80 >> bit) != 0 {
+                        types.push(window as u16 * 256 + index as u16 * 8 + bit);
+                    }
+                }
             }
+            offset += length;
+            last_window = window as isize;
         }
-        offset += length;
-        last_window = window as isize;
+        Ok(TypeBitMap { types })
     }
-    Ok(result)
 }
 
-pub fn encode_nsec_bitmap_from_str(input: &str) -> Result<Vec<u8>, DNSProtoErr> {
-    let mut dtype_items = vec![];
-    for type_item in input.split(" ") {
-        match DNSType::from_str(type_item) {
-            Some(dtype) => dtype_items.push(dtype),
-            _ => return Err(DNSProtoErr::ValidTypeErr(type_item.to_owned())),
+/// parse the presentation format like "A NS SOA RRSIG NSEC DNSKEY TYPE1234"
+impl FromStr for TypeBitMap {
+    type Err = DNSProtoErr;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let mut codes = vec![];
+        for type_item in input.split_whitespace() {
+            let dtype = DNSType::from_str(type_item)
+                .map_err(|_| DNSProtoErr::ValidTypeErr(type_item.to_owned()))?;
+            codes.push(u16::from(dtype));
         }
+        Ok(TypeBitMap::from_codes(&codes))
     }
-    encode_nsec_bitmap_from_types(dtype_items)
 }
 
-pub fn encode_nsec_bitmap_from_types(bitmap: Vec<DNSType>) -> Result<Vec<u8>, DNSProtoErr> {
-    if bitmap.is_empty() {
-        return Ok(vec![]);
-    }
-    let mut offset = 0;
-    let mut last_window = 0u16;
-    let mut last_length = 0u16;
-    let mut result = vec![];
-    for current in bitmap.iter() {
-        let current = *current as u16;
-        let window = current / 256;
-        let length = (current - window * 256) / 8 + 1;
-        if window > last_window && last_length != 0 {
-            offset += last_length + 2;
-            last_length = 0;
-        }
-        if window < last_window || length < last_length {
-            return Err(DNSProtoErr::GeneralErr("nsec bit out of order".to_string()));
-        }
-        let expected_length = (offset + 2 + length) as usize;
-        if expected_length > result.len() {
-            result.resize(expected_length, 0);
-        }
-        result[offset as usize] = window as u8;
-        result[(offset + 1) as usize] = length as u8;
-        result[(offset + 1 + length) as usize] |= 1 << (7 - current % 8);
-        last_length = length;
-        last_window = window;
+/// unknown types are written as TYPE1234(rfc3597)
+impl fmt::Display for TypeBitMap {
+    fn fmt(&self, format: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let types: Vec<String> = self
+            .types
+            .iter()
+            .map(|code| DNSType::from(*code).to_string())
+            .collect();
+        write!(format, "{}", types.join(" "))
     }
-    // offset += last_window + 2;
-    Ok(result)
 }
 
 pub fn hash_dname_for_nsec3(
@@ -184,12 +277,177 @@ pub fn hash_dname_for_nsec3(
     }
 }
 
+/// split a zone file rdata into character-strings (RFC 1035 5.1), quoted strings
+/// may contain spaces, `\X` escapes X and `\DDD` escapes the decimal byte value,
+/// unquoted strings end at whitespace.
+pub fn split_character_bytes(input: &str) -> Result<Vec<Vec<u8>>, DNSProtoErr> {
+    let mut result = vec![];
+    let bytes = input.trim().as_bytes();
+    let mut index = 0;
+    while index < bytes.len() {
+        if bytes[index].is_ascii_whitespace() {
+            index += 1;
+            continue;
+        }
+        let quoted = bytes[index] == b'"';
+        if quoted {
+            index += 1;
+        }
+        let mut current = vec![];
+        let mut closed = !quoted;
+        while index < bytes.len() {
+            let c = bytes[index];
+            index += 1;
+            match c {
+                b'\\' => {
+                    let digits = &bytes[index..bytes.len().min(index + 3)];
+                    if digits.len() == 3 && digits.iter().all(|d| d.is_ascii_digit()) {
+                        let value = digits
+                            .iter()
+                            .fold(0u32, |acc, d| acc * 10 + (d - b'0') as u32);
+                        if value > u8::MAX as u32 {
+                            return Err(DNSProtoErr::ParseDNSFromStrError(format!(
+                                "escape value out of range in {}",
+                                input
+                            )));
+                        }
+                        current.push(value as u8);
+                        index += 3;
+                    } else if index < bytes.len() {
+                        current.push(bytes[index]);
+                        index += 1;
+                    } else {
+                        return Err(DNSProtoErr::ParseDNSFromStrError(format!(
+                            "dangling escape in {}",
+                            input
+                        )));
+                    }
+                }
+                b'"' if quoted => {
+                    closed = true;
+                    break;
+                }
+                c if !quoted && c.is_ascii_whitespace() => break,
+                c => current.push(c),
+            }
+        }
+        if !closed {
+            return Err(DNSProtoErr::ParseDNSFromStrError(format!(
+                "unterminated quoted string in {}",
+                input
+            )));
+        }
+        result.push(current);
+    }
+    Ok(result)
+}
+
+/// split a zone file rdata into utf8 character-strings, see split_character_bytes
+pub fn split_character_strings(input: &str) -> Result<Vec<String>, DNSProtoErr> {
+    split_character_bytes(input)?
+        .into_iter()
+        .map(|bytes| {
+            String::from_utf8(bytes).map_err(|_| {
+                DNSProtoErr::ParseDNSFromStrError(format!("invalid utf8 string in {}", input))
+            })
+        })
+        .collect()
+}
+
+/// encode a single character-string as one length byte followed by the data
+pub fn encode_character_string(input: &str) -> Result<Vec<u8>, DNSProtoErr> {
+    let bytes = input.as_bytes();
+    if bytes.len() > (u8::MAX as usize) {
+        return Err(DNSProtoErr::EncodeTxtLengthTooLongError);
+    }
+    let mut result = Vec::with_capacity(bytes.len() + 1);
+    result.push(bytes.len() as u8);
+    result.extend_from_slice(bytes);
+    Ok(result)
+}
+
+/// decode a single character-string, return the data and the bytes consumed
+pub fn decode_character_bytes(data: &[u8]) -> Result<(&[u8], usize), DNSProtoErr> {
+    if data.is_empty() {
+        return Err(DNSProtoErr::PacketParseError);
+    }
+    let length = data[0] as usize;
+    if data.len() < length + 1 {
+        return Err(DNSProtoErr::PacketParseError);
+    }
+    Ok((&data[1..=length], length + 1))
+}
+
+/// decode a single utf8 character-string, return the string and the bytes consumed
+pub fn decode_character_string(data: &[u8]) -> Result<(String, usize), DNSProtoErr> {
+    let (bytes, consumed) = decode_character_bytes(data)?;
+    match std::str::from_utf8(bytes) {
+        Ok(val) => Ok((val.to_owned(), consumed)),
+        _ => Err(DNSProtoErr::PacketParseError),
+    }
+}
+
+/// quote a character-string for zone file output, escape `"` and `\`
+/// and write the non-printable bytes as `\DDD`.
+pub fn quote_character_bytes(input: &[u8]) -> String {
+    let mut result = String::with_capacity(input.len() + 2);
+    result.push('"');
+    for &c in input {
+        match c {
+            b'"' | b'\\' => {
+                result.push('\\');
+                result.push(c as char);
+            }
+            0x20..=0x7e => result.push(c as char),
+            _ => {
+                let _ = write!(&mut result, "\\{:03}", c);
+            }
+        }
+    }
+    result.push('"');
+    result
+}
+
+/// quote a utf8 character-string for zone file output, see quote_character_bytes
+pub fn quote_character_string(input: &str) -> String {
+    quote_character_bytes(input.as_bytes())
+}
+
 #[cfg(test)]
 mod test {
+    use crate::dnsname::DNSName;
+    use crate::meta::DNSType;
     use crate::qtype::ds::DigestType;
     use crate::qtype::helper::{
-        encode_nsec_bitmap_from_str, hash_dname_for_nsec3, nsec_bitmaps_to_string,
+        decode_character_string, encode_character_string, eui_to_string, hash_dname_for_nsec3,
+        parse_eui, parse_target_name, quote_character_bytes, quote_character_string,
+        split_character_bytes, split_character_strings, string_to_hex_u8, TypeBitMap,
     };
+    use crate::utils::is_fqdn;
+    use rand::Rng;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_string_to_hex_u8() {
+        assert_eq!(string_to_hex_u8("0aFF").unwrap(), vec![0x0a, 0xff]);
+        assert!(string_to_hex_u8("0aF").is_err());
+        assert!(string_to_hex_u8("0中").is_err());
+        assert!(string_to_hex_u8("zz").is_err());
+    }
+
+    #[test]
+    fn test_parse_eui() {
+        let eui48 = parse_eui("00-00-5E-00-53-2a", 6).unwrap();
+        assert_eq!(eui48, vec![0x00, 0x00, 0x5e, 0x00, 0x53, 0x2a]);
+        assert_eq!(eui_to_string(&eui48), "00-00-5e-00-53-2a");
+        assert_eq!(parse_eui("00-00-5e-ef-10-00-00-2a", 8).unwrap().len(), 8);
+        assert!(parse_eui("00-00-5e-00-53-2a", 8).is_err());
+        assert!(parse_eui("00-00-5e-00-53", 6).is_err());
+        assert!(parse_eui("00-00-5e-00-53-2", 6).is_err());
+        assert!(parse_eui("00-00-5e-00-53-+2", 6).is_err());
+        assert!(parse_eui("00:00:5e:00:53:2a", 6).is_err());
+        assert!(parse_eui("0000-5e-00-53-2a", 6).is_err());
+    }
 
     #[test]
     fn test_hash_dname() {
@@ -211,13 +469,121 @@ mod test {
     fn test_encode_decode_nsec_bitmap() {
         let bitmap_binary = vec![0x00, 0x07u8, 0x62, 0x01, 0x80, 0x08, 0x00, 0x02, 0x90];
         let bitmap_string = "A NS SOA MX TXT AAAA RRSIG DNSKEY NSEC3PARAM";
-        let result = nsec_bitmaps_to_string(bitmap_binary.as_slice());
-        assert_eq!(result.is_ok(), true);
-        let result = result.unwrap();
-        assert_eq!(result, bitmap_string);
+        let bitmap = TypeBitMap::decode(bitmap_binary.as_slice()).unwrap();
+        assert_eq!(bitmap.to_string(), bitmap_string);
+        assert_eq!(
+            TypeBitMap::from_str(bitmap_string).unwrap().encode(),
+            bitmap_binary
+        );
+        assert_eq!(
+            TypeBitMap::from_types(&bitmap.to_types()).encode(),
+            bitmap_binary
+        );
+
+        // unordered and duplicated types
+        let bitmap = TypeBitMap::from_types(&[DNSType::NS, DNSType::A, DNSType::NS]);
+        assert_eq!(bitmap.codes(), &[1, 2]);
+        assert!(bitmap.contains(2));
+        assert!(TypeBitMap::from_str("").unwrap().is_empty());
+        assert!(TypeBitMap::from_str("A FOO").is_err());
+        assert!(TypeBitMap::from_str("TYPE65536").is_err());
+
+        // bad window blocks
+        assert!(TypeBitMap::decode(&[0x00]).is_err());
+        assert!(TypeBitMap::decode(&[0x00, 0x00]).is_err());
+        assert!(TypeBitMap::decode(&[0x00, 0x21]).is_err());
+        assert!(TypeBitMap::decode(&[0x00, 0x02, 0x40]).is_err());
+        assert!(TypeBitMap::decode(&[0x01, 0x01, 0x40, 0x00, 0x01, 0x40]).is_err());
+    }
+
+    #[test]
+    fn test_nsec_bitmap_unknown_types() {
+        let bitmap = TypeBitMap::from_str("A TYPE1234 type65535 RRSIG").unwrap();
+        assert_eq!(bitmap.codes(), &[1, 46, 1234, 65535]);
+        assert_eq!(bitmap.to_string(), "A RRSIG TYPE1234 TYPE65535");
+        assert_eq!(bitmap.to_types(), vec![DNSType::A, DNSType::RRSIG]);
+        assert_eq!(
+            bitmap.encode(),
+            vec![
+                0x00, 0x06, 0x40, 0x00, 0x00, 0x00, 0x00, 0x02, // window 0
+                0x04, 0x1b, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+                0, 0, 0x20, // window 4: 1234 = 4 * 256 + 210
+                0xff, 0x20, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+                0, 0, 0, 0, 0, 0, 0, 0x01, // window 255
+            ]
+        );
+        assert_eq!(TypeBitMap::decode(&bitmap.encode()).unwrap(), bitmap);
+    }
+
+    #[test]
+    fn test_nsec_bitmap_round_trip() {
+        let mut rng = rand::thread_rng();
+        for _ in 0..500 {
+            let size = rng.gen_range(0..64);
+            let codes: Vec<u16> = (0..size)
+                .map(|_| match rng.gen_range(0..3) {
+                    0 => rng.gen_range(0..256),
+                    1 => rng.gen_range(0..1024),
+                    _ => rng.gen(),
+                })
+                .collect();
+            let bitmap = TypeBitMap::from_codes(&codes);
+            let decoded = TypeBitMap::decode(&bitmap.encode()).unwrap();
+            assert_eq!(decoded, bitmap, "wire round trip of {:?}", codes);
+            let parsed = TypeBitMap::from_str(&bitmap.to_string()).unwrap();
+            assert_eq!(parsed, bitmap, "text round trip of {:?}", codes);
+        }
+    }
+
+    #[test]
+    fn test_character_strings() {
+        let result = split_character_strings(r#""Intel Xeon" linux "say \"hi\"""#).unwrap();
+        assert_eq!(result, vec!["Intel Xeon", "linux", "say \"hi\""]);
+        assert_eq!(split_character_strings("\"\"").unwrap(), vec![""]);
+        assert!(split_character_strings("\"unterminated").is_err());
+
+        assert_eq!(quote_character_string("say \"hi\""), r#""say \"hi\"""#);
+
+        let encoded = encode_character_string("otter").unwrap();
+        assert_eq!(encoded, vec![5, b'o', b't', b't', b'e', b'r']);
+        assert_eq!(
+            decode_character_string(encoded.as_slice()).unwrap(),
+            ("otter".to_owned(), 6)
+        );
+        assert!(decode_character_string(&[6, b'o']).is_err());
+        assert!(encode_character_string("a".repeat(256).as_str()).is_err());
+
+        let result = split_character_bytes(r#""a\\b" \255\"c "#).unwrap();
+        assert_eq!(result, vec![b"a\\b".to_vec(), vec![255, b'"', b'c']]);
+        assert!(split_character_bytes("\\256").is_err());
+        assert!(split_character_bytes("abc\\").is_err());
+        assert_eq!(quote_character_bytes(&[b'a', 0x0a, 255]), r#""a\010\255""#);
+    }
 
-        let encoded = encode_nsec_bitmap_from_str(bitmap_string);
-        assert_eq!(encoded.is_ok(), true);
-        assert_eq!(encoded.unwrap(), bitmap_binary)
+    #[test]
+    fn test_parse_target_name() {
+        let origin = Some("otter.fun.");
+        assert_eq!(
+            parse_target_name("mail", "", origin).unwrap(),
+            DNSName::new("mail.otter.fun.", None).unwrap()
+        );
+        assert_eq!(
+            parse_target_name("mail.example.com.", " ", origin).unwrap(),
+            DNSName::new("mail.example.com.", None).unwrap()
+        );
+        assert_eq!(
+            parse_target_name("@", "", origin).unwrap(),
+            DNSName::new("otter.fun.", None).unwrap()
+        );
+        assert_eq!(
+            parse_target_name(".", "", origin).unwrap(),
+            DNSName::default()
+        );
+        assert!(parse_target_name("mail", "", None).is_err());
+        assert!(parse_target_name("1.1.1.1", "", origin).is_err());
+        assert!(parse_target_name("1.1.1.1.", "", origin).is_err());
+        assert!(parse_target_name("2001:db8::1", "", origin).is_err());
+        assert!(parse_target_name("mail", " extra", origin).is_err());
+        assert!(parse_target_name("", "", origin).is_err());
     }
 }
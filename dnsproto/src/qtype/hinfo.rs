@@ -0,0 +1,157 @@
+use crate::meta::DNSType;
+use crate::qtype::helper::{
+    decode_character_string, encode_character_string, quote_character_string,
+    split_character_strings,
+};
+use crate::qtype::{CompressionType, DNSWireFrame};
+use otterlib::errors::DNSProtoErr;
+use std::any::Any;
+use std::fmt::{self, Formatter};
+use std::str::FromStr;
+
+// https://tools.ietf.org/html/rfc1035#section-3.3.2
+// +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+// /                      CPU                      /
+// +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+// /                       OS                      /
+// +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+// host.example.com.  3600  IN  HINFO  "Intel Xeon" "Linux 5.4"
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct DnsTypeHINFO {
+    cpu: String,
+    os: String,
+}
+
+impl DnsTypeHINFO {
+    pub fn new(cpu: &str, os: &str) -> Result<Self, DNSProtoErr> {
+        if cpu.len() > (u8::MAX as usize) || os.len() > (u8::MAX as usize) {
+            return Err(DNSProtoErr::EncodeTxtLengthTooLongError);
+        }
+        Ok(DnsTypeHINFO {
+            cpu: cpu.to_owned(),
+            os: os.to_owned(),
+        })
+    }
+    pub fn cpu(&self) -> &str {
+        self.cpu.as_str()
+    }
+    pub fn os(&self) -> &str {
+        self.os.as_str()
+    }
+    pub fn decode(data: &[u8], _: Option<&[u8]>) -> Result<Self, DNSProtoErr> {
+        let (cpu, offset) = decode_character_string(data)?;
+        let (os, length) = decode_character_string(&data[offset..])?;
+        if offset + length != data.len() {
+            return Err(DNSProtoErr::PacketParseError);
+        }
+        DnsTypeHINFO::new(cpu.as_str(), os.as_str())
+    }
+}
+
+impl FromStr for DnsTypeHINFO {
+    type Err = DNSProtoErr;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let strings = split_character_strings(s)?;
+        if strings.len() != 2 {
+            return Err(DNSProtoErr::ParseDNSFromStrError(format!(
+                "hinfo require cpu and os but got: {}",
+                s
+            )));
+        }
+        DnsTypeHINFO::new(strings[0].as_str(), strings[1].as_str())
+    }
+}
+
+impl fmt::Display for DnsTypeHINFO {
+    fn fmt(&self, format: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            format,
+            "{} {}",
+            quote_character_string(self.cpu.as_str()),
+            quote_character_string(self.os.as_str())
+        )
+    }
+}
+
+impl DNSWireFrame for DnsTypeHINFO {
+    fn get_type(&self) -> DNSType {
+        DNSType::HINFO
+    }
+    fn encode(&self, _: CompressionType) -> Result<Vec<u8>, DNSProtoErr> {
+        let mut result = encode_character_string(self.cpu.as_str())?;
+        result.extend(encode_character_string(self.os.as_str())?);
+        Ok(result)
+    }
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+    fn clone_box(&self) -> Box<dyn DNSWireFrame> {
+        Box::new(self.clone())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::dnsname::DNSName;
+    use crate::meta::{DNSClass, DNSType};
+    use crate::qtype::hinfo::DnsTypeHINFO;
+    use crate::qtype::{decode_message_data, DNSWireFrame};
+    use crate::zone::{ZoneReader, ZoneTextReader};
+    use std::str::FromStr;
+
+    #[test]
+    fn test_hinfo_wire_round_trip() {
+        let binary: Vec<u8> = vec![
+            0x0a, 0x49, 0x6e, 0x74, 0x65, 0x6c, 0x20, 0x58, 0x65, 0x6f, 0x6e, 0x09, 0x4c, 0x69,
+            0x6e, 0x75, 0x78, 0x20, 0x35, 0x2e, 0x34,
+        ];
+        let hinfo = DnsTypeHINFO::new("Intel Xeon", "Linux 5.4").unwrap();
+        assert_eq!(hinfo.encode(None).unwrap(), binary);
+        assert_eq!(hinfo.get_type(), DNSType::HINFO);
+
+        let decoded = decode_message_data(binary.as_slice(), &[], DNSType::HINFO).unwrap();
+        assert_eq!(decoded.get_type(), DNSType::HINFO);
+        assert_eq!(
+            decoded.as_any().downcast_ref::<DnsTypeHINFO>(),
+            Some(&hinfo)
+        );
+        assert!(DnsTypeHINFO::decode(&binary[..15], None).is_err());
+        assert!(DnsTypeHINFO::new("a".repeat(256).as_str(), "os").is_err());
+    }
+
+    #[test]
+    fn test_hinfo_from_str() {
+        let hinfo = DnsTypeHINFO::from_str("\"Intel Xeon\" \"Linux 5.4\"").unwrap();
+        assert_eq!(hinfo.cpu(), "Intel Xeon");
+        assert_eq!(hinfo.os(), "Linux 5.4");
+        assert_eq!(hinfo.to_string(), "\"Intel Xeon\" \"Linux 5.4\"");
+
+        let hinfo = DnsTypeHINFO::from_str("PDP-11 UNIX").unwrap();
+        assert_eq!(hinfo.to_string(), "\"PDP-11\" \"UNIX\"");
+
+        assert!(DnsTypeHINFO::from_str("\"Intel Xeon\"").is_err());
+        assert!(DnsTypeHINFO::from_str("a b c").is_err());
+    }
+
+    #[test]
+    fn test_hinfo_zone_loading() {
+        let zone_str = ZoneTextReader::new(
+            "host    3600    IN  HINFO   \"Intel Xeon\" \"Linux 5.4\"   ; host information",
+        );
+        let mut zone = ZoneReader::new(zone_str, Some("example.com.".to_owned()));
+        match zone.next() {
+            Some(Ok(v)) => {
+                assert_eq!(v.name, DNSName::new("host.example.com.", None).unwrap());
+                assert_eq!(v.qclass, DNSClass::IN);
+                assert_eq!(v.qtype, DNSType::HINFO);
+                assert_eq!(v.ttl, 3600);
+                assert_eq!(
+                    (v.data.unwrap().as_ref()).to_string(),
+                    "\"Intel Xeon\" \"Linux 5.4\"".to_owned()
+                );
+            }
+            _ => assert!(false),
+        }
+    }
+}
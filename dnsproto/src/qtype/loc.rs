@@ -51,6 +51,9 @@ impl FromStr for DnsTypeLOC {
         let mut lat = [0.0, 0.0, 0.0];
         let mut current = str;
         let lat_label = loop {
+            if index == lat.len() {
+                return Err(DNSProtoErr::ParseDNSFromStrError(str.to_owned()));
+            }
             let (rest, _) = multispace0(current)?;
             let (rest, lat_val) = double(rest)?;
             lat[index] = lat_val;
@@ -77,6 +80,9 @@ impl FromStr for DnsTypeLOC {
         };
         let mut index = 0;
         let lng_label = loop {
+            if index == lng.len() {
+                return Err(DNSProtoErr::ParseDNSFromStrError(str.to_owned()));
+            }
             let (rest, _) = multispace0(current)?;
             let (rest, lng_val) = double(rest)?;
             lng[index] = lng_val;
@@ -111,6 +117,9 @@ impl FromStr for DnsTypeLOC {
                 "loc record must have altitude infomation".to_owned(),
             ));
         }
+        if val.len() > 4 {
+            return Err(DNSProtoErr::ParseDNSFromStrError(str.to_owned()));
+        }
         let alt_str = val[0];
         let alt = translate_loc_alt_to_u32(alt_str)?;
         let mut additional_u8 = [0, 0, 0];
@@ -213,42 +222,31 @@ impl DnsTypeLOC {
         size
     }
     fn get_lat_and_lng(&self) -> (String, String) {
-        let lat = (self.lat as i64 - (1 << 31)) as f64 / 3600000.0;
-        let remlat = 60.0 * (lat - (lat as i64) as f64);
-
-        let lng = (self.lon as i64 - (1 << 31)) as f64 / 3600000.0;
-        let remlng = 60.0 * (lng - (lng as i64) as f64);
-        let lat_label = match lat > 0.0 {
-            true => "N".to_owned(),
-            false => "S".to_owned(),
-        };
-        let lng_label = match lng > 0.0 {
-            true => "E".to_owned(),
-            false => "W".to_owned(),
-        };
         (
-            format!(
-                "{} {} {} {}",
-                (lat as i64).abs(),
-                (remlat as i64).abs(),
-                ((((remlat - (remlat as i64) as f64) * 60.0) as f64)
-                    .round()
-                    .abs()),
-                lat_label
-            ),
-            format!(
-                "{} {} {} {}",
-                (lng as i64).abs(),
-                (remlng as i64).abs(),
-                ((((remlng - (remlng as i64) as f64) * 60.0) as f64)
-                    .round()
-                    .abs()),
-                lng_label
-            ),
+            format_coordinate(self.lat, ("N", "S")),
+            format_coordinate(self.lon, ("E", "W")),
         )
     }
 }
 
+// format_coordinate format the thousandths of arc seconds from the equator or the prime
+// meridian with integer math, so the seconds keep all the three decimals.
+fn format_coordinate(value: u32, (positive, negative): (&str, &str)) -> String {
+    let (offset, label) = match value >= 1 << 31 {
+        true => (value - (1 << 31), positive),
+        false => ((1 << 31) - value, negative),
+    };
+    let seconds = offset % 60_000;
+    format!(
+        "{} {} {}.{:03} {}",
+        offset / 3_600_000,
+        offset / 60_000 % 60,
+        seconds / 1000,
+        seconds % 1000,
+        label
+    )
+}
+
 impl fmt::Display for DnsTypeLOC {
     fn fmt(&self, format: &mut Formatter<'_>) -> fmt::Result {
         let location = self.get_lat_and_lng();
@@ -305,8 +303,8 @@ fn translate_loc_lat_to_u32(val: [f64; 3], label: &str) -> Result<u32, DNSProtoE
             "loc translate error".to_owned(),
         ));
     }
-    let latitude =
-        (1000.0 * 60.0 * 60.0 * val[0] + (1000.0 * 60.0 * val[1]) + (1000.0 * val[2])) as u32;
+    let latitude = (1000.0 * 60.0 * 60.0 * val[0] + (1000.0 * 60.0 * val[1]) + (1000.0 * val[2]))
+        .round() as u32;
     if latitude > 90 * 1000 * 60 * 60 {
         return Err(DNSProtoErr::ParseDNSFromStrError(
             "loc overflow error".to_owned(),
@@ -327,8 +325,8 @@ fn translate_loc_lng_to_u32(val: [f64; 3], label: &str) -> Result<u32, DNSProtoE
             "loc translate error".to_owned(),
         ));
     }
-    let longitude =
-        ((1000.0 * 60.0 * 60.0 * val[0]) + (1000.0 * 60.0 * val[1]) + (1000.0 * val[2])) as u32;
+    let longitude = ((1000.0 * 60.0 * 60.0 * val[0]) + (1000.0 * 60.0 * val[1]) + (1000.0 * val[2]))
+        .round() as u32;
     if longitude > 180 * 1000 * 60 * 60 {
         return Err(DNSProtoErr::ParseDNSFromStrError(
             "loc overflow error".to_owned(),
@@ -445,6 +443,15 @@ mod test {
             assert_eq!(loc.is_ok(), true);
             assert_eq!(loc.unwrap(), test_case.1)
         }
+        for malformed in [
+            "1 2 3 4 N 5 E 1m",
+            "1 N 2 3 4 5 E 1m",
+            "1 N 2 E 1m 1m 1m 1m 1m",
+        ]
+        .iter()
+        {
+            assert!(DnsTypeLOC::from_str(malformed).is_err(), "{}", malformed);
+        }
     }
 
     #[test]
@@ -466,7 +473,7 @@ mod test {
         let loc = DnsTypeLOC::new(0, 0x33, 0x13, 0x13, 2265864648, 1725418648, 10010700).unwrap();
         assert_eq!(
             loc.get_lat_and_lng(),
-            ("32 53 1 N".to_owned(), "117 14 25 W".to_owned())
+            ("32 53 1.000 N".to_owned(), "117 14 25.000 W".to_owned())
         );
         assert_eq!(loc.get_size(), "30.00m".to_string());
         assert_eq!(loc.get_horizpre(), "10.00m".to_string());
@@ -474,8 +481,15 @@ mod test {
         assert_eq!(loc.get_altitude(), "107.00m".to_string());
         assert_eq!(
             loc.to_string(),
-            "32 53 1 N 117 14 25 W 107.00m 30.00m 10.00m 10.00m"
-        )
+            "32 53 1.000 N 117 14 25.000 W 107.00m 30.00m 10.00m 10.00m"
+        );
+        // the milliseconds of the seconds are kept
+        let loc = DnsTypeLOC::new(0, 0x12, 0x35, 0x13, 2299987176, 1891577364, 9997500).unwrap();
+        assert_eq!(
+            loc.get_lat_and_lng(),
+            ("42 21 43.528 N".to_owned(), "71 5 6.284 W".to_owned())
+        );
+        assert_eq!(DnsTypeLOC::from_str(&loc.to_string()).unwrap(), loc);
     }
 
     #[test]
@@ -1,46 +1,77 @@
 mod a;
 mod aaaa;
+mod afsdb;
+mod cdnskey;
+mod cds;
 mod cname;
+mod csync;
+mod dhcid;
 mod dnskey;
 mod ds;
+mod eui48;
+mod eui64;
 mod helper;
+mod hinfo;
 mod loc;
 mod mx;
 mod ns;
 mod nsec;
 mod nsec3;
+mod openpgpkey;
 mod opt;
 mod ptr;
+mod rp;
 mod rrsig;
+mod smimea;
 mod soa;
+mod spf;
 mod srv;
+mod tlsa;
 mod txt;
+mod uri;
+mod zonemd;
 
-use crate::label::Label;
+use crate::dnsname::CompressionMap;
 use crate::meta::DNSType;
+use crate::utils::remove_parentheses;
 pub use a::DnsTypeA;
 pub use aaaa::DnsTypeAAAA;
+pub use afsdb::DnsTypeAFSDB;
+pub use cdnskey::DnsTypeCDNSKEY;
+pub use cds::DnsTypeCDS;
 pub use cname::DnsTypeCNAME;
+pub use csync::{DnsTypeCSYNC, CSYNC_IMMEDIATE, CSYNC_SOAMINIMUM};
+pub use dhcid::DnsTypeDHCID;
 pub use dnskey::DnsTypeDNSKEY;
 pub use ds::DnsTypeDS;
+pub use eui48::DnsTypeEUI48;
+pub use eui64::DnsTypeEUI64;
+pub use helper::TypeBitMap;
+pub use hinfo::DnsTypeHINFO;
 pub use loc::DnsTypeLOC;
 pub use mx::DnsTypeMX;
-use nom::lib::std::collections::HashMap;
 use nom::lib::std::fmt::Display;
 pub use ns::DnsTypeNS;
 pub use nsec::DnsTypeNSEC;
-pub use opt::DnsTypeOpt;
+pub use openpgpkey::DnsTypeOPENPGPKEY;
+pub use opt::{DnsTypeOpt, EDNSOptionCode};
 use otterlib::errors::DNSProtoErr;
 pub use ptr::DnsTypePTR;
+pub use rp::DnsTypeRP;
 pub use rrsig::DnsTypeRRSIG;
+pub use smimea::DnsTypeSMIMEA;
 pub use soa::DnsTypeSOA;
+pub use spf::DnsTypeSPF;
 pub use srv::DnsTypeSRV;
 use std::any::Any;
 use std::fmt::Debug;
 use std::str::FromStr;
+pub use tlsa::DnsTypeTLSA;
 pub use txt::DnsTypeTXT;
+pub use uri::DnsTypeURI;
+pub use zonemd::{DnsTypeZONEMD, ZONEMD_HASH_SHA384, ZONEMD_HASH_SHA512, ZONEMD_SCHEME_SIMPLE};
 
-type CompressionType<'a> = Option<(&'a mut HashMap<Vec<Label>, usize>, usize)>;
+type CompressionType<'a> = Option<(&'a mut CompressionMap, usize)>;
 
 pub trait DNSWireFrame: Debug + Display {
     fn get_type(&self) -> DNSType;
@@ -59,12 +90,74 @@ impl Clone for Box<dyn DNSWireFrame> {
     }
 }
 
+/// the rdata is serialized as {"type": "MX", "rdata": "10 mail.example.com."}, the rdata
+/// is the presentation format which can be parsed back by decode_dns_data_from_string.
+#[cfg(feature = "serde")]
+impl serde::Serialize for dyn DNSWireFrame {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct;
+        let mut state = serializer.serialize_struct("RData", 2)?;
+        state.serialize_field("type", &self.get_type().to_string())?;
+        state.serialize_field("rdata", &self.to_string())?;
+        state.end()
+    }
+}
+
+#[cfg(feature = "serde")]
+macro_rules! impl_serialize_rdata {
+    ($($dtype:ty),*) => {
+        $(
+            impl serde::Serialize for $dtype {
+                fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                    (self as &dyn DNSWireFrame).serialize(serializer)
+                }
+            }
+        )*
+    };
+}
+
+#[cfg(feature = "serde")]
+impl_serialize_rdata!(
+    DnsTypeA,
+    DnsTypeAAAA,
+    DnsTypeAFSDB,
+    DnsTypeCDNSKEY,
+    DnsTypeCDS,
+    DnsTypeCNAME,
+    DnsTypeCSYNC,
+    DnsTypeDHCID,
+    DnsTypeDNSKEY,
+    DnsTypeDS,
+    DnsTypeEUI48,
+    DnsTypeEUI64,
+    DnsTypeHINFO,
+    DnsTypeLOC,
+    DnsTypeMX,
+    DnsTypeNS,
+    DnsTypeNSEC,
+    nsec3::DnsTypeNSEC3,
+    DnsTypeOPENPGPKEY,
+    DnsTypeOpt,
+    DnsTypePTR,
+    DnsTypeRP,
+    DnsTypeRRSIG,
+    DnsTypeSMIMEA,
+    DnsTypeSOA,
+    DnsTypeSPF,
+    DnsTypeSRV,
+    DnsTypeTLSA,
+    DnsTypeTXT,
+    DnsTypeURI,
+    DnsTypeZONEMD
+);
+
 pub fn decode_message_data<'a>(
     data: &'a [u8],
     original: &'a [u8],
     dtype: DNSType,
 ) -> Result<Box<dyn DNSWireFrame>, DNSProtoErr> {
-    // A NS CNAME MX TXT PTR SOA AAAA LOC SRV OPT DS DNSKEY NSEC
+    // A NS CNAME MX TXT PTR SOA AAAA LOC SRV OPT DS DNSKEY NSEC HINFO CDS CDNSKEY CSYNC ZONEMD
+    // URI RP AFSDB EUI48 EUI64 DHCID TLSA SMIMEA OPENPGPKEY SPF
     match dtype {
         DNSType::A => match DnsTypeA::decode(data, None) {
             Ok(val) => Ok(Box::new(val)),
@@ -126,6 +219,66 @@ pub fn decode_message_data<'a>(
             Ok(val) => Ok(Box::new(val)),
             _ => Err(DNSProtoErr::PacketParseError),
         },
+        DNSType::HINFO => match DnsTypeHINFO::decode(data, None) {
+            Ok(val) => Ok(Box::new(val)),
+            _ => Err(DNSProtoErr::PacketParseError),
+        },
+        DNSType::CDS => match DnsTypeCDS::decode(data, None) {
+            Ok(val) => Ok(Box::new(val)),
+            _ => Err(DNSProtoErr::PacketParseError),
+        },
+        DNSType::CDNSKEY => match DnsTypeCDNSKEY::decode(data, None) {
+            Ok(val) => Ok(Box::new(val)),
+            _ => Err(DNSProtoErr::PacketParseError),
+        },
+        DNSType::CSYNC => match DnsTypeCSYNC::decode(data, None) {
+            Ok(val) => Ok(Box::new(val)),
+            _ => Err(DNSProtoErr::PacketParseError),
+        },
+        DNSType::ZONEMD => match DnsTypeZONEMD::decode(data, None) {
+            Ok(val) => Ok(Box::new(val)),
+            _ => Err(DNSProtoErr::PacketParseError),
+        },
+        DNSType::URI => match DnsTypeURI::decode(data, None) {
+            Ok(val) => Ok(Box::new(val)),
+            _ => Err(DNSProtoErr::PacketParseError),
+        },
+        DNSType::RP => match DnsTypeRP::decode(data, Some(original)) {
+            Ok(val) => Ok(Box::new(val)),
+            _ => Err(DNSProtoErr::PacketParseError),
+        },
+        DNSType::AFSDB => match DnsTypeAFSDB::decode(data, Some(original)) {
+            Ok(val) => Ok(Box::new(val)),
+            _ => Err(DNSProtoErr::PacketParseError),
+        },
+        DNSType::EUI48 => match DnsTypeEUI48::decode(data, None) {
+            Ok(val) => Ok(Box::new(val)),
+            _ => Err(DNSProtoErr::PacketParseError),
+        },
+        DNSType::EUI64 => match DnsTypeEUI64::decode(data, None) {
+            Ok(val) => Ok(Box::new(val)),
+            _ => Err(DNSProtoErr::PacketParseError),
+        },
+        DNSType::DHCID => match DnsTypeDHCID::decode(data, None) {
+            Ok(val) => Ok(Box::new(val)),
+            _ => Err(DNSProtoErr::PacketParseError),
+        },
+        DNSType::TLSA => match DnsTypeTLSA::decode(data, None) {
+            Ok(val) => Ok(Box::new(val)),
+            _ => Err(DNSProtoErr::PacketParseError),
+        },
+        DNSType::SMIMEA => match DnsTypeSMIMEA::decode(data, None) {
+            Ok(val) => Ok(Box::new(val)),
+            _ => Err(DNSProtoErr::PacketParseError),
+        },
+        DNSType::OPENPGPKEY => match DnsTypeOPENPGPKEY::decode(data, None) {
+            Ok(val) => Ok(Box::new(val)),
+            _ => Err(DNSProtoErr::PacketParseError),
+        },
+        DNSType::SPF => match DnsTypeSPF::decode(data, None) {
+            Ok(val) => Ok(Box::new(val)),
+            _ => Err(DNSProtoErr::PacketParseError),
+        },
         _ => Err(DNSProtoErr::UnImplementedError(format!(
             "dns type {} unknown",
             dtype
@@ -138,9 +291,11 @@ pub fn decode_dns_data_from_string(
     dtype: DNSType,
     default_original: Option<&str>,
 ) -> Result<Box<dyn DNSWireFrame>, DNSProtoErr> {
-    // A NS CNAME MX TXT PTR SOA AAAA LOC SRV OPT[unimpl] DS DNSKEY NSEC
-    let original_string = original_string.replace(|c| c == '(' || c == ')', "");
-    let original_string = original_string.trim_matches(|c| c == ' ' || c == '\"');
+    // A NS CNAME MX TXT PTR SOA AAAA LOC SRV OPT[unimpl] DS DNSKEY NSEC HINFO CDS CDNSKEY CSYNC ZONEMD
+    // URI RP AFSDB EUI48 EUI64 DHCID TLSA SMIMEA OPENPGPKEY SPF
+    let stripped = remove_parentheses(original_string);
+    // character-strings(TXT SPF HINFO URI) keep their quotes
+    let original_string = stripped.trim_matches(|c| c == ' ' || c == '\"');
     match dtype {
         DNSType::A => match DnsTypeA::from_str(original_string) {
             Ok(dtype) => Ok(Box::new(dtype)),
@@ -158,7 +313,7 @@ pub fn decode_dns_data_from_string(
             Ok(dtype) => Ok(Box::new(dtype)),
             Err(err) => Err(err),
         },
-        DNSType::TXT => match DnsTypeTXT::from_str(original_string) {
+        DNSType::TXT => match DnsTypeTXT::from_str(stripped.trim()) {
             Ok(dtype) => Ok(Box::new(dtype)),
             Err(err) => Err(err),
         },
@@ -182,7 +337,7 @@ pub fn decode_dns_data_from_string(
             Ok(dtype) => Ok(Box::new(dtype)),
             Err(err) => Err(err),
         },
-        DNSType::RRSIG => match DnsTypeRRSIG::from_str(original_string) {
+        DNSType::RRSIG => match DnsTypeRRSIG::from_str(original_string, default_original) {
             Ok(dtype) => Ok(Box::new(dtype)),
             Err(err) => Err(err),
         },
@@ -198,9 +353,151 @@ pub fn decode_dns_data_from_string(
             Ok(dtype) => Ok(Box::new(dtype)),
             Err(err) => Err(err),
         },
+        DNSType::HINFO => match DnsTypeHINFO::from_str(stripped.trim()) {
+            Ok(dtype) => Ok(Box::new(dtype)),
+            Err(err) => Err(err),
+        },
+        DNSType::CDS => match DnsTypeCDS::from_str(original_string) {
+            Ok(dtype) => Ok(Box::new(dtype)),
+            Err(err) => Err(err),
+        },
+        DNSType::CDNSKEY => match DnsTypeCDNSKEY::from_str(original_string) {
+            Ok(dtype) => Ok(Box::new(dtype)),
+            Err(err) => Err(err),
+        },
+        DNSType::CSYNC => match DnsTypeCSYNC::from_str(original_string) {
+            Ok(dtype) => Ok(Box::new(dtype)),
+            Err(err) => Err(err),
+        },
+        DNSType::ZONEMD => match DnsTypeZONEMD::from_str(original_string) {
+            Ok(dtype) => Ok(Box::new(dtype)),
+            Err(err) => Err(err),
+        },
+        DNSType::URI => match DnsTypeURI::from_str(stripped.trim()) {
+            Ok(dtype) => Ok(Box::new(dtype)),
+            Err(err) => Err(err),
+        },
+        DNSType::RP => match DnsTypeRP::from_str(original_string, default_original) {
+            Ok(dtype) => Ok(Box::new(dtype)),
+            Err(err) => Err(err),
+        },
+        DNSType::AFSDB => match DnsTypeAFSDB::from_str(original_string, default_original) {
+            Ok(dtype) => Ok(Box::new(dtype)),
+            Err(err) => Err(err),
+        },
+        DNSType::EUI48 => match DnsTypeEUI48::from_str(original_string) {
+            Ok(dtype) => Ok(Box::new(dtype)),
+            Err(err) => Err(err),
+        },
+        DNSType::EUI64 => match DnsTypeEUI64::from_str(original_string) {
+            Ok(dtype) => Ok(Box::new(dtype)),
+            Err(err) => Err(err),
+        },
+        DNSType::DHCID => match DnsTypeDHCID::from_str(original_string) {
+            Ok(dtype) => Ok(Box::new(dtype)),
+            Err(err) => Err(err),
+        },
+        DNSType::TLSA => match DnsTypeTLSA::from_str(original_string) {
+            Ok(dtype) => Ok(Box::new(dtype)),
+            Err(err) => Err(err),
+        },
+        DNSType::SMIMEA => match DnsTypeSMIMEA::from_str(original_string) {
+            Ok(dtype) => Ok(Box::new(dtype)),
+            Err(err) => Err(err),
+        },
+        DNSType::OPENPGPKEY => match DnsTypeOPENPGPKEY::from_str(original_string) {
+            Ok(dtype) => Ok(Box::new(dtype)),
+            Err(err) => Err(err),
+        },
+        DNSType::SPF => match DnsTypeSPF::from_str(stripped.trim()) {
+            Ok(dtype) => Ok(Box::new(dtype)),
+            Err(err) => Err(err),
+        },
         _ => Err(DNSProtoErr::UnImplementedError(format!(
             "dns type {} unknown",
             dtype
         ))),
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // presentation samples of every type parsed from string, long values are split by
+    // whitespace like zone files do
+    const SAMPLES: &[(DNSType, &str)] = &[
+            (DNSType::A, "192.0.2.1"),
+            (DNSType::AAAA, "2001:db8::1"),
+            (DNSType::NS, "ns1.example.com."),
+            (DNSType::CNAME, "web.example.com."),
+            (DNSType::MX, "10 mail.example.com."),
+            (DNSType::TXT, "\"v=spf1 -all\" \"say \\\"hi\\\"\""),
+            (DNSType::PTR, "host.example.com."),
+            (DNSType::SOA, "ns1.example.com. admin.example.com. (2021010101 7200 3600 1209600 300)"),
+            (DNSType::LOC, "42 21 43.528 N 71 5 6.284 W -25.00m 1m 3000m 10m"),
+            (DNSType::SRV, "10 60 5060 sip.example.com."),
+            (DNSType::RRSIG, "A 13 2 3600 20210422050000 20210409040000 14631 example.com. AHPsJm1PtK2/PRBKpxT58RAy/Yq22IKfxAtSyG9khdeS jMLr1GRvP+PzdL4R2QW/S+J1+obziJ2CqffcXkHdMg=="),
+            (DNSType::DS, "60485 7 1 42B453BB4F89BAD341C8 C6C1F99867617385E075"),
+            (DNSType::DNSKEY, "257 3 13 gzX6VtSHVi3iSPR778cnQzNAUd3/zCwJJ19mVFSZAxdZ R0XuF8CPeYzX3OC6gVXc2hT2OYwdFUURZSChMwF8CQ=="),
+            (DNSType::NSEC, "host.example.com. A MX RRSIG NSEC TYPE1234"),
+            (DNSType::HINFO, "\"Intel Xeon\" \"Linux 5.4\""),
+            (DNSType::CDS, "60485 13 1 42B453BB4F89BAD341C8C6C1F99867617385E075"),
+            (DNSType::CDNSKEY, "257 3 13 gzX6VtSHVi3iSPR778cnQzNAUd3/zCwJJ19mVFSZAxdZR0XuF8CPeYzX3OC6gVXc2hT2OYwdFUURZSChMwF8CQ=="),
+            (DNSType::CSYNC, "66 3 A NS AAAA"),
+            (DNSType::ZONEMD, "2018031500 1 1 B1295B2239CD16AB9804E3D5E55DB31EE5A6A1F098916611 ADCCDAF2D2F27B4E58600C8337A1C8310C6BCDF223856328"),
+            (DNSType::URI, "10 1 \"ftp://ftp1.example.com/public\""),
+            (DNSType::RP, "mbox.example.com. txt.example.com."),
+            (DNSType::AFSDB, "1 afsdb.example.com."),
+            (DNSType::EUI48, "00-00-5e-00-53-2a"),
+            (DNSType::EUI64, "00-00-5e-ef-10-00-00-2a"),
+            (DNSType::DHCID, "AAIBY2/AuCccgoJbsaxcQc9TUapptP69lOjxfNuVAA2kjEA="),
+            (DNSType::TLSA, "3 1 1 06298432E8066B29E2223BCC23AA9504B56AE508FABF3435508869B9C3190E22"),
+            (DNSType::SMIMEA, "3 1 1 06298432E8066B29E2223BCC23AA9504B56AE508FABF3435508869B9C3190E22"),
+            (DNSType::OPENPGPKEY, "EidkPjlKzq51Tr4YUcBDuT0h2v2i/E5I /DFqsqGeg8VTKyHNYC6OTnce1WPN0nOR"),
+            (DNSType::SPF, "\"v=spf1 -all\""),
+    ];
+
+    #[test]
+    fn test_display_from_str_roundtrip() {
+        for (dtype, sample) in SAMPLES.iter() {
+            let parsed = decode_dns_data_from_string(sample, *dtype, None)
+                .unwrap_or_else(|err| panic!("parse {} {}: {:?}", dtype, sample, err));
+            assert_eq!(parsed.get_type(), *dtype);
+            let display = parsed.to_string();
+            let reparsed = decode_dns_data_from_string(&display, *dtype, None)
+                .unwrap_or_else(|err| panic!("parse {} {}: {:?}", dtype, display, err));
+            assert_eq!(reparsed.to_string(), display, "{}", dtype);
+            assert_eq!(
+                reparsed.encode(None).unwrap(),
+                parsed.encode(None).unwrap(),
+                "{} {}",
+                dtype,
+                display
+            );
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serialize_rdata() {
+        let mx = DnsTypeMX::new(10, "mail.example.com.").unwrap();
+        assert_eq!(
+            serde_json::to_string(&mx).unwrap(),
+            r#"{"type":"MX","rdata":"10 mail.example.com."}"#
+        );
+        for (dtype, sample) in SAMPLES.iter() {
+            let parsed = decode_dns_data_from_string(sample, *dtype, None).unwrap();
+            let value = serde_json::to_value(parsed.as_ref()).unwrap();
+            assert_eq!(value["type"], dtype.to_string());
+            let rdata = value["rdata"].as_str().unwrap();
+            assert_eq!(
+                decode_dns_data_from_string(rdata, *dtype, None)
+                    .unwrap()
+                    .encode(None)
+                    .unwrap(),
+                parsed.encode(None).unwrap()
+            );
+        }
+    }
+}
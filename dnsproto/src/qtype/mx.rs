@@ -1,6 +1,6 @@
 use crate::dnsname::{parse_name, DNSName};
 use crate::meta::DNSType;
-use crate::qtype::helper::not_space;
+use crate::qtype::helper::{not_space, parse_target_name};
 use crate::qtype::{CompressionType, DNSWireFrame};
 use nom::character::complete::{digit1, multispace0};
 use nom::number::complete::be_u16;
@@ -30,14 +30,27 @@ impl DnsTypeMX {
         })
     }
 
+    /// preference return the preference, lower values are preferred
+    pub fn preference(&self) -> u16 {
+        self.priority
+    }
+
+    /// exchange return the mail exchange target
+    pub fn exchange(&self) -> &DNSName {
+        &self.exchange
+    }
+
+    /// from_str parse the preference and exchange, a relative exchange will be
+    /// qualified with the default original.
     pub fn from_str(str: &str, default_original: Option<&str>) -> Result<Self, DNSProtoErr> {
-        let (rest, priority) = digit1(str)?;
+        let (rest, _) = multispace0(str)?;
+        let (rest, priority) = digit1(rest)?;
         let priority = u16::from_str(priority)?;
         let (rest, _) = multispace0(rest)?;
-        let (_, exchange) = not_space(rest)?;
+        let (rest, exchange) = not_space(rest)?;
         Ok(DnsTypeMX {
             priority,
-            exchange: DNSName::new(exchange, default_original)?,
+            exchange: parse_target_name(exchange, rest, default_original)?,
         })
     }
     pub fn decode(data: &[u8], original: Option<&[u8]>) -> Result<Self, DNSProtoErr> {
@@ -50,7 +63,7 @@ impl DnsTypeMX {
 
 impl fmt::Display for DnsTypeMX {
     fn fmt(&self, format: &mut Formatter<'_>) -> fmt::Result {
-        write!(format, "{} {}", self.priority, self.exchange.to_string())
+        write!(format, "{} {}", self.priority, self.exchange)
     }
 }
 impl DNSWireFrame for DnsTypeMX {
@@ -63,7 +76,8 @@ impl DNSWireFrame for DnsTypeMX {
         data.extend_from_slice(&self.priority.to_be_bytes()[..]);
         match compression {
             Some((compression_map, size)) => {
-                let exchange = self.exchange.to_binary(Some((compression_map, size)));
+                // the exchange name starts after the 2 bytes preference
+                let exchange = self.exchange.to_binary(Some((compression_map, size + 2)));
                 data.extend_from_slice(exchange.as_slice());
             }
             _ => {
@@ -98,9 +112,8 @@ named_args!(parse_mx<'a>(original: &[u8])<DnsTypeMX>,
 
 #[cfg(test)]
 mod test {
-    use crate::label::Label;
+    use crate::dnsname::{CompressionMap, DNSName};
     use crate::qtype::{DNSWireFrame, DnsTypeMX};
-    use std::collections::HashMap;
     use std::str::FromStr;
 
     #[test]
@@ -126,8 +139,10 @@ mod test {
                 .unwrap(),
             &bin_arr,
         );
-        let mut compression_map = HashMap::new();
-        compression_map.insert(vec![Label::from_str("com").unwrap()], 12usize);
+        let mut compression_map = CompressionMap::new();
+        DNSName::new("com.", None)
+            .unwrap()
+            .to_binary(Some((&mut compression_map, 12)));
         let compressed_bin = [
             0x00u8, 0x0f, 0x02, 0x6d, 0x78, 0x01, 0x6e, 0x06, 0x73, 0x68, 0x69, 0x66, 0x65, 0x6e,
             0xc0, 0x0c,
@@ -140,4 +155,23 @@ mod test {
             &compressed_bin,
         );
     }
+
+    #[test]
+    fn test_dns_type_mx_from_str() {
+        let mx = DnsTypeMX::from_str("10   mail", Some("otter.fun.")).unwrap();
+        assert_eq!(mx, DnsTypeMX::new(10, "mail.otter.fun.").unwrap());
+        assert_eq!(mx.to_string(), "10 mail.otter.fun.");
+        let mx = DnsTypeMX::from_str("0 .", None).unwrap();
+        assert_eq!(mx.to_string(), "0 .");
+        assert_eq!(
+            DnsTypeMX::from_str(mx.to_string().as_str(), None).unwrap(),
+            mx
+        );
+
+        assert!(DnsTypeMX::from_str("mail.otter.fun.", None).is_err());
+        assert!(DnsTypeMX::from_str("65536 mail.otter.fun.", None).is_err());
+        assert!(DnsTypeMX::from_str("10 1.1.1.1", Some("otter.fun.")).is_err());
+        assert!(DnsTypeMX::from_str("10 mail.otter.fun. extra", None).is_err());
+        assert!(DnsTypeMX::from_str("10 mail", None).is_err());
+    }
 }
@@ -1,5 +1,6 @@
 use crate::dnsname::{parse_name, DNSName};
 use crate::meta::DNSType;
+use crate::qtype::helper::parse_domain_name;
 use crate::qtype::{CompressionType, DNSWireFrame};
 use otterlib::errors::DNSProtoErr;
 use std::any::Any;
@@ -16,9 +17,13 @@ impl DnsTypeNS {
             name: DNSName::new(name, None)?,
         })
     }
+    /// name return the name server target
+    pub fn name(&self) -> &DNSName {
+        &self.name
+    }
     pub fn from_str(a_str: &str, default_original: Option<&str>) -> Result<Self, DNSProtoErr> {
         Ok(DnsTypeNS {
-            name: DNSName::new(a_str, default_original)?,
+            name: parse_domain_name(a_str, default_original)?,
         })
     }
     pub fn decode(data: &[u8], original: Option<&[u8]>) -> Result<Self, DNSProtoErr> {
@@ -62,11 +67,8 @@ impl DNSWireFrame for DnsTypeNS {
 }
 #[cfg(test)]
 mod test {
-    use crate::dnsname::DNSName;
-    use crate::label::Label;
+    use crate::dnsname::{CompressionMap, DNSName};
     use crate::qtype::{DNSWireFrame, DnsTypeNS};
-    use std::collections::HashMap;
-    use std::str::FromStr;
 
     #[test]
     fn test_ns_encode() {
@@ -83,14 +85,10 @@ mod test {
                 assert!(false);
             }
         }
-        let mut compression_map = HashMap::new();
-        compression_map.insert(
-            vec![
-                Label::from_str("gtld-servers").unwrap(),
-                Label::from_str("net").unwrap(),
-            ],
-            23,
-        );
+        let mut compression_map = CompressionMap::new();
+        DNSName::new("gtld-servers.net.", None)
+            .unwrap()
+            .to_binary(Some((&mut compression_map, 23)));
         let compression_vec: Vec<u8> = vec![1, 102, 192, 23];
         match ns.encode(Some((&mut compression_map, 30))) {
             Ok(ns_data) => assert_eq!(ns_data, compression_vec),
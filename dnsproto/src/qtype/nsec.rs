@@ -9,9 +9,7 @@
 // +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
 use crate::dnsname::{parse_name, DNSName};
 use crate::meta::DNSType;
-use crate::qtype::helper::{
-    encode_nsec_bitmap_from_str, encode_nsec_bitmap_from_types, nsec_bitmaps_to_string,
-};
+use crate::qtype::helper::{parse_domain_name, TypeBitMap};
 use crate::qtype::soa::is_not_space;
 use crate::qtype::{CompressionType, DNSWireFrame};
 use nom::bytes::complete::take_while;
@@ -20,20 +18,27 @@ use nom::combinator::rest;
 use otterlib::errors::DNSProtoErr;
 use std::any::Any;
 use std::fmt::{self, Formatter};
+use std::str::FromStr;
 
 #[derive(Debug, PartialEq, Clone)]
 pub struct DnsTypeNSEC {
     next_domain: DNSName,
-    bitmaps: Vec<u8>,
+    bitmaps: TypeBitMap,
 }
 
 impl DnsTypeNSEC {
     pub fn new(domain: &str, type_arr: Vec<DNSType>) -> Result<Self, DNSProtoErr> {
         Ok(DnsTypeNSEC {
             next_domain: DNSName::new(domain, None)?,
-            bitmaps: encode_nsec_bitmap_from_types(type_arr)?,
+            bitmaps: TypeBitMap::from_types(&type_arr),
         })
     }
+    pub fn next_domain(&self) -> &DNSName {
+        &self.next_domain
+    }
+    pub fn bitmaps(&self) -> &TypeBitMap {
+        &self.bitmaps
+    }
     pub fn decode(data: &[u8], original: Option<&[u8]>) -> Result<Self, DNSProtoErr> {
         match parse_nsec(data, original.unwrap_or(&[])) {
             Ok((_, nsec)) => Ok(nsec),
@@ -44,9 +49,9 @@ impl DnsTypeNSEC {
     pub fn from_str(str: &str, default_original: Option<&str>) -> Result<Self, DNSProtoErr> {
         let (rest, next_domain) = take_while(is_not_space)(str)?;
         let (rest, _) = multispace0(rest)?;
-        let bitmaps = encode_nsec_bitmap_from_str(rest)?;
+        let bitmaps = TypeBitMap::from_str(rest)?;
         Ok(DnsTypeNSEC {
-            next_domain: DNSName::new(next_domain, default_original)?,
+            next_domain: parse_domain_name(next_domain, default_original)?,
             bitmaps,
         })
     }
@@ -54,13 +59,7 @@ impl DnsTypeNSEC {
 
 impl fmt::Display for DnsTypeNSEC {
     fn fmt(&self, format: &mut Formatter<'_>) -> fmt::Result {
-        let type_str = {
-            match nsec_bitmaps_to_string(self.bitmaps.as_slice()) {
-                Ok(nsec_result) => nsec_result,
-                Err(err) => format!("decode fail: {:?}", err),
-            }
-        };
-        write!(format, "{} {}", self.next_domain, type_str)
+        write!(format, "{} {}", self.next_domain, self.bitmaps)
     }
 }
 impl DNSWireFrame for DnsTypeNSEC {
@@ -68,20 +67,12 @@ impl DNSWireFrame for DnsTypeNSEC {
         DNSType::NSEC
     }
 
-    fn encode(&self, compression: CompressionType) -> Result<Vec<u8>, DNSProtoErr> {
+    fn encode(&self, _compression: CompressionType) -> Result<Vec<u8>, DNSProtoErr> {
         let mut data = vec![];
 
-        match compression {
-            Some((compression_map, size)) => {
-                let exchange = self.next_domain.to_binary(Some((compression_map, size)));
-                data.extend_from_slice(exchange.as_slice());
-            }
-            _ => {
-                let exchange = self.next_domain.to_binary(None);
-                data.extend_from_slice(exchange.as_slice());
-            }
-        }
-        data.extend_from_slice(self.bitmaps.as_slice());
+        // the next domain name must not be compressed(rfc4034 section 4.1.1)
+        data.extend_from_slice(self.next_domain.to_binary(None).as_slice());
+        data.extend_from_slice(self.bitmaps.encode().as_slice());
         Ok(data)
     }
     fn as_any(&self) -> &dyn Any {
@@ -99,10 +90,10 @@ impl DNSWireFrame for DnsTypeNSEC {
 named_args!(parse_nsec<'a>(original: &[u8])<DnsTypeNSEC>,
     do_parse!(
         next_domain: call!(parse_name, original)>>
-        bitmaps: call!(rest)>>
+        bitmaps: map_res!(rest, TypeBitMap::decode)>>
         (DnsTypeNSEC{
             next_domain,
-            bitmaps: bitmaps.to_vec(),
+            bitmaps,
         }
     )
 ));
@@ -12,10 +12,7 @@
 
 use crate::meta::DNSType;
 use crate::qtype::ds::DigestType;
-use crate::qtype::helper::{
-    encode_nsec_bitmap_from_str, encode_nsec_bitmap_from_types, hex_u8_to_string,
-    nsec_bitmaps_to_string, string_to_hex_u8,
-};
+use crate::qtype::helper::{hex_u8_to_string, string_to_hex_u8, TypeBitMap};
 use crate::qtype::soa::is_not_space;
 use crate::qtype::{CompressionType, DNSWireFrame};
 use data_encoding::BASE32_DNSSEC;
@@ -36,7 +33,7 @@ pub struct DnsTypeNSEC3 {
     iterations: u16,
     salt: Vec<u8>,
     hash: Vec<u8>,
-    bitmaps: Vec<u8>,
+    bitmaps: TypeBitMap,
 }
 
 fn encode_nsec3_hash_from_str(hash: &str) -> Result<Vec<u8>, DNSProtoErr> {
@@ -68,7 +65,7 @@ impl DnsTypeNSEC3 {
             iterations,
             salt: string_to_hex_u8(salt)?,
             hash,
-            bitmaps: encode_nsec_bitmap_from_types(type_arr)?,
+            bitmaps: TypeBitMap::from_types(&type_arr),
         })
     }
 
@@ -107,20 +104,13 @@ impl DnsTypeNSEC3 {
             iterations,
             salt: string_to_hex_u8(salt)?,
             hash: encode_nsec3_hash_from_str(hash)?,
-            bitmaps: encode_nsec_bitmap_from_str(rest)?,
+            bitmaps: TypeBitMap::from_str(rest)?,
         })
     }
 }
 
 impl fmt::Display for DnsTypeNSEC3 {
     fn fmt(&self, format: &mut Formatter<'_>) -> fmt::Result {
-        let type_str = {
-            match nsec_bitmaps_to_string(self.bitmaps.as_slice()) {
-                Ok(nsec_result) => nsec_result,
-                Err(err) => format!("decode fail: {:?}", err),
-            }
-        };
-
         write!(
             format,
             "{} {} {} {} {} {}",
@@ -129,7 +119,7 @@ impl fmt::Display for DnsTypeNSEC3 {
             self.iterations,
             hex_u8_to_string(self.salt.as_slice()),
             decode_nsec3_hash_to_string(self.hash.as_slice()),
-            type_str
+            self.bitmaps
         )
     }
 }
@@ -147,7 +137,7 @@ impl DNSWireFrame for DnsTypeNSEC3 {
         data.extend_from_slice(self.salt.as_slice());
         data.push(self.hash.len() as u8);
         data.extend_from_slice(self.hash.as_slice());
-        data.extend_from_slice(self.bitmaps.as_slice());
+        data.extend_from_slice(self.bitmaps.encode().as_slice());
         Ok(data)
     }
     fn as_any(&self) -> &dyn Any {
@@ -174,14 +164,14 @@ named_args!(parse_nsec3<'a>(original: &[u8])<DnsTypeNSEC3>,
         salt: take!(salt_length)>>
         hash_length: be_u8>>
         hash: take!(hash_length)>>
-        bitmaps: call!(rest)>>
+        bitmaps: map_res!(rest, TypeBitMap::decode)>>
         (DnsTypeNSEC3{
             hash_algorithem,
             flag,
             iterations,
             salt: salt.to_vec(),
             hash: hash.to_vec(),
-            bitmaps: bitmaps.to_vec(),
+            bitmaps,
         }
     )
 ));
@@ -0,0 +1,98 @@
+use crate::meta::DNSType;
+use crate::qtype::{CompressionType, DNSWireFrame};
+use otterlib::errors::DNSProtoErr;
+use std::any::Any;
+use std::fmt::{self, Formatter};
+
+// https://tools.ietf.org/html/rfc7929#section-2.1
+// the rdata is a single openpgp transferable public key(rfc4880 section 11.1) without
+// the ascii armor, and presented as base64 which may be split by spaces.
+// c93f1e400f26708f98cb19d936620da35eec8f72e57f9eec01c1afd6._openpgpkey.example.com.
+//     3600  IN  OPENPGPKEY  ( mQINBFit2jsBEADrbl5vjVxYeAE0g0IDYCBpHirv1Sjlqxx5gjtPhb2YhvyDMXjq ... )
+#[derive(Debug, PartialEq, Clone)]
+pub struct DnsTypeOPENPGPKEY {
+    public_key: Vec<u8>,
+}
+
+impl DnsTypeOPENPGPKEY {
+    pub fn new(public_key: Vec<u8>) -> Result<Self, DNSProtoErr> {
+        if public_key.is_empty() {
+            return Err(DNSProtoErr::GeneralErr(
+                "openpgpkey public key is empty".to_owned(),
+            ));
+        }
+        Ok(DnsTypeOPENPGPKEY { public_key })
+    }
+    pub fn public_key(&self) -> &[u8] {
+        self.public_key.as_slice()
+    }
+    pub fn decode(data: &[u8], _: Option<&[u8]>) -> Result<Self, DNSProtoErr> {
+        DnsTypeOPENPGPKEY::new(data.to_vec()).map_err(|_| DNSProtoErr::PacketParseError)
+    }
+    pub fn from_str(str: &str) -> Result<Self, DNSProtoErr> {
+        let key: String = str.split_whitespace().collect();
+        match base64::decode(key.as_str()) {
+            Ok(public_key) => DnsTypeOPENPGPKEY::new(public_key),
+            Err(err) => Err(DNSProtoErr::ParseDNSFromStrError(format!(
+                "decode openpgpkey base64 fail: {}",
+                err.to_string()
+            ))),
+        }
+    }
+}
+
+impl fmt::Display for DnsTypeOPENPGPKEY {
+    fn fmt(&self, format: &mut Formatter<'_>) -> fmt::Result {
+        write!(format, "{}", base64::encode(&self.public_key))
+    }
+}
+
+impl DNSWireFrame for DnsTypeOPENPGPKEY {
+    fn get_type(&self) -> DNSType {
+        DNSType::OPENPGPKEY
+    }
+
+    fn encode(&self, _: CompressionType) -> Result<Vec<u8>, DNSProtoErr> {
+        Ok(self.public_key.clone())
+    }
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn clone_box(&self) -> Box<dyn DNSWireFrame> {
+        Box::new(self.clone())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::meta::DNSType;
+    use crate::qtype::openpgpkey::DnsTypeOPENPGPKEY;
+    use crate::qtype::{decode_dns_data_from_string, DNSWireFrame};
+
+    #[test]
+    fn openpgpkey_round_trip() {
+        // leading octets of a version 4 public key packet: tag 6, length, version 4
+        let key_str = "mQINBFit2jsBEADrbl5vjVxYeAE0g0IDYCBpHirv1Sjlqxx5gjtPhb2YhvyDMXjq";
+        let key_struct = DnsTypeOPENPGPKEY::from_str(key_str).unwrap();
+        assert_eq!(&key_struct.public_key()[..4], &[0x99, 0x02, 0x0d, 0x04]);
+        assert_eq!(key_struct.to_string(), key_str);
+        let bin_arr = key_struct.encode(None).unwrap();
+        assert_eq!(bin_arr.len(), 48);
+        assert_eq!(
+            DnsTypeOPENPGPKEY::decode(&bin_arr, None).unwrap(),
+            key_struct
+        );
+
+        let decoded = decode_dns_data_from_string(
+            "( mQINBFit2jsBEADrbl5vjVxYeAE0g0ID YCBpHirv1Sjlqxx5gjtPhb2YhvyDMXjq )",
+            DNSType::OPENPGPKEY,
+            None,
+        )
+        .unwrap();
+        assert_eq!(decoded.to_string(), key_str);
+        assert!(DnsTypeOPENPGPKEY::decode(&[], None).is_err());
+        assert!(DnsTypeOPENPGPKEY::from_str("mQINBFit*").is_err());
+        assert!(DnsTypeOPENPGPKEY::from_str("").is_err());
+    }
+}
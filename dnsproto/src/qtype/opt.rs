@@ -1,4 +1,5 @@
 use crate::meta::DNSType;
+use crate::qtype::helper::hex_u8_to_string;
 use crate::qtype::{CompressionType, DNSWireFrame};
 use byteorder::{BigEndian, WriteBytesExt};
 use nom::number::complete::{be_u16, be_u8};
@@ -14,6 +15,8 @@ pub enum EDNSOptionCode {
     Reserved = 0,
     ECS = 8,
     Cookie = 10,
+    // https://tools.ietf.org/html/rfc8914
+    ExtendedError = 15,
 }
 
 impl From<u16> for EDNSOptionCode {
@@ -21,6 +24,7 @@ impl From<u16> for EDNSOptionCode {
         match vdata {
             x1 if x1 == 8 => Self::ECS,
             x1 if x1 == 10 => Self::Cookie,
+            x1 if x1 == 15 => Self::ExtendedError,
             _ => Self::Reserved,
         }
     }
@@ -97,7 +101,25 @@ pub struct EdnsCookie {
     client_cookie: Vec<u8>,
     server_cookie: Vec<u8>,
 }
+// client cookie is 8 bytes and server cookie is 8 to 32 bytes
+const CLIENT_COOKIE_SIZE: usize = 8;
+const MIN_SERVER_COOKIE_SIZE: usize = 8;
+const MAX_SERVER_COOKIE_SIZE: usize = 32;
+
 impl EdnsCookie {
+    fn new(cookie: &[u8]) -> Result<Self, DNSProtoErr> {
+        let server_size = cookie.len().saturating_sub(CLIENT_COOKIE_SIZE);
+        if cookie.len() < CLIENT_COOKIE_SIZE
+            || (server_size != 0
+                && (server_size < MIN_SERVER_COOKIE_SIZE || server_size > MAX_SERVER_COOKIE_SIZE))
+        {
+            return Err(DNSProtoErr::PacketParseError);
+        }
+        Ok(EdnsCookie {
+            client_cookie: cookie[..CLIENT_COOKIE_SIZE].to_vec(),
+            server_cookie: cookie[CLIENT_COOKIE_SIZE..].to_vec(),
+        })
+    }
     fn encode(&self, mut cursor: Cursor<Vec<u8>>) -> Result<(Cursor<Vec<u8>>, usize), DNSProtoErr> {
         cursor.write_all(self.client_cookie.as_slice())?;
         cursor.write_all(self.server_cookie.as_slice())?;
@@ -124,8 +146,13 @@ pub struct DnsTypeOpt {
 }
 
 impl fmt::Display for DnsTypeOpt {
-    fn fmt(&self, _format: &mut Formatter<'_>) -> fmt::Result {
-        unimplemented!()
+    fn fmt(&self, format: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            format,
+            "{:?} {}",
+            self.code,
+            hex_u8_to_string(self.raw_data.as_slice())
+        )
     }
 }
 
@@ -135,24 +162,21 @@ impl DNSWireFrame for DnsTypeOpt {
     }
 
     fn encode(&self, _: CompressionType) -> Result<Vec<u8>, DNSProtoErr> {
-        let frame = vec![];
-        let mut cursor = Cursor::new(frame);
-        if self.data.is_none() {
-            cursor.write_u16::<BigEndian>(0)?;
-            return Ok(cursor.into_inner());
-        }
-        if let Ok((mut cursor, size)) = {
-            match self.data.as_ref().unwrap() {
-                Opt::ECS(ecs) => ecs.encode(cursor),
-                Opt::Cookie(cookie) => cookie.encode(cursor),
+        let mut cursor = Cursor::new(vec![]);
+        cursor.write_u16::<BigEndian>(self.code as u16)?;
+        // option length is updated after the option data is written
+        cursor.write_u16::<BigEndian>(0)?;
+        let (mut cursor, size) = match self.data.as_ref() {
+            Some(Opt::ECS(ecs)) => ecs.encode(cursor)?,
+            Some(Opt::Cookie(cookie)) => cookie.encode(cursor)?,
+            None => {
+                cursor.write_all(self.raw_data.as_slice())?;
+                (cursor, self.raw_data.len())
             }
-        } {
-            cursor.set_position(2);
-            cursor.write_u16::<BigEndian>(size as u16)?;
-            Ok(cursor.into_inner())
-        } else {
-            Err(DNSProtoErr::PacketSerializeError)
-        }
+        };
+        cursor.set_position(2);
+        cursor.write_u16::<BigEndian>(size as u16)?;
+        Ok(cursor.into_inner())
     }
 
     fn as_any(&self) -> &dyn Any {
@@ -207,7 +231,7 @@ impl DnsTypeOpt {
 
     fn decode_with_type(&mut self) -> Result<(), DNSProtoErr> {
         match self.code {
-            EDNSOptionCode::Reserved => Ok(()),
+            EDNSOptionCode::Reserved | EDNSOptionCode::ExtendedError => Ok(()),
             EDNSOptionCode::ECS => match parse_edns_ecs(self.raw_data.as_slice(), self.length) {
                 Ok((_, v2)) => {
                     self.data = Some(Opt::ECS(v2));
@@ -215,12 +239,22 @@ impl DnsTypeOpt {
                 }
                 _ => Err(DNSProtoErr::PacketParseError),
             },
-            _ => Err(DNSProtoErr::UnImplementedError(format!(
-                "unknown opt code: {:?}",
-                self.code
-            ))),
+            EDNSOptionCode::Cookie => {
+                self.data = Some(Opt::Cookie(EdnsCookie::new(self.raw_data.as_slice())?));
+                Ok(())
+            }
         }
     }
+
+    /// new_cookie create a COOKIE option with the client cookie and optional server cookie.
+    pub(crate) fn new_cookie(cookie: &[u8]) -> Result<Self, DNSProtoErr> {
+        Ok(DnsTypeOpt {
+            code: EDNSOptionCode::Cookie,
+            length: cookie.len() as u16,
+            raw_data: cookie.to_vec(),
+            data: Some(Opt::Cookie(EdnsCookie::new(cookie)?)),
+        })
+    }
 }
 #[cfg(test)]
 mod test {
@@ -255,6 +289,24 @@ mod test {
             Err(_) => assert!(false),
         }
     }
+
+    #[test]
+    fn test_cookie_option() {
+        let client_cookie = [1, 2, 3, 4, 5, 6, 7, 8];
+        let opt = DnsTypeOpt::new_cookie(&client_cookie).unwrap();
+        let encoded = opt.encode(None).unwrap();
+        assert_eq!(encoded, vec![0, 10, 0, 8, 1, 2, 3, 4, 5, 6, 7, 8]);
+        assert_eq!(DnsTypeOpt::decode(&encoded, None).unwrap(), opt);
+
+        let mut cookie = client_cookie.to_vec();
+        cookie.extend_from_slice(&[9; 16]);
+        let opt = DnsTypeOpt::new_cookie(&cookie).unwrap();
+        assert_eq!(opt.encode(None).unwrap()[2..4], [0, 24]);
+        // server cookie must be 8 to 32 bytes
+        assert!(DnsTypeOpt::new_cookie(&client_cookie[..7]).is_err());
+        assert!(DnsTypeOpt::new_cookie(&cookie[..12]).is_err());
+        assert!(DnsTypeOpt::decode(&[0, 10, 0, 4, 1, 2, 3, 4], None).is_err());
+    }
 }
 
 // impl FromStr for DnsTypeA {
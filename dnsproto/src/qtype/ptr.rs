@@ -1,4 +1,4 @@
-use crate::dnsname::parse_name;
+use crate::dnsname::{parse_name, DNSName};
 use crate::meta::DNSType;
 use crate::qtype::{CompressionType, DNSWireFrame, DnsTypeNS};
 use otterlib::errors::DNSProtoErr;
@@ -12,6 +12,9 @@ impl DnsTypePTR {
     pub fn new(name: &str) -> Result<Self, DNSProtoErr> {
         Ok(DnsTypePTR(DnsTypeNS::new(name)?))
     }
+    pub fn name(&self) -> &DNSName {
+        &self.0.name
+    }
     pub(crate) fn from_str(
         a_str: &str,
         default_original: Option<&str>,
@@ -34,7 +37,7 @@ impl fmt::Display for DnsTypePTR {
 
 impl DNSWireFrame for DnsTypePTR {
     fn get_type(&self) -> DNSType {
-        DNSType::CNAME
+        DNSType::PTR
     }
     fn encode(&self, compression: CompressionType) -> Result<Vec<u8>, DNSProtoErr> {
         Ok(self.0.name.to_binary(compression))
@@ -52,10 +55,8 @@ impl DNSWireFrame for DnsTypePTR {
 }
 #[cfg(test)]
 mod test {
-    use crate::label::Label;
+    use crate::dnsname::{CompressionMap, DNSName};
     use crate::qtype::{DNSWireFrame, DnsTypePTR};
-    use std::collections::HashMap;
-    use std::str::FromStr;
 
     #[test]
     fn test_ptr_decode() {
@@ -80,14 +81,10 @@ mod test {
                 assert!(false);
             }
         }
-        let mut compression_map = HashMap::new();
-        compression_map.insert(
-            vec![
-                Label::from_str("gtld-servers").unwrap(),
-                Label::from_str("net").unwrap(),
-            ],
-            23,
-        );
+        let mut compression_map = CompressionMap::new();
+        DNSName::new("gtld-servers.net.", None)
+            .unwrap()
+            .to_binary(Some((&mut compression_map, 23)));
         let compression_vec: Vec<u8> = vec![1, 102, 192, 23];
         match ptr_record.encode(Some((&mut compression_map, 30))) {
             Ok(ptr_data) => assert_eq!(ptr_data, compression_vec),
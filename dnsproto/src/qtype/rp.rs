@@ -0,0 +1,125 @@
+use crate::dnsname::{parse_name, DNSName};
+use crate::meta::DNSType;
+use crate::qtype::helper::{not_space, parse_target_name};
+use crate::qtype::{CompressionType, DNSWireFrame};
+use nom::character::complete::multispace0;
+use otterlib::errors::DNSProtoErr;
+use std::any::Any;
+use std::fmt::{self, Formatter};
+
+// https://tools.ietf.org/html/rfc1183#section-2.2
+// +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+// /                   MBOX-DNAME                  /
+// +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+// /                   TXT-DNAME                   /
+// +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+// the names are never compressed on encode(rfc3597 section 4).
+// example.com.  3600  IN  RP  admin.example.com. contact.example.com.
+#[derive(Debug, PartialEq, Clone)]
+pub struct DnsTypeRP {
+    mbox: DNSName,
+    txt: DNSName,
+}
+
+named_args!(parse_rp<'a>(original: &[u8])<DnsTypeRP>,
+    do_parse!(
+        mbox: call!(parse_name, original)>>
+        txt: call!(parse_name, original)>>
+        (DnsTypeRP{
+            mbox,
+            txt,
+        }
+    )
+));
+
+impl DnsTypeRP {
+    pub fn new(mbox: &str, txt: &str) -> Result<Self, DNSProtoErr> {
+        Ok(DnsTypeRP {
+            mbox: DNSName::new(mbox, None)?,
+            txt: DNSName::new(txt, None)?,
+        })
+    }
+    pub fn mbox(&self) -> &DNSName {
+        &self.mbox
+    }
+    pub fn txt(&self) -> &DNSName {
+        &self.txt
+    }
+    pub fn decode(data: &[u8], original: Option<&[u8]>) -> Result<Self, DNSProtoErr> {
+        match parse_rp(data, original.unwrap_or(&[])) {
+            Ok((_, rp)) => Ok(rp),
+            Err(_err) => Err(DNSProtoErr::PacketParseError),
+        }
+    }
+    /// from_str parse the mailbox and txt names, relative names will be qualified with
+    /// the default original, `.` means the name is not available.
+    pub fn from_str(str: &str, default_original: Option<&str>) -> Result<Self, DNSProtoErr> {
+        let (rest, _) = multispace0(str)?;
+        let (rest, mbox) = not_space(rest)?;
+        let (rest, _) = multispace0(rest)?;
+        let (rest, txt) = not_space(rest)?;
+        Ok(DnsTypeRP {
+            mbox: parse_target_name(mbox, "", default_original)?,
+            txt: parse_target_name(txt, rest, default_original)?,
+        })
+    }
+}
+
+impl fmt::Display for DnsTypeRP {
+    fn fmt(&self, format: &mut Formatter<'_>) -> fmt::Result {
+        write!(format, "{} {}", self.mbox, self.txt)
+    }
+}
+
+impl DNSWireFrame for DnsTypeRP {
+    fn get_type(&self) -> DNSType {
+        DNSType::RP
+    }
+
+    fn encode(&self, _: CompressionType) -> Result<Vec<u8>, DNSProtoErr> {
+        let mut data = self.mbox.to_binary(None);
+        data.extend_from_slice(self.txt.to_binary(None).as_slice());
+        Ok(data)
+    }
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn clone_box(&self) -> Box<dyn DNSWireFrame> {
+        Box::new(self.clone())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::dnsname::CompressionMap;
+    use crate::qtype::rp::DnsTypeRP;
+    use crate::qtype::DNSWireFrame;
+
+    #[test]
+    fn rp_round_trip() {
+        let rp_str = "admin.example.com. contact.example.com.";
+        let rp_struct = DnsTypeRP::new("admin.example.com.", "contact.example.com.").unwrap();
+        let bin_arr = b"\x05admin\x07example\x03com\x00\x07contact\x07example\x03com\x00";
+        assert_eq!(DnsTypeRP::from_str(rp_str, None).unwrap(), rp_struct);
+        assert_eq!(
+            DnsTypeRP::from_str("admin contact", Some("example.com.")).unwrap(),
+            rp_struct
+        );
+        assert_eq!(rp_struct.to_string(), rp_str);
+        assert_eq!(DnsTypeRP::decode(bin_arr, None).unwrap(), rp_struct);
+        assert_eq!(rp_struct.encode(None).unwrap(), bin_arr.to_vec());
+
+        // rdata is not compressed even the names are in the compression map
+        let mut compression = CompressionMap::new();
+        rp_struct.mbox().to_binary(Some((&mut compression, 12)));
+        assert_eq!(
+            rp_struct.encode(Some((&mut compression, 40))).unwrap(),
+            bin_arr.to_vec()
+        );
+
+        let no_mailbox = DnsTypeRP::from_str(". contact.example.com.", None).unwrap();
+        assert_eq!(no_mailbox.to_string(), ". contact.example.com.");
+        assert!(DnsTypeRP::from_str("admin.example.com.", None).is_err());
+    }
+}
@@ -1,5 +1,6 @@
 use crate::dnsname::{parse_name, DNSName};
 use crate::meta::DNSType;
+use crate::qtype::helper::parse_domain_name;
 use crate::qtype::soa::is_not_space;
 use crate::qtype::{CompressionType, DNSWireFrame};
 use nom::bytes::complete::take_while;
@@ -8,7 +9,6 @@ use nom::combinator::rest;
 use nom::number::complete::{be_u16, be_u32, be_u8};
 use otterlib::errors::DNSProtoErr;
 use std::any::Any;
-use std::convert::TryFrom;
 use std::str::FromStr;
 use std::{fmt, fmt::Formatter};
 
@@ -95,6 +95,49 @@ impl DnsTypeRRSIG {
             signature,
         }
     }
+    pub fn algorithm(&self) -> u8 {
+        self.algorithm_type
+    }
+    pub fn original_ttl(&self) -> u32 {
+        self.original_ttl
+    }
+    pub fn signature(&self) -> &[u8] {
+        self.signature.as_slice()
+    }
+    /// signed_rdata return the rdata without the signature and with the lowercased signer
+    /// name, the data covered by the signature starts with it(rfc4034 section 3.1.8.1).
+    pub fn signed_rdata(&self) -> Vec<u8> {
+        let mut data = vec![];
+        data.extend_from_slice(&self.rrsig_type.to_be_bytes()[..]);
+        data.push(self.algorithm_type);
+        data.push(self.labels);
+        data.extend_from_slice(&self.original_ttl.to_be_bytes()[..]);
+        data.extend_from_slice(&self.expiration.to_be_bytes()[..]);
+        data.extend_from_slice(&self.inception.to_be_bytes()[..]);
+        data.extend_from_slice(&self.key_tag.to_be_bytes()[..]);
+        data.extend_from_slice(&self.signer.to_canonical_binary());
+        data
+    }
+    pub fn type_covered(&self) -> u16 {
+        self.rrsig_type
+    }
+    /// labels return the label count of the signed owner name, less than the label count
+    /// of the owner when the rrset is expanded from a wildcard.
+    pub fn labels(&self) -> u8 {
+        self.labels
+    }
+    pub fn expiration(&self) -> u32 {
+        self.expiration
+    }
+    pub fn inception(&self) -> u32 {
+        self.inception
+    }
+    pub fn key_tag(&self) -> u16 {
+        self.key_tag
+    }
+    pub fn signer(&self) -> &DNSName {
+        &self.signer
+    }
     pub fn decode(data: &[u8], original: Option<&[u8]>) -> Result<Self, DNSProtoErr> {
         match parse_rrsig(data, original, data.len()) {
             Ok((_, rrsig)) => Ok(rrsig),
@@ -103,13 +146,13 @@ impl DnsTypeRRSIG {
     }
     // example : "SOA 8 0 86400 20210422050000 20210409040000 14631 . W45Xjg7WewB+rNjMHDTpHlmvwT+L3VamaProC1FMIUFGZRcnFd41GSkK c2i2kgtcVjxIuYiw6kVgd7MXxaEsgW6wIexCq8H1JuDJIl/lDRZOPfzy 2IxEvqCFV01beVFnbWAMYOAa6u3W/DB2+uJ7+GNJPzN7vLAsNpFzFvxo 5jxY47I+WU0pFFxYlWoQ29Xzq2MBkwU8pPRovlN1nexk8I+Uwcw6fmUL LXg4U3U4+UK76Vhb0IMRFZFa44n3RjGwIu3lG+5Z16Fo3y8Xo+XA8ojt
     //            wvXpz1hfaKd8f/CMzs9dLSJp5TA15DQ9KAaqKepZmgJvajt/wYUMpTeX 4N0kuA=="
-    pub fn from_str(str: &str) -> Result<Self, DNSProtoErr> {
+    pub fn from_str(str: &str, default_original: Option<&str>) -> Result<Self, DNSProtoErr> {
         let (rest, rrsig_type) = take_while(is_not_space)(str)?;
         let rrsig_type = match DNSType::from_str(rrsig_type) {
-            Some(value) => value,
-            _ => return Err(DNSProtoErr::GeneralErr("unknown rrsig type".to_owned())),
+            Ok(value) => value,
+            Err(_) => return Err(DNSProtoErr::GeneralErr("unknown rrsig type".to_owned())),
         };
-        let rrsig_type = rrsig_type as u16;
+        let rrsig_type = u16::from(rrsig_type);
 
         let (rest, _) = multispace0(rest)?;
         let (rest, algorithm_type) = digit1(rest)?;
@@ -130,14 +173,13 @@ impl DnsTypeRRSIG {
         let key_tag = u16::from_str(key_tag)?;
         let (rest, _) = multispace0(rest)?;
         let (rest, signer) = take_while(is_not_space)(rest)?;
-        let (rest, _) = multispace0(rest)?;
-        let (_, signature) = take_while(is_not_space)(rest)?;
-        // let (rest, _) = multispace0(rest)?;
+        // the signature may be split by whitespace
+        let signature: String = rest.split_whitespace().collect();
 
         let expiration = time_string_to_u32(expiration)?;
         let inception = time_string_to_u32(inception)?;
-        let name = DNSName::new(signer, None)?;
-        match base64::decode(signature) {
+        let name = parse_domain_name(signer, default_original)?;
+        match base64::decode(signature.as_str()) {
             Ok(decode) => Ok(DnsTypeRRSIG::new(
                 rrsig_type,
                 algorithm_type,
@@ -176,7 +218,7 @@ impl fmt::Display for DnsTypeRRSIG {
         write!(
             format,
             "{} {} {} {} {} {} {} {} {}",
-            DNSType::try_from(self.rrsig_type).unwrap_or(DNSType::Unknown),
+            DNSType::from(self.rrsig_type),
             self.algorithm_type,
             self.labels,
             self.original_ttl,
@@ -193,7 +235,7 @@ impl DNSWireFrame for DnsTypeRRSIG {
         DNSType::RRSIG
     }
 
-    fn encode(&self, compression: CompressionType) -> Result<Vec<u8>, DNSProtoErr> {
+    fn encode(&self, _compression: CompressionType) -> Result<Vec<u8>, DNSProtoErr> {
         let mut data = vec![];
 
         data.extend_from_slice(&self.rrsig_type.to_be_bytes()[..]);
@@ -203,7 +245,8 @@ impl DNSWireFrame for DnsTypeRRSIG {
         data.extend_from_slice(&self.expiration.to_be_bytes()[..]);
         data.extend_from_slice(&self.inception.to_be_bytes()[..]);
         data.extend_from_slice(&self.key_tag.to_be_bytes()[..]);
-        data.extend_from_slice(&self.signer.to_binary(compression));
+        // the signer name must not be compressed(rfc4034 section 3.1.7)
+        data.extend_from_slice(&self.signer.to_binary(None));
         data.extend_from_slice(&self.signature.as_slice());
         Ok(data)
     }
@@ -238,7 +281,7 @@ mod test {
         let rrsig_signatrue = "W45Xjg7WewB+rNjMHDTpHlmvwT+L3VamaProC1FMIUFGZRcnFd41GSkKc2i2kgtcVjxIuYiw6kVgd7MXxaEsgW6wIexCq8H1JuDJIl/lDRZOPfzy2IxEvqCFV01beVFnbWAMYOAa6u3W/DB2+uJ7+GNJPzN7vLAsNpFzFvxo5jxY47I+WU0pFFxYlWoQ29Xzq2MBkwU8pPRovlN1nexk8I+Uwcw6fmULLXg4U3U4+UK76Vhb0IMRFZFa44n3RjGwIu3lG+5Z16Fo3y8Xo+XA8ojtwvXpz1hfaKd8f/CMzs9dLSJp5TA15DQ9KAaqKepZmgJvajt/wYUMpTeX4N0kuA==";
 
         let rrsig_struct = DnsTypeRRSIG::new(
-            DNSType::SOA as u16,
+            u16::from(DNSType::SOA),
             AlgorithemType::RSASHA256.into(),
             0,
             86400,
@@ -254,7 +297,7 @@ mod test {
     #[test]
     fn dns_rrsig_from_str() {
         let (rrsig_str, rrsig_struct) = get_example_rrsig();
-        match DnsTypeRRSIG::from_str(rrsig_str.as_str()) {
+        match DnsTypeRRSIG::from_str(rrsig_str.as_str(), None) {
             Ok(rrsig) => {
                 assert_eq!(rrsig, rrsig_struct);
                 assert_eq!(rrsig_str.to_owned(), rrsig_struct.to_string())
@@ -299,5 +342,7 @@ mod test {
             _ => assert!(false, "encode ds fail"),
         }
         assert_eq!(rrsig, rrsig_struct.to_string());
+        let signed = bin_arr.len() - rrsig_struct.signature().len();
+        assert_eq!(rrsig_struct.signed_rdata().as_slice(), &bin_arr[..signed]);
     }
 }
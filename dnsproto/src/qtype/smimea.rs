@@ -0,0 +1,99 @@
+use crate::meta::DNSType;
+use crate::qtype::tlsa::DnsTypeTLSA;
+use crate::qtype::{CompressionType, DNSWireFrame};
+use otterlib::errors::DNSProtoErr;
+use std::any::Any;
+use std::fmt::{self, Formatter};
+
+// https://tools.ietf.org/html/rfc8162#section-2
+// the SMIMEA wire and presentation format is the same as TLSA, the owner name is
+// the hashed local part of the email address under the _smimecert subdomain.
+// <sha2-224 of local part>._smimecert.example.com.  3600  IN  SMIMEA  3 0 1 <sha2-256 of cert>
+#[derive(Debug, PartialEq, Clone)]
+pub struct DnsTypeSMIMEA(DnsTypeTLSA);
+
+impl DnsTypeSMIMEA {
+    pub fn new(
+        usage: u8,
+        selector: u8,
+        matching_type: u8,
+        data: Vec<u8>,
+    ) -> Result<Self, DNSProtoErr> {
+        Ok(DnsTypeSMIMEA(DnsTypeTLSA::new(
+            usage,
+            selector,
+            matching_type,
+            data,
+        )?))
+    }
+    pub fn tlsa(&self) -> &DnsTypeTLSA {
+        &self.0
+    }
+    pub fn decode(data: &[u8], original: Option<&[u8]>) -> Result<Self, DNSProtoErr> {
+        DnsTypeTLSA::decode(data, original).map(DnsTypeSMIMEA)
+    }
+    pub fn from_str(str: &str) -> Result<Self, DNSProtoErr> {
+        DnsTypeTLSA::from_str(str).map(DnsTypeSMIMEA)
+    }
+}
+
+impl From<DnsTypeTLSA> for DnsTypeSMIMEA {
+    fn from(tlsa: DnsTypeTLSA) -> Self {
+        DnsTypeSMIMEA(tlsa)
+    }
+}
+
+impl fmt::Display for DnsTypeSMIMEA {
+    fn fmt(&self, format: &mut Formatter<'_>) -> fmt::Result {
+        self.0.fmt(format)
+    }
+}
+
+impl DNSWireFrame for DnsTypeSMIMEA {
+    fn get_type(&self) -> DNSType {
+        DNSType::SMIMEA
+    }
+
+    fn encode(&self, compression: CompressionType) -> Result<Vec<u8>, DNSProtoErr> {
+        self.0.encode(compression)
+    }
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn clone_box(&self) -> Box<dyn DNSWireFrame> {
+        Box::new(self.clone())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::meta::DNSType;
+    use crate::qtype::smimea::DnsTypeSMIMEA;
+    use crate::qtype::tlsa::DnsTypeTLSA;
+    use crate::qtype::DNSWireFrame;
+
+    #[test]
+    fn smimea_round_trip() {
+        // end entity certificate(3), full certificate(0), sha2-256(1)
+        let smimea_str = "3 0 1 D2ABDE240D7CD3EE6B4B28C54DF034B97983A1D16E8A410E4561CB106618E971";
+        let smimea_struct = DnsTypeSMIMEA::from_str(smimea_str).unwrap();
+        assert_eq!(smimea_struct.tlsa().usage(), 3);
+        assert_eq!(smimea_struct.tlsa().matching_type(), 1);
+        assert_eq!(smimea_struct.to_string(), smimea_str);
+        let bin_arr = smimea_struct.encode(None).unwrap();
+        assert_eq!(bin_arr.len(), 35);
+        assert_eq!(
+            DnsTypeSMIMEA::decode(&bin_arr, None).unwrap(),
+            smimea_struct
+        );
+        assert_eq!(smimea_struct.get_type(), DNSType::SMIMEA);
+        let tlsa = DnsTypeTLSA::from_str(smimea_str).unwrap();
+        assert_eq!(
+            tlsa.encode(None).unwrap(),
+            bin_arr,
+            "smimea should be wire identical with tlsa"
+        );
+        assert_eq!(DnsTypeSMIMEA::from(tlsa), smimea_struct);
+    }
+}
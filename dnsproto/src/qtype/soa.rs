@@ -1,6 +1,8 @@
 use crate::dnsname::{parse_name, DNSName};
 use crate::meta::DNSType;
+use crate::qtype::helper::parse_domain_name;
 use crate::qtype::{CompressionType, DNSWireFrame};
+use crate::utils::remove_parentheses;
 use nom::bytes::complete::take_while;
 use nom::character::complete::digit1;
 use nom::character::complete::multispace0;
@@ -68,6 +70,33 @@ impl DnsTypeSOA {
         })
     }
 
+    /// primary_name return the name server that was the original source of the zone
+    pub fn primary_name(&self) -> &DNSName {
+        &self.primary_name
+    }
+    /// response_email return the mailbox of the person responsible for the zone
+    pub fn response_email(&self) -> &DNSName {
+        &self.response_email
+    }
+    pub fn serial(&self) -> u32 {
+        self.serial
+    }
+    pub fn set_serial(&mut self, serial: u32) {
+        self.serial = serial;
+    }
+    pub fn refresh(&self) -> u32 {
+        self.refresh
+    }
+    pub fn retry(&self) -> u32 {
+        self.retry
+    }
+    pub fn expire(&self) -> u32 {
+        self.expire
+    }
+    pub fn minimum(&self) -> u32 {
+        self.minimum
+    }
+
     pub fn decode(data: &[u8], original: Option<&[u8]>) -> Result<Self, DNSProtoErr> {
         match parse_soa(data, original.unwrap_or(&[])) {
             Ok((_, soa)) => Ok(soa),
@@ -75,9 +104,10 @@ impl DnsTypeSOA {
         }
     }
 
-    // from_str from one line without ()
+    // from_str from one line, the parentheses of multi-line form are allowed
     pub fn from_str(str: &str, default_original: Option<&str>) -> Result<Self, DNSProtoErr> {
-        let (rest, _) = multispace0(str)?;
+        let str = remove_parentheses(str);
+        let (rest, _) = multispace0(str.as_str())?;
         let (rest, primary) = take_while(is_not_space)(rest)?;
         let (rest, _) = multispace0(rest)?;
         let (rest, response) = take_while(is_not_space)(rest)?;
@@ -97,8 +127,8 @@ impl DnsTypeSOA {
         let (_, minimum) = digit1(rest)?;
         let minimum = u32::from_str(minimum)?;
         Ok(DnsTypeSOA {
-            primary_name: DNSName::new(primary, default_original)?,
-            response_email: DNSName::new(response, default_original)?,
+            primary_name: parse_domain_name(primary, default_original)?,
+            response_email: parse_domain_name(response, default_original)?,
             serial,
             refresh,
             retry,
@@ -112,11 +142,11 @@ impl fmt::Display for DnsTypeSOA {
     fn fmt(&self, format: &mut Formatter<'_>) -> fmt::Result {
         write!(
             format,
-            "{} {} ( {} {} {} {} {} )",
-            self.primary_name.to_string(),
-            self.response_email.to_string(),
+            "{} {} {} {} {} {} {}",
+            self.primary_name,
+            self.response_email,
             self.serial,
-            &self.refresh,
+            self.refresh,
             self.retry,
             self.expire,
             self.minimum
@@ -173,10 +203,8 @@ impl DNSWireFrame for DnsTypeSOA {
 }
 #[cfg(test)]
 mod test {
-    use crate::dnsname::DNSName;
-    use crate::label::Label;
+    use crate::dnsname::{CompressionMap, DNSName};
     use crate::qtype::{DNSWireFrame, DnsTypeSOA};
-    use std::collections::HashMap;
     use std::str::FromStr;
 
     #[test]
@@ -211,6 +239,22 @@ mod test {
         let err_soa = "a.dns.cn. root.cnnic.cn. 2027954656";
         let dns_soa = DnsTypeSOA::from_str(err_soa, None);
         assert!(dns_soa.is_err());
+
+        let soa = "a.dns.cn. root.cnnic.cn. ( 2027954656 7200 3600 2419200 21600 )";
+        let dns_soa = DnsTypeSOA::from_str(soa, None).unwrap();
+        assert_eq!(
+            dns_soa.to_string(),
+            "a.dns.cn. root.cnnic.cn. 2027954656 7200 3600 2419200 21600"
+        );
+        assert_eq!(
+            DnsTypeSOA::from_str(dns_soa.to_string().as_str(), None).unwrap(),
+            dns_soa
+        );
+        assert_eq!(dns_soa.serial(), 2027954656);
+        assert_eq!(dns_soa.refresh(), 7200);
+        assert_eq!(dns_soa.retry(), 3600);
+        assert_eq!(dns_soa.expire(), 2419200);
+        assert_eq!(dns_soa.minimum(), 21600);
     }
 
     #[test]
@@ -242,8 +286,10 @@ mod test {
                 assert!(false, format!("error: {:?}", err));
             }
         }
-        let mut compression_map = HashMap::new();
-        compression_map.insert(vec![Label::from_str("com").unwrap()], 12);
+        let mut compression_map = CompressionMap::new();
+        DNSName::new("com.", None)
+            .unwrap()
+            .to_binary(Some((&mut compression_map, 12)));
         match soa.encode(Some((&mut compression_map, 0))) {
             Ok(v) => {
                 // println!("{:x?}", v);
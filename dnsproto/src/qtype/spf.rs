@@ -0,0 +1,93 @@
+use crate::meta::DNSType;
+use crate::qtype::txt::DnsTypeTXT;
+use crate::qtype::{CompressionType, DNSWireFrame};
+use otterlib::errors::DNSProtoErr;
+use std::any::Any;
+use std::fmt::{self, Formatter};
+use std::str::FromStr;
+
+// https://tools.ietf.org/html/rfc7208#section-3.1
+// the SPF type(99) has the same rdata format as TXT and is deprecated, it is only
+// kept for loading old zone exports, spf policy should be published as TXT.
+// example.com.  3600  IN  SPF  "v=spf1 ip4:192.0.2.0/24 -all"
+#[derive(Debug, PartialEq, Clone)]
+pub struct DnsTypeSPF(DnsTypeTXT);
+
+impl FromStr for DnsTypeSPF {
+    type Err = DNSProtoErr;
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        DnsTypeTXT::from_str(text).map(DnsTypeSPF)
+    }
+}
+
+impl DnsTypeSPF {
+    pub fn new(text: &str) -> Result<Self, DNSProtoErr> {
+        Ok(DnsTypeSPF(DnsTypeTXT::new(text)?))
+    }
+    pub fn txt(&self) -> &DnsTypeTXT {
+        &self.0
+    }
+    pub fn decode(data: &[u8], original: Option<&[u8]>) -> Result<Self, DNSProtoErr> {
+        DnsTypeTXT::decode(data, original).map(DnsTypeSPF)
+    }
+}
+
+impl From<DnsTypeTXT> for DnsTypeSPF {
+    fn from(txt: DnsTypeTXT) -> Self {
+        DnsTypeSPF(txt)
+    }
+}
+
+impl fmt::Display for DnsTypeSPF {
+    fn fmt(&self, format: &mut Formatter<'_>) -> fmt::Result {
+        self.0.fmt(format)
+    }
+}
+
+impl DNSWireFrame for DnsTypeSPF {
+    fn get_type(&self) -> DNSType {
+        DNSType::SPF
+    }
+
+    fn encode(&self, compression: CompressionType) -> Result<Vec<u8>, DNSProtoErr> {
+        self.0.encode(compression)
+    }
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn clone_box(&self) -> Box<dyn DNSWireFrame> {
+        Box::new(self.clone())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::meta::DNSType;
+    use crate::qtype::spf::DnsTypeSPF;
+    use crate::qtype::{decode_dns_data_from_string, DNSWireFrame, DnsTypeTXT};
+    use std::str::FromStr;
+
+    #[test]
+    fn spf_round_trip() {
+        let spf_str = "\"v=spf1 ip4:192.0.2.0/24 -all\"";
+        let spf_struct = DnsTypeSPF::new("v=spf1 ip4:192.0.2.0/24 -all").unwrap();
+        let mut bin_arr = vec![28];
+        bin_arr.extend_from_slice(b"v=spf1 ip4:192.0.2.0/24 -all");
+        assert_eq!(DnsTypeSPF::from_str(spf_str).unwrap(), spf_struct);
+        assert_eq!(spf_struct.to_string(), spf_str);
+        assert_eq!(DnsTypeSPF::decode(&bin_arr, None).unwrap(), spf_struct);
+        assert_eq!(spf_struct.encode(None).unwrap(), bin_arr);
+        assert_eq!(spf_struct.get_type(), DNSType::SPF);
+        assert_eq!(spf_struct.txt(), &DnsTypeTXT::from_str(spf_str).unwrap());
+
+        let decoded = decode_dns_data_from_string(
+            "\"v=spf1 ip4:192.0.2.0/24\" \" -all\"",
+            DNSType::SPF,
+            None,
+        )
+        .unwrap();
+        assert_eq!(decoded.get_type(), DNSType::SPF);
+        assert_eq!(decoded.to_string(), "\"v=spf1 ip4:192.0.2.0/24\" \" -all\"");
+    }
+}
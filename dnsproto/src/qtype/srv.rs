@@ -1,5 +1,6 @@
 use crate::dnsname::{parse_name, DNSName};
 use crate::meta::DNSType;
+use crate::qtype::helper::parse_target_name;
 use crate::qtype::soa::is_not_space;
 use crate::qtype::{CompressionType, DNSWireFrame};
 use nom::bytes::complete::take_while;
@@ -65,6 +66,8 @@ impl DnsTypeSRV {
         }
     }
 
+    /// from_str parse the priority, weight, port and target, a relative target will be
+    /// qualified with the default original.
     pub fn from_str(str: &str, default_original: Option<&str>) -> Result<Self, DNSProtoErr> {
         let (rest, _) = multispace0(str)?;
         let (rest, priority) = digit1(rest)?;
@@ -76,13 +79,13 @@ impl DnsTypeSRV {
         let (rest, port) = digit1(rest)?;
         let port = u16::from_str(port)?;
         let (rest, _) = multispace0(rest)?;
-        let (_, target) = take_while(is_not_space)(rest)?;
+        let (rest, target) = take_while(is_not_space)(rest)?;
 
         Ok(DnsTypeSRV {
             priority,
             weight,
             port,
-            target: DNSName::new(target, default_original)?,
+            target: parse_target_name(target, rest, default_original)?,
         })
     }
 }
@@ -92,10 +95,7 @@ impl fmt::Display for DnsTypeSRV {
         write!(
             format,
             "{} {} {} {}",
-            self.priority,
-            self.weight,
-            self.port,
-            self.target.to_string(),
+            self.priority, self.weight, self.port, self.target,
         )
     }
 }
@@ -105,21 +105,13 @@ impl DNSWireFrame for DnsTypeSRV {
         DNSType::SRV
     }
 
-    fn encode(&self, compression: CompressionType) -> Result<Vec<u8>, DNSProtoErr> {
+    fn encode(&self, _compression: CompressionType) -> Result<Vec<u8>, DNSProtoErr> {
         let mut data = vec![];
         data.extend_from_slice(&self.priority.to_be_bytes()[..]);
         data.extend_from_slice(&self.weight.to_be_bytes()[..]);
         data.extend_from_slice(&self.port.to_be_bytes()[..]);
-        match compression {
-            Some((compression_map, size)) => {
-                let m_name = self.target.to_binary(Some((compression_map, size)));
-                data.extend_from_slice(m_name.as_slice());
-            }
-            _ => {
-                let m_name = self.target.to_binary(None);
-                data.extend_from_slice(m_name.as_slice());
-            }
-        }
+        // the target must not be compressed(rfc2782)
+        data.extend_from_slice(self.target.to_binary(None).as_slice());
         Ok(data)
     }
 
@@ -180,4 +172,25 @@ mod test {
         let srv = srv.unwrap();
         assert_eq!(srv.get_type(), DNSType::SRV);
     }
+
+    #[test]
+    fn test_srv_from_str() {
+        let srv = DnsTypeSRV::from_str("10 60 5060 bigbox", Some("example.com.")).unwrap();
+        assert_eq!(
+            srv,
+            DnsTypeSRV::new(10, 60, 5060, "bigbox.example.com.").unwrap()
+        );
+        assert_eq!(srv.to_string(), "10 60 5060 bigbox.example.com.");
+        assert_eq!(
+            DnsTypeSRV::from_str(srv.to_string().as_str(), None).unwrap(),
+            srv
+        );
+        let srv = DnsTypeSRV::from_str("0 0 0 .", None).unwrap();
+        assert_eq!(srv.to_string(), "0 0 0 .");
+
+        assert!(DnsTypeSRV::from_str("10 60 bigbox.example.com.", None).is_err());
+        assert!(DnsTypeSRV::from_str("10 60 70000 bigbox.example.com.", None).is_err());
+        assert!(DnsTypeSRV::from_str("10 60 5060 192.0.2.1", Some("example.com.")).is_err());
+        assert!(DnsTypeSRV::from_str("10 60 5060 bigbox.example.com. extra", None).is_err());
+    }
 }
@@ -0,0 +1,168 @@
+use crate::meta::DNSType;
+use crate::qtype::helper::{hex_u8_to_string, string_to_hex_u8};
+use crate::qtype::{CompressionType, DNSWireFrame};
+use nom::character::complete::{digit1, multispace0};
+use nom::combinator::rest;
+use nom::number::complete::be_u8;
+use otterlib::errors::DNSProtoErr;
+use std::any::Any;
+use std::fmt::{self, Formatter};
+use std::str::FromStr;
+
+// https://tools.ietf.org/html/rfc6698#section-2.1
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |  Cert. Usage  |   Selector    | Matching Type |               /
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+               /
+// /                                                               /
+// /                 Certificate Association Data                  /
+// /                                                               /
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// _443._tcp.www.example.com.  3600  IN  TLSA  ( 0 0 1 d2abde240d7cd3ee6b4b28c54df034b9
+//                                               7983a1d16e8a410e4561cb106618e971 )
+#[derive(Debug, PartialEq, Clone)]
+pub struct DnsTypeTLSA {
+    usage: u8,
+    selector: u8,
+    matching_type: u8,
+    data: Vec<u8>,
+}
+
+named!(parse_tlsa<&[u8], DnsTypeTLSA>,
+    do_parse!(
+        usage: be_u8>>
+        selector: be_u8>>
+        matching_type: be_u8>>
+        data: rest>>
+        (DnsTypeTLSA{
+            usage,
+            selector,
+            matching_type,
+            data: data.to_vec(),
+        }
+    )
+));
+
+impl DnsTypeTLSA {
+    pub fn new(
+        usage: u8,
+        selector: u8,
+        matching_type: u8,
+        data: Vec<u8>,
+    ) -> Result<Self, DNSProtoErr> {
+        if data.is_empty() {
+            return Err(DNSProtoErr::GeneralErr(
+                "certificate association data is empty".to_owned(),
+            ));
+        }
+        Ok(DnsTypeTLSA {
+            usage,
+            selector,
+            matching_type,
+            data,
+        })
+    }
+    pub fn usage(&self) -> u8 {
+        self.usage
+    }
+    pub fn selector(&self) -> u8 {
+        self.selector
+    }
+    pub fn matching_type(&self) -> u8 {
+        self.matching_type
+    }
+    pub fn data(&self) -> &[u8] {
+        self.data.as_slice()
+    }
+    pub fn decode(data: &[u8], _: Option<&[u8]>) -> Result<Self, DNSProtoErr> {
+        match parse_tlsa(data) {
+            Ok((_, tlsa)) if !tlsa.data.is_empty() => Ok(tlsa),
+            _ => Err(DNSProtoErr::PacketParseError),
+        }
+    }
+    // 0 0 1 D2ABDE240D7CD3EE6B4B28C54DF034B9 7983A1D16E8A410E4561CB106618E971, the data
+    // may be split by spaces
+    pub fn from_str(str: &str) -> Result<Self, DNSProtoErr> {
+        let (rest, _) = multispace0(str)?;
+        let (rest, usage) = digit1(rest)?;
+        let usage = u8::from_str(usage)?;
+        let (rest, _) = multispace0(rest)?;
+        let (rest, selector) = digit1(rest)?;
+        let selector = u8::from_str(selector)?;
+        let (rest, _) = multispace0(rest)?;
+        let (rest, matching_type) = digit1(rest)?;
+        let matching_type = u8::from_str(matching_type)?;
+        let data: String = rest.split_whitespace().collect();
+        DnsTypeTLSA::new(
+            usage,
+            selector,
+            matching_type,
+            string_to_hex_u8(data.as_str())?,
+        )
+    }
+}
+
+impl fmt::Display for DnsTypeTLSA {
+    fn fmt(&self, format: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            format,
+            "{} {} {} {}",
+            self.usage,
+            self.selector,
+            self.matching_type,
+            hex_u8_to_string(self.data.as_slice())
+        )
+    }
+}
+
+impl DNSWireFrame for DnsTypeTLSA {
+    fn get_type(&self) -> DNSType {
+        DNSType::TLSA
+    }
+
+    fn encode(&self, _: CompressionType) -> Result<Vec<u8>, DNSProtoErr> {
+        let mut data = vec![self.usage, self.selector, self.matching_type];
+        data.extend_from_slice(self.data.as_slice());
+        Ok(data)
+    }
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn clone_box(&self) -> Box<dyn DNSWireFrame> {
+        Box::new(self.clone())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::meta::DNSType;
+    use crate::qtype::tlsa::DnsTypeTLSA;
+    use crate::qtype::{decode_dns_data_from_string, DNSWireFrame};
+
+    #[test]
+    fn tlsa_round_trip() {
+        // https://tools.ietf.org/html/rfc6698#section-2.3
+        let tlsa_str = "0 0 1 D2ABDE240D7CD3EE6B4B28C54DF034B97983A1D16E8A410E4561CB106618E971";
+        let bin_arr = [
+            0x00, 0x00, 0x01, 0xd2, 0xab, 0xde, 0x24, 0x0d, 0x7c, 0xd3, 0xee, 0x6b, 0x4b, 0x28,
+            0xc5, 0x4d, 0xf0, 0x34, 0xb9, 0x79, 0x83, 0xa1, 0xd1, 0x6e, 0x8a, 0x41, 0x0e, 0x45,
+            0x61, 0xcb, 0x10, 0x66, 0x18, 0xe9, 0x71,
+        ];
+        let tlsa_struct = DnsTypeTLSA::new(0, 0, 1, bin_arr[3..].to_vec()).unwrap();
+        assert_eq!(DnsTypeTLSA::from_str(tlsa_str).unwrap(), tlsa_struct);
+        assert_eq!(tlsa_struct.to_string(), tlsa_str);
+        assert_eq!(DnsTypeTLSA::decode(&bin_arr, None).unwrap(), tlsa_struct);
+        assert_eq!(tlsa_struct.encode(None).unwrap(), bin_arr.to_vec());
+
+        let decoded = decode_dns_data_from_string(
+            "( 0 0 1 d2abde240d7cd3ee6b4b28c54df034b9 7983a1d16e8a410e4561cb106618e971 )",
+            DNSType::TLSA,
+            None,
+        )
+        .unwrap();
+        assert_eq!(decoded.to_string(), tlsa_str);
+        assert!(DnsTypeTLSA::decode(&bin_arr[..3], None).is_err());
+        assert!(DnsTypeTLSA::from_str("0 0 1").is_err());
+        assert!(DnsTypeTLSA::from_str("0 0 1 D2A").is_err());
+    }
+}
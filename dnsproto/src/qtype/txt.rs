@@ -1,50 +1,72 @@
 use crate::meta::DNSType;
+use crate::qtype::helper::{decode_character_bytes, quote_character_bytes, split_character_bytes};
 use crate::qtype::{CompressionType, DNSWireFrame};
 use otterlib::errors::DNSProtoErr;
 use std::any::Any;
 use std::fmt::{self, Formatter};
 use std::str::FromStr;
 
+// https://tools.ietf.org/html/rfc1035#section-3.3.14
+// +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+// /                   TXT-DATA                    /
+// +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+// TXT-DATA is one or more character-strings, each one is at most 255 bytes.
+// long text like dkim keys is written as multiple quoted strings in zone file:
+// default._domainkey  IN  TXT  "v=DKIM1; k=rsa; p=MIIBIjAN..." "...IDAQAB"
+
 #[derive(Debug, PartialEq, Clone)]
 pub struct DnsTypeTXT {
-    pub(crate) text: String,
+    pub(crate) text: Vec<Vec<u8>>,
 }
 
 impl FromStr for DnsTypeTXT {
     type Err = DNSProtoErr;
     fn from_str(text: &str) -> Result<Self, Self::Err> {
-        Ok(DnsTypeTXT {
-            text: text.to_string(),
-        })
+        let text = split_character_bytes(text)?;
+        if text.is_empty() {
+            return Err(DNSProtoErr::EmptyStrErr);
+        }
+        Ok(DnsTypeTXT { text })
     }
 }
 
 impl DnsTypeTXT {
     pub fn new(text: &str) -> Result<Self, DNSProtoErr> {
         Ok(DnsTypeTXT {
-            text: text.to_string(),
+            text: vec![text.as_bytes().to_vec()],
         })
     }
+    /// from_strings create the txt data from multiple character-strings,
+    /// a string longer than 255 bytes will be split when encoding.
+    pub fn from_strings(text: Vec<Vec<u8>>) -> Self {
+        DnsTypeTXT { text }
+    }
+    pub fn strings(&self) -> &[Vec<u8>] {
+        self.text.as_slice()
+    }
     pub fn decode(data: &[u8], _: Option<&[u8]>) -> Result<Self, DNSProtoErr> {
-        let data_length = data.len();
-        if data_length == 0 {
+        if data.is_empty() {
             return Err(DNSProtoErr::PacketParseError);
         }
-
-        let txt_length = data[0];
-        if data.len() < (txt_length as usize + 1) {
-            return Err(DNSProtoErr::PacketParseError);
-        }
-        match std::str::from_utf8(&data[1..=(txt_length as usize)]) {
-            Ok(text_str) => DnsTypeTXT::new(text_str),
-            _ => Err(DNSProtoErr::PacketParseError),
+        let mut text = vec![];
+        let mut offset = 0;
+        while offset < data.len() {
+            let (bytes, consumed) = decode_character_bytes(&data[offset..])?;
+            text.push(bytes.to_vec());
+            offset += consumed;
         }
+        Ok(DnsTypeTXT { text })
     }
 }
 
 impl fmt::Display for DnsTypeTXT {
     fn fmt(&self, format: &mut Formatter<'_>) -> fmt::Result {
-        write!(format, "{}", self.text)
+        let text: Vec<String> = self
+            .text
+            .iter()
+            .map(|bytes| quote_character_bytes(bytes.as_slice()))
+            .collect();
+        write!(format, "{}", text.join(" "))
     }
 }
 
@@ -53,13 +75,24 @@ impl DNSWireFrame for DnsTypeTXT {
         DNSType::TXT
     }
     fn encode(&self, _: CompressionType) -> Result<Vec<u8>, DNSProtoErr> {
-        let text_bytes = self.text.as_bytes();
-        let size = text_bytes.len();
-        if size > (u8::MAX as usize) {
+        let mut result = vec![];
+        for bytes in self.text.iter() {
+            if bytes.is_empty() {
+                result.push(0);
+                continue;
+            }
+            // split oversized string into multiple character-strings
+            for chunk in bytes.chunks(u8::MAX as usize) {
+                result.push(chunk.len() as u8);
+                result.extend_from_slice(chunk);
+            }
+        }
+        if result.is_empty() {
+            result.push(0);
+        }
+        if result.len() > u16::MAX as usize {
             return Err(DNSProtoErr::EncodeTxtLengthTooLongError);
         }
-        let mut result = vec![size as u8];
-        result.extend_from_slice(text_bytes);
         Ok(result)
     }
     fn as_any(&self) -> &dyn Any {
@@ -76,7 +109,8 @@ impl DNSWireFrame for DnsTypeTXT {
 mod test {
     use crate::meta::DNSType;
     use crate::qtype::txt::DnsTypeTXT;
-    use crate::qtype::DNSWireFrame;
+    use crate::qtype::{decode_dns_data_from_string, decode_message_data, DNSWireFrame};
+    use std::str::FromStr;
 
     #[test]
     fn test_dns_type_txt_encode() {
@@ -89,9 +123,10 @@ mod test {
             0x64, 0x75, 0x2e, 0x63, 0x6f, 0x6d, 0x20, 0x61, 0x20, 0x6d, 0x78, 0x20, 0x70, 0x74,
             0x72, 0x20, 0x2d, 0x61, 0x6c, 0x6c,
         ];
-        let text_record = DnsTypeTXT {
-            text: "v=spf1 include:spf1.baidu.com include:spf2.baidu.com include:spf3.baidu.com a mx ptr -all".to_string(),
-        };
+        let text_record = DnsTypeTXT::new(
+            "v=spf1 include:spf1.baidu.com include:spf2.baidu.com include:spf3.baidu.com a mx ptr -all",
+        )
+        .unwrap();
         match text_record.encode(None) {
             Ok(text_data) => assert_eq!(text_data, non_compression_vec),
             _ => {
@@ -105,4 +140,72 @@ mod test {
 
         assert_eq!(text_record.get_type(), DNSType::TXT);
     }
+
+    const DKIM_KEY: &str =
+        "MIIBIjANBgkqhkiG9w0BAQEFAAOCAQ8AMIIBCgKCAQEAwO3Qu2n4cZ7v0yY6bS1kXh8FqG5h\
+        Ez5Zq0bH2cJ9u1G3l9WqVdE6mQ0XbN4Yx7k5oQzQ5rK2t9Lw3pPjVh1xX7mN0fS2gKz3bY6cR8dT1uW4eQ9\
+        vJ5kL2hM7nP0sX3aB6cD9eF2gH5iJ8kL1mN4oP7qR0sT3uV6wX9yZ2aB5cD8eF1gH4iJ7kL0mN3oP6qR9s\
+        T2uV5wX8yZ1aB4cD7eF0gH3iJ6kL9mN2oP5qR8sT1uV4wX7yZ0aB3cD6eF9gH2iJ5kL8mN1oP4qR7sT0u\
+        V3wX6yZ9aB2cD5eF8gH1iJ4kL7mN0oP3qR6sT9uV2wX5yZ8aB1cD4eF7gH0iJ3kL6mN9oP2qR5sT8uV1w\
+        X4yZ7aB0cD3eF6gH9iJ2kL5mN8oP1qR4sT7uV0wX3yZ6IDAQAB";
+
+    #[test]
+    fn test_dns_type_txt_multi_string() {
+        let first = "v=DKIM1; k=rsa; p=".to_owned() + &DKIM_KEY[..200];
+        let second = &DKIM_KEY[200..];
+        let rdata = format!("\"{}\" \"{}\"", first, second);
+        let txt = DnsTypeTXT::from_str(rdata.as_str()).unwrap();
+        assert_eq!(txt.strings().len(), 2);
+        assert_eq!(txt.to_string(), rdata);
+
+        // wire format keep the segments as they are
+        let mut binary = vec![first.len() as u8];
+        binary.extend_from_slice(first.as_bytes());
+        binary.push(second.len() as u8);
+        binary.extend_from_slice(second.as_bytes());
+        let encoded = txt.encode(None).unwrap();
+        assert_eq!(encoded, binary);
+        let decoded = decode_message_data(encoded.as_slice(), &[], DNSType::TXT).unwrap();
+        assert_eq!(decoded.encode(None).unwrap(), binary);
+        assert_eq!(decoded.to_string(), rdata);
+
+        // zone file rdata with parentheses and semicolons inside the strings
+        let zone_rdata = format!("( \"{}\"\t\"{}\" )", first, second);
+        let parsed = decode_dns_data_from_string(zone_rdata.as_str(), DNSType::TXT, None).unwrap();
+        assert_eq!(parsed.encode(None).unwrap(), binary);
+    }
+
+    #[test]
+    fn test_dns_type_txt_long_string() {
+        let long = "a".repeat(300);
+        let txt = DnsTypeTXT::new(long.as_str()).unwrap();
+        let encoded = txt.encode(None).unwrap();
+        assert_eq!(encoded.len(), 302);
+        assert_eq!(encoded[0], 255);
+        assert_eq!(encoded[256], 45);
+        let decoded = DnsTypeTXT::decode(encoded.as_slice(), None).unwrap();
+        assert_eq!(decoded.strings().len(), 2);
+        assert_eq!(decoded.strings().concat(), long.as_bytes());
+    }
+
+    #[test]
+    fn test_dns_type_txt_escape() {
+        let txt = DnsTypeTXT::from_str(r#""say \"hi\"" "back\\slash" "\010" plain"#).unwrap();
+        assert_eq!(
+            txt.strings(),
+            &[
+                b"say \"hi\"".to_vec(),
+                b"back\\slash".to_vec(),
+                vec![0x0a],
+                b"plain".to_vec()
+            ]
+        );
+        assert_eq!(
+            txt.to_string(),
+            r#""say \"hi\"" "back\\slash" "\010" "plain""#
+        );
+        assert_eq!(DnsTypeTXT::from_str(txt.to_string().as_str()).unwrap(), txt);
+        assert!(DnsTypeTXT::from_str("\"unterminated").is_err());
+        assert!(DnsTypeTXT::decode(&[5, b'a'], None).is_err());
+    }
 }
@@ -0,0 +1,142 @@
+use crate::meta::DNSType;
+use crate::qtype::helper::{quote_character_string, split_character_strings};
+use crate::qtype::{CompressionType, DNSWireFrame};
+use nom::character::complete::{digit1, multispace0};
+use nom::combinator::rest;
+use nom::number::complete::be_u16;
+use otterlib::errors::DNSProtoErr;
+use std::any::Any;
+use std::fmt::{self, Formatter};
+use std::str::FromStr;
+
+// https://tools.ietf.org/html/rfc7553#section-4.5
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |          Priority             |          Weight               |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// /                                                               /
+// /                             Target                            /
+// /                                                               /
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// the target is the rest of the rdata without a length octet.
+// _ftp._tcp.example.com.  3600  IN  URI  10 1 "ftp://ftp1.example.com/public"
+#[derive(Debug, PartialEq, Clone)]
+pub struct DnsTypeURI {
+    priority: u16,
+    weight: u16,
+    target: String,
+}
+
+named!(parse_uri<&[u8], (u16, u16, &[u8])>,
+    do_parse!(
+        priority: be_u16>>
+        weight: be_u16>>
+        target: rest>>
+        ((priority, weight, target))
+));
+
+impl DnsTypeURI {
+    pub fn new(priority: u16, weight: u16, target: &str) -> Result<Self, DNSProtoErr> {
+        if target.is_empty() {
+            return Err(DNSProtoErr::GeneralErr("uri target is empty".to_owned()));
+        }
+        Ok(DnsTypeURI {
+            priority,
+            weight,
+            target: target.to_owned(),
+        })
+    }
+    pub fn priority(&self) -> u16 {
+        self.priority
+    }
+    pub fn weight(&self) -> u16 {
+        self.weight
+    }
+    pub fn target(&self) -> &str {
+        self.target.as_str()
+    }
+    pub fn decode(data: &[u8], _: Option<&[u8]>) -> Result<Self, DNSProtoErr> {
+        let (priority, weight, target) = match parse_uri(data) {
+            Ok((_, uri)) => uri,
+            Err(_err) => return Err(DNSProtoErr::PacketParseError),
+        };
+        match std::str::from_utf8(target) {
+            Ok(target) => DnsTypeURI::new(priority, weight, target),
+            Err(_) => Err(DNSProtoErr::PacketParseError),
+        }
+    }
+    // 10 1 "ftp://ftp1.example.com/public"
+    pub fn from_str(str: &str) -> Result<Self, DNSProtoErr> {
+        let (rest, _) = multispace0(str)?;
+        let (rest, priority) = digit1(rest)?;
+        let priority = u16::from_str(priority)?;
+        let (rest, _) = multispace0(rest)?;
+        let (rest, weight) = digit1(rest)?;
+        let weight = u16::from_str(weight)?;
+        let targets = split_character_strings(rest)?;
+        if targets.len() != 1 {
+            return Err(DNSProtoErr::ParseDNSFromStrError(format!(
+                "uri require one target but got: {}",
+                rest
+            )));
+        }
+        DnsTypeURI::new(priority, weight, targets[0].as_str())
+    }
+}
+
+impl fmt::Display for DnsTypeURI {
+    fn fmt(&self, format: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            format,
+            "{} {} {}",
+            self.priority,
+            self.weight,
+            quote_character_string(self.target.as_str())
+        )
+    }
+}
+
+impl DNSWireFrame for DnsTypeURI {
+    fn get_type(&self) -> DNSType {
+        DNSType::URI
+    }
+
+    fn encode(&self, _: CompressionType) -> Result<Vec<u8>, DNSProtoErr> {
+        let mut data = vec![];
+        data.extend_from_slice(&self.priority.to_be_bytes());
+        data.extend_from_slice(&self.weight.to_be_bytes());
+        data.extend_from_slice(self.target.as_bytes());
+        Ok(data)
+    }
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn clone_box(&self) -> Box<dyn DNSWireFrame> {
+        Box::new(self.clone())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::meta::DNSType;
+    use crate::qtype::uri::DnsTypeURI;
+    use crate::qtype::{decode_dns_data_from_string, DNSWireFrame};
+
+    #[test]
+    fn uri_round_trip() {
+        let uri_str = "10 1 \"ftp://ftp1.example.com/public\"";
+        let uri_struct = DnsTypeURI::new(10, 1, "ftp://ftp1.example.com/public").unwrap();
+        let mut bin_arr = vec![0x00, 0x0a, 0x00, 0x01];
+        bin_arr.extend_from_slice(b"ftp://ftp1.example.com/public");
+        assert_eq!(DnsTypeURI::from_str(uri_str).unwrap(), uri_struct);
+        assert_eq!(uri_struct.to_string(), uri_str);
+        assert_eq!(DnsTypeURI::decode(&bin_arr, None).unwrap(), uri_struct);
+        assert_eq!(uri_struct.encode(None).unwrap(), bin_arr);
+
+        let decoded = decode_dns_data_from_string(uri_str, DNSType::URI, None).unwrap();
+        assert_eq!(decoded.to_string(), uri_str);
+        assert!(DnsTypeURI::decode(&bin_arr[..4], None).is_err());
+        assert!(DnsTypeURI::from_str("10 1 \"a\" \"b\"").is_err());
+        assert!(DnsTypeURI::from_str("10 \"a\"").is_err());
+    }
+}
@@ -0,0 +1,186 @@
+use crate::meta::DNSType;
+use crate::qtype::helper::{hex_u8_to_string, string_to_hex_u8};
+use crate::qtype::{CompressionType, DNSWireFrame};
+use nom::character::complete::{digit1, multispace0};
+use nom::combinator::rest;
+use nom::number::complete::{be_u32, be_u8};
+use otterlib::errors::DNSProtoErr;
+use std::any::Any;
+use std::fmt::{self, Formatter};
+use std::str::FromStr;
+
+// https://tools.ietf.org/html/rfc8976#section-2.2
+// 1 1 1 1 1 1 1 1 1 1 2 2 2 2 2 2 2 2 2 2 3 3
+// 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1 2 3 4 5 6 7 8 9 0 1
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |                             Serial                            |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// |    Scheme     |Hash Algorithm |                               |
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+                               |
+// |                             Digest                            |
+// /                                                               /
+// +-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+-+
+// example.  86400  IN  ZONEMD  2018031900 1 1 C68090D90A7AED716BC459F9340E3D7C1370D4D24B7E2FC3A1DDC0B9A87153B9A9713B3C9AE5CC27777F98B8E730044C
+
+/// the digest is calculated over the zone in canonical order
+pub const ZONEMD_SCHEME_SIMPLE: u8 = 1;
+pub const ZONEMD_HASH_SHA384: u8 = 1;
+pub const ZONEMD_HASH_SHA512: u8 = 2;
+// digest shorter than 12 octets is invalid
+const ZONEMD_MIN_DIGEST_SIZE: usize = 12;
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct DnsTypeZONEMD {
+    serial: u32,
+    scheme: u8,
+    hash_algorithm: u8,
+    digest: Vec<u8>,
+}
+
+named!(parse_zonemd<&[u8], DnsTypeZONEMD>,
+    do_parse!(
+        serial: be_u32>>
+        scheme: be_u8>>
+        hash_algorithm: be_u8>>
+        digest: rest>>
+        (DnsTypeZONEMD{
+            serial,
+            scheme,
+            hash_algorithm,
+            digest: digest.to_vec(),
+        }
+    )
+));
+
+impl DnsTypeZONEMD {
+    pub fn new(
+        serial: u32,
+        scheme: u8,
+        hash_algorithm: u8,
+        digest: Vec<u8>,
+    ) -> Result<Self, DNSProtoErr> {
+        if digest.len() < ZONEMD_MIN_DIGEST_SIZE {
+            return Err(DNSProtoErr::GeneralErr(format!(
+                "zonemd digest size {} is less than {}",
+                digest.len(),
+                ZONEMD_MIN_DIGEST_SIZE
+            )));
+        }
+        Ok(DnsTypeZONEMD {
+            serial,
+            scheme,
+            hash_algorithm,
+            digest,
+        })
+    }
+    pub fn serial(&self) -> u32 {
+        self.serial
+    }
+    pub fn scheme(&self) -> u8 {
+        self.scheme
+    }
+    pub fn hash_algorithm(&self) -> u8 {
+        self.hash_algorithm
+    }
+    pub fn digest(&self) -> &[u8] {
+        self.digest.as_slice()
+    }
+    pub fn decode(data: &[u8], _: Option<&[u8]>) -> Result<Self, DNSProtoErr> {
+        match parse_zonemd(data) {
+            Ok((_, zonemd)) if zonemd.digest.len() >= ZONEMD_MIN_DIGEST_SIZE => Ok(zonemd),
+            _ => Err(DNSProtoErr::PacketParseError),
+        }
+    }
+    // 2018031900 1 1 C68090D90A7AED71 6BC459F9340E3D7C ..., the digest may be split by spaces
+    pub fn from_str(str: &str) -> Result<Self, DNSProtoErr> {
+        let (rest, _) = multispace0(str)?;
+        let (rest, serial) = digit1(rest)?;
+        let serial = u32::from_str(serial)?;
+        let (rest, _) = multispace0(rest)?;
+        let (rest, scheme) = digit1(rest)?;
+        let scheme = u8::from_str(scheme)?;
+        let (rest, _) = multispace0(rest)?;
+        let (rest, hash_algorithm) = digit1(rest)?;
+        let hash_algorithm = u8::from_str(hash_algorithm)?;
+        let digest: String = rest.split_whitespace().collect();
+        DnsTypeZONEMD::new(
+            serial,
+            scheme,
+            hash_algorithm,
+            string_to_hex_u8(digest.as_str())?,
+        )
+    }
+}
+
+impl fmt::Display for DnsTypeZONEMD {
+    fn fmt(&self, format: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            format,
+            "{} {} {} {}",
+            self.serial,
+            self.scheme,
+            self.hash_algorithm,
+            hex_u8_to_string(self.digest.as_slice())
+        )
+    }
+}
+
+impl DNSWireFrame for DnsTypeZONEMD {
+    fn get_type(&self) -> DNSType {
+        DNSType::ZONEMD
+    }
+
+    fn encode(&self, _: CompressionType) -> Result<Vec<u8>, DNSProtoErr> {
+        let mut data = vec![];
+        data.extend_from_slice(&self.serial.to_be_bytes());
+        data.push(self.scheme);
+        data.push(self.hash_algorithm);
+        data.extend_from_slice(self.digest.as_slice());
+        Ok(data)
+    }
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn clone_box(&self) -> Box<dyn DNSWireFrame> {
+        Box::new(self.clone())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::qtype::helper::string_to_hex_u8;
+    use crate::qtype::zonemd::{DnsTypeZONEMD, ZONEMD_HASH_SHA384, ZONEMD_SCHEME_SIMPLE};
+    use crate::qtype::DNSWireFrame;
+
+    const DIGEST: &str = "C68090D90A7AED716BC459F9340E3D7C1370D4D24B7E2FC3A1DDC0B9A87153B9A9713B3C9AE5CC27777F98B8E730044C";
+
+    #[test]
+    fn zonemd_round_trip() {
+        let zonemd_str = format!("2018031900 1 1 {}", DIGEST);
+        let zonemd_struct = DnsTypeZONEMD::new(
+            2018031900,
+            ZONEMD_SCHEME_SIMPLE,
+            ZONEMD_HASH_SHA384,
+            string_to_hex_u8(DIGEST).unwrap(),
+        )
+        .unwrap();
+        let mut bin_arr = vec![0x78, 0x48, 0xb9, 0x1c, 0x01, 0x01];
+        bin_arr.extend_from_slice(&string_to_hex_u8(DIGEST).unwrap());
+        assert_eq!(
+            DnsTypeZONEMD::from_str(zonemd_str.as_str()).unwrap(),
+            zonemd_struct
+        );
+        assert_eq!(zonemd_struct.to_string(), zonemd_str);
+        assert_eq!(
+            DnsTypeZONEMD::decode(&bin_arr, None).unwrap(),
+            zonemd_struct
+        );
+        assert_eq!(zonemd_struct.encode(None).unwrap(), bin_arr);
+
+        let split = "2018031900 1 1 c68090d90a7aed71 6bc459f9340e3d7c 1370d4d24b7e2fc3 a1ddc0b9a87153b9 a9713b3c9ae5cc27 777f98b8e730044c";
+        assert_eq!(DnsTypeZONEMD::from_str(split).unwrap(), zonemd_struct);
+        assert!(DnsTypeZONEMD::from_str("2018031900 1 1 C68090D9").is_err());
+        assert!(DnsTypeZONEMD::decode(&bin_arr[..17], None).is_err());
+    }
+}
@@ -1,10 +1,14 @@
 use crate::dnsname::DNSName;
-use crate::meta::{DNSClass, DNSType, ResourceRecord};
+use crate::meta::{DNSClass, DNSType, Provenance, ResourceRecord};
 use crate::qtype::decode_dns_data_from_string;
-use crate::utils::{is_fqdn, valid_domain};
+use crate::utils::{is_fqdn, to_ascii_domain, valid_owner_name};
 use otterlib::errors::DNSProtoErr;
 
 impl ResourceRecord {
+    /// from_zone_data parse one zone file line: `[domain] [ttl] [class] type rdata`,
+    /// ttl and class are optional and may appear in either order.
+    /// a line begin with whitespace use the default domain(owner of the previous record),
+    /// `@` stands for the default origin(the current $ORIGIN).
     #[allow(clippy::too_many_arguments)]
     pub fn from_zone_data(
         rr_str: &str,
@@ -13,57 +17,56 @@ impl ResourceRecord {
         default_domain: Option<&str>,
         default_origin: Option<&str>,
     ) -> Result<Self, DNSProtoErr> {
-        let mut is_ttl_set;
-        let is_domain_set;
-        let is_class_set;
-        let mut with_default_ttl = false;
-        let mut with_default_domain = false;
-        let default_record_class = default_class.unwrap_or(DNSClass::IN);
-        // if begin with a empty or \t then using default domain
-        if rr_str.starts_with(|s| s == ' ' || s == '\t') {
-            with_default_domain = true;
-            is_domain_set = true;
-        } else {
-            is_domain_set = false;
-        }
-        let mut name: &str = "";
-        let r_type: DNSType;
-        let r_data: String;
-        let mut r_class = default_record_class;
-        let mut ttl = 0;
-
-        // split using whitespace
-        let mut s_iter = rr_str.split_whitespace();
-        let token = s_iter.next();
-        if token.is_none() {
+        if rr_str.trim().is_empty() {
             return Err(DNSProtoErr::EmptyStrErr);
         }
-        let mut token = token.unwrap();
-        // if already set ,then parse for ttl, class or type.
-        // otherwise check if include @ replace with default domain later
-        if !is_domain_set {
-            if token.eq("@") {
-                // domain exist with @
-                with_default_domain = true;
-            } else {
-                // domain exist and been set with str
-                name = token;
+        // if begin with a empty or \t then using default domain,
+        // otherwise the first token is domain, @ will be replaced with default origin.
+        let with_default_domain = rr_str.starts_with(|s| s == ' ' || s == '\t');
+        let mut s_iter = rr_str.split_whitespace();
+        let owner = if with_default_domain {
+            None
+        } else {
+            s_iter.next()
+        };
+        let name = match owner {
+            Some("@") => match default_origin {
+                Some(origin) => origin,
+                None => return Err(DNSProtoErr::NoOriginDomainErr),
+            },
+            Some(token) => token,
+            None => match default_domain {
+                Some(default_domain_str) => default_domain_str,
+                None => return Err(DNSProtoErr::NoDefaultDomainErr),
+            },
+        };
+
+        // up to two tokens before type are ttl and class in any order
+        let mut ttl = None;
+        let mut r_class = None;
+        let rtype = loop {
+            let token = match s_iter.next() {
+                Some(token) => token,
+                None => return Err(DNSProtoErr::NoDomainTypeErr),
+            };
+            if ttl.is_none() {
+                if let Ok(t) = gen_ttl_from_token(token) {
+                    ttl = Some(t);
+                    continue;
+                }
             }
-            // get a new token
-            match s_iter.next() {
-                Some(token_str) => token = token_str,
-                // next required is domain type but got nothing
-                _ => return Err(DNSProtoErr::NoDomainTypeErr),
+            if r_class.is_none() {
+                if let Some(class) = gen_class_from_token(token) {
+                    r_class = Some(class);
+                    continue;
+                }
             }
-        }
+            break token;
+        };
 
-        if with_default_domain {
-            if let Some(default_domain_str) = default_domain {
-                name = default_domain_str;
-            } else {
-                return Err(DNSProtoErr::NoDefaultDomainErr);
-            }
-        }
+        // owner name may include U-labels
+        let ascii_name = to_ascii_domain(name)?;
+        let name = ascii_name.as_ref();
         let domain_fqdn: String;
         if !is_fqdn(name) {
             if let Some(origin) = default_origin {
@@ -74,151 +77,62 @@ impl ResourceRecord {
         } else {
             domain_fqdn = name.to_owned();
         }
-        if !valid_domain(name) {
+        if !valid_owner_name(name) {
             return Err(DNSProtoErr::ValidDomainErr(name.to_owned()));
         }
-        // token must be ttl or class or type
-        // after this code, at least we don't need care about ttl.
-        if let Ok(t) = gen_ttl_from_token(token) {
-            is_ttl_set = true;
-            ttl = t;
-        } else {
-            is_ttl_set = false;
-        }
-        if token == "IN" || token == "in" {
-            is_class_set = true;
-            r_class = DNSClass::IN;
-        } else if token == "CH" || token == "ch" {
-            is_class_set = true;
-            r_class = DNSClass::CH;
-        } else {
-            is_class_set = false;
-        }
-
-        if !is_ttl_set {
-            is_ttl_set = true;
-            with_default_ttl = true;
-        }
-
-        if is_class_set {
-            is_ttl_set = true;
-            with_default_ttl = true;
-        }
-
-        let rtype: &str;
 
-        // there are two options
-        // 1. token is ttl when is_ttl_set = true and with_default_ttl = false
-        // 2. token is dns type
-        if !is_class_set {
-            if is_ttl_set && !with_default_ttl {
-                // current token must be ttl, get a new token
-                if let Some(token) = s_iter.next() {
-                    // maybe class or type
-                    if token == "IN" || token == "in" {
-                        r_class = DNSClass::IN;
-                        // get a new type
-                        if let Some(token) = s_iter.next() {
-                            // token is domain type now
-                            rtype = token;
-                        } else {
-                            return Err(DNSProtoErr::NoDomainTypeErr);
-                        }
-                    } else if token == "CH" || token == "ch" {
-                        r_class = DNSClass::CH;
-                        // get a new type
-                        if let Some(token) = s_iter.next() {
-                            // token is domain type now
-                            rtype = token;
-                        } else {
-                            return Err(DNSProtoErr::NoDomainTypeErr);
-                        }
-                    } else {
-                        // current token is type
-                        rtype = token;
-                    }
-                } else {
-                    return Err(DNSProtoErr::NoDomainTypeErr);
-                }
-            } else {
-                // this token is domain type
-                rtype = token;
-            }
-        } else {
-            // class is been set , so get a new token must be dns type
-            if let Some(token) = s_iter.next() {
-                // token is domain type now
-                rtype = token;
-            } else {
-                return Err(DNSProtoErr::NoDomainTypeErr);
+        let r_type = match rtype.parse::<DNSType>() {
+            Ok(rtype) => rtype,
+            Err(_) => {
+                return Err(DNSProtoErr::ValidTypeErr(format!(
+                    "{} can not be recognised",
+                    rtype
+                )))
             }
-        }
-
-        // for now we only care about all required attribute
-        // : rtype rdata
-        if let Ok(rtype) = rtype.to_uppercase().parse::<DNSType>() {
-            r_type = rtype;
-        } else {
-            return Err(DNSProtoErr::ValidTypeErr(format!(
-                "{} can not be recognised",
-                rtype
-            )));
-        }
-        // rdata may include ; comment should be ignored(should remove before feed to RecordResource)
+        };
+        // rdata may include ; comment should be ignored(should remove before feed to RecordResource),
+        // names in rdata(include @) are qualified by the rdata parser with the default origin.
         let mut rest_rdata_vec = vec![];
-        let mut begin_item_processed = false;
-
         for v in s_iter {
-            if !begin_item_processed && v == "@" {
-                let fqdn: String;
-                if let Some(default_domain_str) = default_domain {
-                    // default_domain exist but may not be fqdn , we also need to get orgin
-                    if !is_fqdn(default_domain_str) {
-                        if let Some(orign) = default_origin {
-                            fqdn = format!("{}.{}", default_domain_str, orign);
-                            rest_rdata_vec.push(fqdn);
-                        } else {
-                            return Err(DNSProtoErr::NoOriginDomainErr);
-                        }
-                    } else {
-                        rest_rdata_vec.push(default_domain_str.to_owned())
-                    }
-                } else {
-                    return Err(DNSProtoErr::NoDefaultDomainErr);
-                }
-                begin_item_processed = true;
-            } else {
-                if v.starts_with(';') {
-                    break;
-                }
-                rest_rdata_vec.push(v.to_owned());
+            if v.starts_with(';') {
+                break;
             }
+            rest_rdata_vec.push(v);
         }
-        r_data = rest_rdata_vec.join(" ");
+        let r_data = rest_rdata_vec.join(" ");
 
-        // rr.r_data = s_iter.flat_map(|s| s.chars()).collect();
-        if with_default_ttl {
-            if let Some(t) = default_ttl {
-                ttl = t;
-            } else {
-                return Err(DNSProtoErr::NoDefaultTTLErr);
-            }
-        }
+        let ttl = match ttl.or(default_ttl) {
+            Some(ttl) => ttl,
+            None => return Err(DNSProtoErr::NoDefaultTTLErr),
+        };
         let dname = DNSName::new(domain_fqdn.as_str(), default_origin)?;
         match decode_dns_data_from_string(r_data.as_str(), r_type, default_origin) {
             Ok(data) => Ok(ResourceRecord {
                 name: dname,
                 qtype: r_type,
-                qclass: r_class,
+                qclass: r_class.or(default_class).unwrap_or_default(),
                 ttl,
                 data: Some(data),
+                provenance: Provenance::File,
             }),
             Err(err) => Err(err),
         }
     }
 }
 
+/// gen_class_from_token parse the class of the record data, the query only classes ANY
+/// and NONE are not valid in zone files.
+fn gen_class_from_token(token: &str) -> Option<DNSClass> {
+    match token.parse::<DNSClass>() {
+        Ok(DNSClass::ANY) | Ok(DNSClass::NONE) | Err(_) => None,
+        Ok(class) => Some(class),
+    }
+}
+
 fn gen_ttl_from_token(token: &str) -> Result<u32, DNSProtoErr> {
+    if !token.starts_with(|c: char| c.is_ascii_digit()) {
+        return Err(DNSProtoErr::ParseDNSFromStrError(token.to_owned()));
+    }
     let mut ttl: u32 = 0;
     let mut temp: u32 = 0;
     for i in token.chars() {
@@ -243,7 +157,7 @@ fn gen_ttl_from_token(token: &str) -> Result<u32, DNSProtoErr> {
 #[cfg(test)]
 mod record {
     use crate::dnsname::DNSName;
-    use crate::meta::{DNSClass, DNSType, ResourceRecord};
+    use crate::meta::{DNSClass, DNSType, Provenance, ResourceRecord};
     use crate::qtype::{DnsTypeA, DnsTypeNS};
     use crate::record::gen_ttl_from_token;
     use otterlib::errors::DNSProtoErr;
@@ -259,6 +173,7 @@ mod record {
             qclass: DNSClass::IN,
             ttl: 86400,
             data: Some(Box::new(DnsTypeA::new("192.0.2.3").unwrap())),
+            provenance: Provenance::File,
         };
         match ResourceRecord::try_from(raw_rr) {
             Ok(rr) => {
@@ -279,6 +194,7 @@ mod record {
             qclass: DNSClass::IN,
             ttl: 86400,
             data: Some(Box::new(DnsTypeA::new("192.0.2.3").unwrap())),
+            provenance: Provenance::File,
         };
         match ResourceRecord::try_from(raw_rr) {
             Ok(rr) => {
@@ -299,6 +215,7 @@ mod record {
             qclass: DNSClass::IN,
             ttl: 86400,
             data: Some(Box::new(DnsTypeA::new("192.0.2.3").unwrap())),
+            provenance: Provenance::File,
         };
         match ResourceRecord::try_from(raw_rr) {
             Ok(rr) => {
@@ -320,7 +237,8 @@ mod record {
                 qtype: DNSType::A,
                 qclass: DNSClass::IN,
                 ttl: 86400,
-                data: Some(Box::new(DnsTypeA::new("192.0.2.3").unwrap()))
+                data: Some(Box::new(DnsTypeA::new("192.0.2.3").unwrap())),
+                provenance: Provenance::File,
             }
         );
 
@@ -334,7 +252,8 @@ mod record {
                 qtype: DNSType::A,
                 qclass: DNSClass::IN,
                 ttl: 1000,
-                data: Some(Box::new(DnsTypeA::new("192.0.2.3").unwrap()))
+                data: Some(Box::new(DnsTypeA::new("192.0.2.3").unwrap())),
+                provenance: Provenance::File,
             }
         );
         // TODO:
@@ -364,7 +283,8 @@ mod record {
                 qtype: DNSType::A,
                 qclass: DNSClass::IN,
                 ttl: 1000,
-                data: Some(Box::new(DnsTypeA::new("192.0.2.3").unwrap()))
+                data: Some(Box::new(DnsTypeA::new("192.0.2.3").unwrap())),
+                provenance: Provenance::File,
             }
         );
 
@@ -374,14 +294,100 @@ mod record {
         assert_eq!(
             rr.unwrap(),
             ResourceRecord {
-                name: DNSName::new("mail.google.com.", None).unwrap(),
+                name: DNSName::new("google.com.", None).unwrap(),
                 qtype: DNSType::NS,
                 qclass: DNSClass::IN,
                 ttl: 86400,
-                data: Some(Box::new(DnsTypeNS::new("mail.google.com.").unwrap()))
+                data: Some(Box::new(DnsTypeNS::new("google.com.").unwrap())),
+                provenance: Provenance::File,
             }
         );
+
+        // @ is the origin not the owner of the previous record
+        let s = "@  86400  IN  NS    @";
+        let rr =
+            ResourceRecord::from_zone_data(s, Some(1000), None, Some("mail.google.com."), None);
+        assert_eq!(rr.unwrap_err(), DNSProtoErr::NoOriginDomainErr);
     }
+    #[test]
+    fn test_from_zone_data_field_matrix() {
+        // (line, expected name, ttl, class), default domain is mail., default ttl is 1000
+        // and default class is IN
+        let tcs = [
+            ("www.  300 CH A 1.2.3.4", "www.", 300, DNSClass::CH),
+            ("www.  CH 300 A 1.2.3.4", "www.", 300, DNSClass::CH),
+            ("www.  300 A 1.2.3.4", "www.", 300, DNSClass::IN),
+            ("www.  CH A 1.2.3.4", "www.", 1000, DNSClass::CH),
+            ("www.  A 1.2.3.4", "www.", 1000, DNSClass::IN),
+            (
+                "www   ch 1h A 1.2.3.4",
+                "www.example.com.",
+                3600,
+                DNSClass::CH,
+            ),
+            ("  300 CH A 1.2.3.4", "mail.", 300, DNSClass::CH),
+            ("  CH 300 A 1.2.3.4", "mail.", 300, DNSClass::CH),
+            ("  300 A 1.2.3.4", "mail.", 300, DNSClass::IN),
+            ("  CH A 1.2.3.4", "mail.", 1000, DNSClass::CH),
+            ("\tA 1.2.3.4", "mail.", 1000, DNSClass::IN),
+            ("@ HS 60 A 1.2.3.4", "example.com.", 60, DNSClass::HS),
+            // rfc3597 generic class and type
+            ("www.  CLASS3 300 A 1.2.3.4", "www.", 300, DNSClass::CH),
+            ("www.  300 IN TYPE1 1.2.3.4", "www.", 300, DNSClass::IN),
+            (
+                "_dmarc 60 A 1.2.3.4",
+                "_dmarc.example.com.",
+                60,
+                DNSClass::IN,
+            ),
+            (
+                "*._tcp 60 A 1.2.3.4",
+                "*._tcp.example.com.",
+                60,
+                DNSClass::IN,
+            ),
+        ];
+        for (line, name, ttl, class) in tcs.iter() {
+            let rr = ResourceRecord::from_zone_data(
+                line,
+                Some(1000),
+                Some(DNSClass::IN),
+                Some("mail."),
+                Some("example.com."),
+            );
+            assert!(rr.is_ok(), format!("{}: {:?}", line, rr.unwrap_err()));
+            assert_eq!(
+                rr.unwrap(),
+                ResourceRecord {
+                    name: DNSName::new(name, None).unwrap(),
+                    qtype: DNSType::A,
+                    qclass: *class,
+                    ttl: *ttl,
+                    data: Some(Box::new(DnsTypeA::new("1.2.3.4").unwrap())),
+                    provenance: Provenance::File,
+                },
+                "{}",
+                line
+            );
+        }
+
+        let rr = ResourceRecord::from_zone_data("www CH 300 A 1.2.3.4", None, None, None, None);
+        assert_eq!(rr.unwrap_err(), DNSProtoErr::NoOriginDomainErr);
+        let rr = ResourceRecord::from_zone_data("www. CH A 1.2.3.4", None, None, None, None);
+        assert_eq!(rr.unwrap_err(), DNSProtoErr::NoDefaultTTLErr);
+        let rr = ResourceRecord::from_zone_data("  A 1.2.3.4", Some(1000), None, None, None);
+        assert_eq!(rr.unwrap_err(), DNSProtoErr::NoDefaultDomainErr);
+        let rr = ResourceRecord::from_zone_data("www. 300 IN", Some(1000), None, None, None);
+        assert_eq!(rr.unwrap_err(), DNSProtoErr::NoDomainTypeErr);
+        let rr = ResourceRecord::from_zone_data("www. 300 IN CH A 1.2.3.4", None, None, None, None);
+        assert!(rr.is_err());
+        // ANY and NONE are only used in queries and updates
+        let rr = ResourceRecord::from_zone_data("www. 300 ANY A 1.2.3.4", None, None, None, None);
+        assert!(rr.is_err());
+        let rr = ResourceRecord::from_zone_data("   ", None, None, None, None);
+        assert_eq!(rr.unwrap_err(), DNSProtoErr::EmptyStrErr);
+    }
+
     #[test]
     fn test_gen_ttl_from_token() {
         let tcs = [
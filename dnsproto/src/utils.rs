@@ -1,3 +1,6 @@
+use crate::dnsname::DNSName;
+use otterlib::errors::DNSProtoErr;
+use std::borrow::Cow;
 use std::collections::hash_map::DefaultHasher;
 use std::hash::{Hash, Hasher};
 
@@ -20,7 +23,9 @@ pub fn is_fqdn(domain: &str) -> bool {
 
 static MAX_DOMAIN_LENGTH: usize = 255;
 
-pub fn valid_domain(domain: &str) -> bool {
+/// valid_hostname check the rfc1912 host name rules, only use it where a host name is
+/// required(like the NS and MX targets), owner names are checked by valid_owner_name.
+pub fn valid_hostname(domain: &str) -> bool {
     let domain_str = fqdn(domain);
     if domain_str.len() > MAX_DOMAIN_LENGTH {
         return false;
@@ -54,6 +59,23 @@ pub fn valid_domain(domain: &str) -> bool {
     true
 }
 
+/// valid_owner_name only check the label and name length limits, labels like _dmarc,
+/// the wildcard * and escaped binary labels are legal owner names.
+pub fn valid_owner_name(domain: &str) -> bool {
+    let domain_str = fqdn(domain);
+    if domain_str == "." {
+        return true;
+    }
+    // empty labels are skipped by DNSName::new
+    if domain_str.starts_with('.') || domain_str.contains("..") {
+        return false;
+    }
+    match DNSName::new(domain_str.as_str(), None) {
+        Ok(name) => name.to_binary(None).len() <= MAX_DOMAIN_LENGTH,
+        Err(_) => false,
+    }
+}
+
 pub fn is_safe_ascii(c_char: char, is_first: bool) -> bool {
     match c_char {
         check_c_char if !check_c_char.is_ascii() => false,
@@ -67,6 +89,101 @@ pub fn is_safe_ascii(c_char: char, is_first: bool) -> bool {
     }
 }
 
+/// to_ascii_domain convert the non-ascii labels(U-labels) of the domain to the
+/// punycode A-labels, for example 中文.example. will be xn--fiq228c.example.
+/// ascii labels are kept as they are.
+pub fn to_ascii_domain(domain: &str) -> Result<Cow<str>, DNSProtoErr> {
+    if domain.is_ascii() {
+        return Ok(Cow::from(domain));
+    }
+    let mut labels = vec![];
+    for label in domain.split('.') {
+        if label.is_ascii() {
+            labels.push(label.to_owned());
+            continue;
+        }
+        match idna::domain_to_ascii(label) {
+            Ok(a_label) => labels.push(a_label),
+            Err(err) => {
+                return Err(DNSProtoErr::ParseZoneDataErr(format!(
+                    "invalid idn label {} in {}: {:?}",
+                    label, domain, err
+                )))
+            }
+        }
+    }
+    Ok(Cow::from(labels.join(".")))
+}
+
+/// to_unicode_label convert a punycode A-label back to the U-label,
+/// return None if the label is not a valid A-label.
+pub fn to_unicode_label(label: &str) -> Option<String> {
+    if !label.to_lowercase().starts_with("xn--") {
+        return None;
+    }
+    match idna::domain_to_unicode(label) {
+        (u_label, Ok(())) => Some(u_label),
+        _ => None,
+    }
+}
+
+/// unquoted_chars return the chars and their byte index which are not inside a quoted
+/// character-string and not escaped, used to locate comments and parentheses.
+fn unquoted_chars(line: &str) -> Vec<(usize, char)> {
+    let mut result = vec![];
+    let mut quoted = false;
+    let mut escaped = false;
+    for (index, c) in line.char_indices() {
+        if escaped {
+            escaped = false;
+            continue;
+        }
+        match c {
+            '\\' => escaped = true,
+            '"' => quoted = !quoted,
+            _ if !quoted => result.push((index, c)),
+            _ => {}
+        }
+    }
+    result
+}
+
+/// find_comment return the index of the ';' which starts a comment,
+/// semicolons inside quoted strings are not comments.
+pub fn find_comment(line: &str) -> Option<usize> {
+    unquoted_chars(line)
+        .into_iter()
+        .find(|(_, c)| *c == ';')
+        .map(|(index, _)| index)
+}
+
+/// count_parentheses return the number of '(' and ')' outside quoted strings.
+pub fn count_parentheses(line: &str) -> (usize, usize) {
+    unquoted_chars(line)
+        .into_iter()
+        .fold((0, 0), |(left, right), (_, c)| match c {
+            '(' => (left + 1, right),
+            ')' => (left, right + 1),
+            _ => (left, right),
+        })
+}
+
+/// remove_parentheses replace the '(' and ')' outside quoted strings with a space, so
+/// "admin.(1" still has two fields.
+pub fn remove_parentheses(line: &str) -> String {
+    let removed: Vec<usize> = unquoted_chars(line)
+        .into_iter()
+        .filter(|(_, c)| *c == '(' || *c == ')')
+        .map(|(index, _)| index)
+        .collect();
+    line.char_indices()
+        .map(|(index, c)| match removed.contains(&index) {
+            true => ' ',
+            false => c,
+        })
+        .collect()
+}
+
 pub fn calculate_hash<T: Hash>(val: &T) -> u64 {
     let mut default_hash = DefaultHasher::new();
     val.hash(&mut default_hash);
@@ -94,18 +211,77 @@ mod utils {
         assert_eq!(is_fqdn(""), false);
     }
     #[test]
-    fn test_valid_domain() {
-        assert_eq!(valid_domain("www.baidu.com"), true);
-        assert_eq!(valid_domain(""), true);
-        assert_eq!(valid_domain("_.baidu.com"), false);
-        assert_eq!(valid_domain("..com"), false);
-        assert_eq!(valid_domain(".2.com"), false);
-        assert_eq!(valid_domain("123.baidu.com"), true);
+    fn test_valid_hostname() {
+        assert_eq!(valid_hostname("www.baidu.com"), true);
+        assert_eq!(valid_hostname(""), true);
+        assert_eq!(valid_hostname("_.baidu.com"), false);
+        assert_eq!(valid_hostname("..com"), false);
+        assert_eq!(valid_hostname(".2.com"), false);
+        assert_eq!(valid_hostname("123.baidu.com"), true);
         let oversize_domain: String = vec!['a'; 256].into_iter().collect();
-        assert_eq!(valid_domain(oversize_domain.as_str()), false);
+        assert_eq!(valid_hostname(oversize_domain.as_str()), false);
 
         let oversize_label: String = vec!['a'; 64].into_iter().collect();
-        assert_eq!(valid_domain((oversize_label + ".com").as_str()), false);
+        assert_eq!(valid_hostname((oversize_label + ".com").as_str()), false);
+    }
+    #[test]
+    fn test_valid_owner_name() {
+        for name in [
+            "_dmarc.example.com",
+            "*._tcp.example.com.",
+            "_443._tcp.www.example.com.",
+            "\\000binary.example.com.",
+            "mail-1.example.com.",
+            ".",
+        ]
+        .iter()
+        {
+            assert!(valid_owner_name(name), "{}", name);
+        }
+        let oversize_label: String = vec!['a'; 64].into_iter().collect();
+        let oversize_domain: String = vec!["abcdefghij"; 26].join(".");
+        for name in [
+            "..com",
+            ".example.com",
+            "a b.example.com",
+            oversize_label.as_str(),
+            oversize_domain.as_str(),
+        ]
+        .iter()
+        {
+            assert!(!valid_owner_name(name), "{}", name);
+        }
+    }
+    #[test]
+    fn test_unquoted_chars() {
+        let line = r#"a TXT "v=DKIM1; k=rsa; (x)" "say \"hi;\"" ; comment"#;
+        assert_eq!(find_comment(line), Some(line.rfind(';').unwrap()));
+        assert_eq!(find_comment("a TXT \"no comment;\""), None);
+        assert_eq!(count_parentheses(line), (0, 0));
+        assert_eq!(count_parentheses("SOA ( 1 \"(\" 2"), (1, 0));
+        assert_eq!(
+            remove_parentheses("( \"(x)\" 1 )"),
+            "  \"(x)\" 1  ".to_owned()
+        );
+    }
+    #[test]
+    fn test_to_ascii_domain() {
+        assert_eq!(
+            to_ascii_domain("www.Example.com.").unwrap(),
+            "www.Example.com."
+        );
+        assert_eq!(
+            to_ascii_domain("中文.example.").unwrap(),
+            "xn--fiq228c.example."
+        );
+        assert_eq!(
+            to_ascii_domain("www.Bücher.example").unwrap(),
+            "www.xn--bcher-kva.example"
+        );
+        assert!(to_ascii_domain("\u{301}abc.example.").is_err());
+
+        assert_eq!(to_unicode_label("xn--fiq228c"), Some("中文".to_owned()));
+        assert_eq!(to_unicode_label("example"), None);
     }
     #[test]
     fn test_is_safe_ascii() {
@@ -0,0 +1,489 @@
+use crate::dnsname::DNSName;
+use crate::message::Message;
+use crate::meta::{DNSType, RCode, ResourceRecord};
+use crate::qtype::DnsTypeSOA;
+use otterlib::errors::DNSProtoErr;
+
+/// XfrKind is the format of the transfer told by its first records.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum XfrKind {
+    /// the full zone, also the answer of an ixfr request the server can't answer
+    /// incrementally
+    Axfr,
+    /// the changes since the serial of the request
+    Ixfr,
+    /// only the soa of the server, the zone of the request is up to date
+    UpToDate,
+}
+
+/// IxfrDiff is one change sequence of an incremental transfer, the deleted records
+/// turn the zone of the old soa into the zone of the new soa with the added records.
+#[derive(Debug, Clone, PartialEq)]
+pub struct IxfrDiff {
+    pub old_soa: DnsTypeSOA,
+    pub new_soa: DnsTypeSOA,
+    pub deleted: Vec<ResourceRecord>,
+    pub added: Vec<ResourceRecord>,
+}
+
+/// XfrChunk is the part of the transfer completed by a message.
+#[derive(Debug, Clone, PartialEq)]
+pub enum XfrChunk {
+    /// the records of a full transfer in the message, the opening soa is the first record
+    /// of the first chunk and the closing soa is left out
+    Records(Vec<ResourceRecord>),
+    /// a change sequence of an incremental transfer
+    Diff(IxfrDiff),
+    /// the soa of the server when the zone is up to date
+    UpToDate(DnsTypeSOA),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum XfrState {
+    // waiting for the opening soa
+    Start,
+    // the record after the opening soa tells the format
+    Opened,
+    // the records of a full transfer until the closing soa
+    Full,
+    // the deleted records of a diff until its new soa
+    Deleting,
+    // the added records of a diff until the old soa of the next diff or the closing soa
+    Adding,
+    Complete,
+}
+
+/// XfrResponseParser consume the messages of a zone transfer read from a tcp stream one
+/// by one, and yield the full zone records or the incremental diffs(rfc1995 section 4)
+/// as soon as they are complete. the transfer is complete after the closing soa, the
+/// message id is checked by the caller.
+#[derive(Debug)]
+pub struct XfrResponseParser {
+    zone: DNSName,
+    // the serial sent in the ixfr request, None for axfr
+    current_serial: Option<u32>,
+    state: XfrState,
+    kind: Option<XfrKind>,
+    opening: Option<ResourceRecord>,
+    diff: Option<IxfrDiff>,
+}
+
+impl XfrResponseParser {
+    /// new_axfr return the parser of the response to Message::new_axfr_query.
+    pub fn new_axfr(zone: DNSName) -> XfrResponseParser {
+        XfrResponseParser::new(zone, None)
+    }
+
+    /// new_ixfr return the parser of the response to Message::new_ixfr_query with the
+    /// current serial.
+    pub fn new_ixfr(zone: DNSName, current_serial: u32) -> XfrResponseParser {
+        XfrResponseParser::new(zone, Some(current_serial))
+    }
+
+    fn new(zone: DNSName, current_serial: Option<u32>) -> XfrResponseParser {
+        XfrResponseParser {
+            zone,
+            current_serial,
+            state: XfrState::Start,
+            kind: None,
+            opening: None,
+            diff: None,
+        }
+    }
+
+    /// kind return the format of the transfer, None until it is known.
+    pub fn kind(&self) -> Option<XfrKind> {
+        self.kind
+    }
+
+    /// is_complete return true after the closing soa is received.
+    pub fn is_complete(&self) -> bool {
+        self.state == XfrState::Complete
+    }
+
+    /// push consume the next message of the transfer and return the chunks it completes.
+    pub fn push(&mut self, message: &Message) -> Result<Vec<XfrChunk>, DNSProtoErr> {
+        if message.header.rcode() != RCode::NoError {
+            return Err(invalid(format!(
+                "transfer return {:?}",
+                message.header.rcode()
+            )));
+        }
+        let mut chunks = vec![];
+        let mut records = vec![];
+        for rr in message.answers() {
+            self.push_record(rr, &mut records, &mut chunks)?;
+        }
+        if !records.is_empty() {
+            chunks.push(XfrChunk::Records(records));
+        }
+        // a response with the soa only means the zone of the request is up to date
+        if let (XfrState::Opened, Some(current)) = (self.state, self.current_serial) {
+            let soa = self.opening_soa()?.clone();
+            if !serial_newer(soa.serial(), current) {
+                self.kind = Some(XfrKind::UpToDate);
+                self.state = XfrState::Complete;
+                chunks.push(XfrChunk::UpToDate(soa));
+            }
+        }
+        Ok(chunks)
+    }
+
+    fn push_record(
+        &mut self,
+        rr: &ResourceRecord,
+        records: &mut Vec<ResourceRecord>,
+        chunks: &mut Vec<XfrChunk>,
+    ) -> Result<(), DNSProtoErr> {
+        let soa = match rr.get_type() {
+            DNSType::SOA => Some(
+                rr.rdata_as::<DnsTypeSOA>()
+                    .ok_or_else(|| invalid("soa without data".to_owned()))?,
+            ),
+            _ => None,
+        };
+        match (self.state, soa) {
+            (XfrState::Complete, _) => return Err(invalid("records after the closing soa".into())),
+            (XfrState::Start, Some(_)) if rr.get_dname() == &self.zone => {
+                self.opening = Some(rr.clone());
+                self.state = XfrState::Opened;
+            }
+            (XfrState::Start, _) => {
+                return Err(invalid("transfer not start with the zone soa".into()))
+            }
+            (XfrState::Opened, Some(soa)) if soa.serial() != self.opening_soa()?.serial() => {
+                self.kind = Some(XfrKind::Ixfr);
+                self.state = XfrState::Deleting;
+                self.diff = Some(IxfrDiff {
+                    old_soa: soa.clone(),
+                    new_soa: soa.clone(),
+                    deleted: vec![],
+                    added: vec![],
+                });
+            }
+            (XfrState::Opened, soa) => {
+                // the zone has the soa only when the soa is repeated at once
+                self.kind = Some(XfrKind::Axfr);
+                records.extend(self.opening.clone());
+                self.state = match soa {
+                    Some(_) => XfrState::Complete,
+                    None => {
+                        records.push(rr.clone());
+                        XfrState::Full
+                    }
+                };
+            }
+            (XfrState::Full, Some(soa)) => {
+                if soa != self.opening_soa()? {
+                    return Err(invalid("closing soa differs from the opening soa".into()));
+                }
+                self.state = XfrState::Complete;
+            }
+            (XfrState::Full, None) => records.push(rr.clone()),
+            (XfrState::Deleting, Some(soa)) => {
+                if let Some(diff) = self.diff.as_mut() {
+                    diff.new_soa = soa.clone();
+                }
+                self.state = XfrState::Adding;
+            }
+            (XfrState::Deleting, None) => {
+                if let Some(diff) = self.diff.as_mut() {
+                    diff.deleted.push(rr.clone());
+                }
+            }
+            (XfrState::Adding, Some(soa)) => {
+                let diff = self
+                    .diff
+                    .take()
+                    .ok_or_else(|| invalid("diff not started".into()))?;
+                let last = diff.new_soa.serial() == self.opening_soa()?.serial();
+                if last && soa != self.opening_soa()? {
+                    return Err(invalid("closing soa differs from the opening soa".into()));
+                }
+                if !last && soa.serial() != diff.new_soa.serial() {
+                    return Err(invalid(
+                        "diff not start from the serial of the last one".into(),
+                    ));
+                }
+                chunks.push(XfrChunk::Diff(diff));
+                if last {
+                    self.state = XfrState::Complete;
+                    return Ok(());
+                }
+                self.diff = Some(IxfrDiff {
+                    old_soa: soa.clone(),
+                    new_soa: soa.clone(),
+                    deleted: vec![],
+                    added: vec![],
+                });
+                self.state = XfrState::Deleting;
+            }
+            (XfrState::Adding, None) => {
+                if let Some(diff) = self.diff.as_mut() {
+                    diff.added.push(rr.clone());
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn opening_soa(&self) -> Result<&DnsTypeSOA, DNSProtoErr> {
+        self.opening
+            .as_ref()
+            .and_then(|rr| rr.rdata_as::<DnsTypeSOA>())
+            .ok_or_else(|| invalid("transfer not start with the zone soa".into()))
+    }
+}
+
+fn invalid(reason: String) -> DNSProtoErr {
+    DNSProtoErr::InvalidXfrResponse(reason)
+}
+
+// serial_newer compare the serials with the sequence space arithmetic(rfc1982).
+fn serial_newer(serial: u32, than: u32) -> bool {
+    serial != than && serial.wrapping_sub(than) < 0x8000_0000
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::meta::DNSClass;
+    use crate::qtype::{DNSWireFrame, DnsTypeA};
+
+    fn soa(serial: u32) -> DnsTypeSOA {
+        DnsTypeSOA::new(
+            "ns.example.com.",
+            "root.example.com.",
+            serial,
+            7200,
+            3600,
+            1209600,
+            3600,
+        )
+        .unwrap()
+    }
+
+    fn soa_rr(serial: u32) -> ResourceRecord {
+        ResourceRecord::new(
+            "example.com.",
+            DNSType::SOA,
+            DNSClass::IN,
+            3600,
+            Some(Box::new(soa(serial))),
+        )
+        .unwrap()
+    }
+
+    fn a_rr(name: &str, address: &str) -> ResourceRecord {
+        let data: Box<dyn DNSWireFrame> = Box::new(DnsTypeA::new(address).unwrap());
+        ResourceRecord::new(name, DNSType::A, DNSClass::IN, 300, Some(data)).unwrap()
+    }
+
+    /// response pack each group of records in a message sent back for the query, the
+    /// messages are encoded without the tcp length and parsed like they are read from the
+    /// stream.
+    fn responses(query: &Message, groups: Vec<Vec<ResourceRecord>>) -> Vec<Message> {
+        groups
+            .into_iter()
+            .map(|records| {
+                let mut message = Message::new_transfer_message(query);
+                for rr in records {
+                    message.append_answer(rr);
+                }
+                Message::parse_dns_message(&message.encode(true).unwrap()).unwrap()
+            })
+            .collect()
+    }
+
+    fn parse_all(
+        parser: &mut XfrResponseParser,
+        messages: &[Message],
+    ) -> Result<Vec<XfrChunk>, DNSProtoErr> {
+        let mut chunks = vec![];
+        for message in messages {
+            chunks.extend(parser.push(message)?);
+        }
+        Ok(chunks)
+    }
+
+    fn zone() -> DNSName {
+        DNSName::new("example.com.", None).unwrap()
+    }
+
+    #[test]
+    fn test_xfr_query() {
+        let mut query = Message::new_axfr_query(&zone());
+        let parsed = Message::parse_dns_message(&query.encode(true).unwrap()).unwrap();
+        assert_eq!(
+            parsed.query_name_and_type().unwrap(),
+            (&zone(), &DNSType::AXFR)
+        );
+        assert!(parsed.authorities().is_empty());
+
+        let mut query = Message::new_ixfr_query(&zone(), &soa(10));
+        let parsed = Message::parse_dns_message(&query.encode(true).unwrap()).unwrap();
+        assert_eq!(
+            parsed.query_name_and_type().unwrap(),
+            (&zone(), &DNSType::IXFR)
+        );
+        let authorities = parsed.authorities();
+        assert_eq!(authorities.len(), 1);
+        assert_eq!(authorities[0].get_dname(), &zone());
+        assert_eq!(authorities[0].rdata_as::<DnsTypeSOA>(), Some(&soa(10)));
+    }
+
+    #[test]
+    fn test_axfr_response() {
+        let query = Message::new_axfr_query(&zone());
+        let messages = responses(
+            &query,
+            vec![
+                vec![soa_rr(10), a_rr("www.example.com.", "192.0.2.1")],
+                vec![a_rr("mail.example.com.", "192.0.2.2"), soa_rr(10)],
+            ],
+        );
+        let mut parser = XfrResponseParser::new_axfr(zone());
+        let chunks = parser.push(&messages[0]).unwrap();
+        assert_eq!(parser.kind(), Some(XfrKind::Axfr));
+        assert!(!parser.is_complete());
+        assert_eq!(
+            chunks,
+            vec![XfrChunk::Records(vec![
+                soa_rr(10),
+                a_rr("www.example.com.", "192.0.2.1")
+            ])]
+        );
+        let chunks = parser.push(&messages[1]).unwrap();
+        assert!(parser.is_complete());
+        assert_eq!(
+            chunks,
+            vec![XfrChunk::Records(vec![a_rr(
+                "mail.example.com.",
+                "192.0.2.2"
+            )])]
+        );
+        assert!(parser.push(&messages[1]).is_err(), "transfer is complete");
+
+        // the zone has the soa only
+        let messages = responses(&query, vec![vec![soa_rr(10), soa_rr(10)]]);
+        let mut parser = XfrResponseParser::new_axfr(zone());
+        let chunks = parse_all(&mut parser, &messages).unwrap();
+        assert!(parser.is_complete());
+        assert_eq!(chunks, vec![XfrChunk::Records(vec![soa_rr(10)])]);
+
+        for records in vec![
+            vec![a_rr("www.example.com.", "192.0.2.1"), soa_rr(10)],
+            vec![
+                soa_rr(10),
+                a_rr("www.example.com.", "192.0.2.1"),
+                soa_rr(11),
+            ],
+        ] {
+            let messages = responses(&query, vec![records]);
+            let mut parser = XfrResponseParser::new_axfr(zone());
+            match parse_all(&mut parser, &messages) {
+                Err(DNSProtoErr::InvalidXfrResponse(_)) => {}
+                other => panic!("expect invalid response, got {:?}", other),
+            }
+        }
+    }
+
+    #[test]
+    fn test_ixfr_response() {
+        let query = Message::new_ixfr_query(&zone(), &soa(10));
+        // 10 -> 11 change the www address, 11 -> 12 add mail
+        let messages = responses(
+            &query,
+            vec![
+                vec![
+                    soa_rr(12),
+                    soa_rr(10),
+                    a_rr("www.example.com.", "192.0.2.1"),
+                    soa_rr(11),
+                ],
+                vec![
+                    a_rr("www.example.com.", "192.0.2.10"),
+                    soa_rr(11),
+                    soa_rr(12),
+                    a_rr("mail.example.com.", "192.0.2.2"),
+                    soa_rr(12),
+                ],
+            ],
+        );
+        let mut parser = XfrResponseParser::new_ixfr(zone(), 10);
+        assert!(parser.push(&messages[0]).unwrap().is_empty());
+        assert_eq!(parser.kind(), Some(XfrKind::Ixfr));
+        let chunks = parser.push(&messages[1]).unwrap();
+        assert!(parser.is_complete());
+        assert_eq!(
+            chunks,
+            vec![
+                XfrChunk::Diff(IxfrDiff {
+                    old_soa: soa(10),
+                    new_soa: soa(11),
+                    deleted: vec![a_rr("www.example.com.", "192.0.2.1")],
+                    added: vec![a_rr("www.example.com.", "192.0.2.10")],
+                }),
+                XfrChunk::Diff(IxfrDiff {
+                    old_soa: soa(11),
+                    new_soa: soa(12),
+                    deleted: vec![],
+                    added: vec![a_rr("mail.example.com.", "192.0.2.2")],
+                }),
+            ]
+        );
+
+        // the diffs must follow each other
+        let messages = responses(
+            &query,
+            vec![vec![
+                soa_rr(12),
+                soa_rr(10),
+                soa_rr(11),
+                soa_rr(9),
+                soa_rr(12),
+            ]],
+        );
+        let mut parser = XfrResponseParser::new_ixfr(zone(), 10);
+        assert!(parse_all(&mut parser, &messages).is_err());
+    }
+
+    #[test]
+    fn test_ixfr_fallback_to_axfr() {
+        let query = Message::new_ixfr_query(&zone(), &soa(10));
+        let messages = responses(
+            &query,
+            vec![
+                vec![soa_rr(12)],
+                vec![a_rr("www.example.com.", "192.0.2.1"), soa_rr(12)],
+            ],
+        );
+        let mut parser = XfrResponseParser::new_ixfr(zone(), 10);
+        // the newer soa alone does not tell the format
+        assert!(parser.push(&messages[0]).unwrap().is_empty());
+        assert_eq!(parser.kind(), None);
+        let chunks = parser.push(&messages[1]).unwrap();
+        assert_eq!(parser.kind(), Some(XfrKind::Axfr));
+        assert!(parser.is_complete());
+        assert_eq!(
+            chunks,
+            vec![XfrChunk::Records(vec![
+                soa_rr(12),
+                a_rr("www.example.com.", "192.0.2.1")
+            ])]
+        );
+    }
+
+    #[test]
+    fn test_ixfr_up_to_date() {
+        let query = Message::new_ixfr_query(&zone(), &soa(10));
+        let messages = responses(&query, vec![vec![soa_rr(10)]]);
+        let mut parser = XfrResponseParser::new_ixfr(zone(), 10);
+        let chunks = parse_all(&mut parser, &messages).unwrap();
+        assert!(parser.is_complete());
+        assert_eq!(parser.kind(), Some(XfrKind::UpToDate));
+        assert_eq!(chunks, vec![XfrChunk::UpToDate(soa(10))]);
+        assert!(serial_newer(0, u32::MAX));
+        assert!(!serial_newer(10, 10));
+    }
+}
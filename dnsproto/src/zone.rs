@@ -1,27 +1,66 @@
 use crate::meta::{DNSClass, ResourceRecord};
-use crate::utils::{is_fqdn, valid_domain};
+use crate::utils::{
+    count_parentheses, find_comment, is_fqdn, remove_parentheses, to_ascii_domain, valid_owner_name,
+};
 use otterlib::errors::DNSProtoErr;
 use regex::Regex;
 use std::fs::File;
 use std::io::{self, BufRead};
 use std::path::Path;
 
-pub struct ZoneStr<'a> {
+/// ZoneTextReader split zone data in a string into logical lines, comments are removed and
+/// multi-line records in parentheses are joined into one line.
+///
+/// ```
+/// use dnsproto::meta::DNSType;
+/// use dnsproto::zone::{ZoneReader, ZoneTextReader};
+///
+/// let text = "$TTL 3600
+/// @    IN  SOA  ns1 hostmaster ( 2021010101 7200 3600 1209600 3600 )
+/// www  IN  A    192.0.2.1 ; web server";
+/// let mut reader = ZoneReader::new(ZoneTextReader::new(text), Some("example.com.".to_owned()));
+/// let soa = reader.next().unwrap().unwrap();
+/// assert_eq!(soa.get_type(), DNSType::SOA);
+/// let www = reader.next().unwrap().unwrap();
+/// assert_eq!(www.get_dname().to_string(), "www.example.com.");
+/// assert_eq!(reader.current_ttl(), Some(3600));
+/// assert!(reader.next().is_none());
+/// ```
+pub struct ZoneTextReader<'a> {
     data: Option<&'a str>,
     empty_line_checker: Regex,
+    // lines taken from the data
+    read: usize,
+    // first line of the last logical line
+    line: usize,
 }
 
-impl<'a> ZoneStr<'a> {
-    #[allow(dead_code)]
-    pub fn new(data: &'a str) -> ZoneStr {
-        ZoneStr {
+impl<'a> ZoneTextReader<'a> {
+    pub fn new(data: &'a str) -> ZoneTextReader {
+        ZoneTextReader {
             data: Some(data),
             empty_line_checker: Regex::new(r"^\s*$").unwrap(),
+            read: 0,
+            line: 0,
         }
     }
 }
 
-impl<'a> Iterator for ZoneStr<'a> {
+/// ZoneLines is the logical lines of a zone file or text, the line number is kept for the
+/// error messages.
+pub trait ZoneLines: Iterator<Item = String> {
+    /// line return the number of the first line of the last logical line, 0 before any
+    /// line is read.
+    fn line(&self) -> usize;
+}
+
+impl<'a> ZoneLines for ZoneTextReader<'a> {
+    fn line(&self) -> usize {
+        self.line
+    }
+}
+
+impl<'a> Iterator for ZoneTextReader<'a> {
     type Item = String;
 
     fn next(&mut self) -> Option<Self::Item> {
@@ -35,7 +74,9 @@ impl<'a> Iterator for ZoneStr<'a> {
                 let remainder = self.data.as_mut()?;
                 if let Some(next) = remainder.find(|c: char| c == '\n' || c == '\r') {
                     let mut sep_len = 1;
-                    if remainder[next + 1..].starts_with('\r') {
+                    if remainder[next + 1..].starts_with('\r')
+                        || remainder[next..].starts_with("\r\n")
+                    {
                         sep_len = 2
                     }
                     let until_delimiter = &remainder[..next];
@@ -50,16 +91,19 @@ impl<'a> Iterator for ZoneStr<'a> {
                     }
                 }
                 if let Some(line) = new_line.as_mut() {
+                    self.read += 1;
                     // Comments start with a semicolon ";" and go to the end of line.
-                    if let Some(line_cutter) = line.find(';') {
+                    if let Some(line_cutter) = find_comment(line) {
                         *line = &line[..line_cutter];
                     }
                     // Empty lines are allowed; any combination of tabs and spaces acts as a delimiter.
                     if line.is_empty() || self.empty_line_checker.is_match(line) {
                         break 'inner;
                     }
-                    let left_bracked = line.matches('(').count();
-                    let right_bracked = line.matches(')').count();
+                    if joined_line.is_empty() {
+                        self.line = self.read;
+                    }
+                    let (left_bracked, right_bracked) = count_parentheses(line);
                     multi_line = multi_line + left_bracked - right_bracked;
                     joined_line.push(line.to_string());
                     if multi_line == 0 {
@@ -76,21 +120,22 @@ impl<'a> Iterator for ZoneStr<'a> {
         if joined_line.is_empty() {
             return None;
         }
-        let mut result: String = joined_line.join("");
-        result = result.replace("(", "");
-        result = result.replace(")", "");
-        Some(result)
+        // the lines of a multi-line record are separated by a space
+        Some(remove_parentheses(joined_line.join(" ").as_str()))
     }
 }
 
+/// ZoneReader parse the lines from a ZoneTextReader or ZoneFileParser into resource records,
+/// and track the $ORIGIN, $TTL directives and the owner, class of the previous record.
 pub struct ZoneReader<T>
 where
-    T: Iterator<Item = String>,
+    T: ZoneLines,
 {
     line_iterator: T,
     // because the directive ,for example $ORIGIN, $TTL and $INCLUDE
-    // we need hold those information for update
-    default_origin: Option<String>,
+    // we need hold those information for update.
+    // current_origin start with the origin passed to new and updated by $ORIGIN
+    current_origin: Option<String>,
     current_ttl: Option<u32>,
     current_class: Option<DNSClass>,
     current_domain: Option<String>,
@@ -98,9 +143,9 @@ where
 
 impl<T> ZoneReader<T>
 where
-    T: Iterator<Item = String>,
+    T: ZoneLines,
 {
-    #[allow(dead_code)]
+    /// new a zone reader from a line iterator, the default origin must be fqdn if set.
     pub fn new(line_iterator: T, default_origin: Option<String>) -> ZoneReader<T> {
         if let Some(ref origin) = default_origin {
             // must be fqdn
@@ -113,10 +158,38 @@ where
             current_domain: None,
             current_class: None,
             current_ttl: None,
-            default_origin,
+            current_origin: default_origin,
         }
     }
 
+    /// set the ttl used when the zone file has no $TTL directive
+    pub fn with_default_ttl(mut self, ttl: Option<u32>) -> ZoneReader<T> {
+        if self.current_ttl.is_none() {
+            self.current_ttl = ttl;
+        }
+        self
+    }
+
+    /// the origin used for relative names, updated by $ORIGIN
+    pub fn current_origin(&self) -> Option<&str> {
+        self.current_origin.as_deref()
+    }
+
+    /// the ttl for records without ttl, updated by $TTL and each record
+    pub fn current_ttl(&self) -> Option<u32> {
+        self.current_ttl
+    }
+
+    /// the class of the previous record
+    pub fn current_class(&self) -> Option<DNSClass> {
+        self.current_class
+    }
+
+    /// the line number of the last record or directive read, 0 before any line is read
+    pub fn line(&self) -> usize {
+        self.line_iterator.line()
+    }
+
     fn update_ttl(&mut self, ttl: u32) {
         self.current_ttl = Some(ttl);
     }
@@ -129,22 +202,28 @@ where
         // line is start with $ then split it take second token.
         let mut spliter = line.split_whitespace();
         match spliter.next() {
-            Some(token) if token.to_uppercase().eq("$TTL") => {
-                if let Ok(token) = spliter.next().unwrap().parse::<u32>() {
-                    self.update_ttl(token);
-                } else {
-                    return Err(DNSProtoErr::ValidTTLErr(line));
-                }
-            }
+            Some(token) if token.to_uppercase().eq("$TTL") => match spliter.next() {
+                Some(token) => match token.parse::<u32>() {
+                    Ok(ttl) => self.update_ttl(ttl),
+                    Err(_) => return Err(DNSProtoErr::ValidTTLErr(token.to_owned())),
+                },
+                None => return Err(DNSProtoErr::ValidTTLErr(line)),
+            },
             Some(token) if token.to_uppercase().eq("$ORIGIN") => {
-                let origin = spliter.next().unwrap();
-                if is_fqdn(origin) && valid_domain(origin) {
-                    self.default_origin = Some(origin.to_owned());
+                let origin = match spliter.next() {
+                    Some(origin) => to_ascii_domain(origin)?,
+                    None => return Err(DNSProtoErr::ValidOriginErr(line)),
+                };
+                let origin = origin.as_ref();
+                if is_fqdn(origin) && valid_owner_name(origin) {
+                    self.current_origin = Some(origin.to_owned());
                 } else {
-                    return Err(DNSProtoErr::ValidTTLErr(origin.to_owned()));
+                    return Err(DNSProtoErr::ValidOriginErr(origin.to_owned()));
                 }
             }
-            Some(val) if val.to_uppercase().eq("$INCLUDE") => unimplemented!(),
+            Some(val) if val.to_uppercase().eq("$INCLUDE") => {
+                return Err(DNSProtoErr::UnImplementedError(line));
+            }
             // started with $ but unknown
             _ => {
                 return Err(DNSProtoErr::GeneralErr(format!(
@@ -159,7 +238,7 @@ where
 
 impl<T> Iterator for ZoneReader<T>
 where
-    T: Iterator<Item = String>,
+    T: ZoneLines,
 {
     type Item = Result<ResourceRecord, DNSProtoErr>;
 
@@ -175,7 +254,7 @@ where
                     self.current_ttl,
                     self.current_class,
                     self.current_domain.as_deref(),
-                    self.default_origin.as_deref(),
+                    self.current_origin.as_deref(),
                 ) {
                     Ok(rr) => {
                         self.current_domain = Some(rr.name.to_string());
@@ -191,19 +270,45 @@ where
     }
 }
 
+/// ZoneFileParser split a zone file into logical lines like ZoneTextReader.
+///
+/// ```
+/// use dnsproto::zone::{ZoneFileParser, ZoneReader};
+///
+/// let path = std::env::temp_dir().join("otter_zone_file_parser_doc.zone");
+/// std::fs::write(
+///     &path,
+///     "example.com. 3600 IN SOA ns1.example.com. hostmaster.example.com. (
+///         2021010101 ; serial
+///         7200 3600 1209600 3600 )
+/// ns1 IN A 192.0.2.53",
+/// )
+/// .unwrap();
+/// let parser = ZoneFileParser::new(path.to_str().unwrap()).unwrap();
+/// let reader = ZoneReader::new(parser, Some("example.com.".to_owned()));
+/// let records: Vec<_> = reader.map(|rr| rr.unwrap()).collect();
+/// assert_eq!(records.len(), 2);
+/// assert_eq!(records[1].get_dname().to_string(), "ns1.example.com.");
+/// std::fs::remove_file(&path).unwrap();
+/// ```
 pub struct ZoneFileParser {
     lines: io::Result<io::Lines<io::BufReader<File>>>,
     empty_line_checker: Regex,
+    // lines taken from the file
+    read: usize,
+    // first line of the last logical line
+    line: usize,
 }
 
 impl ZoneFileParser {
-    #[allow(dead_code)]
     pub fn new(path: &str) -> Result<ZoneFileParser, DNSProtoErr> {
         // check file exist
         match std::fs::metadata(path) {
             Ok(_) => Ok(ZoneFileParser {
                 lines: read_lines(path),
                 empty_line_checker: Regex::new(r"^\s*$").unwrap(),
+                read: 0,
+                line: 0,
             }),
             Err(err) => Err(DNSProtoErr::IOError {
                 path: path.to_string(),
@@ -213,6 +318,12 @@ impl ZoneFileParser {
     }
 }
 
+impl ZoneLines for ZoneFileParser {
+    fn line(&self) -> usize {
+        self.line
+    }
+}
+
 impl Iterator for ZoneFileParser {
     type Item = String;
     fn next(&mut self) -> Option<Self::Item> {
@@ -230,8 +341,9 @@ impl Iterator for ZoneFileParser {
                 let new_line;
                 match line_iter.next() {
                     Some(Ok(_line)) => {
+                        self.read += 1;
                         // Comments start with a semicolon ";" and go to the end of line.
-                        if let Some(line_cutter) = _line.find(|x| x == ';') {
+                        if let Some(line_cutter) = find_comment(_line.as_str()) {
                             new_line = _line.split_at(line_cutter).0.to_owned();
                         } else {
                             new_line = _line;
@@ -242,8 +354,11 @@ impl Iterator for ZoneFileParser {
                         {
                             break 'inner;
                         }
-                        multi_line = multi_line + new_line.matches('(').count()
-                            - new_line.matches(')').count();
+                        if joined_line.is_empty() {
+                            self.line = self.read;
+                        }
+                        let (left_bracked, right_bracked) = count_parentheses(new_line.as_str());
+                        multi_line = multi_line + left_bracked - right_bracked;
                         joined_line.push(new_line.clone());
                         if multi_line == 0 {
                             break 'outer;
@@ -276,12 +391,13 @@ where
 #[cfg(test)]
 mod zone {
     use crate::dnsname::DNSName;
-    use crate::meta::{DNSClass, DNSType};
-    use crate::zone::{ZoneReader, ZoneStr};
+    use crate::meta::{DNSClass, DNSType, ResourceRecord};
+    use crate::zone::{ZoneReader, ZoneTextReader};
+    use otterlib::errors::DNSProtoErr;
 
     #[test]
     fn test_zone_str_iterator() {
-        let zone_str = ZoneStr::new(
+        let zone_str = ZoneTextReader::new(
             "ns            IN  A     192.0.2.2             ; IPv4 address for ns.example.com
               IN  AAAA  2001:db8:10::2        ; IPv6 address for ns.example.com",
         );
@@ -296,14 +412,14 @@ mod zone {
         );
         assert_eq!(iter.next(), None);
 
-        let zone_str = ZoneStr::new("$ORIGIN example.com.     ; designates the start of this zone file in the namespace
+        let zone_str = ZoneTextReader::new("$ORIGIN example.com.     ; designates the start of this zone file in the namespace
 $TTL 3600                ; default expiration time (in seconds) of all RRs without their own TTL value");
         let mut iter = zone_str.into_iter();
         assert_eq!(iter.next(), Some("$ORIGIN example.com.     ".to_owned()));
         assert_eq!(iter.next(), Some("$TTL 3600                ".to_owned()));
         assert_eq!(iter.next(), None);
 
-        let zone_str = ZoneStr::new("$ORIGIN example.com.     ; designates the start of this zone file in the namespace
+        let zone_str = ZoneTextReader::new("$ORIGIN example.com.     ; designates the start of this zone file in the namespace
 
 
 $TTL 3600                ; default expiration time (in seconds) of all RRs without their own TTL value");
@@ -312,7 +428,7 @@ $TTL 3600                ; default expiration time (in seconds) of all RRs witho
         assert_eq!(iter.next(), Some("$TTL 3600                ".to_owned()));
         assert_eq!(iter.next(), None);
 
-        let zone_str = ZoneStr::new(
+        let zone_str = ZoneTextReader::new(
             "
 
 
@@ -330,7 +446,7 @@ ns            IN  A     192.0.2.2             ; IPv4 address for ns.example.com
         );
         assert_eq!(iter.next(), None);
 
-        let zone_str = ZoneStr::new(
+        let zone_str = ZoneTextReader::new(
             "
 
 
@@ -345,7 +461,7 @@ ns            IN  A     192.0.2.2             ; IPv4 address for ns.example.com
         );
         assert_eq!(iter.next(), None);
 
-        let zone_str = ZoneStr::new(
+        let zone_str = ZoneTextReader::new(
             ".			86391 IN SOA a.root-servers.net. nstld.verisign-grs.com. (
 				2020091101 ; serial
 				1800       ; refresh (30 minutes)
@@ -355,10 +471,10 @@ ns            IN  A     192.0.2.2             ; IPv4 address for ns.example.com
 				)",
         );
         let mut iter = zone_str.into_iter();
-        assert_eq!(iter.next(), Some(".\t\t\t86391 IN SOA a.root-servers.net. nstld.verisign-grs.com. \t\t\t\t2020091101 \t\t\t\t1800       \t\t\t\t900        \t\t\t\t604800     \t\t\t\t86400      \t\t\t\t".to_owned()));
+        assert_eq!(iter.next(), Some(".\t\t\t86391 IN SOA a.root-servers.net. nstld.verisign-grs.com.   \t\t\t\t2020091101  \t\t\t\t1800        \t\t\t\t900         \t\t\t\t604800      \t\t\t\t86400       \t\t\t\t ".to_owned()));
         assert_eq!(iter.next(), None);
 
-        let zone_str = ZoneStr::new(
+        let zone_str = ZoneTextReader::new(
             ".			180017	IN	NS	e.root-servers.net.
 .			180017	IN	NS	d.root-servers.net.
 .			180017	IN	NS	l.root-servers.net.",
@@ -378,7 +494,7 @@ ns            IN  A     192.0.2.2             ; IPv4 address for ns.example.com
         );
         assert_eq!(iter.next(), None);
 
-        let zone_str = ZoneStr::new(
+        let zone_str = ZoneTextReader::new(
             "www.baidu.com.		176	IN	CNAME	www.a.shifen.com.
 www.a.shifen.com.	300	IN	A	61.135.185.32
 www.a.shifen.com.	300	IN	A	61.135.169.121
@@ -400,27 +516,27 @@ www.a.shifen.com.	300	IN	A	61.135.169.121
         );
         assert_eq!(iter.next(), None);
 
-        let zone_str = ZoneStr::new("");
+        let zone_str = ZoneTextReader::new("");
         let mut iter = zone_str.into_iter();
         assert_eq!(iter.next(), None);
 
-        let zone_str = ZoneStr::new("; comment");
+        let zone_str = ZoneTextReader::new("; comment");
         let mut iter = zone_str.into_iter();
         assert_eq!(iter.next(), None);
 
-        let zone_str = ZoneStr::new("\r   \r\r\r and this a new line;with comment \r end!");
+        let zone_str = ZoneTextReader::new("\r   \r\r\r and this a new line;with comment \r end!");
         let mut iter = zone_str.into_iter();
         assert_eq!(iter.next(), Some(" and this a new line".to_owned()));
         assert_eq!(iter.next(), Some(" end!".to_owned()));
 
-        let zone_str = ZoneStr::new("\r;comment   \r\r\r ;comment\r ;comment");
+        let zone_str = ZoneTextReader::new("\r;comment   \r\r\r ;comment\r ;comment");
         let mut iter = zone_str.into_iter();
         assert_eq!(iter.next(), None);
     }
 
     #[test]
     fn test_zone_iterator() {
-        let zone_str = ZoneStr::new(
+        let zone_str = ZoneTextReader::new(
             "ns      86400      IN  A     192.0.2.2             ; IPv4 address for ns.example.com
               IN  AAAA  2001:db8:10::2        ; IPv6 address for ns.example.com",
         );
@@ -443,7 +559,7 @@ www.a.shifen.com.	300	IN	A	61.135.169.121
             }
         }
 
-        let zone_str = ZoneStr::new(
+        let zone_str = ZoneTextReader::new(
             "ns          IN  A     192.0.2.2             ; IPv4 address for ns.example.com
               IN  AAAA  2001:db8:10::2        ; IPv6 address for ns.example.com",
         );
@@ -455,7 +571,7 @@ www.a.shifen.com.	300	IN	A	61.135.169.121
             }
         }
 
-        let zone_str = ZoneStr::new(
+        let zone_str = ZoneTextReader::new(
             "ns.    86400      IN  A     192.0.2.2             ; IPv4 address for ns.example.com
               IN  AAAA  2001:db8:10::2        ; IPv6 address for ns.example.com",
         );
@@ -475,7 +591,7 @@ www.a.shifen.com.	300	IN	A	61.135.169.121
             }
         }
 
-        let zone_str = ZoneStr::new(
+        let zone_str = ZoneTextReader::new(
             "\
 ; otter.fun DNS zonefile
 $TTL 14400
@@ -505,7 +621,7 @@ otter.fun.   14400   IN	TXT	google-site-verification=zxIkMo9ruPbMyGMy4KWbc0QkOoN
             assert_eq!(v.ttl, 86400);
             assert_eq!(
                 (v.data.unwrap().as_ref()).to_string(),
-                "ns1.domain.com. user.mail.com. ( 2020081601 3600 7200 1209600 86400 )"
+                "ns1.domain.com. user.mail.com. 2020081601 3600 7200 1209600 86400"
             );
         }
         if let Some(Ok(v)) = zone.next() {
@@ -534,8 +650,7 @@ otter.fun.   14400   IN	TXT	google-site-verification=zxIkMo9ruPbMyGMy4KWbc0QkOoN
             assert_eq!(v.qtype, DNSType::MX);
             assert_eq!(v.qclass, DNSClass::IN);
             assert_eq!(v.ttl, 14400);
-            // TODO: impl
-            // assert_eq!((v.data.unwrap().as_ref()).to_string(),"0 g33k.fun.");
+            assert_eq!((v.data.unwrap().as_ref()).to_string(), "0 g33k.fun.");
         }
         assert_eq!(zone.current_class, Some(DNSClass::IN));
         assert_eq!(zone.current_ttl, Some(14400));
@@ -544,16 +659,14 @@ otter.fun.   14400   IN	TXT	google-site-verification=zxIkMo9ruPbMyGMy4KWbc0QkOoN
             assert_eq!(v.qtype, DNSType::CNAME);
             assert_eq!(v.ttl, 14400);
             assert_eq!(v.qclass, DNSClass::IN);
-            // TODO: impl
-            // assert_eq!((v.data.unwrap().as_ref()).to_string(),"0 g33k.fun.");
+            assert_eq!((v.data.unwrap().as_ref()).to_string(), "otter.fun.");
         }
         if let Some(Ok(v)) = zone.next() {
             assert_eq!(v.name, DNSName::new("www.otter.fun.", None).unwrap());
             assert_eq!(v.qtype, DNSType::CNAME);
             assert_eq!(v.ttl, 14400);
             assert_eq!(v.qclass, DNSClass::IN);
-            // TODO: impl
-            // assert_eq!((v.data.unwrap().as_ref()).to_string(),"0 g33k.fun.");
+            assert_eq!((v.data.unwrap().as_ref()).to_string(), "otter.fun.");
         }
         if let Some(Ok(v)) = zone.next() {
             assert_eq!(v.name, DNSName::new("ftp.otter.fun.", None).unwrap());
@@ -568,7 +681,10 @@ otter.fun.   14400   IN	TXT	google-site-verification=zxIkMo9ruPbMyGMy4KWbc0QkOoN
             assert_eq!(v.qtype, DNSType::TXT);
             assert_eq!(v.qclass, DNSClass::IN);
             assert_eq!(v.ttl, 14400);
-            // assert_eq!((v.data.unwrap().as_ref()).to_string(), "\"v=spf1 +a +mx +ip4:1.1.1.1 ~all\"");
+            assert_eq!(
+                (v.data.unwrap().as_ref()).to_string(),
+                "\"v=spf1 +a +mx +ip4:1.1.1.1 ~all\""
+            );
         }
         if let Some(Ok(v)) = zone.next() {
             assert_eq!(
@@ -578,16 +694,197 @@ otter.fun.   14400   IN	TXT	google-site-verification=zxIkMo9ruPbMyGMy4KWbc0QkOoN
             assert_eq!(v.qtype, DNSType::TXT);
             assert_eq!(v.qclass, DNSClass::IN);
             assert_eq!(v.ttl, 14400);
-            // assert_eq!(
-            //     v.data,
-            //     "\"v=DKIM1; k=rsa; p=MIIBIjANBgkqhkiG9w0BAQEFAAOCAQ;\""
-            // );
+            assert_eq!(
+                (v.data.unwrap().as_ref()).to_string(),
+                "\"v=DKIM1; k=rsa; p=MIIBIjANBgkqhkiG9w0BAQEFAAOCAQ;\""
+            );
         }
         if let Some(Ok(v)) = zone.next() {
             assert_eq!(v.name, DNSName::new("otter.fun.", None).unwrap());
             assert_eq!(v.qtype, DNSType::TXT);
             assert_eq!(v.qclass, DNSClass::IN);
             assert_eq!(v.ttl, 14400);
+            assert_eq!(
+                (v.data.unwrap().as_ref()).to_string(),
+                "\"google-site-verification=zxIkMo9ruPbMyGMy4KWbc0QkOoN9aF2iFPvDHc0o8Pg\""
+            );
+        }
+
+        // $ORIGIN switch halfway, relative names and @ follow the new origin
+        let zone_str = ZoneTextReader::new(
+            "\
+$TTL 3600
+$ORIGIN example.com.
+@       IN  NS    ns
+www     IN  A     192.0.2.1
+$ORIGIN sub.example.com.
+@       IN  NS    ns
+www     IN  A     192.0.2.2
+        IN  MX    10 @
+$ORIGIN",
+        );
+        let mut zone = ZoneReader::new(zone_str, Some("otter.fun.".to_owned()));
+        let expected = [
+            ("example.com.", DNSType::NS, "ns.example.com."),
+            ("www.example.com.", DNSType::A, "192.0.2.1"),
+            ("sub.example.com.", DNSType::NS, "ns.sub.example.com."),
+            ("www.sub.example.com.", DNSType::A, "192.0.2.2"),
+            ("www.sub.example.com.", DNSType::MX, "10 sub.example.com."),
+        ];
+        for (name, qtype, data) in expected.iter() {
+            match zone.next() {
+                Some(Ok(v)) => {
+                    assert_eq!(v.name, DNSName::new(name, None).unwrap());
+                    assert_eq!(v.qtype, *qtype);
+                    assert_eq!((v.data.unwrap().as_ref()).to_string(), *data);
+                }
+                _ => assert!(false, format!("{} {} not found", name, qtype)),
+            }
+        }
+        assert_eq!(zone.current_origin, Some("sub.example.com.".to_owned()));
+        match zone.next() {
+            Some(Err(DNSProtoErr::ValidOriginErr(_))) => {}
+            _ => assert!(false),
+        }
+    }
+
+    #[test]
+    fn test_zone_with_relative_rdata() {
+        let zone_str = ZoneTextReader::new(
+            "\
+$TTL 3600
+@       IN  SOA    ns1 hostmaster ( 2021010101 7200 3600 1209600 3600 )
+@       IN  NS     ns1
+www     IN  CNAME  @
+ftp     IN  CNAME  www
+1       IN  PTR    host
+@       IN  MX     10 mail
+_sip._udp IN SRV   10 60 5060 sip
+www     IN  NSEC   ftp A RRSIG NSEC
+@       IN  RP     admin info
+@       IN  AFSDB  1 afsdb
+www     IN  RRSIG  A 13 3 3600 20210422050000 20210409040000 14631 @ AHPsJm1PtK2/PRBKpxT58RAy/Yq22IKfxAtSyG9khdeS
+",
+        );
+        let zone = ZoneReader::new(zone_str, Some("example.com.".to_owned()));
+        let expected = [
+            (
+                DNSType::SOA,
+                "ns1.example.com. hostmaster.example.com. 2021010101 7200 3600 1209600 3600",
+            ),
+            (DNSType::NS, "ns1.example.com."),
+            (DNSType::CNAME, "example.com."),
+            (DNSType::CNAME, "www.example.com."),
+            (DNSType::PTR, "host.example.com."),
+            (DNSType::MX, "10 mail.example.com."),
+            (DNSType::SRV, "10 60 5060 sip.example.com."),
+            (DNSType::NSEC, "ftp.example.com. A RRSIG NSEC"),
+            (DNSType::RP, "admin.example.com. info.example.com."),
+            (DNSType::AFSDB, "1 afsdb.example.com."),
+        ];
+        let records: Vec<ResourceRecord> = zone.map(|rr| rr.unwrap()).collect();
+        assert_eq!(records.len(), expected.len() + 1);
+        for (rr, (qtype, data)) in records.iter().zip(expected.iter()) {
+            assert_eq!(rr.qtype, *qtype);
+            assert_eq!(rr.data.as_ref().unwrap().to_string(), *data);
+        }
+        let rrsig = records.last().unwrap();
+        assert_eq!(rrsig.qtype, DNSType::RRSIG);
+        assert!(rrsig
+            .data
+            .as_ref()
+            .unwrap()
+            .to_string()
+            .contains(" 14631 example.com. "));
+
+        // relative names in rdata need an origin
+        for rdata in [
+            "IN SOA ns1 hostmaster.example.com. 1 7200 3600 1209600 3600",
+            "IN NS ns1",
+            "IN CNAME @",
+            "IN PTR host",
+            "IN MX 10 mail",
+            "IN SRV 10 60 5060 sip",
+            "IN NSEC ftp A",
+            "IN RP admin.example.com. info",
+            "IN AFSDB 1 afsdb",
+            "IN RRSIG A 13 3 3600 20210422050000 20210409040000 14631 example AHPsJm1P",
+        ]
+        .iter()
+        {
+            let line = format!("www.example.com. 3600 {}", rdata);
+            let mut zone = ZoneReader::new(ZoneTextReader::new(&line), None);
+            match zone.next() {
+                Some(Err(DNSProtoErr::NoOriginDomainErr)) => {}
+                other => assert!(false, "{} should fail without origin: {:?}", rdata, other),
+            }
+        }
+    }
+
+    #[test]
+    fn test_zone_with_malformed_directive() {
+        for directive in [
+            "$TTL",
+            "$TTL abc",
+            "$INCLUDE other.zone",
+            "$ORIGIN",
+            "$UNKNOWN 1",
+        ]
+        .iter()
+        {
+            let zone_str = ZoneTextReader::new(directive);
+            let mut zone = ZoneReader::new(zone_str, Some("example.com.".to_owned()));
+            match zone.next() {
+                Some(Err(_)) => {}
+                _ => assert!(false, "{} should return an error", directive),
+            }
+        }
+    }
+
+    #[test]
+    fn test_zone_with_idn_origin() {
+        let zone_str = ZoneTextReader::new(
+            "$ORIGIN 中文.example.
+$TTL 3600
+中文.example.  IN  NS    ns.中文.example.
+www     IN  A     192.0.2.1
+邮件    IN  CNAME www",
+        );
+        let mut zone = ZoneReader::new(zone_str, None);
+        match zone.next() {
+            Some(Ok(v)) => {
+                assert_eq!(v.name, DNSName::new("xn--fiq228c.example.", None).unwrap());
+                assert_eq!(v.qtype, DNSType::NS);
+                assert_eq!(
+                    (v.data.unwrap().as_ref()).to_string(),
+                    "ns.xn--fiq228c.example."
+                );
+            }
+            _ => assert!(false),
+        }
+        match zone.next() {
+            Some(Ok(v)) => {
+                assert_eq!(v.name.to_string(), "www.xn--fiq228c.example.");
+                assert_eq!(format!("{:#}", v.name), "www.中文.example.");
+            }
+            _ => assert!(false),
+        }
+        match zone.next() {
+            Some(Ok(v)) => {
+                assert_eq!(v.name.to_string(), "xn--5nq051n.xn--fiq228c.example.");
+                assert_eq!(
+                    (v.data.unwrap().as_ref()).to_string(),
+                    "www.xn--fiq228c.example."
+                );
+            }
+            _ => assert!(false),
+        }
+
+        let zone_str = ZoneTextReader::new("$ORIGIN \u{301}abc.example.");
+        let mut zone = ZoneReader::new(zone_str, None);
+        match zone.next() {
+            Some(Err(DNSProtoErr::ParseZoneDataErr(_))) => {}
+            _ => assert!(false),
         }
     }
 }
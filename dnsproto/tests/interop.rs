@@ -0,0 +1,441 @@
+//! interop tests of the wire captures in tests/captures, each capture is parsed, encoded
+//! by our encoder, parsed again and compared, then the encoded bytes are checked where the
+//! rfcs mandate the wire layout.
+//!
+//! the captures are one message per file, hex bytes with `;` comment lines. they are
+//! assembled from the rfc wire formats in the layout BIND, Knot and Unbound emit (names
+//! compressed to their first occurrence, the OPT record last, 8 byte client and 16 byte
+//! server cookies), a capture recorded from a live server can replace any of them as long
+//! as the assertions of its test still describe it.
+use dnsproto::message::Message;
+use dnsproto::meta::{DNSType, RCode, ResourceRecord};
+use dnsproto::qtype::DnsTypeTXT;
+use std::ops::Range;
+
+const OPT: u16 = 41;
+
+macro_rules! capture {
+    ($name:expr) => {
+        decode_capture(include_str!(concat!("captures/", $name, ".hex")))
+    };
+}
+
+fn decode_capture(text: &str) -> Vec<u8> {
+    let hex: String = text
+        .lines()
+        .filter(|line| !line.starts_with(';'))
+        .flat_map(|line| line.split_whitespace())
+        .collect();
+    (0..hex.len())
+        .step_by(2)
+        .map(|index| u8::from_str_radix(&hex[index..index + 2], 16).expect("hex byte"))
+        .collect()
+}
+
+fn be16(wire: &[u8], offset: usize) -> u16 {
+    u16::from_be_bytes([wire[offset], wire[offset + 1]])
+}
+
+// WireRecord is the layout of a record walked without decoding the rdata
+#[derive(Debug)]
+struct WireRecord {
+    owner: usize,
+    rtype: u16,
+    rdata: Range<usize>,
+}
+
+// skip_name return the offset after the name, each compression pointer must point backward
+// to a prior occurrence in the message(rfc1035 section 4.1.4).
+fn skip_name(wire: &[u8], mut offset: usize) -> usize {
+    let mut end = None;
+    loop {
+        let length = wire[offset] as usize;
+        if length & 0xc0 == 0xc0 {
+            let target = ((length & 0x3f) << 8) | wire[offset + 1] as usize;
+            assert!(
+                (12..offset).contains(&target),
+                "pointer at {} to {} is not a prior name",
+                offset,
+                target
+            );
+            end.get_or_insert(offset + 2);
+            offset = target;
+        } else if length == 0 {
+            return end.unwrap_or(offset + 1);
+        } else {
+            assert!(length <= 63, "label at {} is {} bytes", offset, length);
+            offset += 1 + length;
+        }
+    }
+}
+
+// assert_uncompressed check the name at offset is written without compression pointers
+fn assert_uncompressed(wire: &[u8], mut offset: usize) {
+    while wire[offset] != 0 {
+        assert!(wire[offset] < 0x40, "name at {} is compressed", offset);
+        offset += 1 + wire[offset] as usize;
+    }
+}
+
+// walk return the records of the message, the questions are skipped
+fn walk(wire: &[u8]) -> Vec<WireRecord> {
+    let mut offset = 12;
+    for _ in 0..be16(wire, 4) {
+        offset = skip_name(wire, offset) + 4;
+    }
+    let total = be16(wire, 6) + be16(wire, 8) + be16(wire, 10);
+    let mut records = vec![];
+    for _ in 0..total {
+        let owner = offset;
+        offset = skip_name(wire, offset);
+        let rtype = be16(wire, offset);
+        let start = offset + 10;
+        let end = start + be16(wire, offset + 8) as usize;
+        // the names of the rfc1035 types may be compressed, the pointers are checked too
+        match DNSType::from_u16(rtype) {
+            Some(DNSType::NS) => {
+                skip_name(wire, start);
+            }
+            Some(DNSType::MX) => {
+                skip_name(wire, start + 2);
+            }
+            Some(DNSType::SOA) => {
+                skip_name(wire, skip_name(wire, start));
+            }
+            _ => {}
+        }
+        records.push(WireRecord {
+            owner,
+            rtype,
+            rdata: start..end,
+        });
+        offset = end;
+    }
+    assert_eq!(offset, wire.len(), "trailing bytes after the records");
+    records
+}
+
+fn render(rr: &ResourceRecord) -> String {
+    rr.to_string()
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn assert_same_records(left: Vec<&ResourceRecord>, right: Vec<&ResourceRecord>) {
+    assert_eq!(left.len(), right.len());
+    for (left, right) in left.iter().zip(right.iter()) {
+        assert_eq!(left, right);
+        assert_eq!(render(left), render(right));
+        assert_eq!(
+            left.canonical_rdata().unwrap(),
+            right.canonical_rdata().unwrap()
+        );
+    }
+}
+
+fn assert_same_message(left: &Message, right: &Message) {
+    assert_eq!(left, right);
+    assert_eq!(left.query_info(), right.query_info());
+    assert_same_records(left.answers(), right.answers());
+    assert_same_records(left.authorities(), right.authorities());
+    assert_same_records(left.additionals(), right.additionals());
+}
+
+/// round_trip parse the capture, encode it with and without compression and parse the
+/// results again, the messages must be the same. the encoded header must keep the flag
+/// bits and counts of the capture, the OPT record must be the last one with the root owner
+/// (rfc6891 section 6.1.1), and the rdata of types without compression must be copied
+/// byte for byte(rfc3597 section 4). return the parsed capture and the compressed encoding.
+fn round_trip(capture: &[u8]) -> (Message, Vec<u8>) {
+    let mut message = Message::parse_dns_message(capture).expect("parse capture");
+    let encoded = message.encode(true).expect("encode");
+    let uncompressed = message
+        .encode_uncompressed(true)
+        .expect("encode uncompressed");
+    let message = Message::parse_dns_message(capture).unwrap();
+    for wire in [&encoded, &uncompressed].iter() {
+        let reparsed = Message::parse_dns_message(wire).expect("parse encoded");
+        assert_same_message(&message, &reparsed);
+        assert_eq!(wire[2..12], capture[2..12], "header flags or counts differ");
+    }
+    assert!(uncompressed.len() >= encoded.len());
+
+    let captured = walk(capture);
+    let records = walk(&encoded);
+    assert_eq!(captured.len(), records.len());
+    for (captured, record) in captured.iter().zip(records.iter()) {
+        assert_eq!(captured.rtype, record.rtype);
+        let compressible = matches!(
+            DNSType::from_u16(record.rtype),
+            Some(DNSType::NS) | Some(DNSType::MX) | Some(DNSType::SOA)
+        );
+        if !compressible {
+            assert_eq!(
+                capture[captured.rdata.clone()],
+                encoded[record.rdata.clone()],
+                "rdata of type {} differ",
+                record.rtype
+            );
+        }
+    }
+    if let Some(position) = records.iter().position(|record| record.rtype == OPT) {
+        assert_eq!(position, records.len() - 1, "OPT is not the last record");
+        assert_eq!(encoded[records[position].owner], 0);
+    }
+    (message, encoded)
+}
+
+fn record_types(records: &[&ResourceRecord]) -> Vec<DNSType> {
+    records.iter().map(|rr| rr.get_type()).collect()
+}
+
+#[test]
+fn test_dig_query_with_cookie() {
+    let capture = capture!("dig_query_cookie");
+    let (message, encoded) = round_trip(&capture);
+    assert!(message.is_query());
+    assert!(message.header.rd());
+    // RD and AD(rfc6840 section 5.7) and nothing else
+    assert_eq!(encoded[2..4], [0x01, 0x20]);
+    assert_eq!(message.header.id(), 0x4f3a);
+    let (name, qtype) = message.query_name_and_type().unwrap();
+    assert_eq!(name.to_string(), "www.example.com.");
+    assert_eq!(*qtype, DNSType::A);
+    let edns = message.edns().unwrap();
+    assert_eq!(edns.payload_size(), 1232);
+    assert!(!edns.do_bit());
+    // a client cookie is 8 bytes(rfc7873 section 4)
+    assert_eq!(
+        edns.cookie(),
+        Some(&[0xf3, 0xa2, 0xb1, 0xc4, 0xd5, 0xe6, 0xf7, 0x08][..])
+    );
+    assert!(message.additionals().is_empty());
+}
+
+#[test]
+fn test_bind_answer_with_glue_and_server_cookie() {
+    let capture = capture!("bind_a_referral_glue");
+    let (message, encoded) = round_trip(&capture);
+    assert!(message.header.aa());
+    assert!(message.header.rd());
+    assert!(!message.header.ra());
+    // QR AA RD, then AD with NOERROR
+    assert_eq!(encoded[2..4], [0x85, 0x20]);
+    let answers: Vec<String> = message.answers().into_iter().map(render).collect();
+    assert_eq!(
+        answers,
+        vec![
+            "www.example.com. 300 IN A 192.0.2.80",
+            "www.example.com. 300 IN A 192.0.2.81"
+        ]
+    );
+    let authorities: Vec<String> = message.authorities().into_iter().map(render).collect();
+    assert_eq!(
+        authorities,
+        vec![
+            "example.com. 3600 IN NS ns1.example.com.",
+            "example.com. 3600 IN NS ns2.example.com."
+        ]
+    );
+    let additionals = message.additionals();
+    assert_eq!(record_types(&additionals), vec![DNSType::A, DNSType::AAAA]);
+    assert_eq!(
+        render(additionals[1]),
+        "ns2.example.com. 3600 IN AAAA 2001:db8::53"
+    );
+
+    // the answer owners point to the question name at offset 12
+    let records = walk(&encoded);
+    for record in records.iter().take(2) {
+        assert_eq!(encoded[record.owner..record.owner + 2], [0xc0, 0x0c]);
+    }
+    // client cookie followed by a 16 byte server cookie(rfc7873 section 4)
+    let cookie = message.edns().unwrap().cookie().unwrap();
+    assert_eq!(cookie.len(), 24);
+    assert_eq!(
+        cookie[..8],
+        [0xf3, 0xa2, 0xb1, 0xc4, 0xd5, 0xe6, 0xf7, 0x08]
+    );
+    let opt = records.last().unwrap();
+    assert_eq!(
+        encoded[opt.rdata.start..opt.rdata.start + 4],
+        [0, 10, 0, 24]
+    );
+}
+
+#[test]
+fn test_knot_soa() {
+    let capture = capture!("knot_soa");
+    let (message, encoded) = round_trip(&capture);
+    assert_eq!(encoded[2..4], [0x84, 0x00]);
+    assert!(!message.header.rd());
+    let answers = message.answers();
+    assert_eq!(
+        render(answers[0]),
+        "example.com. 3600 IN SOA ns1.example.com. hostmaster.example.com. 2021050301 7200 3600 1209600 300"
+    );
+    // the 20 bytes of the soa counters follow the two names unchanged
+    let records = walk(&encoded);
+    let rdata = records[0].rdata.clone();
+    assert_eq!(
+        encoded[rdata.end - 20..rdata.end],
+        capture[capture.len() - 11 - 20..capture.len() - 11]
+    );
+}
+
+#[test]
+fn test_unbound_txt() {
+    let capture = capture!("unbound_txt");
+    let (message, encoded) = round_trip(&capture);
+    // QR RD RA, unbound does not set AA
+    assert_eq!(encoded[2..4], [0x81, 0x80]);
+    assert!(message.header.ra());
+    assert!(!message.header.aa());
+    let strings: Vec<Vec<Vec<u8>>> = message
+        .answers()
+        .iter()
+        .map(|rr| {
+            let data = rr.get_data().as_ref().unwrap();
+            let txt = data.as_any().downcast_ref::<DnsTypeTXT>().unwrap();
+            txt.strings().to_vec()
+        })
+        .collect();
+    assert_eq!(
+        strings,
+        vec![
+            vec![b"v=spf1 ip4:192.0.2.0/24 -all".to_vec()],
+            vec![
+                b"google-site-verification=abc123".to_vec(),
+                b"second string".to_vec(),
+                vec![]
+            ],
+        ]
+    );
+}
+
+#[test]
+fn test_unbound_mx() {
+    let capture = capture!("unbound_mx");
+    let (message, _) = round_trip(&capture);
+    let answers: Vec<String> = message.answers().into_iter().map(render).collect();
+    assert_eq!(
+        answers,
+        vec![
+            "example.com. 300 IN MX 10 mail.example.com.",
+            "example.com. 300 IN MX 20 mx2.example.com."
+        ]
+    );
+    let additionals: Vec<String> = message.additionals().into_iter().map(render).collect();
+    assert_eq!(
+        additionals,
+        vec![
+            "mail.example.com. 300 IN A 192.0.2.25",
+            "mail.example.com. 300 IN AAAA 2001:db8::25"
+        ]
+    );
+}
+
+#[test]
+fn test_knot_srv() {
+    let capture = capture!("knot_srv");
+    let (message, encoded) = round_trip(&capture);
+    let answers: Vec<String> = message.answers().into_iter().map(render).collect();
+    assert_eq!(
+        answers,
+        vec![
+            "_sip._tcp.example.com. 3600 IN SRV 10 60 5060 sip1.example.com.",
+            "_sip._tcp.example.com. 3600 IN SRV 20 0 5060 sip2.example.com."
+        ]
+    );
+    // the target must not be compressed(rfc2782)
+    for record in walk(&encoded).iter().take(2) {
+        assert_eq!(DNSType::from_u16(record.rtype), Some(DNSType::SRV));
+        assert_uncompressed(&encoded, record.rdata.start + 6);
+    }
+}
+
+#[test]
+fn test_knot_aaaa() {
+    let capture = capture!("knot_aaaa");
+    let (message, encoded) = round_trip(&capture);
+    assert_eq!(
+        render(message.answers()[0]),
+        "www.example.com. 300 IN AAAA 2001:db8:0:1::80"
+    );
+    let records = walk(&encoded);
+    assert_eq!(records[0].rdata.len(), 16);
+    // an OPT without options has an empty rdata
+    assert!(records[1].rdata.is_empty());
+}
+
+#[test]
+fn test_bind_dnskey_rrsig() {
+    let capture = capture!("bind_dnskey_rrsig");
+    let (message, encoded) = round_trip(&capture);
+    assert!(message.edns().unwrap().do_bit());
+    assert_eq!(
+        record_types(&message.answers()),
+        vec![DNSType::DNSKEY, DNSType::DNSKEY, DNSType::RRSIG]
+    );
+    let records = walk(&encoded);
+    // flags 257 is the key signing key with protocol 3 and algorithm 8
+    assert_eq!(
+        encoded[records[0].rdata.start..records[0].rdata.start + 4],
+        [0x01, 0x01, 3, 8]
+    );
+    // the signer name must not be compressed(rfc4034 section 3.1.7)
+    assert_uncompressed(&encoded, records[2].rdata.start + 18);
+    // the DO bit is the top bit of the OPT ttl(rfc3225)
+    let opt = records.last().unwrap();
+    assert_eq!(
+        encoded[opt.rdata.start - 4..opt.rdata.start - 2],
+        [0x80, 0x00]
+    );
+}
+
+#[test]
+fn test_bind_nxdomain_with_nsec() {
+    let capture = capture!("bind_nxdomain_nsec");
+    let (message, encoded) = round_trip(&capture);
+    assert_eq!(message.header.rcode(), RCode::NameError);
+    assert!(message.answers().is_empty());
+    // rcode 3 in the low 4 bits of the header and no extended rcode in the OPT
+    assert_eq!(encoded[3] & 0x0f, 3);
+    let authorities = message.authorities();
+    assert_eq!(
+        record_types(&authorities),
+        vec![
+            DNSType::SOA,
+            DNSType::RRSIG,
+            DNSType::NSEC,
+            DNSType::RRSIG,
+            DNSType::NSEC,
+            DNSType::RRSIG
+        ]
+    );
+    assert_eq!(authorities[2].get_dname().to_string(), "mail.example.com.");
+    let records = walk(&encoded);
+    let opt = records.last().unwrap();
+    assert_eq!(encoded[opt.rdata.start - 6], 0);
+    for record in records.iter() {
+        match DNSType::from_u16(record.rtype) {
+            // the next domain name must not be compressed(rfc4034 section 4.1.1)
+            Some(DNSType::NSEC) => assert_uncompressed(&encoded, record.rdata.start),
+            Some(DNSType::RRSIG) => assert_uncompressed(&encoded, record.rdata.start + 18),
+            _ => {}
+        }
+    }
+}
+
+#[test]
+fn test_knot_nodata() {
+    let capture = capture!("knot_nodata");
+    let (message, _) = round_trip(&capture);
+    // no data is NOERROR without answers and the soa in authority(rfc2308 section 2.2)
+    assert_eq!(message.header.rcode(), RCode::NoError);
+    assert!(message.header.aa());
+    assert!(message.answers().is_empty());
+    assert_eq!(record_types(&message.authorities()), vec![DNSType::SOA]);
+    assert_eq!(message.authorities()[0].get_ttl(), 300);
+}
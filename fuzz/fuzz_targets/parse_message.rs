@@ -0,0 +1,15 @@
+#![no_main]
+use dnsproto::message::Message;
+use libfuzzer_sys::fuzz_target;
+
+// parse arbitrary bytes as a dns message, a parsed message which can be encoded again
+// must parse back and encode to the same bytes.
+fuzz_target!(|data: &[u8]| {
+    if let Ok(mut message) = Message::parse_dns_message(data) {
+        if let Ok(encoded) = message.encode(true) {
+            let mut reparsed =
+                Message::parse_dns_message(&encoded).expect("re-encoded message should parse");
+            assert_eq!(reparsed.encode(true).unwrap(), encoded);
+        }
+    }
+});
@@ -0,0 +1,19 @@
+#![no_main]
+use dnsproto::dnsname::parse_name;
+use libfuzzer_sys::fuzz_target;
+
+// the first byte split the data into the name input and an unrelated original message,
+// so compression pointers are resolved against mismatched buffers.
+fuzz_target!(|data: &[u8]| {
+    if data.is_empty() {
+        return;
+    }
+    let split = (data[0] as usize).min(data.len() - 1);
+    let (input, original) = data[1..].split_at(split);
+    if let Ok((_, name)) = parse_name(input, original) {
+        let binary = name.to_binary(None);
+        let (rest, reparsed) = parse_name(&binary, &[]).expect("encoded name should parse");
+        assert!(rest.is_empty());
+        assert_eq!(reparsed, name);
+    }
+});
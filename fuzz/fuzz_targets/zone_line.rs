@@ -0,0 +1,24 @@
+#![no_main]
+use dnsproto::qtype::decode_message_data;
+use dnsproto::zone::{ZoneReader, ZoneTextReader};
+use libfuzzer_sys::fuzz_target;
+
+// read arbitrary utf8 text as zone data, the wire format of every parsed record
+// must decode and encode to the same bytes.
+fuzz_target!(|data: &[u8]| {
+    let text = match std::str::from_utf8(data) {
+        Ok(text) => text,
+        Err(_) => return,
+    };
+    let reader = ZoneReader::new(ZoneTextReader::new(text), Some("example.com.".to_owned()))
+        .with_default_ttl(Some(3600));
+    for rr in reader.flatten() {
+        if let Some(rdata) = rr.get_data() {
+            if let Ok(wire) = rdata.encode(None) {
+                let decoded = decode_message_data(&wire, &wire, rr.get_type())
+                    .expect("encoded rdata should decode");
+                assert_eq!(decoded.encode(None).unwrap(), wire);
+            }
+        }
+    }
+});
@@ -1,16 +1,103 @@
 use clap::{App, Arg};
 use otterlib::setting::{ExSetting, Settings};
+#[cfg(unix)]
+use server::logging::init_logger_with_syslog;
+use server::logging::{init_logger, parse_log_level};
+#[cfg(unix)]
+use server::syslog::{server_syslog_setting, Syslog};
 use server::OtterServer;
+use std::io::Write;
 use std::str::FromStr;
+use storage::diff::{DiffKind, ZoneDiff};
+use storage::storage::{LoadOption, LoadProgress, SafeRBTreeStorage, ZoneOption};
 use tokio::runtime;
 #[macro_use]
 extern crate log;
-use env_logger::Env;
 
 pub fn version() -> &'static str {
     env!("CARGO_PKG_VERSION")
 }
 
+// check_zone load the zone file and print the loading progress to stderr
+fn check_zone(file: &str, origin: Option<String>) -> bool {
+    let mut storage = SafeRBTreeStorage::default();
+    let mut option =
+        LoadOption::new(ZoneOption::default()).with_progress(10_000, |progress: &LoadProgress| {
+            eprint!(
+                "\rloading {}: {} records, {} KiB",
+                file,
+                progress.records,
+                progress.memory / 1024
+            );
+            let _ = std::io::stderr().flush();
+        });
+    let result = storage.update_zone_with_progress(file, origin, &mut option);
+    eprintln!();
+    match result {
+        Ok(_) => {
+            println!("zone file {} is ok", file);
+            true
+        }
+        Err(err) => {
+            println!("zone check fail: {}", err);
+            false
+        }
+    }
+}
+
+// print_zone_diff render the changed rrsets like a unified diff: a header line for each
+// rrset followed by the removed and added records
+fn print_zone_diff(diff: &ZoneDiff) {
+    let (mut added, mut removed, mut changed) = (0, 0, 0);
+    for entry in diff.entries.iter() {
+        let mark = match entry.kind {
+            DiffKind::Added => {
+                added += 1;
+                "+"
+            }
+            DiffKind::Removed => {
+                removed += 1;
+                "-"
+            }
+            DiffKind::Changed => {
+                changed += 1;
+                "~"
+            }
+        };
+        println!("{} {} {}", mark, entry.owner, entry.dtype);
+        for rr in entry.old_rrs.iter() {
+            println!("-\t{}", rr);
+        }
+        for rr in entry.new_rrs.iter() {
+            println!("+\t{}", rr);
+        }
+    }
+    println!(
+        "{} rrsets added, {} removed, {} changed",
+        added, removed, changed
+    );
+}
+
+// diff_zone load both zone files and print the changes, the exit code is 0 when the zones
+// are same, 1 when they differ and 2 when a zone file fails to load
+fn diff_zone(old: &str, new: &str, origin: Option<String>) -> i32 {
+    let load = |file: &str| {
+        SafeRBTreeStorage::new_zone_from_file(file, origin.clone()).map_err(|err| {
+            println!("zone load fail: {}", err);
+        })
+    };
+    let (old_storage, new_storage) = match (load(old), load(new)) {
+        (Ok(old_storage), Ok(new_storage)) => (old_storage, new_storage),
+        _ => return 2,
+    };
+    let diff = old_storage.diff(&new_storage);
+    print_zone_diff(&diff);
+    match diff.is_empty() {
+        true => 0,
+        false => 1,
+    }
+}
+
 fn main() {
     let cpu_number = num_cpus::get();
     let default_workers_number = {
@@ -74,45 +161,113 @@ fn main() {
                 .help("set the level of log output")
                 .takes_value(true),
         )
+        .arg(
+            Arg::with_name("check-zone")
+                .long("check-zone")
+                .value_name("FILE")
+                .help("load the zone file, show the progress and exit")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("diff-zone")
+                .long("diff-zone")
+                .value_names(&["OLD", "NEW"])
+                .number_of_values(2)
+                .conflicts_with("check-zone")
+                .help("print the added, removed and changed rrsets between two zone files")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("check")
+                .long("check")
+                .conflicts_with_all(&["check-zone", "diff-zone"])
+                .help("load the config and zone files, bind all listeners and exit"),
+        )
+        .arg(
+            Arg::with_name("origin")
+                .long("origin")
+                .help("the origin of the zone files to check or diff")
+                .takes_value(true),
+        )
         .get_matches();
+    if let Some(file) = matches.value_of("check-zone") {
+        let origin = matches.value_of("origin").map(|v| v.to_owned());
+        if !check_zone(file, origin) {
+            std::process::exit(1);
+        }
+        return;
+    }
+    if let Some(mut files) = matches.values_of("diff-zone") {
+        let origin = matches.value_of("origin").map(|v| v.to_owned());
+        let (old, new) = (files.next().unwrap(), files.next().unwrap());
+        std::process::exit(diff_zone(old, new, origin));
+    }
     let config_file = matches.value_of("config").unwrap();
-    info!("read config file from {}", config_file);
+    // the logger is installed after the config is read, so the syslog target is known
+    let setting = match Settings::new(config_file) {
+        Ok(setting) => setting,
+        Err(err) => exit_with_error(&format!("setting error: {}", err)),
+    };
     let log_level = matches.value_of("loglevel").unwrap();
+    let level = parse_log_level(log_level).unwrap_or(log::LevelFilter::Info);
+    #[cfg(unix)]
+    let result = match server_syslog_setting(&setting.log) {
+        Some((log, syslog_level)) => {
+            init_logger_with_syslog(level, Syslog::open(log), syslog_level)
+        }
+        None => init_logger(level),
+    };
+    #[cfg(not(unix))]
+    let result = init_logger(level);
+    if let Err(err) = result {
+        eprintln!("init logger fail: {}", err);
+    }
+    info!("read config file from {}", config_file);
     info!("set log level to {}", log_level);
-    env_logger::Builder::from_env(Env::default().default_filter_or(log_level)).init();
-    match Settings::new(config_file) {
-        Ok(setting) => {
-            let mut server = OtterServer::new(setting);
-            info!("OtterDNS {} starting", version());
-            let runtime = runtime::Builder::new_multi_thread()
-                .enable_all()
-                .worker_threads(4)
-                .thread_name("otter-runtime")
-                .build()
-                .expect("failed to initialize dns server runtime");
-            let tcp_workers = usize::from_str(matches.value_of("tcp-workers").unwrap()).unwrap();
-            let udp_workers = usize::from_str(matches.value_of("udp-workers").unwrap()).unwrap();
-            info!(
-                "set tcp workers number to {}, udp workers number to {}",
-                tcp_workers, udp_workers
-            );
-            let exsetting = ExSetting {
-                tcp_workers,
-                udp_workers,
-            };
-            match runtime.block_on(server.run(&exsetting)) {
-                Ok(()) => {
-                    info!("OtterDNS {} stopping", version());
-                }
-                Err(err) => {
-                    let error_msg =
-                        format!("OtterDNS {} encountered a error: {:?}", version(), err);
-
-                    error!("{}", error_msg);
-                    panic!(error_msg)
-                }
+    let mut server = OtterServer::new(setting);
+    let runtime = match runtime::Builder::new_multi_thread()
+        .enable_all()
+        .worker_threads(4)
+        .thread_name("otter-runtime")
+        .build()
+    {
+        Ok(runtime) => runtime,
+        Err(err) => exit_with_error(&format!("initialize runtime error: {}", err)),
+    };
+    // the values are checked by the validators
+    let tcp_workers = usize::from_str(matches.value_of("tcp-workers").unwrap_or("1")).unwrap_or(1);
+    let udp_workers = usize::from_str(matches.value_of("udp-workers").unwrap_or("1")).unwrap_or(1);
+    info!(
+        "set tcp workers number to {}, udp workers number to {}",
+        tcp_workers, udp_workers
+    );
+    let exsetting = ExSetting {
+        tcp_workers,
+        udp_workers,
+    };
+    if matches.is_present("check") {
+        match runtime.block_on(server.check(&exsetting)) {
+            Ok(()) => println!("config file {} is ok", config_file),
+            Err(err) => {
+                exit_with_error(&format!("config file {} check fail: {}", config_file, err))
             }
         }
-        Err(err) => error!("setting error: {:?}", err),
-    };
+        return;
+    }
+    info!("OtterDNS {} starting", version());
+    match runtime.block_on(server.run(&exsetting)) {
+        Ok(()) => info!("OtterDNS {} stopping", version()),
+        Err(err) => exit_with_error(&format!(
+            "OtterDNS {} encountered a error: {}",
+            version(),
+            err
+        )),
+    }
+}
+
+// exit_with_error log the error, print it to stderr and exit with code 1
+fn exit_with_error(message: &str) -> ! {
+    error!("{}", message);
+    eprintln!("{}", message);
+    std::process::exit(1)
 }
@@ -1,3 +1,4 @@
+use std::fmt;
 use std::net::AddrParseError;
 use std::num::ParseIntError;
 use thiserror::Error;
@@ -11,16 +12,18 @@ pub enum SettingError {
 
     #[error("validation server setting failed: {0}")]
     ValidationServerConfigError(String),
+    #[error("unknown config file format: `{0}`, expect yaml, yml, toml or json")]
+    UnknownConfigFormat(String),
 }
 
 #[derive(Error, PartialEq, Debug)]
 pub enum StorageError {
     #[error("domain not exist in storage")]
-    DomainNotFoundError(String),
+    DomainNotFoundError,
     #[error("domain not belong to this zone")]
     RefusedError,
-    #[error("domain :{0} exist but query type:{1} not exist")]
-    DNSTypeNotFoundError(String, String),
+    #[error("domain exist but query type:{0} not exist")]
+    DNSTypeNotFoundError(u16),
     #[error("storage not ready")]
     StorageNotReadyError,
     #[error("zone file must start with soa record")]
@@ -36,12 +39,36 @@ pub enum StorageError {
     #[error("can't add non-NSEC RR to a node that already has a CNAME")]
     AddOtherRRConflictCNAME,
 
-    #[error("domain :{0} not belong to zone : {0}")]
+    #[error("CNAME RR is not allowed at the zone apex {0}")]
+    ApexCNAMEError(String),
+
+    #[error("domain :{0} not belong to zone : {1}")]
     ZoneOutOfArea(String, String),
     #[error("{0}")]
     ValidFQDNError(String),
     #[error("unimplemented feature")]
     Unimplemented,
+    #[error("zone {0} serial {1} is not great than served serial {2}")]
+    SerialNotIncreased(String, u32, u32),
+    #[error("zone file {0} exceeds the {1} limit {2}")]
+    ZoneLoadLimitExceeded(String, String, usize),
+    #[error("catalog zone {0} is invalid: {1}")]
+    CatalogZoneError(String, String),
+    #[error("zone {0} digest verification fail: {1}")]
+    ZoneDigestError(String, String),
+    #[error("zone {0} rrsig verification fail: {1}")]
+    DnssecVerifyError(String, String),
+    #[error("write zone file {0} error: {1}")]
+    ZoneWriteError(String, String),
+    #[error("{1} target {2} of {0} is not a valid host name")]
+    InvalidHostName(String, String, String),
+    #[error("ptr owner {0} is not a valid reverse name")]
+    InvalidReverseName(String),
+    #[error("domain {0} has more than {1} labels")]
+    LabelDepthExceeded(String, usize),
+    #[cfg(feature = "testing")]
+    #[error("injected storage fault")]
+    InjectedFault,
 }
 
 #[derive(Error, Debug, PartialEq)]
@@ -55,7 +82,7 @@ pub enum DNSProtoErr {
     ValidDomainLabelErr(String),
     #[error("dns type: `{0}` validate fail")]
     ValidTypeErr(String),
-    #[error("dns ttl: `{0}` validate fail")]
+    #[error("invalid TTL '{0}'")]
     ValidTTLErr(String),
     #[error("dns origin: `{0}` validate fail")]
     ValidOriginErr(String),
@@ -69,6 +96,8 @@ pub enum DNSProtoErr {
     NoOriginDomainErr,
     #[error("domain is not a valid fqdn: `{0}`")]
     ValidFQDNError(String),
+    #[error("parse zone data error: `{0}`")]
+    ParseZoneDataErr(String),
 
     #[error("edns version number not support")]
     BadEDNSVersion,
@@ -89,6 +118,10 @@ pub enum DNSProtoErr {
 
     #[error("packet parse failed")]
     PacketParseError,
+    #[error("packet claims more records than it can hold")]
+    ExcessiveRecords,
+    #[error("query dropped by response policy")]
+    DroppedByPolicy,
     #[error("packet serial failed")]
     PacketSerializeError,
     #[error("packet encode error")]
@@ -105,6 +138,9 @@ pub enum DNSProtoErr {
 
     #[error("unimplemented error: {0}")]
     UnImplementedError(String),
+
+    #[error("invalid zone transfer response: {0}")]
+    InvalidXfrResponse(String),
 }
 
 impl From<std::io::Error> for DNSProtoErr {
@@ -140,12 +176,6 @@ impl From<ParseIntError> for DNSProtoErr {
         DNSProtoErr::ParseDNSFromStrError(error.to_string())
     }
 }
-// impl From<DNSProtoErr> for StorageError {
-//     fn from(err: DNSProtoErr) -> Self {
-//         StorageError::ValidFQDNError(err.to_string())
-//     }
-// }
-
 #[derive(Error, Debug)]
 pub enum OtterError {
     #[error(transparent)]
@@ -158,6 +188,145 @@ pub enum OtterError {
 
     #[error(transparent)]
     StorageError(#[from] StorageError),
+
+    #[error(transparent)]
+    TransferError(#[from] TransferError),
+
+    #[error("privilege error: {0}")]
+    PrivilegeError(String),
+
+    #[error("startup self test failed: {0}")]
+    SelfTestError(String),
+
+    #[error("query log {0} error: {1}")]
+    QueryLogError(String, String),
+
+    /// the error with the zone, file, line or address it happens at, added by the with_*
+    /// helpers
+    #[error("{context}: {source}")]
+    WithContext {
+        context: ErrorContext,
+        source: Box<OtterError>,
+    },
+}
+
+/// ErrorContext is where an error happens, it is printed before the error like
+/// `zone example.com (file /abc/zones/example.com.zone) line 42`.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct ErrorContext {
+    pub zone: Option<String>,
+    pub file: Option<String>,
+    pub line: Option<usize>,
+    /// the remote or local socket address
+    pub peer: Option<String>,
+}
+
+impl fmt::Display for ErrorContext {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut parts = vec![];
+        if let Some(zone) = &self.zone {
+            parts.push(format!("zone {}", zone));
+        }
+        match (&self.file, self.zone.is_some()) {
+            (Some(file), true) => parts.push(format!("(file {})", file)),
+            (Some(file), false) => parts.push(format!("file {}", file)),
+            _ => {}
+        }
+        if let Some(line) = self.line {
+            parts.push(format!("line {}", line));
+        }
+        if let Some(peer) = &self.peer {
+            parts.push(format!("address {}", peer));
+        }
+        write!(f, "{}", parts.join(" "))
+    }
+}
+
+impl OtterError {
+    /// with_zone attach the zone name unless the error already has one, the innermost
+    /// context is the most precise one.
+    pub fn with_zone(self, zone: &str) -> OtterError {
+        self.with_context(|context| {
+            context.zone.get_or_insert_with(|| zone.to_owned());
+        })
+    }
+
+    /// with_file attach the file path unless the error already has one.
+    pub fn with_file(self, file: &str) -> OtterError {
+        self.with_context(|context| {
+            context.file.get_or_insert_with(|| file.to_owned());
+        })
+    }
+
+    /// with_line attach the line number of the file unless the error already has one.
+    pub fn with_line(self, line: usize) -> OtterError {
+        self.with_context(|context| {
+            context.line.get_or_insert(line);
+        })
+    }
+
+    /// with_peer attach the socket address unless the error already has one.
+    pub fn with_peer<T: fmt::Display>(self, peer: T) -> OtterError {
+        self.with_context(|context| {
+            context.peer.get_or_insert_with(|| peer.to_string());
+        })
+    }
+
+    fn with_context<F>(self, update: F) -> OtterError
+    where
+        F: FnOnce(&mut ErrorContext),
+    {
+        let (mut context, source) = match self {
+            OtterError::WithContext { context, source } => (context, source),
+            err => (ErrorContext::default(), Box::new(err)),
+        };
+        update(&mut context);
+        OtterError::WithContext { context, source }
+    }
+
+    /// context return the attached context, None if no context is attached.
+    pub fn context(&self) -> Option<&ErrorContext> {
+        match self {
+            OtterError::WithContext { context, .. } => Some(context),
+            _ => None,
+        }
+    }
+
+    /// kind return the error without the attached context.
+    pub fn kind(&self) -> &OtterError {
+        match self {
+            OtterError::WithContext { source, .. } => source.kind(),
+            err => err,
+        }
+    }
+
+    /// into_kind take the error out of the attached context.
+    pub fn into_kind(self) -> OtterError {
+        match self {
+            OtterError::WithContext { source, .. } => source.into_kind(),
+            err => err,
+        }
+    }
+}
+
+/// ErrorContextExt attach the context to the error of a result, the error is converted
+/// into OtterError first.
+pub trait ErrorContextExt<T> {
+    fn with_zone(self, zone: &str) -> Result<T, OtterError>;
+    fn with_file(self, file: &str) -> Result<T, OtterError>;
+    fn with_peer<P: fmt::Display>(self, peer: P) -> Result<T, OtterError>;
+}
+
+impl<T, E: Into<OtterError>> ErrorContextExt<T> for Result<T, E> {
+    fn with_zone(self, zone: &str) -> Result<T, OtterError> {
+        self.map_err(|err| Into::<OtterError>::into(err).with_zone(zone))
+    }
+    fn with_file(self, file: &str) -> Result<T, OtterError> {
+        self.map_err(|err| Into::<OtterError>::into(err).with_file(file))
+    }
+    fn with_peer<P: fmt::Display>(self, peer: P) -> Result<T, OtterError> {
+        self.map_err(|err| Into::<OtterError>::into(err).with_peer(peer))
+    }
 }
 
 #[derive(Error, Debug)]
@@ -167,6 +336,38 @@ pub enum NetworkError {
 
     #[error("{0}")]
     IOError(String),
+
+    #[error("listen address invalid: {0}")]
+    ListenAddrError(String),
+
+    #[error("bind {0} listener failed: {1}")]
+    BindError(String, String),
+
+    #[error("bind source address {0} toward {1} failed: {2}")]
+    SourceBindError(String, String, String),
+
+    #[error("query {0} timeout")]
+    QueryTimeout(String),
+
+    #[error("unexpected response from {0}: {1}")]
+    UnexpectedResponse(String, String),
+}
+
+/// TransferError is the reason a zone transfer from the master is rejected, the served
+/// zone data is kept as is.
+#[derive(Error, Debug, PartialEq)]
+pub enum TransferError {
+    #[error("transfer of zone {0} exceeds max_transfer_records {1}")]
+    TooManyRecords(String, usize),
+
+    #[error("transfer of zone {0} exceeds max_transfer_bytes {1}")]
+    TooManyBytes(String, usize),
+
+    #[error("transfer of zone {0} has record {1} out of the zone")]
+    OutOfZone(String, String),
+
+    #[error("transfer of zone {0} soa mismatch: {1}")]
+    SOAMismatch(String, String),
 }
 
 impl From<AddrParseError> for NetworkError {
@@ -0,0 +1,140 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Fault is what happens to an operation picked by a FaultInjector. storage lookups only
+/// apply Error and Delay, network responses apply all faults but Error.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Fault {
+    /// the operation fail
+    Error,
+    /// the operation is done after the delay
+    Delay(Duration),
+    /// the response never arrives
+    Drop,
+    /// the response is damaged and can't be parsed
+    Corrupt,
+    /// the response arrives twice
+    Duplicate,
+}
+
+#[derive(Debug)]
+struct FaultRule {
+    fault: Fault,
+    percent: u8,
+    // operations seen by the rule
+    seen: usize,
+    // faults left to inject, no limit if not set
+    remaining: Option<usize>,
+}
+
+impl FaultRule {
+    // fire spread the faults evenly over the operations, so the first one is picked only
+    // for 100 percent and tests get the same faults on every run.
+    fn fire(&mut self) -> bool {
+        if self.remaining == Some(0) {
+            return false;
+        }
+        self.seen += 1;
+        let percent = self.percent.min(100) as usize;
+        if self.seen * percent / 100 == (self.seen - 1) * percent / 100 {
+            return false;
+        }
+        if let Some(remaining) = self.remaining.as_mut() {
+            *remaining -= 1;
+        }
+        true
+    }
+}
+
+/// FaultInjector pick the operations to fail by its rules, it is installed from tests on
+/// the storage and on the remote addresses of the dns client to check the timeout and
+/// retry paths. the rules are tried in the order they are added for each operation and
+/// the first picked fault is applied.
+#[derive(Debug, Default)]
+pub struct FaultInjector {
+    rules: Mutex<Vec<FaultRule>>,
+    injected: AtomicUsize,
+}
+
+impl FaultInjector {
+    pub fn new() -> FaultInjector {
+        FaultInjector::default()
+    }
+
+    /// add inject the fault into the percent of the operations.
+    pub fn add(&self, fault: Fault, percent: u8) -> &Self {
+        self.push(fault, percent, None)
+    }
+
+    /// add_times inject the fault into the next count operations only.
+    pub fn add_times(&self, fault: Fault, count: usize) -> &Self {
+        self.push(fault, 100, Some(count))
+    }
+
+    fn push(&self, fault: Fault, percent: u8, remaining: Option<usize>) -> &Self {
+        self.rules.lock().unwrap().push(FaultRule {
+            fault,
+            percent,
+            seen: 0,
+            remaining,
+        });
+        self
+    }
+
+    /// clear remove all rules, the following operations are not affected.
+    pub fn clear(&self) {
+        self.rules.lock().unwrap().clear();
+    }
+
+    /// next_fault return the fault of the next operation, None if it is not picked.
+    pub fn next_fault(&self) -> Option<Fault> {
+        let mut picked = None;
+        for rule in self.rules.lock().unwrap().iter_mut() {
+            if rule.fire() && picked.is_none() {
+                picked = Some(rule.fault);
+            }
+        }
+        if picked.is_some() {
+            self.injected.fetch_add(1, Ordering::SeqCst);
+        }
+        picked
+    }
+
+    /// injected return the number of faults injected so far.
+    pub fn injected(&self) -> usize {
+        self.injected.load(Ordering::SeqCst)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_fault_injector() {
+        let injector = FaultInjector::new();
+        assert_eq!(injector.next_fault(), None);
+        injector.add(Fault::Error, 50);
+        let faults: Vec<Option<Fault>> = (0..4).map(|_| injector.next_fault()).collect();
+        assert_eq!(
+            faults,
+            vec![None, Some(Fault::Error), None, Some(Fault::Error)]
+        );
+
+        injector.clear();
+        injector
+            .add_times(Fault::Drop, 2)
+            .add(Fault::Delay(Duration::from_millis(10)), 100);
+        let faults: Vec<Option<Fault>> = (0..3).map(|_| injector.next_fault()).collect();
+        assert_eq!(
+            faults,
+            vec![
+                Some(Fault::Drop),
+                Some(Fault::Drop),
+                Some(Fault::Delay(Duration::from_millis(10)))
+            ]
+        );
+        assert_eq!(injector.injected(), 5);
+    }
+}
@@ -1,7 +1,11 @@
 #[macro_use]
 extern crate serde;
 extern crate config;
+#[macro_use]
+extern crate log;
 
+#[cfg(feature = "testing")]
+pub mod fault;
 pub mod setting;
 #[macro_use]
 pub mod errors;
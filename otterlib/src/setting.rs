@@ -1,8 +1,57 @@
 use crate::errors::SettingError;
-use config::{Config, File};
+use config::{Config, File, FileFormat};
+use std::borrow::Cow;
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, ToSocketAddrs};
+use std::path::Path;
 use std::str::FromStr;
 use validator::{Validate, ValidationError, ValidationErrors};
 
+/// SettingFormat is the serialization format of a config file.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SettingFormat {
+    Yaml,
+    Toml,
+    Json,
+}
+
+impl SettingFormat {
+    /// detect the config format from the file extension (yaml/yml/toml/json)
+    pub fn from_path(filename: &str) -> Result<SettingFormat, SettingError> {
+        let extension = Path::new(filename)
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .unwrap_or("")
+            .to_lowercase();
+        match extension.as_str() {
+            "yaml" | "yml" => Ok(SettingFormat::Yaml),
+            "toml" => Ok(SettingFormat::Toml),
+            "json" => Ok(SettingFormat::Json),
+            _ => Err(SettingError::UnknownConfigFormat(filename.to_owned())),
+        }
+    }
+}
+
+impl From<SettingFormat> for FileFormat {
+    fn from(format: SettingFormat) -> Self {
+        match format {
+            SettingFormat::Yaml => FileFormat::Yaml,
+            SettingFormat::Toml => FileFormat::Toml,
+            SettingFormat::Json => FileFormat::Json,
+        }
+    }
+}
+
+impl fmt::Display for SettingFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SettingFormat::Yaml => write!(f, "yaml"),
+            SettingFormat::Toml => write!(f, "toml"),
+            SettingFormat::Json => write!(f, "json"),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Validate, PartialEq, Deserialize, Default)]
 pub struct ExSetting {
     pub tcp_workers: usize,
@@ -11,48 +60,307 @@ pub struct ExSetting {
 
 #[derive(Debug, Clone, Validate, PartialEq, Deserialize, Default)]
 pub struct ServerSetting {
+    /// addresses for both udp and tcp, a /udp or /tcp suffix limit the entry to one transport
+    #[serde(default)]
     pub listen: Vec<String>,
+    /// udp addresses, replace the udp addresses of listen when set
+    pub listen_udp: Option<Vec<String>>,
+    /// tcp addresses, replace the tcp addresses of listen when set
+    pub listen_tcp: Option<Vec<String>>,
+    /// max udp response size advertised and sent, 1232 if not set, clamped into [512, 4096]
+    #[serde(default = "default_max_edns_size")]
     pub max_edns_size: u16,
+    /// user to run as after all listeners are bound
+    pub user: Option<String>,
+    /// group to run as after all listeners are bound
+    pub group: Option<String>,
+    /// keep serving as root when user is not set
+    #[serde(default)]
+    pub allow_root: bool,
+    /// max entries of the response cache, 0 disable the cache
+    #[serde(default)]
+    pub response_cache_size: usize,
+    /// address of the /healthz and /readyz http listener, disabled if not set
+    pub http_listen: Option<String>,
+    /// server is not ready when any zone fails to reload
+    #[serde(default)]
+    pub strict: bool,
+    /// query the apex soa and ns of each zone before accepting traffic
+    #[serde(default)]
+    pub startup_selftest: SelfTestMode,
+    /// seconds before rrsig expiration to warn, 7 days if not set
+    pub signature_expire_window: Option<u32>,
+    /// bind one udp socket per interface address instead of a wildcard listen address
+    #[serde(default)]
+    pub expand_wildcard_listen: bool,
+    /// milliseconds to answer a query before SERVFAIL, 2000 if not set
+    pub query_timeout: Option<u64>,
+    /// max outgoing zone transfers served at the same time, 10 if not set
+    pub max_concurrent_transfers: Option<usize>,
+    /// max outgoing zone transfers served to one client at the same time, 2 if not set
+    pub max_transfers_per_client: Option<usize>,
+    /// max tcp client connections open at the same time, connections over it are closed
+    /// at accept time, 1000 if not set
+    pub max_tcp_clients: Option<usize>,
+    /// max tcp client connections open from one address at the same time, 100 if not set
+    pub max_tcp_clients_per_ip: Option<usize>,
+    /// max udp queries in process at the same time, new queries over it are dropped
+    /// instead of queued, 1000 if not set
+    pub max_udp_in_flight: Option<usize>,
+    /// bytes per second of each outgoing zone transfer, unlimited if not set
+    pub transfer_rate_limit: Option<u64>,
+    /// log one of every n queries, 1 if not set, 0 disable the query log
+    pub query_log_sample: Option<u64>,
+    /// cap the ttl of the records in responses, not capped if not set
+    pub max_ttl: Option<u32>,
+    /// rotate the records of each answer set between queries, zone transfers and dumped
+    /// zone files always use the canonical order
+    #[serde(default)]
+    pub rotate_answers: bool,
+    /// max cname records followed for one answer, 8 if not set
+    pub max_chain_depth: Option<usize>,
+    /// keep following a cname chain into another local zone, true if not set
+    pub follow_cname_across_zones: Option<bool>,
+    /// local address like ip or ip@port of the soa queries, transfers and notifies sent to
+    /// remotes without source_address, chosen by the system if not set
+    pub transfer_source: Option<String>,
+    /// answer recursive queries(rd set) for names out of all zones with REFUSED and the
+    /// Not Authoritative extended error
+    #[serde(default)]
+    pub refuse_recursive_only_queries: bool,
+    /// record the parse, lookup and encode time of each query in the latency metrics, it
+    /// costs a few more clock reads per query
+    #[serde(default)]
+    pub detailed_timing: bool,
+    /// how ANY queries over udp are answered, ANY queries over tcp are always answered
+    #[serde(default)]
+    pub any_over_udp: AnyOverUdp,
 }
 
-impl ServerSetting {
-    fn validation(&self) -> Option<SettingError> {
-        let (mut t_listeners, u_listeners) = self.get_listen_addr();
-        t_listeners.extend_from_slice(u_listeners.as_slice());
-        for t_listener in t_listeners {
-            if let Err(err) = std::net::SocketAddr::from_str(t_listener.as_str()) {
-                return Some(SettingError::ValidationServerConfigError(format!(
-                    "{}",
-                    err.to_string()
+/// default port used when a listen entry has no port
+pub const DEFAULT_LISTEN_PORT: u16 = 53;
+/// default edns buffer size which avoids ip fragmentation(dns flag day 2020)
+pub const DEFAULT_EDNS_SIZE: u16 = 1232;
+/// smallest edns buffer size, the udp limit without edns(rfc6891 6.2.5)
+pub const MIN_EDNS_SIZE: u16 = 512;
+/// largest edns buffer size accepted from the configuration
+pub const MAX_EDNS_SIZE: u16 = 4096;
+
+fn default_max_edns_size() -> u16 {
+    DEFAULT_EDNS_SIZE
+}
+
+/// parse_listen_addr parse a listen entry like ip, ip:port, [v6]:port or hostname:port,
+/// the port will be 53 if not set, and hostname will be resolved to all its addresses.
+pub fn parse_listen_addr(addr: &str) -> Result<Vec<SocketAddr>, SettingError> {
+    let addr = addr.trim();
+    if let Ok(socket_addr) = SocketAddr::from_str(addr) {
+        return Ok(vec![socket_addr]);
+    }
+    if let Some(ip) = parse_bracketed_ip(addr) {
+        return Ok(vec![SocketAddr::new(ip, DEFAULT_LISTEN_PORT)]);
+    }
+    let resolved = match addr.rfind(':') {
+        Some(_) => addr.to_socket_addrs(),
+        None => (addr, DEFAULT_LISTEN_PORT).to_socket_addrs(),
+    };
+    match resolved {
+        Ok(socket_addrs) => {
+            let socket_addrs: Vec<SocketAddr> = socket_addrs.collect();
+            if socket_addrs.is_empty() {
+                return Err(SettingError::ValidationServerConfigError(format!(
+                    "listen address `{}` resolve to nothing",
+                    addr
                 )));
             }
+            Ok(socket_addrs)
         }
-        if self.max_edns_size < 512 || self.max_edns_size > 4096 {
-            return Some(SettingError::ValidationServerConfigError(
-                "max-edns-size must set in range [512, 4096]".to_string(),
-            ));
+        Err(err) => Err(SettingError::ValidationServerConfigError(format!(
+            "listen address `{}` is not valid: {}",
+            addr,
+            err.to_string()
+        ))),
+    }
+}
+
+fn parse_listen_list(list: &[String]) -> Result<Vec<SocketAddr>, SettingError> {
+    let mut socket_addrs = vec![];
+    for addr in list.iter() {
+        socket_addrs.extend(parse_listen_addr(addr)?);
+    }
+    Ok(socket_addrs)
+}
+
+impl ServerSetting {
+    fn validation(&self) -> Option<SettingError> {
+        if let Err(err) = self.get_listen_addr() {
+            return Some(err);
         }
         None
     }
-    /// get_listen_addr will return tcplistener and udplistener in string vector.
+
+    /// edns_size return the max_edns_size clamped into [512, 4096], 0 means not set and
+    /// the default 1232 is used.
+    pub fn edns_size(&self) -> u16 {
+        match self.max_edns_size {
+            0 => DEFAULT_EDNS_SIZE,
+            size => size.max(MIN_EDNS_SIZE).min(MAX_EDNS_SIZE),
+        }
+    }
+
+    // clamp_edns_size replace the max_edns_size with the effective size, warn when the
+    // configured size is out of range or larger than the size avoiding fragmentation.
+    fn clamp_edns_size(&mut self) {
+        let size = self.edns_size();
+        if size != self.max_edns_size {
+            warn!(
+                "max_edns_size {} out of range [{}, {}], use {}",
+                self.max_edns_size, MIN_EDNS_SIZE, MAX_EDNS_SIZE, size
+            );
+        } else if size > DEFAULT_EDNS_SIZE {
+            warn!(
+                "max_edns_size {} may cause ip fragmentation, {} is recommended",
+                size, DEFAULT_EDNS_SIZE
+            );
+        }
+        self.max_edns_size = size;
+    }
+    /// get_listen_addr will return tcplistener and udplistener in socket address vector.
     ///
-    /// Return : (tcplistener: vec![String], udplistener:vec![String])
+    /// Return : (tcplistener: vec![SocketAddr], udplistener:vec![SocketAddr])
     ///
-    fn get_listen_addr(&self) -> (Vec<String>, Vec<String>) {
+    fn get_listen_addr(&self) -> Result<(Vec<SocketAddr>, Vec<SocketAddr>), SettingError> {
         let mut tcplisteners = vec![];
         let mut udplisteners = vec![];
         for listen_addr in self.listen.iter() {
             let addr = listen_addr.trim().to_lowercase();
             if addr.ends_with("/tcp") {
-                tcplisteners.push(addr[..addr.len() - 4].to_string());
+                tcplisteners.extend(parse_listen_addr(&addr[..addr.len() - 4])?);
             } else if addr.ends_with("/udp") {
-                udplisteners.push(addr[..addr.len() - 4].to_string());
+                udplisteners.extend(parse_listen_addr(&addr[..addr.len() - 4])?);
             } else {
-                tcplisteners.push(addr.clone());
-                udplisteners.push(addr);
+                let socket_addrs = parse_listen_addr(addr.as_str())?;
+                tcplisteners.extend_from_slice(socket_addrs.as_slice());
+                udplisteners.extend(socket_addrs);
             };
         }
-        (tcplisteners, udplisteners)
+        if let Some(listen_tcp) = &self.listen_tcp {
+            tcplisteners = parse_listen_list(listen_tcp)?;
+        }
+        if let Some(listen_udp) = &self.listen_udp {
+            udplisteners = parse_listen_list(listen_udp)?;
+        }
+        if tcplisteners.is_empty() && udplisteners.is_empty() {
+            return Err(SettingError::ValidationServerConfigError(
+                "no udp or tcp listen address".to_string(),
+            ));
+        }
+        Ok((tcplisteners, udplisteners))
+    }
+}
+
+/// SelfTestMode decide what to do when the startup self test answers differ from the zone data.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum SelfTestMode {
+    /// refuse to start
+    Strict,
+    /// log the mismatch and keep starting
+    #[default]
+    Warn,
+    /// skip the self test
+    Off,
+}
+
+/// AnyOverUdp decide how an ANY query over udp is answered, a popular amplification vector.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum AnyOverUdp {
+    /// answer REFUSED with the Not Supported extended error
+    Refuse,
+    /// answer an empty response with the tc flag, so the client retry over tcp
+    Truncate,
+    /// answer like the other types
+    #[default]
+    Answer,
+}
+
+/// SerialPolicy decide what to do when a reloaded zone has a serial not great than the served one.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum SerialPolicy {
+    /// refuse the reload
+    #[default]
+    Keep,
+    /// rewrite serial to current unix timestamp
+    Unixtime,
+    /// rewrite serial to YYYYMMDDnn
+    Date,
+    /// rewrite serial to served serial + 1
+    Increment,
+}
+
+/// ZonemdVerify decide what to do when the zone digest differs from the apex ZONEMD record.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ZonemdVerify {
+    /// refuse to load the zone
+    Reject,
+    /// log the mismatch and load the zone
+    Warn,
+    /// skip the verification
+    #[default]
+    Off,
+}
+
+/// CheckNames decide what to do when a NS or MX target is not a valid host name(rfc1123),
+/// owner names only need to follow the dns length limits.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum CheckNames {
+    /// refuse to load the zone
+    Fail,
+    /// log the invalid name and load the zone
+    Warn,
+    /// skip the check
+    #[default]
+    Ignore,
+}
+
+/// TransferStyle decide how many records are packed in one message of an outgoing zone
+/// transfer.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum TransferStyle {
+    /// pack as many records as fit in each message
+    #[default]
+    ManyAnswers,
+    /// one record per message for old secondaries
+    OneAnswer,
+}
+
+/// Masters is the master setting of a secondary zone, a single remote id or a list of
+/// remote ids for failover.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(untagged)]
+pub enum Masters {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl Masters {
+    /// ids return the remote ids of the masters in the configured order.
+    pub fn ids(&self) -> &[String] {
+        match self {
+            Masters::One(id) => std::slice::from_ref(id),
+            Masters::Many(ids) => ids.as_slice(),
+        }
+    }
+}
+
+impl From<&str> for Masters {
+    fn from(id: &str) -> Self {
+        Masters::One(id.to_owned())
     }
 }
 
@@ -60,9 +368,152 @@ impl ServerSetting {
 pub struct ZoneSetting {
     pub domain: String,
     pub file: String,
-    pub master: Option<String>,
+    /// directory of the secondary zone file, the zone is written to the file after each
+    /// transfer and loaded from it at startup
+    pub storage: Option<String>,
+    /// remote id of the master, or a list of remote ids tried in order
+    pub master: Option<Masters>,
     pub notify: Option<String>,
     pub acl: Option<Vec<String>>,
+    /// records with smaller ttl will be raised to min_ttl when loading
+    pub min_ttl: Option<u32>,
+    /// records with larger ttl will be reduced to max_ttl when loading
+    pub max_ttl: Option<u32>,
+    /// ttl used when the zone file has no $TTL directive
+    pub default_ttl: Option<u32>,
+    /// acl ids of the clients allowed to query this zone, all clients allowed if not set
+    pub allow_query: Option<Vec<String>>,
+    /// the zone is never listed, transferred or shown in stats, only the clients of
+    /// allow_query can query it and others are answered as if it was not served
+    #[serde(default)]
+    pub hidden: bool,
+    /// keep serving the secondary zone after soa expire timer lapses
+    #[serde(default)]
+    pub serve_stale: bool,
+    /// seconds to keep serving stale data after expired, serve forever if not set
+    pub max_stale: Option<u32>,
+    /// what to do when the reloaded zone serial is not increased
+    #[serde(default)]
+    pub serial_policy: SerialPolicy,
+    /// abort loading the zone file when it has more records than max_records
+    pub max_records: Option<usize>,
+    /// abort loading the zone file when the estimated memory(bytes) is more than max_memory
+    pub max_memory: Option<usize>,
+    /// the zone is a catalog zone(rfc9432), its member zones are provisioned as secondaries
+    #[serde(default)]
+    pub catalog: bool,
+    /// verify the zone digest(rfc8976) after the zone is loaded
+    #[serde(default)]
+    pub verify_zonemd: ZonemdVerify,
+    /// synthesize a txt record for each deprecated spf(type 99) record when loading
+    #[serde(default)]
+    pub spf_to_txt: bool,
+    /// abort the transfer from master when it has more records than max_transfer_records
+    pub max_transfer_records: Option<usize>,
+    /// abort the transfer from master when it has more bytes than max_transfer_bytes
+    pub max_transfer_bytes: Option<usize>,
+    /// check the NS and MX targets are valid host names when loading
+    #[serde(default)]
+    pub check_names: CheckNames,
+    /// how records are packed in the outgoing transfer messages
+    #[serde(default)]
+    pub transfer_style: TransferStyle,
+    /// compress the names in outgoing transfer messages, enabled if not set
+    pub transfer_compression: Option<bool>,
+    /// reject the records with more labels than max_label_depth in the owner name when
+    /// loading or transferring, 64 if not set and at most 127
+    pub max_label_depth: Option<usize>,
+    /// verify the rrsig records of the transferred zone with the apex DNSKEY records
+    #[serde(default)]
+    pub verify_dnssec: bool,
+    /// reject the transfer when a rrsig record fails to verify, only log it if not set
+    #[serde(default)]
+    pub verify_dnssec_strict: bool,
+    /// verify at most verify_dnssec_sample rrsig records spread over the zone, all if not set
+    pub verify_dnssec_sample: Option<usize>,
+    /// rules deciding which records an update may change, the first rule matching the
+    /// sender and the record decides. the update acl is used if not set
+    #[serde(default)]
+    pub update_policy: Vec<UpdatePolicyRule>,
+}
+
+impl ZoneSetting {
+    /// storage_file return the file holding the local copy of the secondary zone, a
+    /// relative zone file is placed in the storage directory.
+    pub fn storage_file(&self) -> String {
+        match &self.storage {
+            Some(storage) => Path::new(storage)
+                .join(&self.file)
+                .to_string_lossy()
+                .into_owned(),
+            None => self.file.clone(),
+        }
+    }
+}
+
+/// UpdateAction tell if the records matching an update policy rule can be changed.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum UpdateAction {
+    #[default]
+    Grant,
+    Deny,
+}
+
+/// UpdateMatch tell how the owner name of an update record is matched by the rule name.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum UpdateMatch {
+    /// the owner is the rule name
+    #[default]
+    Name,
+    /// the owner is the rule name or below it
+    Subdomain,
+    /// the owner is the name of the key which signed the update
+    #[serde(rename = "self")]
+    SelfName,
+}
+
+/// UpdatePolicyRule grant or deny the changes of the records matching the name and types to
+/// the updates signed by the key and sent from the address, like the bind update-policy.
+#[derive(Debug, Clone, PartialEq, Deserialize, Default)]
+pub struct UpdatePolicyRule {
+    #[serde(default)]
+    pub action: UpdateAction,
+    /// id of the tsig key the update must be signed with
+    pub key: Option<String>,
+    /// single address, network or `any` the update must be sent from
+    pub address: Option<String>,
+    #[serde(default, rename = "match")]
+    pub matches: UpdateMatch,
+    /// the name matched with the owner name, not used by the self match
+    pub name: Option<String>,
+    /// the record types of the rule, all types if empty or ANY
+    #[serde(default)]
+    pub types: Vec<String>,
+}
+
+/// PolicyAction is the response to a query matching a policy rule.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum PolicyAction {
+    /// answer NXDOMAIN
+    #[default]
+    Nxdomain,
+    /// drop the query without response
+    Drop,
+    /// answer the fixed address in data
+    Fixed,
+}
+
+/// PolicyRule override the answer of a name(or all names below it with `*.name`) without
+/// changing the zone data.
+#[derive(Debug, Clone, Validate, PartialEq, Deserialize, Default)]
+pub struct PolicyRule {
+    pub name: String,
+    pub action: PolicyAction,
+    /// ipv4 or ipv6 address answered by the fixed action
+    pub data: Option<String>,
 }
 
 #[derive(Debug, Clone, Validate, PartialEq, Deserialize, Default)]
@@ -81,6 +532,33 @@ pub struct Log {
     pub query: Option<String>,
     #[validate(custom = "validate_log_level")]
     pub control: Option<String>,
+    /// rotate the target file when it is greater than the size, rotated daily if not set
+    pub rotate_size_mb: Option<u64>,
+    /// rotated files of the target kept, 7 if not set
+    pub keep: Option<usize>,
+    /// facility of the records sent to the syslog target like daemon or local0, daemon if
+    /// not set
+    pub facility: Option<String>,
+    /// app name of the records sent to the syslog target, otter if not set
+    pub tag: Option<String>,
+}
+
+const SYSLOG_FACILITIES: [&str; 24] = [
+    "kern", "user", "mail", "daemon", "auth", "syslog", "lpr", "news", "uucp", "cron", "authpriv",
+    "ftp", "ntp", "audit", "alert", "clock", "local0", "local1", "local2", "local3", "local4",
+    "local5", "local6", "local7",
+];
+
+impl Log {
+    /// syslog_facility return the rfc5424 facility code of the syslog target, none when the
+    /// facility name is unknown.
+    pub fn syslog_facility(&self) -> Option<u8> {
+        let facility = self.facility.as_deref().unwrap_or("daemon").to_lowercase();
+        SYSLOG_FACILITIES
+            .iter()
+            .position(|name| *name == facility)
+            .map(|code| code as u8)
+    }
 }
 
 #[derive(Debug, Clone, Validate, PartialEq, Deserialize, Default)]
@@ -88,17 +566,149 @@ pub struct Remote {
     pub id: String,
     pub address: String,
     pub key: Option<String>,
+    /// local address like ip or ip@port of the queries and transfers sent to the remote,
+    /// server.transfer_source if not set
+    pub source_address: Option<String>,
+}
+
+impl Remote {
+    /// socket_addr parse the remote address like ip or ip@port, the port will be 53 if not set.
+    /// an ipv6 address can be written in brackets like [2001:db8::1]@53.
+    pub fn socket_addr(&self) -> Result<SocketAddr, SettingError> {
+        parse_ip_port(&self.address, DEFAULT_LISTEN_PORT).ok_or_else(|| {
+            SettingError::ValidationError(format!(
+                "remote {} address `{}` is not valid",
+                self.id, self.address
+            ))
+        })
+    }
+
+    /// source_addr parse the local address of the sockets toward the remote, default is the
+    /// server.transfer_source used when source_address is not set. the port will be 0(chosen
+    /// by the system) if not set, and the source must be in the address family of the remote.
+    pub fn source_addr(&self, default: Option<&str>) -> Result<Option<SocketAddr>, SettingError> {
+        let source = match self.source_address.as_deref().or(default) {
+            Some(source) => source,
+            None => return Ok(None),
+        };
+        let addr = parse_ip_port(source, 0).ok_or_else(|| {
+            SettingError::ValidationError(format!(
+                "remote {} source address `{}` is not valid",
+                self.id, source
+            ))
+        })?;
+        let remote = self.socket_addr()?;
+        if addr.is_ipv4() != remote.is_ipv4() {
+            return Err(SettingError::ValidationError(format!(
+                "remote {} source address `{}` is not in the address family of `{}`",
+                self.id, source, self.address
+            )));
+        }
+        Ok(Some(addr))
+    }
+}
+
+/// parse_ip_port parse an address like ip or ip@port, default_port is used if not set.
+fn parse_ip_port(address: &str, default_port: u16) -> Option<SocketAddr> {
+    let mut spliter = address.trim().splitn(2, '@');
+    let ip = spliter.next().and_then(parse_bracketed_ip)?;
+    let port = match spliter.next() {
+        Some(port) => port.trim().parse::<u16>().ok()?,
+        None => default_port,
+    };
+    Some(SocketAddr::new(ip, port))
+}
+
+/// parse_bracketed_ip parse an ip address, brackets are allowed around an ipv6 address only
+/// and must be balanced.
+fn parse_bracketed_ip(ip: &str) -> Option<IpAddr> {
+    let ip = ip.trim();
+    match ip.strip_prefix('[') {
+        Some(inner) => inner
+            .strip_suffix(']')?
+            .parse::<Ipv6Addr>()
+            .ok()
+            .map(IpAddr::V6),
+        None => ip.parse().ok(),
+    }
+}
+
+/// unmap_ipv4 return the ipv4 address of an ipv4-mapped ipv6 address(::ffff:a.b.c.d),
+/// other addresses are returned as is.
+fn unmap_ipv4(ip: &IpAddr) -> IpAddr {
+    match ip {
+        IpAddr::V6(v6) => match v6.segments() {
+            [0, 0, 0, 0, 0, 0xffff, high, low] => {
+                IpAddr::V4(Ipv4Addr::from((u32::from(high) << 16) | u32::from(low)))
+            }
+            _ => *ip,
+        },
+        IpAddr::V4(_) => *ip,
+    }
 }
 
 #[derive(Debug, Clone, Validate, PartialEq, Deserialize, Default)]
 pub struct ACL {
     pub id: String,
+    /// single address, network or `any` for the acl only check the key
     pub address: String,
     #[validate(custom = "validate_action")]
     pub action: String,
+    /// id of the tsig key the message must be signed with
+    pub key: Option<String>,
+}
+
+impl ACL {
+    /// allows check both the source address and the key of the message, key is the name of
+    /// the tsig key which signed the message, None for unsigned messages.
+    pub fn allows(&self, ip: &IpAddr, key: Option<&str>) -> bool {
+        if !self.match_address(ip) {
+            return false;
+        }
+        match (&self.key, key) {
+            (None, _) => true,
+            (Some(expect), Some(key)) => expect
+                .trim_end_matches('.')
+                .eq_ignore_ascii_case(key.trim_end_matches('.')),
+            (Some(_), None) => false,
+        }
+    }
+
+    /// check if the ip address is covered by the acl address, which can be a single
+    /// address like 192.168.1.1, a network like 192.168.3.0/24 or 2001:db8::/32, or any.
+    /// ipv4-mapped ipv6 peers are matched as ipv4 addresses.
+    pub fn match_address(&self, ip: &IpAddr) -> bool {
+        if self.address.trim().eq_ignore_ascii_case("any") {
+            return true;
+        }
+        let ip = &unmap_ipv4(ip);
+        let mut spliter = self.address.trim().splitn(2, '/');
+        let network = match spliter.next().and_then(parse_bracketed_ip) {
+            Some(network) => network,
+            None => return false,
+        };
+        let max_prefix = if network.is_ipv4() { 32 } else { 128 };
+        let prefix = match spliter.next() {
+            Some(prefix) => match prefix.trim().parse::<u32>() {
+                Ok(prefix) if prefix <= max_prefix => prefix,
+                _ => return false,
+            },
+            None => max_prefix,
+        };
+        let (network, ip) = match (network, ip) {
+            (IpAddr::V4(network), IpAddr::V4(ip)) => {
+                (u32::from(network) as u128, u32::from(*ip) as u128)
+            }
+            (IpAddr::V6(network), IpAddr::V6(ip)) => (u128::from(network), u128::from(*ip)),
+            _ => return false,
+        };
+        let shift = max_prefix - prefix;
+        network.checked_shr(shift).unwrap_or(0) == ip.checked_shr(shift).unwrap_or(0)
+    }
 }
 
 #[derive(Debug, Clone, Validate, PartialEq, Deserialize, Default)]
+#[serde(default)]
 pub struct Settings {
     pub server: ServerSetting,
     pub zone: Vec<ZoneSetting>,
@@ -106,34 +716,211 @@ pub struct Settings {
     pub log: Vec<Log>,
     pub remote: Vec<Remote>,
     pub acl: Vec<ACL>,
+    pub policy: Vec<PolicyRule>,
 }
 
 impl Settings {
+    /// read settings from file, the format is detected from the file extension.
     #[allow(dead_code)]
     pub fn new(filename: &str) -> Result<Settings, SettingError> {
+        let format = SettingFormat::from_path(filename)?;
+        Settings::from_source(File::new(filename, format.into()), format)
+    }
+
+    /// read settings from a string with the given format, useful for embedding config in tests.
+    #[allow(dead_code)]
+    pub fn from_str(content: &str, format: SettingFormat) -> Result<Settings, SettingError> {
+        Settings::from_source(File::from_str(content, format.into()), format)
+    }
+
+    fn from_source<T>(source: T, format: SettingFormat) -> Result<Settings, SettingError>
+    where
+        T: config::Source + Send + Sync + 'static,
+    {
         let mut config_obj = Config::new();
         // set default value
-        config_obj.set_default("server.max_edns_size", 1243)?;
-        if let Err(err) = config_obj.merge(File::with_name(filename)) {
-            return Err(SettingError::ParseConfigError(err.to_string()));
+        config_obj.set_default("server.max_edns_size", i64::from(DEFAULT_EDNS_SIZE))?;
+        if let Err(err) = config_obj.merge(source) {
+            return Err(SettingError::ParseConfigError(format!(
+                "[{}] {}",
+                format,
+                err.to_string()
+            )));
         }
         match config_obj.try_into::<Settings>() {
-            Ok(setting) => {
+            Ok(mut setting) => {
                 if let Err(e) = setting.validation() {
-                    return Err(SettingError::ValidationError(e.to_string()));
+                    return Err(SettingError::ValidationError(format!(
+                        "[{}] {}",
+                        format,
+                        e.to_string()
+                    )));
                 }
                 Ok(setting)
             }
-            Err(err) => Err(SettingError::ParseConfigError(err.to_string())),
+            Err(err) => Err(SettingError::ParseConfigError(format!(
+                "[{}] {}",
+                format,
+                err.to_string()
+            ))),
         }
     }
+    /// validation check the settings, out of range values with a safe fallback like
+    /// max_edns_size are clamped instead of rejected.
     #[allow(dead_code)]
-    pub fn validation(&self) -> Result<(), ValidationErrors> {
-        self.validate()
+    pub fn validation(&mut self) -> Result<(), ValidationErrors> {
+        self.validate()?;
+        self.server.clamp_edns_size();
         // extension validate
+        if let Some(err) = self.server.validation() {
+            let mut errors = ValidationErrors::new();
+            errors.add("server", validation_error(err.to_string()));
+            return Err(errors);
+        }
+        if let Err(err) = self.validate_zone_options() {
+            let mut errors = ValidationErrors::new();
+            errors.add("zone", err);
+            return Err(errors);
+        }
+        if let Err(err) = self.validate_acl() {
+            let mut errors = ValidationErrors::new();
+            errors.add("acl", err);
+            return Err(errors);
+        }
+        if let Err(err) = self.validate_policy() {
+            let mut errors = ValidationErrors::new();
+            errors.add("policy", err);
+            return Err(errors);
+        }
+        if let Err(err) = self.validate_remotes() {
+            let mut errors = ValidationErrors::new();
+            errors.add("remote", err);
+            return Err(errors);
+        }
+        if let Err(err) = self.validate_logs() {
+            let mut errors = ValidationErrors::new();
+            errors.add("log", err);
+            return Err(errors);
+        }
+        Ok(())
+    }
+
+    fn validate_logs(&self) -> Result<(), ValidationError> {
+        for log in self.log.iter() {
+            if log.syslog_facility().is_none() {
+                let error_message = format!(
+                    "log {} syslog facility `{}` is unknown",
+                    log.target,
+                    log.facility.as_deref().unwrap_or_default()
+                );
+                return Err(validation_error(error_message));
+            }
+        }
+        Ok(())
+    }
+
+    fn validate_remotes(&self) -> Result<(), ValidationError> {
+        let default = self.server.transfer_source.as_deref();
+        for remote in self.remote.iter() {
+            // remotes with an invalid address are skipped as masters when the server starts
+            if remote.socket_addr().is_err() {
+                continue;
+            }
+            if let Err(err) = remote.source_addr(default) {
+                return Err(validation_error(err.to_string()));
+            }
+        }
+        Ok(())
+    }
+
+    fn validate_acl(&self) -> Result<(), ValidationError> {
+        for acl in self.acl.iter() {
+            if let Some(key) = &acl.key {
+                if self.get_key_by_id(key).is_none() {
+                    let error_message = format!("acl {} reference unknown key={}", acl.id, key);
+                    return Err(validation_error(error_message));
+                }
+            }
+        }
+        Ok(())
     }
 
-    pub fn get_listeners(&self) -> (Vec<String>, Vec<String>) {
+    fn validate_policy(&self) -> Result<(), ValidationError> {
+        for rule in self.policy.iter() {
+            let valid = match (rule.action, &rule.data) {
+                (PolicyAction::Fixed, Some(data)) => IpAddr::from_str(data.trim()).is_ok(),
+                (PolicyAction::Fixed, None) => false,
+                _ => true,
+            };
+            if !valid {
+                let error_message =
+                    format!("policy {} fixed action need an ip address data", rule.name);
+                return Err(validation_error(error_message));
+            }
+        }
+        Ok(())
+    }
+
+    fn validate_zone_options(&self) -> Result<(), ValidationError> {
+        for zone in self.zone.iter() {
+            if let (Some(min_ttl), Some(max_ttl)) = (zone.min_ttl, zone.max_ttl) {
+                if min_ttl > max_ttl {
+                    let error_message = format!(
+                        "zone {} min_ttl={} great than max_ttl={}",
+                        zone.domain, min_ttl, max_ttl
+                    );
+                    return Err(validation_error(error_message));
+                }
+            }
+            if zone.master.as_ref().map(|master| master.ids().is_empty()) == Some(true) {
+                let error_message = format!("zone {} master list is empty", zone.domain);
+                return Err(validation_error(error_message));
+            }
+            if zone.hidden && zone.allow_query.is_none() {
+                let error_message =
+                    format!("zone {} is hidden but has no allow_query", zone.domain);
+                return Err(validation_error(error_message));
+            }
+            self.validate_update_policy(zone)?;
+            for acl_id in zone.allow_query.iter().flatten() {
+                if self.get_acl_by_id(acl_id).is_none() {
+                    let error_message = format!(
+                        "zone {} allow_query reference unknown acl={}",
+                        zone.domain, acl_id
+                    );
+                    return Err(validation_error(error_message));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn validate_update_policy(&self, zone: &ZoneSetting) -> Result<(), ValidationError> {
+        for (index, rule) in zone.update_policy.iter().enumerate() {
+            let error = match (&rule.key, &rule.address, rule.matches, &rule.name) {
+                (None, None, _, _) => Some("need a key or an address".to_owned()),
+                (Some(key), _, _, _) if self.get_key_by_id(key).is_none() => {
+                    Some(format!("reference unknown key={}", key))
+                }
+                (None, _, UpdateMatch::SelfName, _) => Some("self match need a key".to_owned()),
+                (_, _, UpdateMatch::Name, None) | (_, _, UpdateMatch::Subdomain, None) => {
+                    Some("name or subdomain match need a name".to_owned())
+                }
+                _ => None,
+            };
+            if let Some(error) = error {
+                let error_message = format!(
+                    "zone {} update_policy rule {} {}",
+                    zone.domain, index, error
+                );
+                return Err(validation_error(error_message));
+            }
+        }
+        Ok(())
+    }
+
+    /// return (tcp listeners, udp listeners)
+    pub fn get_listeners(&self) -> Result<(Vec<SocketAddr>, Vec<SocketAddr>), SettingError> {
         self.server.get_listen_addr()
     }
     /// return (filepath, domain)
@@ -173,18 +960,24 @@ impl Settings {
     }
 }
 
+/// validation_error build an error carrying the owned message, the code is the same for all
+/// the setting errors.
+fn validation_error(message: String) -> ValidationError {
+    let mut error = ValidationError::new("setting");
+    error.message = Some(Cow::Owned(message));
+    error
+}
+
 fn validate_action(action: &str) -> Result<(), ValidationError> {
     let action = action.to_lowercase();
-    let desired_action = ["update", "notify", "transfer"];
+    let desired_action = ["update", "notify", "transfer", "query"];
     for i in desired_action.iter() {
         if action.eq(*i) {
             return Ok(());
         }
     }
     let error_message = format!("action validate fail: unknown action={}", action);
-    Err(ValidationError::new(Box::leak(
-        error_message.into_boxed_str(),
-    )))
+    Err(validation_error(error_message))
 }
 
 fn validate_log_level(level: &str) -> Result<(), ValidationError> {
@@ -196,9 +989,7 @@ fn validate_log_level(level: &str) -> Result<(), ValidationError> {
         }
     }
     let error_message = format!("logging level validate fail: unknown level={}", level);
-    Err(ValidationError::new(Box::leak(
-        error_message.into_boxed_str(),
-    )))
+    Err(validation_error(error_message))
 }
 #[cfg(test)]
 mod test {
@@ -208,24 +999,230 @@ mod test {
         let server = ServerSetting {
             listen: vec!["0.0.0.0:53".to_string(), "127.0.0.1:53/tcp".to_string()],
             max_edns_size: 1243,
+            ..Default::default()
         };
         assert_eq!(server.validation(), None);
-        let (tcplisteners, udplisteners) = server.get_listen_addr();
+        let (tcplisteners, udplisteners) = server.get_listen_addr().unwrap();
         assert_eq!(
             tcplisteners,
-            vec!["0.0.0.0:53".to_string(), "127.0.0.1:53".to_string()]
+            vec![
+                "0.0.0.0:53".parse::<SocketAddr>().unwrap(),
+                "127.0.0.1:53".parse::<SocketAddr>().unwrap()
+            ]
+        );
+        assert_eq!(
+            udplisteners,
+            vec!["0.0.0.0:53".parse::<SocketAddr>().unwrap()]
         );
-        assert_eq!(udplisteners, vec!["0.0.0.0:53".to_string()]);
         let server = ServerSetting {
-            listen: vec!["0.0.0.0:53".to_string(), "127.0.0.1:53/tcp".to_string()],
-            max_edns_size: 100,
+            listen: vec!["eth0".to_string()],
+            max_edns_size: 1243,
+            ..Default::default()
         };
         assert_eq!(server.validation().is_some(), true);
     }
     #[test]
+    fn test_max_edns_size_clamp() {
+        for (configured, effective) in [
+            (0, 1232),
+            (100, 512),
+            (511, 512),
+            (512, 512),
+            (1232, 1232),
+            (1400, 1400),
+            (4096, 4096),
+            (4097, 4096),
+            (65535, 4096),
+        ]
+        .iter()
+        {
+            let mut server = ServerSetting {
+                listen: vec!["0.0.0.0:53".to_string()],
+                max_edns_size: *configured,
+                ..Default::default()
+            };
+            assert_eq!(server.edns_size(), *effective, "{}", configured);
+            server.clamp_edns_size();
+            assert_eq!(server.max_edns_size, *effective, "{}", configured);
+            assert_eq!(server.validation(), None);
+        }
+        let mut setting = Settings::from_str(
+            "server:\n  listen: [127.0.0.1]\n  max_edns_size: 65535\n",
+            SettingFormat::Yaml,
+        )
+        .unwrap();
+        assert_eq!(setting.server.max_edns_size, 4096);
+        setting.server.max_edns_size = 100;
+        assert_eq!(setting.validation(), Ok(()));
+        assert_eq!(setting.server.max_edns_size, 512);
+        let setting =
+            Settings::from_str("server:\n  listen: [127.0.0.1]\n", SettingFormat::Yaml).unwrap();
+        assert_eq!(setting.server.max_edns_size, 1232);
+    }
+    #[test]
+    fn test_get_listen_addr_by_transport() {
+        let addrs = |list: &[&str]| -> Vec<SocketAddr> {
+            list.iter().map(|addr| addr.parse().unwrap()).collect()
+        };
+        let server = |listen: &[&str], udp: Option<&[&str]>, tcp: Option<&[&str]>| ServerSetting {
+            listen: listen.iter().map(|addr| addr.to_string()).collect(),
+            listen_udp: udp.map(|list| list.iter().map(|addr| addr.to_string()).collect()),
+            listen_tcp: tcp.map(|list| list.iter().map(|addr| addr.to_string()).collect()),
+            max_edns_size: 1232,
+            ..Default::default()
+        };
+        // udp only
+        let setting = server(&[], Some(&["0.0.0.0", "[::]:5353"]), None);
+        assert_eq!(
+            setting.get_listen_addr().unwrap(),
+            (vec![], addrs(&["0.0.0.0:53", "[::]:5353"]))
+        );
+        // tcp only, the tcp list replace the shared list
+        let setting = server(&["0.0.0.0:53"], Some(&[]), Some(&["10.0.0.1:53"]));
+        assert_eq!(
+            setting.get_listen_addr().unwrap(),
+            (addrs(&["10.0.0.1:53"]), vec![])
+        );
+        // mixed, tcp behind the load balancer and udp everywhere
+        let setting = server(&["0.0.0.0:53", "[::]:53"], None, Some(&["10.0.0.1:53"]));
+        assert_eq!(
+            setting.get_listen_addr().unwrap(),
+            (addrs(&["10.0.0.1:53"]), addrs(&["0.0.0.0:53", "[::]:53"]))
+        );
+        assert_eq!(setting.validation(), None);
+
+        assert!(server(&[], None, None).validation().is_some());
+        assert!(server(&["0.0.0.0"], Some(&[]), Some(&[]))
+            .validation()
+            .is_some());
+        assert!(server(&[], Some(&["eth0"]), None).validation().is_some());
+
+        let setting = Settings::from_str(
+            "server:\n  listen_udp: [127.0.0.1]\n  listen_tcp: [\"127.0.0.1:5353\"]\n  max_edns_size: 1232\n",
+            SettingFormat::Yaml,
+        )
+        .unwrap();
+        assert_eq!(
+            setting.get_listeners().unwrap(),
+            (addrs(&["127.0.0.1:5353"]), addrs(&["127.0.0.1:53"]))
+        );
+    }
+    #[test]
+    fn test_remote_socket_addr() {
+        let remote = |address: &str| Remote {
+            id: "master".to_string(),
+            address: address.to_string(),
+            ..Default::default()
+        };
+        assert_eq!(
+            remote("127.0.0.1").socket_addr().unwrap(),
+            "127.0.0.1:53".parse::<SocketAddr>().unwrap()
+        );
+        assert_eq!(
+            remote("192.168.2.1@5353").socket_addr().unwrap(),
+            "192.168.2.1:5353".parse::<SocketAddr>().unwrap()
+        );
+        assert_eq!(
+            remote("[2001:db8::1]@53").socket_addr().unwrap(),
+            "[2001:db8::1]:53".parse::<SocketAddr>().unwrap()
+        );
+        assert_eq!(
+            remote(" 2001:db8::1@5353 ").socket_addr().unwrap(),
+            "[2001:db8::1]:5353".parse::<SocketAddr>().unwrap()
+        );
+        assert_eq!(
+            remote("[2001:db8::1]").socket_addr().unwrap(),
+            "[2001:db8::1]:53".parse::<SocketAddr>().unwrap()
+        );
+        assert!(remote("master.example.com").socket_addr().is_err());
+        assert!(remote("127.0.0.1@port").socket_addr().is_err());
+        for address in [
+            "[2001:db8::1@53",
+            "2001:db8::1]@53",
+            "[192.0.2.1]@53",
+            "[2001:db8::1]:53",
+        ]
+        .iter()
+        {
+            assert!(remote(address).socket_addr().is_err(), "{}", address);
+        }
+    }
+
+    #[test]
+    fn test_remote_source_addr() {
+        let remote = |address: &str, source: Option<&str>| Remote {
+            id: "master".to_string(),
+            address: address.to_string(),
+            source_address: source.map(|source| source.to_string()),
+            ..Default::default()
+        };
+        let addr = |addr: &str| Some(addr.parse::<SocketAddr>().unwrap());
+        assert_eq!(remote("127.0.0.1", None).source_addr(None).unwrap(), None);
+        assert_eq!(
+            remote("127.0.0.1", Some("127.0.0.2"))
+                .source_addr(None)
+                .unwrap(),
+            addr("127.0.0.2:0")
+        );
+        // the remote source_address replace the server transfer_source
+        assert_eq!(
+            remote("127.0.0.1", Some("127.0.0.2@5300"))
+                .source_addr(Some("127.0.0.3"))
+                .unwrap(),
+            addr("127.0.0.2:5300")
+        );
+        assert_eq!(
+            remote("[2001:db8::1]@53", None)
+                .source_addr(Some("[2001:db8::53]"))
+                .unwrap(),
+            addr("[2001:db8::53]:0")
+        );
+        assert!(remote("127.0.0.1", Some("source"))
+            .source_addr(None)
+            .is_err());
+        // a v4 source can not reach a v6 master and the reverse
+        assert!(remote("2001:db8::1", Some("127.0.0.2"))
+            .source_addr(None)
+            .is_err());
+        assert!(remote("127.0.0.1", None)
+            .source_addr(Some("2001:db8::53"))
+            .is_err());
+
+        let mut setting = Settings::new("example.config.yaml").unwrap();
+        setting.remote[0].source_address = Some("127.0.0.2".to_string());
+        assert!(setting.validation().is_ok());
+        setting.remote[0].source_address = None;
+        setting.server.transfer_source = Some("[::1]".to_string());
+        assert!(setting.validation().is_err());
+    }
+
+    #[test]
+    fn test_parse_listen_addr() {
+        let cases = vec![
+            ("0.0.0.0", "0.0.0.0:53"),
+            ("127.0.0.1:5353", "127.0.0.1:5353"),
+            ("::", "[::]:53"),
+            ("[::1]", "[::1]:53"),
+            ("[::]:53", "[::]:53"),
+            ("[2001:db8::1]:5353", "[2001:db8::1]:5353"),
+        ];
+        for (addr, expect) in cases {
+            assert_eq!(
+                parse_listen_addr(addr).unwrap(),
+                vec![expect.parse::<SocketAddr>().unwrap()]
+            );
+        }
+        assert!(parse_listen_addr("localhost")
+            .unwrap()
+            .iter()
+            .all(|addr| addr.port() == 53 && addr.ip().is_loopback()));
+        assert!(parse_listen_addr("127.0.0.1:abc").is_err());
+        assert!(parse_listen_addr("[::1]:70000").is_err());
+    }
+    #[test]
     fn test_read_config() {
         match Settings::new("example.config.yaml") {
-            Ok(setting) => match setting.validation() {
+            Ok(mut setting) => match setting.validation() {
                 Ok(_) => {}
                 Err(err) => assert!(
                     false,
@@ -245,7 +1242,7 @@ mod test {
         assert_eq!(setting.server.max_edns_size, 1024);
         assert_eq!(setting.zone[0].domain, "abc.com");
         assert_eq!(setting.zone[0].file, "example.com.zone");
-        assert_eq!(setting.zone[0].master, Some("master01".to_owned()));
+        assert_eq!(setting.zone[0].master, Some(Masters::from("master01")));
         assert_eq!(
             setting.zone[0].acl.as_ref().unwrap()[0],
             "notify_from_master"
@@ -276,6 +1273,10 @@ mod test {
                 server: Some("info".to_string()),
                 query: Some("warning".to_string()),
                 control: Some("debug".to_string()),
+                rotate_size_mb: None,
+                keep: None,
+                facility: None,
+                tag: None,
             }]
         );
 
@@ -285,12 +1286,14 @@ mod test {
                 Remote {
                     id: "master01".to_string(),
                     address: "127.0.0.1".to_string(),
-                    key: None
+                    key: None,
+                    source_address: None,
                 },
                 Remote {
                     id: "slave1".to_string(),
                     address: "192.168.2.1@53".to_string(),
-                    key: Some("slave1_key".to_owned())
+                    key: Some("slave1_key".to_owned()),
+                    source_address: None,
                 }
             ]
         );
@@ -301,25 +1304,392 @@ mod test {
                 ACL {
                     id: "notify_from_master".to_string(),
                     address: "192.168.1.1".to_string(),
-                    action: "notify".to_owned()
+                    action: "notify".to_owned(),
+                    key: None
                 },
                 ACL {
                     id: "slave1_acl".to_string(),
                     address: "192.168.2.1".to_string(),
-                    action: "transfer".to_string()
+                    action: "transfer".to_string(),
+                    key: None
                 },
                 ACL {
                     id: "others_acl".to_string(),
                     address: "192.168.3.0/24".to_string(),
-                    action: "transfer".to_string()
+                    action: "transfer".to_string(),
+                    key: None
                 },
                 ACL {
                     id: "update_acl".to_string(),
                     address: "192.168.3.0/24".to_string(),
-                    action: "update".to_string()
+                    action: "update".to_string(),
+                    key: None
+                }
+            ]
+        );
+    }
+    #[test]
+    fn test_config_formats() {
+        let yaml = Settings::new("example.config.yaml").unwrap();
+        let toml = Settings::new("example.config.toml").unwrap();
+        let json = Settings::new("example.config.json").unwrap();
+        assert_eq!(yaml, toml);
+        assert_eq!(yaml, json);
+        assert_eq!(
+            Settings::new("example.config.ini"),
+            Err(SettingError::UnknownConfigFormat(
+                "example.config.ini".to_owned()
+            ))
+        );
+    }
+    #[test]
+    fn test_config_from_str() {
+        let setting = Settings::from_str(
+            "[server]\nlisten = [\"127.0.0.1:53\"]\n",
+            SettingFormat::Toml,
+        )
+        .unwrap();
+        assert_eq!(setting.server.listen, vec!["127.0.0.1:53".to_string()]);
+        assert_eq!(setting.server.max_edns_size, DEFAULT_EDNS_SIZE);
+
+        match Settings::from_str(
+            r#"{"server": {"listen": ["127.0.0.1:53"], "max_edns_size": "abc"}}"#,
+            SettingFormat::Json,
+        ) {
+            Err(SettingError::ParseConfigError(msg)) => {
+                assert!(msg.starts_with("[json]"));
+                assert!(msg.contains("server.max_edns_size"));
+            }
+            other => assert!(false, format!("expect parse error, got {:?}", other)),
+        }
+    }
+    #[test]
+    fn test_zone_masters() {
+        let zone = |master: &str, format: SettingFormat| {
+            let content = match format {
+                SettingFormat::Yaml => format!(
+                    "server:\n  listen: [127.0.0.1]\nzone:\n  - domain: example.com\n    file: example.com.zone\n    master: {}\n",
+                    master
+                ),
+                _ => format!(
+                    "[server]\nlisten = [\"127.0.0.1\"]\n[[zone]]\ndomain = \"example.com\"\nfile = \"example.com.zone\"\nmaster = {}\n",
+                    master
+                ),
+            };
+            Settings::from_str(&content, format).map(|setting| setting.zone[0].master.clone())
+        };
+        // the single remote id form is still accepted
+        for (master, format) in [
+            ("master01", SettingFormat::Yaml),
+            ("\"master01\"", SettingFormat::Toml),
+        ]
+        .iter()
+        {
+            let master = zone(master, *format).unwrap().unwrap();
+            assert_eq!(master, Masters::from("master01"));
+            assert_eq!(master.ids(), ["master01".to_owned()]);
+        }
+        for (master, format) in [
+            ("[master01, master02]", SettingFormat::Yaml),
+            ("[\"master01\", \"master02\"]", SettingFormat::Toml),
+        ]
+        .iter()
+        {
+            let master = zone(master, *format).unwrap().unwrap();
+            assert_eq!(
+                master,
+                Masters::Many(vec!["master01".to_owned(), "master02".to_owned()])
+            );
+            assert_eq!(master.ids(), ["master01".to_owned(), "master02".to_owned()]);
+        }
+        assert!(zone("[]", SettingFormat::Yaml).is_err());
+    }
+    #[test]
+    fn test_zone_transfer_format() {
+        let zone = |options: &str| {
+            Settings::from_str(
+                &format!(
+                    "server:\n  listen: [127.0.0.1]\nzone:\n  - domain: example.com\n    file: example.com.zone\n{}",
+                    options
+                ),
+                SettingFormat::Yaml,
+            )
+            .map(|setting| setting.zone[0].clone())
+        };
+        let default = zone("").unwrap();
+        assert_eq!(default.transfer_style, TransferStyle::ManyAnswers);
+        assert_eq!(default.transfer_compression, None);
+        let old =
+            zone("    transfer_style: one-answer\n    transfer_compression: false\n").unwrap();
+        assert_eq!(old.transfer_style, TransferStyle::OneAnswer);
+        assert_eq!(old.transfer_compression, Some(false));
+        assert!(zone("    transfer_style: one_answer\n").is_err());
+    }
+    #[test]
+    fn test_acl_match_address() {
+        let acl = ACL {
+            id: "others_acl".to_string(),
+            address: "192.168.3.0/24".to_string(),
+            action: "query".to_string(),
+            key: None,
+        };
+        assert!(acl.match_address(&"192.168.3.10".parse().unwrap()));
+        assert!(!acl.match_address(&"192.168.4.10".parse().unwrap()));
+        assert!(!acl.match_address(&"::1".parse().unwrap()));
+        let acl = ACL {
+            id: "single".to_string(),
+            address: "2001:db8::1".to_string(),
+            action: "query".to_string(),
+            key: None,
+        };
+        assert!(acl.match_address(&"2001:db8::1".parse().unwrap()));
+        assert!(!acl.match_address(&"2001:db8::2".parse().unwrap()));
+        let acl = ACL {
+            id: "any".to_string(),
+            address: "0.0.0.0/0".to_string(),
+            action: "query".to_string(),
+            key: None,
+        };
+        assert!(acl.match_address(&"10.0.0.1".parse().unwrap()));
+        assert!(!acl.match_address(&"2001:db8::1".parse().unwrap()));
+        // v6 prefixes
+        for (address, matched, unmatched) in [
+            ("2001:db8::/32", "2001:db8:ffff::1", "2001:db9::1"),
+            ("[2001:db8::]/32", "2001:db8::53", "2001:db7:ffff::1"),
+            (
+                "2001:db8:1:2::/64",
+                "2001:db8:1:2:aaaa::1",
+                "2001:db8:1:3::1",
+            ),
+            ("2001:db8::1/128", "2001:db8::1", "2001:db8::2"),
+            ("::/0", "::1", "192.0.2.1"),
+            ("::1", "::1", "::2"),
+        ]
+        .iter()
+        {
+            let acl = ACL {
+                id: "v6".to_string(),
+                address: address.to_string(),
+                action: "query".to_string(),
+                key: None,
+            };
+            assert!(acl.match_address(&matched.parse().unwrap()), "{}", address);
+            assert!(
+                !acl.match_address(&unmatched.parse().unwrap()),
+                "{}",
+                address
+            );
+        }
+        // ipv4-mapped peers match v4 entries
+        let acl = ACL {
+            id: "mapped".to_string(),
+            address: "192.168.3.0/24".to_string(),
+            action: "query".to_string(),
+            key: None,
+        };
+        assert!(acl.match_address(&"::ffff:192.168.3.10".parse().unwrap()));
+        assert!(!acl.match_address(&"::ffff:192.168.4.10".parse().unwrap()));
+        let acl = ACL {
+            address: "2001:db8::/129".to_string(),
+            ..acl
+        };
+        assert!(!acl.match_address(&"2001:db8::1".parse().unwrap()));
+    }
+    #[test]
+    fn test_acl_allows() {
+        let acl = |address: &str, key: Option<&str>| ACL {
+            id: "transfer_acl".to_string(),
+            address: address.to_string(),
+            action: "transfer".to_string(),
+            key: key.map(|key| key.to_string()),
+        };
+        let (slave, other) = (
+            "192.168.2.1".parse().unwrap(),
+            "192.168.4.1".parse().unwrap(),
+        );
+        // address only, signed or not
+        let address_only = acl("192.168.2.0/24", None);
+        assert!(address_only.allows(&slave, None));
+        assert!(address_only.allows(&slave, Some("slave1_key")));
+        assert!(!address_only.allows(&other, None));
+        // key only
+        let key_only = acl("any", Some("slave1_key"));
+        assert!(key_only.allows(&slave, Some("slave1_key")));
+        assert!(key_only.allows(&other, Some("SLAVE1_KEY.")));
+        assert!(!key_only.allows(&slave, Some("other_key")));
+        assert!(!key_only.allows(&slave, None));
+        // address and key
+        let both = acl("192.168.2.1", Some("slave1_key"));
+        assert!(both.allows(&slave, Some("slave1_key")));
+        assert!(!both.allows(&slave, None));
+        // signed by the key but from the wrong address
+        assert!(!both.allows(&other, Some("slave1_key")));
+
+        let mut setting = Settings::new("example.config.yaml").unwrap();
+        setting.acl.push(acl("any", Some("slave1_key")));
+        assert_eq!(setting.validation(), Ok(()));
+        setting.acl.push(acl("any", Some("missing_key")));
+        assert!(setting.validation().is_err());
+    }
+    #[test]
+    fn test_zone_options_validation() {
+        let mut setting = Settings::new("example.config.yaml").unwrap();
+        setting.zone[0].min_ttl = Some(60);
+        setting.zone[0].max_ttl = Some(86400);
+        setting.zone[0].allow_query = Some(vec!["others_acl".to_string()]);
+        assert!(setting.validation().is_ok());
+        setting.zone[0].allow_query = Some(vec!["unknown_acl".to_string()]);
+        assert!(setting.validation().is_err());
+        setting.zone[0].allow_query = None;
+        setting.zone[0].hidden = true;
+        assert!(setting.validation().is_err());
+        setting.zone[0].allow_query = Some(vec!["others_acl".to_string()]);
+        assert!(setting.validation().is_ok());
+        setting.zone[0].allow_query = None;
+        setting.zone[0].hidden = false;
+        setting.zone[0].min_ttl = Some(86401);
+        assert!(setting.validation().is_err());
+    }
+    #[test]
+    fn test_serial_policy() {
+        let setting = Settings::from_str(
+            "server:\n  listen: [127.0.0.1]\nzone:\n  - domain: example.com\n    file: example.com.zone\n    serial_policy: date\n",
+            SettingFormat::Yaml,
+        )
+        .unwrap();
+        assert_eq!(setting.zone[0].serial_policy, SerialPolicy::Date);
+        let setting = Settings::new("example.config.yaml").unwrap();
+        assert_eq!(setting.zone[0].serial_policy, SerialPolicy::Keep);
+    }
+    #[test]
+    fn test_any_over_udp() {
+        let setting = Settings::from_str(
+            "server:\n  listen: [127.0.0.1]\n  any_over_udp: truncate\n",
+            SettingFormat::Yaml,
+        )
+        .unwrap();
+        assert_eq!(setting.server.any_over_udp, AnyOverUdp::Truncate);
+        let setting = Settings::new("example.config.yaml").unwrap();
+        assert_eq!(setting.server.any_over_udp, AnyOverUdp::Answer);
+    }
+    #[test]
+    fn test_syslog_setting() {
+        let setting = Settings::from_str(
+            "server:\n  listen: [127.0.0.1]\nlog:\n  - target: syslog\n    server: warning\n    facility: LOCAL3\n    tag: dns\n",
+            SettingFormat::Yaml,
+        )
+        .unwrap();
+        assert_eq!(setting.log[0].syslog_facility(), Some(19));
+        assert_eq!(setting.log[0].tag.as_deref(), Some("dns"));
+        let setting = Settings::new("example.config.yaml").unwrap();
+        assert_eq!(setting.log[0].syslog_facility(), Some(3));
+        assert!(Settings::from_str(
+            "server:\n  listen: [127.0.0.1]\nlog:\n  - target: syslog\n    facility: local9\n",
+            SettingFormat::Yaml,
+        )
+        .is_err());
+    }
+    #[test]
+    fn test_zone_storage_file() {
+        let setting = Settings::new("example.config.yaml").unwrap();
+        assert_eq!(setting.zone[0].storage_file(), "example.com.zone");
+        assert_eq!(
+            setting.zone[2].storage_file(),
+            "/abc/zones/example.com.zone"
+        );
+    }
+    #[test]
+    fn test_check_names() {
+        let setting = Settings::from_str(
+            "server:\n  listen: [127.0.0.1]\nzone:\n  - domain: example.com\n    file: example.com.zone\n    check_names: fail\n",
+            SettingFormat::Yaml,
+        )
+        .unwrap();
+        assert_eq!(setting.zone[0].check_names, CheckNames::Fail);
+        let setting = Settings::new("example.config.yaml").unwrap();
+        assert_eq!(setting.zone[0].check_names, CheckNames::Ignore);
+    }
+    #[test]
+    fn test_update_policy_setting() {
+        let zone = |rules: &str| {
+            Settings::from_str(
+                &format!(
+                    "server:\n  listen: [127.0.0.1]\nkey:\n  - id: ddns-key\n    algorithm: hmac-sha256\n    secret: Wg==\nzone:\n  - domain: example.com\n    file: example.com.zone\n    update_policy:\n{}",
+                    rules
+                ),
+                SettingFormat::Yaml,
+            )
+            .map(|setting| setting.zone[0].clone())
+        };
+        let setting = zone(
+            "      - key: ddns-key\n        name: host1.example.com\n        types: [A, AAAA]\n      \
+             - action: deny\n        address: 10.0.0.0/8\n        match: subdomain\n        name: dyn.example.com\n      \
+             - key: ddns-key\n        match: self\n",
+        )
+        .unwrap();
+        assert_eq!(
+            setting.update_policy,
+            vec![
+                UpdatePolicyRule {
+                    key: Some("ddns-key".to_owned()),
+                    name: Some("host1.example.com".to_owned()),
+                    types: vec!["A".to_owned(), "AAAA".to_owned()],
+                    ..Default::default()
+                },
+                UpdatePolicyRule {
+                    action: UpdateAction::Deny,
+                    address: Some("10.0.0.0/8".to_owned()),
+                    matches: UpdateMatch::Subdomain,
+                    name: Some("dyn.example.com".to_owned()),
+                    ..Default::default()
+                },
+                UpdatePolicyRule {
+                    key: Some("ddns-key".to_owned()),
+                    matches: UpdateMatch::SelfName,
+                    ..Default::default()
+                },
+            ]
+        );
+        for rules in [
+            "      - name: host1.example.com\n",
+            "      - key: other-key\n        name: host1.example.com\n",
+            "      - address: any\n        match: self\n",
+            "      - key: ddns-key\n        match: subdomain\n",
+            "      - key: ddns-key\n        match: zone\n        name: example.com\n",
+            "      - key: ddns-key\n        action: allow\n        name: example.com\n",
+        ]
+        .iter()
+        {
+            assert!(zone(rules).is_err(), "{}", rules);
+        }
+    }
+    #[test]
+    fn test_policy_setting() {
+        let setting = Settings::from_str(
+            "server:\n  listen: [127.0.0.1]\npolicy:\n  - name: \"*.tracker.example\"\n    action: drop\n  - name: ads.example\n    action: fixed\n    data: 192.0.2.10\n",
+            SettingFormat::Yaml,
+        )
+        .unwrap();
+        assert_eq!(
+            setting.policy,
+            vec![
+                PolicyRule {
+                    name: "*.tracker.example".to_string(),
+                    action: PolicyAction::Drop,
+                    data: None,
+                },
+                PolicyRule {
+                    name: "ads.example".to_string(),
+                    action: PolicyAction::Fixed,
+                    data: Some("192.0.2.10".to_string()),
                 }
             ]
         );
+        let mut setting = setting;
+        setting.policy[1].data = Some("not-an-address".to_string());
+        assert!(setting.validation().is_err());
+        setting.policy[1].data = None;
+        assert!(setting.validation().is_err());
     }
     #[test]
     fn test_config_method() {
@@ -330,7 +1700,8 @@ mod test {
                 ACL {
                     id: "update_acl".to_string(),
                     address: "192.168.3.0/24".to_string(),
-                    action: "update".to_string()
+                    action: "update".to_string(),
+                    key: None
                 }
             );
         } else {
@@ -343,7 +1714,8 @@ mod test {
                 Remote {
                     id: "master01".to_string(),
                     address: "127.0.0.1".to_string(),
-                    key: None
+                    key: None,
+                    source_address: None,
                 }
             );
         } else {
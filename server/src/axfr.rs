@@ -0,0 +1,388 @@
+use dnsproto::message::{Message, Record};
+use dnsproto::meta::ResourceRecord;
+use otterlib::errors::DNSProtoErr;
+use otterlib::setting::{TransferStyle, ZoneSetting};
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+/// default max outgoing transfers served at the same time
+pub(crate) const DEFAULT_MAX_CONCURRENT_TRANSFERS: usize = 10;
+/// default max outgoing transfers served to one client at the same time
+pub(crate) const DEFAULT_MAX_TRANSFERS_PER_CLIENT: usize = 2;
+/// max bytes(uncompressed) of the records packed in one transfer message
+const TRANSFER_MESSAGE_SIZE: usize = 16 * 1024;
+
+/// TransferFormat is how the records of an outgoing transfer are written in messages.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct TransferFormat {
+    pub(crate) style: TransferStyle,
+    pub(crate) compression: bool,
+}
+
+impl Default for TransferFormat {
+    fn default() -> Self {
+        TransferFormat {
+            style: TransferStyle::ManyAnswers,
+            compression: true,
+        }
+    }
+}
+
+impl From<&ZoneSetting> for TransferFormat {
+    fn from(zone: &ZoneSetting) -> Self {
+        TransferFormat {
+            style: zone.transfer_style,
+            compression: zone.transfer_compression.unwrap_or(true),
+        }
+    }
+}
+
+/// TransferLimiter bound the outgoing transfers of the server and of each client, a
+/// transfer over the limits is rejected instead of waiting for a free slot.
+#[derive(Debug)]
+pub(crate) struct TransferLimiter {
+    global: Arc<Semaphore>,
+    per_client: usize,
+    clients: Arc<Mutex<HashMap<IpAddr, usize>>>,
+    rate_limit: Option<u64>,
+}
+
+impl Default for TransferLimiter {
+    fn default() -> Self {
+        TransferLimiter::new(
+            DEFAULT_MAX_CONCURRENT_TRANSFERS,
+            DEFAULT_MAX_TRANSFERS_PER_CLIENT,
+            None,
+        )
+    }
+}
+
+/// TransferPermit hold a transfer slot until it is dropped.
+#[derive(Debug)]
+pub(crate) struct TransferPermit {
+    _permit: OwnedSemaphorePermit,
+    client: IpAddr,
+    clients: Arc<Mutex<HashMap<IpAddr, usize>>>,
+}
+
+impl Drop for TransferPermit {
+    fn drop(&mut self) {
+        let mut clients = self.clients.lock().unwrap();
+        if let Some(count) = clients.get_mut(&self.client) {
+            *count -= 1;
+            if *count == 0 {
+                clients.remove(&self.client);
+            }
+        }
+    }
+}
+
+impl TransferLimiter {
+    pub(crate) fn new(
+        max_concurrent: usize,
+        per_client: usize,
+        rate_limit: Option<u64>,
+    ) -> TransferLimiter {
+        TransferLimiter {
+            global: Arc::new(Semaphore::new(max_concurrent)),
+            per_client,
+            clients: Arc::new(Mutex::new(HashMap::new())),
+            rate_limit: rate_limit.filter(|rate| *rate > 0),
+        }
+    }
+
+    /// try_acquire return None when the server or the client has no free transfer slot.
+    pub(crate) fn try_acquire(&self, client: IpAddr) -> Option<TransferPermit> {
+        let mut clients = self.clients.lock().unwrap();
+        let count = clients.get(&client).copied().unwrap_or(0);
+        if count >= self.per_client {
+            return None;
+        }
+        let permit = self.global.clone().try_acquire_owned().ok()?;
+        clients.insert(client, count + 1);
+        Some(TransferPermit {
+            _permit: permit,
+            client,
+            clients: self.clients.clone(),
+        })
+    }
+
+    pub(crate) fn rate_limit(&self) -> Option<u64> {
+        self.rate_limit
+    }
+}
+
+/// transfer_messages pack the records into tcp framed responses of the query, each
+/// response holds at most TRANSFER_MESSAGE_SIZE bytes of records before compression, or
+/// a single record in the one-answer style.
+pub(crate) fn transfer_messages(
+    query: &Message,
+    records: Vec<ResourceRecord>,
+    format: TransferFormat,
+) -> Result<Vec<Vec<u8>>, DNSProtoErr> {
+    let encode_chunk = |chunk: Vec<Record>| {
+        let mut message = Message::new_transfer_message(query);
+        message.update_answer(chunk);
+        if format.compression {
+            message.encode(false)
+        } else {
+            message.encode_uncompressed(false)
+        }
+    };
+    let mut messages = vec![];
+    let mut chunk = vec![];
+    let mut size = 0;
+    for rr in records {
+        // a record which fails to encode goes alone and reports the error
+        let rr_size = match format.style {
+            TransferStyle::ManyAnswers => rr
+                .encode_canonical()
+                .map(|wire| wire.len())
+                .unwrap_or(TRANSFER_MESSAGE_SIZE),
+            TransferStyle::OneAnswer => TRANSFER_MESSAGE_SIZE,
+        };
+        if !chunk.is_empty() && size + rr_size > TRANSFER_MESSAGE_SIZE {
+            messages.push(encode_chunk(std::mem::take(&mut chunk))?);
+            size = 0;
+        }
+        size += rr_size;
+        chunk.push(Record::AnswerRecord(rr));
+    }
+    if !chunk.is_empty() {
+        messages.push(encode_chunk(chunk)?);
+    }
+    Ok(messages)
+}
+
+/// write_paced write the messages in order, when rate_limit(bytes per second) is set each
+/// write waits until the bytes already sent are within the rate.
+pub(crate) async fn write_paced<W: AsyncWrite + Unpin>(
+    writer: &mut W,
+    messages: &[Vec<u8>],
+    rate_limit: Option<u64>,
+) -> std::io::Result<()> {
+    let started = Instant::now();
+    let mut sent: u64 = 0;
+    for message in messages.iter() {
+        if let Some(rate) = rate_limit {
+            let due = Duration::from_secs_f64(sent as f64 / rate as f64);
+            let elapsed = started.elapsed();
+            if due > elapsed {
+                tokio::time::sleep(due - elapsed).await;
+            }
+        }
+        writer.write_all(message).await?;
+        sent += message.len() as u64;
+    }
+    writer.flush().await
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use dnsproto::dnsname::DNSName;
+    use dnsproto::meta::{DNSClass, DNSType, Header, Question};
+    use storage::storage::SafeRBTreeStorage;
+
+    #[test]
+    fn test_transfer_limiter() {
+        let limiter = TransferLimiter::new(2, 1, Some(0));
+        let client: IpAddr = "192.0.2.1".parse().unwrap();
+        let other: IpAddr = "192.0.2.2".parse().unwrap();
+        let first = limiter.try_acquire(client).unwrap();
+        // over the per client limit
+        assert!(limiter.try_acquire(client).is_none());
+        let second = limiter.try_acquire(other).unwrap();
+        // over the global limit
+        assert!(limiter.try_acquire("192.0.2.3".parse().unwrap()).is_none());
+        drop(first);
+        let _third = limiter.try_acquire(client).unwrap();
+        drop(second);
+        assert!(limiter.clients.lock().unwrap().get(&other).is_none());
+        assert_eq!(limiter.rate_limit(), None);
+    }
+
+    #[test]
+    fn test_transfer_messages() {
+        let storage =
+            SafeRBTreeStorage::new_zone_from_file("../storage/test/example.zone", None).unwrap();
+        let apex = DNSName::new("example.com.", None).unwrap();
+        let records = storage.transfer_records(&apex).unwrap();
+        assert_eq!(records.first().unwrap().get_type(), DNSType::SOA);
+        assert_eq!(records.last().unwrap().get_type(), DNSType::SOA);
+        let soa_count = records
+            .iter()
+            .filter(|rr| rr.get_type() == DNSType::SOA)
+            .count();
+        assert_eq!(soa_count, 2);
+
+        let mut query = Message::new_with_header(Header::new());
+        query.set_question(Question::new("example.com.", DNSType::AXFR, DNSClass::IN).unwrap());
+        let total = records.len();
+        let messages = transfer_messages(&query, records, TransferFormat::default()).unwrap();
+        let mut answers = 0;
+        for message in messages.iter() {
+            let size = ((message[0] as usize) << 8) + message[1] as usize;
+            assert_eq!(size, message.len() - 2);
+            let response = Message::parse_dns_message(&message[2..]).unwrap();
+            assert!(response.header.aa());
+            answers += response.answers().len();
+        }
+        assert_eq!(answers, total);
+    }
+
+    #[test]
+    fn test_transfer_messages_repeatable() {
+        let apex = DNSName::new("example.com.", None).unwrap();
+        let mut query = Message::new_with_header(Header::new());
+        query.set_question(Question::new("example.com.", DNSType::AXFR, DNSClass::IN).unwrap());
+        let transfer = |storage: &SafeRBTreeStorage| {
+            let records = storage.transfer_records(&apex).unwrap();
+            transfer_messages(&query, records, TransferFormat::default()).unwrap()
+        };
+        let storage =
+            SafeRBTreeStorage::new_zone_from_file("../storage/test/example.zone", None).unwrap();
+        let first = transfer(&storage);
+        assert_eq!(transfer(&storage), first);
+        // a new storage has other hash maps and the same zone loaded in another order
+        let reordered = SafeRBTreeStorage::new_zone_from_str(
+            "$ORIGIN example.com.
+$TTL 3600
+@        IN  SOA   ns.example.com. username.example.com. ( 2020091025 7200 3600 1209600 3600 )
+mail3    IN  A     192.0.2.5
+mail2    IN  A     192.0.2.4
+mail     IN  A     192.0.2.3
+wwwtest  IN  CNAME www
+www      IN  CNAME example.com.
+ns       IN  AAAA  2001:db8:10::2
+ns       IN  A     192.0.2.2
+@        IN  AAAA  2001:db8:10::1
+@        IN  A     192.0.2.1
+@        IN  MX    50 mail3
+@        IN  MX    20 mail2
+@        IN  MX    10 mail
+@        IN  NS    ns.somewhere.example.
+@        IN  NS    ns
+",
+            None,
+        )
+        .unwrap();
+        assert_eq!(transfer(&reordered), first);
+    }
+
+    #[test]
+    fn test_transfer_messages_with_child_zone() {
+        let mut storage = SafeRBTreeStorage::new_zone_from_str(
+            "$ORIGIN sub.example.com.
+@        300 IN SOA ns admin 1 7200 3600 1209600 300
+@        300 IN NS  ns
+ns       300 IN A   192.0.2.53
+www      300 IN A   192.0.2.2
+",
+            None,
+        )
+        .unwrap();
+        storage
+            .update_zone_from_str(
+                "$ORIGIN example.com.
+@        300 IN SOA ns admin 1 7200 3600 1209600 300
+www      300 IN A   192.0.2.1
+sub      300 IN NS  ns.sub
+sub      300 IN DS  1657 8 2 9D6BAE62219231C99FAA479716B6E4619330CE8206670AEA6C1673A055DC3AF2
+ns.sub   300 IN A   192.0.2.53
+",
+                None,
+                &Default::default(),
+            )
+            .unwrap();
+        let apex = DNSName::new("example.com.", None).unwrap();
+        let mut query = Message::new_with_header(Header::new());
+        query.set_question(Question::new("example.com.", DNSType::AXFR, DNSClass::IN).unwrap());
+        let records = storage.transfer_records(&apex).unwrap();
+        let messages = transfer_messages(&query, records, TransferFormat::default()).unwrap();
+        let mut answers = vec![];
+        for message in messages.iter() {
+            let response = Message::parse_dns_message(&message[2..]).unwrap();
+            for rr in response.answers() {
+                answers.push(format!("{} {}", rr.get_dname().to_string(), rr.get_type()));
+            }
+        }
+        // the delegation and the glue stay in the parent, the child zone data does not
+        assert_eq!(
+            answers,
+            vec![
+                "example.com. SOA",
+                "sub.example.com. NS",
+                "sub.example.com. DS",
+                "ns.sub.example.com. A",
+                "www.example.com. A",
+                "example.com. SOA",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_transfer_format() {
+        let storage =
+            SafeRBTreeStorage::new_zone_from_file("../storage/test/example.zone", None).unwrap();
+        let apex = DNSName::new("example.com.", None).unwrap();
+        let records: Vec<ResourceRecord> = storage.transfer_records(&apex).unwrap();
+        let mut header = Header::new();
+        header.set_id(1);
+        let mut query = Message::new_with_header(header);
+        query.set_question(Question::new("example.com.", DNSType::AXFR, DNSClass::IN).unwrap());
+        // a message has pointers when it is shorter than its uncompressed encoding
+        let has_pointer = |messages: &[Vec<u8>]| {
+            messages.iter().any(|message| {
+                let mut parsed = Message::parse_dns_message(&message[2..]).unwrap();
+                parsed.encode_uncompressed(true).unwrap().len() > message.len() - 2
+            })
+        };
+
+        let packed = transfer_messages(&query, records.clone(), TransferFormat::default()).unwrap();
+        assert_eq!(packed.len(), 1);
+        assert!(has_pointer(&packed));
+
+        for compression in [true, false].iter() {
+            let format = TransferFormat {
+                style: TransferStyle::OneAnswer,
+                compression: *compression,
+            };
+            let messages = transfer_messages(&query, records.clone(), format).unwrap();
+            assert_eq!(messages.len(), records.len());
+            assert_eq!(has_pointer(&messages), *compression);
+            for (message, rr) in messages.iter().zip(records.iter()) {
+                let response = Message::parse_dns_message(&message[2..]).unwrap();
+                assert_eq!(response.answers().len(), 1);
+                assert_eq!(response.answers()[0].get_dname(), rr.get_dname());
+                assert_eq!(response.answers()[0].get_type(), rr.get_type());
+            }
+        }
+
+        let format = TransferFormat {
+            style: TransferStyle::ManyAnswers,
+            compression: false,
+        };
+        let uncompressed = transfer_messages(&query, records, format).unwrap();
+        assert_eq!(uncompressed.len(), 1);
+        assert!(!has_pointer(&uncompressed));
+        assert!(uncompressed[0].len() > packed[0].len());
+    }
+
+    #[tokio::test]
+    async fn test_write_paced() {
+        let messages = vec![vec![0u8; 1000]; 3];
+        let mut output: Vec<u8> = vec![];
+        let started = Instant::now();
+        write_paced(&mut output, &messages, Some(10_000))
+            .await
+            .unwrap();
+        // the third message waits until 2000 bytes have taken 200ms
+        assert!(started.elapsed() >= Duration::from_millis(200));
+        assert_eq!(output.len(), 3000);
+    }
+}
@@ -0,0 +1,259 @@
+use crate::transfer::Clock;
+use dnsproto::dnsname::DNSName;
+use dnsproto::edns::EDNS;
+use dnsproto::meta::DNSType;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex};
+
+/// CacheKey identify a cached response, the query name is lowercased so queries with
+/// different case share the same entry.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct CacheKey {
+    qname: String,
+    qtype: u16,
+    edns: bool,
+    do_bit: bool,
+    from_udp: bool,
+}
+
+impl CacheKey {
+    pub fn new(qname: &DNSName, qtype: &DNSType, edns: Option<&EDNS>, from_udp: bool) -> CacheKey {
+        CacheKey {
+            qname: qname.to_string().to_lowercase(),
+            qtype: u16::from(*qtype),
+            edns: edns.is_some(),
+            do_bit: edns.map(|edns| edns.do_bit()).unwrap_or(false),
+            from_udp,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct CacheEntry {
+    response: Vec<u8>,
+    zone: DNSName,
+    expire_at: u64,
+    tick: u64,
+}
+
+#[derive(Debug, Default)]
+struct CacheInner {
+    entries: HashMap<CacheKey, CacheEntry>,
+    // least recently used entry has the smallest tick
+    recency: BTreeMap<u64, CacheKey>,
+    tick: u64,
+}
+
+impl CacheInner {
+    fn remove(&mut self, key: &CacheKey) -> Option<CacheEntry> {
+        let entry = self.entries.remove(key)?;
+        self.recency.remove(&entry.tick);
+        Some(entry)
+    }
+
+    fn next_tick(&mut self) -> u64 {
+        self.tick += 1;
+        self.tick
+    }
+}
+
+/// ResponseCache holds the encoded responses of hot queries, entries expire with the
+/// smallest ttl of the answer and the least recently used entry is evicted when full.
+pub struct ResponseCache {
+    capacity: usize,
+    inner: Mutex<CacheInner>,
+    clock: Arc<dyn Clock>,
+}
+
+impl std::fmt::Debug for ResponseCache {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ResponseCache")
+            .field("capacity", &self.capacity)
+            .field("len", &self.len())
+            .finish()
+    }
+}
+
+impl ResponseCache {
+    pub fn new(capacity: usize, clock: Arc<dyn Clock>) -> ResponseCache {
+        ResponseCache {
+            capacity,
+            inner: Mutex::new(CacheInner::default()),
+            clock,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.inner.lock().unwrap().entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// get return the cached response and its zone apex, expired entry will be removed.
+    pub fn get(&self, key: &CacheKey) -> Option<(Vec<u8>, DNSName)> {
+        let now = self.clock.now();
+        let mut inner = self.inner.lock().unwrap();
+        let expired = inner.entries.get(key)?.expire_at <= now;
+        if expired {
+            inner.remove(key);
+            return None;
+        }
+        let tick = inner.next_tick();
+        let mut entry = inner.remove(key)?;
+        entry.tick = tick;
+        let result = (entry.response.clone(), entry.zone.clone());
+        inner.recency.insert(tick, key.clone());
+        inner.entries.insert(key.clone(), entry);
+        Some(result)
+    }
+
+    /// insert the encoded response of the zone, response with zero ttl is not cached.
+    pub fn insert(&self, key: CacheKey, zone: DNSName, response: Vec<u8>, ttl: u32) {
+        if self.capacity == 0 || ttl == 0 {
+            return;
+        }
+        let expire_at = self.clock.now() + ttl as u64;
+        let mut inner = self.inner.lock().unwrap();
+        inner.remove(&key);
+        while inner.entries.len() >= self.capacity {
+            let oldest = match inner.recency.keys().next() {
+                Some(tick) => *tick,
+                None => break,
+            };
+            if let Some(oldest) = inner.recency.remove(&oldest) {
+                inner.entries.remove(&oldest);
+            }
+        }
+        let tick = inner.next_tick();
+        inner.recency.insert(tick, key.clone());
+        inner.entries.insert(
+            key,
+            CacheEntry {
+                response,
+                zone,
+                expire_at,
+                tick,
+            },
+        );
+    }
+
+    /// invalidate_zone remove all responses of the zone and the zones below it.
+    pub fn invalidate_zone(&self, apex: &DNSName) {
+        let mut inner = self.inner.lock().unwrap();
+        let keys: Vec<CacheKey> = inner
+            .entries
+            .iter()
+            .filter(|(_, entry)| entry.zone.is_part_of(apex))
+            .map(|(key, _)| key.clone())
+            .collect();
+        for key in keys.iter() {
+            inner.remove(key);
+        }
+    }
+
+    pub fn clear(&self) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.entries.clear();
+        inner.recency.clear();
+    }
+}
+
+/// patch_response rewrite the cached response for the query: copy the query id, rd bit and
+/// question name(keep the query case), and clamp the OPT payload size. return None when
+/// the udp response is great than max_size and need to be truncated.
+pub fn patch_response(
+    mut response: Vec<u8>,
+    query: &[u8],
+    from_udp: bool,
+    max_size: u16,
+) -> Option<Vec<u8>> {
+    let offset = if from_udp { 0 } else { 2 };
+    if from_udp && response.len() > max_size as usize {
+        return None;
+    }
+    if query.len() < 12 || response.len() < offset + 12 {
+        return None;
+    }
+    response[offset] = query[0];
+    response[offset + 1] = query[1];
+    response[offset + 2] = (response[offset + 2] & 0xfe) | (query[2] & 0x01);
+    // question name is never compressed, so both have the same length
+    let mut end = 12;
+    while end < query.len() && query[end] != 0 {
+        end += query[end] as usize + 1;
+    }
+    if end >= query.len() || offset + end >= response.len() {
+        return None;
+    }
+    let name = &mut response[offset + 12..offset + end + 1];
+    if !name.eq_ignore_ascii_case(&query[12..=end]) {
+        return None;
+    }
+    name.copy_from_slice(&query[12..=end]);
+    // OPT record without options is always the last 11 bytes of the response
+    let additional_count = ((response[offset + 10] as u16) << 8) + response[offset + 11] as u16;
+    let len = response.len();
+    if additional_count > 0 && len >= offset + 12 + 11 && response[len - 11] == 0 {
+        let rtype = ((response[len - 10] as u16) << 8) + response[len - 9] as u16;
+        if rtype == u16::from(DNSType::OPT) {
+            let payload = ((response[len - 8] as u16) << 8) + response[len - 7] as u16;
+            let payload = payload.min(max_size);
+            response[len - 8] = (payload >> 8) as u8;
+            response[len - 7] = (payload & 0xff) as u8;
+        }
+    }
+    Some(response)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::transfer::test::FakeClock;
+
+    fn key(name: &str) -> CacheKey {
+        let name = DNSName::new(name, None).unwrap();
+        CacheKey::new(&name, &DNSType::A, None, true)
+    }
+
+    #[test]
+    fn test_cache_expire_and_lru() {
+        let clock = Arc::new(FakeClock::default());
+        let cache = ResponseCache::new(2, clock.clone());
+        let zone = DNSName::new("example.com.", None).unwrap();
+        cache.insert(key("a.example.com."), zone.clone(), vec![1], 10);
+        cache.insert(key("b.example.com."), zone.clone(), vec![2], 100);
+        cache.insert(key("c.example.com."), zone.clone(), vec![3], 0);
+        assert_eq!(cache.len(), 2);
+        assert_eq!(
+            key("A.Example.com."),
+            key("a.example.com."),
+            "key should be case insensitive"
+        );
+        // a is used recently, so b will be evicted
+        assert!(cache.get(&key("a.example.com.")).is_some());
+        cache.insert(key("d.example.com."), zone.clone(), vec![4], 100);
+        assert!(cache.get(&key("b.example.com.")).is_none());
+        clock.advance(10);
+        assert!(cache.get(&key("a.example.com.")).is_none());
+        assert_eq!(cache.get(&key("d.example.com.")), Some((vec![4], zone)));
+        assert_eq!(cache.len(), 1);
+    }
+
+    #[test]
+    fn test_cache_invalidate_zone() {
+        let cache = ResponseCache::new(10, Arc::new(FakeClock::default()));
+        let zone = DNSName::new("example.com.", None).unwrap();
+        let subzone = DNSName::new("sub.example.com.", None).unwrap();
+        let other = DNSName::new("example.org.", None).unwrap();
+        cache.insert(key("www.example.com."), zone.clone(), vec![1], 10);
+        cache.insert(key("www.sub.example.com."), subzone, vec![2], 10);
+        cache.insert(key("www.example.org."), other, vec![3], 10);
+        cache.invalidate_zone(&zone);
+        assert_eq!(cache.len(), 1);
+        assert!(cache.get(&key("www.example.org.")).is_some());
+        cache.clear();
+        assert!(cache.is_empty());
+    }
+}
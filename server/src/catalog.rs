@@ -0,0 +1,275 @@
+use dnsproto::dnsname::DNSName;
+use dnsproto::meta::DNSType;
+use dnsproto::qtype::{DnsTypePTR, DnsTypeTXT};
+use otterlib::errors::StorageError;
+use otterlib::setting::Masters;
+use std::collections::HashMap;
+use std::sync::RwLock;
+use storage::storage::SafeRBTreeStorage;
+
+// https://www.rfc-editor.org/rfc/rfc9432.html
+const CATALOG_VERSION: &[u8] = b"2";
+
+/// CatalogMember is a member zone listed as <unique-id>.zones.<catalog> PTR <member>.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CatalogMember {
+    pub id: String,
+    pub zone: DNSName,
+}
+
+/// catalog_members read the member zones from the loaded catalog zone, return error
+/// when the catalog version is not supported.
+pub fn catalog_members(
+    storage: &SafeRBTreeStorage,
+    catalog: &DNSName,
+) -> Result<Vec<CatalogMember>, StorageError> {
+    let mut version_name = catalog.clone();
+    version_name
+        .push_front("version")
+        .map_err(|err| StorageError::CatalogZoneError(catalog.to_string(), err.to_string()))?;
+    if !is_supported_version(storage, &version_name) {
+        return Err(StorageError::CatalogZoneError(
+            catalog.to_string(),
+            "catalog version is not 2".to_owned(),
+        ));
+    }
+    let mut zones_name = catalog.clone();
+    zones_name
+        .push_front("zones")
+        .map_err(|err| StorageError::CatalogZoneError(catalog.to_string(), err.to_string()))?;
+    let mut members = vec![];
+    for node in storage.find_children(&zones_name) {
+        let node = node.read().unwrap();
+        let rrset = match node.find_rrset(DNSType::PTR) {
+            Ok(rrset) => rrset,
+            Err(_) => continue,
+        };
+        let rrset = rrset.read().unwrap();
+        let name = node.get_name();
+        // member with multiple ptr records is broken and must be ignored
+        if rrset.size() != 1 {
+            warn!(
+                "catalog member {} has {} ptr records, ignored",
+                name.to_string(),
+                rrset.size()
+            );
+            continue;
+        }
+        let ptr = rrset.content()[0]
+            .get_data()
+            .as_ref()
+            .and_then(|data| data.as_any().downcast_ref::<DnsTypePTR>());
+        if let (Some(ptr), Some(id)) = (ptr, name.labels.first()) {
+            members.push(CatalogMember {
+                id: id.to_string(),
+                zone: ptr.name().clone(),
+            });
+        }
+    }
+    Ok(members)
+}
+
+fn is_supported_version(storage: &SafeRBTreeStorage, version_name: &DNSName) -> bool {
+    let node = match storage.find(version_name) {
+        Ok(node) => node,
+        Err(_) => return false,
+    };
+    let rrset = match node.read().unwrap().find_rrset(DNSType::TXT) {
+        Ok(rrset) => rrset,
+        Err(_) => return false,
+    };
+    let rrset = rrset.read().unwrap();
+    rrset.content().iter().any(|rr| {
+        rr.get_data()
+            .as_ref()
+            .and_then(|data| data.as_any().downcast_ref::<DnsTypeTXT>())
+            .map(|txt| txt.strings() == [CATALOG_VERSION.to_vec()])
+            .unwrap_or(false)
+    })
+}
+
+/// MemberZone is a secondary zone provisioned by a catalog zone.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MemberZone {
+    pub catalog: DNSName,
+    pub id: String,
+    pub master: Option<Masters>,
+}
+
+/// CatalogChange is the member zones added and removed by a catalog update.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct CatalogChange {
+    pub added: Vec<DNSName>,
+    pub removed: Vec<DNSName>,
+}
+
+/// ZoneCatalog tracks the member zones of all catalog zones.
+#[derive(Debug, Default)]
+pub struct ZoneCatalog {
+    members: RwLock<HashMap<DNSName, MemberZone>>,
+}
+
+impl ZoneCatalog {
+    /// update replace the member zones of the catalog, unchanged members keep their
+    /// state and a member with a new unique id is treated as removed and added again.
+    /// zone already provisioned by another catalog is ignored.
+    pub fn update(
+        &self,
+        catalog: &DNSName,
+        master: Option<Masters>,
+        members: Vec<CatalogMember>,
+    ) -> CatalogChange {
+        let mut current = self.members.write().unwrap();
+        let mut change = CatalogChange::default();
+        let removed: Vec<DNSName> = current
+            .iter()
+            .filter(|(_, member)| &member.catalog == catalog)
+            .filter(|(zone, member)| {
+                !members
+                    .iter()
+                    .any(|item| &item.zone == *zone && item.id == member.id)
+            })
+            .map(|(zone, _)| zone.clone())
+            .collect();
+        for zone in removed {
+            current.remove(&zone);
+            change.removed.push(zone);
+        }
+        for member in members {
+            match current.get(&member.zone) {
+                Some(exist) if &exist.catalog == catalog => continue,
+                Some(exist) => {
+                    warn!(
+                        "zone {} of catalog {} is already provisioned by catalog {}",
+                        member.zone.to_string(),
+                        catalog.to_string(),
+                        exist.catalog.to_string()
+                    );
+                    continue;
+                }
+                None => {}
+            }
+            change.added.push(member.zone.clone());
+            current.insert(
+                member.zone,
+                MemberZone {
+                    catalog: catalog.clone(),
+                    id: member.id,
+                    master: master.clone(),
+                },
+            );
+        }
+        change
+    }
+
+    /// members return the sorted member zones of the catalog.
+    pub fn members(&self, catalog: &DNSName) -> Vec<DNSName> {
+        let mut zones: Vec<DNSName> = self
+            .members
+            .read()
+            .unwrap()
+            .iter()
+            .filter(|(_, member)| &member.catalog == catalog)
+            .map(|(zone, _)| zone.clone())
+            .collect();
+        zones.sort_by_key(|zone| zone.to_string());
+        zones
+    }
+
+    pub fn get(&self, zone: &DNSName) -> Option<MemberZone> {
+        self.members.read().unwrap().get(zone).cloned()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn names(zones: &[&str]) -> Vec<DNSName> {
+        zones
+            .iter()
+            .map(|zone| DNSName::new(zone, None).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_catalog_members() {
+        let storage =
+            SafeRBTreeStorage::new_zone_from_file("../storage/test/catalog.zone", None).unwrap();
+        let catalog = DNSName::new("catalog.example.", None).unwrap();
+        let mut members = catalog_members(&storage, &catalog).unwrap();
+        members.sort_by_key(|member| member.zone.to_string());
+        assert_eq!(
+            members,
+            vec![
+                CatalogMember {
+                    id: "5960775ba382e7a4e09263fc06e7c00569b6a05c".to_owned(),
+                    zone: DNSName::new("example.com.", None).unwrap(),
+                },
+                CatalogMember {
+                    id: "c7e1a2b3".to_owned(),
+                    zone: DNSName::new("example.net.", None).unwrap(),
+                },
+                CatalogMember {
+                    id: "b2f1d4a8".to_owned(),
+                    zone: DNSName::new("example.org.", None).unwrap(),
+                },
+            ]
+        );
+        let storage =
+            SafeRBTreeStorage::new_zone_from_file("../storage/test/example.zone", None).unwrap();
+        let zone = DNSName::new("example.com.", None).unwrap();
+        assert!(catalog_members(&storage, &zone).is_err());
+    }
+
+    #[test]
+    fn test_zone_catalog_update() {
+        let catalog = DNSName::new("catalog.example.", None).unwrap();
+        let zone_catalog = ZoneCatalog::default();
+        let master = Some(Masters::from("192.0.2.1:53"));
+        let storage =
+            SafeRBTreeStorage::new_zone_from_file("../storage/test/catalog.zone", None).unwrap();
+        let members = catalog_members(&storage, &catalog).unwrap();
+        let mut change = zone_catalog.update(&catalog, master.clone(), members);
+        change.added.sort_by_key(|zone| zone.to_string());
+        assert_eq!(
+            change.added,
+            names(&["example.com.", "example.net.", "example.org."])
+        );
+        assert!(change.removed.is_empty());
+
+        let storage =
+            SafeRBTreeStorage::new_zone_from_file("../storage/test/catalog2.zone", None).unwrap();
+        let members = catalog_members(&storage, &catalog).unwrap();
+        let change = zone_catalog.update(&catalog, master.clone(), members);
+        assert_eq!(
+            change,
+            CatalogChange {
+                added: names(&["example.edu."]),
+                removed: names(&["example.org."]),
+            }
+        );
+        assert_eq!(
+            zone_catalog.members(&catalog),
+            names(&["example.com.", "example.edu.", "example.net."])
+        );
+        let member = zone_catalog
+            .get(&DNSName::new("example.net.", None).unwrap())
+            .unwrap();
+        assert_eq!(member.id, "c7e1a2b3");
+        assert_eq!(member.master, master);
+
+        // member zone provisioned by other catalog is ignored
+        let other = DNSName::new("other.example.", None).unwrap();
+        let change = zone_catalog.update(
+            &other,
+            None,
+            vec![CatalogMember {
+                id: "1".to_owned(),
+                zone: DNSName::new("example.com.", None).unwrap(),
+            }],
+        );
+        assert_eq!(change, CatalogChange::default());
+        assert!(zone_catalog.members(&other).is_empty());
+    }
+}
@@ -0,0 +1,462 @@
+use dnsproto::message::Message;
+use otterlib::errors::{DNSProtoErr, NetworkError, OtterError};
+#[cfg(feature = "testing")]
+use otterlib::fault::{Fault, FaultInjector};
+use rand::Rng;
+use std::net::SocketAddr;
+#[cfg(feature = "testing")]
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpSocket, TcpStream, UdpSocket};
+use tokio::time::timeout;
+
+const MAX_UDP_RESPONSE_SIZE: usize = 65535;
+
+// the fault injectors of the remote addresses, installed by tests
+#[cfg(feature = "testing")]
+static FAULT_INJECTORS: RwLock<Vec<(SocketAddr, Arc<FaultInjector>)>> = RwLock::new(Vec::new());
+
+/// set_fault_injector install the injector on the responses from the remote address, None
+/// remove it. the injectors are kept per remote so tests running at the same time don't
+/// affect each other.
+#[cfg(feature = "testing")]
+pub fn set_fault_injector(remote: SocketAddr, injector: Option<Arc<FaultInjector>>) {
+    let mut injectors = FAULT_INJECTORS.write().unwrap();
+    injectors.retain(|(addr, _)| *addr != remote);
+    if let Some(injector) = injector {
+        injectors.push((remote, injector));
+    }
+}
+
+/// inject_fault apply the next fault of the remote injector to the response. a dropped
+/// response never arrives so the timeout of the caller fires, a corrupted response claims
+/// more answers than it has, and a duplicated response is returned to be handled again.
+#[cfg(feature = "testing")]
+pub(crate) async fn inject_fault(remote: SocketAddr, response: &mut Vec<u8>) -> Option<Vec<u8>> {
+    let injector = FAULT_INJECTORS
+        .read()
+        .unwrap()
+        .iter()
+        .find(|(addr, _)| *addr == remote)
+        .map(|(_, injector)| injector.clone())?;
+    match injector.next_fault()? {
+        Fault::Drop => std::future::pending::<()>().await,
+        Fault::Delay(delay) => tokio::time::sleep(delay).await,
+        Fault::Corrupt if response.len() >= 12 => response[6..8].copy_from_slice(&[0xff, 0xff]),
+        Fault::Corrupt => response.clear(),
+        Fault::Duplicate => return Some(response.clone()),
+        Fault::Error => {}
+    }
+    None
+}
+
+/// QueryOption controls how the client sends a query and accepts the response.
+#[derive(Debug, Clone)]
+pub struct QueryOption {
+    /// timeout of each udp attempt or tcp exchange
+    pub timeout: Duration,
+    /// udp attempts after the first one timeout
+    pub retries: usize,
+    /// randomize the qname case(0x20) and require the response to echo it exactly
+    pub randomize_case: bool,
+    /// skip udp and send the query over tcp
+    pub tcp_only: bool,
+    /// local address the sockets are bound to, chosen by the system if not set
+    pub source: Option<SocketAddr>,
+}
+
+impl Default for QueryOption {
+    fn default() -> Self {
+        QueryOption {
+            timeout: Duration::from_secs(2),
+            retries: 2,
+            randomize_case: false,
+            tcp_only: false,
+            source: None,
+        }
+    }
+}
+
+/// query send the message to remote with a random id and return the matched response.
+/// udp responses from other source address or with mismatched id or question are
+/// dropped, and the query is retried over tcp when the udp response is truncated.
+pub async fn query(
+    remote: SocketAddr,
+    message: &mut Message,
+    option: &QueryOption,
+) -> Result<Message, OtterError> {
+    message.header.set_random_id();
+    let mut request = message.encode(true)?;
+    let name_end = question_name_end(&request).ok_or(DNSProtoErr::PacketSerializeError)?;
+    if option.randomize_case {
+        randomize_case(&mut request[12..name_end]);
+    }
+    if !option.tcp_only {
+        let mut attempts = 0;
+        let response = loop {
+            match timeout(option.timeout, udp_exchange(remote, &request, option)).await {
+                Ok(result) => break result?,
+                Err(_) if attempts < option.retries => {
+                    attempts += 1;
+                    debug!("query {} timeout, retry {}", remote, attempts);
+                }
+                Err(_) => return Err(NetworkError::QueryTimeout(remote.to_string()).into()),
+            }
+        };
+        if response[2] & 0x02 == 0 {
+            return Ok(Message::parse_dns_message(&response)?);
+        }
+        debug!("response from {} is truncated, retry with tcp", remote);
+    }
+    let response = match timeout(option.timeout, tcp_exchange(remote, &request, option)).await {
+        Ok(result) => result?,
+        Err(_) => return Err(NetworkError::QueryTimeout(remote.to_string()).into()),
+    };
+    Ok(Message::parse_dns_message(&response)?)
+}
+
+/// forward send the raw message to remote over tcp and return the raw response, the
+/// message id and any signature in the message are kept as is.
+pub async fn forward(
+    remote: SocketAddr,
+    request: &[u8],
+    option: &QueryOption,
+) -> Result<Vec<u8>, OtterError> {
+    match timeout(option.timeout, tcp_exchange(remote, request, option)).await {
+        Ok(result) => result,
+        Err(_) => Err(NetworkError::QueryTimeout(remote.to_string()).into()),
+    }
+}
+
+/// udp_exchange send the request from the source address(a new random port if not set) and
+/// wait for the matched response.
+async fn udp_exchange(
+    remote: SocketAddr,
+    request: &[u8],
+    option: &QueryOption,
+) -> Result<Vec<u8>, OtterError> {
+    let socket = match option.source {
+        Some(source) => UdpSocket::bind(source)
+            .await
+            .map_err(|err| source_bind_error(source, remote, err))?,
+        None => {
+            let local = match remote {
+                SocketAddr::V4(_) => "0.0.0.0:0",
+                SocketAddr::V6(_) => "[::]:0",
+            };
+            UdpSocket::bind(local).await.map_err(NetworkError::from)?
+        }
+    };
+    socket
+        .send_to(request, remote)
+        .await
+        .map_err(NetworkError::from)?;
+    let mut buffer = vec![0; MAX_UDP_RESPONSE_SIZE];
+    loop {
+        let (size, source) = socket
+            .recv_from(&mut buffer)
+            .await
+            .map_err(NetworkError::from)?;
+        if source != remote {
+            debug!("drop response from {}, query sent to {}", source, remote);
+            continue;
+        }
+        if !response_matches(&buffer[..size], request, option.randomize_case) {
+            debug!("drop mismatched response from {}", source);
+            continue;
+        }
+        #[allow(unused_mut)]
+        let mut response = buffer[..size].to_vec();
+        // only one response is read from the socket, a duplicate is never seen
+        #[cfg(feature = "testing")]
+        inject_fault(remote, &mut response).await;
+        return Ok(response);
+    }
+}
+
+/// tcp_exchange send the request over a new tcp connection and read one response.
+async fn tcp_exchange(
+    remote: SocketAddr,
+    request: &[u8],
+    option: &QueryOption,
+) -> Result<Vec<u8>, OtterError> {
+    let mut stream = connect(remote, option.source).await?;
+    let mut frame = (request.len() as u16).to_be_bytes().to_vec();
+    frame.extend_from_slice(request);
+    stream.write_all(&frame).await.map_err(NetworkError::from)?;
+    let size = stream.read_u16().await.map_err(NetworkError::from)?;
+    let mut response = vec![0; size as usize];
+    stream
+        .read_exact(&mut response)
+        .await
+        .map_err(NetworkError::from)?;
+    #[cfg(feature = "testing")]
+    inject_fault(remote, &mut response).await;
+    if !response_matches(&response, request, option.randomize_case) {
+        return Err(NetworkError::UnexpectedResponse(
+            remote.to_string(),
+            "id or question mismatch".to_string(),
+        )
+        .into());
+    }
+    Ok(response)
+}
+
+/// connect open a tcp connection to remote from the source address, the system choose the
+/// local address if not set.
+pub async fn connect(
+    remote: SocketAddr,
+    source: Option<SocketAddr>,
+) -> Result<TcpStream, OtterError> {
+    let source = match source {
+        Some(source) => source,
+        None => {
+            return Ok(TcpStream::connect(remote)
+                .await
+                .map_err(NetworkError::from)?)
+        }
+    };
+    let socket = match source {
+        SocketAddr::V4(_) => TcpSocket::new_v4(),
+        SocketAddr::V6(_) => TcpSocket::new_v6(),
+    }
+    .map_err(NetworkError::from)?;
+    // a fixed source port is reused by the following connections
+    socket
+        .set_reuseaddr(true)
+        .and_then(|_| socket.bind(source))
+        .map_err(|err| source_bind_error(source, remote, err))?;
+    Ok(socket.connect(remote).await.map_err(NetworkError::from)?)
+}
+
+fn source_bind_error(source: SocketAddr, remote: SocketAddr, err: std::io::Error) -> OtterError {
+    NetworkError::SourceBindError(source.to_string(), remote.to_string(), err.to_string()).into()
+}
+
+/// question_name_end return the end offset of the uncompressed question name.
+fn question_name_end(message: &[u8]) -> Option<usize> {
+    let mut offset = 12;
+    loop {
+        let length = *message.get(offset)? as usize;
+        if length == 0 {
+            return Some(offset + 1);
+        }
+        if length & 0xc0 != 0 {
+            return None;
+        }
+        offset += length + 1;
+    }
+}
+
+/// randomize_case flip the case of letters in the wire format name randomly.
+fn randomize_case(name: &mut [u8]) {
+    let mut rng = rand::thread_rng();
+    let mut offset = 0;
+    while offset < name.len() && name[offset] != 0 {
+        let end = (offset + 1 + name[offset] as usize).min(name.len());
+        for byte in name[offset + 1..end].iter_mut() {
+            if byte.is_ascii_alphabetic() && rng.gen::<bool>() {
+                *byte ^= 0x20;
+            }
+        }
+        offset = end;
+    }
+}
+
+/// response_matches check the response has the qr bit, and the same id and question with
+/// the request, the question name must keep the request case when exact_case is true.
+fn response_matches(response: &[u8], request: &[u8], exact_case: bool) -> bool {
+    let name_end = match question_name_end(request) {
+        Some(name_end) => name_end,
+        None => return false,
+    };
+    let question_end = name_end + 4;
+    if request.len() < question_end || response.len() < question_end {
+        return false;
+    }
+    if response[..2] != request[..2] || response[2] & 0x80 == 0 || response[4..6] == [0, 0] {
+        return false;
+    }
+    let (name, expected) = (&response[12..name_end], &request[12..name_end]);
+    let name_matches = if exact_case {
+        name == expected
+    } else {
+        name.eq_ignore_ascii_case(expected)
+    };
+    name_matches && response[name_end..question_end] == request[name_end..question_end]
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use dnsproto::meta::{DNSClass, DNSType, Header, Question};
+    use tokio::net::TcpListener;
+
+    fn build_message() -> Message {
+        let mut message = Message::new_with_header(Header::new());
+        message.set_question(Question::new("example.com.", DNSType::SOA, DNSClass::IN).unwrap());
+        message
+    }
+
+    fn option() -> QueryOption {
+        QueryOption {
+            timeout: Duration::from_millis(200),
+            retries: 0,
+            ..Default::default()
+        }
+    }
+
+    fn echo_response(request: &[u8], flags: u8) -> Vec<u8> {
+        let mut response = request.to_vec();
+        response[2] |= 0x80 | flags;
+        response
+    }
+
+    #[test]
+    fn test_response_matches() {
+        let request = build_message().encode(true).unwrap();
+        let name_end = question_name_end(&request).unwrap();
+        assert_eq!(name_end, 12 + 13);
+        let mut randomized = request.clone();
+        randomized[13] = b'E';
+        assert!(!response_matches(&request, &request, false), "qr not set");
+        assert!(response_matches(
+            &echo_response(&request, 0),
+            &request,
+            false
+        ));
+        let mut response = echo_response(&randomized, 0);
+        assert!(response_matches(&response, &request, false));
+        assert!(!response_matches(&response, &request, true));
+        response[0] ^= 0xff;
+        assert!(!response_matches(&response, &request, false));
+        assert!(!response_matches(&response[..20], &request, false));
+    }
+
+    #[test]
+    fn test_randomize_case() {
+        let mut name = b"\x07example\x03com\x00".to_vec();
+        randomize_case(&mut name);
+        assert!(name.eq_ignore_ascii_case(b"\x07example\x03com\x00"));
+        assert_eq!(name[0], 7);
+        assert_eq!(name[8], 3);
+    }
+
+    #[tokio::test]
+    async fn test_query_drop_spoofed_response() {
+        let server = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let spoofer = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let remote = server.local_addr().unwrap();
+        tokio::spawn(async move {
+            let mut buffer = vec![0; 512];
+            let (size, client) = server.recv_from(&mut buffer).await.unwrap();
+            let request = buffer[..size].to_vec();
+            // the same id from other source address
+            let spoofed = echo_response(&request, 0x04);
+            spoofer.send_to(&spoofed, client).await.unwrap();
+            // mismatched id from the server address
+            let mut mismatched = echo_response(&request, 0x04);
+            mismatched[1] = mismatched[1].wrapping_add(1);
+            server.send_to(&mismatched, client).await.unwrap();
+            server
+                .send_to(&echo_response(&request, 0), client)
+                .await
+                .unwrap();
+        });
+        let mut message = build_message();
+        let option = QueryOption {
+            randomize_case: true,
+            ..option()
+        };
+        let response = query(remote, &mut message, &option).await.unwrap();
+        assert!(!response.is_query());
+        assert!(!response.header.aa(), "spoofed response should be dropped");
+        assert_eq!(response.header.id(), message.header.id());
+    }
+
+    #[tokio::test]
+    async fn test_query_truncated_fallback_tcp() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let remote = listener.local_addr().unwrap();
+        let server = UdpSocket::bind(remote).await.unwrap();
+        tokio::spawn(async move {
+            let mut buffer = vec![0; 512];
+            let (size, client) = server.recv_from(&mut buffer).await.unwrap();
+            let truncated = echo_response(&buffer[..size], 0x02);
+            server.send_to(&truncated, client).await.unwrap();
+        });
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let size = stream.read_u16().await.unwrap();
+            let mut request = vec![0; size as usize];
+            stream.read_exact(&mut request).await.unwrap();
+            let response = echo_response(&request, 0x04);
+            stream.write_u16(response.len() as u16).await.unwrap();
+            stream.write_all(&response).await.unwrap();
+        });
+        let mut message = build_message();
+        let response = query(remote, &mut message, &option()).await.unwrap();
+        assert!(response.header.aa(), "response should come from tcp");
+        assert!(!response.header.tc());
+    }
+
+    #[tokio::test]
+    async fn test_query_timeout() {
+        let server = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let remote = server.local_addr().unwrap();
+        let mut message = build_message();
+        let option = QueryOption {
+            retries: 1,
+            ..option()
+        };
+        match query(remote, &mut message, &option).await {
+            Err(OtterError::NetworkError(NetworkError::QueryTimeout(addr))) => {
+                assert_eq!(addr, remote.to_string())
+            }
+            _ => assert!(false, "query should timeout"),
+        }
+        drop(server);
+    }
+
+    #[tokio::test]
+    async fn test_query_source_address() {
+        let server = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let remote = server.local_addr().unwrap();
+        let source = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let source_addr = source.local_addr().unwrap();
+        // free the port for the client
+        drop(source);
+        tokio::spawn(async move {
+            let mut buffer = vec![0; 512];
+            let (size, client) = server.recv_from(&mut buffer).await.unwrap();
+            let mut response = echo_response(&buffer[..size], 0);
+            // answer with aa only when the query come from the source address
+            if client == source_addr {
+                response[2] |= 0x04;
+            }
+            server.send_to(&response, client).await.unwrap();
+        });
+        let mut message = build_message();
+        let src_option = QueryOption {
+            source: Some(source_addr),
+            ..option()
+        };
+        let response = query(remote, &mut message, &src_option).await.unwrap();
+        assert!(response.header.aa(), "query should be sent from the source");
+
+        // the documentation address is not local
+        let bad_option = QueryOption {
+            source: Some("192.0.2.1:0".parse().unwrap()),
+            tcp_only: true,
+            ..option()
+        };
+        match query(remote, &mut message, &bad_option).await {
+            Err(OtterError::NetworkError(NetworkError::SourceBindError(source, addr, _))) => {
+                assert_eq!(source, "192.0.2.1:0");
+                assert_eq!(addr, remote.to_string());
+            }
+            _ => assert!(false, "query should fail to bind the source"),
+        }
+    }
+}
@@ -0,0 +1,426 @@
+use crate::inspect::record_response;
+use crate::limits::ConnectionLimiter;
+use crate::logging::{
+    parse_log_level, parse_query_log_sample, set_log_level, set_query_log_sample,
+};
+use crate::metrics::Metrics;
+use crate::stats::QueryStats;
+use crate::transfer::{Clock, TransferScheduler, ZoneFreshness};
+use dnsproto::dnsname::DNSName;
+use dnsproto::utils::fqdn;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use storage::storage::SafeRBTreeStorage;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+/// the watchdog self query runs every WATCHDOG_INTERVAL seconds
+pub const WATCHDOG_INTERVAL: u64 = 5;
+// the server is not alive when the watchdog missed three self queries
+const LIVENESS_TIMEOUT: u64 = WATCHDOG_INTERVAL * 3;
+const MAX_REQUEST_SIZE: usize = 1024;
+
+/// ServerState holds the liveness and readiness of the server.
+pub struct ServerState {
+    zones_loaded: AtomicBool,
+    reload_failed: AtomicBool,
+    // unix timestamp of the last successful watchdog self query
+    last_heartbeat: AtomicU64,
+    scheduler: Arc<TransferScheduler>,
+    stats: Arc<QueryStats>,
+    metrics: Arc<Metrics>,
+    connections: Arc<ConnectionLimiter>,
+    clock: Arc<dyn Clock>,
+}
+
+impl std::fmt::Debug for ServerState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ServerState")
+            .field("zones_loaded", &self.zones_loaded)
+            .field("reload_failed", &self.reload_failed)
+            .field("last_heartbeat", &self.last_heartbeat)
+            .finish()
+    }
+}
+
+impl ServerState {
+    pub fn new(scheduler: Arc<TransferScheduler>, clock: Arc<dyn Clock>) -> ServerState {
+        let now = clock.now();
+        ServerState {
+            zones_loaded: AtomicBool::new(false),
+            reload_failed: AtomicBool::new(false),
+            last_heartbeat: AtomicU64::new(now),
+            scheduler,
+            stats: Arc::new(QueryStats::default()),
+            metrics: Arc::new(Metrics::default()),
+            connections: Arc::new(ConnectionLimiter::default()),
+            clock,
+        }
+    }
+
+    /// with_connections replace the default connection limits.
+    pub fn with_connections(mut self, connections: ConnectionLimiter) -> ServerState {
+        self.connections = Arc::new(connections);
+        self
+    }
+
+    /// stats return the query statistics dumped by the /stats endpoints.
+    pub fn stats(&self) -> Arc<QueryStats> {
+        self.stats.clone()
+    }
+
+    /// metrics return the query metrics dumped by the /metrics endpoint.
+    pub fn metrics(&self) -> Arc<Metrics> {
+        self.metrics.clone()
+    }
+
+    /// connections return the connection limiter dumped by the /connections endpoint.
+    pub fn connections(&self) -> Arc<ConnectionLimiter> {
+        self.connections.clone()
+    }
+
+    pub fn set_zones_loaded(&self, loaded: bool) {
+        self.zones_loaded.store(loaded, Ordering::Relaxed);
+    }
+
+    pub fn set_reload_failed(&self, failed: bool) {
+        self.reload_failed.store(failed, Ordering::Relaxed);
+    }
+
+    /// heartbeat is called by the watchdog after a successful self query.
+    pub fn heartbeat(&self) {
+        self.last_heartbeat
+            .store(self.clock.now(), Ordering::Relaxed);
+    }
+
+    /// is_alive return false when the watchdog self query is not answered in time.
+    pub fn is_alive(&self) -> bool {
+        let last_heartbeat = self.last_heartbeat.load(Ordering::Relaxed);
+        self.clock.now().saturating_sub(last_heartbeat) <= LIVENESS_TIMEOUT
+    }
+
+    /// is_ready return true when all zones are loaded, the last reload not failed in
+    /// strict mode and no secondary zone is expired.
+    pub fn is_ready(&self) -> bool {
+        self.zones_loaded.load(Ordering::Relaxed)
+            && !self.reload_failed.load(Ordering::Relaxed)
+            && self
+                .scheduler
+                .report()
+                .iter()
+                .all(|(_, _, freshness)| *freshness != ZoneFreshness::Expired)
+    }
+
+    /// http_response return the status code and body for the request path.
+    fn http_response(&self, path: &str) -> (u16, String) {
+        if path == "/stats" || path.starts_with("/stats?") || path.starts_with("/stats/") {
+            return self.stats_response(&path["/stats".len()..]);
+        }
+        if let Some(level) = path.strip_prefix("/set-log-level/") {
+            return match parse_log_level(level) {
+                Some(level) => {
+                    set_log_level(level);
+                    let body = format!("log level set to {}", level.to_string().to_lowercase());
+                    info!("{}", body);
+                    (200, body)
+                }
+                None => (400, "unknown log level".to_owned()),
+            };
+        }
+        if let Some(sample) = path.strip_prefix("/set-query-log-sample/") {
+            return match parse_query_log_sample(sample) {
+                Some(sample) => {
+                    set_query_log_sample(sample);
+                    let body = format!("query log sample set to 1/{}", sample);
+                    info!("{}", body);
+                    (200, body)
+                }
+                None => (400, "query log sample should be like 1/1000".to_owned()),
+            };
+        }
+        if path == "/metrics" {
+            let mut body = self.metrics.to_prometheus();
+            body.push_str(&self.connections.to_prometheus());
+            return (200, body);
+        }
+        if path == "/connections" {
+            return (200, self.connections.to_json());
+        }
+        let healthy = match path {
+            "/healthz" => self.is_alive(),
+            "/readyz" => self.is_ready(),
+            _ => return (404, "not found".to_owned()),
+        };
+        if healthy {
+            (200, "ok".to_owned())
+        } else {
+            (503, "unavailable".to_owned())
+        }
+    }
+
+    /// stats_response dump the global stats for `/stats` and the zone stats for
+    /// `/stats/example.com.` in json, a `?reset` query reset the stats after the dump.
+    fn stats_response(&self, path: &str) -> (u16, String) {
+        let (zone, query) = match path.find('?') {
+            Some(index) => (&path[..index], Some(&path[index + 1..])),
+            None => (path, None),
+        };
+        let reset = query == Some("reset");
+        let zone = zone.trim_start_matches('/');
+        if zone.is_empty() {
+            let body = self.stats.global().to_json(None, &[]);
+            if reset {
+                self.stats.reset(None);
+            }
+            return (200, body);
+        }
+        let zone = match DNSName::new(fqdn(zone).as_str(), None) {
+            Ok(zone) => zone,
+            Err(_) => return (404, "not found".to_owned()),
+        };
+        match self.stats.zone(&zone) {
+            Some(stats) => {
+                let body = stats.to_json(Some(&zone), &self.scheduler.history(&zone));
+                if reset {
+                    self.stats.reset(Some(&zone));
+                }
+                (200, body)
+            }
+            None => (404, "not found".to_owned()),
+        }
+    }
+}
+
+/// serve_health answer the /healthz and /readyz http requests, and the /get requests
+/// with the zone data of the storage.
+pub async fn serve_health(
+    listener: TcpListener,
+    state: Arc<ServerState>,
+    storage: SafeRBTreeStorage,
+) {
+    loop {
+        match listener.accept().await {
+            Ok((stream, _)) => {
+                let (state, storage) = (state.clone(), storage.clone());
+                tokio::spawn(async move {
+                    if let Err(err) = handle_health_request(stream, &state, storage).await {
+                        debug!("health check request error: {}", err);
+                    }
+                });
+            }
+            Err(err) => error!("accept health check connection error: {}", err),
+        }
+    }
+}
+
+async fn handle_health_request(
+    mut stream: TcpStream,
+    state: &ServerState,
+    // owned, the storage is Send but not Sync and is kept across the awaits
+    storage: SafeRBTreeStorage,
+) -> std::io::Result<()> {
+    let mut request = vec![0; MAX_REQUEST_SIZE];
+    let size = stream.read(&mut request).await?;
+    let request = String::from_utf8_lossy(&request[..size]);
+    // request line: GET /healthz HTTP/1.1
+    let mut parts = request.split_whitespace();
+    let (status, body, content_type) = match (parts.next(), parts.next()) {
+        (Some("GET"), Some(path)) if path.starts_with("/get/") => {
+            let (status, body) = record_response(&storage, &path["/get".len()..]);
+            match status {
+                200 => (status, body, "application/json"),
+                _ => (status, body, "text/plain"),
+            }
+        }
+        (Some("GET"), Some(path)) => {
+            let (status, body) = state.http_response(path);
+            match (status, path) {
+                (200, path) if path.starts_with("/stats") => (status, body, "application/json"),
+                (200, "/connections") => (status, body, "application/json"),
+                (200, "/metrics") => (status, body, "text/plain; version=0.0.4"),
+                _ => (status, body, "text/plain"),
+            }
+        }
+        _ => (405, "method not allowed".to_owned(), "text/plain"),
+    };
+    let reason = match status {
+        200 => "OK",
+        400 => "Bad Request",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        _ => "Service Unavailable",
+    };
+    let response = format!(
+        "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        reason,
+        content_type,
+        body.len(),
+        body
+    );
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::transfer::test::FakeClock;
+    use dnsproto::meta::{DNSType, RCode};
+    use dnsproto::qtype::DnsTypeSOA;
+
+    #[test]
+    fn test_server_state() {
+        let clock = Arc::new(FakeClock::default());
+        let scheduler = Arc::new(TransferScheduler::new(clock.clone()));
+        let state = ServerState::new(scheduler.clone(), clock.clone());
+        assert_eq!(state.http_response("/healthz"), (200, "ok".to_owned()));
+        assert_eq!(
+            state.http_response("/readyz"),
+            (503, "unavailable".to_owned())
+        );
+        assert_eq!(state.http_response("/unknown").0, 404);
+        state.set_zones_loaded(true);
+        assert!(state.is_ready());
+
+        let soa = DnsTypeSOA::new(
+            "ns.example.com.",
+            "admin.example.com.",
+            1,
+            100,
+            10,
+            1000,
+            60,
+        )
+        .unwrap();
+        scheduler.register(
+            DNSName::new("example.com.", None).unwrap(),
+            &soa,
+            false,
+            None,
+        );
+        clock.advance(1000);
+        assert!(!state.is_ready(), "secondary zone expired");
+        assert!(!state.is_alive(), "watchdog missed");
+        state.heartbeat();
+        assert!(state.is_alive());
+        scheduler.mark_refreshed(&DNSName::new("example.com.", None).unwrap(), &soa);
+        assert!(state.is_ready());
+        state.set_reload_failed(true);
+        assert!(!state.is_ready());
+    }
+
+    #[test]
+    fn test_stats_response() {
+        let clock = Arc::new(FakeClock::default());
+        let scheduler = Arc::new(TransferScheduler::new(clock.clone()));
+        let state = ServerState::new(scheduler, clock);
+        let zone = DNSName::new("example.com.", None).unwrap();
+        let name = DNSName::new("www.example.com.", None).unwrap();
+        state
+            .stats()
+            .record(Some(&zone), &name, &DNSType::A, RCode::NoError);
+        assert_eq!(state.http_response("/stats/example.org").0, 404);
+        for path in ["/stats/example.com", "/stats/example.com."].iter() {
+            let (status, body) = state.http_response(path);
+            assert_eq!(status, 200);
+            assert!(body.starts_with("{\"zone\":\"example.com.\",\"queries\":1,"));
+        }
+        let (status, body) = state.http_response("/stats?reset");
+        assert_eq!(status, 200);
+        assert!(body.starts_with("{\"zone\":null,\"queries\":1,"));
+        let (_, body) = state.http_response("/stats/example.com.");
+        assert!(body.contains("\"queries\":0,"));
+    }
+
+    #[test]
+    fn test_metrics_response() {
+        let state = ServerState::new(
+            Arc::new(TransferScheduler::default()),
+            Arc::new(FakeClock::default()),
+        );
+        state.metrics().observe_query(
+            crate::query::Transport::Udp,
+            0,
+            std::time::Duration::from_micros(50),
+        );
+        let (status, body) = state.http_response("/metrics");
+        assert_eq!(status, 200);
+        assert!(body.contains(
+            "otter_query_latency_microseconds_count{transport=\"udp\",rcode=\"NoError\"} 1\n"
+        ));
+        assert!(body.contains("otter_tcp_clients 0\n"));
+    }
+
+    #[test]
+    fn test_connections_response() {
+        let state = ServerState::new(
+            Arc::new(TransferScheduler::default()),
+            Arc::new(FakeClock::default()),
+        )
+        .with_connections(ConnectionLimiter::new(1, 1, 1));
+        let _permit = state
+            .connections()
+            .try_accept_tcp("192.0.2.1".parse().unwrap())
+            .unwrap();
+        assert!(state.connections().try_begin_udp().is_some());
+        assert!(state.connections().try_begin_udp().is_some());
+        let (status, body) = state.http_response("/connections");
+        assert_eq!(status, 200);
+        assert!(body.starts_with("{\"tcp_clients\":1,\"max_tcp_clients\":1,"));
+        assert!(body.ends_with("\"udp_in_flight\":0,\"max_udp_in_flight\":1,\"udp_dropped\":0}"));
+    }
+
+    #[test]
+    fn test_log_setting_response() {
+        let state = ServerState::new(
+            Arc::new(TransferScheduler::default()),
+            Arc::new(FakeClock::default()),
+        );
+        assert_eq!(
+            state.http_response("/set-log-level/DEBUG"),
+            (200, "log level set to debug".to_owned())
+        );
+        assert_eq!(crate::logging::log_level(), log::LevelFilter::Debug);
+        assert_eq!(state.http_response("/set-log-level/loud").0, 400);
+        assert_eq!(
+            state.http_response("/set-query-log-sample/1/1000"),
+            (200, "query log sample set to 1/1000".to_owned())
+        );
+        assert_eq!(crate::logging::query_log_sample(), 1000);
+        assert_eq!(state.http_response("/set-query-log-sample/all").0, 400);
+        set_log_level(log::LevelFilter::Info);
+        set_query_log_sample(1);
+    }
+
+    #[tokio::test]
+    async fn test_serve_health() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let state = Arc::new(ServerState::new(
+            Arc::new(TransferScheduler::default()),
+            Arc::new(FakeClock::default()),
+        ));
+        let storage = SafeRBTreeStorage::new_zone_from_str(
+            "example.com. 3600 IN SOA ns1.example.com. admin.example.com. 1 7200 3600 1209600 300\n",
+            None,
+        )
+        .unwrap();
+        tokio::spawn(serve_health(listener, state, storage));
+        for (path, status) in [
+            ("/healthz", "200"),
+            ("/readyz", "503"),
+            ("/get/example.com./SOA", "200"),
+            ("/get/example.org./SOA", "404"),
+        ]
+        .iter()
+        {
+            let mut stream = TcpStream::connect(addr).await.unwrap();
+            let request = format!("GET {} HTTP/1.1\r\nHost: localhost\r\n\r\n", path);
+            stream.write_all(request.as_bytes()).await.unwrap();
+            let mut response = String::new();
+            stream.read_to_string(&mut response).await.unwrap();
+            assert!(response.starts_with(&format!("HTTP/1.1 {}", status)));
+        }
+    }
+}
@@ -0,0 +1,201 @@
+use std::convert::TryFrom;
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::time::Duration;
+
+/// upper bounds in microseconds of the latency buckets, 16us to 512ms by powers of two.
+/// slower observations are only counted by the +Inf bucket.
+pub const LATENCY_BUCKETS: [u64; 16] = [
+    16, 32, 64, 128, 256, 512, 1024, 2048, 4096, 8192, 16384, 32768, 65536, 131072, 262144, 524288,
+];
+
+/// shards of each histogram, the threads of the runtime are spread over them
+const HISTOGRAM_SHARDS: usize = 16;
+
+static NEXT_SHARD: AtomicUsize = AtomicUsize::new(0);
+
+thread_local! {
+    // each thread always record to the same shard, assigned round robin
+    static SHARD: usize = NEXT_SHARD.fetch_add(1, Ordering::Relaxed) % HISTOGRAM_SHARDS;
+}
+
+// one cache line per shard, so the workers do not write the same line
+#[derive(Debug, Default)]
+#[repr(align(64))]
+struct Shard {
+    // the last one is the +Inf bucket
+    buckets: [AtomicU64; LATENCY_BUCKETS.len() + 1],
+    sum: AtomicU64,
+}
+
+/// LatencyHistogram count the observed latencies in the LATENCY_BUCKETS without locks,
+/// each worker thread write its own shard and the shards are merged when scraped.
+#[derive(Debug)]
+pub struct LatencyHistogram {
+    shards: Vec<Shard>,
+}
+
+impl Default for LatencyHistogram {
+    fn default() -> Self {
+        LatencyHistogram {
+            shards: (0..HISTOGRAM_SHARDS).map(|_| Shard::default()).collect(),
+        }
+    }
+}
+
+/// HistogramSnapshot is the merged counts of a histogram, the buckets are not cumulative.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct HistogramSnapshot {
+    pub buckets: [u64; LATENCY_BUCKETS.len() + 1],
+    // microseconds
+    pub sum: u64,
+}
+
+/// bucket_index return the index of the first bucket the latency fits in.
+pub fn bucket_index(micros: u64) -> usize {
+    LATENCY_BUCKETS
+        .iter()
+        .position(|bound| micros <= *bound)
+        .unwrap_or(LATENCY_BUCKETS.len())
+}
+
+impl LatencyHistogram {
+    pub fn observe(&self, elapsed: Duration) {
+        let micros = u64::try_from(elapsed.as_micros()).unwrap_or(u64::MAX);
+        let shard = &self.shards[SHARD.with(|shard| *shard)];
+        shard.buckets[bucket_index(micros)].fetch_add(1, Ordering::Relaxed);
+        shard.sum.fetch_add(micros, Ordering::Relaxed);
+    }
+
+    /// snapshot merge the shards, observations recorded during the merge may be counted in
+    /// the buckets but not in the sum yet.
+    pub fn snapshot(&self) -> HistogramSnapshot {
+        let mut snapshot = HistogramSnapshot::default();
+        for shard in self.shards.iter() {
+            for (total, bucket) in snapshot.buckets.iter_mut().zip(shard.buckets.iter()) {
+                *total += bucket.load(Ordering::Relaxed);
+            }
+            snapshot.sum += shard.sum.load(Ordering::Relaxed);
+        }
+        snapshot
+    }
+}
+
+impl HistogramSnapshot {
+    pub fn count(&self) -> u64 {
+        self.buckets.iter().sum()
+    }
+
+    /// write_prometheus append the histogram in the prometheus text format, labels are like
+    /// `transport="udp",rcode="NoError"` and can be empty. the TYPE line is written by
+    /// the caller once for all series of the name.
+    pub fn write_prometheus(&self, out: &mut String, name: &str, labels: &str) {
+        let separator = if labels.is_empty() { "" } else { "," };
+        let mut cumulative = 0;
+        for (index, count) in self.buckets.iter().enumerate() {
+            cumulative += count;
+            let bound = match LATENCY_BUCKETS.get(index) {
+                Some(bound) => bound.to_string(),
+                None => "+Inf".to_owned(),
+            };
+            let _ = writeln!(
+                out,
+                "{}_bucket{{{}{}le=\"{}\"}} {}",
+                name, labels, separator, bound, cumulative
+            );
+        }
+        let labels = match labels {
+            "" => String::new(),
+            labels => format!("{{{}}}", labels),
+        };
+        let _ = writeln!(out, "{}_sum{} {}", name, labels, self.sum);
+        let _ = writeln!(out, "{}_count{} {}", name, labels, cumulative);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_bucket_boundaries() {
+        assert_eq!(bucket_index(0), 0);
+        assert_eq!(bucket_index(16), 0);
+        assert_eq!(bucket_index(17), 1);
+        assert_eq!(bucket_index(1024), 6);
+        assert_eq!(bucket_index(1025), 7);
+        assert_eq!(bucket_index(524288), 15);
+        assert_eq!(bucket_index(524289), 16);
+        assert_eq!(bucket_index(u64::MAX), 16);
+    }
+
+    #[test]
+    fn test_histogram_shards_merged() {
+        let histogram = Arc::new(LatencyHistogram::default());
+        let workers: Vec<_> = (0..4)
+            .map(|_| {
+                let histogram = histogram.clone();
+                std::thread::spawn(move || {
+                    for _ in 0..1000 {
+                        histogram.observe(Duration::from_micros(100));
+                    }
+                })
+            })
+            .collect();
+        for worker in workers {
+            worker.join().unwrap();
+        }
+        histogram.observe(Duration::from_secs(1));
+        let snapshot = histogram.snapshot();
+        assert_eq!(snapshot.count(), 4001);
+        assert_eq!(snapshot.buckets[bucket_index(100)], 4000);
+        assert_eq!(snapshot.buckets[LATENCY_BUCKETS.len()], 1);
+        assert_eq!(snapshot.sum, 400_000 + 1_000_000);
+    }
+
+    #[test]
+    fn test_write_prometheus() {
+        let histogram = LatencyHistogram::default();
+        histogram.observe(Duration::from_micros(10));
+        histogram.observe(Duration::from_micros(20));
+        histogram.observe(Duration::from_millis(600));
+        let mut out = String::new();
+        histogram.snapshot().write_prometheus(
+            &mut out,
+            "otter_query_latency_microseconds",
+            "transport=\"udp\"",
+        );
+        let lines: Vec<&str> = out.lines().collect();
+        assert_eq!(lines.len(), LATENCY_BUCKETS.len() + 3);
+        assert_eq!(
+            lines[0],
+            "otter_query_latency_microseconds_bucket{transport=\"udp\",le=\"16\"} 1"
+        );
+        assert_eq!(
+            lines[1],
+            "otter_query_latency_microseconds_bucket{transport=\"udp\",le=\"32\"} 2"
+        );
+        assert_eq!(
+            lines[15],
+            "otter_query_latency_microseconds_bucket{transport=\"udp\",le=\"524288\"} 2"
+        );
+        assert_eq!(
+            lines[16],
+            "otter_query_latency_microseconds_bucket{transport=\"udp\",le=\"+Inf\"} 3"
+        );
+        assert_eq!(
+            lines[17],
+            "otter_query_latency_microseconds_sum{transport=\"udp\"} 600030"
+        );
+        assert_eq!(
+            lines[18],
+            "otter_query_latency_microseconds_count{transport=\"udp\"} 3"
+        );
+
+        let mut out = String::new();
+        HistogramSnapshot::default().write_prometheus(&mut out, "otter_stage", "");
+        assert!(out.starts_with("otter_stage_bucket{le=\"16\"} 0\n"));
+        assert!(out.ends_with("otter_stage_sum 0\notter_stage_count 0\n"));
+    }
+}
@@ -0,0 +1,247 @@
+use crate::stats::json_string;
+use dnsproto::dnsname::DNSName;
+use dnsproto::meta::{DNSType, ResourceRecord};
+use dnsproto::utils::fqdn;
+use std::str::FromStr;
+use storage::lookup::{LookupResult, NodeReport};
+use storage::storage::SafeRBTreeStorage;
+
+/// record_response dump what the storage holds for `/get/www.example.com./A` in json,
+/// a `?zone=example.com.` query looks the name up in the zone instead of the closest one,
+/// and the name is relative to the zone when it is not below it(`@` for the apex).
+pub fn record_response(storage: &SafeRBTreeStorage, path: &str) -> (u16, String) {
+    let (path, query) = match path.find('?') {
+        Some(index) => (&path[..index], Some(&path[index + 1..])),
+        None => (path, None),
+    };
+    let zone = match query.and_then(|query| query.strip_prefix("zone=")) {
+        Some(zone) => match DNSName::new(fqdn(zone).as_str(), None) {
+            Ok(zone) => Some(zone),
+            Err(_) => return (400, "invalid zone".to_owned()),
+        },
+        None => None,
+    };
+    let mut parts = path.trim_start_matches('/').splitn(2, '/');
+    let (name, dtype) = match (parts.next(), parts.next()) {
+        (Some(name), Some(dtype)) if !name.is_empty() => (name, dtype),
+        _ => return (400, "request should be like /get/<name>/<type>".to_owned()),
+    };
+    let dtype = match DNSType::from_str(dtype) {
+        Ok(dtype) => dtype,
+        Err(_) => return (400, "invalid type".to_owned()),
+    };
+    let name = match (&zone, name) {
+        (Some(zone), "@") => Ok(zone.clone()),
+        (Some(zone), name) => DNSName::new(fqdn(name).as_str(), None).and_then(|absolute| {
+            if absolute.is_part_of(zone) {
+                Ok(absolute)
+            } else {
+                DNSName::new(name, Some(zone.to_string().as_str()))
+            }
+        }),
+        (None, name) => DNSName::new(fqdn(name).as_str(), None),
+    };
+    let name = match name {
+        Ok(name) => name,
+        Err(_) => return (400, "invalid name".to_owned()),
+    };
+    // the names of hidden zones are answered as not served, the delegation in the parent
+    // zone as well
+    let hidden = storage
+        .find_zone(&name)
+        .map_or(false, |zone| storage.is_zone_hidden(&zone));
+    match storage.inspect(&name, dtype, zone.as_ref()) {
+        Ok(report) if !hidden && !storage.is_zone_hidden(&report.zone) => {
+            (200, report_json(&name, dtype, &report))
+        }
+        _ => (404, "not found".to_owned()),
+    }
+}
+
+fn report_json(name: &DNSName, dtype: DNSType, report: &NodeReport) -> String {
+    let result = match report.result {
+        LookupResult::Answer(_) => "answer",
+        LookupResult::Any(_) => "any",
+        LookupResult::CName(_) => "cname",
+        LookupResult::Delegation { .. } => "delegation",
+        LookupResult::NoData { .. } => "nodata",
+        LookupResult::NxDomain { .. } => "nxdomain",
+        LookupResult::OutOfZone => "outofzone",
+    };
+    let rrsets: Vec<String> = report
+        .rrsets
+        .iter()
+        .map(|(rtype, rrset)| {
+            let rrset = rrset.read().unwrap();
+            format!(
+                "{{\"type\":{},\"ttl\":{},\"status\":\"{}\",\"records\":[{}],\"signatures\":[{}]}}",
+                json_string(&rtype.to_string()),
+                rrset.ttl(),
+                report.status(*rtype),
+                records_json(rrset.content()),
+                records_json(rrset.signatures())
+            )
+        })
+        .collect();
+    let optional_name = |name: &Option<DNSName>| {
+        name.as_ref()
+            .map_or("null".to_owned(), |name| json_string(&name.to_string()))
+    };
+    format!(
+        "{{\"zone\":{},\"name\":{},\"type\":{},\"result\":\"{}\",\"node\":{},\"apex\":{},\"wildcard\":{},\"delegation\":{},\"below_cut\":{},\"rrsets\":[{}]}}",
+        json_string(&report.zone.to_string()),
+        json_string(&name.to_string()),
+        json_string(&dtype.to_string()),
+        result,
+        optional_name(&report.node),
+        report.apex,
+        report.wildcard,
+        report.delegation,
+        optional_name(&report.below_cut),
+        rrsets.join(",")
+    )
+}
+
+fn records_json(records: &[ResourceRecord]) -> String {
+    let records: Vec<String> = records
+        .iter()
+        .map(|rr| {
+            let rdata = rr
+                .get_data()
+                .as_ref()
+                .map_or(String::new(), |data| data.to_string());
+            format!(
+                "{{\"ttl\":{},\"rdata\":{},\"provenance\":\"{}\"}}",
+                rr.get_ttl(),
+                json_string(&rdata),
+                rr.provenance()
+            )
+        })
+        .collect();
+    records.join(",")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn storage() -> SafeRBTreeStorage {
+        let mut storage = SafeRBTreeStorage::new_zone_from_str(
+            "$ORIGIN example.com.\n\
+             @ 3600 IN SOA ns1 admin 1 7200 3600 1209600 300\n\
+             @ 3600 IN NS ns1\n\
+             ns1 3600 IN A 192.0.2.1\n\
+             www 300 IN A 192.0.2.80\n\
+             *.wild 600 IN TXT \"wildcard\"\n\
+             sub 3600 IN NS ns.sub\n\
+             ns.sub 3600 IN A 192.0.2.53\n\
+             other 3600 IN NS ns.other\n\
+             ns.other 3600 IN A 192.0.2.54\n\
+             ns.other 3600 IN TXT \"occluded\"\n",
+            None,
+        )
+        .unwrap();
+        storage
+            .update_zone_from_str(
+                "$ORIGIN sub.example.com.\n\
+                 @ 3600 IN SOA ns admin 1 7200 3600 1209600 300\n\
+                 @ 3600 IN NS ns\n\
+                 ns 3600 IN A 192.0.2.53\n\
+                 www 300 IN A 192.0.2.81\n",
+                None,
+                &Default::default(),
+            )
+            .unwrap();
+        storage
+    }
+
+    #[test]
+    fn test_record_response() {
+        let storage = storage();
+        let (status, body) = record_response(&storage, "/www.example.com/A");
+        assert_eq!(status, 200);
+        assert_eq!(
+            body,
+            "{\"zone\":\"example.com.\",\"name\":\"www.example.com.\",\"type\":\"A\",\
+             \"result\":\"answer\",\"node\":\"www.example.com.\",\"apex\":false,\
+             \"wildcard\":false,\"delegation\":false,\"below_cut\":null,\"rrsets\":[\
+             {\"type\":\"A\",\"ttl\":300,\"status\":\"authoritative\",\"records\":[\
+             {\"ttl\":300,\"rdata\":\"192.0.2.80\",\"provenance\":\"file\"}],\"signatures\":[]}]}"
+        );
+
+        let (_, body) = record_response(&storage, "/example.com./SOA");
+        assert!(body.contains("\"result\":\"answer\",\"node\":\"example.com.\",\"apex\":true,"));
+        assert!(body.contains("{\"type\":\"NS\",\"ttl\":3600,\"status\":\"authoritative\""));
+
+        let (_, body) = record_response(&storage, "/a.wild.example.com/TXT");
+        assert!(body.contains("\"node\":\"*.wild.example.com.\",\"apex\":false,\"wildcard\":true,"));
+        assert!(body.contains("{\"ttl\":600,\"rdata\":\"\\\"wildcard\\\"\""));
+
+        let (_, body) = record_response(&storage, "/missing.example.com/A");
+        assert!(body.contains("\"result\":\"nxdomain\",\"node\":null,"));
+        assert!(body.ends_with("\"rrsets\":[]}"));
+
+        let (_, body) = record_response(&storage, "/www/MX?zone=example.com");
+        assert!(
+            body.contains("\"name\":\"www.example.com.\",\"type\":\"MX\",\"result\":\"nodata\"")
+        );
+    }
+
+    #[test]
+    fn test_record_response_with_zone() {
+        let storage = storage();
+        // the closest zone of the name is the child zone
+        let (_, body) = record_response(&storage, "/ns.sub.example.com/A");
+        assert!(body.starts_with("{\"zone\":\"sub.example.com.\","));
+        assert!(body.contains("\"status\":\"authoritative\""));
+
+        // the parent side of the zone cut
+        let (_, body) = record_response(&storage, "/sub/NS?zone=example.com.");
+        assert!(body.contains(
+            "\"result\":\"delegation\",\"node\":\"sub.example.com.\",\"apex\":false,\"wildcard\":false,\"delegation\":true,\"below_cut\":null,"
+        ));
+        assert!(body.contains("{\"type\":\"NS\",\"ttl\":3600,\"status\":\"delegation\""));
+        let (_, body) = record_response(&storage, "/@/NS?zone=sub.example.com.");
+        assert!(body.contains("\"result\":\"answer\",\"node\":\"sub.example.com.\",\"apex\":true,"));
+
+        let (_, body) = record_response(&storage, "/ns.other/A?zone=example.com");
+        assert!(body.contains("\"below_cut\":\"other.example.com.\""));
+        assert!(body.contains("{\"type\":\"A\",\"ttl\":3600,\"status\":\"glue\""));
+        assert!(body.contains("{\"type\":\"TXT\",\"ttl\":3600,\"status\":\"occluded\""));
+
+        for path in [
+            "/www.example.org/A",
+            "/www.example.com./A?zone=example.org",
+            "/www.example.com./A?zone=sub.example.com",
+        ]
+        .iter()
+        {
+            assert_eq!(record_response(&storage, path).0, 404, "{}", path);
+        }
+        for path in ["/www.example.com", "/www.example.com/BAD", "//A"].iter() {
+            assert_eq!(record_response(&storage, path).0, 400, "{}", path);
+        }
+    }
+
+    #[test]
+    fn test_record_response_hidden_zone() {
+        let storage = storage();
+        let sub = DNSName::new("sub.example.com.", None).unwrap();
+        storage.set_zone_hidden(&sub, true);
+        for path in [
+            "/ns.sub.example.com/A",
+            "/@/NS?zone=sub.example.com.",
+            "/missing.sub.example.com/A",
+            "/sub/NS?zone=example.com.",
+        ]
+        .iter()
+        {
+            assert_eq!(
+                record_response(&storage, path),
+                (404, "not found".to_owned())
+            );
+        }
+        // the parent zone is still dumped
+        assert_eq!(record_response(&storage, "/www.example.com/A").0, 200);
+    }
+}
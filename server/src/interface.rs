@@ -0,0 +1,135 @@
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+
+/// interface_addrs return the addresses of all interfaces which are up.
+pub(crate) fn interface_addrs() -> io::Result<Vec<IpAddr>> {
+    let mut ifaddrs: *mut libc::ifaddrs = std::ptr::null_mut();
+    if unsafe { libc::getifaddrs(&mut ifaddrs) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let mut addrs = vec![];
+    let mut current = ifaddrs;
+    while !current.is_null() {
+        let ifaddr = unsafe { &*current };
+        current = ifaddr.ifa_next;
+        if ifaddr.ifa_addr.is_null() || ifaddr.ifa_flags & libc::IFF_UP as libc::c_uint == 0 {
+            continue;
+        }
+        let addr = match unsafe { (*ifaddr.ifa_addr).sa_family } as libc::c_int {
+            libc::AF_INET => {
+                let sin = unsafe { &*(ifaddr.ifa_addr as *const libc::sockaddr_in) };
+                IpAddr::V4(Ipv4Addr::from(u32::from_be(sin.sin_addr.s_addr)))
+            }
+            libc::AF_INET6 => {
+                let sin6 = unsafe { &*(ifaddr.ifa_addr as *const libc::sockaddr_in6) };
+                IpAddr::V6(Ipv6Addr::from(sin6.sin6_addr.s6_addr))
+            }
+            _ => continue,
+        };
+        if !addrs.contains(&addr) {
+            addrs.push(addr);
+        }
+    }
+    unsafe { libc::freeifaddrs(ifaddrs) };
+    Ok(addrs)
+}
+
+/// expand_wildcard_addrs replace each wildcard listen address with the interface addresses
+/// of the same family and port. ipv6 link-local addresses are skipped since they need a
+/// scope id, a wildcard address is kept if no interface address matches it.
+pub(crate) fn expand_wildcard_addrs(
+    listeners: &[SocketAddr],
+    interfaces: &[IpAddr],
+) -> Vec<SocketAddr> {
+    let mut expanded = vec![];
+    for listener in listeners.iter() {
+        let mut addrs = vec![*listener];
+        if listener.ip().is_unspecified() {
+            addrs = interfaces
+                .iter()
+                .filter(|ip| ip.is_ipv4() == listener.is_ipv4())
+                .filter(|ip| match ip {
+                    IpAddr::V6(v6) => v6.segments()[0] & 0xffc0 != 0xfe80,
+                    IpAddr::V4(_) => true,
+                })
+                .map(|ip| SocketAddr::new(*ip, listener.port()))
+                .collect();
+            if addrs.is_empty() {
+                warn!("no interface address found for {}, keep it", listener);
+                addrs.push(*listener);
+            }
+        }
+        for addr in addrs {
+            if !expanded.contains(&addr) {
+                expanded.push(addr);
+            }
+        }
+    }
+    expanded
+}
+
+/// expand_wildcard_listen expand the wildcard listen addresses with the addresses of the
+/// current interfaces, so replies always leave from the address the query was sent to.
+pub(crate) fn expand_wildcard_listen(listeners: &[SocketAddr]) -> Vec<SocketAddr> {
+    let interfaces = match interface_addrs() {
+        Ok(interfaces) => interfaces,
+        Err(err) => {
+            warn!("enumerate interface addresses fail: {}", err);
+            return listeners.to_vec();
+        }
+    };
+    let expanded = expand_wildcard_addrs(listeners, &interfaces);
+    for addr in expanded.iter() {
+        info!("expand wildcard udp listen address to: {}", addr);
+    }
+    expanded
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_expand_wildcard_addrs() {
+        let interfaces: Vec<IpAddr> = vec![
+            "127.0.0.1".parse().unwrap(),
+            "192.0.2.1".parse().unwrap(),
+            "::1".parse().unwrap(),
+            "fe80::1".parse().unwrap(),
+            "2001:db8::1".parse().unwrap(),
+        ];
+        let listeners: Vec<SocketAddr> = vec![
+            "0.0.0.0:53".parse().unwrap(),
+            "[::]:53".parse().unwrap(),
+            "127.0.0.1:53".parse().unwrap(),
+            "192.0.2.2:5353".parse().unwrap(),
+        ];
+        let expanded: Vec<String> = expand_wildcard_addrs(&listeners, &interfaces)
+            .iter()
+            .map(|addr| addr.to_string())
+            .collect();
+        assert_eq!(
+            expanded,
+            vec![
+                "127.0.0.1:53",
+                "192.0.2.1:53",
+                "[::1]:53",
+                "[2001:db8::1]:53",
+                "192.0.2.2:5353"
+            ]
+        );
+
+        // no ipv6 interface address
+        let listeners: Vec<SocketAddr> = vec!["[::]:53".parse().unwrap()];
+        assert_eq!(
+            expand_wildcard_addrs(&listeners, &interfaces[..2]),
+            listeners
+        );
+    }
+
+    #[test]
+    fn test_interface_addrs() {
+        let addrs = interface_addrs().unwrap();
+        assert!(addrs.contains(&IpAddr::V4(Ipv4Addr::LOCALHOST)));
+    }
+}
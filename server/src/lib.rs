@@ -1,6 +1,32 @@
 #[macro_use]
 extern crate log;
 pub use otter_server::OtterServer;
+mod axfr;
+pub mod cache;
+pub mod catalog;
+pub mod dnsclient;
+pub mod health;
+pub mod histogram;
+pub mod inspect;
+#[cfg(unix)]
+mod interface;
+pub mod limits;
+pub mod logging;
+pub mod metrics;
 mod otter_server;
+pub mod policy;
+#[cfg(unix)]
+mod privilege;
+pub mod query;
+pub mod querylog;
+pub mod signature;
+pub mod stats;
+#[cfg(unix)]
+pub mod syslog;
+#[cfg(all(target_os = "linux", feature = "systemd"))]
+mod systemd;
 mod tcp_server;
+pub mod transfer;
 mod udp_server;
+pub mod update;
+pub mod zonestate;
@@ -0,0 +1,243 @@
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::net::IpAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+/// default max tcp client connections open at the same time
+pub const DEFAULT_MAX_TCP_CLIENTS: usize = 1000;
+/// default max tcp client connections open from one address at the same time
+pub const DEFAULT_MAX_TCP_CLIENTS_PER_IP: usize = 100;
+/// default max udp queries in process at the same time
+pub const DEFAULT_MAX_UDP_IN_FLIGHT: usize = 1000;
+
+/// ConnectionLimiter bound the tcp client connections of the server and of each address,
+/// and the udp queries in process. a connection over the limits is closed at accept time
+/// and a udp query over the ceiling is dropped instead of waiting for a free slot, both
+/// are counted.
+#[derive(Debug)]
+pub struct ConnectionLimiter {
+    max_tcp_clients: usize,
+    max_tcp_clients_per_ip: usize,
+    max_udp_in_flight: usize,
+    tcp_global: Arc<Semaphore>,
+    tcp_clients: Arc<Mutex<HashMap<IpAddr, usize>>>,
+    udp_global: Arc<Semaphore>,
+    tcp_refused: AtomicU64,
+    udp_dropped: AtomicU64,
+}
+
+impl Default for ConnectionLimiter {
+    fn default() -> Self {
+        ConnectionLimiter::new(
+            DEFAULT_MAX_TCP_CLIENTS,
+            DEFAULT_MAX_TCP_CLIENTS_PER_IP,
+            DEFAULT_MAX_UDP_IN_FLIGHT,
+        )
+    }
+}
+
+/// TcpPermit hold a tcp client slot until the connection is closed.
+#[derive(Debug)]
+pub struct TcpPermit {
+    _permit: OwnedSemaphorePermit,
+    client: IpAddr,
+    clients: Arc<Mutex<HashMap<IpAddr, usize>>>,
+}
+
+impl Drop for TcpPermit {
+    fn drop(&mut self) {
+        let mut clients = self.clients.lock().unwrap();
+        if let Some(count) = clients.get_mut(&self.client) {
+            *count -= 1;
+            if *count == 0 {
+                clients.remove(&self.client);
+            }
+        }
+    }
+}
+
+/// UdpPermit hold an in-flight udp query slot until the response is sent.
+#[derive(Debug)]
+pub struct UdpPermit {
+    _permit: OwnedSemaphorePermit,
+}
+
+impl ConnectionLimiter {
+    pub fn new(
+        max_tcp_clients: usize,
+        max_tcp_clients_per_ip: usize,
+        max_udp_in_flight: usize,
+    ) -> ConnectionLimiter {
+        ConnectionLimiter {
+            max_tcp_clients,
+            max_tcp_clients_per_ip,
+            max_udp_in_flight,
+            tcp_global: Arc::new(Semaphore::new(max_tcp_clients)),
+            tcp_clients: Arc::new(Mutex::new(HashMap::new())),
+            udp_global: Arc::new(Semaphore::new(max_udp_in_flight)),
+            tcp_refused: AtomicU64::new(0),
+            udp_dropped: AtomicU64::new(0),
+        }
+    }
+
+    /// try_accept_tcp return None and count the refusal when the server or the client
+    /// has no free tcp slot.
+    pub fn try_accept_tcp(&self, client: IpAddr) -> Option<TcpPermit> {
+        let mut clients = self.tcp_clients.lock().unwrap();
+        let count = clients.get(&client).copied().unwrap_or(0);
+        let permit = match count < self.max_tcp_clients_per_ip {
+            true => self.tcp_global.clone().try_acquire_owned().ok(),
+            false => None,
+        };
+        let permit = match permit {
+            Some(permit) => permit,
+            None => {
+                self.tcp_refused.fetch_add(1, Ordering::Relaxed);
+                return None;
+            }
+        };
+        clients.insert(client, count + 1);
+        Some(TcpPermit {
+            _permit: permit,
+            client,
+            clients: self.tcp_clients.clone(),
+        })
+    }
+
+    /// try_begin_udp return None and count the dropped query when max_udp_in_flight
+    /// queries are already in process.
+    pub fn try_begin_udp(&self) -> Option<UdpPermit> {
+        match self.udp_global.clone().try_acquire_owned() {
+            Ok(permit) => Some(UdpPermit { _permit: permit }),
+            Err(_) => {
+                self.udp_dropped.fetch_add(1, Ordering::Relaxed);
+                None
+            }
+        }
+    }
+
+    /// tcp_clients return the tcp client connections open now.
+    pub fn tcp_clients(&self) -> usize {
+        self.max_tcp_clients - self.tcp_global.available_permits()
+    }
+
+    /// tcp_clients_of return the tcp connections open now from the client.
+    pub fn tcp_clients_of(&self, client: IpAddr) -> usize {
+        let clients = self.tcp_clients.lock().unwrap();
+        clients.get(&client).copied().unwrap_or(0)
+    }
+
+    /// udp_in_flight return the udp queries in process now.
+    pub fn udp_in_flight(&self) -> usize {
+        self.max_udp_in_flight - self.udp_global.available_permits()
+    }
+
+    pub fn tcp_refused(&self) -> u64 {
+        self.tcp_refused.load(Ordering::Relaxed)
+    }
+
+    pub fn udp_dropped(&self) -> u64 {
+        self.udp_dropped.load(Ordering::Relaxed)
+    }
+
+    /// to_json dump the current values and the limits for the /connections endpoint.
+    pub fn to_json(&self) -> String {
+        format!(
+            "{{\"tcp_clients\":{},\"max_tcp_clients\":{},\"max_tcp_clients_per_ip\":{},\"tcp_refused\":{},\"udp_in_flight\":{},\"max_udp_in_flight\":{},\"udp_dropped\":{}}}",
+            self.tcp_clients(),
+            self.max_tcp_clients,
+            self.max_tcp_clients_per_ip,
+            self.tcp_refused(),
+            self.udp_in_flight(),
+            self.max_udp_in_flight,
+            self.udp_dropped()
+        )
+    }
+
+    /// to_prometheus dump the current values for the /metrics endpoint.
+    pub fn to_prometheus(&self) -> String {
+        let mut out = String::new();
+        for (name, kind, help, value) in [
+            (
+                "otter_tcp_clients",
+                "gauge",
+                "Tcp client connections open.",
+                self.tcp_clients() as u64,
+            ),
+            (
+                "otter_tcp_refused_total",
+                "counter",
+                "Tcp client connections closed by the connection limits.",
+                self.tcp_refused(),
+            ),
+            (
+                "otter_udp_in_flight",
+                "gauge",
+                "Udp queries in process.",
+                self.udp_in_flight() as u64,
+            ),
+            (
+                "otter_udp_dropped_total",
+                "counter",
+                "Udp queries dropped over max_udp_in_flight.",
+                self.udp_dropped(),
+            ),
+        ]
+        .iter()
+        {
+            let _ = writeln!(out, "# HELP {} {}", name, help);
+            let _ = writeln!(out, "# TYPE {} {}", name, kind);
+            let _ = writeln!(out, "{} {}", name, value);
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_tcp_limits() {
+        let limiter = ConnectionLimiter::new(2, 1, 1);
+        let client: IpAddr = "192.0.2.1".parse().unwrap();
+        let other: IpAddr = "192.0.2.2".parse().unwrap();
+        let first = limiter.try_accept_tcp(client).unwrap();
+        // over the per address limit
+        assert!(limiter.try_accept_tcp(client).is_none());
+        let second = limiter.try_accept_tcp(other).unwrap();
+        // over the global limit
+        assert!(limiter
+            .try_accept_tcp("192.0.2.3".parse().unwrap())
+            .is_none());
+        assert_eq!(limiter.tcp_clients(), 2);
+        assert_eq!(limiter.tcp_refused(), 2);
+        drop(first);
+        assert_eq!(limiter.tcp_clients_of(client), 0);
+        let _third = limiter.try_accept_tcp(client).unwrap();
+        drop(second);
+        assert_eq!(limiter.tcp_clients(), 1);
+        assert_eq!(limiter.tcp_clients_of(other), 0);
+    }
+
+    #[test]
+    fn test_udp_in_flight() {
+        let limiter = ConnectionLimiter::new(1, 1, 2);
+        let first = limiter.try_begin_udp().unwrap();
+        let _second = limiter.try_begin_udp().unwrap();
+        assert!(limiter.try_begin_udp().is_none());
+        assert_eq!(limiter.udp_in_flight(), 2);
+        assert_eq!(limiter.udp_dropped(), 1);
+        drop(first);
+        assert!(limiter.try_begin_udp().is_some());
+        assert_eq!(
+            limiter.to_json(),
+            "{\"tcp_clients\":0,\"max_tcp_clients\":1,\"max_tcp_clients_per_ip\":1,\"tcp_refused\":0,\"udp_in_flight\":1,\"max_udp_in_flight\":2,\"udp_dropped\":1}"
+        );
+        let out = limiter.to_prometheus();
+        assert!(out.contains("# TYPE otter_udp_in_flight gauge\notter_udp_in_flight 1\n"));
+        assert!(out.contains("otter_udp_dropped_total 1\n"));
+    }
+}
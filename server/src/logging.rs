@@ -0,0 +1,181 @@
+#[cfg(unix)]
+use crate::syslog::{severity, Syslog};
+use env_logger::{Builder, Env, Logger};
+use log::{LevelFilter, Log, Metadata, Record, SetLoggerError};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, AtomicU8, Ordering};
+
+static LOG_LEVEL: AtomicU8 = AtomicU8::new(LevelFilter::Info as u8);
+// level of the server records sent to the syslog, off without a syslog target
+static SYSLOG_LEVEL: AtomicU8 = AtomicU8::new(LevelFilter::Off as u8);
+// log one of every QUERY_LOG_SAMPLE queries, 0 disable the query log
+static QUERY_LOG_SAMPLE: AtomicU64 = AtomicU64::new(1);
+static QUERY_LOG_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// ReloadableLogger check the runtime log level before passing the record to env_logger,
+/// so the level can be changed without a restart. the records at the syslog level are
+/// sent to the syslog as well.
+struct ReloadableLogger {
+    inner: Logger,
+    #[cfg(unix)]
+    syslog: Option<Syslog>,
+}
+
+impl ReloadableLogger {
+    fn syslog_enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= level_filter(SYSLOG_LEVEL.load(Ordering::Relaxed))
+    }
+}
+
+impl Log for ReloadableLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        (metadata.level() <= log_level() && self.inner.enabled(metadata))
+            || self.syslog_enabled(metadata)
+    }
+
+    fn log(&self, record: &Record) {
+        if record.level() <= log_level() && self.inner.enabled(record.metadata()) {
+            self.inner.log(record);
+        }
+        #[cfg(unix)]
+        if let Some(syslog) = self.syslog.as_ref() {
+            if self.syslog_enabled(record.metadata()) {
+                syslog.send(severity(record.level()), &record.args().to_string());
+            }
+        }
+    }
+
+    fn flush(&self) {
+        self.inner.flush();
+    }
+}
+
+/// init_logger install the reloadable logger with the initial level, RUST_LOG can still
+/// select the modules to log but not raise the level.
+pub fn init_logger(level: LevelFilter) -> Result<(), SetLoggerError> {
+    let inner = Builder::from_env(Env::default().default_filter_or("trace")).build();
+    log::set_boxed_logger(Box::new(ReloadableLogger {
+        inner,
+        #[cfg(unix)]
+        syslog: None,
+    }))?;
+    set_log_level(level);
+    Ok(())
+}
+
+/// init_logger_with_syslog install the reloadable logger which also send the server records
+/// at the syslog level or lower to the syslog, the syslog level is not changed by a reload.
+#[cfg(unix)]
+pub fn init_logger_with_syslog(
+    level: LevelFilter,
+    syslog: Syslog,
+    syslog_level: LevelFilter,
+) -> Result<(), SetLoggerError> {
+    let inner = Builder::from_env(Env::default().default_filter_or("trace")).build();
+    log::set_boxed_logger(Box::new(ReloadableLogger {
+        inner,
+        syslog: Some(syslog),
+    }))?;
+    SYSLOG_LEVEL.store(syslog_level as u8, Ordering::Relaxed);
+    set_log_level(level);
+    Ok(())
+}
+
+pub fn set_log_level(level: LevelFilter) {
+    LOG_LEVEL.store(level as u8, Ordering::Relaxed);
+    log::set_max_level(level.max(level_filter(SYSLOG_LEVEL.load(Ordering::Relaxed))));
+}
+
+pub fn log_level() -> LevelFilter {
+    level_filter(LOG_LEVEL.load(Ordering::Relaxed))
+}
+
+fn level_filter(level: u8) -> LevelFilter {
+    match level {
+        0 => LevelFilter::Off,
+        1 => LevelFilter::Error,
+        2 => LevelFilter::Warn,
+        3 => LevelFilter::Info,
+        4 => LevelFilter::Debug,
+        _ => LevelFilter::Trace,
+    }
+}
+
+/// parse_log_level parse the level name like warn or debug.
+pub fn parse_log_level(level: &str) -> Option<LevelFilter> {
+    LevelFilter::from_str(level.trim()).ok()
+}
+
+/// channel_level parse the level of a log channel in the config like warning or fatal, off
+/// if not set.
+pub fn channel_level(level: Option<&str>) -> LevelFilter {
+    match level.map(|level| level.trim().to_lowercase()).as_deref() {
+        Some("fatal") | Some("error") => LevelFilter::Error,
+        Some("warning") => LevelFilter::Warn,
+        Some("info") => LevelFilter::Info,
+        Some("debug") => LevelFilter::Debug,
+        Some("trace") => LevelFilter::Trace,
+        _ => LevelFilter::Off,
+    }
+}
+
+/// set_query_log_sample log one of every n queries, 0 disable the query log.
+pub fn set_query_log_sample(n: u64) {
+    QUERY_LOG_SAMPLE.store(n, Ordering::Relaxed);
+}
+
+pub fn query_log_sample() -> u64 {
+    QUERY_LOG_SAMPLE.load(Ordering::Relaxed)
+}
+
+/// parse_query_log_sample parse the sample rate like 1/1000 or 1000.
+pub fn parse_query_log_sample(sample: &str) -> Option<u64> {
+    let sample = sample.trim();
+    u64::from_str(sample.strip_prefix("1/").unwrap_or(sample)).ok()
+}
+
+/// sample_query return true when the current query should be logged.
+pub fn sample_query() -> bool {
+    match query_log_sample() {
+        0 => false,
+        1 => true,
+        n => QUERY_LOG_COUNTER.fetch_add(1, Ordering::Relaxed) % n == 0,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_log_setting() {
+        assert_eq!(parse_log_level("DEBUG"), Some(LevelFilter::Debug));
+        assert_eq!(parse_log_level("off"), Some(LevelFilter::Off));
+        assert_eq!(parse_log_level("verbose"), None);
+        assert_eq!(parse_query_log_sample("1/1000"), Some(1000));
+        assert_eq!(parse_query_log_sample("10"), Some(10));
+        assert_eq!(parse_query_log_sample("1/"), None);
+        assert_eq!(parse_query_log_sample("half"), None);
+        assert_eq!(channel_level(Some("Warning")), LevelFilter::Warn);
+        assert_eq!(channel_level(Some("fatal")), LevelFilter::Error);
+        assert_eq!(channel_level(None), LevelFilter::Off);
+    }
+
+    #[test]
+    fn test_log_level_roundtrip() {
+        for level in [
+            LevelFilter::Off,
+            LevelFilter::Error,
+            LevelFilter::Warn,
+            LevelFilter::Info,
+            LevelFilter::Debug,
+            LevelFilter::Trace,
+        ]
+        .iter()
+        {
+            LOG_LEVEL.store(*level as u8, Ordering::Relaxed);
+            assert_eq!(log_level(), *level);
+        }
+        LOG_LEVEL.store(LevelFilter::Info as u8, Ordering::Relaxed);
+    }
+}
@@ -0,0 +1,343 @@
+use crate::histogram::{HistogramSnapshot, LatencyHistogram};
+use crate::query::Transport;
+use dnsproto::meta::RCode;
+use otterlib::errors::TransferError;
+use otterlib::setting::AnyOverUdp;
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::RwLock;
+use std::time::Duration;
+
+/// Stage is a step of the query process timed when detailed_timing is set.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Stage {
+    Parse,
+    Lookup,
+    Encode,
+}
+
+const STAGES: [Stage; 3] = [Stage::Parse, Stage::Lookup, Stage::Encode];
+
+const ANY_OUTCOMES: [(AnyOverUdp, &str); 3] = [
+    (AnyOverUdp::Refuse, "refuse"),
+    (AnyOverUdp::Truncate, "truncate"),
+    (AnyOverUdp::Answer, "answer"),
+];
+
+impl Stage {
+    fn name(self) -> &'static str {
+        match self {
+            Stage::Parse => "parse",
+            Stage::Lookup => "lookup",
+            Stage::Encode => "encode",
+        }
+    }
+}
+
+/// Metrics holds the counters of query process, shared by all listeners.
+#[derive(Debug, Default)]
+pub struct Metrics {
+    // query for zone not configured in this server
+    refused_not_auth: AtomicU64,
+    // query refused by zone allow_query setting
+    refused_by_acl: AtomicU64,
+    // query for expired secondary zone
+    servfail_expired: AtomicU64,
+    // query not answered before the query deadline
+    servfail_timeout: AtomicU64,
+    // update of secondary zone failed to forward to the master
+    servfail_forward: AtomicU64,
+    // cname chain loops or exceeds max_chain_depth
+    servfail_chain: AtomicU64,
+    // query answered with stale secondary zone data
+    stale_answers: AtomicU64,
+    // query answered NXDOMAIN by a policy rule
+    policy_nxdomain: AtomicU64,
+    // query dropped by a policy rule
+    policy_drop: AtomicU64,
+    // query answered the fixed address by a policy rule
+    policy_fixed: AtomicU64,
+    // query answered from response cache
+    cache_hits: AtomicU64,
+    // query not found in response cache
+    cache_misses: AtomicU64,
+    // outgoing zone transfers in progress
+    transfers_active: AtomicU64,
+    // outgoing zone transfers refused by the concurrent transfer limits
+    transfers_denied: AtomicU64,
+    // incoming zone transfers rejected by max_transfer_records
+    transfers_in_too_many_records: AtomicU64,
+    // incoming zone transfers rejected by max_transfer_bytes
+    transfers_in_too_many_bytes: AtomicU64,
+    // incoming zone transfers rejected for records out of the zone
+    transfers_in_out_of_zone: AtomicU64,
+    // incoming zone transfers rejected for missing or mismatched soa records
+    transfers_in_soa_mismatch: AtomicU64,
+    // rrsig records expire within the warning window
+    signatures_expiring: AtomicU64,
+    // rrsig records already expired
+    signatures_expired: AtomicU64,
+    // ANY queries over udp refused, truncated and answered by any_over_udp
+    any_over_udp: [AtomicU64; 3],
+    // master remote id => (succeeded, failed) refreshes of secondary zones
+    masters: RwLock<HashMap<String, (u64, u64)>>,
+    // udp and tcp query latency by the 4 bits rcode of the response header
+    latency: [[LatencyHistogram; 16]; 2],
+    // parse, lookup and encode time of the queries when detailed_timing is set
+    stages: [LatencyHistogram; 3],
+}
+
+impl Metrics {
+    pub fn incr_refused_not_auth(&self) {
+        self.refused_not_auth.fetch_add(1, Ordering::Relaxed);
+    }
+    pub fn incr_refused_by_acl(&self) {
+        self.refused_by_acl.fetch_add(1, Ordering::Relaxed);
+    }
+    pub fn incr_servfail_expired(&self) {
+        self.servfail_expired.fetch_add(1, Ordering::Relaxed);
+    }
+    pub fn incr_servfail_timeout(&self) {
+        self.servfail_timeout.fetch_add(1, Ordering::Relaxed);
+    }
+    pub fn incr_servfail_chain(&self) {
+        self.servfail_chain.fetch_add(1, Ordering::Relaxed);
+    }
+    pub fn incr_servfail_forward(&self) {
+        self.servfail_forward.fetch_add(1, Ordering::Relaxed);
+    }
+    pub fn incr_stale_answers(&self) {
+        self.stale_answers.fetch_add(1, Ordering::Relaxed);
+    }
+    pub fn incr_policy_nxdomain(&self) {
+        self.policy_nxdomain.fetch_add(1, Ordering::Relaxed);
+    }
+    pub fn incr_policy_drop(&self) {
+        self.policy_drop.fetch_add(1, Ordering::Relaxed);
+    }
+    pub fn incr_policy_fixed(&self) {
+        self.policy_fixed.fetch_add(1, Ordering::Relaxed);
+    }
+    pub fn incr_cache_hits(&self) {
+        self.cache_hits.fetch_add(1, Ordering::Relaxed);
+    }
+    pub fn incr_cache_misses(&self) {
+        self.cache_misses.fetch_add(1, Ordering::Relaxed);
+    }
+    pub fn incr_transfers_active(&self) {
+        self.transfers_active.fetch_add(1, Ordering::Relaxed);
+    }
+    pub fn decr_transfers_active(&self) {
+        self.transfers_active.fetch_sub(1, Ordering::Relaxed);
+    }
+    pub fn incr_transfers_denied(&self) {
+        self.transfers_denied.fetch_add(1, Ordering::Relaxed);
+    }
+    /// incr_transfer_rejected count the rejected incoming transfer by the reason.
+    pub fn incr_any_over_udp(&self, outcome: AnyOverUdp) {
+        self.any_over_udp[outcome as usize].fetch_add(1, Ordering::Relaxed);
+    }
+    pub fn incr_transfer_rejected(&self, err: &TransferError) {
+        let counter = match err {
+            TransferError::TooManyRecords(..) => &self.transfers_in_too_many_records,
+            TransferError::TooManyBytes(..) => &self.transfers_in_too_many_bytes,
+            TransferError::OutOfZone(..) => &self.transfers_in_out_of_zone,
+            TransferError::SOAMismatch(..) => &self.transfers_in_soa_mismatch,
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+    pub fn incr_master_success(&self, master: &str) {
+        let mut masters = self.masters.write().unwrap();
+        masters.entry(master.to_owned()).or_default().0 += 1;
+    }
+    pub fn incr_master_failure(&self, master: &str) {
+        let mut masters = self.masters.write().unwrap();
+        masters.entry(master.to_owned()).or_default().1 += 1;
+    }
+    pub fn set_signatures(&self, expiring: u64, expired: u64) {
+        self.signatures_expiring.store(expiring, Ordering::Relaxed);
+        self.signatures_expired.store(expired, Ordering::Relaxed);
+    }
+    pub fn refused_not_auth(&self) -> u64 {
+        self.refused_not_auth.load(Ordering::Relaxed)
+    }
+    pub fn refused_by_acl(&self) -> u64 {
+        self.refused_by_acl.load(Ordering::Relaxed)
+    }
+    pub fn servfail_expired(&self) -> u64 {
+        self.servfail_expired.load(Ordering::Relaxed)
+    }
+    pub fn servfail_timeout(&self) -> u64 {
+        self.servfail_timeout.load(Ordering::Relaxed)
+    }
+    pub fn servfail_chain(&self) -> u64 {
+        self.servfail_chain.load(Ordering::Relaxed)
+    }
+    pub fn servfail_forward(&self) -> u64 {
+        self.servfail_forward.load(Ordering::Relaxed)
+    }
+    pub fn stale_answers(&self) -> u64 {
+        self.stale_answers.load(Ordering::Relaxed)
+    }
+    pub fn policy_nxdomain(&self) -> u64 {
+        self.policy_nxdomain.load(Ordering::Relaxed)
+    }
+    pub fn policy_drop(&self) -> u64 {
+        self.policy_drop.load(Ordering::Relaxed)
+    }
+    pub fn policy_fixed(&self) -> u64 {
+        self.policy_fixed.load(Ordering::Relaxed)
+    }
+    pub fn cache_hits(&self) -> u64 {
+        self.cache_hits.load(Ordering::Relaxed)
+    }
+    pub fn cache_misses(&self) -> u64 {
+        self.cache_misses.load(Ordering::Relaxed)
+    }
+    pub fn transfers_active(&self) -> u64 {
+        self.transfers_active.load(Ordering::Relaxed)
+    }
+    pub fn transfers_denied(&self) -> u64 {
+        self.transfers_denied.load(Ordering::Relaxed)
+    }
+    pub fn transfers_in_too_many_records(&self) -> u64 {
+        self.transfers_in_too_many_records.load(Ordering::Relaxed)
+    }
+    pub fn transfers_in_too_many_bytes(&self) -> u64 {
+        self.transfers_in_too_many_bytes.load(Ordering::Relaxed)
+    }
+    pub fn transfers_in_out_of_zone(&self) -> u64 {
+        self.transfers_in_out_of_zone.load(Ordering::Relaxed)
+    }
+    pub fn transfers_in_soa_mismatch(&self) -> u64 {
+        self.transfers_in_soa_mismatch.load(Ordering::Relaxed)
+    }
+    pub fn signatures_expiring(&self) -> u64 {
+        self.signatures_expiring.load(Ordering::Relaxed)
+    }
+    pub fn signatures_expired(&self) -> u64 {
+        self.signatures_expired.load(Ordering::Relaxed)
+    }
+    pub fn any_over_udp(&self, outcome: AnyOverUdp) -> u64 {
+        self.any_over_udp[outcome as usize].load(Ordering::Relaxed)
+    }
+    /// observe_query record the time to answer a query with the response rcode.
+    pub fn observe_query(&self, transport: Transport, rcode: u8, elapsed: Duration) {
+        self.latency[transport as usize][usize::from(rcode & 0x0f)].observe(elapsed);
+    }
+    pub fn observe_stage(&self, stage: Stage, elapsed: Duration) {
+        self.stages[stage as usize].observe(elapsed);
+    }
+    pub fn query_latency(&self, transport: Transport, rcode: u8) -> HistogramSnapshot {
+        self.latency[transport as usize][usize::from(rcode & 0x0f)].snapshot()
+    }
+    pub fn stage_latency(&self, stage: Stage) -> HistogramSnapshot {
+        self.stages[stage as usize].snapshot()
+    }
+
+    /// to_prometheus dump the latency histograms for the /metrics endpoint, series without
+    /// any observation are skipped.
+    pub fn to_prometheus(&self) -> String {
+        let mut out = String::new();
+        out.push_str("# HELP otter_query_latency_microseconds Time to answer a query.\n");
+        out.push_str("# TYPE otter_query_latency_microseconds histogram\n");
+        for transport in [Transport::Udp, Transport::Tcp].iter() {
+            for rcode in 0..16u8 {
+                let snapshot = self.query_latency(*transport, rcode);
+                if snapshot.count() == 0 {
+                    continue;
+                }
+                let labels = format!(
+                    "transport=\"{}\",rcode=\"{}\"",
+                    transport.name(),
+                    rcode_label(rcode)
+                );
+                snapshot.write_prometheus(&mut out, "otter_query_latency_microseconds", &labels);
+            }
+        }
+        out.push_str("# HELP otter_query_stage_microseconds Time of each query process stage.\n");
+        out.push_str("# TYPE otter_query_stage_microseconds histogram\n");
+        for stage in STAGES.iter() {
+            let snapshot = self.stage_latency(*stage);
+            if snapshot.count() == 0 {
+                continue;
+            }
+            let labels = format!("stage=\"{}\"", stage.name());
+            snapshot.write_prometheus(&mut out, "otter_query_stage_microseconds", &labels);
+        }
+        out.push_str("# HELP otter_any_over_udp_total ANY queries over udp by the outcome.\n");
+        out.push_str("# TYPE otter_any_over_udp_total counter\n");
+        for (outcome, name) in ANY_OUTCOMES.iter() {
+            let _ = writeln!(
+                out,
+                "otter_any_over_udp_total{{outcome=\"{}\"}} {}",
+                name,
+                self.any_over_udp(*outcome)
+            );
+        }
+        out
+    }
+
+    /// master_refreshes return the (succeeded, failed) refreshes from the master.
+    pub fn master_refreshes(&self, master: &str) -> (u64, u64) {
+        self.masters
+            .read()
+            .unwrap()
+            .get(master)
+            .copied()
+            .unwrap_or_default()
+    }
+}
+
+// rcode_label is the rcode name, or the number for the codes without a name
+fn rcode_label(rcode: u8) -> String {
+    match RCode::from(rcode) {
+        RCode::Unknown => rcode.to_string(),
+        rcode => format!("{:?}", rcode),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_metrics_to_prometheus() {
+        let metrics = Metrics::default();
+        metrics.observe_query(Transport::Udp, 0, Duration::from_micros(20));
+        metrics.observe_query(Transport::Udp, 0, Duration::from_micros(40));
+        metrics.observe_query(Transport::Tcp, 3, Duration::from_micros(100));
+        metrics.observe_query(Transport::Tcp, 12, Duration::from_micros(100));
+        metrics.observe_stage(Stage::Lookup, Duration::from_micros(5));
+        metrics.incr_any_over_udp(AnyOverUdp::Truncate);
+        assert_eq!(metrics.query_latency(Transport::Udp, 0).count(), 2);
+        assert_eq!(metrics.query_latency(Transport::Udp, 3).count(), 0);
+        assert_eq!(metrics.stage_latency(Stage::Lookup).sum, 5);
+
+        let out = metrics.to_prometheus();
+        let lines: Vec<&str> = out.lines().collect();
+        assert_eq!(
+            lines[..3],
+            [
+                "# HELP otter_query_latency_microseconds Time to answer a query.",
+                "# TYPE otter_query_latency_microseconds histogram",
+                "otter_query_latency_microseconds_bucket{transport=\"udp\",rcode=\"NoError\",le=\"16\"} 0",
+            ]
+        );
+        assert!(lines.contains(
+            &"otter_query_latency_microseconds_bucket{transport=\"udp\",rcode=\"NoError\",le=\"64\"} 2"
+        ));
+        assert!(lines.contains(
+            &"otter_query_latency_microseconds_count{transport=\"tcp\",rcode=\"NameError\"} 1"
+        ));
+        assert!(lines
+            .contains(&"otter_query_latency_microseconds_count{transport=\"tcp\",rcode=\"12\"} 1"));
+        assert!(lines.contains(&"otter_query_stage_microseconds_sum{stage=\"lookup\"} 5"));
+        assert!(lines.contains(&"otter_any_over_udp_total{outcome=\"truncate\"} 1"));
+        assert!(lines.contains(&"otter_any_over_udp_total{outcome=\"refuse\"} 0"));
+        // 3 query series and 1 stage series of 19 lines each, 3 any counters and 6 comment
+        // lines
+        assert_eq!(lines.len(), 4 * 19 + 3 + 6);
+    }
+}
@@ -1,139 +1,634 @@
+use crate::axfr::{transfer_messages, write_paced};
+use crate::catalog::{catalog_members, ZoneCatalog};
+use crate::dnsclient::{self, QueryOption};
+use crate::health::{serve_health, ServerState, WATCHDOG_INTERVAL};
+#[cfg(unix)]
+use crate::interface;
+use crate::limits::{
+    ConnectionLimiter, DEFAULT_MAX_TCP_CLIENTS, DEFAULT_MAX_TCP_CLIENTS_PER_IP,
+    DEFAULT_MAX_UDP_IN_FLIGHT,
+};
+use crate::logging;
+#[cfg(unix)]
+use crate::privilege;
+use crate::query::{process_message, ServerContext, TsigState};
+use crate::querylog::{query_log_setting, QueryLog};
+use crate::signature::{signature_monitor, DEFAULT_SIGNATURE_WINDOW};
+#[cfg(unix)]
+use crate::syslog::query_syslog_setting;
+#[cfg(all(target_os = "linux", feature = "systemd"))]
+use crate::systemd;
 use crate::tcp_server::TCPServer;
+use crate::transfer::{
+    load_secondary, query_soa_tcp, transfer_in, transfer_records, Clock, Master, MasterList,
+    SystemClock, TransferLimits, TransferScheduler,
+};
 use crate::udp_server::UdpServer;
 use dnsproto::dnsname::DNSName;
 use dnsproto::message::Message;
-use dnsproto::meta::{DNSType, RCode};
+use dnsproto::meta::{DNSClass, DNSType, Header, OpCode, Question, RCode};
+use dnsproto::qtype::DnsTypeSOA;
+use dnsproto::utils::fqdn;
 use net2::unix::UnixUdpBuilderExt;
 // use net2::{TcpBuilder, UdpBuilder};
-use otterlib::errors::OtterError;
-use otterlib::errors::{DNSProtoErr, NetworkError, StorageError};
-use otterlib::setting::{ExSetting, Settings};
+use otterlib::errors::{DNSProtoErr, NetworkError};
+use otterlib::errors::{ErrorContextExt, OtterError};
+use otterlib::setting::{ExSetting, Masters, SelfTestMode, Settings, ZoneSetting};
+use std::collections::HashMap;
 use std::net::SocketAddr;
 use std::result::Result::Err;
 use std::sync::Arc;
-use storage::storage::SafeRBTreeStorage;
+use std::time::Duration;
+use storage::storage::{LoadOption, LoadProgress, SafeRBTreeStorage, ZoneOption};
+use storage::writer::write_zone_file;
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
-use tokio::net::{TcpListener, UdpSocket};
+use tokio::net::{TcpListener, TcpStream, UdpSocket};
 use tokio::task::JoinHandle;
+use tokio::time::timeout;
 
 pub type TokioError = Box<dyn std::error::Error + Send + Sync>;
 pub type TokioResult<T> = std::result::Result<T, TokioError>;
 
-/// report_query_message
-fn report_query_message(dnsname: &DNSName, dnstype: &DNSType, remote: &SocketAddr, from_udp: bool) {
-    info!(
-        "receive query: {} IN {} from {} +{}",
-        dnsname.to_string(),
-        dnstype.to_string(),
-        remote.to_string(),
-        {
-            if from_udp {
-                "udp".to_string()
-            } else {
-                "tcp".to_string()
-            }
+// log the zone loading progress every million records
+const LOAD_PROGRESS_INTERVAL: usize = 1_000_000;
+
+// seconds between the checks of the secondary zone refresh timers
+const REFRESH_CHECK_INTERVAL: u64 = 10;
+
+/// process_message_with_deadline run process_message on the blocking pool, so a stuck
+/// query does not wedge the listener, and answer SERVFAIL when the query deadline passes.
+async fn process_message_with_deadline(
+    storage: SafeRBTreeStorage,
+    message: Vec<u8>,
+    remote: SocketAddr,
+    from_udp: bool,
+    context: Arc<ServerContext>,
+) -> Result<Vec<u8>, DNSProtoErr> {
+    if let Some(target) = forward_target(&message, &context) {
+        return forward_update(message, target, remote, from_udp, &context).await;
+    }
+    let query_timeout = context.query_timeout;
+    let task = {
+        let (message, context) = (message.clone(), context.clone());
+        tokio::task::spawn_blocking(move || {
+            process_message(storage, &message, &remote, from_udp, &context)
+        })
+    };
+    match timeout(query_timeout, task).await {
+        Ok(Ok(result)) => return result,
+        Ok(Err(err)) => error!("process message from {} fail: {}", remote, err),
+        Err(_) => {
+            warn!(
+                "process message from {} exceed the deadline {:?}",
+                remote, query_timeout
+            );
+            context.metrics.incr_servfail_timeout();
         }
-    )
+    }
+    let query = Message::parse_dns_message(&message)?;
+    Message::new_server_failure_message(&query).encode(from_udp)
 }
 
-/// process_message is the main dns process logic function
-/// implements the rfc1034 and used for udp and tcp listeners
-/// but not axfr and ixfr. if return err then just ignore the packet
-fn process_message(
-    mut storage: SafeRBTreeStorage,
-    message: &[u8],
-    remote: &SocketAddr,
+/// forward_target return the zone and its master when the raw message is an update of a
+/// secondary zone.
+fn forward_target(message: &[u8], context: &ServerContext) -> Option<(DNSName, Master)> {
+    let update = Message::parse_dns_message(message).ok()?;
+    if !update.is_query() || !matches!(update.header.op_code(), OpCode::Update) {
+        return None;
+    }
+    let (zone, _) = update.query_name_and_type().ok()?;
+    let master = context.masters.get(zone)?.first()?;
+    Some((zone.clone(), master.clone()))
+}
+
+/// forward_update relay the update of a secondary zone to its master over tcp and return
+/// the master response, the update is never applied locally. SERVFAIL is answered when
+/// the master can not be reached in time.
+async fn forward_update(
+    message: Vec<u8>,
+    (zone, master): (DNSName, Master),
+    remote: SocketAddr,
     from_udp: bool,
-    max_edns_size: u16,
+    context: &ServerContext,
 ) -> Result<Vec<u8>, DNSProtoErr> {
-    let parsed_message = Message::parse_dns_message(&message)?;
-    if !parsed_message.is_query() {
-        // no need to process just drop packet
-        return Err(DNSProtoErr::ValidQueryDomainErr);
-    }
-
-    let (dnsname, dnstype) = parsed_message.query_name_and_type()?;
-    report_query_message(dnsname, dnstype, remote, from_udp);
-    let (mut message, max_size, terminator) =
-        Message::new_message_from_query(&parsed_message, from_udp, max_edns_size);
-    if terminator {
-        return message.encode(from_udp);
-    }
-    // 1. find the best zone for this query
-    let best_zone = storage.find_best(dnsname);
-    // 2. if not found
-    //     2.1 because no subzone? then get the best zone name( using cut method )
-    //     2.2 because not in this zone? then return refused status
-    if best_zone.is_none() {
-        message.header.set_rcode(RCode::Refused);
-        return message.encode(from_udp);
-    }
-    // 3. find best zone for the qname (with wildcard, and zone cut info collection)
-    // if query is a CNAME， we need do some loop job
-    // 4. set dns header aa = true
-    message.header.set_aa(true);
-    // 5. get the zone reference
-    let best_zone = best_zone.unwrap();
-    // loop {}
-
-    // 6. search current domain in best zone and trace the zonecut if exist!
-    // 7. if zonecut exist :
-    //      7.1 set dns header aa = false
-    //      7.2 get the ns information put it to additional section
-    // 8. if not exist
-    //      8.1 then get the best zone's soa record
-    //      8.2 get the ttl soa minimum
-    //      8.3 set nxdomain
-    // 9. find the node
-    //      9.1 if CNAME exist then
-    //          9.1.1 if qtype is CNAME then return a cname record
-    //          9.1.2 if qtype is not CNAME then chase this query back to step 6:
-    //          9.1.3 when chase the CNAME if not belong to this zone we do not chase again
-    //          9.1.4 add any CNAME to the answer section
-    //      9.2 if query type exist
-    //          9.2.1 if query type is mx we also need to chase the mx record a/aaaa in the zone and
-    //                add to additional
-    //          9.2.2 add records to answer section
-    //      9.3. if query type not exist
-    //          9.3.1  find the soa record
-    //          9.3.2  put the soa record to authority section
-    // 10. add additional info
-
-    match storage.search_rrset(dnsname, dnstype) {
-        Ok(rrset) => {
-            let rrset = rrset.read().unwrap().to_records();
-            message.update_answer(rrset);
+    if !context.is_action_allowed(&zone, "update", &remote.ip(), &TsigState::Unsigned) {
+        debug!(
+            "refuse update {} from {}: not in update acl",
+            zone.to_string(),
+            remote.to_string()
+        );
+        context.metrics.incr_refused_by_acl();
+        let update = Message::parse_dns_message(&message)?;
+        return Message::new_refused_message(&update).encode(from_udp);
+    }
+    let option = QueryOption {
+        timeout: context.query_timeout,
+        tcp_only: true,
+        source: master.source,
+        ..Default::default()
+    };
+    match dnsclient::forward(master.addr, &message, &option).await {
+        Ok(response) => {
+            info!(
+                "forward update {} from {} to master {}",
+                zone.to_string(),
+                remote.to_string(),
+                master.addr
+            );
+            if from_udp {
+                return Ok(response);
+            }
+            let mut framed = (response.len() as u16).to_be_bytes().to_vec();
+            framed.extend_from_slice(&response);
+            Ok(framed)
         }
         Err(err) => {
-            match err {
-                // add soa ?
-                StorageError::DomainNotFoundError(_) => {
-                    debug!(
-                        "can't find record {} in zone database: {:?}",
-                        dnsname.to_string(),
-                        err,
-                    );
-                    message.set_nxdomain();
-                }
-                _ => {
-                    debug!(
-                        "can't find record {} in zone database: {:?}",
-                        dnsname.to_string(),
-                        err
-                    );
-                    message.set_serverfail();
-                }
-            }
+            warn!(
+                "forward update {} to master {} fail: {}",
+                zone.to_string(),
+                master.addr,
+                err
+            );
+            context.metrics.incr_servfail_forward();
+            let update = Message::parse_dns_message(&message)?;
+            Message::new_server_failure_message(&update).encode(from_udp)
         }
     }
-    // debug!(logger, "response message: {:?}", message);
-    let message_byte = message.encode(from_udp)?;
-    // when query from udp and message size great than max_size(maybe limit by edns size)
-    if from_udp && message_byte.len() > (max_size as usize) {
-        let tc_message = Message::new_tc_message_from_build_message(&mut message);
-        Ok(tc_message.encode(from_udp)?)
+}
+
+/// transfer_zone return the zone name when the raw message is an axfr query.
+fn transfer_zone(message: &[u8]) -> Option<DNSName> {
+    let query = Message::parse_dns_message(message).ok()?;
+    if !query.is_query() || !matches!(query.header.op_code(), OpCode::Query) {
+        return None;
+    }
+    match query.query_name_and_type() {
+        Ok((name, &DNSType::AXFR)) => Some(name.clone()),
+        _ => None,
+    }
+}
+
+/// serve_transfer stream the zone to the client, the transfer is refused when the zone is
+/// hidden, the client is not in the zone transfer acl or the concurrent transfer limits
+/// are reached.
+async fn serve_transfer(
+    stream: &mut TcpStream,
+    storage: SafeRBTreeStorage,
+    (message, zone): (Vec<u8>, DNSName),
+    remote: SocketAddr,
+    context: Arc<ServerContext>,
+) -> Result<(), DNSProtoErr> {
+    let permit = if !storage.zones().contains(&zone)
+        || storage.is_zone_hidden(&zone)
+        || !context.is_action_allowed(&zone, "transfer", &remote.ip(), &TsigState::Unsigned)
+    {
+        debug!(
+            "refuse transfer {} from {}: not allowed",
+            zone.to_string(),
+            remote.to_string()
+        );
+        None
     } else {
-        Ok(message_byte)
+        let permit = context.transfers.try_acquire(remote.ip());
+        if permit.is_none() {
+            warn!(
+                "refuse transfer {} from {}: too many transfers",
+                zone.to_string(),
+                remote.to_string()
+            );
+            context.metrics.incr_transfers_denied();
+        }
+        permit
+    };
+    let permit = match permit {
+        Some(permit) => permit,
+        None => {
+            let response = Message::parse_dns_message(&message)
+                .and_then(|query| Message::new_refused_message(&query).encode(false))?;
+            stream.write_all(&response).await?;
+            return Ok(());
+        }
+    };
+    info!(
+        "start transfer {} to {}",
+        zone.to_string(),
+        remote.to_string()
+    );
+    context.metrics.incr_transfers_active();
+    let format = context
+        .transfer_formats
+        .get(&zone)
+        .copied()
+        .unwrap_or_default();
+    let messages = tokio::task::spawn_blocking(move || {
+        let query = Message::parse_dns_message(&message)?;
+        let records = storage
+            .transfer_records(&zone)
+            .map_err(|err| DNSProtoErr::GeneralErr(err.to_string()))?;
+        transfer_messages(&query, records, format)
+    })
+    .await
+    .map_err(|err| DNSProtoErr::GeneralErr(err.to_string()));
+    let result = match messages {
+        Ok(Ok(messages)) => write_paced(stream, &messages, context.transfers.rate_limit())
+            .await
+            .map_err(DNSProtoErr::from),
+        Ok(Err(err)) | Err(err) => Err(err),
+    };
+    context.metrics.decr_transfers_active();
+    drop(permit);
+    result
+}
+
+/// handle_tcp_connection answer the query of the tcp connection, axfr queries are streamed
+/// by serve_transfer and the others are answered by process_message.
+async fn handle_tcp_connection(
+    mut stream: TcpStream,
+    remote_addr: SocketAddr,
+    storage: SafeRBTreeStorage,
+    context: Arc<ServerContext>,
+) {
+    let mut packet_length = [0u8; 2];
+    // a slow client should not hold the connection longer than a query
+    match timeout(context.query_timeout, stream.read_exact(&mut packet_length)).await {
+        Ok(Ok(2)) => {}
+        _ => return,
+    }
+    let next_size = ((packet_length[0] as u16) << 8) + (packet_length[1] as u16);
+    let mut message: Vec<u8> = vec![0u8; next_size as usize];
+    match timeout(context.query_timeout, stream.read_exact(&mut message)).await {
+        Ok(Ok(vsize)) => message.truncate(vsize),
+        Ok(Err(err)) => {
+            error!("process message fail: {:?}", err);
+            return;
+        }
+        Err(_) => {
+            debug!("read tcp message from {} timeout", remote_addr);
+            return;
+        }
+    }
+    if let Some(zone) = transfer_zone(&message) {
+        if let Err(err) =
+            serve_transfer(&mut stream, storage, (message, zone), remote_addr, context).await
+        {
+            error!("transfer to {} fail: {:?}", remote_addr, err);
+        }
+        return;
+    }
+    match process_message_with_deadline(storage, message, remote_addr, false, context).await {
+        Ok(message) => {
+            if let Err(err) = stream.write_all(message.as_slice()).await {
+                error!("{:?}", err)
+            };
+        }
+        Err(err) => error!("serialize message fail: {:?}", err),
+    }
+}
+
+/// bind_tcp_listener create tcp listener, ipv6 listener only accept ipv6 connections
+/// so that both [::]:53 and 0.0.0.0:53 can be listened at the same time.
+fn bind_tcp_listener(addr: &SocketAddr) -> std::io::Result<TcpListener> {
+    let builder = if addr.is_ipv4() {
+        net2::TcpBuilder::new_v4()?
+    } else {
+        let builder = net2::TcpBuilder::new_v6()?;
+        builder.only_v6(true)?;
+        builder
+    };
+    let listener = builder.reuse_address(true)?.bind(addr)?.listen(1024)?;
+    listener.set_nonblocking(true)?;
+    TcpListener::from_std(listener)
+}
+
+/// bind_udp_socket create udp socket with reuse_port, so multi workers can share one address.
+fn bind_udp_socket(addr: &SocketAddr) -> std::io::Result<UdpSocket> {
+    let builder = if addr.is_ipv4() {
+        net2::UdpBuilder::new_v4()?
+    } else {
+        let builder = net2::UdpBuilder::new_v6()?;
+        builder.only_v6(true)?;
+        builder
+    };
+    let socket = builder.reuse_port(true)?.bind(addr)?;
+    socket.set_nonblocking(true)?;
+    UdpSocket::from_std(socket)
+}
+
+/// watchdog answer a soa self query of the zone through process_message periodically,
+/// the server state is kept alive as long as the query is answered.
+async fn watchdog(
+    storage: SafeRBTreeStorage,
+    zone: Option<String>,
+    context: Arc<ServerContext>,
+    state: Arc<ServerState>,
+) -> TokioResult<()> {
+    // the message is not Send, only the encoded query is kept across the await points
+    let query = {
+        let mut message = Message::new_with_header(Header::new());
+        let domain = fqdn(zone.as_deref().unwrap_or("."));
+        message.set_question(Question::new(&domain, DNSType::SOA, DNSClass::IN)?);
+        message.encode(true)?
+    };
+    let remote: SocketAddr = "127.0.0.1:0".parse()?;
+    let mut interval = tokio::time::interval(std::time::Duration::from_secs(WATCHDOG_INTERVAL));
+    loop {
+        interval.tick().await;
+        match process_message(storage.clone(), &query, &remote, true, &context) {
+            Ok(_) => state.heartbeat(),
+            Err(err) => error!("watchdog self query fail: {:?}", err),
+        }
+    }
+}
+
+/// systemd_watchdog send WATCHDOG=1 to systemd while the watchdog self query is answered,
+/// systemd restarts the server once the pings stop for WatchdogSec.
+#[cfg(all(target_os = "linux", feature = "systemd"))]
+async fn systemd_watchdog(state: Arc<ServerState>, interval: Duration) -> TokioResult<()> {
+    let mut interval = tokio::time::interval(interval);
+    loop {
+        interval.tick().await;
+        if state.is_alive() {
+            systemd::notify("WATCHDOG=1");
+        }
+    }
+}
+
+/// self_query send the query through process_message from loopback address, the response
+/// must be an authoritative answer without error.
+fn self_query(
+    storage: &SafeRBTreeStorage,
+    apex: &DNSName,
+    dtype: DNSType,
+    context: &ServerContext,
+) -> Result<Message, String> {
+    let mut message = Message::new_with_header(Header::new());
+    let question =
+        Question::new(&apex.to_string(), dtype, DNSClass::IN).map_err(|err| err.to_string())?;
+    message.set_question(question);
+    let query = message.encode(true).map_err(|err| err.to_string())?;
+    let remote = SocketAddr::from(([127, 0, 0, 1], 53));
+    let response = process_message(storage.clone(), &query, &remote, true, context)
+        .and_then(|response| Message::parse_dns_message(&response))
+        .map_err(|err| format!("{} query error: {}", dtype, err))?;
+    if response.header.rcode() != RCode::NoError || !response.header.aa() {
+        return Err(format!(
+            "{} query return {:?} aa={}",
+            dtype,
+            response.header.rcode(),
+            response.header.aa()
+        ));
+    }
+    Ok(response)
+}
+
+/// self_test_zone check the apex soa and ns answers match the zone data.
+fn self_test_zone(
+    storage: &SafeRBTreeStorage,
+    apex: &DNSName,
+    context: &ServerContext,
+) -> Result<(), String> {
+    if storage.find_zone(apex).as_ref() != Some(apex) {
+        return Err("zone is not loaded at the apex".to_owned());
+    }
+    let soa = storage.get_zone_soa(apex).map_err(|err| err.to_string())?;
+    let response = self_query(storage, apex, DNSType::SOA, context)?;
+    let answered = response
+        .answers_of_type::<DnsTypeSOA>()
+        .next()
+        .map(|(_, _, soa)| soa.clone());
+    if answered.as_ref() != Some(&soa) {
+        return Err(format!(
+            "soa answer {:?} not match zone soa {}",
+            answered, soa
+        ));
+    }
+
+    let node = storage.find(apex).map_err(|err| err.to_string())?;
+    let rrset = node
+        .read()
+        .unwrap()
+        .find_rrset(DNSType::NS)
+        .map_err(|err| err.to_string())?;
+    let mut expected: Vec<String> = rrset
+        .read()
+        .unwrap()
+        .content()
+        .iter()
+        .filter_map(|rr| rr.get_data().as_ref().map(|data| data.to_string()))
+        .collect();
+    let response = self_query(storage, apex, DNSType::NS, context)?;
+    let mut answered: Vec<String> = response
+        .answers()
+        .iter()
+        .filter(|answer| answer.get_type() == DNSType::NS)
+        .filter_map(|answer| answer.get_data().as_ref().map(|data| data.to_string()))
+        .collect();
+    expected.sort();
+    answered.sort();
+    if answered != expected {
+        return Err(format!(
+            "ns answer {:?} not match zone ns {:?}",
+            answered, expected
+        ));
+    }
+    Ok(())
+}
+
+/// zone_load_option return the origin and load options of the zone setting.
+fn zone_load_option(zone: &ZoneSetting) -> Result<(Option<String>, ZoneOption), OtterError> {
+    let mut orginal: Option<String> = None;
+    if !zone.domain.is_empty() {
+        match DNSName::new(fqdn(&zone.domain).as_str(), None) {
+            Ok(name) => {
+                orginal = Some(name.to_string());
+            }
+            Err(err) => {
+                return Err(OtterError::DNSProtoError(err));
+            }
+        }
+    }
+    let option = ZoneOption {
+        min_ttl: zone.min_ttl,
+        max_ttl: zone.max_ttl,
+        default_ttl: zone.default_ttl,
+        serial_policy: zone.serial_policy,
+        max_records: zone.max_records,
+        max_memory: zone.max_memory,
+        verify_zonemd: zone.verify_zonemd,
+        spf_to_txt: zone.spf_to_txt,
+        check_names: zone.check_names,
+        max_label_depth: zone.max_label_depth,
+        verify_dnssec: zone.verify_dnssec,
+        verify_dnssec_strict: zone.verify_dnssec_strict,
+        verify_dnssec_sample: zone.verify_dnssec_sample,
+    };
+    Ok((orginal, option))
+}
+
+/// SecondaryZone holds the masters and the options used to refresh a secondary zone, the
+/// transferred zone is written to the storage file.
+#[derive(Debug, Clone)]
+struct SecondaryZone {
+    masters: MasterList,
+    limits: TransferLimits,
+    option: ZoneOption,
+    storage_file: String,
+}
+
+/// secondary_zones return the configured zones with at least one valid master.
+fn secondary_zones(
+    setting: &Settings,
+    context: &ServerContext,
+) -> Result<HashMap<DNSName, SecondaryZone>, OtterError> {
+    let mut zones = HashMap::new();
+    for zone in setting.zone.iter() {
+        let (apex, option) = match zone_load_option(zone)? {
+            (Some(origin), option) => (DNSName::new(&origin, None)?, option),
+            (None, _) => continue,
+        };
+        if let Some(masters) = context.masters.get(&apex) {
+            let limits = TransferLimits {
+                max_records: zone.max_transfer_records,
+                max_bytes: zone.max_transfer_bytes,
+            };
+            let mut masters = MasterList::new(masters.clone());
+            // the master which succeeded before the restart is tried first
+            if let Some(master) = context.scheduler.zone_state(&apex).and_then(|s| s.master) {
+                masters.prefer(&master);
+            }
+            zones.insert(
+                apex,
+                SecondaryZone {
+                    masters,
+                    limits,
+                    option,
+                    storage_file: zone.storage_file(),
+                },
+            );
+        }
+    }
+    Ok(zones)
+}
+
+/// refresh_zone refresh the secondary zone from its masters, the master which last
+/// succeeded is tried first and the others follow in the configured order. a failed
+/// master is skipped until its backoff lapses, the error of the last tried master is
+/// returned when all of them failed, and nothing is done when all masters are in backoff.
+/// the zone state is written next to the storage file after each refresh.
+async fn refresh_zone(
+    storage: SafeRBTreeStorage,
+    zone: &DNSName,
+    secondary: &mut SecondaryZone,
+    context: &ServerContext,
+) -> Result<(), OtterError> {
+    let candidates = secondary.masters.candidates(context.scheduler.now());
+    if candidates.is_empty() {
+        return Ok(());
+    }
+    let mut result = Ok(());
+    for index in candidates {
+        let master = match secondary.masters.get(index) {
+            Some(master) => master.clone(),
+            None => continue,
+        };
+        result = refresh_from_master(storage.clone(), zone, &master, secondary, context).await;
+        match &result {
+            Ok(()) => {
+                context.metrics.incr_master_success(&master.id);
+                context.scheduler.set_master(zone, &master.id);
+                secondary.masters.mark_success(index);
+                break;
+            }
+            Err(err) => {
+                warn!(
+                    "refresh zone {} from master {}({}) fail: {}",
+                    zone.to_string(),
+                    master.id,
+                    master.addr,
+                    err
+                );
+                context.metrics.incr_master_failure(&master.id);
+                context.scheduler.mark_failed(zone);
+                secondary
+                    .masters
+                    .mark_failure(index, context.scheduler.now());
+            }
+        }
+    }
+    context.scheduler.save_state(zone, &secondary.storage_file);
+    result
+}
+
+/// refresh_from_master check the master serial of the secondary zone and transfer the zone
+/// when the master has a newer serial or the zone has no local data. the served zone is
+/// replaced only when the transfer pass all checks, rejected transfers are counted by
+/// reason. the transferred zone is written to the storage file for the next startup.
+async fn refresh_from_master(
+    mut storage: SafeRBTreeStorage,
+    zone: &DNSName,
+    master: &Master,
+    secondary: &SecondaryZone,
+    context: &ServerContext,
+) -> Result<(), OtterError> {
+    let option = QueryOption {
+        timeout: context.query_timeout,
+        source: master.source,
+        ..Default::default()
+    };
+    if storage.find_zone(zone).as_ref() == Some(zone) {
+        let soa = query_soa_tcp(master.addr, zone, &option).await?;
+        if !context.scheduler.check_serial(zone, &soa) {
+            return Ok(());
+        }
+    }
+    let messages = match transfer_in(master.addr, zone, &option, secondary.limits).await {
+        Err(OtterError::TransferError(err)) => {
+            context.metrics.incr_transfer_rejected(&err);
+            return Err(err.into());
+        }
+        result => result?,
+    };
+    let records = transfer_records(&messages)?;
+    storage.replace_zone(records, &secondary.option)?;
+    let soa = storage.get_zone_soa(zone)?;
+    info!(
+        "transfer zone {} serial {} from master {}({}) success",
+        zone.to_string(),
+        soa.serial(),
+        master.id,
+        master.addr
+    );
+    context.scheduler.mark_refreshed(zone, &soa);
+    let path = secondary.storage_file.as_str();
+    if let Err(err) = write_zone_file(&storage, zone, path, SystemClock.now()) {
+        warn!("write zone {} to {} fail: {}", zone.to_string(), path, err);
+    }
+    Ok(())
+}
+
+/// refresh_secondaries check all secondary zones at startup to confirm the loaded copies are
+/// fresh, then refresh the zones which refresh timer lapsed periodically.
+async fn refresh_secondaries(
+    storage: SafeRBTreeStorage,
+    mut zones: HashMap<DNSName, SecondaryZone>,
+    context: Arc<ServerContext>,
+) -> TokioResult<()> {
+    let mut interval = tokio::time::interval(Duration::from_secs(REFRESH_CHECK_INTERVAL));
+    let mut startup: Option<Vec<DNSName>> = Some(zones.keys().cloned().collect());
+    loop {
+        interval.tick().await;
+        let pending = startup
+            .take()
+            .unwrap_or_else(|| context.scheduler.zones_need_refresh());
+        for zone in pending {
+            let secondary = match zones.get_mut(&zone) {
+                Some(secondary) => secondary,
+                None => continue,
+            };
+            if let Err(err) = refresh_zone(storage.clone(), &zone, secondary, &context).await {
+                warn!("refresh zone {} fail: {}", zone.to_string(), err);
+            }
+        }
     }
 }
 
@@ -142,109 +637,94 @@ pub struct OtterServer {
     tcp_servers: Arc<Vec<TCPServer>>,
     storage: SafeRBTreeStorage,
     setting: Settings,
+    scheduler: Arc<TransferScheduler>,
+    catalog: Arc<ZoneCatalog>,
+    state: Arc<ServerState>,
     threads: Vec<JoinHandle<TokioResult<()>>>,
+    // the storage is built by the caller, the zone files are not loaded
+    preloaded: bool,
 }
 
 impl OtterServer {
     // bind addr must be string like: 127.0.0.1:53 192.168.0.1:53
     pub fn new(setting: Settings) -> OtterServer {
         // TODO: config file to logger
+        let scheduler = Arc::new(TransferScheduler::default());
+        let connections = ConnectionLimiter::new(
+            setting
+                .server
+                .max_tcp_clients
+                .unwrap_or(DEFAULT_MAX_TCP_CLIENTS),
+            setting
+                .server
+                .max_tcp_clients_per_ip
+                .unwrap_or(DEFAULT_MAX_TCP_CLIENTS_PER_IP),
+            setting
+                .server
+                .max_udp_in_flight
+                .unwrap_or(DEFAULT_MAX_UDP_IN_FLIGHT),
+        );
+        let state = Arc::new(
+            ServerState::new(scheduler.clone(), Arc::new(SystemClock))
+                .with_connections(connections),
+        );
         OtterServer {
             udp_servers: Arc::new(vec![]),
             tcp_servers: Arc::new(vec![]),
             storage: SafeRBTreeStorage::default(),
             setting,
+            scheduler,
+            catalog: Arc::new(ZoneCatalog::default()),
+            state,
             threads: vec![],
+            preloaded: false,
+        }
+    }
+
+    /// with_storage create the server which serve the pre-built storage, for example the one
+    /// built by storage::builder::ZoneBuilder, instead of loading the zone files of the setting.
+    pub fn with_storage(setting: Settings, storage: SafeRBTreeStorage) -> OtterServer {
+        OtterServer {
+            storage,
+            preloaded: true,
+            ..OtterServer::new(setting)
         }
     }
     // setup after storage is ready
-    pub async fn init_network(&mut self, extension: &ExSetting) -> Result<(), NetworkError> {
-        let (tcp_listeners, udp_listeners) = self.setting.get_listeners();
+    pub async fn init_network(&mut self, extension: &ExSetting) -> Result<(), OtterError> {
+        #[cfg(all(target_os = "linux", feature = "systemd"))]
+        {
+            let listen_fds =
+                systemd::listen_fds().map_err(|err| NetworkError::IOError(err.to_string()))?;
+            if let Some(listen_fds) = listen_fds {
+                return Ok(self.adopt_listen_fds(listen_fds)?);
+            }
+        }
+        let (tcp_listeners, udp_listeners) = self
+            .setting
+            .get_listeners()
+            .map_err(|err| NetworkError::ListenAddrError(err.to_string()))?;
         let mut tcp_servers = vec![];
-
-        // for tcp_addr in tcp_listeners.iter() {
-        //     info!("start listen tcp connection at: {}", tcp_addr);
-        //     for _ in 0..extension.tcp_workers {
-        //         let tcp_addr = tcp_addr.parse::<SocketAddr>()?;
-        //         // let tcp_server = TcpListener::bind(tcp_addr).await?;
-        //         // let tcp_socket = net2::TcpBuilder ::reuse_port(true).unwrap();
-        //         let tcp_socket = if tcp_addr.is_ipv4() {
-        //             net2::TcpBuilder::new_v4()
-        //                 .unwrap()
-        //                 .reuse_port(true)
-        //                 .unwrap()
-        //                 .bind(tcp_addr)
-        //                 .unwrap()
-        //                 .to_tcp_listener()
-        //                 .unwrap()
-        //         } else {
-        //             net2::TcpBuilder::new_v6()
-        //                 .unwrap()
-        //                 .reuse_port(true)
-        //                 .unwrap()
-        //                 .bind(tcp_addr)
-        //                 .unwrap()
-        //                 .to_tcp_listener()
-        //                 .unwrap()
-        //         };
-        //         let tcp_server = TcpListener::from_std(tcp_socket).unwrap();
-        //         tcp_servers.push(TCPServer::new(tcp_server));
-        //     }
-        // }
-
         for tcp_addr in tcp_listeners.iter() {
             info!("start listen tcp connection at: {}", tcp_addr);
-            // for _ in 0..extension.tcp_workers {
-            let tcp_addr = tcp_addr.parse::<SocketAddr>()?;
-            let tcp_server = TcpListener::bind(tcp_addr).await?;
-            // let tcp_server = TcpListener::bind(tcp_addr).await?;
-            // let tcp_socket = net2::TcpBuilder ::reuse_port(true).unwrap();
-            // let tcp_socket = if tcp_addr.is_ipv4() {
-            //     net2::TcpBuilder::new_v4()
-            //         .unwrap()
-            //         .reuse_port(true)
-            //         .unwrap()
-            //         .bind(tcp_addr)
-            //         .unwrap()
-            //         .to_tcp_listener()
-            //         .unwrap()
-            // } else {
-            //     net2::TcpBuilder::new_v6()
-            //         .unwrap()
-            //         .reuse_port(true)
-            //         .unwrap()
-            //         .bind(tcp_addr)
-            //         .unwrap()
-            //         .to_tcp_listener()
-            //         .unwrap()
-            // };
-            // let tcp_server = TcpListener::from_std(tcp_socket).unwrap();
+            let tcp_server = bind_tcp_listener(tcp_addr)
+                .map_err(|err| NetworkError::BindError("tcp".to_owned(), err.to_string()))
+                .with_peer(tcp_addr)?;
             tcp_servers.push(TCPServer::new(tcp_server));
-            // }
         }
 
+        #[cfg(unix)]
+        let udp_listeners = match self.setting.server.expand_wildcard_listen {
+            true => interface::expand_wildcard_listen(&udp_listeners),
+            false => udp_listeners,
+        };
         let mut udp_servers = vec![];
-
         for udp_addr in udp_listeners.iter() {
             info!("start listen udp connection at: {}", udp_addr);
             for _ in 0..extension.udp_workers {
-                let udp_socket_addr = udp_addr.parse::<SocketAddr>()?;
-                let udp_socket = if udp_socket_addr.is_ipv4() {
-                    net2::UdpBuilder::new_v4()
-                        .unwrap()
-                        .reuse_port(true)
-                        .unwrap()
-                        .bind(udp_socket_addr)
-                        .unwrap()
-                } else {
-                    net2::UdpBuilder::new_v6()
-                        .unwrap()
-                        .reuse_port(true)
-                        .unwrap()
-                        .bind(udp_socket_addr)
-                        .unwrap()
-                };
-                let udp_socket = UdpSocket::from_std(udp_socket).unwrap();
+                let udp_socket = bind_udp_socket(udp_addr)
+                    .map_err(|err| NetworkError::BindError("udp".to_owned(), err.to_string()))
+                    .with_peer(udp_addr)?;
                 udp_servers.push(UdpServer::new(udp_socket));
             }
         }
@@ -253,35 +733,274 @@ impl OtterServer {
         Ok(())
     }
 
+    /// adopt_listen_fds serve the sockets passed by systemd socket activation instead of
+    /// the configured listen addresses.
+    #[cfg(all(target_os = "linux", feature = "systemd"))]
+    fn adopt_listen_fds(&mut self, listen_fds: systemd::ListenFds) -> Result<(), NetworkError> {
+        let io_error = |err: std::io::Error| NetworkError::IOError(err.to_string());
+        let mut tcp_servers = vec![];
+        for listener in listen_fds.tcp {
+            info!("adopt tcp listener at: {:?}", listener.local_addr());
+            listener.set_nonblocking(true).map_err(io_error)?;
+            tcp_servers.push(TCPServer::new(
+                TcpListener::from_std(listener).map_err(io_error)?,
+            ));
+        }
+        let mut udp_servers = vec![];
+        for socket in listen_fds.udp {
+            info!("adopt udp socket at: {:?}", socket.local_addr());
+            socket.set_nonblocking(true).map_err(io_error)?;
+            udp_servers.push(UdpServer::new(
+                UdpSocket::from_std(socket).map_err(io_error)?,
+            ));
+        }
+        self.tcp_servers = Arc::new(tcp_servers);
+        self.udp_servers = Arc::new(udp_servers);
+        Ok(())
+    }
+
+    /// check load the configured zones and bind all listeners without serving, the
+    /// listeners are closed when the server is dropped.
+    pub async fn check(&mut self, extension: &ExSetting) -> Result<(), OtterError> {
+        self.init_load_storage()?;
+        ServerContext::new(&self.setting)?;
+        self.init_network(extension).await?;
+        Ok(())
+    }
+
+    /// local_addrs return the bound (tcp, udp) addresses, the port is the real one when the
+    /// listen address use port 0.
+    pub fn local_addrs(&self) -> (Vec<SocketAddr>, Vec<SocketAddr>) {
+        let tcp = self
+            .tcp_servers
+            .iter()
+            .filter_map(|server| server.tcp_listener.local_addr().ok())
+            .collect();
+        let mut udp: Vec<SocketAddr> = self
+            .udp_servers
+            .iter()
+            .filter_map(|server| server.udp_socket.local_addr().ok())
+            .collect();
+        udp.dedup();
+        (tcp, udp)
+    }
+
     fn init_load_storage(&mut self) -> Result<(), OtterError> {
-        let zone_file_list = self.setting.get_zone_file_list();
-        for (file, domain) in &zone_file_list {
-            let mut orginal: Option<String> = None;
-            if !domain.is_empty() {
-                match DNSName::new(domain, None) {
-                    Ok(name) => {
-                        orginal = Some(name.to_string());
-                    }
-                    Err(err) => {
-                        return Err(OtterError::DNSProtoError(err));
-                    }
+        // the flags are kept by name, a secondary zone is hidden before its first transfer
+        for zone in self.setting.zone.iter().filter(|zone| zone.hidden) {
+            let apex = DNSName::new(fqdn(&zone.domain).as_str(), None).with_zone(&zone.domain)?;
+            self.storage.set_zone_hidden(&apex, true);
+        }
+        if self.preloaded {
+            info!("serve the pre-built storage, skip loading zone files");
+            self.state.set_zones_loaded(true);
+            return Ok(());
+        }
+        for zone in self.setting.zone.iter() {
+            let (orginal, option) = zone_load_option(zone).with_zone(&zone.domain)?;
+            if zone.master.is_some() {
+                // a missing or corrupted local copy is transferred from the master later
+                if !load_secondary(&mut self.storage, &self.scheduler, zone, &option) {
+                    continue;
                 }
+                if let (true, Some(origin)) = (zone.catalog, orginal) {
+                    let apex = DNSName::new(&origin, None)?;
+                    self.apply_catalog(&apex, zone.master.clone())?;
+                }
+                info!("load secondary zone: {} success", zone.storage_file());
+                continue;
+            }
+            let file = zone.file.clone();
+            let mut load_option = LoadOption::new(option).with_progress(
+                LOAD_PROGRESS_INTERVAL,
+                |progress: &LoadProgress| {
+                    info!(
+                        "load zone file: {} records: {} estimated memory: {} bytes",
+                        file, progress.records, progress.memory
+                    )
+                },
+            );
+            let start_point = self
+                .storage
+                .update_zone_with_progress(&zone.file, orginal, &mut load_option)
+                .with_zone(&zone.domain)?;
+            if zone.catalog {
+                let apex = start_point.read().unwrap().get_name();
+                self.apply_catalog(&apex, zone.master.clone())?;
             }
-            self.storage.update_zone(file, orginal)?;
-            info!("{}", format!("load zone file: {} success", file));
+            info!("{}", format!("load zone file: {} success", zone.file));
         }
         info!("load all zone files success");
+        self.state.set_zones_loaded(true);
+        Ok(())
+    }
+
+    /// reload_zones reload all configured zone files, the serial policy of the zone
+    /// decide what to do if the serial is not increased.
+    /// the server is not ready after a failed reload in strict mode.
+    pub fn reload_zones(&mut self) -> Result<(), OtterError> {
+        let result = self.reload_all_zones();
+        if self.setting.server.strict {
+            self.state.set_reload_failed(result.is_err());
+        }
+        result
+    }
+
+    fn reload_all_zones(&mut self) -> Result<(), OtterError> {
+        for zone in self.setting.zone.iter() {
+            let (orginal, option) = zone_load_option(zone).with_zone(&zone.domain)?;
+            let start_point = self
+                .storage
+                .reload_zone(&zone.file, orginal, &option)
+                .with_zone(&zone.domain)?;
+            if zone.catalog {
+                let apex = start_point.read().unwrap().get_name();
+                self.apply_catalog(&apex, zone.master.clone())?;
+            }
+            info!("reload zone file: {} success", zone.file);
+        }
+        Ok(())
+    }
+
+    /// startup_self_test query the apex soa and ns of each configured zone through
+    /// process_message, and compare the answers with the loaded zone data.
+    fn startup_self_test(&self, context: &ServerContext) -> Result<(), OtterError> {
+        let mode = self.setting.server.startup_selftest;
+        if mode == SelfTestMode::Off {
+            return Ok(());
+        }
+        let mut failures = vec![];
+        for zone in self.setting.zone.iter() {
+            let apex = match zone_load_option(zone)? {
+                (Some(origin), _) => DNSName::new(&origin, None)?,
+                (None, _) => continue,
+            };
+            if let Err(err) = self_test_zone(&self.storage, &apex, context) {
+                error!("self test of zone {} failed: {}", apex.to_string(), err);
+                failures.push(format!("{}: {}", apex.to_string(), err));
+            }
+        }
+        if mode == SelfTestMode::Strict && !failures.is_empty() {
+            return Err(OtterError::SelfTestError(failures.join("; ")));
+        }
+        Ok(())
+    }
+
+    /// apply_catalog provision the member zones of the catalog zone as secondaries of
+    /// the catalog master, unchanged member zones keep their transfer state.
+    fn apply_catalog(&self, catalog: &DNSName, master: Option<Masters>) -> Result<(), OtterError> {
+        let members = catalog_members(&self.storage, catalog)?;
+        let change = self.catalog.update(catalog, master, members);
+        for zone in change.removed.iter() {
+            self.scheduler.unregister(zone);
+            info!(
+                "catalog {} remove member zone {}",
+                catalog.to_string(),
+                zone.to_string()
+            );
+        }
+        for zone in change.added.iter() {
+            info!(
+                "catalog {} add member zone {}",
+                catalog.to_string(),
+                zone.to_string()
+            );
+        }
         Ok(())
     }
     pub async fn run(&mut self, extension: &ExSetting) -> Result<(), OtterError> {
         self.init_load_storage()?;
-        if let Err(err) = self.init_network(extension).await {
-            return Err(OtterError::NetworkError(err));
+        let mut context = ServerContext::new(&self.setting)?;
+        logging::set_query_log_sample(self.setting.server.query_log_sample.unwrap_or(1));
+        context.scheduler = self.scheduler.clone();
+        context.stats = self.state.stats();
+        context.metrics = self.state.metrics();
+        context.connections = self.state.connections();
+        if let Some(log) = query_log_setting(&self.setting.log) {
+            let query_log = QueryLog::open(log)
+                .map_err(|err| OtterError::QueryLogError(log.target.clone(), err.to_string()))?;
+            info!("write query log to {}", log.target);
+            context.query_log = Some(Arc::new(query_log));
+        }
+        #[cfg(unix)]
+        if context.query_log.is_none() {
+            if let Some(log) = query_syslog_setting(&self.setting.log) {
+                let query_log = QueryLog::open_syslog(log).map_err(|err| {
+                    OtterError::QueryLogError(log.target.clone(), err.to_string())
+                })?;
+                info!("send query log to syslog");
+                context.query_log = Some(Arc::new(query_log));
+            }
+        }
+        // the secondary zones not loaded yet are hidden as well
+        for zone in self.storage.hidden_zones().iter() {
+            context.stats.hide_zone(zone);
+        }
+        for zone in self.storage.zones().iter() {
+            context.stats.add_zone(zone);
+        }
+        self.startup_self_test(&context)?;
+        // listeners bound by init_network before run are kept
+        if self.udp_servers.is_empty() && self.tcp_servers.is_empty() {
+            self.init_network(extension).await?;
+        }
+        #[cfg(unix)]
+        {
+            privilege::drop_privilege(&self.setting.server)?;
+            privilege::check_file_access(&self.setting)?;
         }
         let udp_server_number = self.udp_servers.len();
-        let max_edns_size = self.setting.server.max_edns_size;
+        if let Some(cache) = context.cache.clone() {
+            self.storage
+                .add_zone_change_hook(move |apex| cache.invalidate_zone(apex));
+        }
+        let context = Arc::new(context);
+        if let Some(http_listen) = &self.setting.server.http_listen {
+            let addr: SocketAddr = http_listen.parse().map_err(NetworkError::from)?;
+            info!("start listen health check http at: {}", addr);
+            let listener = TcpListener::bind(addr)
+                .await
+                .map_err(|err| NetworkError::BindError("http".to_owned(), err.to_string()))
+                .with_peer(addr)?;
+            let (state, storage) = (self.state.clone(), self.storage.clone());
+            self.threads.push(tokio::spawn(async move {
+                serve_health(listener, state, storage).await;
+                Ok(())
+            }));
+        }
+        let window = self
+            .setting
+            .server
+            .signature_expire_window
+            .unwrap_or(DEFAULT_SIGNATURE_WINDOW);
+        let zone_changed = Arc::new(tokio::sync::Notify::new());
+        {
+            let zone_changed = zone_changed.clone();
+            self.storage
+                .add_zone_change_hook(move |_| zone_changed.notify_one());
+        }
+        let (storage, metrics) = (self.storage.clone(), context.metrics.clone());
+        self.threads.push(tokio::spawn(async move {
+            signature_monitor(storage, metrics, window, zone_changed).await;
+            Ok(())
+        }));
+        let secondaries = secondary_zones(&self.setting, &context)?;
+        if !secondaries.is_empty() {
+            self.threads.push(tokio::spawn(refresh_secondaries(
+                self.storage.clone(),
+                secondaries,
+                context.clone(),
+            )));
+        }
+        self.threads.push(tokio::spawn(watchdog(
+            self.storage.clone(),
+            self.setting.zone.first().map(|zone| zone.domain.clone()),
+            context.clone(),
+            self.state.clone(),
+        )));
         for index in 0..udp_server_number {
             let storage = self.storage.clone();
+            let context = context.clone();
             let servers_clone = self.udp_servers.clone();
             self.threads.push(tokio::spawn(async move {
                 loop {
@@ -293,29 +1012,46 @@ impl OtterServer {
                         .await
                     {
                         Ok((vsize, connected_peer)) => {
-                            let message = &message[0..vsize];
-                            match process_message(
-                                storage,
-                                &message,
-                                &connected_peer,
-                                true,
-                                max_edns_size,
-                            ) {
-                                Ok(message) => {
-                                    if let Err(err) = servers_clone[index]
-                                        .udp_socket
-                                        .send_to(message.as_slice(), &connected_peer)
-                                        .await
-                                    {
-                                        error!("send dns message back to client error: {}", err);
-                                    }
+                            // shed the load instead of queueing the query behind the
+                            // ones in process
+                            let permit = match context.connections.try_begin_udp() {
+                                Some(permit) => permit,
+                                None => {
+                                    debug!(
+                                        "drop udp query from {}: too many queries in flight",
+                                        connected_peer
+                                    );
                                     continue;
                                 }
-                                Err(err) => {
-                                    println!("serilize message fail: {:?}", err);
-                                    continue;
+                            };
+                            let (servers, context) = (servers_clone.clone(), context.clone());
+                            let message = message[0..vsize].to_vec();
+                            tokio::spawn(async move {
+                                let _permit = permit;
+                                match process_message_with_deadline(
+                                    storage,
+                                    message,
+                                    connected_peer,
+                                    true,
+                                    context,
+                                )
+                                .await
+                                {
+                                    Ok(message) => {
+                                        if let Err(err) = servers[index]
+                                            .udp_socket
+                                            .send_to(message.as_slice(), &connected_peer)
+                                            .await
+                                        {
+                                            error!(
+                                                "send dns message back to client error: {}",
+                                                err
+                                            );
+                                        }
+                                    }
+                                    Err(err) => println!("serilize message fail: {:?}", err),
                                 }
-                            }
+                            });
                         }
                         Err(err) => {
                             println!("process message fail: {:?}", err);
@@ -328,58 +1064,41 @@ impl OtterServer {
         let tcp_server_number = self.tcp_servers.len();
         for index in 0..tcp_server_number {
             let storage = self.storage.clone();
+            let context = context.clone();
             let servers_clone = self.tcp_servers.clone();
             self.threads.push(tokio::spawn(async move {
                 loop {
-                    let storage = storage.clone();
-                    if let Ok((mut stream, remote_addr)) =
+                    if let Ok((stream, remote_addr)) =
                         servers_clone[index].tcp_listener.accept().await
                     {
-                        let mut packet_length = [0u8; 2];
-                        let next_size: u16;
-                        if let Ok(v) = stream.read_exact(&mut packet_length).await {
-                            if v != 2 {
-                                continue;
-                            }
-                            next_size =
-                                ((packet_length[0] as u16) << 8) + (packet_length[1] as u16);
-                        } else {
-                            continue;
-                        }
-                        let mut message: Vec<u8> = vec![0u8; next_size as usize];
-                        match stream.read_exact(message.as_mut_slice()).await {
-                            Ok(vsize) => {
-                                let message = &message[0..vsize];
-                                println!("{:?}", message);
-                                match process_message(
-                                    storage,
-                                    &message,
-                                    &remote_addr,
-                                    false,
-                                    max_edns_size,
-                                ) {
-                                    Ok(message) => {
-                                        if let Err(err) = stream.write(message.as_slice()).await {
-                                            error!("{:?}", err)
-                                        };
-                                        println!("{:?}", message);
-                                        continue;
-                                    }
-                                    Err(err) => {
-                                        error!("serialize message fail: {:?}", err);
-                                        continue;
-                                    }
-                                }
-                            }
-                            Err(err) => {
-                                error!("process message fail: {:?}", err);
+                        // the stream is dropped and closed at once over the limits
+                        let permit = match context.connections.try_accept_tcp(remote_addr.ip()) {
+                            Some(permit) => permit,
+                            None => {
+                                debug!(
+                                    "close tcp connection from {}: too many clients",
+                                    remote_addr
+                                );
                                 continue;
                             }
                         };
-                    };
+                        let (storage, context) = (storage.clone(), context.clone());
+                        tokio::spawn(async move {
+                            let _permit = permit;
+                            handle_tcp_connection(stream, remote_addr, storage, context).await
+                        });
+                    }
                 }
             }));
         }
+        #[cfg(all(target_os = "linux", feature = "systemd"))]
+        {
+            if let Some(interval) = systemd::watchdog_interval() {
+                self.threads
+                    .push(tokio::spawn(systemd_watchdog(self.state.clone(), interval)));
+            }
+            systemd::notify("READY=1");
+        }
         for join_handler in self.threads.iter_mut() {
             if let Err(err) = join_handler.await {
                 error!("{:?}", err)
@@ -392,30 +1111,1197 @@ impl OtterServer {
 #[cfg(test)]
 mod test {
     use super::*;
-    use otterlib::setting::ZoneSetting;
+    use crate::axfr::TransferFormat;
+    use crate::transfer::test::FakeClock;
+    use crate::transfer::ZoneFreshness;
+    use dnsproto::meta::ResourceRecord;
+    use dnsproto::qtype::{DnsTypeA, DnsTypeTLSA};
+    use otterlib::errors::TransferError;
+    use otterlib::setting::{PolicyAction, PolicyRule, Remote, ACL};
+    use storage::builder::ZoneBuilder;
     #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
-    async fn new_socket_server() {
+    async fn new_socket_server_by_transport() {
+        let extension = ExSetting {
+            tcp_workers: 1,
+            udp_workers: 2,
+        };
+        let listen = |list: &[&str]| -> Option<Vec<String>> {
+            Some(list.iter().map(|addr| addr.to_string()).collect())
+        };
+        for (udp, tcp, udp_servers, tcp_servers) in [
+            (listen(&["127.0.0.1:0"]), listen(&[]), 2, 0),
+            (listen(&[]), listen(&["127.0.0.1:0"]), 0, 1),
+            // udp from the shared list
+            (None, listen(&["127.0.0.1:0"]), 2, 1),
+        ]
+        .iter()
+        {
+            let mut settings = Settings::default();
+            settings.server.listen = vec!["127.0.0.1:0/udp".to_string()];
+            settings.server.listen_udp = udp.clone();
+            settings.server.listen_tcp = tcp.clone();
+            let mut servers = OtterServer::new(settings);
+            servers.init_network(&extension).await.unwrap();
+            assert_eq!(servers.udp_servers.len(), *udp_servers);
+            assert_eq!(servers.tcp_servers.len(), *tcp_servers);
+        }
+
         let mut settings = Settings::default();
-        settings.server.listen = vec!["0.0.0.0:15353".to_string()];
-        let zone = ZoneSetting {
-            domain: "abc.com.".to_string(),
-            file: "example.com.zone".to_string(),
-            master: None,
-            notify: None,
-            acl: None,
+        settings.server.listen_udp = listen(&[]);
+        let mut servers = OtterServer::new(settings);
+        assert!(servers.init_network(&extension).await.is_err());
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn new_socket_server_with_address_in_use() {
+        let extension = ExSetting {
+            tcp_workers: 1,
+            udp_workers: 1,
         };
-        settings.zone.push(zone);
+        let taken = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = taken.local_addr().unwrap();
+        let mut settings = Settings::default();
+        settings.server.listen = vec![format!("{}/tcp", addr)];
+        settings.zone.push(ZoneSetting {
+            domain: "example.com.".to_string(),
+            file: "../storage/test/example.zone".to_string(),
+            ..Default::default()
+        });
+        let mut servers = OtterServer::new(settings.clone());
+        let err = servers.init_network(&extension).await.unwrap_err();
+        assert_eq!(err.context().unwrap().peer, Some(addr.to_string()));
+        match err.kind() {
+            OtterError::NetworkError(NetworkError::BindError(transport, _)) => {
+                assert_eq!(transport, "tcp")
+            }
+            other => panic!("{:?}", other),
+        }
+        assert!(err
+            .to_string()
+            .starts_with(&format!("address {}: bind tcp listener failed: ", addr)));
+        let mut servers = OtterServer::new(settings.clone());
+        assert!(servers.check(&extension).await.is_err());
+
+        let taken = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        let addr = taken.local_addr().unwrap();
+        settings.server.listen = vec![format!("{}/udp", addr)];
+        let mut servers = OtterServer::new(settings.clone());
+        let err = servers.init_network(&extension).await.unwrap_err();
+        assert!(matches!(
+            err.kind(),
+            OtterError::NetworkError(NetworkError::BindError(_, _))
+        ));
+
+        // check pass once the address is free
+        drop(taken);
+        let mut servers = OtterServer::new(settings);
+        servers.check(&extension).await.unwrap();
+        assert_eq!(servers.storage.zones().len(), 1);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn new_socket_server_with_expand_wildcard_listen() {
+        let mut settings = Settings::default();
+        settings.server.listen = vec!["0.0.0.0:15354/udp".to_string()];
+        settings.server.expand_wildcard_listen = true;
         let extension = ExSetting {
             tcp_workers: 1,
             udp_workers: 1,
         };
         let mut servers = OtterServer::new(settings);
-        let init_status = servers.init_network(&extension).await;
-        assert_eq!(init_status.is_ok(), true);
-        let init_status = servers.init_load_storage();
-        // assert_eq!(init_status.is_ok(), true);
-        if init_status.is_err() {
-            println!("{:?}", init_status.unwrap_err())
+        servers.init_network(&extension).await.unwrap();
+        let local_addrs: Vec<SocketAddr> = servers
+            .udp_servers
+            .iter()
+            .map(|server| server.udp_socket.local_addr().unwrap())
+            .collect();
+        let loopback: SocketAddr = "127.0.0.1:15354".parse().unwrap();
+        assert!(local_addrs.contains(&loopback));
+        assert!(local_addrs.iter().all(|addr| !addr.ip().is_unspecified()));
+
+        // reply leaves from the address the query was sent to
+        let client = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        client.send_to(&[0u8; 12], loopback).await.unwrap();
+        let server = servers
+            .udp_servers
+            .iter()
+            .find(|server| server.udp_socket.local_addr().unwrap() == loopback)
+            .unwrap();
+        let mut buffer = [0u8; 512];
+        let (_, remote) = server.udp_socket.recv_from(&mut buffer).await.unwrap();
+        server.udp_socket.send_to(&[1u8; 12], remote).await.unwrap();
+        let (_, source) = client.recv_from(&mut buffer).await.unwrap();
+        assert_eq!(source, loopback);
+    }
+
+    fn build_query(domain: &str, dtype: DNSType) -> Vec<u8> {
+        let mut message = Message::new_with_header(Header::new());
+        message.set_question(Question::new(domain, dtype, DNSClass::IN).unwrap());
+        message.encode(true).unwrap()
+    }
+
+    #[test]
+    fn process_message_with_allow_query() {
+        let mut settings = Settings::default();
+        settings.server.max_edns_size = 1232;
+        settings.zone.push(ZoneSetting {
+            domain: "example.com".to_string(),
+            file: "../storage/test/example.zone".to_string(),
+            allow_query: Some(vec!["internal".to_string()]),
+            ..Default::default()
+        });
+        settings.acl.push(ACL {
+            id: "internal".to_string(),
+            address: "10.0.0.0/8".to_string(),
+            action: "query".to_string(),
+            key: None,
+        });
+        let context = ServerContext::new(&settings).unwrap();
+        let storage =
+            SafeRBTreeStorage::new_zone_from_file("../storage/test/example.zone", None).unwrap();
+        let query = build_query("www.example.com.", DNSType::A);
+
+        let allowed: SocketAddr = "10.1.1.1:5353".parse().unwrap();
+        let response = process_message(storage.clone(), &query, &allowed, true, &context).unwrap();
+        let response = Message::parse_dns_message(&response).unwrap();
+        assert_ne!(response.header.rcode(), RCode::Refused);
+
+        let denied: SocketAddr = "192.168.1.1:5353".parse().unwrap();
+        let response = process_message(storage, &query, &denied, true, &context).unwrap();
+        let response = Message::parse_dns_message(&response).unwrap();
+        assert_eq!(response.header.rcode(), RCode::Refused);
+        assert_eq!(context.metrics.refused_by_acl(), 1);
+    }
+
+    #[test]
+    fn process_message_for_empty_non_terminal() {
+        let context = ServerContext::new(&Settings::default()).unwrap();
+        let mut storage =
+            SafeRBTreeStorage::new_zone_from_file("../storage/test/example.zone", None).unwrap();
+        let tlsa = DnsTypeTLSA::from_str(
+            "3 1 1 D2ABDE240D7CD3EE6B4B28C54DF034B97983A1D16E8A410E4561CB106618E971",
+        )
+        .unwrap();
+        let rr = ResourceRecord::new(
+            "_25._tcp.example.com.",
+            DNSType::TLSA,
+            DNSClass::IN,
+            3600,
+            Some(Box::new(tlsa)),
+        )
+        .unwrap();
+        storage.insert_rr(rr).unwrap();
+        let remote: SocketAddr = "127.0.0.1:5353".parse().unwrap();
+        for (domain, dtype, rcode, answers) in [
+            ("_25._tcp.example.com.", DNSType::TLSA, RCode::NoError, 1),
+            // empty non-terminal of a minimizing resolver query
+            ("_tcp.example.com.", DNSType::NS, RCode::NoError, 0),
+            ("mail.example.com.", DNSType::TXT, RCode::NoError, 0),
+            ("_udp.example.com.", DNSType::NS, RCode::NameError, 0),
+            ("_udp._tcp.example.com.", DNSType::NS, RCode::NameError, 0),
+        ]
+        .iter()
+        {
+            let query = build_query(domain, *dtype);
+            let response =
+                process_message(storage.clone(), &query, &remote, true, &context).unwrap();
+            let response = Message::parse_dns_message(&response).unwrap();
+            assert_eq!(response.header.rcode(), *rcode, "{}", domain);
+            assert!(response.header.aa());
+            assert_eq!(response.answers().len(), *answers, "{}", domain);
+            if *answers == 0 {
+                let authorities = response.authorities();
+                assert_eq!(authorities.len(), 1);
+                assert_eq!(authorities[0].get_type(), DNSType::SOA);
+            }
+        }
+    }
+
+    #[test]
+    fn process_message_for_delegation() {
+        let context = ServerContext::new(&Settings::default()).unwrap();
+        let storage =
+            SafeRBTreeStorage::new_zone_from_file("../storage/test/lookup.zone", None).unwrap();
+        let remote: SocketAddr = "127.0.0.1:5353".parse().unwrap();
+        let query = build_query("host.sub.example.net.", DNSType::A);
+        let response = process_message(storage.clone(), &query, &remote, true, &context).unwrap();
+        let response = Message::parse_dns_message(&response).unwrap();
+        assert_eq!(response.header.rcode(), RCode::NoError);
+        assert!(!response.header.aa());
+        assert!(response.answers().is_empty());
+        assert_eq!(response.authorities().len(), 2);
+        assert_eq!(
+            response.additionals()[0].get_dname().to_string(),
+            "ns.sub.example.net."
+        );
+
+        let query = build_query("alias.example.net.", DNSType::A);
+        let response = process_message(storage, &query, &remote, true, &context).unwrap();
+        let response = Message::parse_dns_message(&response).unwrap();
+        assert!(response.header.aa());
+        assert_eq!(response.answers()[0].get_type(), DNSType::CNAME);
+    }
+
+    #[test]
+    fn process_message_for_root_zone() {
+        let context = ServerContext::new(&Settings::default()).unwrap();
+        let remote: SocketAddr = "127.0.0.1:5353".parse().unwrap();
+        let answer = |storage: &SafeRBTreeStorage, domain: &str, dtype: DNSType| {
+            let query = build_query(domain, dtype);
+            let response =
+                process_message(storage.clone(), &query, &remote, true, &context).unwrap();
+            Message::parse_dns_message(&response).unwrap()
+        };
+
+        // the root name is refused when only other zones are loaded
+        let storage = SafeRBTreeStorage::new_zone_from_str(
+            "example.org. 600 IN SOA ns.example.org. admin.example.org. 1 7200 3600 1209600 60\n\
+             example.org. 600 IN NS ns.example.org.\n",
+            None,
+        )
+        .unwrap();
+        assert_eq!(
+            answer(&storage, ".", DNSType::NS).header.rcode(),
+            RCode::Refused
+        );
+        let apex = answer(&storage, "example.org.", DNSType::Any);
+        assert!(apex.header.aa());
+        let mut types: Vec<String> = apex
+            .answers()
+            .iter()
+            .map(|rr| rr.get_type().to_string())
+            .collect();
+        types.sort();
+        assert_eq!(types, vec!["NS", "SOA"]);
+
+        let storage = SafeRBTreeStorage::new_zone_from_str(
+            ". 86400 IN SOA a.root-servers.net. nstld.verisign-grs.com. 1 1800 900 604800 86400\n\
+             . 518400 IN NS a.root-servers.net.\n\
+             a.root-servers.net. 518400 IN A 198.41.0.4\n",
+            None,
+        )
+        .unwrap();
+        let root = answer(&storage, ".", DNSType::NS);
+        assert_eq!(root.header.rcode(), RCode::NoError);
+        assert!(root.header.aa());
+        assert_eq!(root.answers().len(), 1);
+        assert_eq!(root.answers()[0].get_dname().to_string(), ".");
+    }
+
+    #[test]
+    fn process_message_with_ttl() {
+        let mut settings = Settings::default();
+        settings.server.max_ttl = Some(3600);
+        settings.server.response_cache_size = 10;
+        let context = ServerContext::new(&settings).unwrap();
+        let storage = SafeRBTreeStorage::new_zone_from_str(
+            "example.org. 600 IN SOA ns.example.org. admin.example.org. 1 7200 3600 1209600 60\n\
+             example.org. 600 IN NS ns.example.org.\n\
+             zero.example.org. 0 IN A 192.0.2.1\n\
+             long.example.org. 86400 IN A 192.0.2.2\n\
+             mixed.example.org. 300 IN A 192.0.2.3\n\
+             mixed.example.org. 100 IN A 192.0.2.4\n",
+            None,
+        )
+        .unwrap();
+        let remote: SocketAddr = "127.0.0.1:5353".parse().unwrap();
+        let answer = |domain: &str, dtype: DNSType| {
+            let query = build_query(domain, dtype);
+            let response =
+                process_message(storage.clone(), &query, &remote, true, &context).unwrap();
+            Message::parse_dns_message(&response).unwrap()
+        };
+        let ttls = |records: Vec<&ResourceRecord>| {
+            let mut ttls: Vec<u32> = records.iter().map(|rr| rr.get_ttl()).collect();
+            ttls.sort_unstable();
+            ttls
+        };
+
+        // ttl 0 is answered as is and never cached
+        for _ in 0..2 {
+            assert_eq!(ttls(answer("zero.example.org.", DNSType::A).answers()), [0]);
+        }
+        assert_eq!(context.metrics.cache_hits(), 0);
+        // ttl great than max_ttl is capped
+        assert_eq!(
+            ttls(answer("long.example.org.", DNSType::A).answers()),
+            [3600]
+        );
+        // each record keeps its own ttl
+        assert_eq!(
+            ttls(answer("mixed.example.org.", DNSType::A).answers()),
+            [100, 300]
+        );
+        // negative answers use the soa minimum when it is smaller than the soa ttl
+        let nodata = answer("zero.example.org.", DNSType::MX);
+        assert_eq!(ttls(nodata.authorities()), [60]);
+        let nxdomain = answer("missing.example.org.", DNSType::A);
+        assert_eq!(nxdomain.header.rcode(), RCode::NameError);
+        assert_eq!(ttls(nxdomain.authorities()), [60]);
+        // the soa itself is answered with its own ttl
+        assert_eq!(ttls(answer("example.org.", DNSType::SOA).answers()), [600]);
+    }
+
+    #[test]
+    fn process_message_for_unknown_type() {
+        let context = ServerContext::new(&Settings::default()).unwrap();
+        let storage =
+            SafeRBTreeStorage::new_zone_from_file("../storage/test/example.zone", None).unwrap();
+        let remote: SocketAddr = "127.0.0.1:5353".parse().unwrap();
+        for (domain, rcode) in [
+            ("Mail.Example.com.", RCode::NoError),
+            ("Missing.Example.com.", RCode::NameError),
+        ]
+        .iter()
+        {
+            let query = build_query(domain, DNSType::Unknown(65280));
+            let raw = process_message(storage.clone(), &query, &remote, true, &context).unwrap();
+            assert_eq!(&raw[12..query.len()], &query[12..]);
+            let response = Message::parse_dns_message(&raw).unwrap();
+            assert_eq!(response.header.rcode(), *rcode, "{}", domain);
+            assert!(response.answers().is_empty());
+            let authorities = response.authorities();
+            assert_eq!(authorities.len(), 1);
+            assert_eq!(authorities[0].get_type(), DNSType::SOA);
+            // the question is echoed with the type code and the case of the query
+            let (name, dtype) = response.query_name_and_type().unwrap();
+            assert_eq!(name.to_string(), *domain);
+            assert_eq!(*dtype, DNSType::Unknown(65280));
+            assert_eq!(dtype.to_string(), "TYPE65280");
+        }
+    }
+
+    #[test]
+    fn process_message_for_legacy_types() {
+        let context = ServerContext::new(&Settings::default()).unwrap();
+        let storage =
+            SafeRBTreeStorage::new_zone_from_file("../storage/test/legacy.zone", None).unwrap();
+        let remote: SocketAddr = "127.0.0.1:5353".parse().unwrap();
+        for (domain, dtype, expect) in [
+            (
+                "legacy.example.",
+                DNSType::RP,
+                vec!["admin.legacy.example. contact.legacy.example."],
+            ),
+            (
+                "legacy.example.",
+                DNSType::AFSDB,
+                vec!["1 afsdb.legacy.example.", "2 afsdb.example.net."],
+            ),
+            (
+                "ftp.legacy.example.",
+                DNSType::URI,
+                vec![
+                    "10 1 \"ftp://ftp1.legacy.example/public\"",
+                    "20 1 \"ftp://ftp2.legacy.example/public\"",
+                ],
+            ),
+            (
+                "host.legacy.example.",
+                DNSType::EUI48,
+                vec!["00-00-5e-00-53-2a"],
+            ),
+            (
+                "host.legacy.example.",
+                DNSType::EUI64,
+                vec!["00-00-5e-ef-10-00-00-2a"],
+            ),
+            (
+                "host.legacy.example.",
+                DNSType::DHCID,
+                vec!["AAIBY2/AuCccgoJbsaxcQc9TUapptP69lOjxfNuVAA2kjEA="],
+            ),
+        ]
+        .iter()
+        {
+            let query = build_query(domain, *dtype);
+            let response =
+                process_message(storage.clone(), &query, &remote, true, &context).unwrap();
+            let response = Message::parse_dns_message(&response).unwrap();
+            assert_eq!(response.header.rcode(), RCode::NoError);
+            let mut answers: Vec<String> = response
+                .answers()
+                .iter()
+                .map(|rr| rr.get_data().as_ref().unwrap().to_string())
+                .collect();
+            answers.sort();
+            assert_eq!(&answers, expect);
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn process_message_with_query_deadline() {
+        let mut context = ServerContext::new(&Settings::default()).unwrap();
+        context.query_timeout = Duration::from_millis(100);
+        let context = Arc::new(context);
+        let storage =
+            SafeRBTreeStorage::new_zone_from_file("../storage/test/example.zone", None).unwrap();
+        let remote: SocketAddr = "127.0.0.1:5353".parse().unwrap();
+        let query = build_query("mail.example.com.", DNSType::A);
+        let response = process_message_with_deadline(
+            storage.clone(),
+            query.clone(),
+            remote,
+            true,
+            context.clone(),
+        )
+        .await
+        .unwrap();
+        let response = Message::parse_dns_message(&response).unwrap();
+        assert_eq!(response.header.rcode(), RCode::NoError);
+
+        // a writer holding the rrset lock makes the query stuck
+        let rrset = storage
+            .clone()
+            .search_rrset(
+                &DNSName::new("mail.example.com.", None).unwrap(),
+                &DNSType::A,
+            )
+            .unwrap();
+        let (locked_sender, locked) = std::sync::mpsc::channel();
+        let (release, release_receiver) = std::sync::mpsc::channel::<()>();
+        let writer = std::thread::spawn(move || {
+            let _guard = rrset.write().unwrap();
+            locked_sender.send(()).unwrap();
+            release_receiver.recv().unwrap();
+        });
+        locked.recv().unwrap();
+        let response = process_message_with_deadline(storage, query, remote, true, context.clone())
+            .await
+            .unwrap();
+        release.send(()).unwrap();
+        writer.join().unwrap();
+        let response = Message::parse_dns_message(&response).unwrap();
+        assert_eq!(response.header.rcode(), RCode::ServerFailure);
+        assert_eq!(
+            response.query_name_and_type().unwrap().0.to_string(),
+            "mail.example.com."
+        );
+        assert_eq!(context.metrics.servfail_timeout(), 1);
+    }
+
+    #[test]
+    fn process_message_with_query_stats() {
+        let context = ServerContext::new(&Settings::default()).unwrap();
+        let storage =
+            SafeRBTreeStorage::new_zone_from_file("../storage/test/example.zone", None).unwrap();
+        let remote: SocketAddr = "127.0.0.1:5353".parse().unwrap();
+        for (domain, dtype) in [
+            ("mail.example.com.", DNSType::A),
+            ("Mail.example.com.", DNSType::A),
+            ("missing.example.com.", DNSType::A),
+            ("example.com.", DNSType::MX),
+            ("example.org.", DNSType::A),
+        ]
+        .iter()
+        {
+            let query = build_query(domain, *dtype);
+            process_message(storage.clone(), &query, &remote, true, &context).unwrap();
+        }
+        let zone = context
+            .stats
+            .zone(&DNSName::new("example.com.", None).unwrap())
+            .unwrap();
+        assert_eq!(zone.queries(), 4);
+        assert_eq!(zone.nxdomain(), 1);
+        assert_eq!(zone.qtypes().get("A"), Some(&3));
+        assert_eq!(zone.top_names(1), vec![("mail.example.com.".to_owned(), 2)]);
+        assert_eq!(context.stats.global().queries(), 5);
+    }
+
+    #[test]
+    fn process_message_with_latency_metrics() {
+        use crate::metrics::Stage;
+        use crate::query::Transport;
+        let storage =
+            SafeRBTreeStorage::new_zone_from_file("../storage/test/example.zone", None).unwrap();
+        let remote: SocketAddr = "127.0.0.1:5353".parse().unwrap();
+        let mut settings = Settings::default();
+        for detailed_timing in [false, true].iter() {
+            settings.server.detailed_timing = *detailed_timing;
+            let context = ServerContext::new(&settings).unwrap();
+            let query = build_query("mail.example.com.", DNSType::A);
+            process_message(storage.clone(), &query, &remote, true, &context).unwrap();
+            let query = build_query("missing.example.com.", DNSType::A);
+            process_message(storage.clone(), &query, &remote, false, &context).unwrap();
+            let metrics = &context.metrics;
+            assert_eq!(metrics.query_latency(Transport::Udp, 0).count(), 1);
+            assert_eq!(metrics.query_latency(Transport::Tcp, 3).count(), 1);
+            assert_eq!(metrics.query_latency(Transport::Tcp, 0).count(), 0);
+            let stages = if *detailed_timing { 2 } else { 0 };
+            for stage in [Stage::Parse, Stage::Lookup, Stage::Encode].iter() {
+                assert_eq!(metrics.stage_latency(*stage).count(), stages);
+            }
+        }
+    }
+
+    // transfer_exchange send an axfr query over tcp and read the responses until the
+    // server closes the connection
+    async fn transfer_exchange(addr: SocketAddr, zone: &str) -> Vec<Message> {
+        let query = {
+            let mut message = Message::new_with_header(Header::new());
+            message.set_question(Question::new(zone, DNSType::AXFR, DNSClass::IN).unwrap());
+            message.encode(false).unwrap()
+        };
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        stream.write_all(&query).await.unwrap();
+        let mut responses = vec![];
+        let mut length = [0u8; 2];
+        while stream.read_exact(&mut length).await.is_ok() {
+            let mut response = vec![0u8; ((length[0] as usize) << 8) + length[1] as usize];
+            stream.read_exact(&mut response).await.unwrap();
+            responses.push(Message::parse_dns_message(&response).unwrap());
+        }
+        responses
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn serve_transfer_with_limits() {
+        let mut settings = Settings::default();
+        settings.server.max_edns_size = 1232;
+        settings.server.max_concurrent_transfers = Some(1);
+        settings.zone.push(ZoneSetting {
+            domain: "example.com".to_string(),
+            file: "../storage/test/example.zone".to_string(),
+            acl: Some(vec!["secondary".to_string()]),
+            ..Default::default()
+        });
+        settings.acl.push(ACL {
+            id: "secondary".to_string(),
+            address: "127.0.0.1".to_string(),
+            action: "transfer".to_string(),
+            key: None,
+        });
+        let context = Arc::new(ServerContext::new(&settings).unwrap());
+        let storage =
+            SafeRBTreeStorage::new_zone_from_file("../storage/test/example.zone", None).unwrap();
+        let hidden = storage.clone();
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        {
+            let context = context.clone();
+            tokio::spawn(async move {
+                while let Ok((stream, remote)) = listener.accept().await {
+                    tokio::spawn(handle_tcp_connection(
+                        stream,
+                        remote,
+                        storage.clone(),
+                        context.clone(),
+                    ));
+                }
+            });
+        }
+
+        // another transfer holds the only slot
+        let permit = context
+            .transfers
+            .try_acquire("192.0.2.1".parse().unwrap())
+            .unwrap();
+        let responses = transfer_exchange(addr, "example.com.").await;
+        assert_eq!(responses.len(), 1);
+        assert_eq!(responses[0].header.rcode(), RCode::Refused);
+        assert_eq!(context.metrics.transfers_denied(), 1);
+        drop(permit);
+
+        let responses = transfer_exchange(addr, "example.com.").await;
+        let answers: Vec<&ResourceRecord> = responses
+            .iter()
+            .flat_map(|response| response.answers())
+            .collect();
+        assert!(answers.len() > 2);
+        assert_eq!(answers.first().unwrap().get_type(), DNSType::SOA);
+        assert_eq!(answers.last().unwrap().get_type(), DNSType::SOA);
+        assert_eq!(context.metrics.transfers_active(), 0);
+
+        // only the zone apex can be transferred
+        let responses = transfer_exchange(addr, "www.example.com.").await;
+        assert_eq!(responses[0].header.rcode(), RCode::Refused);
+
+        // a hidden zone is never transferred
+        hidden.set_zone_hidden(&DNSName::new("example.com.", None).unwrap(), true);
+        let responses = transfer_exchange(addr, "example.com.").await;
+        assert_eq!(responses.len(), 1);
+        assert_eq!(responses[0].header.rcode(), RCode::Refused);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn process_message_with_update_forwarding() {
+        let master = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let mut settings = Settings::default();
+        settings.server.max_edns_size = 1232;
+        settings.server.query_timeout = Some(500);
+        settings.zone.push(ZoneSetting {
+            domain: "example.com".to_string(),
+            file: "../storage/test/example.zone".to_string(),
+            master: Some(Masters::from("master")),
+            acl: Some(vec!["updater".to_string()]),
+            ..Default::default()
+        });
+        settings.remote.push(Remote {
+            id: "master".to_string(),
+            address: format!("127.0.0.1@{}", master.local_addr().unwrap().port()),
+            ..Default::default()
+        });
+        settings.acl.push(ACL {
+            id: "updater".to_string(),
+            address: "10.0.0.0/8".to_string(),
+            action: "update".to_string(),
+            key: None,
+        });
+        let context = Arc::new(ServerContext::new(&settings).unwrap());
+        let storage =
+            SafeRBTreeStorage::new_zone_from_file("../storage/test/example.zone", None).unwrap();
+        let update = {
+            let mut message = Message::new_with_header(Header::new());
+            message.header.set_op_code(OpCode::Update);
+            message.header.set_id(0x1234);
+            message
+                .set_question(Question::new("example.com.", DNSType::SOA, DNSClass::IN).unwrap());
+            message.encode(true).unwrap()
+        };
+        // the fake master answer the update with NOERROR and return the received update
+        let master_task = tokio::spawn(async move {
+            let (mut stream, _) = master.accept().await.unwrap();
+            let size = stream.read_u16().await.unwrap();
+            let mut request = vec![0u8; size as usize];
+            stream.read_exact(&mut request).await.unwrap();
+            let mut response = request.clone();
+            response[2] |= 0x80;
+            let mut framed = (response.len() as u16).to_be_bytes().to_vec();
+            framed.extend_from_slice(&response);
+            stream.write_all(&framed).await.unwrap();
+            request
+        });
+
+        let allowed: SocketAddr = "10.1.1.1:5353".parse().unwrap();
+        let response = process_message_with_deadline(
+            storage.clone(),
+            update.clone(),
+            allowed,
+            true,
+            context.clone(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(master_task.await.unwrap(), update);
+        let response = Message::parse_dns_message(&response).unwrap();
+        assert_eq!(response.header.id(), 0x1234);
+        assert_eq!(response.header.rcode(), RCode::NoError);
+        assert!(matches!(response.header.op_code(), OpCode::Update));
+
+        let denied: SocketAddr = "192.168.1.1:5353".parse().unwrap();
+        let response = process_message_with_deadline(
+            storage.clone(),
+            update.clone(),
+            denied,
+            true,
+            context.clone(),
+        )
+        .await
+        .unwrap();
+        let response = Message::parse_dns_message(&response).unwrap();
+        assert_eq!(response.header.rcode(), RCode::Refused);
+
+        // the master is gone
+        let response =
+            process_message_with_deadline(storage, update, allowed, true, context.clone())
+                .await
+                .unwrap();
+        let response = Message::parse_dns_message(&response).unwrap();
+        assert_eq!(response.header.rcode(), RCode::ServerFailure);
+        assert_eq!(response.header.id(), 0x1234);
+        assert_eq!(context.metrics.servfail_forward(), 1);
+    }
+
+    /// fake_master answer each tcp connection with the prepared messages, the message id
+    /// is replaced with the request id.
+    async fn fake_master(connections: Vec<Vec<Vec<u8>>>) -> SocketAddr {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let master = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            for messages in connections {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                let size = stream.read_u16().await.unwrap();
+                let mut request = vec![0u8; size as usize];
+                stream.read_exact(&mut request).await.unwrap();
+                for mut message in messages {
+                    message[2..4].copy_from_slice(&request[0..2]);
+                    if stream.write_all(&message).await.is_err() {
+                        break;
+                    }
+                }
+            }
+        });
+        master
+    }
+
+    #[tokio::test]
+    async fn refresh_zone_with_transfer_checks() {
+        let storage =
+            SafeRBTreeStorage::new_zone_from_file("../storage/test/example.zone", None).unwrap();
+        let apex = DNSName::new("example.com.", None).unwrap();
+        let name = DNSName::new("ns.example.com.", None).unwrap();
+        let served = storage.get_zone_soa(&apex).unwrap();
+        let mut soa = served.clone();
+        soa.set_serial(served.serial() + 1);
+        let mut records = storage.transfer_records(&apex).unwrap();
+        let last = records.len() - 1;
+        for index in [0, last].iter() {
+            records[*index].set_data(Some(Box::new(soa.clone())));
+        }
+        let query = |dtype| {
+            let mut query = Message::new_with_header(Header::new());
+            query.set_question(Question::new("example.com.", dtype, DNSClass::IN).unwrap());
+            query
+        };
+        let soa_response = transfer_messages(
+            &query(DNSType::SOA),
+            vec![records[0].clone()],
+            TransferFormat::default(),
+        )
+        .unwrap();
+        let mut bad = records.clone();
+        let outside = ResourceRecord::new(
+            "www.example.org.",
+            DNSType::A,
+            DNSClass::IN,
+            3600,
+            Some(Box::new(DnsTypeA::new("192.0.2.1").unwrap())),
+        )
+        .unwrap();
+        bad.insert(1, outside);
+        let good = records
+            .into_iter()
+            .filter(|rr| !(rr.get_dname() == &name && rr.get_type() == DNSType::A))
+            .collect();
+        let master = fake_master(vec![
+            soa_response.clone(),
+            transfer_messages(&query(DNSType::AXFR), bad, TransferFormat::default()).unwrap(),
+            soa_response,
+            transfer_messages(&query(DNSType::AXFR), good, TransferFormat::default()).unwrap(),
+        ])
+        .await;
+
+        // the first master refuse all connections
+        let unreachable = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+
+        let clock = Arc::new(FakeClock::default());
+        let mut context = ServerContext::default();
+        context.query_timeout = Duration::from_millis(500);
+        context.scheduler = Arc::new(TransferScheduler::new(clock.clone()));
+        context
+            .scheduler
+            .register(apex.clone(), &served, false, None);
+        let storage_file = std::env::temp_dir().join("otter_refresh_zone.zone");
+        let mut secondary = SecondaryZone {
+            masters: MasterList::new(vec![
+                Master {
+                    id: "master01".to_owned(),
+                    addr: unreachable,
+                    key: None,
+                    source: None,
+                },
+                Master {
+                    id: "master02".to_owned(),
+                    addr: master,
+                    key: None,
+                    source: None,
+                },
+            ]),
+            limits: TransferLimits::default(),
+            option: ZoneOption::default(),
+            storage_file: storage_file.to_str().unwrap().to_owned(),
+        };
+        // the out of zone record reject the transfer and the served zone is kept
+        let result = refresh_zone(storage.clone(), &apex, &mut secondary, &context).await;
+        assert!(matches!(
+            result,
+            Err(OtterError::TransferError(TransferError::OutOfZone(..)))
+        ));
+        assert_eq!(context.metrics.transfers_in_out_of_zone(), 1);
+        assert_eq!(context.metrics.master_refreshes("master01"), (0, 1));
+        assert_eq!(context.metrics.master_refreshes("master02"), (0, 1));
+        assert_eq!(
+            storage.get_zone_soa(&apex).unwrap().serial(),
+            served.serial()
+        );
+        assert!(storage.clone().search_rrset(&name, &DNSType::A).is_ok());
+
+        // both masters are in backoff
+        refresh_zone(storage.clone(), &apex, &mut secondary, &context)
+            .await
+            .unwrap();
+        assert_eq!(context.metrics.master_refreshes("master02"), (0, 1));
+
+        clock.advance(10);
+        refresh_zone(storage.clone(), &apex, &mut secondary, &context)
+            .await
+            .unwrap();
+        assert_eq!(context.metrics.master_refreshes("master01"), (0, 2));
+        assert_eq!(context.metrics.master_refreshes("master02"), (1, 1));
+        // the master succeeded is tried first in the next refresh
+        assert_eq!(secondary.masters.preferred().unwrap().id, "master02");
+        assert_eq!(storage.get_zone_soa(&apex).unwrap().serial(), soa.serial());
+        assert!(storage.clone().search_rrset(&name, &DNSType::A).is_err());
+        assert_eq!(
+            context.scheduler.history(&apex).last().map(|item| item.1),
+            Some(soa.serial())
+        );
+        // the transferred zone is written for the next startup
+        let header = storage::writer::read_transfer_header(&secondary.storage_file).unwrap();
+        assert_eq!(header.serial, soa.serial());
+        // so is the zone state with the master used
+        let path = crate::zonestate::state_path(&secondary.storage_file);
+        let state = crate::zonestate::ZoneState::load(&path).unwrap();
+        assert_eq!(state.serial, soa.serial());
+        assert_eq!(state.master.as_deref(), Some("master02"));
+        assert!(state.last_failure.is_some());
+        std::fs::remove_file(&secondary.storage_file).unwrap();
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn process_message_with_policy() {
+        let mut settings = Settings::default();
+        for (name, action, data) in [
+            ("mail.example.com.", PolicyAction::Nxdomain, None),
+            ("*.tracker.example.", PolicyAction::Drop, None),
+            ("ns.example.com.", PolicyAction::Fixed, Some("192.0.2.53")),
+            ("*.example.com.", PolicyAction::Fixed, Some("2001:db8::53")),
+        ]
+        .iter()
+        {
+            settings.policy.push(PolicyRule {
+                name: name.to_string(),
+                action: *action,
+                data: data.map(|data| data.to_string()),
+            });
+        }
+        let context = ServerContext::new(&settings).unwrap();
+        let storage =
+            SafeRBTreeStorage::new_zone_from_file("../storage/test/example.zone", None).unwrap();
+        let remote: SocketAddr = "127.0.0.1:5353".parse().unwrap();
+        let answer = |domain: &str, dtype: DNSType| {
+            let query = build_query(domain, dtype);
+            let response = process_message(storage.clone(), &query, &remote, true, &context)?;
+            Message::parse_dns_message(&response)
+        };
+
+        // the policy has precedence over the zone data
+        let response = answer("mail.example.com.", DNSType::A).unwrap();
+        assert_eq!(response.header.rcode(), RCode::NameError);
+        assert!(response.answers().is_empty());
+        assert_eq!(
+            answer("ads.tracker.example.", DNSType::A),
+            Err(DNSProtoErr::DroppedByPolicy)
+        );
+        let response = answer("ns.example.com.", DNSType::A).unwrap();
+        assert_eq!(response.header.rcode(), RCode::NoError);
+        assert_eq!(
+            response.answers()[0]
+                .get_data()
+                .as_ref()
+                .unwrap()
+                .to_string(),
+            "192.0.2.53"
+        );
+        // the fixed address does not match the query type
+        let response = answer("ns.example.com.", DNSType::AAAA).unwrap();
+        assert_eq!(response.header.rcode(), RCode::NoError);
+        assert!(response.answers().is_empty());
+        let response = answer("mail2.example.com.", DNSType::AAAA).unwrap();
+        assert_eq!(
+            response.answers()[0]
+                .get_data()
+                .as_ref()
+                .unwrap()
+                .to_string(),
+            "2001:db8::53"
+        );
+        // the apex is not covered by the wildcard rule
+        let response = answer("example.com.", DNSType::SOA).unwrap();
+        assert_eq!(response.answers()[0].get_type(), DNSType::SOA);
+
+        assert_eq!(context.metrics.policy_nxdomain(), 1);
+        assert_eq!(context.metrics.policy_drop(), 1);
+        assert_eq!(context.metrics.policy_fixed(), 3);
+    }
+
+    #[test]
+    fn process_message_for_expired_zone() {
+        let clock = Arc::new(FakeClock::default());
+        let mut context = ServerContext::new(&Settings::default()).unwrap();
+        context.scheduler = Arc::new(TransferScheduler::new(clock.clone()));
+        let storage =
+            SafeRBTreeStorage::new_zone_from_file("../storage/test/example.zone", None).unwrap();
+        let apex = DNSName::new("example.com.", None).unwrap();
+        let soa = storage.get_zone_soa(&apex).unwrap();
+        context.scheduler.register(apex, &soa, false, None);
+        let remote: SocketAddr = "127.0.0.1:5353".parse().unwrap();
+        let query = build_query("example.com.", DNSType::SOA);
+
+        let response = process_message(storage.clone(), &query, &remote, true, &context).unwrap();
+        let response = Message::parse_dns_message(&response).unwrap();
+        assert_eq!(response.header.rcode(), RCode::NoError);
+
+        clock.advance(soa.expire() as u64);
+        let response = process_message(storage, &query, &remote, true, &context).unwrap();
+        let response = Message::parse_dns_message(&response).unwrap();
+        assert_eq!(response.header.rcode(), RCode::ServerFailure);
+        assert_eq!(context.metrics.servfail_expired(), 1);
+    }
+
+    #[test]
+    fn process_message_for_unconfigured_zone() {
+        let context = ServerContext::new(&Settings::default()).unwrap();
+        let storage =
+            SafeRBTreeStorage::new_zone_from_file("../storage/test/example.zone", None).unwrap();
+        let remote: SocketAddr = "127.0.0.1:5353".parse().unwrap();
+        // sibling domain and parent domain of example.com.
+        for (index, domain) in ["example.org.", "com.", "."].iter().enumerate() {
+            let query = build_query(domain, DNSType::SOA);
+            let response =
+                process_message(storage.clone(), &query, &remote, true, &context).unwrap();
+            let response = Message::parse_dns_message(&response).unwrap();
+            assert_eq!(response.header.rcode(), RCode::Refused);
+            assert!(!response.header.aa());
+            assert_eq!(context.metrics.refused_not_auth(), index as u64 + 1);
+        }
+        let query = build_query("example.com.", DNSType::SOA);
+        let response = process_message(storage, &query, &remote, true, &context).unwrap();
+        let response = Message::parse_dns_message(&response).unwrap();
+        assert_eq!(response.header.rcode(), RCode::NoError);
+        assert!(response.header.aa());
+    }
+
+    #[test]
+    fn process_message_with_response_cache() {
+        let mut settings = Settings::default();
+        settings.server.max_edns_size = 1232;
+        settings.server.response_cache_size = 16;
+        let context = ServerContext::new(&settings).unwrap();
+        let storage =
+            SafeRBTreeStorage::new_zone_from_file("../storage/test/example.zone", None).unwrap();
+        let remote: SocketAddr = "127.0.0.1:5353".parse().unwrap();
+
+        let query = build_query("ns.example.com.", DNSType::A);
+        let first = process_message(storage.clone(), &query, &remote, true, &context).unwrap();
+        assert_eq!(context.metrics.cache_misses(), 1);
+
+        let mut query = build_query("NS.Example.COM.", DNSType::A);
+        query[0] = 0x12;
+        query[1] = 0x34;
+        let second = process_message(storage.clone(), &query, &remote, true, &context).unwrap();
+        assert_eq!(context.metrics.cache_hits(), 1);
+        assert_eq!(&second[0..2], &[0x12, 0x34]);
+        assert_eq!(
+            &second[2..],
+            &first[2..12]
+                .iter()
+                .chain(query[12..28].iter())
+                .chain(first[28..].iter())
+                .cloned()
+                .collect::<Vec<u8>>()[..]
+        );
+
+        // tcp query use another cache entry
+        let _ = process_message(storage.clone(), &query, &remote, false, &context).unwrap();
+        assert_eq!(context.metrics.cache_misses(), 2);
+
+        context
+            .cache
+            .as_ref()
+            .unwrap()
+            .invalidate_zone(&DNSName::new("example.com.", None).unwrap());
+        let _ = process_message(storage, &query, &remote, true, &context).unwrap();
+        assert_eq!(context.metrics.cache_misses(), 3);
+    }
+
+    #[test]
+    fn process_message_with_opcode() {
+        let context = ServerContext::new(&Settings::default()).unwrap();
+        let storage =
+            SafeRBTreeStorage::new_zone_from_file("../storage/test/example.zone", None).unwrap();
+        let remote: SocketAddr = "127.0.0.1:5353".parse().unwrap();
+        // Query, IQuery, Status, Reserved, Notify, Update and an unassigned opcode
+        for opcode in [0u8, 1, 2, 3, 4, 5, 9].iter() {
+            let mut query = build_query("example.com.", DNSType::SOA);
+            query[2] = (query[2] & 0x87) | (opcode << 3);
+            let response =
+                process_message(storage.clone(), &query, &remote, true, &context).unwrap();
+            let response = Message::parse_dns_message(&response).unwrap();
+            assert!(!response.is_query());
+            let response_opcode: u8 = response.header.op_code().into();
+            assert_eq!(response_opcode, *opcode);
+            let (name, dtype) = response.query_name_and_type().unwrap();
+            assert_eq!(name.to_string(), "example.com.");
+            assert_eq!(*dtype, DNSType::SOA);
+            match *opcode {
+                0 => assert_eq!(response.header.rcode(), RCode::NoError),
+                // no notify or update acl for the zone
+                4 | 5 => assert_eq!(response.header.rcode(), RCode::Refused),
+                _ => assert_eq!(response.header.rcode(), RCode::NotImplemented),
+            }
+        }
+    }
+
+    #[test]
+    fn load_catalog_zone() {
+        let mut settings = Settings::default();
+        settings.zone.push(ZoneSetting {
+            domain: "catalog.example.".to_string(),
+            file: "../storage/test/catalog.zone".to_string(),
+            master: Some(Masters::from("192.0.2.1:53")),
+            catalog: true,
+            ..Default::default()
+        });
+        let mut server = OtterServer::new(settings);
+        server.init_load_storage().unwrap();
+        let catalog = DNSName::new("catalog.example.", None).unwrap();
+        let members: Vec<String> = server
+            .catalog
+            .members(&catalog)
+            .iter()
+            .map(|zone| zone.to_string())
+            .collect();
+        assert_eq!(
+            members,
+            vec!["example.com.", "example.net.", "example.org."]
+        );
+
+        // pretend the member zones are transferred
+        let soa = server.storage.get_zone_soa(&catalog).unwrap();
+        for member in server.catalog.members(&catalog) {
+            server.scheduler.register(member, &soa, false, None);
+        }
+        server.setting.zone[0].file = "../storage/test/catalog2.zone".to_string();
+        server.reload_zones().unwrap();
+        let members: Vec<String> = server
+            .catalog
+            .members(&catalog)
+            .iter()
+            .map(|zone| zone.to_string())
+            .collect();
+        assert_eq!(
+            members,
+            vec!["example.com.", "example.edu.", "example.net."]
+        );
+        let net = DNSName::new("example.net.", None).unwrap();
+        let org = DNSName::new("example.org.", None).unwrap();
+        let edu = DNSName::new("example.edu.", None).unwrap();
+        assert_eq!(server.scheduler.freshness(&net), Some(ZoneFreshness::Fresh));
+        assert_eq!(server.scheduler.freshness(&org), None);
+        assert_eq!(server.scheduler.freshness(&edu), None);
+    }
+
+    #[test]
+    fn readiness_after_failed_reload() {
+        let mut settings = Settings::default();
+        settings.server.strict = true;
+        settings.zone.push(ZoneSetting {
+            domain: "example.com.".to_string(),
+            file: "../storage/test/example.zone".to_string(),
+            ..Default::default()
+        });
+        let mut server = OtterServer::new(settings);
+        assert!(!server.state.is_ready());
+        server.init_load_storage().unwrap();
+        assert!(server.state.is_ready());
+        server.setting.zone[0].file = "./not-exist.zone".to_string();
+        assert!(server.reload_zones().is_err());
+        assert!(!server.state.is_ready());
+        server.setting.zone[0].file = "../storage/test/catalog.zone".to_string();
+        server.setting.zone[0].domain = "catalog.example.".to_string();
+        server.reload_zones().unwrap();
+        assert!(server.state.is_ready());
+    }
+
+    #[test]
+    fn load_storage_error_with_context() {
+        let file = std::env::temp_dir().join(format!("otter_bad_ttl_{}.zone", std::process::id()));
+        std::fs::write(
+            &file,
+            "$ORIGIN example.com.\n\n$TTL abc\n@ 3600 IN SOA ns1 admin 1 7200 3600 1209600 300\n",
+        )
+        .unwrap();
+        let file = file.to_str().unwrap().to_owned();
+        let mut settings = Settings::default();
+        settings.zone.push(ZoneSetting {
+            domain: "example.com".to_string(),
+            file: file.clone(),
+            ..Default::default()
+        });
+        let mut server = OtterServer::new(settings);
+        let err = server.init_load_storage().unwrap_err();
+        std::fs::remove_file(&file).unwrap();
+        assert_eq!(
+            err.to_string(),
+            format!("zone example.com (file {}) line 3: invalid TTL 'abc'", file)
+        );
+        assert!(matches!(
+            err.kind(),
+            OtterError::DNSProtoError(DNSProtoErr::ValidTTLErr(_))
+        ));
+        assert!(!server.state.is_ready());
+    }
+
+    #[test]
+    fn serve_pre_built_storage() {
+        let soa = DnsTypeSOA::new(
+            "ns1.example.com.",
+            "hostmaster.example.com.",
+            1,
+            7200,
+            3600,
+            1209600,
+            300,
+        )
+        .unwrap();
+        let storage = ZoneBuilder::new("example.com.")
+            .soa(3600, soa)
+            .build()
+            .unwrap();
+        let mut settings = Settings::default();
+        // the zone files of the setting are not loaded
+        settings.zone.push(ZoneSetting {
+            domain: "example.com.".to_string(),
+            file: "./not-exist.zone".to_string(),
+            ..Default::default()
+        });
+        let mut server = OtterServer::with_storage(settings, storage);
+        server.init_load_storage().unwrap();
+        assert!(server.state.is_ready());
+        let apex = DNSName::new("example.com.", None).unwrap();
+        assert_eq!(server.storage.get_zone_soa(&apex).unwrap().serial(), 1);
+    }
+
+    #[test]
+    fn startup_self_test() {
+        let mut settings = Settings::default();
+        settings.server.startup_selftest = SelfTestMode::Strict;
+        settings.zone.push(ZoneSetting {
+            domain: "example.com.".to_string(),
+            file: "../storage/test/example.zone".to_string(),
+            ..Default::default()
+        });
+        let mut server = OtterServer::new(settings.clone());
+        server.init_load_storage().unwrap();
+        let context = ServerContext::new(&settings).unwrap();
+        assert!(server.startup_self_test(&context).is_ok());
+
+        // zone file declares example.com. but configured as example.org.
+        settings.zone[0].domain = "example.org.".to_string();
+        let mut server = OtterServer::new(settings.clone());
+        server.init_load_storage().unwrap();
+        let context = ServerContext::new(&settings).unwrap();
+        match server.startup_self_test(&context) {
+            Err(OtterError::SelfTestError(err)) => assert!(err.starts_with("example.org.")),
+            _ => assert!(false, "self test should fail"),
         }
+        server.setting.server.startup_selftest = SelfTestMode::Warn;
+        assert!(server.startup_self_test(&context).is_ok());
     }
 }
@@ -0,0 +1,114 @@
+use dnsproto::dnsname::DNSName;
+use dnsproto::utils::fqdn;
+use otterlib::errors::DNSProtoErr;
+use otterlib::setting::{PolicyAction, PolicyRule};
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::str::FromStr;
+
+/// ttl of the fixed answer of a policy rule
+pub const POLICY_TTL: u32 = 60;
+
+/// PolicyMatch is the compiled action of a policy rule.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PolicyMatch {
+    NXDomain,
+    Drop,
+    Fixed(IpAddr),
+}
+
+/// ResponsePolicy holds the policy rules consulted before the zone data, a rule for
+/// `*.name` matches all names below name but not name itself.
+#[derive(Debug, Clone, Default)]
+pub struct ResponsePolicy {
+    rules: HashMap<DNSName, PolicyMatch>,
+}
+
+impl ResponsePolicy {
+    pub fn new(rules: &[PolicyRule]) -> Result<ResponsePolicy, DNSProtoErr> {
+        let mut policy = ResponsePolicy::default();
+        for rule in rules.iter() {
+            let name = DNSName::new(fqdn(rule.name.trim()).as_str(), None)?;
+            let action = match (rule.action, &rule.data) {
+                (PolicyAction::Nxdomain, _) => PolicyMatch::NXDomain,
+                (PolicyAction::Drop, _) => PolicyMatch::Drop,
+                (PolicyAction::Fixed, Some(data)) => {
+                    PolicyMatch::Fixed(IpAddr::from_str(data.trim())?)
+                }
+                (PolicyAction::Fixed, None) => {
+                    return Err(DNSProtoErr::GeneralErr(format!(
+                        "policy {} fixed action need an ip address data",
+                        rule.name
+                    )))
+                }
+            };
+            policy.rules.insert(name, action);
+        }
+        Ok(policy)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// lookup return the rule of the name, an exact rule has precedence over the wildcard
+    /// rules, and the wildcard rule of the closest enclosing name wins.
+    pub fn lookup(&self, name: &DNSName) -> Option<&PolicyMatch> {
+        if let Some(action) = self.rules.get(name) {
+            return Some(action);
+        }
+        let parent = name.parent()?;
+        for mut suffix in parent.iter_suffixes() {
+            if suffix.push_front("*").is_err() {
+                continue;
+            }
+            if let Some(action) = self.rules.get(&suffix) {
+                return Some(action);
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn rule(name: &str, action: PolicyAction, data: Option<&str>) -> PolicyRule {
+        PolicyRule {
+            name: name.to_string(),
+            action,
+            data: data.map(|data| data.to_string()),
+        }
+    }
+
+    #[test]
+    fn test_policy_lookup() {
+        let policy = ResponsePolicy::new(&[
+            rule("*.tracker.example", PolicyAction::Drop, None),
+            rule("ads.tracker.example.", PolicyAction::Nxdomain, None),
+            rule("*.sub.tracker.example.", PolicyAction::Nxdomain, None),
+            rule("ads.example", PolicyAction::Fixed, Some("192.0.2.10")),
+        ])
+        .unwrap();
+        let lookup = |name: &str| policy.lookup(&DNSName::new(name, None).unwrap()).cloned();
+        assert_eq!(
+            lookup("ADS.example."),
+            Some(PolicyMatch::Fixed("192.0.2.10".parse().unwrap()))
+        );
+        assert_eq!(lookup("www.ads.example."), None);
+        assert_eq!(lookup("a.tracker.example."), Some(PolicyMatch::Drop));
+        assert_eq!(lookup("a.b.tracker.example."), Some(PolicyMatch::Drop));
+        assert_eq!(lookup("tracker.example."), None);
+        assert_eq!(lookup("ads.tracker.example."), Some(PolicyMatch::NXDomain));
+        assert_eq!(
+            lookup("a.sub.tracker.example."),
+            Some(PolicyMatch::NXDomain)
+        );
+        assert_eq!(lookup("example."), None);
+        assert_eq!(lookup("."), None);
+
+        assert!(ResponsePolicy::new(&[rule("ads.example", PolicyAction::Fixed, None)]).is_err());
+        assert!(ResponsePolicy::default().is_empty());
+    }
+}
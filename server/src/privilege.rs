@@ -0,0 +1,190 @@
+use otterlib::errors::OtterError;
+use otterlib::setting::{ServerSetting, Settings};
+use std::ffi::CString;
+use std::fs::{File, OpenOptions};
+
+const PASSWD_BUFFER_SIZE: usize = 16384;
+
+/// lookup_user return (uid, primary gid) of the user name or numeric uid.
+fn lookup_user(user: &str) -> Result<(libc::uid_t, libc::gid_t), OtterError> {
+    let name = CString::new(user)
+        .map_err(|_| OtterError::PrivilegeError(format!("invalid user name: {}", user)))?;
+    let mut passwd: libc::passwd = unsafe { std::mem::zeroed() };
+    let mut buffer = vec![0 as libc::c_char; PASSWD_BUFFER_SIZE];
+    let mut result: *mut libc::passwd = std::ptr::null_mut();
+    let ret = unsafe {
+        if let Ok(uid) = user.parse::<libc::uid_t>() {
+            libc::getpwuid_r(
+                uid,
+                &mut passwd,
+                buffer.as_mut_ptr(),
+                buffer.len(),
+                &mut result,
+            )
+        } else {
+            libc::getpwnam_r(
+                name.as_ptr(),
+                &mut passwd,
+                buffer.as_mut_ptr(),
+                buffer.len(),
+                &mut result,
+            )
+        }
+    };
+    if ret != 0 || result.is_null() {
+        return Err(OtterError::PrivilegeError(format!(
+            "user: {} not found",
+            user
+        )));
+    }
+    Ok((passwd.pw_uid, passwd.pw_gid))
+}
+
+/// lookup_group return the gid of the group name or numeric gid.
+fn lookup_group(group: &str) -> Result<libc::gid_t, OtterError> {
+    let name = CString::new(group)
+        .map_err(|_| OtterError::PrivilegeError(format!("invalid group name: {}", group)))?;
+    let mut grp: libc::group = unsafe { std::mem::zeroed() };
+    let mut buffer = vec![0 as libc::c_char; PASSWD_BUFFER_SIZE];
+    let mut result: *mut libc::group = std::ptr::null_mut();
+    let ret = unsafe {
+        if let Ok(gid) = group.parse::<libc::gid_t>() {
+            libc::getgrgid_r(
+                gid,
+                &mut grp,
+                buffer.as_mut_ptr(),
+                buffer.len(),
+                &mut result,
+            )
+        } else {
+            libc::getgrnam_r(
+                name.as_ptr(),
+                &mut grp,
+                buffer.as_mut_ptr(),
+                buffer.len(),
+                &mut result,
+            )
+        }
+    };
+    if ret != 0 || result.is_null() {
+        return Err(OtterError::PrivilegeError(format!(
+            "group: {} not found",
+            group
+        )));
+    }
+    Ok(grp.gr_gid)
+}
+
+fn switch_group(gid: libc::gid_t) -> Result<(), OtterError> {
+    // clear supplementary groups which still hold the root group
+    if unsafe { libc::setgroups(1, &gid) } != 0 || unsafe { libc::setgid(gid) } != 0 {
+        return Err(OtterError::PrivilegeError(format!(
+            "change to gid: {} failed: {}",
+            gid,
+            std::io::Error::last_os_error()
+        )));
+    }
+    Ok(())
+}
+
+/// drop_privilege switch the process to the configured group and user, must be called
+/// after all listeners are bound. serving as root is refused unless allow_root is set.
+pub(crate) fn drop_privilege(setting: &ServerSetting) -> Result<(), OtterError> {
+    let user = match &setting.user {
+        Some(user) => Some(lookup_user(user)?),
+        None => None,
+    };
+    // group must be changed before user, otherwise we lose the permission to setgid
+    match (&setting.group, user) {
+        (Some(group), _) => switch_group(lookup_group(group)?)?,
+        (None, Some((_, primary_gid))) => switch_group(primary_gid)?,
+        _ => {}
+    }
+    if let Some((uid, _)) = user {
+        if unsafe { libc::setuid(uid) } != 0 {
+            return Err(OtterError::PrivilegeError(format!(
+                "change to uid: {} failed: {}",
+                uid,
+                std::io::Error::last_os_error()
+            )));
+        }
+        info!("drop privilege to uid: {} success", uid);
+    }
+    if unsafe { libc::geteuid() } == 0 && !setting.allow_root {
+        return Err(OtterError::PrivilegeError(
+            "refuse to serve as root, set server.user or server.allow_root: true".to_owned(),
+        ));
+    }
+    Ok(())
+}
+
+/// check_file_access make sure the zone files are readable and the log files are writable
+/// by current user.
+pub(crate) fn check_file_access(setting: &Settings) -> Result<(), OtterError> {
+    for zone in setting.zone.iter() {
+        if let Err(err) = File::open(&zone.file) {
+            return Err(OtterError::PrivilegeError(format!(
+                "zone file: {} is not readable by uid: {}: {}",
+                zone.file,
+                unsafe { libc::geteuid() },
+                err
+            )));
+        }
+    }
+    for log in setting.log.iter() {
+        if log.target == "stdout" || log.target == "stderr" {
+            continue;
+        }
+        if let Err(err) = OpenOptions::new()
+            .append(true)
+            .create(true)
+            .open(&log.target)
+        {
+            return Err(OtterError::PrivilegeError(format!(
+                "log file: {} is not writable by uid: {}: {}",
+                log.target,
+                unsafe { libc::geteuid() },
+                err
+            )));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use otterlib::setting::ZoneSetting;
+
+    #[test]
+    fn test_lookup_user_and_group() {
+        assert_eq!(lookup_user("root").unwrap().0, 0);
+        assert_eq!(lookup_user("0").unwrap().0, 0);
+        assert!(lookup_user("otter-not-exist-user").is_err());
+        assert_eq!(lookup_group("0").unwrap(), 0);
+        assert!(lookup_group("otter-not-exist-group").is_err());
+    }
+
+    #[test]
+    fn test_refuse_serve_as_root() {
+        let mut setting = ServerSetting::default();
+        if unsafe { libc::geteuid() } == 0 {
+            assert!(drop_privilege(&setting).is_err());
+            setting.allow_root = true;
+        }
+        assert!(drop_privilege(&setting).is_ok());
+    }
+
+    #[test]
+    fn test_check_file_access() {
+        let mut setting = Settings::default();
+        setting.zone.push(ZoneSetting {
+            domain: "example.com".to_string(),
+            file: "../storage/test/example.zone".to_string(),
+            ..Default::default()
+        });
+        assert!(check_file_access(&setting).is_ok());
+        setting.zone[0].file = "./not-exist.zone".to_string();
+        assert!(check_file_access(&setting).is_err());
+    }
+}
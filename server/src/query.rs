@@ -0,0 +1,1721 @@
+use crate::axfr::{
+    TransferFormat, TransferLimiter, DEFAULT_MAX_CONCURRENT_TRANSFERS,
+    DEFAULT_MAX_TRANSFERS_PER_CLIENT,
+};
+use crate::cache::{patch_response, CacheKey, ResponseCache};
+use crate::limits::ConnectionLimiter;
+use crate::logging;
+use crate::metrics::{Metrics, Stage};
+use crate::policy::{PolicyMatch, ResponsePolicy, POLICY_TTL};
+use crate::querylog::QueryLog;
+use crate::stats::QueryStats;
+use crate::transfer::{Master, SystemClock, TransferScheduler, ZoneFreshness};
+use crate::update::UpdatePolicy;
+use dnsproto::dnsname::DNSName;
+use dnsproto::message::Message;
+use dnsproto::meta::{DNSClass, DNSType, OpCode, RCode, RecordOrder, ResourceRecord};
+use dnsproto::qtype::{DNSWireFrame, DnsTypeA, DnsTypeAAAA, DnsTypeCNAME};
+use dnsproto::utils::fqdn;
+use otterlib::errors::DNSProtoErr;
+use otterlib::setting::{AnyOverUdp, Settings, ACL};
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use storage::lookup::{negative_soa, LookupResult, RRSetHandle};
+use storage::storage::SafeRBTreeStorage;
+
+/// default milliseconds to answer a query before SERVFAIL
+const DEFAULT_QUERY_TIMEOUT: u64 = 2000;
+
+/// ServerContext holds the settings used by every query process.
+#[derive(Debug, Clone, Default)]
+pub struct ServerContext {
+    pub(crate) max_edns_size: u16,
+    // zone name => acl list for zones which set allow_query
+    pub(crate) allow_query: HashMap<DNSName, Vec<ACL>>,
+    // zone name => acl list of the zone acl setting
+    pub(crate) zone_acl: HashMap<DNSName, Vec<ACL>>,
+    // zone name => update policy for zones which set update_policy, used instead of the
+    // update acl
+    pub(crate) update_policies: HashMap<DNSName, UpdatePolicy>,
+    // zone name => format of the outgoing transfer messages
+    pub(crate) transfer_formats: HashMap<DNSName, TransferFormat>,
+    // secondary zone name => masters in the configured order, updates of the zone are
+    // forwarded to the first one
+    pub(crate) masters: HashMap<DNSName, Vec<Master>>,
+    pub(crate) policy: ResponsePolicy,
+    pub(crate) metrics: Arc<Metrics>,
+    pub(crate) stats: Arc<QueryStats>,
+    pub(crate) scheduler: Arc<TransferScheduler>,
+    pub(crate) cache: Option<Arc<ResponseCache>>,
+    pub(crate) query_timeout: Duration,
+    pub(crate) transfers: Arc<TransferLimiter>,
+    // tcp client connections and in-flight udp queries, shared with the server state
+    pub(crate) connections: Arc<ConnectionLimiter>,
+    pub(crate) max_ttl: Option<u32>,
+    // offset of the next rotated answer, None if rotate_answers is not set
+    pub(crate) rotation: Option<Arc<AtomicUsize>>,
+    pub(crate) chain: ChainPolicy,
+    // explain the refusal of recursive queries for names out of all zones
+    pub(crate) refuse_recursive: bool,
+    // time the parse, lookup and encode stages of each query
+    pub(crate) detailed_timing: bool,
+    // the queries are written to the query log file instead of the logger when it is set
+    pub(crate) query_log: Option<Arc<QueryLog>>,
+    // how ANY queries over udp are answered
+    pub(crate) any_over_udp: AnyOverUdp,
+}
+
+/// default max cname records followed for one answer
+const DEFAULT_MAX_CHAIN_DEPTH: usize = 8;
+/// EDE info code(rfc8914) of a cname chain not answered, 0 is Other Error
+const EDE_OTHER_ERROR: u16 = 0;
+/// EDE info code(rfc8914) of a recursive query for a name out of all zones
+const EDE_NOT_AUTHORITATIVE: u16 = 20;
+/// EDE info code(rfc8914) of an ANY query over udp refused by any_over_udp
+const EDE_NOT_SUPPORTED: u16 = 21;
+
+/// ChainPolicy is how far the cname chain of an answer is followed.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct ChainPolicy {
+    pub(crate) max_depth: usize,
+    pub(crate) across_zones: bool,
+}
+
+impl Default for ChainPolicy {
+    fn default() -> Self {
+        ChainPolicy {
+            max_depth: DEFAULT_MAX_CHAIN_DEPTH,
+            across_zones: true,
+        }
+    }
+}
+
+/// CNameChain is the aliases followed from the query name and how the chase stops.
+struct CNameChain {
+    aliases: Vec<RRSetHandle>,
+    // lookup result of the last target, None when the target is not followed
+    end: Option<LookupResult>,
+    // the chain leaves the zone of the query name
+    cross_zone: bool,
+}
+
+impl ServerContext {
+    pub fn new(setting: &Settings) -> Result<ServerContext, DNSProtoErr> {
+        let mut allow_query = HashMap::new();
+        let mut zone_acl = HashMap::new();
+        let mut update_policies = HashMap::new();
+        let mut transfer_formats = HashMap::new();
+        let mut masters = HashMap::new();
+        for zone in setting.zone.iter() {
+            let zone_name = DNSName::new(fqdn(zone.domain.as_str()).as_str(), None)?;
+            if let Some(acl_ids) = &zone.allow_query {
+                let acl_list = acl_ids
+                    .iter()
+                    .filter_map(|id| setting.get_acl_by_id(id))
+                    .collect();
+                allow_query.insert(zone_name.clone(), acl_list);
+            }
+            if let Some(acl_ids) = &zone.acl {
+                let acl_list = acl_ids
+                    .iter()
+                    .filter_map(|id| setting.get_acl_by_id(id))
+                    .collect();
+                zone_acl.insert(zone_name.clone(), acl_list);
+            }
+            if !zone.update_policy.is_empty() {
+                let policy = UpdatePolicy::new(&zone.update_policy)?;
+                update_policies.insert(zone_name.clone(), policy);
+            }
+            transfer_formats.insert(zone_name.clone(), TransferFormat::from(zone));
+            let mut zone_masters = vec![];
+            for id in zone.master.iter().flat_map(|master| master.ids()) {
+                let remote = match setting.get_remote_by_id(id) {
+                    Some(remote) => remote,
+                    None => {
+                        warn!("zone {} master {} is not a remote", zone.domain, id);
+                        continue;
+                    }
+                };
+                if let Some(key) = &remote.key {
+                    if setting.get_key_by_id(key).is_none() {
+                        warn!("remote {} reference unknown key {}", remote.id, key);
+                    }
+                }
+                let source = remote.source_addr(setting.server.transfer_source.as_deref());
+                match (remote.socket_addr(), source) {
+                    (Ok(addr), Ok(source)) => zone_masters.push(Master {
+                        id: remote.id,
+                        addr,
+                        key: remote.key,
+                        source,
+                    }),
+                    (Err(err), _) | (_, Err(err)) => {
+                        warn!("zone {} master is not valid: {}", zone.domain, err)
+                    }
+                }
+            }
+            if !zone_masters.is_empty() {
+                masters.insert(zone_name, zone_masters);
+            }
+        }
+        let cache = match setting.server.response_cache_size {
+            0 => None,
+            size => Some(Arc::new(ResponseCache::new(size, Arc::new(SystemClock)))),
+        };
+        Ok(ServerContext {
+            max_edns_size: setting.server.edns_size(),
+            allow_query,
+            zone_acl,
+            update_policies,
+            transfer_formats,
+            masters,
+            policy: ResponsePolicy::new(&setting.policy)?,
+            metrics: Arc::new(Metrics::default()),
+            stats: Arc::new(QueryStats::default()),
+            scheduler: Arc::new(TransferScheduler::default()),
+            cache,
+            query_timeout: Duration::from_millis(
+                setting
+                    .server
+                    .query_timeout
+                    .unwrap_or(DEFAULT_QUERY_TIMEOUT),
+            ),
+            transfers: Arc::new(TransferLimiter::new(
+                setting
+                    .server
+                    .max_concurrent_transfers
+                    .unwrap_or(DEFAULT_MAX_CONCURRENT_TRANSFERS),
+                setting
+                    .server
+                    .max_transfers_per_client
+                    .unwrap_or(DEFAULT_MAX_TRANSFERS_PER_CLIENT),
+                setting.server.transfer_rate_limit,
+            )),
+            connections: Arc::new(ConnectionLimiter::default()),
+            max_ttl: setting.server.max_ttl,
+            rotation: match setting.server.rotate_answers {
+                true => Some(Arc::new(AtomicUsize::new(0))),
+                false => None,
+            },
+            chain: ChainPolicy {
+                max_depth: setting
+                    .server
+                    .max_chain_depth
+                    .unwrap_or(DEFAULT_MAX_CHAIN_DEPTH),
+                across_zones: setting.server.follow_cname_across_zones.unwrap_or(true),
+            },
+            refuse_recursive: setting.server.refuse_recursive_only_queries,
+            detailed_timing: setting.server.detailed_timing,
+            query_log: None,
+            any_over_udp: setting.server.any_over_udp,
+        })
+    }
+
+    /// chase_cname follow the cname chain from the alias rrset until a name has no alias,
+    /// the target is out of the zones the client can query or in another zone when
+    /// follow_cname_across_zones is false. a chain loops or longer than max_chain_depth
+    /// return the reason for the extended error.
+    fn chase_cname(
+        &self,
+        storage: &SafeRBTreeStorage,
+        query: &QueryContext,
+        qtype: DNSType,
+        alias: RRSetHandle,
+    ) -> Result<CNameChain, &'static str> {
+        let mut chain = CNameChain {
+            aliases: vec![],
+            end: None,
+            cross_zone: false,
+        };
+        let mut visited: Vec<DNSName> = vec![];
+        let mut zone = None;
+        let mut current = alias;
+        loop {
+            let target = {
+                let rrset = current.read().unwrap();
+                let cname = match rrset.content().first() {
+                    Some(cname) => cname,
+                    None => return Ok(chain),
+                };
+                let owner = cname.get_dname().clone();
+                if zone.is_none() {
+                    zone = storage.find_zone(&owner);
+                }
+                visited.push(owner);
+                cname
+                    .rdata_as::<DnsTypeCNAME>()
+                    .map(|cname| cname.name().clone())
+            };
+            chain.aliases.push(current);
+            let target = match target {
+                Some(target) => target,
+                None => return Ok(chain),
+            };
+            if visited.contains(&target) {
+                return Err("cname loop detected");
+            }
+            if chain.aliases.len() > self.chain.max_depth {
+                return Err("cname chain exceeds max_chain_depth");
+            }
+            let target_zone = match storage.find_zone(&target) {
+                Some(target_zone) => target_zone,
+                None => return Ok(chain),
+            };
+            if zone.as_ref() != Some(&target_zone) {
+                if !self.chain.across_zones
+                    || !self.is_query_allowed(&target, &query.peer.ip(), &query.tsig)
+                    || self.scheduler.freshness(&target_zone) == Some(ZoneFreshness::Expired)
+                {
+                    return Ok(chain);
+                }
+                chain.cross_zone = true;
+            }
+            match storage.lookup(&target, qtype) {
+                LookupResult::CName(next) => current = next,
+                end => {
+                    chain.end = Some(end);
+                    return Ok(chain);
+                }
+            }
+        }
+    }
+
+    /// answer_order return the order of the answer records, each query takes the next
+    /// offset when rotate_answers is set.
+    pub(crate) fn answer_order(&self) -> RecordOrder {
+        match &self.rotation {
+            Some(rotation) => RecordOrder::Rotated(rotation.fetch_add(1, Ordering::Relaxed)),
+            None => RecordOrder::Stable,
+        }
+    }
+
+    /// the action(transfer, update, notify) on the zone is allowed only for clients matching
+    /// an acl of the zone with the same action, and signed by the key if the acl has one.
+    pub(crate) fn is_action_allowed(
+        &self,
+        zone: &DNSName,
+        action: &str,
+        remote: &IpAddr,
+        tsig: &TsigState,
+    ) -> bool {
+        let key = tsig.key_name();
+        match self.zone_acl.get(zone) {
+            Some(acl_list) => acl_list.iter().any(|acl| {
+                acl.action.eq_ignore_ascii_case(action) && acl.allows(remote, key.as_deref())
+            }),
+            None => false,
+        }
+    }
+
+    /// the closest zone with allow_query setting decide if the client can query the name,
+    /// names not belong to those zones are allowed.
+    fn is_query_allowed(&self, dnsname: &DNSName, remote: &IpAddr, tsig: &TsigState) -> bool {
+        let key = tsig.key_name();
+        match dnsname
+            .iter_suffixes()
+            .find_map(|suffix| self.allow_query.get(&suffix))
+        {
+            Some(acl_list) => acl_list
+                .iter()
+                .any(|acl| acl.allows(remote, key.as_deref())),
+            None => true,
+        }
+    }
+}
+
+/// Transport is the protocol the query is received from.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Transport {
+    Udp,
+    Tcp,
+}
+
+impl Transport {
+    pub fn name(self) -> &'static str {
+        match self {
+            Transport::Udp => "udp",
+            Transport::Tcp => "tcp",
+        }
+    }
+}
+
+/// TsigState is the transaction signature verification result of the query. signed
+/// queries are not verified yet, so every query is Unsigned for now.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TsigState {
+    Unsigned,
+    /// the query is signed by the key with a valid signature
+    Verified(DNSName),
+}
+
+impl TsigState {
+    /// key_name return the name of the key which signed the message.
+    pub fn key_name(&self) -> Option<String> {
+        match self {
+            TsigState::Unsigned => None,
+            TsigState::Verified(key) => Some(key.to_string()),
+        }
+    }
+}
+
+/// QueryContext holds the state of a single query.
+#[derive(Debug, Clone)]
+pub struct QueryContext {
+    pub transport: Transport,
+    pub peer: SocketAddr,
+    pub tsig: TsigState,
+    /// the query is answered SERVFAIL instead of walking the zone after the deadline
+    pub deadline: Option<Instant>,
+}
+
+impl QueryContext {
+    pub fn new(transport: Transport, peer: SocketAddr) -> QueryContext {
+        QueryContext {
+            transport,
+            peer,
+            tsig: TsigState::Unsigned,
+            deadline: None,
+        }
+    }
+
+    pub fn with_deadline(mut self, deadline: Instant) -> QueryContext {
+        self.deadline = Some(deadline);
+        self
+    }
+
+    pub fn from_udp(&self) -> bool {
+        self.transport == Transport::Udp
+    }
+}
+
+/// Response is the answer of handle_query, the message is not encoded yet.
+#[derive(Debug)]
+pub struct Response {
+    pub message: Message,
+    /// the largest udp response of the client, a greater udp response is truncated
+    pub max_size: u16,
+    // the zone and the smallest answer ttl when the response can be cached
+    cacheable: Option<(DNSName, u32)>,
+}
+
+impl Response {
+    fn new(message: Message, max_size: u16) -> Response {
+        Response {
+            message,
+            max_size,
+            cacheable: None,
+        }
+    }
+}
+
+/// report_query_message log the query to the query log file or the logger, only the
+/// sampled queries are logged
+fn report_query_message(
+    dnsname: &DNSName,
+    dnstype: &DNSType,
+    query: &QueryContext,
+    context: &ServerContext,
+) {
+    if !logging::sample_query() {
+        return;
+    }
+    let message = format!(
+        "receive query: {} IN {} from {} +{}",
+        dnsname.to_string(),
+        dnstype.to_string(),
+        query.peer.to_string(),
+        query.transport.name()
+    );
+    match &context.query_log {
+        Some(query_log) => {
+            let now = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default();
+            query_log.log(format!(
+                "{}.{:03} {}",
+                now.as_secs(),
+                now.subsec_millis(),
+                message
+            ))
+        }
+        None => info!("{}", message),
+    }
+}
+
+/// zone_operation answer REFUSED when the notify or update is not allowed by the zone acl,
+/// otherwise NOTIMP since the operation has no handler yet.
+fn zone_operation(
+    parsed_message: &Message,
+    action: &str,
+    query: &QueryContext,
+    context: &ServerContext,
+) -> Response {
+    let allowed = parsed_message
+        .query_name_and_type()
+        .map(|(zone, _)| context.is_action_allowed(zone, action, &query.peer.ip(), &query.tsig))
+        .unwrap_or(false);
+    if !allowed {
+        debug!(
+            "refuse {} from {}: not in {} acl",
+            action,
+            query.peer.to_string(),
+            action
+        );
+        context.metrics.incr_refused_by_acl();
+        return Response::new(Message::new_refused_message(parsed_message), u16::MAX);
+    }
+    Response::new(
+        Message::new_not_implemented_message(parsed_message),
+        u16::MAX,
+    )
+}
+
+/// update_operation check the records of the update section with the update policy of
+/// the zone, the update acl is checked instead when the zone has no policy. the whole
+/// update is refused when any record is not allowed.
+fn update_operation(
+    parsed_message: &Message,
+    query: &QueryContext,
+    context: &ServerContext,
+) -> Response {
+    let policy = parsed_message
+        .query_name_and_type()
+        .ok()
+        .and_then(|(zone, _)| context.update_policies.get(zone));
+    let policy = match policy {
+        Some(policy) => policy,
+        None => return zone_operation(parsed_message, "update", query, context),
+    };
+    let key = match &query.tsig {
+        TsigState::Verified(key) => Some(key),
+        TsigState::Unsigned => None,
+    };
+    if let Some(rr) = policy.denied_record(parsed_message, &query.peer.ip(), key) {
+        debug!(
+            "refuse update from {}: {} {} not allowed by update policy",
+            query.peer.to_string(),
+            rr.get_dname().to_string(),
+            rr.get_type()
+        );
+        context.metrics.incr_refused_by_acl();
+        return Response::new(Message::new_refused_message(parsed_message), u16::MAX);
+    }
+    Response::new(
+        Message::new_not_implemented_message(parsed_message),
+        u16::MAX,
+    )
+}
+
+/// handle_query is the main dns process logic function, implements the rfc1034 for the
+/// parsed query from udp and tcp listeners but not axfr and ixfr. if return err then
+/// just ignore the query.
+pub fn handle_query(
+    storage: &SafeRBTreeStorage,
+    parsed_message: &Message,
+    query: &QueryContext,
+    context: &ServerContext,
+) -> Result<Response, DNSProtoErr> {
+    let (from_udp, remote) = (query.from_udp(), &query.peer);
+    if !parsed_message.is_query() {
+        // no need to process just drop packet
+        return Err(DNSProtoErr::ValidQueryDomainErr);
+    }
+    // only standard query is answered from the zone data
+    match parsed_message.header.op_code() {
+        OpCode::Query => {}
+        // notify and update of primary zones are only checked, applying them is not
+        // implemented yet. updates of secondary zones are forwarded before process_message
+        OpCode::Notify => return Ok(zone_operation(parsed_message, "notify", query, context)),
+        OpCode::Update => return Ok(update_operation(parsed_message, query, context)),
+        op_code => {
+            debug!(
+                "not implemented opcode {:?} from {}",
+                op_code,
+                remote.to_string()
+            );
+            let message = Message::new_not_implemented_message(parsed_message);
+            return Ok(Response::new(message, u16::MAX));
+        }
+    }
+
+    // queries without exactly one question are answered here(formerr or cookie only)
+    let (mut message, max_size, terminator) =
+        Message::new_message_from_query(parsed_message, from_udp, context.max_edns_size);
+    if terminator {
+        return Ok(Response::new(message, max_size));
+    }
+    let (dnsname, dnstype) = parsed_message.query_name_and_type()?;
+    report_query_message(dnsname, dnstype, query, context);
+    // policy rules have precedence over the zone data
+    if let Some(action) = context.policy.lookup(dnsname) {
+        info!(
+            "query {} {} from {} match policy {:?}",
+            dnsname.to_string(),
+            dnstype.to_string(),
+            remote.to_string(),
+            action
+        );
+        let message = apply_policy(action, message, (dnsname, dnstype), context)?;
+        return Ok(Response::new(message, max_size));
+    }
+    // ANY over udp is refused or truncated by any_over_udp, over tcp it is always answered
+    if from_udp && *dnstype == DNSType::Any {
+        let outcome = context.any_over_udp;
+        context.metrics.incr_any_over_udp(outcome);
+        match outcome {
+            AnyOverUdp::Refuse => {
+                debug!("refuse ANY query over udp from {}", remote.to_string());
+                message.header.set_aa(false);
+                message.header.set_rcode(RCode::Refused);
+                message.add_extended_error(EDE_NOT_SUPPORTED, "ANY over UDP");
+                context.stats.record(None, dnsname, dnstype, RCode::Refused);
+                return Ok(Response::new(message, max_size));
+            }
+            AnyOverUdp::Truncate => {
+                Message::new_tc_message_from_build_message(&mut message);
+                context.stats.record(None, dnsname, dnstype, RCode::NoError);
+                return Ok(Response::new(message, max_size));
+            }
+            AnyOverUdp::Answer => {}
+        }
+    }
+    // 0. check the query name belong to a configured zone and the client is allowed to query it,
+    // a hidden zone is filtered out for the clients not allowed to query it, the name is
+    // answered from the enclosing zone as if the subtree of the hidden zone did not exist
+    let mut zone = storage.find_zone(dnsname);
+    let mut hidden = false;
+    while let Some(apex) = zone.take() {
+        let acl_name = match hidden {
+            true => &apex,
+            false => dnsname,
+        };
+        if !storage.is_zone_hidden(&apex)
+            || context.is_query_allowed(acl_name, &remote.ip(), &query.tsig)
+        {
+            zone = Some(apex);
+            break;
+        }
+        hidden = true;
+        zone = apex.parent().and_then(|parent| storage.find_zone(&parent));
+    }
+    let zone = match zone {
+        Some(zone) => zone,
+        None => {
+            debug!(
+                "refuse query {} from {}: not authoritative for it",
+                dnsname.to_string(),
+                remote.to_string()
+            );
+            context.metrics.incr_refused_not_auth();
+            context.stats.record(None, dnsname, dnstype, RCode::Refused);
+            message.header.set_aa(false);
+            message.header.set_rcode(RCode::Refused);
+            // tell the resolver we are not a recursive server for the name
+            if context.refuse_recursive && parsed_message.header.rd() {
+                message.add_extended_error(EDE_NOT_AUTHORITATIVE, "Not Authoritative");
+            }
+            return Ok(Response::new(message, max_size));
+        }
+    };
+    // the acl of the enclosing zone is checked instead of the one of the hidden zone
+    let acl_name = match hidden {
+        true => &zone,
+        false => dnsname,
+    };
+    if !context.is_query_allowed(acl_name, &remote.ip(), &query.tsig) {
+        debug!(
+            "refuse query {} from {}: not in allow_query list",
+            dnsname.to_string(),
+            remote.to_string()
+        );
+        context.metrics.incr_refused_by_acl();
+        context
+            .stats
+            .record(Some(&zone), dnsname, dnstype, RCode::Refused);
+        message.header.set_rcode(RCode::Refused);
+        return Ok(Response::new(message, max_size));
+    }
+    // secondary zone expired should not answer authoritatively
+    match context.scheduler.freshness(&zone) {
+        Some(ZoneFreshness::Expired) => {
+            debug!("zone of {} is expired", dnsname.to_string());
+            context.metrics.incr_servfail_expired();
+            context
+                .stats
+                .record(Some(&zone), dnsname, dnstype, RCode::ServerFailure);
+            message.set_serverfail();
+            return Ok(Response::new(message, max_size));
+        }
+        Some(ZoneFreshness::Stale) => context.metrics.incr_stale_answers(),
+        _ => {}
+    }
+    if let Some(deadline) = query.deadline {
+        if Instant::now() > deadline {
+            warn!(
+                "query {} from {} exceed the deadline",
+                dnsname.to_string(),
+                remote.to_string()
+            );
+            context.metrics.incr_servfail_timeout();
+            message.set_serverfail();
+            return Ok(Response::new(message, max_size));
+        }
+    }
+    // walk down from the zone apex to the query name(rfc1034 4.3.2)
+    let order = context.answer_order();
+    let mut min_ttl = None;
+    let mut answers = vec![];
+    let mut cacheable = !hidden;
+    let mut result = match hidden {
+        true => match storage.lookup(&zone, DNSType::SOA) {
+            LookupResult::Answer(soa) => Some(LookupResult::NxDomain { soa }),
+            _ => None,
+        },
+        false => Some(storage.lookup(dnsname, *dnstype)),
+    };
+    // an alias is answered with the cname chain and then the records of the last target
+    if let Some(LookupResult::CName(alias)) = &result {
+        match context.chase_cname(storage, query, *dnstype, alias.clone()) {
+            Ok(chain) => {
+                for rrset in chain.aliases.iter() {
+                    let rrset = rrset.read().unwrap();
+                    min_ttl = rrset
+                        .content()
+                        .iter()
+                        .map(|rr| rr.get_ttl())
+                        .chain(min_ttl)
+                        .min();
+                    answers.extend(rrset.to_records(order));
+                }
+                // the cache only drops entries when the zone of the query name changes
+                cacheable = !chain.cross_zone;
+                result = chain.end;
+            }
+            Err(reason) => {
+                warn!(
+                    "query {} from {}: {}",
+                    dnsname.to_string(),
+                    remote.to_string(),
+                    reason
+                );
+                context.metrics.incr_servfail_chain();
+                context
+                    .stats
+                    .record(Some(&zone), dnsname, dnstype, RCode::ServerFailure);
+                message.set_serverfail();
+                message.add_extended_error(EDE_OTHER_ERROR, reason);
+                return Ok(Response::new(message, max_size));
+            }
+        }
+    }
+    if !answers.is_empty() {
+        message.header.set_aa(true);
+    }
+    match result {
+        Some(LookupResult::Answer(rrset)) | Some(LookupResult::CName(rrset)) => {
+            let rrset = rrset.read().unwrap();
+            min_ttl = rrset
+                .content()
+                .iter()
+                .map(|rr| rr.get_ttl())
+                .chain(min_ttl)
+                .min();
+            message.header.set_aa(true);
+            answers.extend(rrset.to_records(order));
+        }
+        Some(LookupResult::Any(rrsets)) => {
+            for rrset in rrsets.iter() {
+                let rrset = rrset.read().unwrap();
+                min_ttl = rrset
+                    .content()
+                    .iter()
+                    .map(|rr| rr.get_ttl())
+                    .chain(min_ttl)
+                    .min();
+                answers.extend(rrset.to_records(order));
+            }
+            message.header.set_aa(true);
+        }
+        // referrals are not authoritative, the glue goes to the additional section
+        Some(LookupResult::Delegation { ns, glue }) => {
+            message.update_authority(&ns.read().unwrap());
+            for rrset in glue.iter() {
+                for rr in rrset.read().unwrap().content().iter() {
+                    message.append_additional(rr.clone());
+                }
+            }
+        }
+        // the name exists(maybe an empty non-terminal) but has no such type
+        Some(LookupResult::NoData { soa }) => {
+            message.header.set_aa(true);
+            if let Some(soa) = negative_soa(&soa.read().unwrap()) {
+                message.append_authority(soa);
+            }
+        }
+        // the rcode of a cname chain is the one of the last target(rfc6604)
+        Some(LookupResult::NxDomain { soa }) => {
+            debug!("can't find record {} in zone database", dnsname.to_string());
+            message.header.set_aa(true);
+            message.set_nxdomain();
+            if let Some(soa) = negative_soa(&soa.read().unwrap()) {
+                message.append_authority(soa);
+            }
+            // signed zones prove the query name does not exist to the dnssec clients
+            if answers.is_empty()
+                && !hidden
+                && parsed_message.edns().map_or(false, |edns| edns.do_bit())
+            {
+                for rr in storage.nxdomain_proof(dnsname) {
+                    message.append_authority(rr);
+                }
+            }
+        }
+        Some(LookupResult::OutOfZone) => {
+            message.header.set_rcode(RCode::Refused);
+            return Ok(Response::new(message, max_size));
+        }
+        // the chain stops before a target not answered from the local zones
+        None => {}
+    }
+    message.update_answer(answers);
+    if !cacheable {
+        min_ttl = None;
+    }
+    if let Some(max_ttl) = context.max_ttl {
+        message.cap_ttl(max_ttl);
+        min_ttl = min_ttl.map(|ttl| ttl.min(max_ttl));
+    }
+    context
+        .stats
+        .record(Some(&zone), dnsname, dnstype, message.header.rcode());
+    Ok(Response {
+        message,
+        max_size,
+        cacheable: min_ttl.map(|ttl| (zone, ttl)),
+    })
+}
+
+/// apply_policy answer the query with the matched policy rule, a fixed address is only
+/// answered to the query of the same address type.
+fn apply_policy(
+    action: &PolicyMatch,
+    mut message: Message,
+    (dnsname, dnstype): (&DNSName, &DNSType),
+    context: &ServerContext,
+) -> Result<Message, DNSProtoErr> {
+    match action {
+        PolicyMatch::Drop => {
+            context.metrics.incr_policy_drop();
+            return Err(DNSProtoErr::DroppedByPolicy);
+        }
+        PolicyMatch::NXDomain => {
+            context.metrics.incr_policy_nxdomain();
+            message.set_nxdomain();
+        }
+        PolicyMatch::Fixed(address) => {
+            context.metrics.incr_policy_fixed();
+            let data: Option<Box<dyn DNSWireFrame>> = match (address, dnstype) {
+                (IpAddr::V4(address), DNSType::A) => {
+                    Some(Box::new(DnsTypeA::new(&address.to_string())?))
+                }
+                (IpAddr::V6(address), DNSType::AAAA) => {
+                    Some(Box::new(DnsTypeAAAA::new(&address.to_string())?))
+                }
+                _ => None,
+            };
+            if data.is_some() {
+                message.append_answer(ResourceRecord::new(
+                    &dnsname.to_string(),
+                    *dnstype,
+                    DNSClass::IN,
+                    POLICY_TTL,
+                    data,
+                )?);
+            }
+        }
+    }
+    if let Some(max_ttl) = context.max_ttl {
+        message.cap_ttl(max_ttl);
+    }
+    context
+        .stats
+        .record(None, dnsname, dnstype, message.header.rcode());
+    Ok(message)
+}
+
+/// cached_response answer the query from the response cache when the query would be
+/// answered from the zone data, the cached response is patched with the raw query.
+fn cached_response(
+    storage: &SafeRBTreeStorage,
+    parsed_message: &Message,
+    raw: &[u8],
+    query: &QueryContext,
+    context: &ServerContext,
+) -> Option<Vec<u8>> {
+    let cache = context.cache.as_ref()?;
+    if !parsed_message.is_query() || parsed_message.header.op_code() != OpCode::Query {
+        return None;
+    }
+    let (dnsname, dnstype) = parsed_message.query_name_and_type().ok()?;
+    let from_udp = query.from_udp();
+    if context.policy.lookup(dnsname).is_some()
+        || (from_udp && *dnstype == DNSType::Any && context.any_over_udp != AnyOverUdp::Answer)
+        || !context.is_query_allowed(dnsname, &query.peer.ip(), &query.tsig)
+    {
+        return None;
+    }
+    let zone = storage.find_zone(dnsname)?;
+    let freshness = context.scheduler.freshness(&zone);
+    if freshness == Some(ZoneFreshness::Expired) {
+        return None;
+    }
+    let (_, max_size, terminator) =
+        Message::new_message_from_query(parsed_message, from_udp, context.max_edns_size);
+    if terminator {
+        return None;
+    }
+    let key = CacheKey::new(dnsname, dnstype, parsed_message.edns(), from_udp);
+    match cache
+        .get(&key)
+        .and_then(|(response, _)| patch_response(response, raw, from_udp, max_size))
+    {
+        Some(response) => {
+            if freshness == Some(ZoneFreshness::Stale) {
+                context.metrics.incr_stale_answers();
+            }
+            context.metrics.incr_cache_hits();
+            context
+                .stats
+                .record(Some(&zone), dnsname, dnstype, RCode::NoError);
+            Some(response)
+        }
+        None => {
+            context.metrics.incr_cache_misses();
+            None
+        }
+    }
+}
+
+/// StageTimer record the time since the previous stage ended, it does not read the clock
+/// unless detailed_timing is set.
+struct StageTimer<'a> {
+    metrics: &'a Metrics,
+    last: Option<Instant>,
+}
+
+impl<'a> StageTimer<'a> {
+    fn new(context: &'a ServerContext) -> StageTimer<'a> {
+        StageTimer {
+            metrics: &context.metrics,
+            last: match context.detailed_timing {
+                true => Some(Instant::now()),
+                false => None,
+            },
+        }
+    }
+
+    fn end(&mut self, stage: Stage) {
+        if let Some(last) = self.last {
+            let now = Instant::now();
+            self.metrics.observe_stage(stage, now - last);
+            self.last = Some(now);
+        }
+    }
+}
+
+/// process_message parse the raw query, answer it by handle_query and encode the response
+/// for the transport. the response is truncated when it is great than the udp size of the
+/// client. if return err then just ignore the packet. the time to answer is recorded in the
+/// latency metrics by the transport and the response rcode.
+pub fn process_message(
+    storage: SafeRBTreeStorage,
+    message: &[u8],
+    remote: &SocketAddr,
+    from_udp: bool,
+    context: &ServerContext,
+) -> Result<Vec<u8>, DNSProtoErr> {
+    let start = Instant::now();
+    let result = answer_message(storage, message, remote, from_udp, context);
+    if let Ok(response) = &result {
+        let transport = if from_udp {
+            Transport::Udp
+        } else {
+            Transport::Tcp
+        };
+        // the rcode is the low 4 bits of the fourth header byte, tcp responses start with
+        // the 2 bytes length
+        let offset = if from_udp { 3 } else { 5 };
+        let rcode = response.get(offset).map_or(0, |flags| flags & 0x0f);
+        context
+            .metrics
+            .observe_query(transport, rcode, start.elapsed());
+    }
+    result
+}
+
+fn answer_message(
+    storage: SafeRBTreeStorage,
+    message: &[u8],
+    remote: &SocketAddr,
+    from_udp: bool,
+    context: &ServerContext,
+) -> Result<Vec<u8>, DNSProtoErr> {
+    let mut timer = StageTimer::new(context);
+    // plain queries only need the header, the question and the OPT record, other opcodes
+    // and messages with several questions go through the full parser
+    let parsed_message = match Message::parse_query_header_and_question(message) {
+        Ok(view) if view.header.op_code() == OpCode::Query && view.header.question_count() <= 1 => {
+            Ok(Message::from_query_view(&view))
+        }
+        _ => Message::parse_dns_message(message),
+    };
+    let parsed_message = match parsed_message {
+        Ok(parsed_message) => parsed_message,
+        Err(DNSProtoErr::ExcessiveRecords) => {
+            debug!(
+                "packet from {} claims more records than it holds",
+                remote.to_string()
+            );
+            return match Message::new_format_error_message(message) {
+                Some(mut response) => response.encode(from_udp),
+                None => Err(DNSProtoErr::ExcessiveRecords),
+            };
+        }
+        Err(err) => return Err(err),
+    };
+    timer.end(Stage::Parse);
+    let transport = if from_udp {
+        Transport::Udp
+    } else {
+        Transport::Tcp
+    };
+    let query =
+        QueryContext::new(transport, *remote).with_deadline(Instant::now() + context.query_timeout);
+    if let Some(response) = cached_response(&storage, &parsed_message, message, &query, context) {
+        return Ok(response);
+    }
+    let Response {
+        mut message,
+        max_size,
+        cacheable,
+    } = handle_query(&storage, &parsed_message, &query, context)?;
+    timer.end(Stage::Lookup);
+    let message_byte = message.encode(from_udp)?;
+    if let (Some(cache), Some((zone, ttl))) = (context.cache.as_ref(), cacheable) {
+        let (dnsname, dnstype) = parsed_message.query_name_and_type()?;
+        let key = CacheKey::new(dnsname, dnstype, parsed_message.edns(), from_udp);
+        cache.insert(key, zone, message_byte.clone(), ttl);
+    }
+    // when query from udp and message size great than max_size(maybe limit by edns size)
+    let response = if from_udp && message_byte.len() > (max_size as usize) {
+        let tc_message = Message::new_tc_message_from_build_message(&mut message);
+        tc_message.encode(from_udp)?
+    } else {
+        message_byte
+    };
+    timer.end(Stage::Encode);
+    Ok(response)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use dnsproto::edns::EDNS;
+    use dnsproto::meta::{Header, Question};
+    use storage::storage::ZoneOption;
+
+    fn build_query(domain: &str, dtype: DNSType, class: DNSClass, edns: Option<u16>) -> Message {
+        let mut message = Message::new_with_header(Header::new());
+        message.set_question(Question::new(domain, dtype, class).unwrap());
+        if let Some(payload_size) = edns {
+            let mut opt = EDNS::new();
+            opt.set_payload_size(payload_size);
+            message.append_edns(opt);
+        }
+        message
+    }
+
+    #[test]
+    fn test_handle_query() {
+        let mut settings = Settings::default();
+        settings.server.max_edns_size = 1232;
+        let context = ServerContext::new(&settings).unwrap();
+        let storage =
+            SafeRBTreeStorage::new_zone_from_file("../storage/test/example.zone", None).unwrap();
+        let query = QueryContext::new(Transport::Udp, "127.0.0.1:5353".parse().unwrap());
+        for (case, domain, dtype, class, edns, rcode, aa, answers, max_size) in [
+            (
+                "answer",
+                "mail.example.com.",
+                DNSType::A,
+                DNSClass::IN,
+                None,
+                RCode::NoError,
+                true,
+                1,
+                512,
+            ),
+            (
+                "nxdomain",
+                "missing.example.com.",
+                DNSType::A,
+                DNSClass::IN,
+                None,
+                RCode::NameError,
+                true,
+                0,
+                512,
+            ),
+            (
+                "refused",
+                "example.org.",
+                DNSType::A,
+                DNSClass::IN,
+                None,
+                RCode::Refused,
+                false,
+                0,
+                512,
+            ),
+            (
+                "chaos txt",
+                "version.bind.",
+                DNSType::TXT,
+                DNSClass::CH,
+                None,
+                RCode::NoError,
+                false,
+                1,
+                512,
+            ),
+            (
+                "edns clamp",
+                "mail.example.com.",
+                DNSType::A,
+                DNSClass::IN,
+                Some(4096),
+                RCode::NoError,
+                true,
+                1,
+                1232,
+            ),
+            (
+                "edns",
+                "mail.example.com.",
+                DNSType::A,
+                DNSClass::IN,
+                Some(1000),
+                RCode::NoError,
+                true,
+                1,
+                1000,
+            ),
+        ]
+        .iter()
+        {
+            let message = build_query(domain, *dtype, *class, *edns);
+            let response = handle_query(&storage, &message, &query, &context).unwrap();
+            assert_eq!(response.message.header.rcode(), *rcode, "{}", case);
+            assert_eq!(response.message.header.aa(), *aa, "{}", case);
+            assert_eq!(response.message.answers().len(), *answers, "{}", case);
+            assert_eq!(response.max_size, *max_size, "{}", case);
+            assert_eq!(
+                response.message.edns().map(|edns| edns.payload_size()),
+                edns.map(|_| *max_size),
+                "{}",
+                case
+            );
+        }
+
+        // the zone is not walked after the deadline
+        let query = query.with_deadline(Instant::now() - Duration::from_millis(1));
+        let message = build_query("mail.example.com.", DNSType::A, DNSClass::IN, None);
+        let response = handle_query(&storage, &message, &query, &context).unwrap();
+        assert_eq!(response.message.header.rcode(), RCode::ServerFailure);
+        assert_eq!(context.metrics.servfail_timeout(), 1);
+    }
+
+    #[test]
+    fn test_handle_query_cname_chain() {
+        let mut storage = SafeRBTreeStorage::new_zone_from_str(
+            "$ORIGIN example.com.\n\
+             @ 300 IN SOA ns admin 1 7200 3600 1209600 300\n\
+             a 300 IN CNAME b\n\
+             b 300 IN CNAME c\n\
+             c 300 IN CNAME host\n\
+             host 300 IN A 192.0.2.1\n\
+             away 300 IN CNAME www.example.org.\n\
+             missing 300 IN CNAME nothing.example.org.\n\
+             loop1 300 IN CNAME loop2\n\
+             loop2 300 IN CNAME loop1\n",
+            None,
+        )
+        .unwrap();
+        storage
+            .update_zone_from_str(
+                "$ORIGIN example.org.\n\
+                 @ 300 IN SOA ns admin 1 7200 3600 1209600 300\n\
+                 www 300 IN A 192.0.2.2\n",
+                None,
+                &ZoneOption::default(),
+            )
+            .unwrap();
+        let query = QueryContext::new(Transport::Udp, "127.0.0.1:5353".parse().unwrap());
+        let answer = |context: &ServerContext, name: &str| {
+            let message = build_query(name, DNSType::A, DNSClass::IN, Some(1232));
+            handle_query(&storage, &message, &query, context).unwrap()
+        };
+        let types = |response: &Response| -> Vec<DNSType> {
+            response
+                .message
+                .answers()
+                .iter()
+                .map(|rr| rr.get_type())
+                .collect()
+        };
+
+        // in zone chain of 3
+        let context = ServerContext::new(&Settings::default()).unwrap();
+        let response = answer(&context, "a.example.com.");
+        assert_eq!(response.message.header.rcode(), RCode::NoError);
+        assert!(response.message.header.aa());
+        assert_eq!(
+            types(&response),
+            vec![DNSType::CNAME, DNSType::CNAME, DNSType::CNAME, DNSType::A]
+        );
+        assert!(response.cacheable.is_some());
+
+        // cross zone chain is followed by default and still authoritative
+        let response = answer(&context, "away.example.com.");
+        assert!(response.message.header.aa());
+        assert_eq!(types(&response), vec![DNSType::CNAME, DNSType::A]);
+        assert!(response.cacheable.is_none());
+        // the rcode is the one of the last target
+        let response = answer(&context, "missing.example.com.");
+        assert_eq!(response.message.header.rcode(), RCode::NameError);
+        assert_eq!(types(&response), vec![DNSType::CNAME]);
+
+        let mut settings = Settings::default();
+        settings.server.follow_cname_across_zones = Some(false);
+        let context = ServerContext::new(&settings).unwrap();
+        let response = answer(&context, "away.example.com.");
+        assert_eq!(response.message.header.rcode(), RCode::NoError);
+        assert!(response.message.header.aa());
+        assert_eq!(types(&response), vec![DNSType::CNAME]);
+
+        // a loop of two names
+        let response = answer(&context, "loop1.example.com.");
+        assert_eq!(response.message.header.rcode(), RCode::ServerFailure);
+        assert!(response.message.answers().is_empty());
+        assert_eq!(
+            response.message.edns().unwrap().extended_error(),
+            Some((EDE_OTHER_ERROR, "cname loop detected".to_string()))
+        );
+        assert_eq!(context.metrics.servfail_chain(), 1);
+
+        // the chain of 3 is longer than 2
+        settings.server.max_chain_depth = Some(2);
+        let context = ServerContext::new(&settings).unwrap();
+        let response = answer(&context, "a.example.com.");
+        assert_eq!(response.message.header.rcode(), RCode::ServerFailure);
+        assert_eq!(
+            response.message.edns().unwrap().extended_error(),
+            Some((
+                EDE_OTHER_ERROR,
+                "cname chain exceeds max_chain_depth".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn test_handle_query_nxdomain_proof() {
+        let context = ServerContext::new(&Settings::default()).unwrap();
+        let storage =
+            SafeRBTreeStorage::new_zone_from_file("../storage/test/nsec.zone", None).unwrap();
+        let query = QueryContext::new(Transport::Udp, "127.0.0.1:5353".parse().unwrap());
+        let authority = |dnssec_ok: bool| -> Vec<(String, DNSType)> {
+            let mut message = Message::new_with_header(Header::new());
+            message.set_question(
+                Question::new("zzz.nsec.example.", DNSType::A, DNSClass::IN).unwrap(),
+            );
+            let mut opt = EDNS::new();
+            opt.set_dnssec_enable(dnssec_ok);
+            message.append_edns(opt);
+            let response = handle_query(&storage, &message, &query, &context).unwrap();
+            assert_eq!(response.message.header.rcode(), RCode::NameError);
+            response
+                .message
+                .authorities()
+                .iter()
+                .map(|rr| (rr.get_dname().to_string(), rr.get_type()))
+                .collect()
+        };
+        assert_eq!(
+            authority(false),
+            vec![("nsec.example.".to_string(), DNSType::SOA)]
+        );
+        assert_eq!(
+            authority(true),
+            vec![
+                ("nsec.example.".to_string(), DNSType::SOA),
+                ("x.nsec.example.".to_string(), DNSType::NSEC),
+                ("x.nsec.example.".to_string(), DNSType::RRSIG),
+                ("nsec.example.".to_string(), DNSType::NSEC),
+                ("nsec.example.".to_string(), DNSType::RRSIG),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_zone_acl_with_key() {
+        let mut settings = Settings::default();
+        settings.zone.push(otterlib::setting::ZoneSetting {
+            domain: "example.com".to_string(),
+            file: "../storage/test/example.zone".to_string(),
+            acl: Some(vec!["transfer_key".to_string(), "notify_any".to_string()]),
+            ..Default::default()
+        });
+        settings.key.push(otterlib::setting::KeySetting {
+            id: "slave1-key".to_string(),
+            algorithm: "hmac-sha256".to_string(),
+            secret: "Wg==".to_string(),
+        });
+        for (id, address, action) in [
+            ("transfer_key", "192.168.2.1", "transfer"),
+            ("notify_any", "any", "notify"),
+        ]
+        .iter()
+        {
+            settings.acl.push(ACL {
+                id: id.to_string(),
+                address: address.to_string(),
+                action: action.to_string(),
+                key: Some("slave1-key".to_string()),
+            });
+        }
+        let context = ServerContext::new(&settings).unwrap();
+        let zone = DNSName::new("example.com.", None).unwrap();
+        let signed = TsigState::Verified(DNSName::new("slave1-key.", None).unwrap());
+        let (slave, other) = (
+            "192.168.2.1".parse().unwrap(),
+            "192.168.4.1".parse().unwrap(),
+        );
+        assert!(context.is_action_allowed(&zone, "transfer", &slave, &signed));
+        assert!(!context.is_action_allowed(&zone, "transfer", &slave, &TsigState::Unsigned));
+        // signed by the key but from the wrong address
+        assert!(!context.is_action_allowed(&zone, "transfer", &other, &signed));
+        assert!(context.is_action_allowed(&zone, "notify", &other, &signed));
+        assert!(!context.is_action_allowed(&zone, "notify", &other, &TsigState::Unsigned));
+        assert!(!context.is_action_allowed(&zone, "update", &slave, &signed));
+
+        // unsigned notify is refused before it reaches the not implemented handler
+        let storage =
+            SafeRBTreeStorage::new_zone_from_file("../storage/test/example.zone", None).unwrap();
+        let mut message = build_query("example.com.", DNSType::SOA, DNSClass::IN, None);
+        message.header.set_op_code(OpCode::Notify);
+        let mut query = QueryContext::new(Transport::Udp, "192.168.4.1:5353".parse().unwrap());
+        let response = handle_query(&storage, &message, &query, &context).unwrap();
+        assert_eq!(response.message.header.rcode(), RCode::Refused);
+        query.tsig = signed;
+        let response = handle_query(&storage, &message, &query, &context).unwrap();
+        assert_eq!(response.message.header.rcode(), RCode::NotImplemented);
+    }
+
+    #[test]
+    fn test_update_with_policy() {
+        let mut settings = Settings::default();
+        settings.zone.push(otterlib::setting::ZoneSetting {
+            domain: "example.com".to_string(),
+            file: "../storage/test/example.zone".to_string(),
+            update_policy: vec![otterlib::setting::UpdatePolicyRule {
+                key: Some("ddns-key".to_string()),
+                name: Some("host1.example.com".to_string()),
+                types: vec!["A".to_string()],
+                ..Default::default()
+            }],
+            ..Default::default()
+        });
+        let context = ServerContext::new(&settings).unwrap();
+        let storage =
+            SafeRBTreeStorage::new_zone_from_file("../storage/test/example.zone", None).unwrap();
+        let mut query = QueryContext::new(Transport::Udp, "192.168.4.1:5353".parse().unwrap());
+        query.tsig = TsigState::Verified(DNSName::new("ddns-key.", None).unwrap());
+        let update = |records: &[(&str, DNSType)]| {
+            let mut message = build_query("example.com.", DNSType::SOA, DNSClass::IN, None);
+            message.header.set_op_code(OpCode::Update);
+            for (owner, dtype) in records.iter() {
+                message.append_authority(
+                    ResourceRecord::new(owner, *dtype, DNSClass::IN, 300, None).unwrap(),
+                );
+            }
+            message
+        };
+        // the allowed update reach the not implemented handler
+        let message = update(&[("host1.example.com.", DNSType::A)]);
+        let response = handle_query(&storage, &message, &query, &context).unwrap();
+        assert_eq!(response.message.header.rcode(), RCode::NotImplemented);
+        // one record not allowed refuse the whole update
+        let message = update(&[
+            ("host1.example.com.", DNSType::A),
+            ("host1.example.com.", DNSType::TXT),
+        ]);
+        let response = handle_query(&storage, &message, &query, &context).unwrap();
+        assert_eq!(response.message.header.rcode(), RCode::Refused);
+        query.tsig = TsigState::Unsigned;
+        let message = update(&[("host1.example.com.", DNSType::A)]);
+        let response = handle_query(&storage, &message, &query, &context).unwrap();
+        assert_eq!(response.message.header.rcode(), RCode::Refused);
+
+        // the policy is compiled with the context
+        settings.zone[0].update_policy[0].types = vec!["BAD".to_string()];
+        assert!(ServerContext::new(&settings).is_err());
+    }
+
+    #[test]
+    fn test_handle_query_hidden_zone() {
+        let mut settings = Settings::default();
+        settings.zone.push(otterlib::setting::ZoneSetting {
+            domain: "example.com".to_string(),
+            file: "../storage/test/example.zone".to_string(),
+            allow_query: Some(vec!["internal".to_string()]),
+            hidden: true,
+            ..Default::default()
+        });
+        settings.acl.push(ACL {
+            id: "internal".to_string(),
+            address: "10.0.0.0/8".to_string(),
+            action: "query".to_string(),
+            key: None,
+        });
+        let context = ServerContext::new(&settings).unwrap();
+        let storage =
+            SafeRBTreeStorage::new_zone_from_file("../storage/test/example.zone", None).unwrap();
+        let zone = DNSName::new("example.com.", None).unwrap();
+        let message = build_query("mail.example.com.", DNSType::A, DNSClass::IN, None);
+        let inside = QueryContext::new(Transport::Udp, "10.0.0.1:5353".parse().unwrap());
+        let outside = QueryContext::new(Transport::Udp, "192.0.2.1:5353".parse().unwrap());
+        for hidden in [false, true].iter() {
+            storage.set_zone_hidden(&zone, *hidden);
+            let response = handle_query(&storage, &message, &inside, &context).unwrap();
+            assert_eq!(response.message.header.rcode(), RCode::NoError);
+            assert!(response.message.header.aa());
+            // the refusal of a hidden zone is the same as a name not served
+            let response = handle_query(&storage, &message, &outside, &context).unwrap();
+            assert_eq!(response.message.header.rcode(), RCode::Refused);
+        }
+        assert_eq!(context.metrics.refused_by_acl(), 1);
+        assert_eq!(context.metrics.refused_not_auth(), 1);
+
+        // a hidden child is answered from the hosted parent as if it did not exist
+        settings.zone[0].domain = "sub.example.com".to_string();
+        let context = ServerContext::new(&settings).unwrap();
+        let mut storage = SafeRBTreeStorage::new_zone_from_str(
+            "$ORIGIN sub.example.com.\n\
+             @ 300 IN SOA ns admin 1 7200 3600 1209600 300\n\
+             @ 300 IN NS ns\n\
+             ns 300 IN A 192.0.2.53\n",
+            None,
+        )
+        .unwrap();
+        storage
+            .update_zone("../storage/test/example.zone", None)
+            .unwrap();
+        storage.set_zone_hidden(&DNSName::new("sub.example.com.", None).unwrap(), true);
+        let rcode = |name: &str, query: &QueryContext| {
+            let message = build_query(name, DNSType::A, DNSClass::IN, None);
+            let response = handle_query(&storage, &message, query, &context).unwrap();
+            (
+                response.message.header.rcode(),
+                response.cacheable.is_some(),
+            )
+        };
+        assert_eq!(
+            rcode("ns.sub.example.com.", &inside),
+            (RCode::NoError, true)
+        );
+        assert_eq!(
+            rcode("ns.sub.example.com.", &outside),
+            (RCode::NameError, false)
+        );
+        assert_eq!(
+            rcode("sub.example.com.", &outside),
+            rcode("none.example.com.", &outside)
+        );
+    }
+
+    #[test]
+    fn test_process_message_truncated() {
+        let context = ServerContext::new(&Settings::default()).unwrap();
+        let mut zone = String::from(
+            "example.org. 300 IN SOA ns.example.org. admin.example.org. 1 7200 3600 1209600 300\n",
+        );
+        for index in 0..20 {
+            zone.push_str(&format!("big.example.org. 300 IN TXT \"{:060}\"\n", index));
+        }
+        let storage = SafeRBTreeStorage::new_zone_from_str(&zone, None).unwrap();
+        let remote: SocketAddr = "127.0.0.1:5353".parse().unwrap();
+        let mut query = build_query("big.example.org.", DNSType::TXT, DNSClass::IN, None);
+        let query = query.encode(true).unwrap();
+
+        // the udp response of a query without edns is limited to 512 bytes
+        let response = process_message(storage.clone(), &query, &remote, true, &context).unwrap();
+        assert!(response.len() <= 512);
+        let response = Message::parse_dns_message(&response).unwrap();
+        assert!(response.header.tc());
+        assert!(response.answers().is_empty());
+
+        let response = process_message(storage, &query, &remote, false, &context).unwrap();
+        let response = Message::parse_dns_message(&response[2..]).unwrap();
+        assert!(!response.header.tc());
+        assert_eq!(response.answers().len(), 20);
+    }
+
+    #[test]
+    fn test_process_message_rd_flags() {
+        let storage =
+            SafeRBTreeStorage::new_zone_from_file("../storage/test/example.zone", None).unwrap();
+        let remote: SocketAddr = "127.0.0.1:5353".parse().unwrap();
+        let mut settings = Settings::default();
+        let default_context = ServerContext::new(&settings).unwrap();
+        settings.server.refuse_recursive_only_queries = true;
+        let refuse_context = ServerContext::new(&settings).unwrap();
+        for (case, domain, rd, ra, refuse, rcode, ede) in [
+            (
+                "in zone",
+                "mail.example.com.",
+                false,
+                false,
+                true,
+                RCode::NoError,
+                false,
+            ),
+            (
+                "in zone rd",
+                "mail.example.com.",
+                true,
+                false,
+                true,
+                RCode::NoError,
+                false,
+            ),
+            (
+                "in zone rd ra",
+                "mail.example.com.",
+                true,
+                true,
+                true,
+                RCode::NoError,
+                false,
+            ),
+            (
+                "out of zone",
+                "www.example.org.",
+                false,
+                false,
+                true,
+                RCode::Refused,
+                false,
+            ),
+            (
+                "out of zone ra",
+                "www.example.org.",
+                false,
+                true,
+                true,
+                RCode::Refused,
+                false,
+            ),
+            (
+                "out of zone rd",
+                "www.example.org.",
+                true,
+                false,
+                true,
+                RCode::Refused,
+                true,
+            ),
+            (
+                "out of zone rd ra",
+                "www.example.org.",
+                true,
+                true,
+                true,
+                RCode::Refused,
+                true,
+            ),
+            (
+                "option off",
+                "www.example.org.",
+                true,
+                false,
+                false,
+                RCode::Refused,
+                false,
+            ),
+        ]
+        .iter()
+        {
+            let mut query = build_query(domain, DNSType::A, DNSClass::IN, Some(1232));
+            query.header.set_rd(*rd);
+            let mut query = query.encode(true).unwrap();
+            // the ra bit of a query is meaningless and never echoed
+            if *ra {
+                query[3] |= 0x80;
+            }
+            let context = if *refuse {
+                &refuse_context
+            } else {
+                &default_context
+            };
+            let response =
+                process_message(storage.clone(), &query, &remote, true, context).unwrap();
+            let response = Message::parse_dns_message(&response).unwrap();
+            assert_eq!(response.header.rcode(), *rcode, "{}", case);
+            assert_eq!(response.header.rd(), *rd, "{}", case);
+            assert!(!response.header.ra(), "{}", case);
+            let expected = match ede {
+                true => Some((EDE_NOT_AUTHORITATIVE, "Not Authoritative".to_string())),
+                false => None,
+            };
+            assert_eq!(
+                response.edns().unwrap().extended_error(),
+                expected,
+                "{}",
+                case
+            );
+        }
+    }
+
+    #[test]
+    fn test_process_message_any_over_udp() {
+        let storage =
+            SafeRBTreeStorage::new_zone_from_file("../storage/test/example.zone", None).unwrap();
+        let remote: SocketAddr = "127.0.0.1:5353".parse().unwrap();
+        let mut query = build_query("example.com.", DNSType::Any, DNSClass::IN, Some(1232));
+        let query = query.encode(true).unwrap();
+        let mut settings = Settings::default();
+        for mode in [AnyOverUdp::Refuse, AnyOverUdp::Truncate, AnyOverUdp::Answer].iter() {
+            settings.server.any_over_udp = *mode;
+            let context = ServerContext::new(&settings).unwrap();
+            let response =
+                process_message(storage.clone(), &query, &remote, true, &context).unwrap();
+            let response = Message::parse_dns_message(&response).unwrap();
+            let ede = response.edns().unwrap().extended_error();
+            match mode {
+                AnyOverUdp::Refuse => {
+                    assert_eq!(response.header.rcode(), RCode::Refused);
+                    assert!(!response.header.tc());
+                    assert!(response.answers().is_empty());
+                    assert_eq!(ede, Some((EDE_NOT_SUPPORTED, "ANY over UDP".to_string())));
+                }
+                AnyOverUdp::Truncate => {
+                    assert_eq!(response.header.rcode(), RCode::NoError);
+                    assert!(response.header.tc());
+                    assert!(response.answers().is_empty());
+                    assert!(response.authorities().is_empty());
+                    assert_eq!(ede, None);
+                }
+                AnyOverUdp::Answer => {
+                    assert_eq!(response.header.rcode(), RCode::NoError);
+                    assert!(!response.header.tc());
+                    assert!(response.header.aa());
+                    assert!(!response.answers().is_empty());
+                }
+            }
+            assert_eq!(context.metrics.any_over_udp(*mode), 1, "{:?}", mode);
+
+            // tcp is always answered and not counted
+            let response =
+                process_message(storage.clone(), &query, &remote, false, &context).unwrap();
+            let response = Message::parse_dns_message(&response[2..]).unwrap();
+            assert_eq!(response.header.rcode(), RCode::NoError);
+            assert!(!response.header.tc());
+            let types: Vec<DNSType> = response.answers().iter().map(|rr| rr.get_type()).collect();
+            assert!(types.contains(&DNSType::SOA));
+            assert!(types.contains(&DNSType::NS));
+            assert_eq!(context.metrics.any_over_udp(*mode), 1, "{:?}", mode);
+        }
+    }
+
+    #[test]
+    fn test_process_message_wildcard_owner() {
+        let context = ServerContext::new(&Settings::default()).unwrap();
+        let remote: SocketAddr = "127.0.0.1:5353".parse().unwrap();
+        let storage = SafeRBTreeStorage::new_zone_from_str(
+            "example.org. 300 IN SOA ns.example.org. admin.example.org. 1 7200 3600 1209600 300\n\
+             *.example.org. 300 IN A 192.0.2.1\n",
+            None,
+        )
+        .unwrap();
+        let mut query = build_query("a.b.example.org.", DNSType::A, DNSClass::IN, None);
+        let query = query.encode(true).unwrap();
+        let response = process_message(storage.clone(), &query, &remote, true, &context).unwrap();
+        // the answer owner points to the query name right after the header
+        let qname = DNSName::new("a.b.example.org.", None).unwrap();
+        let answer_offset = 12 + qname.to_binary(None).len() + 4;
+        assert_eq!(&response[answer_offset..answer_offset + 2], &[0xc0, 0x0c]);
+        assert!(!response.windows(2).any(|label| label == [1, b'*']));
+        let response = Message::parse_dns_message(&response).unwrap();
+        assert_eq!(response.answers()[0].get_dname(), &qname);
+
+        // the record stored in the zone keep the wildcard owner
+        let wildcard = DNSName::new("*.example.org.", None).unwrap();
+        let rrset = storage
+            .clone()
+            .search_rrset(&wildcard, &DNSType::A)
+            .unwrap();
+        assert_eq!(rrset.read().unwrap().content()[0].get_dname(), &wildcard);
+    }
+
+    #[test]
+    fn test_process_message_truncated_at_edns_limit() {
+        // the default limit is 1232 even if the client advertise 4096
+        let context = ServerContext::new(&Settings::default()).unwrap();
+        assert_eq!(context.max_edns_size, 1232);
+        let remote: SocketAddr = "127.0.0.1:5353".parse().unwrap();
+        // a txt record of five strings, the response grows one byte with each char
+        let storage = |size: usize| {
+            let chunk = size / 5;
+            let strings: Vec<String> = (0..5)
+                .map(|index| {
+                    let len = if index == 4 { size - chunk * 4 } else { chunk };
+                    format!("\"{}\"", "a".repeat(len))
+                })
+                .collect();
+            let zone = format!(
+                "example.org. 300 IN SOA ns.example.org. admin.example.org. 1 7200 3600 1209600 300\n\
+                 big.example.org. 300 IN TXT {}\n",
+                strings.join(" ")
+            );
+            SafeRBTreeStorage::new_zone_from_str(&zone, None).unwrap()
+        };
+        let mut query = build_query("big.example.org.", DNSType::TXT, DNSClass::IN, Some(4096));
+        let query = query.encode(true).unwrap();
+        let tcp_size = |size: usize| {
+            process_message(storage(size), &query, &remote, false, &context)
+                .unwrap()
+                .len()
+                - 2
+        };
+        let size = 1000 + 1232 - tcp_size(1000);
+        assert_eq!(tcp_size(size), 1232);
+
+        // exactly the limit is sent as is
+        let response = process_message(storage(size), &query, &remote, true, &context).unwrap();
+        assert_eq!(response.len(), 1232);
+        let response = Message::parse_dns_message(&response).unwrap();
+        assert!(!response.header.tc());
+        assert_eq!(response.answers().len(), 1);
+        assert_eq!(response.edns().unwrap().payload_size(), 1232);
+
+        // one byte over the limit is truncated
+        let response = process_message(storage(size + 1), &query, &remote, true, &context).unwrap();
+        assert!(response.len() <= 1232);
+        let response = Message::parse_dns_message(&response).unwrap();
+        assert!(response.header.tc());
+        assert!(response.answers().is_empty());
+    }
+}
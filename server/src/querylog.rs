@@ -0,0 +1,349 @@
+#[cfg(unix)]
+use crate::syslog::{severity, Syslog};
+use otterlib::setting::Log;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{sync_channel, Receiver, SyncSender};
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// query log lines buffered between the query path and the writer thread
+pub const QUERY_LOG_QUEUE: usize = 65536;
+// lines written and flushed together at most
+const QUERY_LOG_BATCH: usize = 1024;
+const DEFAULT_QUERY_LOG_KEEP: usize = 7;
+const SECONDS_PER_DAY: u64 = 86400;
+
+/// Rotation is when the query log file is moved aside and a new one is started.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Rotation {
+    /// before a write makes the file greater than the bytes
+    Size(u64),
+    /// at the first write of each utc day
+    Daily,
+}
+
+/// query_log_setting return the first log setting which writes the queries to a file, the
+/// queries are logged at info level.
+pub fn query_log_setting(logs: &[Log]) -> Option<&Log> {
+    logs.iter().find(|log| {
+        let level = log.query.as_deref().unwrap_or("off").to_lowercase();
+        log.target != "stdout"
+            && log.target != "stderr"
+            && log.target != "syslog"
+            && ["info", "debug", "trace"].contains(&level.as_str())
+    })
+}
+
+/// QueryLog send the query lines to the writer thread of the file. a line is dropped and
+/// counted instead of waiting when the queue is full, so the query path never blocks.
+#[derive(Debug)]
+pub struct QueryLog {
+    sender: SyncSender<String>,
+    dropped: Arc<AtomicU64>,
+}
+
+impl QueryLog {
+    /// open the target file of the log setting and start its writer thread.
+    pub fn open(setting: &Log) -> io::Result<QueryLog> {
+        let rotation = match setting.rotate_size_mb {
+            Some(size) => Rotation::Size(size.saturating_mul(1024 * 1024)),
+            None => Rotation::Daily,
+        };
+        let keep = setting.keep.unwrap_or(DEFAULT_QUERY_LOG_KEEP);
+        let writer = QueryLogWriter::open(Path::new(&setting.target), rotation, keep, unix_now())?;
+        let (query_log, receiver) = QueryLog::channel(QUERY_LOG_QUEUE);
+        let dropped = query_log.dropped.clone();
+        std::thread::Builder::new()
+            .name("query-log".to_owned())
+            .spawn(move || write_query_log(receiver, writer, dropped))?;
+        Ok(query_log)
+    }
+
+    /// open_syslog start the writer thread which send the query lines of the log setting
+    /// to the syslog at info level.
+    #[cfg(unix)]
+    pub fn open_syslog(setting: &Log) -> io::Result<QueryLog> {
+        let syslog = Syslog::open(setting);
+        let (query_log, receiver) = QueryLog::channel(QUERY_LOG_QUEUE);
+        std::thread::Builder::new()
+            .name("query-syslog".to_owned())
+            .spawn(move || write_query_syslog(receiver, syslog))?;
+        Ok(query_log)
+    }
+
+    fn channel(capacity: usize) -> (QueryLog, Receiver<String>) {
+        let (sender, receiver) = sync_channel(capacity);
+        let query_log = QueryLog {
+            sender,
+            dropped: Arc::new(AtomicU64::new(0)),
+        };
+        (query_log, receiver)
+    }
+
+    pub fn log(&self, line: String) {
+        if self.sender.try_send(line).is_err() {
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// dropped return the lines dropped since the log is opened.
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+}
+
+// write_query_log write the lines in batches until all senders are dropped
+fn write_query_log(
+    receiver: Receiver<String>,
+    mut writer: QueryLogWriter,
+    dropped: Arc<AtomicU64>,
+) {
+    let mut batch = Vec::with_capacity(QUERY_LOG_BATCH);
+    let mut reported = 0;
+    while let Ok(line) = receiver.recv() {
+        batch.push(line);
+        while batch.len() < QUERY_LOG_BATCH {
+            match receiver.try_recv() {
+                Ok(line) => batch.push(line),
+                Err(_) => break,
+            }
+        }
+        if let Err(err) = writer.write_batch(&batch, unix_now()) {
+            error!("write query log {} fail: {}", writer.path.display(), err);
+        }
+        batch.clear();
+        let total = dropped.load(Ordering::Relaxed);
+        if total != reported {
+            warn!(
+                "query log queue is full, {} lines dropped",
+                total - reported
+            );
+            reported = total;
+        }
+    }
+    if let Err(err) = writer.sync() {
+        error!("sync query log {} fail: {}", writer.path.display(), err);
+    }
+}
+
+// write_query_syslog send the lines until all senders are dropped, each failure of the
+// syslog socket is reported once, so the warnings are paced by the retry backoff
+#[cfg(unix)]
+fn write_query_syslog(receiver: Receiver<String>, syslog: Syslog) {
+    let mut reported = 0;
+    while let Ok(line) = receiver.recv() {
+        syslog.send(severity(log::Level::Info), &line);
+        let failures = syslog.failures();
+        if failures != reported {
+            warn!(
+                "query syslog is not reachable, {} failures and {} lines dropped",
+                failures,
+                syslog.dropped()
+            );
+            reported = failures;
+        }
+    }
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0)
+}
+
+/// QueryLogWriter append the lines to the file and rotate it, the rotated files are named
+/// like query.log.1(the newest) to query.log.<keep>.
+#[derive(Debug)]
+pub struct QueryLogWriter {
+    path: PathBuf,
+    rotation: Rotation,
+    keep: usize,
+    file: BufWriter<File>,
+    size: u64,
+    // utc day of the current file
+    day: u64,
+}
+
+impl QueryLogWriter {
+    pub fn open(
+        path: &Path,
+        rotation: Rotation,
+        keep: usize,
+        now: u64,
+    ) -> io::Result<QueryLogWriter> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        let size = file.metadata()?.len();
+        Ok(QueryLogWriter {
+            path: path.to_path_buf(),
+            rotation,
+            keep,
+            file: BufWriter::new(file),
+            size,
+            day: now / SECONDS_PER_DAY,
+        })
+    }
+
+    /// write_batch append the lines at the unix time now, the file is rotated first when
+    /// it is due.
+    pub fn write_batch(&mut self, lines: &[String], now: u64) -> io::Result<()> {
+        let batch_size: u64 = lines.iter().map(|line| line.len() as u64 + 1).sum();
+        let rotate = match self.rotation {
+            Rotation::Size(limit) => self.size > 0 && self.size + batch_size > limit,
+            Rotation::Daily => now / SECONDS_PER_DAY != self.day,
+        };
+        if rotate {
+            self.rotate(now)?;
+        }
+        for line in lines {
+            writeln!(self.file, "{}", line)?;
+        }
+        self.file.flush()?;
+        self.size += batch_size;
+        Ok(())
+    }
+
+    fn sync(&mut self) -> io::Result<()> {
+        self.file.flush()?;
+        self.file.get_ref().sync_all()
+    }
+
+    fn rotated_path(&self, index: usize) -> PathBuf {
+        let mut path = self.path.clone().into_os_string();
+        path.push(format!(".{}", index));
+        PathBuf::from(path)
+    }
+
+    // rotate sync the current file, shift the rotated files by one and remove the oldest
+    fn rotate(&mut self, now: u64) -> io::Result<()> {
+        self.sync()?;
+        if self.keep == 0 {
+            fs::remove_file(&self.path)?;
+        } else {
+            match fs::remove_file(self.rotated_path(self.keep)) {
+                Err(err) if err.kind() != io::ErrorKind::NotFound => return Err(err),
+                _ => {}
+            }
+            for index in (1..self.keep).rev() {
+                let from = self.rotated_path(index);
+                if from.exists() {
+                    fs::rename(&from, self.rotated_path(index + 1))?;
+                }
+            }
+            fs::rename(&self.path, self.rotated_path(1))?;
+        }
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        self.file = BufWriter::new(file);
+        self.size = 0;
+        self.day = now / SECONDS_PER_DAY;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("otter-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn file_count(dir: &Path) -> usize {
+        fs::read_dir(dir).unwrap().count()
+    }
+
+    #[test]
+    fn test_rotate_by_size() {
+        let dir = temp_dir("querylog-size");
+        let path = dir.join("query.log");
+        let mut writer = QueryLogWriter::open(&path, Rotation::Size(100), 2, 0).unwrap();
+        // each batch is 60 bytes, so every batch after the first one rotates the file
+        let batch = vec!["x".repeat(29), "y".repeat(29)];
+        for (index, count) in [1, 2, 3, 3, 3].iter().enumerate() {
+            writer.write_batch(&batch, index as u64).unwrap();
+            assert_eq!(file_count(&dir), *count);
+        }
+        assert_eq!(fs::read_to_string(&path).unwrap().len(), 60);
+        assert!(dir.join("query.log.2").exists());
+        assert!(!dir.join("query.log.3").exists());
+
+        // keep 0 only leave the current file
+        let mut writer = QueryLogWriter::open(&path, Rotation::Size(100), 0, 0).unwrap();
+        writer.write_batch(&batch, 0).unwrap();
+        assert_eq!(file_count(&dir), 3);
+        assert_eq!(fs::read_to_string(&path).unwrap().len(), 60);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_rotate_daily() {
+        let dir = temp_dir("querylog-daily");
+        let path = dir.join("query.log");
+        let mut writer = QueryLogWriter::open(&path, Rotation::Daily, 7, 10).unwrap();
+        let batch = vec!["line".to_owned()];
+        writer.write_batch(&batch, 20).unwrap();
+        writer.write_batch(&batch, SECONDS_PER_DAY - 1).unwrap();
+        assert_eq!(file_count(&dir), 1);
+        writer.write_batch(&batch, SECONDS_PER_DAY).unwrap();
+        assert_eq!(file_count(&dir), 2);
+        assert_eq!(
+            fs::read_to_string(dir.join("query.log.1")).unwrap(),
+            "line\nline\n"
+        );
+        assert_eq!(fs::read_to_string(&path).unwrap(), "line\n");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_query_log_dropped() {
+        let (query_log, receiver) = QueryLog::channel(2);
+        for index in 0..5 {
+            query_log.log(format!("query {}", index));
+        }
+        assert_eq!(query_log.dropped(), 3);
+        assert_eq!(receiver.try_recv().unwrap(), "query 0");
+        query_log.log("query 5".to_owned());
+        assert_eq!(query_log.dropped(), 3);
+    }
+
+    #[test]
+    fn test_query_log_writer_thread() {
+        let dir = temp_dir("querylog-thread");
+        let setting = Log {
+            target: dir.join("query.log").to_str().unwrap().to_owned(),
+            query: Some("info".to_owned()),
+            ..Default::default()
+        };
+        assert_eq!(query_log_setting(&[setting.clone()]), Some(&setting));
+        let query_log = QueryLog::open(&setting).unwrap();
+        query_log.log("www.example.com. IN A".to_owned());
+        let content = (0..100)
+            .map(|_| {
+                std::thread::sleep(std::time::Duration::from_millis(10));
+                fs::read_to_string(dir.join("query.log")).unwrap()
+            })
+            .find(|content| !content.is_empty());
+        assert_eq!(content, Some("www.example.com. IN A\n".to_owned()));
+        fs::remove_dir_all(&dir).unwrap();
+
+        let stdout = Log {
+            target: "stdout".to_owned(),
+            query: Some("info".to_owned()),
+            ..Default::default()
+        };
+        let warning = Log {
+            query: Some("warning".to_owned()),
+            ..setting
+        };
+        assert_eq!(query_log_setting(&[stdout, warning]), None);
+    }
+}
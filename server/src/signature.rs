@@ -0,0 +1,173 @@
+use crate::metrics::Metrics;
+use crate::transfer::{Clock, SystemClock};
+use dnsproto::dnsname::DNSName;
+use dnsproto::meta::DNSType;
+use dnsproto::qtype::DnsTypeRRSIG;
+use std::sync::Arc;
+use std::time::Duration;
+use storage::storage::SafeRBTreeStorage;
+use tokio::sync::Notify;
+
+/// warn when rrsig will expire in 7 days by default
+pub const DEFAULT_SIGNATURE_WINDOW: u32 = 7 * 86400;
+const SIGNATURE_CHECK_INTERVAL: u64 = 86400;
+
+/// SignatureState is the validity of a rrsig record compared with current time.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SignatureState {
+    Valid,
+    /// seconds left before the signature expires
+    ExpiringSoon(u32),
+    Expired,
+}
+
+impl SignatureState {
+    /// new compare the rrsig expiration with rfc4034 serial number arithmetic,
+    /// so the 32 bit timestamp works after 2106 as well.
+    pub fn new(rrsig: &DnsTypeRRSIG, now: u64, window: u32) -> SignatureState {
+        let left = rrsig.expiration().wrapping_sub(now as u32) as i32;
+        if left <= 0 {
+            SignatureState::Expired
+        } else if (left as u32) <= window {
+            SignatureState::ExpiringSoon(left as u32)
+        } else {
+            SignatureState::Valid
+        }
+    }
+}
+
+/// SignatureReport is a rrsig record which is expired or will expire soon.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SignatureReport {
+    pub owner: DNSName,
+    pub type_covered: DNSType,
+    pub key_tag: u16,
+    pub expiration: u32,
+    pub state: SignatureState,
+}
+
+/// check_zone_signatures walk all rrsig records of the zone and return those expired
+/// or expire within the window seconds.
+pub fn check_zone_signatures(
+    storage: &SafeRBTreeStorage,
+    apex: &DNSName,
+    now: u64,
+    window: u32,
+) -> Vec<SignatureReport> {
+    let mut reports = vec![];
+    storage.for_each_rrset(apex, |owner, dtype, rrset| {
+        if dtype != DNSType::RRSIG {
+            return;
+        }
+        for rr in rrset.signatures().iter() {
+            let rrsig = match rr
+                .get_data()
+                .as_ref()
+                .and_then(|data| data.as_any().downcast_ref::<DnsTypeRRSIG>())
+            {
+                Some(rrsig) => rrsig,
+                None => continue,
+            };
+            let state = SignatureState::new(rrsig, now, window);
+            if state == SignatureState::Valid {
+                continue;
+            }
+            reports.push(SignatureReport {
+                owner: owner.clone(),
+                type_covered: DNSType::from(rrsig.type_covered()),
+                key_tag: rrsig.key_tag(),
+                expiration: rrsig.expiration(),
+                state,
+            });
+        }
+    });
+    reports
+}
+
+/// check_signatures check the rrsig records of all loaded zones, log the problems
+/// and update the signature metrics.
+pub fn check_signatures(storage: &SafeRBTreeStorage, metrics: &Metrics, now: u64, window: u32) {
+    let (mut expiring, mut expired) = (0, 0);
+    for apex in storage.zones() {
+        for report in check_zone_signatures(storage, &apex, now, window) {
+            match report.state {
+                SignatureState::Expired => {
+                    expired += 1;
+                    error!(
+                        "zone {} rrsig {} {} key tag {} is expired",
+                        apex.to_string(),
+                        report.owner.to_string(),
+                        report.type_covered,
+                        report.key_tag
+                    );
+                }
+                SignatureState::ExpiringSoon(left) => {
+                    expiring += 1;
+                    warn!(
+                        "zone {} rrsig {} {} key tag {} will expire in {} seconds",
+                        apex.to_string(),
+                        report.owner.to_string(),
+                        report.type_covered,
+                        report.key_tag,
+                        left
+                    );
+                }
+                SignatureState::Valid => {}
+            }
+        }
+    }
+    metrics.set_signatures(expiring, expired);
+}
+
+/// signature_monitor check the signatures of all zones daily and whenever a zone changed.
+pub async fn signature_monitor(
+    storage: SafeRBTreeStorage,
+    metrics: Arc<Metrics>,
+    window: u32,
+    zone_changed: Arc<Notify>,
+) {
+    let mut interval = tokio::time::interval(Duration::from_secs(SIGNATURE_CHECK_INTERVAL));
+    loop {
+        tokio::select! {
+            _ = interval.tick() => {}
+            _ = zone_changed.notified() => {}
+        }
+        check_signatures(&storage, &metrics, SystemClock.now(), window);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // 2021-04-20 00:00:00 UTC
+    const NOW: u64 = 1618876800;
+
+    #[test]
+    fn test_check_zone_signatures() {
+        let storage =
+            SafeRBTreeStorage::new_zone_from_file("../storage/test/signed.zone", None).unwrap();
+        let apex = DNSName::new("signed.example.", None).unwrap();
+        let mut reports = check_zone_signatures(&storage, &apex, NOW, DEFAULT_SIGNATURE_WINDOW);
+        reports.sort_by_key(|report| report.owner.to_string());
+        assert_eq!(reports.len(), 2);
+        assert_eq!(reports[0].owner.to_string(), "ns.signed.example.");
+        assert_eq!(reports[0].type_covered, DNSType::A);
+        assert_eq!(reports[0].state, SignatureState::Expired);
+        assert_eq!(reports[1].owner.to_string(), "signed.example.");
+        assert_eq!(reports[1].type_covered, DNSType::NS);
+        assert_eq!(reports[1].key_tag, 12345);
+        // expire at 2021-04-22 05:00:00
+        assert_eq!(
+            reports[1].state,
+            SignatureState::ExpiringSoon(2 * 86400 + 5 * 3600)
+        );
+
+        let metrics = Metrics::default();
+        check_signatures(&storage, &metrics, NOW, DEFAULT_SIGNATURE_WINDOW);
+        assert_eq!(metrics.signatures_expiring(), 1);
+        assert_eq!(metrics.signatures_expired(), 1);
+        check_signatures(&storage, &metrics, NOW, 3600);
+        assert_eq!(metrics.signatures_expiring(), 0);
+    }
+}
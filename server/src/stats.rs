@@ -0,0 +1,325 @@
+use dnsproto::dnsname::DNSName;
+use dnsproto::meta::{DNSType, RCode};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+
+/// max query names tracked by the top names counter of each zone
+const TOP_NAMES_CAPACITY: usize = 128;
+/// query names shown in the stats dump
+const TOP_NAMES_DUMP: usize = 10;
+
+/// TopCounter count the most queried names in bounded space(the space-saving algorithm):
+/// when it is full, the least counted name is replaced by the new one which inherits
+/// its count, so counts of the top names are upper bounds.
+#[derive(Debug)]
+pub struct TopCounter {
+    capacity: usize,
+    counts: HashMap<String, u64>,
+}
+
+impl TopCounter {
+    pub fn new(capacity: usize) -> TopCounter {
+        TopCounter {
+            capacity,
+            counts: HashMap::new(),
+        }
+    }
+
+    pub fn add(&mut self, key: &str) {
+        if let Some(count) = self.counts.get_mut(key) {
+            *count += 1;
+            return;
+        }
+        let mut count = 1;
+        if self.counts.len() >= self.capacity {
+            let least = self
+                .counts
+                .iter()
+                .min_by_key(|(_, count)| **count)
+                .map(|(key, count)| (key.clone(), *count));
+            if let Some((key, least_count)) = least {
+                self.counts.remove(&key);
+                count += least_count;
+            }
+        }
+        self.counts.insert(key.to_owned(), count);
+    }
+
+    /// top return at most n (key, count) ordered by count.
+    pub fn top(&self, n: usize) -> Vec<(String, u64)> {
+        let mut top: Vec<(String, u64)> = self
+            .counts
+            .iter()
+            .map(|(key, count)| (key.clone(), *count))
+            .collect();
+        top.sort_by(|left, right| right.1.cmp(&left.1).then_with(|| left.0.cmp(&right.0)));
+        top.truncate(n);
+        top
+    }
+}
+
+/// ZoneStats holds the query statistics of a zone.
+#[derive(Debug)]
+pub struct ZoneStats {
+    queries: AtomicU64,
+    nxdomain: AtomicU64,
+    qtypes: Mutex<BTreeMap<String, u64>>,
+    top_names: Mutex<TopCounter>,
+}
+
+impl Default for ZoneStats {
+    fn default() -> Self {
+        ZoneStats {
+            queries: AtomicU64::new(0),
+            nxdomain: AtomicU64::new(0),
+            qtypes: Mutex::new(BTreeMap::new()),
+            top_names: Mutex::new(TopCounter::new(TOP_NAMES_CAPACITY)),
+        }
+    }
+}
+
+impl ZoneStats {
+    fn record(&self, name: &str, qtype: &DNSType, rcode: RCode) {
+        self.queries.fetch_add(1, Ordering::Relaxed);
+        if rcode == RCode::NameError {
+            self.nxdomain.fetch_add(1, Ordering::Relaxed);
+        }
+        *self
+            .qtypes
+            .lock()
+            .unwrap()
+            .entry(qtype.to_string())
+            .or_insert(0) += 1;
+        self.top_names.lock().unwrap().add(name);
+    }
+
+    fn reset(&self) {
+        self.queries.store(0, Ordering::Relaxed);
+        self.nxdomain.store(0, Ordering::Relaxed);
+        self.qtypes.lock().unwrap().clear();
+        *self.top_names.lock().unwrap() = TopCounter::new(TOP_NAMES_CAPACITY);
+    }
+
+    pub fn queries(&self) -> u64 {
+        self.queries.load(Ordering::Relaxed)
+    }
+
+    pub fn nxdomain(&self) -> u64 {
+        self.nxdomain.load(Ordering::Relaxed)
+    }
+
+    pub fn qtypes(&self) -> BTreeMap<String, u64> {
+        self.qtypes.lock().unwrap().clone()
+    }
+
+    pub fn top_names(&self, n: usize) -> Vec<(String, u64)> {
+        self.top_names.lock().unwrap().top(n)
+    }
+
+    /// to_json dump the stats with the zone name and its (unix time, serial) refresh history.
+    pub fn to_json(&self, zone: Option<&DNSName>, transfers: &[(u64, u32)]) -> String {
+        let queries = self.queries();
+        let nxdomain = self.nxdomain();
+        let ratio = match queries {
+            0 => 0.0,
+            _ => nxdomain as f64 / queries as f64,
+        };
+        let qtypes: Vec<String> = self
+            .qtypes()
+            .iter()
+            .map(|(qtype, count)| format!("{}:{}", json_string(qtype), count))
+            .collect();
+        let top_names: Vec<String> = self
+            .top_names(TOP_NAMES_DUMP)
+            .iter()
+            .map(|(name, count)| format!("{{\"name\":{},\"count\":{}}}", json_string(name), count))
+            .collect();
+        let transfers: Vec<String> = transfers
+            .iter()
+            .map(|(time, serial)| format!("{{\"time\":{},\"serial\":{}}}", time, serial))
+            .collect();
+        format!(
+            "{{\"zone\":{},\"queries\":{},\"nxdomain\":{},\"nxdomain_ratio\":{:.4},\"qtypes\":{{{}}},\"top_names\":[{}],\"transfers\":[{}]}}",
+            zone.map_or("null".to_owned(), |zone| json_string(&zone.to_string())),
+            queries,
+            nxdomain,
+            ratio,
+            qtypes.join(","),
+            top_names.join(","),
+            transfers.join(",")
+        )
+    }
+}
+
+/// QueryStats holds the global and per zone query statistics, shared by all listeners.
+#[derive(Debug, Default)]
+pub struct QueryStats {
+    global: ZoneStats,
+    zones: RwLock<HashMap<DNSName, Arc<ZoneStats>>>,
+    // hidden zones are not tracked, their query names must not show up in the outputs
+    hidden: RwLock<Vec<DNSName>>,
+}
+
+impl QueryStats {
+    /// add_zone start tracking the zone, so an idle zone still has stats.
+    pub fn add_zone(&self, zone: &DNSName) {
+        if self.hidden.read().unwrap().contains(zone) {
+            return;
+        }
+        self.zones
+            .write()
+            .unwrap()
+            .entry(zone.clone())
+            .or_insert_with(|| Arc::new(ZoneStats::default()));
+    }
+
+    /// hide_zone stop tracking the zone and drop its stats, the queries of a hidden zone
+    /// are not counted in the global stats either.
+    pub fn hide_zone(&self, zone: &DNSName) {
+        let mut hidden = self.hidden.write().unwrap();
+        if !hidden.contains(zone) {
+            hidden.push(zone.clone());
+        }
+        self.zones.write().unwrap().remove(zone);
+    }
+
+    /// record a query answered by the zone, zone is None when the server is not
+    /// authoritative for the query name.
+    pub fn record(&self, zone: Option<&DNSName>, name: &DNSName, qtype: &DNSType, rcode: RCode) {
+        if let Some(zone) = zone {
+            if self.hidden.read().unwrap().contains(zone) {
+                return;
+            }
+        }
+        let name = name.to_string().to_lowercase();
+        self.global.record(&name, qtype, rcode);
+        let zone = match zone {
+            Some(zone) => zone,
+            None => return,
+        };
+        let stats = self.zones.read().unwrap().get(zone).cloned();
+        let stats = match stats {
+            Some(stats) => stats,
+            None => {
+                self.add_zone(zone);
+                self.zones.read().unwrap().get(zone).cloned().unwrap()
+            }
+        };
+        stats.record(&name, qtype, rcode);
+    }
+
+    pub fn global(&self) -> &ZoneStats {
+        &self.global
+    }
+
+    pub fn zone(&self, zone: &DNSName) -> Option<Arc<ZoneStats>> {
+        self.zones.read().unwrap().get(zone).cloned()
+    }
+
+    /// reset the stats of the zone, or the global and all zones stats when zone is None.
+    pub fn reset(&self, zone: Option<&DNSName>) {
+        match zone {
+            Some(zone) => {
+                if let Some(stats) = self.zone(zone) {
+                    stats.reset();
+                }
+            }
+            None => {
+                self.global.reset();
+                for stats in self.zones.read().unwrap().values() {
+                    stats.reset();
+                }
+            }
+        }
+    }
+}
+
+/// json_string quote and escape the string as a json string.
+pub(crate) fn json_string(input: &str) -> String {
+    let mut output = String::with_capacity(input.len() + 2);
+    output.push('"');
+    for c in input.chars() {
+        match c {
+            '"' => output.push_str("\\\""),
+            '\\' => output.push_str("\\\\"),
+            c if (c as u32) < 0x20 => output.push_str(&format!("\\u{:04x}", c as u32)),
+            c => output.push(c),
+        }
+    }
+    output.push('"');
+    output
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_top_counter() {
+        let mut counter = TopCounter::new(2);
+        for key in ["a", "a", "a", "b", "b", "c"].iter() {
+            counter.add(key);
+        }
+        // c replace b and inherit its count
+        assert_eq!(
+            counter.top(5),
+            vec![("a".to_owned(), 3), ("c".to_owned(), 3)]
+        );
+        assert_eq!(counter.top(1), vec![("a".to_owned(), 3)]);
+    }
+
+    #[test]
+    fn test_query_stats() {
+        let stats = QueryStats::default();
+        let zone = DNSName::new("example.com.", None).unwrap();
+        let www = DNSName::new("WWW.example.com.", None).unwrap();
+        let missing = DNSName::new("missing.example.com.", None).unwrap();
+        stats.add_zone(&zone);
+        stats.record(Some(&zone), &www, &DNSType::A, RCode::NoError);
+        stats.record(Some(&zone), &www, &DNSType::AAAA, RCode::NoError);
+        stats.record(Some(&zone), &missing, &DNSType::A, RCode::NameError);
+        stats.record(None, &missing, &DNSType::A, RCode::Refused);
+
+        let zone_stats = stats.zone(&zone).unwrap();
+        assert_eq!(zone_stats.queries(), 3);
+        assert_eq!(zone_stats.nxdomain(), 1);
+        assert_eq!(zone_stats.qtypes().get("A"), Some(&2));
+        assert_eq!(
+            zone_stats.top_names(1),
+            vec![("www.example.com.".to_owned(), 2)]
+        );
+        assert_eq!(stats.global().queries(), 4);
+        assert_eq!(
+            zone_stats.to_json(Some(&zone), &[(1600000000, 2020091025)]),
+            "{\"zone\":\"example.com.\",\"queries\":3,\"nxdomain\":1,\"nxdomain_ratio\":0.3333,\
+             \"qtypes\":{\"A\":2,\"AAAA\":1},\"top_names\":[{\"name\":\"www.example.com.\",\
+             \"count\":2},{\"name\":\"missing.example.com.\",\"count\":1}],\
+             \"transfers\":[{\"time\":1600000000,\"serial\":2020091025}]}"
+        );
+
+        stats.reset(Some(&zone));
+        assert_eq!(zone_stats.queries(), 0);
+        assert_eq!(stats.global().queries(), 4);
+        stats.reset(None);
+        assert_eq!(stats.global().queries(), 0);
+        assert_eq!(json_string("a\"b\\c"), "\"a\\\"b\\\\c\"");
+    }
+
+    #[test]
+    fn test_query_stats_hidden_zone() {
+        let stats = QueryStats::default();
+        let zone = DNSName::new("example.com.", None).unwrap();
+        let hidden = DNSName::new("internal.example.com.", None).unwrap();
+        let name = DNSName::new("db.internal.example.com.", None).unwrap();
+        stats.add_zone(&hidden);
+        stats.hide_zone(&hidden);
+        stats.add_zone(&hidden);
+        stats.record(Some(&hidden), &name, &DNSType::A, RCode::NoError);
+        let www = DNSName::new("www.example.com.", None).unwrap();
+        stats.record(Some(&zone), &www, &DNSType::A, RCode::NoError);
+        assert!(stats.zone(&hidden).is_none());
+        assert_eq!(stats.zone(&zone).unwrap().queries(), 1);
+        assert_eq!(stats.global().queries(), 1);
+    }
+}
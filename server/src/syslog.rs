@@ -0,0 +1,371 @@
+use crate::logging::channel_level;
+use log::{Level, LevelFilter};
+use otterlib::setting::Log;
+use std::io;
+use std::os::unix::net::UnixDatagram;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// target name of the log settings sent to the local syslog daemon
+pub const SYSLOG_TARGET: &str = "syslog";
+const SYSLOG_SOCKET: &str = "/dev/log";
+const DEFAULT_SYSLOG_TAG: &str = "otter";
+// facility daemon
+const DEFAULT_SYSLOG_FACILITY: u8 = 3;
+// longer records are truncated so a datagram is never rejected by the size
+const MAX_RECORD_SIZE: usize = 8192;
+// rfc5424 section 6.2.5, the app name is at most 48 printable characters
+const MAX_TAG_SIZE: usize = 48;
+const RETRY_MIN: Duration = Duration::from_millis(100);
+const RETRY_MAX: Duration = Duration::from_secs(60);
+
+/// server_syslog_setting return the first syslog setting with the server channel on and the
+/// level of the channel.
+pub fn server_syslog_setting(logs: &[Log]) -> Option<(&Log, LevelFilter)> {
+    logs.iter()
+        .filter(|log| log.target == SYSLOG_TARGET)
+        .map(|log| (log, channel_level(log.server.as_deref())))
+        .find(|(_, level)| *level != LevelFilter::Off)
+}
+
+/// query_syslog_setting return the first syslog setting which sends the queries, the
+/// queries are logged at info level.
+pub fn query_syslog_setting(logs: &[Log]) -> Option<&Log> {
+    logs.iter().find(|log| {
+        log.target == SYSLOG_TARGET && channel_level(log.query.as_deref()) >= LevelFilter::Info
+    })
+}
+
+/// severity return the rfc5424 severity of the log level.
+pub fn severity(level: Level) -> u8 {
+    match level {
+        Level::Error => 3,
+        Level::Warn => 4,
+        Level::Info => 6,
+        Level::Debug | Level::Trace => 7,
+    }
+}
+
+/// SyslogHeader is the fields of the rfc5424 header which are same for all records.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SyslogHeader {
+    pub facility: u8,
+    pub hostname: String,
+    pub tag: String,
+    pub pid: u32,
+}
+
+impl SyslogHeader {
+    /// new build the header of the log setting with the local host name and process id.
+    pub fn new(setting: &Log) -> SyslogHeader {
+        let tag: String = setting
+            .tag
+            .as_deref()
+            .unwrap_or(DEFAULT_SYSLOG_TAG)
+            .chars()
+            .filter(|c| c.is_ascii_graphic())
+            .take(MAX_TAG_SIZE)
+            .collect();
+        SyslogHeader {
+            facility: setting.syslog_facility().unwrap_or(DEFAULT_SYSLOG_FACILITY),
+            hostname: hostname(),
+            tag,
+            pid: std::process::id(),
+        }
+    }
+
+    /// format the message as a rfc5424 record at the unix time now, the message id and the
+    /// structured data are nil.
+    pub fn format(&self, severity: u8, now: Duration, message: &str) -> String {
+        let mut record = format!(
+            "<{}>1 {} {} {} {} - - {}",
+            u32::from(self.facility) * 8 + u32::from(severity),
+            timestamp(now),
+            nil_or(&self.hostname),
+            nil_or(&self.tag),
+            self.pid,
+            message
+        );
+        if record.len() > MAX_RECORD_SIZE {
+            let mut end = MAX_RECORD_SIZE;
+            while !record.is_char_boundary(end) {
+                end -= 1;
+            }
+            record.truncate(end);
+        }
+        record
+    }
+}
+
+fn nil_or(value: &str) -> &str {
+    match value.is_empty() {
+        true => "-",
+        false => value,
+    }
+}
+
+fn hostname() -> String {
+    let mut buffer = [0u8; 256];
+    let ret = unsafe { libc::gethostname(buffer.as_mut_ptr() as *mut libc::c_char, buffer.len()) };
+    if ret != 0 {
+        return String::new();
+    }
+    let end = buffer.iter().position(|c| *c == 0).unwrap_or(buffer.len());
+    String::from_utf8_lossy(&buffer[..end])
+        .chars()
+        .filter(|c| c.is_ascii_graphic())
+        .collect()
+}
+
+// timestamp format the unix time like 2025-01-01T00:00:00.000Z(rfc5424 section 6.2.3)
+fn timestamp(now: Duration) -> String {
+    let secs = now.as_secs();
+    let (year, month, day) = civil_from_days((secs / 86400) as i64);
+    let secs = secs % 86400;
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+        year,
+        month,
+        day,
+        secs / 3600,
+        secs / 60 % 60,
+        secs % 60,
+        now.subsec_millis()
+    )
+}
+
+// civil_from_days convert the days since 1970-01-01 to (year, month, day) of the
+// proleptic gregorian calendar
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = (if mp < 10 { mp + 3 } else { mp - 9 }) as u32;
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+#[derive(Debug)]
+struct SyslogSocket {
+    socket: Option<UnixDatagram>,
+    // no connection is tried before the time after a failure
+    retry_at: Option<Instant>,
+    backoff: Duration,
+}
+
+impl SyslogSocket {
+    // fail drop the socket and double the delay of the next connection
+    fn fail(&mut self, now: Instant) {
+        self.socket = None;
+        self.retry_at = Some(now + self.backoff);
+        self.backoff = (self.backoff * 2).min(RETRY_MAX);
+    }
+}
+
+/// Syslog send the records to the unix datagram socket of the syslog daemon. a lost
+/// connection is retried with backoff and the records in between are dropped and counted,
+/// so logging never blocks or panics.
+#[derive(Debug)]
+pub struct Syslog {
+    header: SyslogHeader,
+    path: PathBuf,
+    socket: Mutex<SyslogSocket>,
+    failures: AtomicU64,
+    dropped: AtomicU64,
+}
+
+impl Syslog {
+    /// open the syslog of the log setting, the socket is connected at the first record.
+    pub fn open(setting: &Log) -> Syslog {
+        Syslog::with_path(Path::new(SYSLOG_SOCKET), SyslogHeader::new(setting))
+    }
+
+    pub fn with_path(path: &Path, header: SyslogHeader) -> Syslog {
+        Syslog {
+            header,
+            path: path.to_path_buf(),
+            socket: Mutex::new(SyslogSocket {
+                socket: None,
+                retry_at: None,
+                backoff: RETRY_MIN,
+            }),
+            failures: AtomicU64::new(0),
+            dropped: AtomicU64::new(0),
+        }
+    }
+
+    /// send the message with the severity, return false when the record is dropped.
+    pub fn send(&self, severity: u8, message: &str) -> bool {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        let record = self.header.format(severity, now, message);
+        let sent = self.send_record(record.as_bytes(), Instant::now());
+        if !sent {
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+        }
+        sent
+    }
+
+    fn send_record(&self, record: &[u8], now: Instant) -> bool {
+        // a panic while the lock is held must not disable the logging
+        let mut state = match self.socket.lock() {
+            Ok(state) => state,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        if state.socket.is_none() {
+            if state.retry_at.map_or(false, |retry_at| now < retry_at) {
+                return false;
+            }
+            match connect(&self.path) {
+                Ok(socket) => state.socket = Some(socket),
+                Err(_) => {
+                    self.failures.fetch_add(1, Ordering::Relaxed);
+                    state.fail(now);
+                    return false;
+                }
+            }
+        }
+        let result = match state.socket.as_ref() {
+            Some(socket) => socket.send(record),
+            None => return false,
+        };
+        match result {
+            Ok(_) => {
+                state.retry_at = None;
+                state.backoff = RETRY_MIN;
+                true
+            }
+            // the daemon is busy, the connection is still good
+            Err(err) if err.kind() == io::ErrorKind::WouldBlock => false,
+            Err(_) => {
+                self.failures.fetch_add(1, Ordering::Relaxed);
+                state.fail(now);
+                false
+            }
+        }
+    }
+
+    /// failures return the failed connections and sends since the syslog is opened.
+    pub fn failures(&self) -> u64 {
+        self.failures.load(Ordering::Relaxed)
+    }
+
+    /// dropped return the records not sent since the syslog is opened.
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+}
+
+fn connect(path: &Path) -> io::Result<UnixDatagram> {
+    let socket = UnixDatagram::unbound()?;
+    socket.connect(path)?;
+    socket.set_nonblocking(true)?;
+    Ok(socket)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn header() -> SyslogHeader {
+        SyslogHeader {
+            facility: 16,
+            hostname: "ns1.example.com".to_owned(),
+            tag: "otter".to_owned(),
+            pid: 4242,
+        }
+    }
+
+    #[test]
+    fn test_format_record() {
+        // 2025-03-01 12:34:56.789
+        let now = Duration::from_millis(1_740_832_496_789);
+        assert_eq!(
+            header().format(severity(Level::Warn), now, "zone example.com. expired"),
+            "<132>1 2025-03-01T12:34:56.789Z ns1.example.com otter 4242 - - zone example.com. expired"
+        );
+        let record = SyslogHeader {
+            facility: 3,
+            hostname: String::new(),
+            ..header()
+        }
+        .format(severity(Level::Debug), Duration::from_secs(951_782_400), "");
+        assert_eq!(record, "<31>1 2000-02-29T00:00:00.000Z - otter 4242 - - ");
+        let record = header().format(6, now, &"x".repeat(MAX_RECORD_SIZE));
+        assert_eq!(record.len(), MAX_RECORD_SIZE);
+
+        let setting = Log {
+            target: SYSLOG_TARGET.to_owned(),
+            tag: Some("otter dns".to_owned()),
+            ..Default::default()
+        };
+        let header = SyslogHeader::new(&setting);
+        assert_eq!((header.facility, header.tag.as_str()), (3, "otterdns"));
+    }
+
+    #[test]
+    fn test_syslog_setting() {
+        let file = Log {
+            target: "/var/log/otter/query.log".to_owned(),
+            server: Some("info".to_owned()),
+            query: Some("info".to_owned()),
+            ..Default::default()
+        };
+        let syslog = Log {
+            target: SYSLOG_TARGET.to_owned(),
+            server: Some("warning".to_owned()),
+            query: Some("warning".to_owned()),
+            ..Default::default()
+        };
+        let logs = vec![file, syslog.clone()];
+        assert_eq!(
+            server_syslog_setting(&logs),
+            Some((&syslog, LevelFilter::Warn))
+        );
+        assert_eq!(query_syslog_setting(&logs), None);
+    }
+
+    #[test]
+    fn test_syslog_reconnect() {
+        let dir = std::env::temp_dir().join(format!("otter-syslog-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("log");
+        let syslog = Syslog::with_path(&path, header());
+
+        // no daemon yet, the next connection waits for the backoff
+        let start = Instant::now();
+        assert!(!syslog.send_record(b"lost", start));
+        assert!(!syslog.send_record(b"lost", start + RETRY_MIN / 2));
+        assert_eq!(syslog.failures(), 1);
+        assert!(!syslog.send_record(b"lost", start + RETRY_MIN));
+        assert_eq!(syslog.failures(), 2);
+
+        let daemon = UnixDatagram::bind(&path).unwrap();
+        assert!(!syslog.send_record(b"lost", start + RETRY_MIN * 2));
+        assert!(syslog.send_record(b"sent", start + RETRY_MIN * 3));
+        let mut buffer = [0u8; 16];
+        let size = daemon.recv(&mut buffer).unwrap();
+        assert_eq!(&buffer[..size], b"sent");
+
+        // the daemon restarts, the record is dropped and the socket is connected again
+        drop(daemon);
+        std::fs::remove_file(&path).unwrap();
+        assert!(!syslog.send_record(b"lost", start + RETRY_MIN * 4));
+        assert_eq!(syslog.failures(), 3);
+        let daemon = UnixDatagram::bind(&path).unwrap();
+        assert!(syslog.send_record(b"again", start + RETRY_MIN * 5));
+        let size = daemon.recv(&mut buffer).unwrap();
+        assert_eq!(&buffer[..size], b"again");
+        assert!(syslog.send(6, "record"));
+        assert_eq!(syslog.dropped(), 0);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
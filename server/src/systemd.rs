@@ -0,0 +1,237 @@
+use std::ffi::OsStr;
+use std::io;
+use std::net::{TcpListener, UdpSocket};
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::io::{AsRawFd, FromRawFd, RawFd};
+use std::os::unix::net::UnixDatagram;
+use std::time::Duration;
+
+// the first fd passed by systemd socket activation(sd_listen_fds)
+const LISTEN_FDS_START: RawFd = 3;
+
+/// ListenFds are the sockets passed by systemd socket activation.
+#[derive(Debug, Default)]
+pub(crate) struct ListenFds {
+    pub(crate) tcp: Vec<TcpListener>,
+    pub(crate) udp: Vec<UdpSocket>,
+}
+
+/// listen_fds take the sockets passed by systemd, None if the process is not socket
+/// activated. the environment variables are removed so they are not inherited.
+pub(crate) fn listen_fds() -> io::Result<Option<ListenFds>> {
+    let count = listen_fds_count(
+        std::env::var("LISTEN_PID").ok().as_deref(),
+        std::env::var("LISTEN_FDS").ok().as_deref(),
+        std::process::id(),
+    );
+    for key in ["LISTEN_PID", "LISTEN_FDS", "LISTEN_FDNAMES"].iter() {
+        std::env::remove_var(key);
+    }
+    match count {
+        Some(count) => {
+            let fds: Vec<RawFd> = (LISTEN_FDS_START..LISTEN_FDS_START + count as RawFd).collect();
+            adopt_fds(&fds).map(Some)
+        }
+        None => Ok(None),
+    }
+}
+
+/// listen_fds_count return the number of passed fds when LISTEN_PID is the current process.
+fn listen_fds_count(pid: Option<&str>, fds: Option<&str>, current: u32) -> Option<usize> {
+    if pid?.parse::<u32>().ok()? != current {
+        return None;
+    }
+    match fds?.parse::<usize>().ok()? {
+        0 => None,
+        count => Some(count),
+    }
+}
+
+/// adopt_fds wrap the fds as tcp listeners or udp sockets by the socket type, only inet
+/// stream and datagram sockets are accepted.
+pub(crate) fn adopt_fds(fds: &[RawFd]) -> io::Result<ListenFds> {
+    let mut listen_fds = ListenFds::default();
+    for fd in fds.iter() {
+        let domain = socket_option(*fd, libc::SO_DOMAIN)?;
+        if domain != libc::AF_INET && domain != libc::AF_INET6 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("fd {} is not an inet socket", fd),
+            ));
+        }
+        if unsafe { libc::fcntl(*fd, libc::F_SETFD, libc::FD_CLOEXEC) } < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        match socket_option(*fd, libc::SO_TYPE)? {
+            libc::SOCK_STREAM => listen_fds
+                .tcp
+                .push(unsafe { TcpListener::from_raw_fd(*fd) }),
+            libc::SOCK_DGRAM => listen_fds.udp.push(unsafe { UdpSocket::from_raw_fd(*fd) }),
+            sotype => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("fd {} has unsupported socket type {}", fd, sotype),
+                ))
+            }
+        }
+    }
+    Ok(listen_fds)
+}
+
+fn socket_option(fd: RawFd, name: libc::c_int) -> io::Result<libc::c_int> {
+    let mut value: libc::c_int = 0;
+    let mut len = std::mem::size_of::<libc::c_int>() as libc::socklen_t;
+    let ret = unsafe {
+        libc::getsockopt(
+            fd,
+            libc::SOL_SOCKET,
+            name,
+            &mut value as *mut libc::c_int as *mut libc::c_void,
+            &mut len,
+        )
+    };
+    if ret < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(value)
+}
+
+/// notify send the state like READY=1 to the NOTIFY_SOCKET of systemd, nothing is sent if
+/// the service is not Type=notify.
+pub(crate) fn notify(state: &str) {
+    if let Some(path) = std::env::var_os("NOTIFY_SOCKET") {
+        if let Err(err) = notify_socket(&path, state) {
+            warn!("send {} to systemd fail: {}", state, err);
+        }
+    }
+}
+
+/// notify_socket send the state to the unix datagram socket, a path start with '@' is
+/// in the abstract namespace.
+fn notify_socket(path: &OsStr, state: &str) -> io::Result<()> {
+    let socket = UnixDatagram::unbound()?;
+    let path = path.as_bytes();
+    if path.first() != Some(&b'@') {
+        socket.send_to(state.as_bytes(), OsStr::from_bytes(path))?;
+        return Ok(());
+    }
+    let mut addr: libc::sockaddr_un = unsafe { std::mem::zeroed() };
+    if path.len() > addr.sun_path.len() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "notify socket path too long",
+        ));
+    }
+    addr.sun_family = libc::AF_UNIX as libc::sa_family_t;
+    // the leading '@' is replaced by the nul byte of the abstract namespace
+    for (dst, src) in addr.sun_path.iter_mut().zip(path.iter()).skip(1) {
+        *dst = *src as libc::c_char;
+    }
+    let len = std::mem::size_of::<libc::sa_family_t>() + path.len();
+    let ret = unsafe {
+        libc::sendto(
+            socket.as_raw_fd(),
+            state.as_ptr() as *const libc::c_void,
+            state.len(),
+            libc::MSG_NOSIGNAL,
+            &addr as *const libc::sockaddr_un as *const libc::sockaddr,
+            len as libc::socklen_t,
+        )
+    };
+    if ret < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// watchdog_interval return the interval of WATCHDOG=1 pings, which is half of the
+/// WatchdogSec of the service, None if the watchdog is not enabled for this process.
+pub(crate) fn watchdog_interval() -> Option<Duration> {
+    if let Ok(pid) = std::env::var("WATCHDOG_PID") {
+        if pid.parse::<u32>().ok()? != std::process::id() {
+            return None;
+        }
+    }
+    match std::env::var("WATCHDOG_USEC").ok()?.parse::<u64>().ok()? {
+        0 => None,
+        usec => Some(Duration::from_micros(usec / 2)),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::os::unix::io::IntoRawFd;
+
+    #[test]
+    fn test_listen_fds_count() {
+        assert_eq!(listen_fds_count(Some("100"), Some("2"), 100), Some(2));
+        // the fds are passed to another process
+        assert_eq!(listen_fds_count(Some("101"), Some("2"), 100), None);
+        assert_eq!(listen_fds_count(Some("100"), Some("0"), 100), None);
+        assert_eq!(listen_fds_count(None, Some("2"), 100), None);
+        assert_eq!(listen_fds_count(Some("100"), Some("x"), 100), None);
+    }
+
+    #[test]
+    fn test_adopt_fds() {
+        let tcp = TcpListener::bind("127.0.0.1:0").unwrap();
+        let udp = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let (tcp_addr, udp_addr) = (tcp.local_addr().unwrap(), udp.local_addr().unwrap());
+        let fds = [tcp.into_raw_fd(), udp.into_raw_fd()];
+        let adopted = adopt_fds(&fds).unwrap();
+        assert_eq!(adopted.tcp.len(), 1);
+        assert_eq!(adopted.udp.len(), 1);
+        assert_eq!(adopted.tcp[0].local_addr().unwrap(), tcp_addr);
+        assert_eq!(adopted.udp[0].local_addr().unwrap(), udp_addr);
+
+        // unix sockets from socketpair are not adopted
+        let mut pair = [0 as RawFd; 2];
+        let ret =
+            unsafe { libc::socketpair(libc::AF_UNIX, libc::SOCK_STREAM, 0, pair.as_mut_ptr()) };
+        assert_eq!(ret, 0);
+        assert!(adopt_fds(&pair[..1]).is_err());
+        unsafe {
+            libc::close(pair[0]);
+            libc::close(pair[1]);
+        }
+    }
+
+    #[test]
+    fn test_notify_socket() {
+        let path = std::env::temp_dir().join(format!("otter_notify_{}", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let receiver = UnixDatagram::bind(&path).unwrap();
+        notify_socket(path.as_os_str(), "READY=1").unwrap();
+        let mut buf = [0u8; 64];
+        let size = receiver.recv(&mut buf).unwrap();
+        assert_eq!(&buf[..size], b"READY=1");
+        std::fs::remove_file(&path).unwrap();
+
+        let name = format!("@otter_notify_{}", std::process::id());
+        let receiver = abstract_socket(&name[1..]);
+        notify_socket(OsStr::new(&name), "WATCHDOG=1").unwrap();
+        let size = receiver.recv(&mut buf).unwrap();
+        assert_eq!(&buf[..size], b"WATCHDOG=1");
+    }
+
+    fn abstract_socket(name: &str) -> UnixDatagram {
+        let fd = unsafe { libc::socket(libc::AF_UNIX, libc::SOCK_DGRAM, 0) };
+        assert!(fd >= 0);
+        let mut addr: libc::sockaddr_un = unsafe { std::mem::zeroed() };
+        addr.sun_family = libc::AF_UNIX as libc::sa_family_t;
+        for (dst, src) in addr.sun_path[1..].iter_mut().zip(name.as_bytes()) {
+            *dst = *src as libc::c_char;
+        }
+        let len = std::mem::size_of::<libc::sa_family_t>() + 1 + name.len();
+        let ret = unsafe {
+            libc::bind(
+                fd,
+                &addr as *const libc::sockaddr_un as *const libc::sockaddr,
+                len as libc::socklen_t,
+            )
+        };
+        assert_eq!(ret, 0);
+        unsafe { UnixDatagram::from_raw_fd(fd) }
+    }
+}
@@ -0,0 +1,1032 @@
+use crate::dnsclient::{connect, forward, query, QueryOption};
+use crate::zonestate::{state_path, ZoneState};
+use dnsproto::dnsname::DNSName;
+use dnsproto::message::Message;
+use dnsproto::meta::{DNSClass, DNSType, Header, OpCode, Question, RCode, ResourceRecord};
+use dnsproto::qtype::DnsTypeSOA;
+use dnsproto::utils::fqdn;
+use otterlib::errors::{NetworkError, OtterError, TransferError};
+use otterlib::setting::ZoneSetting;
+use std::collections::{HashMap, VecDeque};
+use std::net::SocketAddr;
+use std::sync::{Arc, RwLock};
+use std::time::{SystemTime, UNIX_EPOCH};
+use storage::serial::serial_gt;
+use storage::storage::{SafeRBTreeStorage, ZoneOption};
+use storage::writer::read_transfer_header;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::time::timeout;
+
+/// Clock return the current unix timestamp in seconds, replaced by a fake clock in tests.
+pub trait Clock: Send + Sync {
+    fn now(&self) -> u64;
+}
+
+#[derive(Debug, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> u64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_secs())
+            .unwrap_or(0)
+    }
+}
+
+/// ZoneFreshness is the state of a secondary zone based on its soa timers.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ZoneFreshness {
+    /// refresh timer not lapsed
+    Fresh,
+    /// refresh timer lapsed, but the zone is still valid until expire
+    NeedRefresh,
+    /// expire timer lapsed, serving stale data as configured
+    Stale,
+    /// expire timer lapsed, stop answering authoritatively
+    Expired,
+}
+
+/// SecondaryZoneState holds the soa timers and last refresh time of a secondary zone.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SecondaryZoneState {
+    pub serial: u32,
+    pub refresh: u32,
+    pub retry: u32,
+    pub expire: u32,
+    pub last_refresh: u64,
+    pub serve_stale: bool,
+    pub max_stale: Option<u32>,
+    /// unix time of the last failed refresh
+    pub last_failure: Option<u64>,
+    /// id of the master the zone was last refreshed from
+    pub master: Option<String>,
+}
+
+impl SecondaryZoneState {
+    pub fn freshness(&self, now: u64) -> ZoneFreshness {
+        let elapsed = now.saturating_sub(self.last_refresh);
+        if elapsed < self.refresh as u64 {
+            return ZoneFreshness::Fresh;
+        }
+        if elapsed < self.expire as u64 {
+            return ZoneFreshness::NeedRefresh;
+        }
+        if self.serve_stale {
+            match self.max_stale {
+                Some(max_stale) if elapsed >= self.expire as u64 + max_stale as u64 => {}
+                _ => return ZoneFreshness::Stale,
+            }
+        }
+        ZoneFreshness::Expired
+    }
+}
+
+/// max (unix time, serial) refresh records kept for each secondary zone
+const MAX_REFRESH_HISTORY: usize = 16;
+
+/// TransferScheduler tracks the refresh state of all secondary zones.
+pub struct TransferScheduler {
+    zones: RwLock<HashMap<DNSName, SecondaryZoneState>>,
+    history: RwLock<HashMap<DNSName, VecDeque<(u64, u32)>>>,
+    clock: Arc<dyn Clock>,
+}
+
+impl std::fmt::Debug for TransferScheduler {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TransferScheduler")
+            .field("zones", &self.zones)
+            .field("history", &self.history)
+            .finish()
+    }
+}
+
+impl Default for TransferScheduler {
+    fn default() -> Self {
+        TransferScheduler::new(Arc::new(SystemClock))
+    }
+}
+
+impl TransferScheduler {
+    pub fn new(clock: Arc<dyn Clock>) -> TransferScheduler {
+        TransferScheduler {
+            zones: RwLock::new(HashMap::new()),
+            history: RwLock::new(HashMap::new()),
+            clock,
+        }
+    }
+
+    /// register a secondary zone, the loaded soa is treated as just refreshed.
+    pub fn register(
+        &self,
+        zone: DNSName,
+        soa: &DnsTypeSOA,
+        serve_stale: bool,
+        max_stale: Option<u32>,
+    ) {
+        let state = SecondaryZoneState {
+            serial: soa.serial(),
+            refresh: soa.refresh(),
+            retry: soa.retry(),
+            expire: soa.expire(),
+            last_refresh: self.clock.now(),
+            serve_stale,
+            max_stale,
+            last_failure: None,
+            master: None,
+        };
+        self.add_history(&zone, state.serial);
+        self.zones.write().unwrap().insert(zone, state);
+    }
+
+    /// register_pending register a secondary zone without local data, the zone need
+    /// refresh at once and is transferred from master.
+    pub fn register_pending(&self, zone: DNSName, serve_stale: bool, max_stale: Option<u32>) {
+        let state = SecondaryZoneState {
+            serial: 0,
+            refresh: 0,
+            retry: 0,
+            expire: 0,
+            last_refresh: 0,
+            serve_stale,
+            max_stale,
+            last_failure: None,
+            master: None,
+        };
+        self.zones.write().unwrap().insert(zone, state);
+    }
+
+    /// set_last_refresh resume the refresh timers of the zone from the last refresh time.
+    pub fn set_last_refresh(&self, zone: &DNSName, last_refresh: u64) {
+        if let Some(state) = self.zones.write().unwrap().get_mut(zone) {
+            state.last_refresh = last_refresh;
+        }
+    }
+
+    /// restore_state resume the zone from the state written before a restart, the refresh
+    /// timers resume from the last success only when the loaded copy has the same serial.
+    pub fn restore_state(&self, zone: &DNSName, saved: &ZoneState) {
+        if let Some(state) = self.zones.write().unwrap().get_mut(zone) {
+            if let (true, Some(last_success)) = (state.serial == saved.serial, saved.last_success) {
+                state.last_refresh = last_success;
+            }
+            state.last_failure = saved.last_failure;
+            state.master = saved.master.clone();
+        }
+    }
+
+    /// zone_state return the state of the zone to write for the next startup, None if the
+    /// zone is not a secondary zone. a zone never refreshed has no last success.
+    pub fn zone_state(&self, zone: &DNSName) -> Option<ZoneState> {
+        self.zones.read().unwrap().get(zone).map(|state| ZoneState {
+            serial: state.serial,
+            last_success: Some(state.last_refresh).filter(|time| *time > 0),
+            last_failure: state.last_failure,
+            master: state.master.clone(),
+        })
+    }
+
+    /// mark_failed record the time of a failed refresh of the zone.
+    pub fn mark_failed(&self, zone: &DNSName) {
+        if let Some(state) = self.zones.write().unwrap().get_mut(zone) {
+            state.last_failure = Some(self.clock.now());
+        }
+    }
+
+    /// set_master record the master the zone was refreshed from.
+    pub fn set_master(&self, zone: &DNSName, master: &str) {
+        if let Some(state) = self.zones.write().unwrap().get_mut(zone) {
+            state.master = Some(master.to_owned());
+        }
+    }
+
+    /// save_state write the state of the zone next to its storage file.
+    pub fn save_state(&self, zone: &DNSName, storage_file: &str) {
+        let path = state_path(storage_file);
+        if let Some(state) = self.zone_state(zone) {
+            if let Err(err) = state.save(&path) {
+                warn!(
+                    "write zone {} state to {} fail: {}",
+                    zone.to_string(),
+                    path,
+                    err
+                );
+            }
+        }
+    }
+
+    fn add_history(&self, zone: &DNSName, serial: u32) {
+        let mut history = self.history.write().unwrap();
+        let history = history.entry(zone.clone()).or_insert_with(VecDeque::new);
+        if history.len() >= MAX_REFRESH_HISTORY {
+            history.pop_front();
+        }
+        history.push_back((self.clock.now(), serial));
+    }
+
+    /// history return the (unix time, serial) of the recent refreshes of the zone.
+    pub fn history(&self, zone: &DNSName) -> Vec<(u64, u32)> {
+        self.history
+            .read()
+            .unwrap()
+            .get(zone)
+            .map(|history| history.iter().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    /// unregister remove the secondary zone, return false if the zone is not registered.
+    pub fn unregister(&self, zone: &DNSName) -> bool {
+        self.history.write().unwrap().remove(zone);
+        self.zones.write().unwrap().remove(zone).is_some()
+    }
+
+    /// mark_refreshed update the soa timers after a successful refresh.
+    pub fn mark_refreshed(&self, zone: &DNSName, soa: &DnsTypeSOA) {
+        if let Some(state) = self.zones.write().unwrap().get_mut(zone) {
+            state.serial = soa.serial();
+            state.refresh = soa.refresh();
+            state.retry = soa.retry();
+            state.expire = soa.expire();
+            state.last_refresh = self.clock.now();
+        } else {
+            return;
+        }
+        self.add_history(zone, soa.serial());
+    }
+
+    /// freshness return None if the zone is not a secondary zone.
+    pub fn freshness(&self, zone: &DNSName) -> Option<ZoneFreshness> {
+        let now = self.clock.now();
+        self.zones
+            .read()
+            .unwrap()
+            .get(zone)
+            .map(|state| state.freshness(now))
+    }
+
+    /// zones_need_refresh return all secondary zones which refresh timer lapsed.
+    pub fn zones_need_refresh(&self) -> Vec<DNSName> {
+        let now = self.clock.now();
+        self.zones
+            .read()
+            .unwrap()
+            .iter()
+            .filter(|(_, state)| state.freshness(now) != ZoneFreshness::Fresh)
+            .map(|(zone, _)| zone.clone())
+            .collect()
+    }
+
+    /// check_master query the soa serial of the zone from master, return true when the
+    /// master has a newer serial and the zone need transfer, otherwise the zone is marked
+    /// as refreshed.
+    pub async fn check_master(
+        &self,
+        zone: &DNSName,
+        master: SocketAddr,
+        option: &QueryOption,
+    ) -> Result<bool, OtterError> {
+        let soa = query_soa(master, zone, option).await?;
+        Ok(self.check_serial(zone, &soa))
+    }
+
+    /// check_serial return true when the master soa has a newer serial, otherwise the zone
+    /// is marked as refreshed.
+    pub fn check_serial(&self, zone: &DNSName, soa: &DnsTypeSOA) -> bool {
+        let serial = match self.zones.read().unwrap().get(zone) {
+            Some(state) => state.serial,
+            None => return false,
+        };
+        if serial_gt(soa.serial(), serial) {
+            return true;
+        }
+        self.mark_refreshed(zone, soa);
+        false
+    }
+
+    /// report return (zone, serial, freshness) of all secondary zones.
+    pub fn report(&self) -> Vec<(DNSName, u32, ZoneFreshness)> {
+        let now = self.clock.now();
+        self.zones
+            .read()
+            .unwrap()
+            .iter()
+            .map(|(zone, state)| (zone.clone(), state.serial, state.freshness(now)))
+            .collect()
+    }
+
+    /// now return the current time of the scheduler clock.
+    pub fn now(&self) -> u64 {
+        self.clock.now()
+    }
+}
+
+/// first retry delay(seconds) of a failed master, doubled by each following failure
+const MASTER_BACKOFF_BASE: u64 = 10;
+/// max retry delay(seconds) of a failed master
+const MASTER_BACKOFF_MAX: u64 = 3600;
+
+/// Master is a remote master of a secondary zone, key is the tsig key id of the remote
+/// used for the queries and transfers sent to it, and source is the local address they
+/// are sent from.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Master {
+    pub id: String,
+    pub addr: SocketAddr,
+    pub key: Option<String>,
+    pub source: Option<SocketAddr>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+struct MasterBackoff {
+    failures: u32,
+    retry_at: u64,
+}
+
+/// MasterList holds the masters of a secondary zone in the configured order, the master
+/// which last succeeded is tried first and a failed master is skipped until its
+/// exponential backoff lapses.
+#[derive(Debug, Clone)]
+pub struct MasterList {
+    masters: Vec<Master>,
+    backoff: Vec<MasterBackoff>,
+    preferred: usize,
+}
+
+impl MasterList {
+    pub fn new(masters: Vec<Master>) -> MasterList {
+        MasterList {
+            backoff: vec![MasterBackoff::default(); masters.len()],
+            masters,
+            preferred: 0,
+        }
+    }
+
+    pub fn masters(&self) -> &[Master] {
+        &self.masters
+    }
+
+    pub fn get(&self, index: usize) -> Option<&Master> {
+        self.masters.get(index)
+    }
+
+    /// preferred return the master which last succeeded, the first one if none succeeded.
+    pub fn preferred(&self) -> Option<&Master> {
+        self.masters.get(self.preferred)
+    }
+
+    /// prefer try the master with the id first in the next refresh, like it last succeeded.
+    pub fn prefer(&mut self, id: &str) {
+        if let Some(index) = self.masters.iter().position(|master| master.id == id) {
+            self.preferred = index;
+        }
+    }
+
+    /// candidates return the indexes of the masters to try at now, the preferred master
+    /// first and the others in the configured order, masters in backoff are skipped.
+    pub fn candidates(&self, now: u64) -> Vec<usize> {
+        std::iter::once(self.preferred)
+            .chain((0..self.masters.len()).filter(|index| *index != self.preferred))
+            .filter(|index| {
+                self.backoff
+                    .get(*index)
+                    .map_or(false, |backoff| backoff.retry_at <= now)
+            })
+            .collect()
+    }
+
+    /// mark_success clear the backoff of the master and prefer it in the next refresh.
+    pub fn mark_success(&mut self, index: usize) {
+        if let Some(backoff) = self.backoff.get_mut(index) {
+            *backoff = MasterBackoff::default();
+            self.preferred = index;
+        }
+    }
+
+    /// mark_failure double the retry delay of the master, up to MASTER_BACKOFF_MAX.
+    pub fn mark_failure(&mut self, index: usize, now: u64) {
+        if let Some(backoff) = self.backoff.get_mut(index) {
+            // 10 << 16 is already great than MASTER_BACKOFF_MAX
+            let delay = (MASTER_BACKOFF_BASE << backoff.failures.min(16)).min(MASTER_BACKOFF_MAX);
+            backoff.failures = backoff.failures.saturating_add(1);
+            backoff.retry_at = now + delay;
+        }
+    }
+}
+
+fn zone_message(zone: &DNSName, op_code: OpCode) -> Result<Message, OtterError> {
+    let mut header = Header::new();
+    header.set_op_code(op_code);
+    let mut message = Message::new_with_header(header);
+    message.set_question(Question::new(
+        &zone.to_string(),
+        DNSType::SOA,
+        DNSClass::IN,
+    )?);
+    Ok(message)
+}
+
+/// query_soa ask the master for the soa record of the zone.
+pub async fn query_soa(
+    master: SocketAddr,
+    zone: &DNSName,
+    option: &QueryOption,
+) -> Result<DnsTypeSOA, OtterError> {
+    let mut message = zone_message(zone, OpCode::Query)?;
+    let response = query(master, &mut message, option).await?;
+    soa_answer(master, zone, &response)
+}
+
+/// query_soa_tcp ask the master for the soa record of the zone over tcp, only raw bytes
+/// are kept across the await points so the future can be spawned.
+pub async fn query_soa_tcp(
+    master: SocketAddr,
+    zone: &DNSName,
+    option: &QueryOption,
+) -> Result<DnsTypeSOA, OtterError> {
+    let request = {
+        let mut message = zone_message(zone, OpCode::Query)?;
+        message.header.set_random_id();
+        message.encode(true)?
+    };
+    let response = forward(master, &request, option).await?;
+    soa_answer(master, zone, &Message::parse_dns_message(&response)?)
+}
+
+/// soa_answer return the soa record in the answer section of the response.
+fn soa_answer(
+    master: SocketAddr,
+    zone: &DNSName,
+    response: &Message,
+) -> Result<DnsTypeSOA, OtterError> {
+    if response.header.rcode() != RCode::NoError {
+        return Err(NetworkError::UnexpectedResponse(
+            master.to_string(),
+            format!("soa query of {} return {:?}", zone, response.header.rcode()),
+        )
+        .into());
+    }
+    response
+        .answers_of_type::<DnsTypeSOA>()
+        .next()
+        .map(|(_, _, soa)| soa.clone())
+        .ok_or_else(|| {
+            NetworkError::UnexpectedResponse(
+                master.to_string(),
+                format!("no soa record of {} in answer", zone),
+            )
+            .into()
+        })
+}
+
+/// send_notify notify the slave that the zone is changed and wait for its response.
+pub async fn send_notify(
+    slave: SocketAddr,
+    zone: &DNSName,
+    option: &QueryOption,
+) -> Result<(), OtterError> {
+    let mut message = zone_message(zone, OpCode::Notify)?;
+    message.header.set_aa(true);
+    let response = query(slave, &mut message, option).await?;
+    if response.header.rcode() != RCode::NoError {
+        return Err(NetworkError::UnexpectedResponse(
+            slave.to_string(),
+            format!("notify of {} return {:?}", zone, response.header.rcode()),
+        )
+        .into());
+    }
+    Ok(())
+}
+
+/// load_secondary load the local copy of the secondary zone written after the last
+/// transfer and resume the refresh timers from its state file, or from the transfer header
+/// of the copy when the state file is missing. return false when the copy is missing or
+/// corrupted, then the zone is registered without data and transferred from master.
+pub fn load_secondary(
+    storage: &mut SafeRBTreeStorage,
+    scheduler: &TransferScheduler,
+    zone: &ZoneSetting,
+    option: &ZoneOption,
+) -> bool {
+    let path = zone.storage_file();
+    let path = path.as_str();
+    let origin = fqdn(&zone.domain);
+    let loaded = storage
+        .reload_zone(path, Some(origin.clone()), option)
+        .map_err(|err| err.to_string())
+        .and_then(|start_point| {
+            let apex = start_point.read().unwrap().get_name();
+            match storage.get_zone_soa(&apex) {
+                Ok(soa) => Ok((apex, soa)),
+                Err(err) => Err(err.to_string()),
+            }
+        });
+    let (apex, soa) = match loaded {
+        Ok(loaded) => loaded,
+        Err(err) => {
+            warn!(
+                "load secondary zone {} from {} fail: {}, transfer from master",
+                origin, path, err
+            );
+            if let Ok(apex) = DNSName::new(&origin, None) {
+                scheduler.register_pending(apex.clone(), zone.serve_stale, zone.max_stale);
+                if let Some(state) = ZoneState::load(&state_path(path)) {
+                    scheduler.restore_state(&apex, &state);
+                }
+            }
+            return false;
+        }
+    };
+    scheduler.register(apex.clone(), &soa, zone.serve_stale, zone.max_stale);
+    if let Some(state) = ZoneState::load(&state_path(path)) {
+        scheduler.restore_state(&apex, &state);
+    } else if let Some(header) = read_transfer_header(path) {
+        if header.serial == soa.serial() {
+            scheduler.set_last_refresh(&apex, header.refreshed);
+        }
+    }
+    true
+}
+
+/// TransferLimits bound the zone transfer received from the master, unlimited if not set.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct TransferLimits {
+    pub max_records: Option<usize>,
+    pub max_bytes: Option<usize>,
+}
+
+/// TransferChecker check the messages of an incoming zone transfer one by one. the
+/// transfer must start with the apex soa and end with the identical soa, every record
+/// must be at or below the apex and the limits must not be exceeded.
+#[derive(Debug)]
+pub struct TransferChecker {
+    zone: DNSName,
+    limits: TransferLimits,
+    records: usize,
+    bytes: usize,
+    soa: Option<DnsTypeSOA>,
+    complete: bool,
+}
+
+impl TransferChecker {
+    pub fn new(zone: DNSName, limits: TransferLimits) -> TransferChecker {
+        TransferChecker {
+            zone,
+            limits,
+            records: 0,
+            bytes: 0,
+            soa: None,
+            complete: false,
+        }
+    }
+
+    /// complete return true after the closing soa is received.
+    pub fn complete(&self) -> bool {
+        self.complete
+    }
+
+    fn reject(&self, reason: &str) -> TransferError {
+        TransferError::SOAMismatch(self.zone.to_string(), reason.to_owned())
+    }
+
+    /// add_bytes count the size of the next message before it is read.
+    pub fn add_bytes(&mut self, size: usize) -> Result<(), TransferError> {
+        self.bytes += size;
+        match self.limits.max_bytes {
+            Some(max_bytes) if self.bytes > max_bytes => Err(TransferError::TooManyBytes(
+                self.zone.to_string(),
+                max_bytes,
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// add_record check the next record of the transfer.
+    pub fn add_record(&mut self, rr: &ResourceRecord) -> Result<(), TransferError> {
+        if self.complete {
+            return Err(self.reject("records after the closing soa"));
+        }
+        self.records += 1;
+        if let Some(max_records) = self.limits.max_records {
+            if self.records > max_records {
+                return Err(TransferError::TooManyRecords(
+                    self.zone.to_string(),
+                    max_records,
+                ));
+            }
+        }
+        if !rr.get_dname().is_part_of(&self.zone) {
+            return Err(TransferError::OutOfZone(
+                self.zone.to_string(),
+                rr.get_dname().to_string(),
+            ));
+        }
+        if rr.get_type() != DNSType::SOA {
+            return match self.soa {
+                Some(_) => Ok(()),
+                None => Err(self.reject("transfer not start with soa")),
+            };
+        }
+        if rr.get_dname() != &self.zone {
+            return Err(self.reject("soa not at the zone apex"));
+        }
+        let soa = rr
+            .rdata_as::<DnsTypeSOA>()
+            .ok_or_else(|| self.reject("soa without data"))?;
+        match &self.soa {
+            None => self.soa = Some(soa.clone()),
+            Some(first) if first == soa => self.complete = true,
+            Some(_) => return Err(self.reject("closing soa differs from the opening soa")),
+        }
+        Ok(())
+    }
+
+    /// add_message check the size and all answers of the raw message.
+    pub fn add_message(&mut self, message: &[u8]) -> Result<(), OtterError> {
+        let message = Message::parse_dns_message(message)?;
+        if message.header.rcode() != RCode::NoError {
+            return Err(NetworkError::UnexpectedResponse(
+                self.zone.to_string(),
+                format!("transfer return {:?}", message.header.rcode()),
+            )
+            .into());
+        }
+        for answer in message.answers() {
+            self.add_record(answer)?;
+        }
+        Ok(())
+    }
+}
+
+/// transfer_in request the full zone(axfr) from master over tcp, each message is checked
+/// by the TransferChecker as soon as it is received. the raw messages are returned after
+/// the closing soa, records are parsed from them by transfer_records.
+pub async fn transfer_in(
+    master: SocketAddr,
+    zone: &DNSName,
+    option: &QueryOption,
+    limits: TransferLimits,
+) -> Result<Vec<Vec<u8>>, OtterError> {
+    // the message is not Send, keep it out of the await points
+    let request = {
+        let mut message = Message::new_axfr_query(zone);
+        message.encode(true)?
+    };
+    let mut stream = match timeout(option.timeout, connect(master, option.source)).await {
+        Ok(result) => result?,
+        Err(_) => return Err(NetworkError::QueryTimeout(master.to_string()).into()),
+    };
+    let mut frame = (request.len() as u16).to_be_bytes().to_vec();
+    frame.extend_from_slice(&request);
+    stream.write_all(&frame).await.map_err(NetworkError::from)?;
+    let mut checker = TransferChecker::new(zone.clone(), limits);
+    let mut messages = vec![];
+    while !checker.complete() {
+        let size = match timeout(option.timeout, stream.read_u16()).await {
+            Ok(result) => result.map_err(NetworkError::from)? as usize,
+            Err(_) => return Err(NetworkError::QueryTimeout(master.to_string()).into()),
+        };
+        checker.add_bytes(size + 2)?;
+        let mut response = vec![0; size];
+        match timeout(option.timeout, stream.read_exact(&mut response)).await {
+            Ok(result) => result.map_err(NetworkError::from)?,
+            Err(_) => return Err(NetworkError::QueryTimeout(master.to_string()).into()),
+        };
+        #[cfg(feature = "testing")]
+        match timeout(
+            option.timeout,
+            crate::dnsclient::inject_fault(master, &mut response),
+        )
+        .await
+        {
+            Ok(Some(duplicate)) => {
+                accept_message(master, &request, duplicate, &mut checker, &mut messages)?
+            }
+            Ok(None) => {}
+            Err(_) => return Err(NetworkError::QueryTimeout(master.to_string()).into()),
+        }
+        accept_message(master, &request, response, &mut checker, &mut messages)?;
+    }
+    Ok(messages)
+}
+
+/// accept_message check the id and the records of the transfer message and keep it.
+fn accept_message(
+    master: SocketAddr,
+    request: &[u8],
+    response: Vec<u8>,
+    checker: &mut TransferChecker,
+    messages: &mut Vec<Vec<u8>>,
+) -> Result<(), OtterError> {
+    if response.len() < 2 || response[0..2] != request[0..2] {
+        return Err(NetworkError::UnexpectedResponse(
+            master.to_string(),
+            "transfer message id mismatch".to_string(),
+        )
+        .into());
+    }
+    checker.add_message(&response)?;
+    messages.push(response);
+    Ok(())
+}
+
+/// transfer_records return the records of the checked transfer messages without the
+/// closing soa.
+pub fn transfer_records(messages: &[Vec<u8>]) -> Result<Vec<ResourceRecord>, OtterError> {
+    let mut records = vec![];
+    for message in messages {
+        let message = Message::parse_dns_message(message)?;
+        records.extend(message.answers().into_iter().cloned());
+    }
+    records.pop();
+    Ok(records)
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use super::*;
+    use crate::axfr::{transfer_messages, TransferFormat};
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    #[derive(Debug, Default)]
+    pub(crate) struct FakeClock(AtomicU64);
+
+    impl FakeClock {
+        pub(crate) fn advance(&self, seconds: u64) {
+            self.0.fetch_add(seconds, Ordering::SeqCst);
+        }
+    }
+
+    impl Clock for FakeClock {
+        fn now(&self) -> u64 {
+            self.0.load(Ordering::SeqCst)
+        }
+    }
+
+    fn example_soa() -> DnsTypeSOA {
+        DnsTypeSOA::new(
+            "ns.example.com.",
+            "admin.example.com.",
+            1,
+            100,
+            10,
+            1000,
+            60,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_zone_freshness() {
+        let clock = Arc::new(FakeClock::default());
+        let scheduler = TransferScheduler::new(clock.clone());
+        let zone = DNSName::new("example.com.", None).unwrap();
+        assert_eq!(scheduler.freshness(&zone), None);
+        scheduler.register(zone.clone(), &example_soa(), false, None);
+        assert_eq!(scheduler.freshness(&zone), Some(ZoneFreshness::Fresh));
+        clock.advance(100);
+        assert_eq!(scheduler.freshness(&zone), Some(ZoneFreshness::NeedRefresh));
+        assert_eq!(scheduler.zones_need_refresh(), vec![zone.clone()]);
+        clock.advance(900);
+        assert_eq!(scheduler.freshness(&zone), Some(ZoneFreshness::Expired));
+        scheduler.mark_refreshed(&zone, &example_soa());
+        assert_eq!(scheduler.freshness(&zone), Some(ZoneFreshness::Fresh));
+        assert_eq!(
+            scheduler.report(),
+            vec![(zone.clone(), 1, ZoneFreshness::Fresh)]
+        );
+        assert_eq!(scheduler.history(&zone), vec![(0, 1), (1000, 1)]);
+        scheduler.unregister(&zone);
+        assert!(scheduler.history(&zone).is_empty());
+    }
+
+    #[test]
+    fn test_master_list() {
+        let master = |id: &str, port: u16| Master {
+            id: id.to_owned(),
+            addr: SocketAddr::from(([127, 0, 0, 1], port)),
+            key: None,
+            source: None,
+        };
+        let mut masters = MasterList::new(vec![
+            master("master01", 5301),
+            master("master02", 5302),
+            master("master03", 5303),
+        ]);
+        assert_eq!(masters.candidates(0), vec![0, 1, 2]);
+        assert_eq!(masters.preferred().unwrap().id, "master01");
+
+        // the failed master is skipped until the backoff lapses, and the delay is doubled
+        masters.mark_failure(0, 0);
+        masters.mark_success(1);
+        assert_eq!(masters.preferred().unwrap().id, "master02");
+        assert_eq!(masters.candidates(0), vec![1, 2]);
+        assert_eq!(masters.candidates(MASTER_BACKOFF_BASE), vec![1, 0, 2]);
+        masters.mark_failure(0, 100);
+        assert_eq!(masters.candidates(100 + MASTER_BACKOFF_BASE), vec![1, 2]);
+        assert_eq!(
+            masters.candidates(100 + MASTER_BACKOFF_BASE * 2),
+            vec![1, 0, 2]
+        );
+        for _ in 0..64 {
+            masters.mark_failure(2, 100);
+        }
+        assert_eq!(masters.candidates(100 + MASTER_BACKOFF_MAX - 1), vec![1, 0]);
+        assert_eq!(masters.candidates(100 + MASTER_BACKOFF_MAX), vec![1, 0, 2]);
+
+        // a success clears the backoff
+        masters.mark_success(2);
+        assert_eq!(masters.candidates(120), vec![2, 0, 1]);
+        assert!(MasterList::new(vec![]).candidates(0).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_check_master() {
+        let server = tokio::net::UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let master = server.local_addr().unwrap();
+        tokio::spawn(async move {
+            let mut buffer = vec![0; 512];
+            for serial in [1, 2].iter() {
+                let (size, client) = server.recv_from(&mut buffer).await.unwrap();
+                let request = Message::parse_dns_message(&buffer[..size]).unwrap();
+                let mut response = Message::new_not_implemented_message(&request);
+                response.header.set_rcode(RCode::NoError);
+                let soa = DnsTypeSOA::new(
+                    "ns.example.com.",
+                    "admin.example.com.",
+                    *serial,
+                    100,
+                    10,
+                    1000,
+                    60,
+                )
+                .unwrap();
+                response.append_answer(
+                    ResourceRecord::new(
+                        "example.com.",
+                        DNSType::SOA,
+                        DNSClass::IN,
+                        3600,
+                        Some(Box::new(soa)),
+                    )
+                    .unwrap(),
+                );
+                let response = response.encode(true).unwrap();
+                server.send_to(&response, client).await.unwrap();
+            }
+        });
+        let clock = Arc::new(FakeClock::default());
+        let scheduler = TransferScheduler::new(clock.clone());
+        let zone = DNSName::new("example.com.", None).unwrap();
+        scheduler.register(zone.clone(), &example_soa(), false, None);
+        clock.advance(100);
+        let option = QueryOption {
+            timeout: std::time::Duration::from_millis(500),
+            ..Default::default()
+        };
+        assert!(!scheduler
+            .check_master(&zone, master, &option)
+            .await
+            .unwrap());
+        assert_eq!(scheduler.freshness(&zone), Some(ZoneFreshness::Fresh));
+        assert!(scheduler
+            .check_master(&zone, master, &option)
+            .await
+            .unwrap());
+    }
+
+    /// scripted_master answer one axfr request with the records, the records are packed
+    /// like a normal transfer.
+    async fn scripted_master(records: Vec<ResourceRecord>) -> SocketAddr {
+        let mut query = Message::new_with_header(Header::new());
+        query.set_question(Question::new("example.com.", DNSType::AXFR, DNSClass::IN).unwrap());
+        let messages = transfer_messages(&query, records, TransferFormat::default()).unwrap();
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let master = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let size = stream.read_u16().await.unwrap();
+            let mut request = vec![0; size as usize];
+            stream.read_exact(&mut request).await.unwrap();
+            for mut message in messages {
+                message[2..4].copy_from_slice(&request[0..2]);
+                if stream.write_all(&message).await.is_err() {
+                    break;
+                }
+            }
+        });
+        master
+    }
+
+    async fn transfer_example(
+        records: Vec<ResourceRecord>,
+        limits: TransferLimits,
+    ) -> Result<Vec<Vec<u8>>, OtterError> {
+        let master = scripted_master(records).await;
+        let zone = DNSName::new("example.com.", None).unwrap();
+        let option = QueryOption {
+            timeout: std::time::Duration::from_millis(500),
+            ..Default::default()
+        };
+        transfer_in(master, &zone, &option, limits).await
+    }
+
+    fn example_records() -> Vec<ResourceRecord> {
+        let storage =
+            SafeRBTreeStorage::new_zone_from_file("../storage/test/example.zone", None).unwrap();
+        let apex = DNSName::new("example.com.", None).unwrap();
+        storage.transfer_records(&apex).unwrap()
+    }
+
+    fn expect_rejected(result: Result<Vec<Vec<u8>>, OtterError>, expected: TransferError) {
+        match result {
+            Err(OtterError::TransferError(err)) => assert_eq!(err, expected),
+            other => panic!("expect {:?}, got {:?}", expected, other.map(|m| m.len())),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_transfer_in() {
+        let records = example_records();
+        let total = records.len();
+        let messages = transfer_example(records, TransferLimits::default())
+            .await
+            .unwrap();
+        let records = transfer_records(&messages).unwrap();
+        assert_eq!(records.len(), total - 1);
+        assert_eq!(records[0].get_type(), DNSType::SOA);
+
+        let limits = TransferLimits {
+            max_records: Some(total),
+            max_bytes: Some(65535),
+        };
+        assert!(transfer_example(example_records(), limits).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_transfer_in_rejected() {
+        let zone = "example.com.".to_string();
+        let limits = TransferLimits {
+            max_records: Some(3),
+            ..Default::default()
+        };
+        let result = transfer_example(example_records(), limits).await;
+        expect_rejected(result, TransferError::TooManyRecords(zone.clone(), 3));
+
+        let limits = TransferLimits {
+            max_bytes: Some(100),
+            ..Default::default()
+        };
+        let result = transfer_example(example_records(), limits).await;
+        expect_rejected(result, TransferError::TooManyBytes(zone.clone(), 100));
+
+        let mut records = example_records();
+        let outside = ResourceRecord::new(
+            "www.example.org.",
+            DNSType::SOA,
+            DNSClass::IN,
+            3600,
+            Some(Box::new(example_soa())),
+        )
+        .unwrap();
+        records.insert(1, outside);
+        let result = transfer_example(records, TransferLimits::default()).await;
+        expect_rejected(
+            result,
+            TransferError::OutOfZone(zone.clone(), "www.example.org.".to_string()),
+        );
+
+        let mut records = example_records();
+        let soa = records.pop().unwrap();
+        let mut changed = soa.clone();
+        changed.set_data(Some(Box::new(example_soa())));
+        records.push(changed);
+        let result = transfer_example(records, TransferLimits::default()).await;
+        expect_rejected(
+            result,
+            TransferError::SOAMismatch(
+                zone.clone(),
+                "closing soa differs from the opening soa".to_string(),
+            ),
+        );
+
+        let mut records = example_records();
+        records.remove(0);
+        let result = transfer_example(records, TransferLimits::default()).await;
+        expect_rejected(
+            result,
+            TransferError::SOAMismatch(zone, "transfer not start with soa".to_string()),
+        );
+    }
+
+    #[test]
+    fn test_zone_serve_stale() {
+        let clock = Arc::new(FakeClock::default());
+        let scheduler = TransferScheduler::new(clock.clone());
+        let zone = DNSName::new("example.com.", None).unwrap();
+        scheduler.register(zone.clone(), &example_soa(), true, Some(500));
+        clock.advance(1000);
+        assert_eq!(scheduler.freshness(&zone), Some(ZoneFreshness::Stale));
+        clock.advance(500);
+        assert_eq!(scheduler.freshness(&zone), Some(ZoneFreshness::Expired));
+    }
+}
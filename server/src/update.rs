@@ -0,0 +1,318 @@
+use dnsproto::dnsname::DNSName;
+use dnsproto::message::Message;
+use dnsproto::meta::{DNSType, ResourceRecord};
+use dnsproto::utils::fqdn;
+use otterlib::errors::DNSProtoErr;
+use otterlib::setting::{UpdateAction, UpdateMatch, UpdatePolicyRule, ACL};
+use std::net::IpAddr;
+use std::str::FromStr;
+
+/// UpdateRule is the compiled update policy rule.
+#[derive(Debug, Clone)]
+struct UpdateRule {
+    action: UpdateAction,
+    // the key and the address of the rule, any address if not set
+    sender: ACL,
+    matches: UpdateMatch,
+    name: Option<DNSName>,
+    // all types if empty
+    types: Vec<DNSType>,
+}
+
+/// UpdatePolicy decide which records of an update the sender can change, the rules are
+/// evaluated in order and a record no rule matches is denied.
+#[derive(Debug, Clone, Default)]
+pub struct UpdatePolicy {
+    rules: Vec<UpdateRule>,
+}
+
+impl UpdatePolicy {
+    pub fn new(rules: &[UpdatePolicyRule]) -> Result<UpdatePolicy, DNSProtoErr> {
+        let mut policy = UpdatePolicy::default();
+        for rule in rules.iter() {
+            let name = match &rule.name {
+                Some(name) => Some(DNSName::new(fqdn(name.trim()).as_str(), None)?),
+                None => None,
+            };
+            let mut types = vec![];
+            for dtype in rule.types.iter() {
+                match DNSType::from_str(dtype.trim()) {
+                    Ok(dtype) => types.push(dtype),
+                    Err(_) => {
+                        return Err(DNSProtoErr::GeneralErr(format!(
+                            "update policy type {} is not valid",
+                            dtype
+                        )))
+                    }
+                }
+            }
+            // ANY covers all types
+            if types.contains(&DNSType::Any) {
+                types.clear();
+            }
+            policy.rules.push(UpdateRule {
+                action: rule.action,
+                sender: ACL {
+                    id: String::new(),
+                    address: rule.address.clone().unwrap_or_else(|| "any".to_owned()),
+                    action: "update".to_owned(),
+                    key: rule.key.clone(),
+                },
+                matches: rule.matches,
+                name,
+                types,
+            });
+        }
+        Ok(policy)
+    }
+
+    /// allows return true when the first rule matching the sender, the owner and the type
+    /// grants the change. the ANY type of a record deleting all rrsets of the owner is only
+    /// matched by the rules of all types.
+    pub fn allows(
+        &self,
+        remote: &IpAddr,
+        key: Option<&DNSName>,
+        owner: &DNSName,
+        dtype: DNSType,
+    ) -> bool {
+        let key_name = key.map(|key| key.to_string());
+        for rule in self.rules.iter() {
+            if !rule.sender.allows(remote, key_name.as_deref()) {
+                continue;
+            }
+            let owner_matched = match rule.matches {
+                UpdateMatch::Name => rule.name.as_ref() == Some(owner),
+                UpdateMatch::Subdomain => rule
+                    .name
+                    .as_ref()
+                    .map_or(false, |name| owner.is_part_of(name)),
+                UpdateMatch::SelfName => key == Some(owner),
+            };
+            if owner_matched && (rule.types.is_empty() || rule.types.contains(&dtype)) {
+                return rule.action == UpdateAction::Grant;
+            }
+        }
+        false
+    }
+
+    /// denied_record return the first record of the update section the sender can not
+    /// change, the update is applied only when all records are allowed.
+    pub fn denied_record<'a>(
+        &self,
+        update: &'a Message,
+        remote: &IpAddr,
+        key: Option<&DNSName>,
+    ) -> Option<&'a ResourceRecord> {
+        update
+            .authorities()
+            .into_iter()
+            .find(|rr| !self.allows(remote, key, rr.get_dname(), rr.get_type()))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use dnsproto::meta::{DNSClass, Header};
+
+    // rule build a rule from (action, match), (key, address), the name and the types
+    fn rule(
+        (action, matches): (UpdateAction, UpdateMatch),
+        (key, address): (Option<&str>, Option<&str>),
+        name: Option<&str>,
+        types: &[&str],
+    ) -> UpdatePolicyRule {
+        UpdatePolicyRule {
+            action,
+            key: key.map(str::to_owned),
+            address: address.map(str::to_owned),
+            matches,
+            name: name.map(str::to_owned),
+            types: types.iter().map(|dtype| dtype.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn test_update_policy_allows() {
+        let policy = UpdatePolicy::new(&[
+            rule(
+                (UpdateAction::Deny, UpdateMatch::Name),
+                (Some("ddns-key"), None),
+                Some("locked.dyn.example.com"),
+                &[],
+            ),
+            rule(
+                (UpdateAction::Grant, UpdateMatch::Name),
+                (Some("ddns-key"), None),
+                Some("host1.example.com."),
+                &["A", "AAAA"],
+            ),
+            rule(
+                (UpdateAction::Grant, UpdateMatch::Subdomain),
+                (Some("tsig-key"), None),
+                Some("dyn.example.com"),
+                &["ANY"],
+            ),
+            rule(
+                (UpdateAction::Grant, UpdateMatch::Subdomain),
+                (None, Some("10.0.0.0/8")),
+                Some("dyn.example.com"),
+                &["TXT"],
+            ),
+            rule(
+                (UpdateAction::Grant, UpdateMatch::SelfName),
+                (Some("ddns-key"), None),
+                None,
+                &[],
+            ),
+        ])
+        .unwrap();
+        let name = |name: &str| DNSName::new(name, None).unwrap();
+        let (inside, outside): (IpAddr, IpAddr) =
+            ("10.1.1.1".parse().unwrap(), "192.0.2.1".parse().unwrap());
+        let ddns = name("ddns-key.");
+        let tsig = name("tsig-key.");
+        for (remote, key, owner, dtype, allowed) in [
+            (
+                &outside,
+                Some(&ddns),
+                "host1.example.com.",
+                DNSType::A,
+                true,
+            ),
+            (
+                &outside,
+                Some(&ddns),
+                "HOST1.example.com.",
+                DNSType::AAAA,
+                true,
+            ),
+            (
+                &outside,
+                Some(&ddns),
+                "host1.example.com.",
+                DNSType::MX,
+                false,
+            ),
+            (
+                &outside,
+                Some(&ddns),
+                "host2.example.com.",
+                DNSType::A,
+                false,
+            ),
+            (
+                &outside,
+                Some(&tsig),
+                "host1.example.com.",
+                DNSType::A,
+                false,
+            ),
+            (&outside, None, "host1.example.com.", DNSType::A, false),
+            // the subdomain match covers the name itself and all types for ANY
+            (&outside, Some(&tsig), "dyn.example.com.", DNSType::MX, true),
+            (
+                &outside,
+                Some(&tsig),
+                "a.b.dyn.example.com.",
+                DNSType::Any,
+                true,
+            ),
+            (
+                &outside,
+                Some(&tsig),
+                "dyndns.example.com.",
+                DNSType::A,
+                false,
+            ),
+            // the first matching rule decides
+            (
+                &outside,
+                Some(&tsig),
+                "locked.dyn.example.com.",
+                DNSType::A,
+                true,
+            ),
+            (
+                &outside,
+                Some(&ddns),
+                "locked.dyn.example.com.",
+                DNSType::A,
+                false,
+            ),
+            // address rules without a key match unsigned and signed updates
+            (&inside, None, "a.dyn.example.com.", DNSType::TXT, true),
+            (
+                &inside,
+                Some(&ddns),
+                "a.dyn.example.com.",
+                DNSType::TXT,
+                true,
+            ),
+            (&inside, None, "a.dyn.example.com.", DNSType::A, false),
+            (&outside, None, "a.dyn.example.com.", DNSType::TXT, false),
+            // the self match is the name of the key
+            (&outside, Some(&ddns), "ddns-key.", DNSType::TXT, true),
+            (&outside, Some(&tsig), "ddns-key.", DNSType::TXT, false),
+            // deleting all rrsets of a name is not covered by a rule of some types
+            (
+                &outside,
+                Some(&ddns),
+                "host1.example.com.",
+                DNSType::Any,
+                false,
+            ),
+        ]
+        .iter()
+        {
+            assert_eq!(
+                policy.allows(remote, *key, &name(owner), *dtype),
+                *allowed,
+                "{} {:?} {} {}",
+                remote,
+                key,
+                owner,
+                dtype
+            );
+        }
+
+        let txt_update = |owners: &[&str]| {
+            let mut update = Message::new_with_header(Header::new());
+            for owner in owners.iter() {
+                update.append_authority(
+                    ResourceRecord::new(owner, DNSType::TXT, DNSClass::IN, 300, None).unwrap(),
+                );
+            }
+            update
+        };
+        // the whole update is denied when one record is not allowed, no rule grants TXT of
+        // host1 to any key
+        let update = txt_update(&["host1.example.com.", "a.dyn.example.com."]);
+        for key in [&ddns, &tsig].iter() {
+            let denied = policy.denied_record(&update, &inside, Some(*key)).unwrap();
+            assert_eq!(denied.get_dname(), &name("host1.example.com."));
+        }
+        let update = txt_update(&["a.dyn.example.com.", "b.dyn.example.com."]);
+        assert!(policy
+            .denied_record(&update, &inside, Some(&tsig))
+            .is_none());
+        assert!(UpdatePolicy::default()
+            .denied_record(&Message::new_with_header(Header::new()), &inside, None)
+            .is_none());
+    }
+
+    #[test]
+    fn test_update_policy_invalid() {
+        let long = format!("{}.example.com", "a".repeat(64));
+        for (name, dtype) in [(long.as_str(), "A"), ("host.example.com", "BAD")].iter() {
+            let rules = [rule(
+                (UpdateAction::Grant, UpdateMatch::Name),
+                (Some("ddns-key"), None),
+                Some(name),
+                &[dtype],
+            )];
+            assert!(UpdatePolicy::new(&rules).is_err(), "{} {}", name, dtype);
+        }
+    }
+}
@@ -0,0 +1,177 @@
+use std::fs::File;
+use std::io::Write;
+
+// the first line of the state file
+const STATE_HEADER: &str = "# otter zone state";
+
+/// ZoneState is the refresh state of a secondary zone written to a small toml file next to
+/// its storage file, so the refresh timers and the readiness resume after a restart
+/// instead of treating the loaded copy as just transferred.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ZoneState {
+    /// serial of the zone when the state is written
+    pub serial: u32,
+    /// unix time of the last successful refresh
+    pub last_success: Option<u64>,
+    /// unix time of the last failed refresh
+    pub last_failure: Option<u64>,
+    /// id of the master the zone was last refreshed from
+    pub master: Option<String>,
+}
+
+/// state_path return the path of the state file of the zone storage file.
+pub fn state_path(storage_file: &str) -> String {
+    format!("{}.state", storage_file)
+}
+
+impl ZoneState {
+    pub fn to_toml(&self) -> String {
+        let mut lines = vec![STATE_HEADER.to_owned(), format!("serial = {}", self.serial)];
+        if let Some(last_success) = self.last_success {
+            lines.push(format!("last_success = {}", last_success));
+        }
+        if let Some(last_failure) = self.last_failure {
+            lines.push(format!("last_failure = {}", last_failure));
+        }
+        if let Some(master) = &self.master {
+            lines.push(format!("master = {}", toml_string(master)));
+        }
+        lines.push(String::new());
+        lines.join("\n")
+    }
+
+    /// from_toml parse the state file written by to_toml, the serial is required and
+    /// unknown keys are skipped.
+    pub fn from_toml(text: &str) -> Result<ZoneState, String> {
+        let mut state = ZoneState::default();
+        let mut serial = None;
+        for (index, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let mut fields = line.splitn(2, '=');
+            let (key, value) = match (fields.next(), fields.next()) {
+                (Some(key), Some(value)) => (key.trim(), value.trim()),
+                _ => return Err(format!("line {}: expect key = value", index + 1)),
+            };
+            let invalid = || format!("line {}: invalid {} '{}'", index + 1, key, value);
+            match key {
+                "serial" => serial = Some(value.parse().map_err(|_| invalid())?),
+                "last_success" => state.last_success = Some(value.parse().map_err(|_| invalid())?),
+                "last_failure" => state.last_failure = Some(value.parse().map_err(|_| invalid())?),
+                "master" => state.master = Some(parse_toml_string(value).ok_or_else(invalid)?),
+                _ => {}
+            }
+        }
+        state.serial = serial.ok_or_else(|| "serial not found".to_owned())?;
+        Ok(state)
+    }
+
+    /// load read the state file, None when the file is missing. a corrupted file is
+    /// ignored with a warning.
+    pub fn load(path: &str) -> Option<ZoneState> {
+        let text = std::fs::read_to_string(path).ok()?;
+        match ZoneState::from_toml(&text) {
+            Ok(state) => Some(state),
+            Err(err) => {
+                warn!("ignore corrupted zone state file {}: {}", path, err);
+                None
+            }
+        }
+    }
+
+    /// save write the state to a temporary file first and then rename it, so a failed
+    /// write never leaves a truncated state file.
+    pub fn save(&self, path: &str) -> std::io::Result<()> {
+        let temp_path = format!("{}.tmp", path);
+        {
+            let mut file = File::create(&temp_path)?;
+            file.write_all(self.to_toml().as_bytes())?;
+            file.sync_all()?;
+        }
+        std::fs::rename(&temp_path, path)
+    }
+}
+
+fn toml_string(input: &str) -> String {
+    let mut output = String::with_capacity(input.len() + 2);
+    output.push('"');
+    for c in input.chars() {
+        match c {
+            '"' => output.push_str("\\\""),
+            '\\' => output.push_str("\\\\"),
+            c => output.push(c),
+        }
+    }
+    output.push('"');
+    output
+}
+
+fn parse_toml_string(value: &str) -> Option<String> {
+    let inner = value.strip_prefix('"')?.strip_suffix('"')?;
+    let mut output = String::with_capacity(inner.len());
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => output.push(chars.next().filter(|c| *c == '"' || *c == '\\')?),
+            '"' => return None,
+            c => output.push(c),
+        }
+    }
+    Some(output)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_zone_state_toml() {
+        let state = ZoneState {
+            serial: 2020091025,
+            last_success: Some(1_600_000_000),
+            last_failure: None,
+            master: Some("master \"one\"".to_owned()),
+        };
+        let text = state.to_toml();
+        assert!(text.starts_with(STATE_HEADER));
+        assert!(text.contains("master = \"master \\\"one\\\"\""));
+        assert_eq!(ZoneState::from_toml(&text), Ok(state));
+
+        assert_eq!(
+            ZoneState::from_toml("serial = 1\nunknown = 2\n"),
+            Ok(ZoneState {
+                serial: 1,
+                ..Default::default()
+            })
+        );
+        for text in [
+            "last_success = 1\n",
+            "serial = abc\n",
+            "serial\n",
+            "serial = 1\nmaster = one\n",
+        ]
+        .iter()
+        {
+            assert!(ZoneState::from_toml(text).is_err(), "{}", text);
+        }
+    }
+
+    #[test]
+    fn test_zone_state_file() {
+        let path = std::env::temp_dir().join(format!("otter_zone_{}.state", std::process::id()));
+        let path = path.to_str().unwrap();
+        assert_eq!(ZoneState::load(path), None);
+        let state = ZoneState {
+            serial: 7,
+            last_failure: Some(1_600_000_100),
+            ..Default::default()
+        };
+        state.save(path).unwrap();
+        assert_eq!(ZoneState::load(path), Some(state));
+        std::fs::write(path, "serial = broken\n").unwrap();
+        assert_eq!(ZoneState::load(path), None);
+        std::fs::remove_file(path).unwrap();
+    }
+}
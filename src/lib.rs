@@ -0,0 +1,5 @@
+//! otterdns re-export the member crates, so downstream users get the dns protocol, the
+//! zone storage and the server from one dependency.
+pub use dnsproto;
+pub use server;
+pub use storage;
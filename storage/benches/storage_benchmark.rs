@@ -1,11 +1,42 @@
 extern crate storage;
-use criterion::{criterion_group, criterion_main, Criterion};
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
 use dnsproto::dnsname::DNSName;
 use dnsproto::meta::{DNSClass, DNSType, ResourceRecord};
-use dnsproto::qtype::DnsTypeA;
-use storage::safe_rbtree::SafeRBTree;
+use dnsproto::qtype::{DnsTypeA, DnsTypeSOA};
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::net::Ipv4Addr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use storage::lookup::LookupResult;
+use storage::storage::SafeRBTreeStorage;
 use storage::unsafe_rbtree::UnSafeRBTreeStorage;
 
+// names of the synthetic zone used by lookup benchmark
+const SYNTHETIC_ZONE_SIZE: u32 = 1_000_000;
+// lookups against the example zone counted by the allocator
+const EXAMPLE_ZONE_LOOKUPS: usize = 100_000;
+// random names not in the example zone, like a random subdomain attack
+const RANDOM_NAMES: usize = 1_000_000;
+// distinct names repeated by the cached nxdomain benchmark
+const REPEATED_NAMES: usize = 1000;
+
+// CountingAlloc count the allocations so the lookup benchmark can report them
+struct CountingAlloc;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAlloc = CountingAlloc;
+
 fn unsafe_rbtree_search(c: &mut Criterion) {
     let mut zone = UnSafeRBTreeStorage::default();
     let rr = ResourceRecord::new(
@@ -27,7 +58,7 @@ fn unsafe_rbtree_search(c: &mut Criterion) {
 }
 
 fn safe_rbtree_search(c: &mut Criterion) {
-    let mut zone = SafeRBTree::default();
+    let mut zone = SafeRBTreeStorage::default();
     let rr = ResourceRecord::new(
         "baidu.com.",
         DNSType::A,
@@ -39,12 +70,167 @@ fn safe_rbtree_search(c: &mut Criterion) {
     let _ = zone.insert_rr(rr);
     let name = DNSName::new("baidu.com.", None).unwrap();
     c.bench_function("safe_rbtree_search", |b| {
-        b.iter(|| match zone.search_rrset(&name, DNSType::A) {
+        b.iter(|| match zone.search_rrset(&name, &DNSType::A) {
             Ok(_) => {}
             Err(err) => panic!(err),
         })
     });
 }
 
-criterion_group!(storage_benches, unsafe_rbtree_search, safe_rbtree_search);
-criterion_main!(storage_benches);
+fn synthetic_zone(size: u32) -> SafeRBTreeStorage {
+    let mut zone = SafeRBTreeStorage::default();
+    let soa = DnsTypeSOA::new(
+        "ns.example.com.",
+        "admin.example.com.",
+        1,
+        7200,
+        3600,
+        1209600,
+        3600,
+    )
+    .unwrap();
+    let rr = ResourceRecord::new(
+        "example.com.",
+        DNSType::SOA,
+        DNSClass::IN,
+        3600,
+        Some(Box::new(soa)),
+    )
+    .unwrap();
+    zone.insert_rr(rr).unwrap();
+    for index in 0..size {
+        let address = Ipv4Addr::from(0x0a00_0000 + index).to_string();
+        let rr = ResourceRecord::new(
+            &format!("host{}.example.com.", index),
+            DNSType::A,
+            DNSClass::IN,
+            3600,
+            Some(Box::new(DnsTypeA::new(&address).unwrap())),
+        )
+        .unwrap();
+        zone.insert_rr(rr).unwrap();
+    }
+    zone
+}
+
+fn synthetic_zone_lookup(c: &mut Criterion) {
+    let mut zone = synthetic_zone(SYNTHETIC_ZONE_SIZE);
+    let exist = DNSName::new(
+        &format!("host{}.example.com.", SYNTHETIC_ZONE_SIZE / 2),
+        None,
+    )
+    .unwrap();
+    let not_exist = DNSName::new("www.host1.example.com.", None).unwrap();
+    let mut group = c.benchmark_group("synthetic_zone_lookup");
+    group.bench_function("search_rrset", |b| {
+        b.iter(|| zone.search_rrset(black_box(&exist), &DNSType::A).is_ok())
+    });
+    group.bench_function("search_rrset_not_exist", |b| {
+        b.iter(|| {
+            zone.search_rrset(black_box(&not_exist), &DNSType::A)
+                .is_err()
+        })
+    });
+    group.bench_function("find_best", |b| {
+        b.iter(|| zone.find_best(black_box(&not_exist)).is_some())
+    });
+    group.finish();
+}
+
+fn example_zone_lookup(c: &mut Criterion) {
+    let zone = SafeRBTreeStorage::new_zone_from_file("./test/example.zone", None).unwrap();
+    let exist: Vec<DNSName> = [
+        "example.com.",
+        "www.example.com.",
+        "mail3.example.com.",
+        "ns.example.com.",
+    ]
+    .iter()
+    .map(|name| DNSName::new(name, None).unwrap())
+    .collect();
+    let not_exist = DNSName::new("www.not-exist.example.com.", None).unwrap();
+    let lookups = || {
+        for index in 0..EXAMPLE_ZONE_LOOKUPS {
+            let name = &exist[index % exist.len()];
+            assert!(zone.find(black_box(name)).is_ok());
+            assert!(zone.find_best(black_box(&not_exist)).is_some());
+        }
+    };
+    let before = ALLOCATIONS.load(Ordering::Relaxed);
+    lookups();
+    println!(
+        "example zone: {} lookups with {} allocations",
+        EXAMPLE_ZONE_LOOKUPS,
+        ALLOCATIONS.load(Ordering::Relaxed) - before
+    );
+    c.bench_function("example_zone_lookup", |b| b.iter(lookups));
+}
+
+// random_names build names like x3f9a0c1d.example.com. with a xorshift generator, so
+// each run looks up the same names.
+fn random_names(count: usize) -> Vec<DNSName> {
+    let mut state: u64 = 0x9e37_79b9_7f4a_7c15;
+    (0..count)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            DNSName::new(&format!("x{:x}.example.com.", state), None).unwrap()
+        })
+        .collect()
+}
+
+fn nxdomain_lookup(c: &mut Criterion) {
+    let zone = SafeRBTreeStorage::new_zone_from_file("./test/example.zone", None).unwrap();
+    let names = random_names(RANDOM_NAMES);
+    // the tree walk of each name before and after the negative cache
+    for (label, cached) in [("uncached", false), ("cached", true)].iter() {
+        let before = ALLOCATIONS.load(Ordering::Relaxed);
+        let start = std::time::Instant::now();
+        for name in names.iter() {
+            let result = match cached {
+                true => zone.lookup(black_box(name), DNSType::A),
+                false => zone.lookup_uncached(black_box(name), DNSType::A),
+            };
+            assert!(matches!(result, LookupResult::NxDomain { .. }));
+        }
+        println!(
+            "nxdomain {}: {} random names in {:?} with {} allocations",
+            label,
+            RANDOM_NAMES,
+            start.elapsed(),
+            ALLOCATIONS.load(Ordering::Relaxed) - before
+        );
+    }
+    let repeated = &names[..REPEATED_NAMES];
+    let mut group = c.benchmark_group("nxdomain_lookup");
+    group.bench_function("lookup_uncached", |b| {
+        b.iter(|| {
+            for name in repeated.iter() {
+                black_box(zone.lookup_uncached(name, DNSType::A));
+            }
+        })
+    });
+    group.bench_function("lookup", |b| {
+        b.iter(|| {
+            for name in repeated.iter() {
+                black_box(zone.lookup(name, DNSType::A));
+            }
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(
+    storage_benches,
+    unsafe_rbtree_search,
+    safe_rbtree_search,
+    example_zone_lookup,
+    nxdomain_lookup
+);
+criterion_group! {
+    name = synthetic_benches;
+    config = Criterion::default().sample_size(20);
+    targets = synthetic_zone_lookup
+}
+criterion_main!(storage_benches, synthetic_benches);
@@ -0,0 +1,292 @@
+use crate::safe_rbtree::SafeRBTreeNode;
+use crate::storage::{LoadOption, SafeRBTreeStorage, ZoneOption};
+use dnsproto::dnsname::DNSName;
+use dnsproto::meta::{DNSClass, DNSType, ResourceRecord};
+use dnsproto::qtype::{DNSWireFrame, DnsTypeNS, DnsTypeSOA};
+use otterlib::errors::OtterError;
+use std::sync::{Arc, RwLock};
+
+/// ZoneBuilder construct a zone in code instead of a zone file. names are relative to the
+/// apex unless they end with a dot, and `@` is the apex itself. the records are checked
+/// like a zone file when the zone is built: exactly one soa at the apex, no cname at the
+/// apex and all names at or below the apex.
+///
+/// ```
+/// use dnsproto::dnsname::DNSName;
+/// use dnsproto::meta::DNSType;
+/// use dnsproto::qtype::{DnsTypeA, DnsTypeSOA};
+/// use storage::builder::ZoneBuilder;
+/// use storage::lookup::LookupResult;
+///
+/// let soa = DnsTypeSOA::new(
+///     "ns1.example.com.",
+///     "hostmaster.example.com.",
+///     2021010101,
+///     7200,
+///     3600,
+///     1209600,
+///     3600,
+/// )
+/// .unwrap();
+/// let storage = ZoneBuilder::new("example.com.")
+///     .soa(3600, soa)
+///     .add("www", 300, Box::new(DnsTypeA::new("192.0.2.1").unwrap()))
+///     .build()
+///     .unwrap();
+/// let www = DNSName::new("www.example.com.", None).unwrap();
+/// match storage.lookup(&www, DNSType::A) {
+///     LookupResult::Answer(rrset) => assert_eq!(rrset.read().unwrap().content().len(), 1),
+///     other => panic!("unexpected {:?}", other),
+/// }
+/// let ftp = DNSName::new("ftp.example.com.", None).unwrap();
+/// assert!(matches!(
+///     storage.lookup(&ftp, DNSType::A),
+///     LookupResult::NxDomain { .. }
+/// ));
+/// ```
+#[derive(Debug)]
+pub struct ZoneBuilder {
+    apex: String,
+    records: Vec<ResourceRecord>,
+    option: ZoneOption,
+    // the first error of the chained calls, returned by build
+    error: Option<OtterError>,
+}
+
+impl ZoneBuilder {
+    pub fn new(apex: &str) -> ZoneBuilder {
+        let (apex, error) = match DNSName::new(apex, None) {
+            Ok(apex) => (apex.to_string(), None),
+            Err(err) => (apex.to_owned(), Some(err.into())),
+        };
+        ZoneBuilder {
+            apex,
+            records: vec![],
+            option: ZoneOption::default(),
+            error,
+        }
+    }
+
+    /// option set the zone options applied to each record like a zone file.
+    pub fn option(mut self, option: ZoneOption) -> ZoneBuilder {
+        self.option = option;
+        self
+    }
+
+    /// soa add the soa record of the apex.
+    pub fn soa(self, ttl: u32, soa: DnsTypeSOA) -> ZoneBuilder {
+        self.add("@", ttl, Box::new(soa))
+    }
+
+    /// ns add a name server of the apex, the target is relative to the apex unless it ends
+    /// with a dot.
+    pub fn ns(mut self, ttl: u32, target: &str) -> ZoneBuilder {
+        let ns = self
+            .absolute_name(target)
+            .and_then(|target| Ok(DnsTypeNS::new(&target.to_string())?));
+        match ns {
+            Ok(ns) => self.add("@", ttl, Box::new(ns)),
+            Err(err) => {
+                self.error.get_or_insert(err);
+                self
+            }
+        }
+    }
+
+    /// add a record of the name with the rdata, the type is the type of the rdata.
+    pub fn add(mut self, name: &str, ttl: u32, rdata: Box<dyn DNSWireFrame>) -> ZoneBuilder {
+        let record = self.absolute_name(name).and_then(|name| {
+            Ok(ResourceRecord::new(
+                &name.to_string(),
+                rdata.get_type(),
+                DNSClass::IN,
+                ttl,
+                Some(rdata),
+            )?)
+        });
+        match record {
+            Ok(record) => self.records.push(record),
+            Err(err) => {
+                self.error.get_or_insert(err);
+            }
+        }
+        self
+    }
+
+    /// build the storage which only has the zone.
+    pub fn build(self) -> Result<SafeRBTreeStorage, OtterError> {
+        let mut storage = SafeRBTreeStorage::default();
+        self.load(&mut storage)?;
+        Ok(storage)
+    }
+
+    /// merge_into add the zone to the storage, a zone served by the storage with the same
+    /// apex is replaced as a whole. the storage is not changed when the zone is not valid.
+    pub fn merge_into(
+        self,
+        storage: &mut SafeRBTreeStorage,
+    ) -> Result<Arc<RwLock<SafeRBTreeNode>>, OtterError> {
+        let apex = DNSName::new(&self.apex, None)?;
+        let mut built = self.build()?;
+        storage.swap_zone(&mut built, &apex)
+    }
+
+    fn load(self, storage: &mut SafeRBTreeStorage) -> Result<(), OtterError> {
+        if let Some(err) = self.error {
+            return Err(err);
+        }
+        let mut records = self.records;
+        // the soa is loaded first whatever the order it is added
+        records.sort_by_key(|rr| rr.get_type() != DNSType::SOA);
+        let source = format!("<builder {}>", self.apex);
+        storage.load_zone_records(
+            &source,
+            records.into_iter().map(Ok),
+            &mut LoadOption::new(self.option),
+        )?;
+        Ok(())
+    }
+
+    fn absolute_name(&self, name: &str) -> Result<DNSName, OtterError> {
+        let name = match name.trim() {
+            "@" => self.apex.as_str(),
+            name => name,
+        };
+        Ok(DNSName::new(name, Some(&self.apex))?)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use dnsproto::qtype::{DnsTypeA, DnsTypeCNAME};
+    use otterlib::errors::StorageError;
+
+    fn soa() -> DnsTypeSOA {
+        DnsTypeSOA::new(
+            "ns1.example.com.",
+            "hostmaster.example.com.",
+            1,
+            7200,
+            3600,
+            1209600,
+            300,
+        )
+        .unwrap()
+    }
+
+    fn a(ip: &str) -> Box<dyn DNSWireFrame> {
+        Box::new(DnsTypeA::new(ip).unwrap())
+    }
+
+    #[test]
+    fn test_zone_builder() {
+        let storage = ZoneBuilder::new("example.com.")
+            .ns(3600, "ns1")
+            .add("ns1", 3600, a("192.0.2.53"))
+            .add("www.example.com.", 300, a("192.0.2.80"))
+            // the soa is moved before the other records
+            .soa(3600, soa())
+            .build()
+            .unwrap();
+        let apex = DNSName::new("example.com.", None).unwrap();
+        assert_eq!(storage.zones(), vec![apex.clone()]);
+        assert_eq!(storage.get_zone_soa(&apex).unwrap().serial(), 1);
+        let ns = storage
+            .find(&apex)
+            .unwrap()
+            .read()
+            .unwrap()
+            .find_rrset(DNSType::NS)
+            .unwrap();
+        assert_eq!(
+            ns.read().unwrap().content()[0]
+                .get_data()
+                .as_ref()
+                .unwrap()
+                .to_string(),
+            "ns1.example.com."
+        );
+        let www = DNSName::new("www.example.com.", None).unwrap();
+        assert!(storage.find(&www).is_ok());
+    }
+
+    #[test]
+    fn test_zone_builder_validation() {
+        let error = |builder: ZoneBuilder| match builder.build() {
+            Err(OtterError::StorageError(err)) => err,
+            other => panic!("unexpected {:?}", other.map(|_| ())),
+        };
+        assert_eq!(
+            error(ZoneBuilder::new("example.com.").add("www", 300, a("192.0.2.80"))),
+            StorageError::NotStartWithSOARecord
+        );
+        assert_eq!(
+            error(ZoneBuilder::new("example.com.")),
+            StorageError::SOAResourceError
+        );
+        assert_eq!(
+            error(
+                ZoneBuilder::new("example.com.")
+                    .soa(3600, soa())
+                    .soa(3600, soa())
+            ),
+            StorageError::TooManySOARecords
+        );
+        assert_eq!(
+            error(ZoneBuilder::new("example.com.").soa(3600, soa()).add(
+                "www.example.net.",
+                300,
+                a("192.0.2.80")
+            )),
+            StorageError::ZoneOutOfArea("www.example.net.".to_string(), "example.com.".to_string())
+        );
+        let cname = Box::new(DnsTypeCNAME::new("www.example.net.").unwrap());
+        assert_eq!(
+            error(
+                ZoneBuilder::new("example.com.")
+                    .soa(3600, soa())
+                    .add("@", 300, cname)
+            ),
+            StorageError::ApexCNAMEError("example.com.".to_string())
+        );
+        // the first invalid name is returned by build
+        let long_label = "a".repeat(64);
+        assert!(matches!(
+            ZoneBuilder::new("example.com.")
+                .soa(3600, soa())
+                .add(&long_label, 300, a("192.0.2.80"))
+                .build(),
+            Err(OtterError::DNSProtoError(_))
+        ));
+    }
+
+    #[test]
+    fn test_zone_builder_merge_into() {
+        let mut storage =
+            SafeRBTreeStorage::new_zone_from_file("./test/example.zone", None).unwrap();
+        let org = DNSName::new("example.org.", None).unwrap();
+        ZoneBuilder::new("example.org.")
+            .soa(3600, soa())
+            .merge_into(&mut storage)
+            .unwrap();
+        assert_eq!(storage.zones().len(), 2);
+        assert_eq!(storage.find_zone(&org), Some(org));
+
+        // the zone with the same apex is replaced, an invalid zone change nothing
+        let apex = DNSName::new("example.com.", None).unwrap();
+        let mail = DNSName::new("mail.example.com.", None).unwrap();
+        assert!(ZoneBuilder::new("example.com.")
+            .add("www", 300, a("192.0.2.80"))
+            .merge_into(&mut storage)
+            .is_err());
+        assert!(storage.find(&mail).is_ok());
+        ZoneBuilder::new("example.com.")
+            .soa(3600, soa())
+            .add("www", 300, a("192.0.2.80"))
+            .merge_into(&mut storage)
+            .unwrap();
+        assert_eq!(storage.get_zone_soa(&apex).unwrap().serial(), 1);
+        assert!(storage.find(&mail).is_err());
+    }
+}
@@ -0,0 +1,247 @@
+use crate::storage::SafeRBTreeStorage;
+use dnsproto::dnsname::DNSName;
+use dnsproto::meta::{DNSType, ResourceRecord};
+use std::cmp::Ordering;
+
+/// DiffKind tells how a rrset changed between two storages.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DiffKind {
+    Added,
+    Removed,
+    Changed,
+}
+
+/// RRSetDiff is the change of a rrset, old_rrs are the records only in the old storage
+/// and new_rrs the records only in the new storage, the unchanged records are left out.
+#[derive(Debug, Clone)]
+pub struct RRSetDiff {
+    pub owner: DNSName,
+    pub dtype: DNSType,
+    pub kind: DiffKind,
+    pub old_rrs: Vec<ResourceRecord>,
+    pub new_rrs: Vec<ResourceRecord>,
+}
+
+/// ZoneDiff holds the changed rrsets of two storages in canonical order.
+#[derive(Debug, Clone, Default)]
+pub struct ZoneDiff {
+    pub entries: Vec<RRSetDiff>,
+}
+
+impl ZoneDiff {
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// deletions return the records removed from the old storage, SOA records go first
+    /// as the deletion part of an ixfr difference sequence.
+    pub fn deletions(&self) -> Vec<&ResourceRecord> {
+        soa_first(self.entries.iter().flat_map(|entry| entry.old_rrs.iter()))
+    }
+
+    /// additions return the records added by the new storage, SOA records go first
+    /// as the addition part of an ixfr difference sequence.
+    pub fn additions(&self) -> Vec<&ResourceRecord> {
+        soa_first(self.entries.iter().flat_map(|entry| entry.new_rrs.iter()))
+    }
+}
+
+fn soa_first<'a>(records: impl Iterator<Item = &'a ResourceRecord>) -> Vec<&'a ResourceRecord> {
+    let (mut soa, others): (Vec<&ResourceRecord>, Vec<&ResourceRecord>) =
+        records.partition(|rr| rr.get_type() == DNSType::SOA);
+    soa.extend(others);
+    soa
+}
+
+// record_key return the canonical wire format of the record which includes the ttl, so
+// a ttl change is a deletion and an addition like ixfr does.
+fn record_key(rr: &ResourceRecord) -> Vec<u8> {
+    rr.encode_canonical()
+        .unwrap_or_else(|_| rr.to_string().into_bytes())
+}
+
+// sorted_rrsets return all rrsets of the storage in canonical order, signatures are
+// grouped as the RRSIG rrset of the owner.
+fn sorted_rrsets(storage: &SafeRBTreeStorage) -> Vec<(DNSName, DNSType, Vec<ResourceRecord>)> {
+    let mut records: Vec<(DNSName, DNSType, ResourceRecord)> = vec![];
+    for apex in storage.zones() {
+        for node in storage.zone_nodes(&apex) {
+            let node = node.read().unwrap();
+            let name = node.get_name();
+            for rrset in node.rr_sets.iter() {
+                let rrset = rrset.value().read().unwrap();
+                for rr in rrset.content().iter().chain(rrset.signatures().iter()) {
+                    records.push((name.clone(), rr.get_type(), rr.clone()));
+                }
+            }
+        }
+    }
+    records.sort_by(|left, right| {
+        left.0
+            .cmp(&right.0)
+            .then_with(|| u16::from(left.1).cmp(&u16::from(right.1)))
+    });
+    let mut rrsets: Vec<(DNSName, DNSType, Vec<ResourceRecord>)> = vec![];
+    for (name, dtype, rr) in records {
+        match rrsets.last_mut() {
+            Some(last) if last.0 == name && last.1 == dtype => last.2.push(rr),
+            _ => rrsets.push((name, dtype, vec![rr])),
+        }
+    }
+    rrsets
+}
+
+impl SafeRBTreeStorage {
+    /// diff walk the rrsets of both storages in canonical order and return the changes
+    /// from self to other.
+    pub fn diff(&self, other: &SafeRBTreeStorage) -> ZoneDiff {
+        let mut old = sorted_rrsets(self).into_iter().peekable();
+        let mut new = sorted_rrsets(other).into_iter().peekable();
+        let mut entries = vec![];
+        loop {
+            let order = match (old.peek(), new.peek()) {
+                (None, None) => break,
+                (Some(_), None) => Ordering::Less,
+                (None, Some(_)) => Ordering::Greater,
+                (Some(left), Some(right)) => left
+                    .0
+                    .cmp(&right.0)
+                    .then_with(|| u16::from(left.1).cmp(&u16::from(right.1))),
+            };
+            let (owner, dtype, old_rrs, new_rrs) = match order {
+                Ordering::Less => {
+                    let (owner, dtype, rrs) = old.next().unwrap();
+                    (owner, dtype, rrs, vec![])
+                }
+                Ordering::Greater => {
+                    let (owner, dtype, rrs) = new.next().unwrap();
+                    (owner, dtype, vec![], rrs)
+                }
+                Ordering::Equal => {
+                    let (owner, dtype, old_rrs) = old.next().unwrap();
+                    let (_, _, new_rrs) = new.next().unwrap();
+                    let old_keys: Vec<Vec<u8>> = old_rrs.iter().map(record_key).collect();
+                    let new_keys: Vec<Vec<u8>> = new_rrs.iter().map(record_key).collect();
+                    let removed: Vec<ResourceRecord> = old_rrs
+                        .into_iter()
+                        .zip(old_keys.iter())
+                        .filter(|(_, key)| !new_keys.contains(key))
+                        .map(|(rr, _)| rr)
+                        .collect();
+                    let added: Vec<ResourceRecord> = new_rrs
+                        .into_iter()
+                        .zip(new_keys.iter())
+                        .filter(|(_, key)| !old_keys.contains(key))
+                        .map(|(rr, _)| rr)
+                        .collect();
+                    if removed.is_empty() && added.is_empty() {
+                        continue;
+                    }
+                    entries.push(RRSetDiff {
+                        owner,
+                        dtype,
+                        kind: DiffKind::Changed,
+                        old_rrs: removed,
+                        new_rrs: added,
+                    });
+                    continue;
+                }
+            };
+            let kind = match old_rrs.is_empty() {
+                true => DiffKind::Added,
+                false => DiffKind::Removed,
+            };
+            entries.push(RRSetDiff {
+                owner,
+                dtype,
+                kind,
+                old_rrs,
+                new_rrs,
+            });
+        }
+        ZoneDiff { entries }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_storage_diff() {
+        let old = SafeRBTreeStorage::new_zone_from_file("./test/diff-old.zone", None).unwrap();
+        let new = SafeRBTreeStorage::new_zone_from_file("./test/diff-new.zone", None).unwrap();
+        assert!(old.diff(&old).is_empty());
+
+        let diff = old.diff(&new);
+        let entries: Vec<(String, DNSType, DiffKind, usize, usize)> = diff
+            .entries
+            .iter()
+            .map(|entry| {
+                (
+                    entry.owner.to_string(),
+                    entry.dtype,
+                    entry.kind,
+                    entry.old_rrs.len(),
+                    entry.new_rrs.len(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            entries,
+            vec![
+                (
+                    "diff.example.".to_owned(),
+                    DNSType::SOA,
+                    DiffKind::Changed,
+                    1,
+                    1
+                ),
+                (
+                    "mail.diff.example.".to_owned(),
+                    DNSType::A,
+                    DiffKind::Changed,
+                    1,
+                    1
+                ),
+                (
+                    "new.diff.example.".to_owned(),
+                    DNSType::TXT,
+                    DiffKind::Added,
+                    0,
+                    1
+                ),
+                (
+                    "old.diff.example.".to_owned(),
+                    DNSType::TXT,
+                    DiffKind::Removed,
+                    1,
+                    0
+                ),
+                (
+                    "www.diff.example.".to_owned(),
+                    DNSType::A,
+                    DiffKind::Changed,
+                    1,
+                    1
+                ),
+            ]
+        );
+        let deletions = diff.deletions();
+        assert_eq!(deletions.len(), 4);
+        assert_eq!(deletions[0].get_type(), DNSType::SOA);
+        assert_eq!(
+            deletions[3].get_data().as_ref().unwrap().to_string(),
+            "192.0.2.11"
+        );
+        let additions = diff.additions();
+        assert_eq!(additions.len(), 4);
+        assert_eq!(additions[0].get_type(), DNSType::SOA);
+        assert_eq!(additions[1].get_ttl(), 300);
+
+        // the reverse diff swap the deletions and additions
+        let reverse = new.diff(&old);
+        assert_eq!(reverse.deletions().len(), 4);
+        assert_eq!(reverse.entries[2].kind, DiffKind::Removed);
+    }
+}
@@ -0,0 +1,307 @@
+use crate::serial::serial_gt;
+use crate::storage::{SafeRBTreeStorage, WILDCARD_LABEL};
+use dnsproto::dnsname::DNSName;
+use dnsproto::meta::{DNSType, RRSet, ResourceRecord};
+use dnsproto::qtype::{DnsTypeDNSKEY, DnsTypeRRSIG};
+use otterlib::errors::{DNSProtoErr, StorageError};
+use ring::signature;
+
+const ALGORITHM_RSASHA256: u8 = 8;
+const ALGORITHM_ECDSAP256SHA256: u8 = 13;
+
+fn verify_error(apex: &DNSName, reason: &str) -> StorageError {
+    StorageError::DnssecVerifyError(apex.to_string(), reason.to_owned())
+}
+
+// signed_data build the data covered by the rrsig(rfc4034 section 3.1.8.1): the rrsig rdata
+// without the signature, then the records of the set in canonical form and order with the
+// original ttl. the owner of a wildcard is restored from the labels field of the rrsig.
+fn signed_data(rrsig: &DnsTypeRRSIG, rrset: &RRSet) -> Result<Vec<u8>, DNSProtoErr> {
+    let first = rrset
+        .content()
+        .first()
+        .ok_or(DNSProtoErr::PacketSerializeError)?;
+    let owner = first.get_dname();
+    let labels = rrsig.labels() as usize;
+    let owner = match labels < owner.label_count() {
+        true => {
+            let mut wildcard = DNSName {
+                labels: owner.labels[owner.label_count() - labels..].to_vec(),
+            };
+            wildcard.labels.insert(0, WILDCARD_LABEL.clone());
+            wildcard.to_canonical_binary()
+        }
+        false => owner.to_canonical_binary(),
+    };
+    let mut rdatas = vec![];
+    for rr in rrset.content().iter() {
+        rdatas.push(rr.canonical_rdata()?);
+    }
+    rdatas.sort();
+    rdatas.dedup();
+    let mut data = rrsig.signed_rdata();
+    for rdata in rdatas.iter() {
+        data.extend_from_slice(owner.as_slice());
+        data.extend_from_slice(&u16::from(first.get_type()).to_be_bytes());
+        data.extend_from_slice(&u16::from(first.get_class()).to_be_bytes());
+        data.extend_from_slice(&rrsig.original_ttl().to_be_bytes());
+        data.extend_from_slice(&(rdata.len() as u16).to_be_bytes());
+        data.extend_from_slice(rdata.as_slice());
+    }
+    Ok(data)
+}
+
+// verify_signature verify the signature of the data with the public key of the dnskey, the
+// rsa key is in rfc3110 format and the ecdsa key is the x and y of the point(rfc6605).
+fn verify_signature(key: &DnsTypeDNSKEY, data: &[u8], sig: &[u8]) -> bool {
+    let public_key = key.public_key();
+    match key.algorithm() {
+        ALGORITHM_RSASHA256 => {
+            let (exponent_len, offset) = match public_key {
+                [0, high, low, ..] => (u16::from_be_bytes([*high, *low]) as usize, 3),
+                [len, ..] => (*len as usize, 1),
+                [] => return false,
+            };
+            if public_key.len() <= offset + exponent_len {
+                return false;
+            }
+            let components = signature::RsaPublicKeyComponents {
+                n: &public_key[offset + exponent_len..],
+                e: &public_key[offset..offset + exponent_len],
+            };
+            components
+                .verify(
+                    &signature::RSA_PKCS1_1024_8192_SHA256_FOR_LEGACY_USE_ONLY,
+                    data,
+                    sig,
+                )
+                .is_ok()
+        }
+        ALGORITHM_ECDSAP256SHA256 => {
+            let mut point = vec![4];
+            point.extend_from_slice(public_key);
+            signature::UnparsedPublicKey::new(&signature::ECDSA_P256_SHA256_FIXED, point)
+                .verify(data, sig)
+                .is_ok()
+        }
+        _ => false,
+    }
+}
+
+// verify_rrsig verify the rrsig record of the rrset with the zone keys: the signer must be
+// the apex, now must be in the validity window and a zone key with the key tag and the
+// algorithm of the rrsig must verify the signature. the reason is returned on failure.
+fn verify_rrsig(
+    apex: &DNSName,
+    rr: &ResourceRecord,
+    rrset: &RRSet,
+    keys: &[DnsTypeDNSKEY],
+    now: u32,
+) -> Result<(), String> {
+    let rrsig = rr
+        .get_data()
+        .as_ref()
+        .and_then(|data| data.as_any().downcast_ref::<DnsTypeRRSIG>())
+        .ok_or_else(|| "invalid rrsig record".to_owned())?;
+    let covered = DNSType::from(rrsig.type_covered());
+    let reason = |reason: &str| {
+        format!(
+            "{} {} key tag {}: {}",
+            rr.get_dname().to_string(),
+            covered,
+            rrsig.key_tag(),
+            reason
+        )
+    };
+    if rrsig.signer() != apex {
+        return Err(reason("signer is not the zone apex"));
+    }
+    if rrsig.algorithm() != ALGORITHM_RSASHA256 && rrsig.algorithm() != ALGORITHM_ECDSAP256SHA256 {
+        return Err(reason("unsupported algorithm"));
+    }
+    // the window is compared with rfc4034 serial number arithmetic
+    if serial_gt(rrsig.inception(), now) {
+        return Err(reason("signature is not yet valid"));
+    }
+    if serial_gt(now, rrsig.expiration()) {
+        return Err(reason("signature is expired"));
+    }
+    if rrsig.labels() as usize > rr.get_dname().label_count() {
+        return Err(reason("labels field is more than the owner labels"));
+    }
+    if rrset.content().is_empty() {
+        return Err(reason("no record of the covered type"));
+    }
+    let data = signed_data(rrsig, rrset).map_err(|err| reason(&err.to_string()))?;
+    let keys: Vec<&DnsTypeDNSKEY> = keys
+        .iter()
+        .filter(|key| {
+            key.is_zone_key()
+                && key.key_tag() == rrsig.key_tag()
+                && key.algorithm() == rrsig.algorithm()
+        })
+        .collect();
+    if keys.is_empty() {
+        return Err(reason("no DNSKEY with the key tag"));
+    }
+    match keys
+        .iter()
+        .any(|key| verify_signature(key, data.as_slice(), rrsig.signature()))
+    {
+        true => Ok(()),
+        false => Err(reason("signature mismatch")),
+    }
+}
+
+/// verify_dnssec verify the rrsig records of the zone with the apex DNSKEY records, a sample
+/// of them spread over the zone is verified when sample is set. return the number of
+/// verified records or the first failure.
+pub fn verify_dnssec(
+    storage: &SafeRBTreeStorage,
+    apex: &DNSName,
+    now: u32,
+    sample: Option<usize>,
+) -> Result<usize, StorageError> {
+    let rrset = storage
+        .find(apex)?
+        .read()
+        .unwrap()
+        .find_rrset(DNSType::DNSKEY)
+        .map_err(|_| verify_error(apex, "no DNSKEY record at apex"))?;
+    let keys: Vec<DnsTypeDNSKEY> = rrset
+        .read()
+        .unwrap()
+        .content()
+        .iter()
+        .filter_map(|rr| {
+            rr.get_data()
+                .as_ref()
+                .and_then(|data| data.as_any().downcast_ref::<DnsTypeDNSKEY>())
+                .cloned()
+        })
+        .collect();
+    // the signatures of a node are kept in its RRSIG set
+    let rrsig_sets: Vec<_> = storage
+        .zone_nodes(apex)
+        .into_iter()
+        .filter_map(|node| {
+            let rrset = node.read().unwrap().find_rrset(DNSType::RRSIG).ok()?;
+            Some((node, rrset))
+        })
+        .collect();
+    let total: usize = rrsig_sets
+        .iter()
+        .map(|(_, rrset)| rrset.read().unwrap().signatures().len())
+        .sum();
+    if total == 0 {
+        return Err(verify_error(apex, "no RRSIG record"));
+    }
+    let (limit, step) = match sample {
+        Some(sample) if sample > 0 && sample < total => (sample, total / sample),
+        _ => (total, 1),
+    };
+    let empty = RRSet::default();
+    let (mut index, mut verified) = (0, 0);
+    for (node, rrsigs) in rrsig_sets.iter() {
+        let node = node.read().unwrap();
+        for rr in rrsigs.read().unwrap().signatures().iter() {
+            index += 1;
+            if (index - 1) % step != 0 || verified == limit {
+                continue;
+            }
+            let rrset = rr
+                .get_data()
+                .as_ref()
+                .and_then(|data| data.as_any().downcast_ref::<DnsTypeRRSIG>())
+                .and_then(|rrsig| node.find_rrset(DNSType::from(rrsig.type_covered())).ok());
+            let rrset = rrset.as_ref().map(|rrset| rrset.read().unwrap());
+            verify_rrsig(apex, rr, rrset.as_deref().unwrap_or(&empty), &keys, now)
+                .map_err(|reason| verify_error(apex, &reason))?;
+            verified += 1;
+        }
+    }
+    Ok(verified)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::storage::ZoneOption;
+
+    // 2025-01-01, the test zone is signed from 2024-01-01 to 2050-01-01
+    const NOW: u32 = 1735689600;
+
+    fn load_zone(replace: Option<(&str, &str)>) -> SafeRBTreeStorage {
+        let mut text = std::fs::read_to_string("./test/dnssec.zone").unwrap();
+        if let Some((from, to)) = replace {
+            text = text.replace(from, to);
+        }
+        SafeRBTreeStorage::new_zone_from_str(&text, None).unwrap()
+    }
+
+    fn verify_reason(storage: &SafeRBTreeStorage, now: u32) -> String {
+        let apex = DNSName::new("dnssec.example.", None).unwrap();
+        match verify_dnssec(storage, &apex, now, None) {
+            Err(StorageError::DnssecVerifyError(zone, reason)) => {
+                assert_eq!(zone, "dnssec.example.");
+                reason
+            }
+            other => panic!("{:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_verify_dnssec() {
+        let storage = load_zone(None);
+        let apex = DNSName::new("dnssec.example.", None).unwrap();
+        // the ksk is RSASHA256 and the zsk is ECDSAP256SHA256, the wildcard and the
+        // uppercase names in rdata are verified in canonical form
+        assert_eq!(verify_dnssec(&storage, &apex, NOW, None), Ok(8));
+        assert_eq!(verify_dnssec(&storage, &apex, NOW, Some(3)), Ok(3));
+        assert_eq!(verify_dnssec(&storage, &apex, NOW, Some(100)), Ok(8));
+
+        assert!(verify_reason(&storage, 1672531200).ends_with("signature is not yet valid"));
+        assert!(verify_reason(&storage, 2556144000).ends_with("signature is expired"));
+        let storage = load_zone(Some(("www IN A 192.0.2.2", "www IN A 192.0.2.3")));
+        assert_eq!(
+            verify_reason(&storage, NOW),
+            "www.dnssec.example. A key tag 33430: signature mismatch"
+        );
+        let storage = load_zone(Some(("@ IN DNSKEY 256", "; @ IN DNSKEY 256")));
+        assert!(verify_reason(&storage, NOW).ends_with("no DNSKEY with the key tag"));
+        let storage = SafeRBTreeStorage::new_zone_from_file("./test/example.zone", None).unwrap();
+        let apex = DNSName::new("example.com.", None).unwrap();
+        assert!(verify_dnssec(&storage, &apex, NOW, None).is_err());
+    }
+
+    #[test]
+    fn test_replace_zone_verify_dnssec() {
+        let apex = DNSName::new("dnssec.example.", None).unwrap();
+        let mut records = load_zone(None).transfer_records(&apex).unwrap();
+        records.pop();
+        let mut tampered = load_zone(Some(("mail IN A 192.0.2.25", "mail IN A 192.0.2.26")))
+            .transfer_records(&apex)
+            .unwrap();
+        tampered.pop();
+        let mut option = ZoneOption {
+            verify_dnssec: true,
+            verify_dnssec_strict: true,
+            ..Default::default()
+        };
+        let mut storage = SafeRBTreeStorage::default();
+        storage.replace_zone(records, &option).unwrap();
+        assert!(storage.replace_zone(tampered.clone(), &option).is_err());
+        option.verify_dnssec_strict = false;
+        storage.replace_zone(tampered, &option).unwrap();
+        let mail = DNSName::new("mail.dnssec.example.", None).unwrap();
+        let rrset = storage.search_rrset(&mail, &DNSType::A).unwrap();
+        assert_eq!(
+            rrset.read().unwrap().content()[0]
+                .get_data()
+                .as_ref()
+                .unwrap()
+                .to_string(),
+            "192.0.2.26"
+        );
+    }
+}
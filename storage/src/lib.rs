@@ -1,9 +1,20 @@
 #![allow(unused_doc_comments)]
+#[macro_use]
+extern crate log;
+pub mod builder;
+pub mod diff;
+pub mod dnssec;
+pub mod lookup;
+pub mod negative;
+pub mod nsec;
 mod rbtree;
 pub mod unsafe_rbtree;
 #[macro_use]
 pub mod safe_rbtree;
+pub mod serial;
 pub mod storage;
+pub mod writer;
+pub mod zonemd;
 // mod example;
 
 use dnsproto::dnsname::DNSName;
@@ -0,0 +1,631 @@
+use crate::safe_rbtree::{child_node, SafeRBTreeNode};
+use crate::storage::{SafeRBTreeStorage, WILDCARD_LABEL};
+use dnsproto::dnsname::DNSName;
+use dnsproto::meta::{DNSType, RRSet, ResourceRecord};
+use dnsproto::qtype::{DnsTypeNS, DnsTypeSOA};
+use otterlib::errors::StorageError;
+use std::sync::{Arc, RwLock};
+
+/// RRSetHandle is the shared rrset stored in the zone tree.
+pub type RRSetHandle = Arc<RwLock<RRSet>>;
+
+/// LookupResult is the answer of a query name and type from the zone data, the soa is the
+/// rrset of the zone apex used in the authority section of negative answers.
+#[derive(Debug, Clone)]
+pub enum LookupResult {
+    /// the rrset of the query type, a rrset of a wildcard node is copied with the query
+    /// name as the owner
+    Answer(RRSetHandle),
+    /// all rrsets of the name for an ANY query, ordered by type
+    Any(Vec<RRSetHandle>),
+    /// the name is an alias, the query type is not CNAME
+    CName(RRSetHandle),
+    /// the name is at or below a zone cut, glue are the address rrsets of the
+    /// name servers inside the delegated zone
+    Delegation {
+        ns: RRSetHandle,
+        glue: Vec<RRSetHandle>,
+    },
+    /// the name exists(maybe an empty non-terminal) but has no such type
+    NoData {
+        soa: RRSetHandle,
+    },
+    NxDomain {
+        soa: RRSetHandle,
+    },
+    /// the name is not belong to any loaded zone
+    OutOfZone,
+}
+
+/// DataStatus tell how the rrset of a node is served.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DataStatus {
+    Authoritative,
+    /// the NS rrset of a zone cut
+    Delegation,
+    /// the address rrset at or below a zone cut
+    Glue,
+    /// the other rrsets at or below a zone cut, they are never answered
+    Occluded,
+}
+
+impl std::fmt::Display for DataStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let status = match self {
+            DataStatus::Authoritative => "authoritative",
+            DataStatus::Delegation => "delegation",
+            DataStatus::Glue => "glue",
+            DataStatus::Occluded => "occluded",
+        };
+        f.write_str(status)
+    }
+}
+
+/// NodeReport is what the zone data holds for a name, it is dumped by the control channel
+/// so the zone data can be checked without a dns query.
+#[derive(Debug, Clone)]
+pub struct NodeReport {
+    /// the zone the name is looked up in
+    pub zone: DNSName,
+    /// the name of the node, the wildcard node when the name is answered by a wildcard and
+    /// None when the name does not exist
+    pub node: Option<DNSName>,
+    pub apex: bool,
+    pub wildcard: bool,
+    /// the node is a zone cut
+    pub delegation: bool,
+    /// the closest zone cut above the node
+    pub below_cut: Option<DNSName>,
+    /// all rrsets of the node ordered by type
+    pub rrsets: Vec<(DNSType, RRSetHandle)>,
+    /// how a query of the name and type is answered
+    pub result: LookupResult,
+}
+
+impl NodeReport {
+    /// status tell how the rrset of the type at the node is served.
+    pub fn status(&self, dtype: DNSType) -> DataStatus {
+        if !self.delegation && self.below_cut.is_none() {
+            return DataStatus::Authoritative;
+        }
+        match dtype {
+            DNSType::A | DNSType::AAAA => DataStatus::Glue,
+            _ if self.below_cut.is_some() => DataStatus::Occluded,
+            DNSType::NS => DataStatus::Delegation,
+            DNSType::DS | DNSType::NSEC | DNSType::RRSIG => DataStatus::Authoritative,
+            _ => DataStatus::Occluded,
+        }
+    }
+}
+
+impl SafeRBTreeStorage {
+    /// lookup tell how the query should be answered, recent NxDomain and NoData results
+    /// are answered from the negative cache without walking the tree.
+    pub fn lookup(&self, qname: &DNSName, qtype: DNSType) -> LookupResult {
+        // the lookup can't fail, an injected error panics like a poisoned rrset lock
+        #[cfg(feature = "testing")]
+        if let Err(err) = self.inject_fault() {
+            panic!("lookup {} fail: {}", qname.to_string(), err);
+        }
+        let cache = self.negative_cache();
+        if let Some(result) = cache.get(qname, qtype) {
+            return result;
+        }
+        let generation = cache.generation();
+        let result = self.lookup_uncached(qname, qtype);
+        cache.insert(qname, qtype, &result, generation);
+        result
+    }
+
+    /// lookup_uncached walk down from the closest zone apex of the query name, stop at the
+    /// first zone cut or the wildcard node when the name is not found, and tell how the
+    /// query should be answered. DS queries are answered by the parent side of the zone cut.
+    pub fn lookup_uncached(&self, qname: &DNSName, qtype: DNSType) -> LookupResult {
+        match self.find_zone(qname) {
+            Some(apex) => self.lookup_in_zone(&apex, qname, qtype),
+            None => LookupResult::OutOfZone,
+        }
+    }
+
+    // lookup_in_zone answer the query from the zone, the name must be at or below the apex.
+    fn lookup_in_zone(&self, apex: &DNSName, qname: &DNSName, qtype: DNSType) -> LookupResult {
+        let mut current = match self.find_or_none(apex) {
+            Some(node) => node,
+            None => return LookupResult::OutOfZone,
+        };
+        let soa = match current.read().unwrap().find_rrset(DNSType::SOA) {
+            Ok(soa) => soa,
+            Err(_) => return LookupResult::OutOfZone,
+        };
+        // the apex is never a zone cut of its own zone, its NS rrset is answered as is
+        if qname == apex {
+            return node_answer(&current, qtype, soa);
+        }
+        let below = qname.label_count() - apex.label_count();
+        for (depth, label) in qname.labels[..below].iter().rev().enumerate() {
+            let next = child_node(&current, label);
+            let next = match next {
+                Some(node) => node,
+                None => match child_node(&current, &WILDCARD_LABEL) {
+                    Some(wildcard) => {
+                        return expand_wildcard(node_answer(&wildcard, qtype, soa), qname);
+                    }
+                    None => return LookupResult::NxDomain { soa },
+                },
+            };
+            let at_qname = depth + 1 == below;
+            if !(at_qname && qtype == DNSType::DS) {
+                if let Ok(ns) = next.read().unwrap().find_rrset(DNSType::NS) {
+                    let glue = self.glue_rrsets(&next.read().unwrap().get_name(), &ns);
+                    return LookupResult::Delegation { ns, glue };
+                }
+            }
+            current = next;
+        }
+        node_answer(&current, qtype, soa)
+    }
+
+    /// inspect tell what the zone data holds for the name and how a query of the type is
+    /// answered, the negative cache is not used. the name is looked up in the zone if set,
+    /// a parent zone tells the name is delegated, or the closest zone of the name.
+    pub fn inspect(
+        &self,
+        name: &DNSName,
+        qtype: DNSType,
+        zone: Option<&DNSName>,
+    ) -> Result<NodeReport, StorageError> {
+        let apex = match zone {
+            Some(zone) if !self.zones().contains(zone) => return Err(StorageError::RefusedError),
+            Some(zone) if !name.is_part_of(zone) => {
+                return Err(StorageError::ZoneOutOfArea(
+                    name.to_string(),
+                    zone.to_string(),
+                ))
+            }
+            Some(zone) => zone.clone(),
+            None => self.find_zone(name).ok_or(StorageError::RefusedError)?,
+        };
+        let mut current = self.find_or_none(&apex).ok_or(StorageError::RefusedError)?;
+        let mut below_cut = None;
+        let mut wildcard = false;
+        let mut found = true;
+        let below = name.label_count() - apex.label_count();
+        for (depth, label) in name.labels[..below].iter().rev().enumerate() {
+            if depth > 0
+                && below_cut.is_none()
+                && current.read().unwrap().find_rrset(DNSType::NS).is_ok()
+            {
+                below_cut = Some(current.read().unwrap().get_name());
+            }
+            current = match child_node(&current, label) {
+                Some(next) => next,
+                None => match child_node(&current, &WILDCARD_LABEL) {
+                    Some(next) => {
+                        wildcard = true;
+                        next
+                    }
+                    None => {
+                        found = false;
+                        break;
+                    }
+                },
+            };
+            if wildcard {
+                break;
+            }
+        }
+        let (node, mut rrsets) = if found {
+            let current = current.read().unwrap();
+            let rrsets: Vec<(DNSType, RRSetHandle)> = current
+                .rr_sets
+                .iter()
+                .map(|rrset| (*rrset.key(), rrset.value().clone()))
+                .collect();
+            (Some(current.get_name()), rrsets)
+        } else {
+            (None, vec![])
+        };
+        rrsets.sort_by_key(|(dtype, _)| u16::from(*dtype));
+        let is_apex = node.as_ref() == Some(&apex);
+        let wildcard = wildcard || (found && name.labels.first() == Some(&*WILDCARD_LABEL));
+        let delegation = !is_apex && rrsets.iter().any(|(dtype, _)| *dtype == DNSType::NS);
+        Ok(NodeReport {
+            result: self.lookup_in_zone(&apex, name, qtype),
+            zone: apex,
+            node,
+            apex: is_apex,
+            wildcard,
+            delegation,
+            below_cut,
+            rrsets,
+        })
+    }
+
+    // glue_rrsets return the A and AAAA rrsets of the name servers at or below the zone cut.
+    fn glue_rrsets(&self, cut: &DNSName, ns: &RRSetHandle) -> Vec<RRSetHandle> {
+        let mut glue = vec![];
+        for ns in ns.read().unwrap().iter_as::<DnsTypeNS>() {
+            let target = ns.name();
+            if !target.is_part_of(cut) {
+                continue;
+            }
+            if let Some(node) = self.find_or_none(target) {
+                let node = node.read().unwrap();
+                for dtype in [DNSType::A, DNSType::AAAA].iter() {
+                    if let Ok(rrset) = node.find_rrset(*dtype) {
+                        glue.push(rrset);
+                    }
+                }
+            }
+        }
+        glue
+    }
+}
+
+/// negative_soa return the soa record for the authority section of a negative answer,
+/// the ttl is the smaller one of the soa ttl and the soa minimum(rfc2308 section 3).
+pub fn negative_soa(soa: &RRSet) -> Option<ResourceRecord> {
+    let mut rr = soa.content().first()?.clone();
+    let minimum = rr.rdata_as::<DnsTypeSOA>()?.minimum();
+    rr.set_ttl(rr.get_ttl().min(minimum));
+    Some(rr)
+}
+
+/// node_answer answer the query type from the rrsets of the node found by lookup.
+fn node_answer(
+    node: &Arc<RwLock<SafeRBTreeNode>>,
+    qtype: DNSType,
+    soa: RRSetHandle,
+) -> LookupResult {
+    let node = node.read().unwrap();
+    if qtype == DNSType::Any {
+        // signatures are answered with the rrsets they cover
+        let mut rrsets: Vec<(u16, RRSetHandle)> = node
+            .rr_sets
+            .iter()
+            .filter(|rrset| *rrset.key() != DNSType::RRSIG)
+            .map(|rrset| (u16::from(*rrset.key()), rrset.value().clone()))
+            .collect();
+        if rrsets.is_empty() {
+            return LookupResult::NoData { soa };
+        }
+        rrsets.sort_by_key(|(code, _)| *code);
+        return LookupResult::Any(rrsets.into_iter().map(|(_, rrset)| rrset).collect());
+    }
+    if let Ok(rrset) = node.find_rrset(qtype) {
+        return LookupResult::Answer(rrset);
+    }
+    if qtype != DNSType::CNAME {
+        if let Ok(cname) = node.find_rrset(DNSType::CNAME) {
+            return LookupResult::CName(cname);
+        }
+    }
+    LookupResult::NoData { soa }
+}
+
+/// expand_wildcard rewrite the owner of the records answered by a wildcard node to the
+/// query name, the records stored in the zone are not changed.
+fn expand_wildcard(result: LookupResult, qname: &DNSName) -> LookupResult {
+    let expand = |rrset: &RRSetHandle| -> RRSetHandle {
+        Arc::new(RwLock::new(rrset.read().unwrap().with_owner(qname)))
+    };
+    match result {
+        LookupResult::Answer(rrset) => LookupResult::Answer(expand(&rrset)),
+        LookupResult::CName(rrset) => LookupResult::CName(expand(&rrset)),
+        LookupResult::Any(rrsets) => LookupResult::Any(rrsets.iter().map(expand).collect()),
+        other => other,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use dnsproto::qtype::DnsTypeRRSIG;
+
+    fn lookup(storage: &SafeRBTreeStorage, name: &str, qtype: DNSType) -> LookupResult {
+        storage.lookup(&DNSName::new(name, None).unwrap(), qtype)
+    }
+
+    fn owners(rrset: &RRSetHandle) -> Vec<String> {
+        let mut owners: Vec<String> = rrset
+            .read()
+            .unwrap()
+            .content()
+            .iter()
+            .map(|rr| format!("{} {}", rr.get_dname(), rr.get_type()))
+            .collect();
+        owners.dedup();
+        owners
+    }
+
+    #[test]
+    fn test_lookup() {
+        let storage = SafeRBTreeStorage::new_zone_from_file("./test/lookup.zone", None).unwrap();
+
+        for (name, qtype, expect) in [
+            ("example.net.", DNSType::SOA, "example.net. SOA"),
+            ("www.example.net.", DNSType::A, "www.example.net. A"),
+            ("WWW.Example.net.", DNSType::A, "www.example.net. A"),
+            (
+                "alias.example.net.",
+                DNSType::CNAME,
+                "alias.example.net. CNAME",
+            ),
+            // the wildcard is expanded to the query name
+            ("a.wild.example.net.", DNSType::A, "a.wild.example.net. A"),
+            (
+                "a.b.wild.example.net.",
+                DNSType::A,
+                "a.b.wild.example.net. A",
+            ),
+            // DS records belong to the parent side of the zone cut
+            ("sub.example.net.", DNSType::DS, "sub.example.net. DS"),
+        ]
+        .iter()
+        {
+            match lookup(&storage, name, *qtype) {
+                LookupResult::Answer(rrset) => assert_eq!(owners(&rrset), vec![*expect]),
+                other => panic!("{} {}: {:?}", name, qtype, other),
+            }
+        }
+
+        match lookup(&storage, "alias.example.net.", DNSType::A) {
+            LookupResult::CName(rrset) => {
+                assert_eq!(owners(&rrset), vec!["alias.example.net. CNAME"])
+            }
+            other => panic!("{:?}", other),
+        }
+
+        for name in ["sub.example.net.", "host.sub.example.net."].iter() {
+            match lookup(&storage, name, DNSType::A) {
+                LookupResult::Delegation { ns, glue } => {
+                    assert_eq!(ns.read().unwrap().content().len(), 2);
+                    // the out of bailiwick name server has no glue
+                    let glue: Vec<String> = glue.iter().flat_map(owners).collect();
+                    assert_eq!(glue, vec!["ns.sub.example.net. A"]);
+                }
+                other => panic!("{}: {:?}", name, other),
+            }
+        }
+
+        for (name, qtype) in [
+            ("www.example.net.", DNSType::MX),
+            ("www.example.net.", DNSType::Unknown(65280)),
+            // empty non-terminal
+            ("ent.example.net.", DNSType::A),
+            ("a.wild.example.net.", DNSType::TXT),
+        ]
+        .iter()
+        {
+            match lookup(&storage, name, *qtype) {
+                LookupResult::NoData { soa } => {
+                    assert_eq!(owners(&soa), vec!["example.net. SOA"])
+                }
+                other => panic!("{} {}: {:?}", name, qtype, other),
+            }
+        }
+
+        for name in ["missing.example.net.", "a.host.ent.example.net."].iter() {
+            match lookup(&storage, name, DNSType::A) {
+                LookupResult::NxDomain { soa } => {
+                    assert_eq!(owners(&soa), vec!["example.net. SOA"])
+                }
+                other => panic!("{}: {:?}", name, other),
+            }
+        }
+
+        // the soa ttl 3600 is great than its minimum 300
+        let soa = storage.lookup(&DNSName::new("example.net.", None).unwrap(), DNSType::SOA);
+        match soa {
+            LookupResult::Answer(soa) => {
+                let soa = soa.read().unwrap();
+                assert_eq!(soa.ttl(), 3600);
+                assert_eq!(negative_soa(&soa).unwrap().get_ttl(), 300);
+            }
+            other => panic!("{:?}", other),
+        }
+
+        // the apex ns rrset is an answer instead of a delegation
+        match lookup(&storage, "example.net.", DNSType::NS) {
+            LookupResult::Answer(rrset) => assert_eq!(owners(&rrset), vec!["example.net. NS"]),
+            other => panic!("{:?}", other),
+        }
+        match lookup(&storage, "example.net.", DNSType::Any) {
+            LookupResult::Any(rrsets) => {
+                let owners: Vec<String> = rrsets.iter().flat_map(owners).collect();
+                assert_eq!(owners, vec!["example.net. NS", "example.net. SOA"]);
+            }
+            other => panic!("{:?}", other),
+        }
+        match lookup(&storage, "ent.example.net.", DNSType::Any) {
+            LookupResult::NoData { .. } => {}
+            other => panic!("{:?}", other),
+        }
+
+        for name in ["example.org.", "net.", "."].iter() {
+            match lookup(&storage, name, DNSType::A) {
+                LookupResult::OutOfZone => {}
+                other => panic!("{}: {:?}", name, other),
+            }
+        }
+    }
+    #[test]
+    fn test_inspect() {
+        let storage = SafeRBTreeStorage::new_zone_from_file("./test/lookup.zone", None).unwrap();
+        let inspect = |name: &str| {
+            storage
+                .inspect(&DNSName::new(name, None).unwrap(), DNSType::A, None)
+                .unwrap()
+        };
+        let report = inspect("example.net.");
+        assert!(report.apex && !report.delegation && !report.wildcard);
+        let types: Vec<DNSType> = report.rrsets.iter().map(|(dtype, _)| *dtype).collect();
+        assert_eq!(types, vec![DNSType::NS, DNSType::SOA]);
+        assert_eq!(report.status(DNSType::NS), DataStatus::Authoritative);
+
+        let report = inspect("sub.example.net.");
+        assert!(report.delegation && report.below_cut.is_none());
+        assert_eq!(report.status(DNSType::NS), DataStatus::Delegation);
+        assert_eq!(report.status(DNSType::DS), DataStatus::Authoritative);
+        assert!(matches!(report.result, LookupResult::Delegation { .. }));
+
+        let report = inspect("ns.sub.example.net.");
+        assert_eq!(
+            report.below_cut.as_ref().unwrap().to_string(),
+            "sub.example.net."
+        );
+        assert_eq!(report.status(DNSType::A), DataStatus::Glue);
+        assert_eq!(report.status(DNSType::TXT), DataStatus::Occluded);
+
+        let report = inspect("a.b.wild.example.net.");
+        assert!(report.wildcard);
+        assert_eq!(report.node.unwrap().to_string(), "*.wild.example.net.");
+        assert!(matches!(report.result, LookupResult::Answer(_)));
+
+        let report = inspect("missing.example.net.");
+        assert!(report.node.is_none() && report.rrsets.is_empty());
+        assert!(matches!(report.result, LookupResult::NxDomain { .. }));
+
+        let name = DNSName::new("www.example.org.", None).unwrap();
+        assert!(storage.inspect(&name, DNSType::A, None).is_err());
+        let zone = DNSName::new("sub.example.net.", None).unwrap();
+        assert!(storage.inspect(&name, DNSType::A, Some(&zone)).is_err());
+    }
+
+    #[test]
+    fn test_lookup_wildcard_signatures() {
+        let storage = SafeRBTreeStorage::new_zone_from_str(
+            "$ORIGIN example.net.\n\
+             @ 3600 IN SOA ns admin 1 7200 3600 1209600 300\n\
+             *.wild 3600 IN A 192.0.2.4\n\
+             *.wild 3600 IN RRSIG A 8 3 3600 20300101000000 20200101000000 12345 example.net. AAAA\n",
+            None,
+        )
+        .unwrap();
+        let qname = DNSName::new("a.b.wild.example.net.", None).unwrap();
+        // the signatures of a node are kept in its RRSIG set
+        let rrset = match lookup(&storage, "a.b.wild.example.net.", DNSType::RRSIG) {
+            LookupResult::Answer(rrset) => rrset,
+            other => panic!("{:?}", other),
+        };
+        let rrset = rrset.read().unwrap();
+        let signature = &rrset.signatures()[0];
+        assert_eq!(signature.get_dname(), &qname);
+        // the labels field still tells the answer is expanded from *.wild.example.net.
+        let rrsig = signature
+            .get_data()
+            .as_ref()
+            .and_then(|data| data.as_any().downcast_ref::<DnsTypeRRSIG>())
+            .unwrap();
+        assert_eq!(rrsig.labels(), 3);
+
+        // the zone data is not changed by the expansion
+        match lookup(&storage, "*.wild.example.net.", DNSType::A) {
+            LookupResult::Answer(rrset) => {
+                assert_eq!(owners(&rrset), vec!["*.wild.example.net. A"])
+            }
+            other => panic!("{:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_lookup_root_zone() {
+        let storage = SafeRBTreeStorage::new_zone_from_str(
+            ". 86400 IN SOA a.root-servers.net. nstld.verisign-grs.com. 1 1800 900 604800 86400\n\
+             . 518400 IN NS a.root-servers.net.\n\
+             com. 172800 IN NS a.gtld-servers.net.\n\
+             a.root-servers.net. 518400 IN A 198.41.0.4\n",
+            None,
+        )
+        .unwrap();
+        match lookup(&storage, ".", DNSType::NS) {
+            LookupResult::Answer(rrset) => assert_eq!(owners(&rrset), vec![". NS"]),
+            other => panic!("{:?}", other),
+        }
+        match lookup(&storage, "example.com.", DNSType::A) {
+            LookupResult::Delegation { ns, glue } => {
+                assert_eq!(owners(&ns), vec!["com. NS"]);
+                assert!(glue.is_empty());
+            }
+            other => panic!("{:?}", other),
+        }
+        match lookup(&storage, "a.root-servers.net.", DNSType::A) {
+            LookupResult::Answer(rrset) => {
+                assert_eq!(owners(&rrset), vec!["a.root-servers.net. A"])
+            }
+            other => panic!("{:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_lookup_compressed_edge() {
+        let storage = SafeRBTreeStorage::new_zone_from_str(
+            "$ORIGIN example.net.\n\
+             @ 3600 IN SOA ns admin 1 7200 3600 1209600 300\n\
+             @ 3600 IN NS ns\n\
+             a.b.c.deep 3600 IN A 192.0.2.1\n\
+             *.x.y.z 3600 IN A 192.0.2.2\n",
+            None,
+        )
+        .unwrap();
+        for (name, expect) in [
+            ("a.b.c.deep.example.net.", "a.b.c.deep.example.net. A"),
+            ("q.x.y.z.example.net.", "q.x.y.z.example.net. A"),
+        ]
+        .iter()
+        {
+            match lookup(&storage, name, DNSType::A) {
+                LookupResult::Answer(rrset) => assert_eq!(owners(&rrset), vec![*expect]),
+                other => panic!("{}: {:?}", name, other),
+            }
+        }
+        // the empty names kept in the edge of one node
+        for name in [
+            "deep.example.net.",
+            "b.c.deep.example.net.",
+            "y.z.example.net.",
+        ]
+        .iter()
+        {
+            match lookup(&storage, name, DNSType::A) {
+                LookupResult::NoData { .. } => {}
+                other => panic!("{}: {:?}", name, other),
+            }
+        }
+        for name in [
+            "q.b.c.deep.example.net.",
+            "q.y.z.example.net.",
+            "q.deep.example.net.",
+        ]
+        .iter()
+        {
+            match lookup(&storage, name, DNSType::A) {
+                LookupResult::NxDomain { .. } => {}
+                other => panic!("{}: {:?}", name, other),
+            }
+        }
+        let closest = |name: &str| {
+            storage
+                .closest_encloser(&DNSName::new(name, None).unwrap())
+                .map(|name| name.to_string())
+        };
+        assert_eq!(
+            closest("q.b.c.deep.example.net."),
+            Some("b.c.deep.example.net.".to_owned())
+        );
+        assert_eq!(
+            closest("q.y.z.example.net."),
+            Some("y.z.example.net.".to_owned())
+        );
+
+        let report = storage
+            .inspect(
+                &DNSName::new("c.deep.example.net.", None).unwrap(),
+                DNSType::A,
+                None,
+            )
+            .unwrap();
+        assert_eq!(report.node.unwrap().to_string(), "c.deep.example.net.");
+        assert!(report.rrsets.is_empty());
+    }
+}
@@ -0,0 +1,162 @@
+use crate::lookup::{negative_soa, LookupResult};
+use dnsproto::dnsname::DNSName;
+use dnsproto::meta::DNSType;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// slots of the negative cache of a storage
+pub const NEGATIVE_CACHE_SLOTS: usize = 4096;
+
+#[derive(Debug)]
+struct NegativeEntry {
+    qname: DNSName,
+    // nxdomain is answered to all query types
+    qtype: Option<DNSType>,
+    result: LookupResult,
+    generation: u64,
+    expire_at: Instant,
+}
+
+/// NegativeCache keeps the recent NxDomain and NoData lookup results in a fixed number of
+/// slots indexed by the query name hash, a new entry replace the one in its slot. entries
+/// expire with the negative ttl of the zone soa(rfc2308 section 5), and all of them are
+/// dropped by invalidate when the zone data is written.
+pub struct NegativeCache {
+    slots: Vec<Mutex<Option<NegativeEntry>>>,
+    generation: AtomicU64,
+}
+
+impl std::fmt::Debug for NegativeCache {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("NegativeCache")
+            .field("slots", &self.slots.len())
+            .field("generation", &self.generation())
+            .finish()
+    }
+}
+
+impl Default for NegativeCache {
+    fn default() -> Self {
+        NegativeCache::new(NEGATIVE_CACHE_SLOTS)
+    }
+}
+
+impl NegativeCache {
+    pub fn new(size: usize) -> NegativeCache {
+        NegativeCache {
+            slots: (0..size.max(1)).map(|_| Mutex::new(None)).collect(),
+            generation: AtomicU64::new(0),
+        }
+    }
+
+    /// generation return the current generation, read it before the tree walk and pass it
+    /// to insert so a result computed before a write is never cached.
+    pub fn generation(&self) -> u64 {
+        self.generation.load(Ordering::Acquire)
+    }
+
+    /// invalidate drop all entries, called after any write of the zone data.
+    pub fn invalidate(&self) {
+        self.generation.fetch_add(1, Ordering::AcqRel);
+    }
+
+    /// get return the cached negative result of the name and type.
+    pub fn get(&self, qname: &DNSName, qtype: DNSType) -> Option<LookupResult> {
+        let generation = self.generation();
+        let slot = self.slot(qname).lock().unwrap();
+        let entry = slot.as_ref()?;
+        if entry.generation != generation
+            || entry.expire_at <= Instant::now()
+            || entry.qtype.map_or(false, |cached| cached != qtype)
+            || &entry.qname != qname
+        {
+            return None;
+        }
+        Some(entry.result.clone())
+    }
+
+    /// insert cache the result when it is NxDomain or NoData, other results are ignored.
+    pub fn insert(&self, qname: &DNSName, qtype: DNSType, result: &LookupResult, generation: u64) {
+        let (qtype, soa) = match result {
+            LookupResult::NxDomain { soa } => (None, soa),
+            LookupResult::NoData { soa } => (Some(qtype), soa),
+            _ => return,
+        };
+        let ttl = match negative_soa(&soa.read().unwrap()) {
+            Some(rr) if rr.get_ttl() > 0 => rr.get_ttl(),
+            _ => return,
+        };
+        *self.slot(qname).lock().unwrap() = Some(NegativeEntry {
+            qname: qname.clone(),
+            qtype,
+            result: result.clone(),
+            generation,
+            expire_at: Instant::now() + Duration::from_secs(u64::from(ttl)),
+        });
+    }
+
+    fn slot(&self, qname: &DNSName) -> &Mutex<Option<NegativeEntry>> {
+        let mut hasher = DefaultHasher::new();
+        qname.hash(&mut hasher);
+        &self.slots[(hasher.finish() % self.slots.len() as u64) as usize]
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::storage::SafeRBTreeStorage;
+    use dnsproto::meta::{DNSClass, ResourceRecord};
+
+    fn name(name: &str) -> DNSName {
+        DNSName::new(name, None).unwrap()
+    }
+
+    #[test]
+    fn test_negative_cache() {
+        let mut storage =
+            SafeRBTreeStorage::new_zone_from_file("./test/example.zone", None).unwrap();
+        let cache = storage.negative_cache();
+        let missing = name("missing.example.com.");
+        let mail = name("mail.example.com.");
+        assert!(matches!(
+            storage.lookup(&missing, DNSType::A),
+            LookupResult::NxDomain { .. }
+        ));
+        assert!(matches!(
+            storage.lookup(&mail, DNSType::TXT),
+            LookupResult::NoData { .. }
+        ));
+        // nxdomain is cached for all types, nodata for the query type only
+        assert!(matches!(
+            cache.get(&missing, DNSType::MX),
+            Some(LookupResult::NxDomain { .. })
+        ));
+        assert!(matches!(
+            cache.get(&mail, DNSType::TXT),
+            Some(LookupResult::NoData { .. })
+        ));
+        assert!(cache.get(&mail, DNSType::A).is_none());
+
+        // a write drop the cached results
+        let rr = ResourceRecord::new("missing.example.com.", DNSType::A, DNSClass::IN, 60, None)
+            .unwrap();
+        storage.insert_rr(rr).unwrap();
+        assert!(cache.get(&missing, DNSType::A).is_none());
+        assert!(cache.get(&mail, DNSType::TXT).is_none());
+        assert!(matches!(
+            storage.lookup(&missing, DNSType::A),
+            LookupResult::Answer(_)
+        ));
+
+        // a result computed before a write is not cached
+        let generation = cache.generation();
+        let result = storage.lookup_uncached(&mail, DNSType::TXT);
+        cache.invalidate();
+        cache.insert(&mail, DNSType::TXT, &result, generation);
+        assert!(cache.get(&mail, DNSType::TXT).is_none());
+    }
+}
@@ -0,0 +1,237 @@
+use crate::safe_rbtree::{child_node, walk_view, SafeRBTreeNode};
+use crate::storage::{SafeRBTreeStorage, WILDCARD_LABEL};
+use dnsproto::dnsname::DNSName;
+use dnsproto::meta::{DNSType, ResourceRecord};
+use dnsproto::qtype::DnsTypeRRSIG;
+use std::ops::Bound;
+use std::sync::{Arc, RwLock};
+
+type NodeHandle = Arc<RwLock<SafeRBTreeNode>>;
+
+impl SafeRBTreeStorage {
+    /// closest_encloser return the longest existing name of the zone which is the query name
+    /// or an ancestor of it(rfc4592 section 3.3.1), None if the name is out of the zones.
+    pub fn closest_encloser(&self, qname: &DNSName) -> Option<DNSName> {
+        let (path, _) = self.encloser_path(qname)?;
+        path.last().map(|node| node.read().unwrap().get_name())
+    }
+
+    /// find_covering_nsec return the NSEC records and their signatures of the last name
+    /// before the query name in canonical order, which proves the name does not exist.
+    /// None if the name exists or the zone has no NSEC chain.
+    pub fn find_covering_nsec(&self, qname: &DNSName) -> Option<Vec<ResourceRecord>> {
+        let (path, exists) = self.encloser_path(qname)?;
+        if exists {
+            return None;
+        }
+        // walk back from the closest encloser, the predecessor is the last name with NSEC
+        // before the label of the query name below each node of the path
+        for node in path.iter().rev() {
+            let depth = node.read().unwrap().depth();
+            let bound = &qname.labels[qname.label_count() - depth - 1];
+            let children: Vec<NodeHandle> = node
+                .read()
+                .unwrap()
+                .subtree
+                .read()
+                .unwrap()
+                .range((Bound::Unbounded, Bound::Excluded(bound)))
+                .map(|(_, child)| walk_view(child))
+                .collect();
+            for child in children.iter().rev() {
+                if let Some(found) = last_nsec_node(child) {
+                    return nsec_records(&found);
+                }
+            }
+            if has_nsec(node) {
+                return nsec_records(node);
+            }
+        }
+        None
+    }
+
+    /// find_wildcard_nsec return the NSEC records and their signatures which prove the
+    /// wildcard of the closest encloser does not exist, None if the wildcard exists.
+    pub fn find_wildcard_nsec(&self, closest_encloser: &DNSName) -> Option<Vec<ResourceRecord>> {
+        let mut wildcard = closest_encloser.clone();
+        wildcard.labels.insert(0, WILDCARD_LABEL.clone());
+        self.find_covering_nsec(&wildcard)
+    }
+
+    /// nxdomain_proof return the NSEC records and signatures for the authority section of
+    /// a signed NXDOMAIN answer(rfc4035 section 3.1.3.2), the name and the wildcard of its
+    /// closest encloser do not exist. empty if the name exists or the zone has no NSEC
+    /// chain.
+    pub fn nxdomain_proof(&self, qname: &DNSName) -> Vec<ResourceRecord> {
+        if let Some((_, true)) = self.encloser_path(qname) {
+            return vec![];
+        }
+        let mut records = self.find_covering_nsec(qname).unwrap_or_default();
+        let wildcard = self
+            .closest_encloser(qname)
+            .and_then(|encloser| self.find_wildcard_nsec(&encloser))
+            .unwrap_or_default();
+        // one NSEC may cover both names
+        if records.first().map(|rr| rr.get_dname()) != wildcard.first().map(|rr| rr.get_dname()) {
+            records.extend(wildcard);
+        }
+        records
+    }
+
+    // encloser_path return the nodes from the zone apex down to the closest encloser of
+    // the name, and whether the closest encloser is the name itself.
+    fn encloser_path(&self, qname: &DNSName) -> Option<(Vec<NodeHandle>, bool)> {
+        let apex = self.find_zone(qname)?;
+        let mut path = vec![self.find_or_none(&apex)?];
+        let below = qname.label_count() - apex.label_count();
+        for label in qname.labels[..below].iter().rev() {
+            match child_node(path.last().unwrap(), label) {
+                Some(next) => path.push(next),
+                None => return Some((path, false)),
+            }
+        }
+        Some((path, true))
+    }
+}
+
+fn has_nsec(node: &NodeHandle) -> bool {
+    node.read().unwrap().rr_sets.contains_key(&DNSType::NSEC)
+}
+
+// last_nsec_node return the last node in canonical order of the subtree(the node itself
+// included) which has the NSEC records, names below the node sort after it.
+fn last_nsec_node(node: &NodeHandle) -> Option<NodeHandle> {
+    let children: Vec<NodeHandle> = node
+        .read()
+        .unwrap()
+        .subtree
+        .read()
+        .unwrap()
+        .values()
+        .map(walk_view)
+        .collect();
+    for child in children.iter().rev() {
+        if let Some(found) = last_nsec_node(child) {
+            return Some(found);
+        }
+    }
+    match has_nsec(node) {
+        true => Some(node.clone()),
+        false => None,
+    }
+}
+
+// nsec_records return the NSEC records of the node followed by the signatures covering them.
+fn nsec_records(node: &NodeHandle) -> Option<Vec<ResourceRecord>> {
+    let node = node.read().unwrap();
+    let mut records = node
+        .find_rrset(DNSType::NSEC)
+        .ok()?
+        .read()
+        .unwrap()
+        .content()
+        .clone();
+    if let Ok(rrsig) = node.find_rrset(DNSType::RRSIG) {
+        let rrsig = rrsig.read().unwrap();
+        records.extend(
+            rrsig
+                .signatures()
+                .iter()
+                .filter(|rr| {
+                    rr.get_data()
+                        .as_ref()
+                        .and_then(|data| data.as_any().downcast_ref::<DnsTypeRRSIG>())
+                        .map(|rrsig| rrsig.type_covered() == u16::from(DNSType::NSEC))
+                        .unwrap_or(false)
+                })
+                .cloned(),
+        );
+    }
+    Some(records)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn nsec_owner(records: Option<Vec<ResourceRecord>>) -> Option<String> {
+        let records = records?;
+        // the NSEC record is followed by its signature
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].get_type(), DNSType::NSEC);
+        assert_eq!(records[1].get_type(), DNSType::RRSIG);
+        assert_eq!(records[0].get_dname(), records[1].get_dname());
+        Some(records[0].get_dname().to_string())
+    }
+
+    #[test]
+    fn test_find_covering_nsec() {
+        let storage = SafeRBTreeStorage::new_zone_from_file("./test/nsec.zone", None).unwrap();
+        let covering =
+            |name: &str| nsec_owner(storage.find_covering_nsec(&DNSName::new(name, None).unwrap()));
+        for (name, owner) in [
+            ("b.nsec.example.", "a.nsec.example."),
+            ("zzz.nsec.example.", "x.nsec.example."),
+            ("0.nsec.example.", "nsec.example."),
+            // below the empty non-terminal, before and after its only child
+            ("foo.ent.nsec.example.", "a.nsec.example."),
+            ("zz.ent.nsec.example.", "host.ent.nsec.example."),
+            ("a.host.ent.nsec.example.", "host.ent.nsec.example."),
+            ("b.a.nsec.example.", "a.nsec.example."),
+        ]
+        .iter()
+        {
+            assert_eq!(covering(name), Some(owner.to_string()), "{}", name);
+        }
+        // existing names and empty non-terminals are not denied
+        assert_eq!(covering("a.nsec.example."), None);
+        assert_eq!(covering("ent.nsec.example."), None);
+        assert_eq!(covering("www.example.org."), None);
+    }
+
+    #[test]
+    fn test_nxdomain_proof() {
+        let storage = SafeRBTreeStorage::new_zone_from_file("./test/nsec.zone", None).unwrap();
+        let owners = |name: &str| -> Vec<String> {
+            storage
+                .nxdomain_proof(&DNSName::new(name, None).unwrap())
+                .iter()
+                .filter(|rr| rr.get_type() == DNSType::NSEC)
+                .map(|rr| rr.get_dname().to_string())
+                .collect()
+        };
+        assert_eq!(
+            owners("zzz.nsec.example."),
+            vec!["x.nsec.example.", "nsec.example."]
+        );
+        // a.nsec.example. NSEC covers both foo.ent and *.ent
+        assert_eq!(owners("foo.ent.nsec.example."), vec!["a.nsec.example."]);
+        assert!(owners("a.nsec.example.").is_empty());
+        assert!(owners("ent.nsec.example.").is_empty());
+    }
+
+    #[test]
+    fn test_find_wildcard_nsec() {
+        let storage = SafeRBTreeStorage::new_zone_from_file("./test/nsec.zone", None).unwrap();
+        let name = |name: &str| DNSName::new(name, None).unwrap();
+        let encloser = storage
+            .closest_encloser(&name("foo.ent.nsec.example."))
+            .unwrap();
+        assert_eq!(encloser, name("ent.nsec.example."));
+        assert_eq!(
+            nsec_owner(storage.find_wildcard_nsec(&encloser)),
+            Some("a.nsec.example.".to_string())
+        );
+        // '*' sorts before the letters, the apex NSEC covers the wildcard of the apex
+        let encloser = storage.closest_encloser(&name("b.nsec.example.")).unwrap();
+        assert_eq!(encloser, name("nsec.example."));
+        assert_eq!(
+            nsec_owner(storage.find_wildcard_nsec(&encloser)),
+            Some("nsec.example.".to_string())
+        );
+        assert_eq!(
+            storage.closest_encloser(&name("a.nsec.example.")),
+            Some(name("a.nsec.example."))
+        );
+    }
+}
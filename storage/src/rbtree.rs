@@ -1,4 +1,5 @@
 #![allow(dead_code)]
+use std::borrow::Borrow;
 use std::cmp::Ord;
 use std::cmp::Ordering;
 use std::fmt::Debug;
@@ -122,11 +123,17 @@ where
         Self::default()
     }
 
-    pub fn get(&self, key: &K) -> Option<&V> {
+    /// get return the value of the key, the key can be any borrowed form of K which
+    /// must order the same as K does.
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
         let mut maybe_id = self.root;
         while let Some(id) = maybe_id {
             let node = &self.nodes[id];
-            match key.cmp(&node.key) {
+            match key.cmp(node.key.borrow()) {
                 Ordering::Equal => return Some(&node.value),
                 Ordering::Less => maybe_id = node.left,
                 Ordering::Greater => maybe_id = node.right,
@@ -135,11 +142,15 @@ where
         None
     }
 
-    pub fn get_id(&self, key: &K) -> Option<usize> {
+    pub fn get_id<Q>(&self, key: &Q) -> Option<usize>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
         let mut maybe_id = self.root;
         while let Some(id) = maybe_id {
             let node = &self.nodes[id];
-            match key.cmp(&node.key) {
+            match key.cmp(node.key.borrow()) {
                 Ordering::Equal => return Some(id),
                 Ordering::Less => maybe_id = node.left,
                 Ordering::Greater => maybe_id = node.right,
@@ -187,7 +198,11 @@ where
         }
     }
 
-    pub fn contains(&self, key: &K) -> bool {
+    pub fn contains<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
         self.get(key).is_some()
     }
 
@@ -880,6 +895,19 @@ mod test {
         assert_eq!(9, tree.len());
         // println!("{}", tree.nodes.len());
     }
+
+    #[test]
+    fn test_tree_borrowed_keys() {
+        let mut tree = RBTree::new();
+        for key in ["a", "b", "c"].iter() {
+            tree.insert(key.to_string(), key.len());
+        }
+        // owned keys are looked up without building a String
+        assert_eq!(Some(&1), tree.get("b"));
+        assert_eq!(tree.get_id("c"), tree.get_id(&"c".to_string()));
+        assert!(tree.contains("a"));
+        assert!(!tree.contains("d"));
+    }
     #[test]
     fn test_tree_find_smallest() {
         let mut tree = RBTree::new();
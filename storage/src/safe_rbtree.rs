@@ -12,7 +12,15 @@ use std::sync::{Arc, RwLock, Weak};
 
 #[derive(Debug)]
 pub struct SafeRBTreeNode {
-    label: Label,
+    pub(crate) label: Label,
+    // label count of the absolute node name, root is 0
+    depth: usize,
+    // labels of the empty names between the parent and the node, the one below the parent
+    // first. a node spanning several names is kept in the subtree of its parent by the first
+    // label of its edge and only empty nodes have an edge.
+    pub(crate) edge: Vec<Label>,
+    // a detached copy of the names of an edge made when walking down the tree
+    detached: bool,
     pub(crate) auth_data: bool,
     pub(crate) rr_sets: DashMap<DNSType, Arc<RwLock<RRSet>>>,
     pub(crate) parent: Option<Weak<RwLock<SafeRBTreeNode>>>,
@@ -48,7 +56,7 @@ impl Iterator for SafeZoneIterator {
                 return if let Some(v) = tree
                     .read()
                     .unwrap()
-                    .range(&next.read().unwrap().label..)
+                    .range(next.read().unwrap().key()..)
                     .next()
                 {
                     self.next = Some(v.1.clone());
@@ -83,6 +91,9 @@ impl SafeRBTreeNode {
     pub fn new_root() -> SafeRBTreeNode {
         SafeRBTreeNode {
             label: Label::root(),
+            depth: 0,
+            edge: vec![],
+            detached: false,
             auth_data: false,
             rr_sets: Default::default(),
             parent: None,
@@ -165,14 +176,10 @@ impl SafeRBTreeNode {
     pub fn find_rrset(&self, dtype: DNSType) -> Result<Arc<RwLock<RRSet>>, StorageError> {
         match self.rr_sets.get(&dtype) {
             Some(rrset) => Ok(rrset.clone()),
-            None => Err(StorageError::DNSTypeNotFoundError(
-                self.get_name().to_string(),
-                dtype.to_string(),
-            )),
+            None => Err(StorageError::DNSTypeNotFoundError(u16::from(dtype))),
         }
     }
 
-    #[allow(dead_code)]
     fn get_parent(&self) -> Option<Arc<RwLock<SafeRBTreeNode>>> {
         if let Some(parent) = self.parent.clone() {
             parent.upgrade()
@@ -181,34 +188,120 @@ impl SafeRBTreeNode {
         }
     }
 
-    #[allow(dead_code)]
+    /// depth return the label count of the absolute node name.
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    /// contains return true if the name is the node name or below it.
+    pub fn contains(&self, name: &DNSName) -> bool {
+        let count = name.label_count();
+        count >= self.depth && self.suffix_eq(0, &name.labels[count - self.depth..])
+    }
+
+    /// is_part_of return true if the node name is the zone or below it.
+    pub fn is_part_of(&self, zone: &DNSName) -> bool {
+        let count = zone.label_count();
+        count <= self.depth && self.suffix_eq(self.depth - count, &zone.labels)
+    }
+
+    // suffix_eq compare the labels with the node name after skip its leftmost labels, the
+    // labels are compared walking up the parents so the node name is never built.
+    fn suffix_eq(&self, mut skip: usize, labels: &[Label]) -> bool {
+        let mut labels = labels.iter();
+        if self.depth == 0 {
+            return labels.next().is_none();
+        }
+        let mut matches = |node: &SafeRBTreeNode| {
+            node.own_labels().all(|label| {
+                if skip > 0 {
+                    skip -= 1;
+                    return true;
+                }
+                labels.next() == Some(label)
+            })
+        };
+        if !matches(self) {
+            return false;
+        }
+        let mut current = self.get_parent();
+        while let Some(parent) = current {
+            let node = parent.read().unwrap();
+            if node.depth == 0 {
+                break;
+            }
+            if !matches(&node) {
+                return false;
+            }
+            current = node.get_parent();
+        }
+        labels.next().is_none()
+    }
+
+    // own_labels return the label of the node followed by the labels of its edge from the
+    // nearest one, they are the leftmost labels of the node name.
+    fn own_labels(&self) -> impl Iterator<Item = &Label> {
+        std::iter::once(&self.label).chain(self.edge.iter().rev())
+    }
+
+    /// key return the label the node is kept by in the subtree of its parent.
+    pub(crate) fn key(&self) -> &Label {
+        self.edge.first().unwrap_or(&self.label)
+    }
+
     pub fn get_name(&self) -> DNSName {
         if self.label.is_empty() {
             return DNSName { labels: vec![] };
         }
-        let mut labels = vec![];
-        labels.push(self.label.clone());
-        if let Some(parent) = &self.parent {
-            let mut current = parent.upgrade();
-            while let Some(value) = current {
-                let label = (*value).read().unwrap().label.to_owned();
-                if label.is_empty() {
-                    break;
-                }
-                labels.push(label);
-                if let Some(parent) = &(*value).read().unwrap().parent {
-                    current = parent.upgrade();
-                } else {
-                    break;
-                }
+        let mut labels: Vec<Label> = self.own_labels().cloned().collect();
+        let mut current = self.get_parent();
+        while let Some(value) = current {
+            let node = value.read().unwrap();
+            if node.label.is_empty() {
+                break;
             }
+            labels.extend(node.own_labels().cloned());
+            current = node.get_parent();
         }
         DNSName { labels }
     }
-    /// create a new node from dns label and with default values.
-    pub(crate) fn from_label(label: Label) -> Arc<RwLock<SafeRBTreeNode>> {
+
+    // edge_view return a detached copy of the top name of the edge, each copy holds the
+    // copy of the next name in its subtree and the last one holds the children of the node,
+    // so the tree below is walked label by label like the names are not compressed.
+    fn edge_view(&self) -> Arc<RwLock<SafeRBTreeNode>> {
+        let next = SafeRBTreeNode::from_label(self.label.clone(), self.depth);
+        {
+            let mut node = next.write().unwrap();
+            node.edge = self.edge.clone();
+            node.detached = true;
+            node.parent = self.parent.clone();
+            node.subtree = self.subtree.clone();
+        }
+        let count = self.edge.len();
+        let mut next = next;
+        for (index, label) in self.edge.iter().enumerate().rev() {
+            let view = SafeRBTreeNode::from_label(label.clone(), self.depth - (count - index));
+            {
+                let mut node = view.write().unwrap();
+                node.edge = self.edge[..index].to_vec();
+                node.detached = true;
+                node.parent = self.parent.clone();
+                let key = next.read().unwrap().label.clone();
+                node.subtree.write().unwrap().insert(key, next);
+            }
+            next = view;
+        }
+        next
+    }
+
+    /// create a new node from dns label and the depth of its name with default values.
+    pub(crate) fn from_label(label: Label, depth: usize) -> Arc<RwLock<SafeRBTreeNode>> {
         Arc::new(RwLock::new(SafeRBTreeNode {
             label,
+            depth,
+            edge: vec![],
+            detached: false,
             auth_data: false,
             rr_sets: Default::default(),
             parent: None,
@@ -216,3 +309,30 @@ impl SafeRBTreeNode {
         }))
     }
 }
+
+/// child_node return the child of the node by the label, a child spanning several empty names
+/// is returned as the detached copy of its top name, it must not be changed.
+pub(crate) fn child_node(
+    node: &Arc<RwLock<SafeRBTreeNode>>,
+    label: &Label,
+) -> Option<Arc<RwLock<SafeRBTreeNode>>> {
+    let child = node
+        .read()
+        .unwrap()
+        .subtree
+        .read()
+        .unwrap()
+        .get(label)
+        .cloned()?;
+    Some(walk_view(&child))
+}
+
+/// walk_view return the node as it is seen walking down from its parent, the node itself or
+/// the detached copy of the top name of its edge.
+pub(crate) fn walk_view(node: &Arc<RwLock<SafeRBTreeNode>>) -> Arc<RwLock<SafeRBTreeNode>> {
+    let guard = node.read().unwrap();
+    match guard.detached || guard.edge.is_empty() {
+        true => node.clone(),
+        false => guard.edge_view(),
+    }
+}
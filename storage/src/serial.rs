@@ -0,0 +1,106 @@
+use otterlib::setting::SerialPolicy;
+
+const SERIAL_HALF_RANGE: u32 = 1 << 31;
+
+/// serial_gt compare two zone serials with rfc1982 serial number arithmetic.
+pub fn serial_gt(serial: u32, other: u32) -> bool {
+    (serial < other && other - serial > SERIAL_HALF_RANGE)
+        || (serial > other && serial - other < SERIAL_HALF_RANGE)
+}
+
+/// date_of_unixtime return (year, month, day) of the unix timestamp in utc.
+fn date_of_unixtime(now: u64) -> (u64, u64, u64) {
+    // http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let days = now / 86400 + 719468;
+    let era = days / 146097;
+    let day_of_era = days - era * 146097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+/// bump_serial return the new serial based on the policy and the served serial,
+/// return None if the policy is keep.
+pub fn bump_serial(policy: SerialPolicy, served: u32, now: u64) -> Option<u32> {
+    match policy {
+        SerialPolicy::Keep => None,
+        SerialPolicy::Increment => Some(served.wrapping_add(1)),
+        SerialPolicy::Unixtime => {
+            let now = now as u32;
+            if serial_gt(now, served) {
+                Some(now)
+            } else {
+                Some(served.wrapping_add(1))
+            }
+        }
+        SerialPolicy::Date => {
+            let (year, month, day) = date_of_unixtime(now);
+            let today = ((year * 10000 + month * 100 + day) * 100) as u32;
+            // more than 99 changes in a day will borrow the number of next day
+            if serial_gt(today, served) {
+                Some(today)
+            } else {
+                Some(served.wrapping_add(1))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // 2021-01-01T12:00:00Z
+    const NOW: u64 = 1609502400;
+
+    #[test]
+    fn test_serial_gt() {
+        assert!(serial_gt(2, 1));
+        assert!(!serial_gt(1, 2));
+        assert!(!serial_gt(1, 1));
+        assert!(serial_gt(0, u32::MAX));
+    }
+
+    #[test]
+    fn test_bump_serial() {
+        assert_eq!(bump_serial(SerialPolicy::Keep, 10, NOW), None);
+        assert_eq!(bump_serial(SerialPolicy::Increment, 10, NOW), Some(11));
+        assert_eq!(
+            bump_serial(SerialPolicy::Unixtime, 10, NOW),
+            Some(1609502400)
+        );
+        assert_eq!(
+            bump_serial(SerialPolicy::Unixtime, 1609502400, NOW),
+            Some(1609502401)
+        );
+        assert_eq!(
+            bump_serial(SerialPolicy::Date, 2020123105, NOW),
+            Some(2021010100)
+        );
+        assert_eq!(
+            bump_serial(SerialPolicy::Date, 2021010100, NOW),
+            Some(2021010101)
+        );
+    }
+
+    #[test]
+    fn test_bump_serial_date_rollover() {
+        // more than 99 changes in a day
+        assert_eq!(
+            bump_serial(SerialPolicy::Date, 2021010199, NOW),
+            Some(2021010200)
+        );
+        // serial already in the future keep increasing
+        assert_eq!(
+            bump_serial(SerialPolicy::Date, 2021010200, NOW),
+            Some(2021010201)
+        );
+        assert_eq!(date_of_unixtime(NOW), (2021, 1, 1));
+        assert_eq!(date_of_unixtime(951782400), (2000, 2, 29));
+    }
+}
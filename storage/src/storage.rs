@@ -1,21 +1,236 @@
-use crate::safe_rbtree::SafeRBTreeNode;
+use crate::dnssec::verify_dnssec;
+use crate::negative::NegativeCache;
+use crate::safe_rbtree::{child_node, walk_view, SafeRBTreeNode};
+use crate::serial::{bump_serial, serial_gt};
+use crate::zonemd::verify_zonemd;
 use dashmap::DashMap;
 use dnsproto::dnsname::DNSName;
 use dnsproto::label::Label;
-use dnsproto::meta::{DNSType, RRSet, ResourceRecord};
-use dnsproto::zone::{ZoneFileParser, ZoneReader};
+use dnsproto::meta::{DNSType, Provenance, RRSet, RecordOrder, ResourceRecord};
+use dnsproto::qtype::{DnsTypeMX, DnsTypeNS, DnsTypeRRSIG, DnsTypeSOA, DnsTypeSPF, DnsTypeTXT};
+use dnsproto::utils::valid_hostname;
+use dnsproto::zone::{ZoneFileParser, ZoneLines, ZoneReader, ZoneTextReader};
 use lazy_static::lazy_static;
-use otterlib::errors::{OtterError, StorageError};
+use otterlib::errors::{DNSProtoErr, OtterError, StorageError};
+#[cfg(feature = "testing")]
+use otterlib::fault::{Fault, FaultInjector};
+use otterlib::setting::{CheckNames, SerialPolicy, ZonemdVerify};
 use std::str::FromStr;
 use std::sync::{Arc, RwLock};
+use std::time::{SystemTime, UNIX_EPOCH};
 lazy_static! {
-    static ref WILDCARD_LABEL: Label = Label::from_str("*").unwrap();
+    pub(crate) static ref WILDCARD_LABEL: Label = Label::from_str("*").unwrap();
+    static ref REVERSE_ZONES: Vec<DNSName> = vec![
+        DNSName::new("in-addr.arpa.", None).unwrap(),
+        DNSName::new("ip6.arpa.", None).unwrap(),
+    ];
+}
+
+/// MAX_LABEL_DEPTH is the most labels of a domain name, a name is at most 255 octets.
+pub const MAX_LABEL_DEPTH: usize = 127;
+/// DEFAULT_MAX_LABEL_DEPTH is the most labels of a zone record owner name if the zone
+/// option is not set.
+pub const DEFAULT_MAX_LABEL_DEPTH: usize = 64;
+
+// estimated memory used by a record besides the owner name and rdata
+const RECORD_MEMORY_OVERHEAD: usize = 96;
+
+/// ZoneOption holds the per zone options applied when loading zone data.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ZoneOption {
+    pub min_ttl: Option<u32>,
+    pub max_ttl: Option<u32>,
+    pub default_ttl: Option<u32>,
+    pub serial_policy: SerialPolicy,
+    pub max_records: Option<usize>,
+    pub max_memory: Option<usize>,
+    pub verify_zonemd: ZonemdVerify,
+    pub spf_to_txt: bool,
+    pub check_names: CheckNames,
+    /// the most labels of a record owner name, DEFAULT_MAX_LABEL_DEPTH if not set
+    pub max_label_depth: Option<usize>,
+    /// verify the rrsig records of the transferred zone
+    pub verify_dnssec: bool,
+    /// reject the transfer when a rrsig record fails to verify
+    pub verify_dnssec_strict: bool,
+    /// the most rrsig records verified, all if not set
+    pub verify_dnssec_sample: Option<usize>,
+}
+
+/// LoadProgress is reported to the progress callback when loading a zone file.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct LoadProgress {
+    pub records: usize,
+    pub memory: usize,
+}
+
+/// LoadOption holds the zone option and the progress callback which is invoked
+/// every progress_interval records and once more when loading is done.
+pub struct LoadOption<'a> {
+    pub zone_option: ZoneOption,
+    pub progress_interval: usize,
+    pub progress: Option<Box<dyn FnMut(&LoadProgress) + 'a>>,
+}
+
+impl<'a> LoadOption<'a> {
+    pub fn new(zone_option: ZoneOption) -> LoadOption<'a> {
+        LoadOption {
+            zone_option,
+            progress_interval: 0,
+            progress: None,
+        }
+    }
+
+    pub fn with_progress<F>(mut self, interval: usize, callback: F) -> LoadOption<'a>
+    where
+        F: FnMut(&LoadProgress) + 'a,
+    {
+        self.progress_interval = interval;
+        self.progress = Some(Box::new(callback));
+        self
+    }
+
+    fn report(&mut self, progress: &LoadProgress, done: bool) {
+        if let Some(callback) = self.progress.as_mut() {
+            let reached =
+                self.progress_interval > 0 && progress.records % self.progress_interval == 0;
+            if reached != done {
+                callback(progress);
+            }
+        }
+    }
+}
+
+fn estimate_record_memory(rr: &ResourceRecord) -> usize {
+    let rdata = rr
+        .get_data()
+        .as_ref()
+        .and_then(|data| data.encode(None).ok())
+        .map(|data| data.len())
+        .unwrap_or(0);
+    RECORD_MEMORY_OVERHEAD + rr.get_dname().to_string().len() + rdata
+}
+
+impl ZoneOption {
+    /// clamp the record ttl into [min_ttl, max_ttl]
+    pub fn clamp_ttl(&self, rr: &mut ResourceRecord) {
+        let ttl = rr.get_ttl();
+        let mut clamped = ttl;
+        if let Some(min_ttl) = self.min_ttl {
+            clamped = clamped.max(min_ttl);
+        }
+        if let Some(max_ttl) = self.max_ttl {
+            clamped = clamped.min(max_ttl);
+        }
+        if clamped != ttl {
+            warn!(
+                "ttl of {} {} is out of range, clamp {} to {}",
+                rr.get_dname().to_string(),
+                rr.get_type(),
+                ttl,
+                clamped
+            );
+            rr.set_ttl(clamped);
+        }
+    }
+
+    /// check_label_depth reject the record when its owner name has more labels than the
+    /// max_label_depth, a crafted name would build a long chain of tree nodes.
+    pub fn check_label_depth(&self, rr: &ResourceRecord) -> Result<(), StorageError> {
+        let limit = self
+            .max_label_depth
+            .unwrap_or(DEFAULT_MAX_LABEL_DEPTH)
+            .min(MAX_LABEL_DEPTH);
+        match rr.get_dname().label_count() > limit {
+            true => Err(StorageError::LabelDepthExceeded(
+                rr.get_dname().to_string(),
+                limit,
+            )),
+            false => Ok(()),
+        }
+    }
+}
+
+/// check_ptr_owner check the owner name of a PTR record under in-addr.arpa or ip6.arpa is a
+/// well-formed reverse name, an invalid owner fails the loading in CheckNames::Fail mode and
+/// is logged in Warn mode. PTR records of other names like the catalog zone members and
+/// DNS-SD services are not checked.
+pub fn check_ptr_owner(rr: &ResourceRecord, mode: CheckNames) -> Result<(), StorageError> {
+    let owner = rr.get_dname();
+    if mode == CheckNames::Ignore
+        || rr.get_type() != DNSType::PTR
+        || !REVERSE_ZONES.iter().any(|zone| owner.is_part_of(zone))
+        || owner.to_ip_addr().is_some()
+    {
+        return Ok(());
+    }
+    let err = StorageError::InvalidReverseName(owner.to_string());
+    if mode == CheckNames::Fail {
+        return Err(err);
+    }
+    warn!("{}", err);
+    Ok(())
+}
+
+/// check_target_hostname check the NS and MX target of the record is a valid host name,
+/// an invalid target fails the loading in CheckNames::Fail mode and is logged in Warn mode.
+pub fn check_target_hostname(rr: &ResourceRecord, mode: CheckNames) -> Result<(), StorageError> {
+    if mode == CheckNames::Ignore {
+        return Ok(());
+    }
+    let data = match rr.get_data() {
+        Some(data) => data.as_any(),
+        None => return Ok(()),
+    };
+    let target = match rr.get_type() {
+        DNSType::NS => data.downcast_ref::<DnsTypeNS>().map(|ns| ns.name()),
+        DNSType::MX => data.downcast_ref::<DnsTypeMX>().map(|mx| mx.exchange()),
+        _ => None,
+    };
+    let target = match target {
+        Some(target) => target.to_string(),
+        None => return Ok(()),
+    };
+    if valid_hostname(target.as_str()) {
+        return Ok(());
+    }
+    let err = StorageError::InvalidHostName(
+        rr.get_dname().to_string(),
+        rr.get_type().to_string(),
+        target,
+    );
+    if mode == CheckNames::Fail {
+        return Err(err);
+    }
+    warn!("{}", err);
+    Ok(())
+}
+
+/// ZoneChangeHook is invoked with the zone apex after the zone data is loaded or reloaded.
+pub type ZoneChangeHook = Box<dyn Fn(&DNSName) + Send + Sync>;
+
+#[derive(Default, Clone)]
+struct ZoneChangeHooks(Arc<RwLock<Vec<ZoneChangeHook>>>);
+
+impl std::fmt::Debug for ZoneChangeHooks {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "ZoneChangeHooks({})", self.0.read().unwrap().len())
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct SafeRBTreeStorage {
     domain_tree: Arc<RwLock<SafeRBTreeNode>>,
     fast_cache: DashMap<DNSName, Arc<RwLock<SafeRBTreeNode>>>,
+    // apex names of all loaded zones
+    zone_apexes: Arc<RwLock<Vec<DNSName>>>,
+    // apex names of the zones which must not be listed or transferred
+    hidden_zones: Arc<RwLock<Vec<DNSName>>>,
+    zone_hooks: ZoneChangeHooks,
+    negative_cache: Arc<NegativeCache>,
+    // the fault injector of the lookups, shared by all clones of the storage
+    #[cfg(feature = "testing")]
+    faults: Arc<RwLock<Option<Arc<FaultInjector>>>>,
 }
 
 unsafe impl Send for SafeRBTreeStorage {}
@@ -31,6 +246,58 @@ impl SafeRBTreeStorage {
         SafeRBTreeStorage {
             domain_tree: Arc::new(RwLock::new(node)),
             fast_cache: DashMap::new(),
+            zone_apexes: Arc::new(RwLock::new(vec![])),
+            hidden_zones: Arc::new(RwLock::new(vec![])),
+            zone_hooks: ZoneChangeHooks::default(),
+            negative_cache: Arc::new(NegativeCache::default()),
+            #[cfg(feature = "testing")]
+            faults: Arc::new(RwLock::new(None)),
+        }
+    }
+
+    /// negative_cache return the cache of NxDomain and NoData lookup results, it is shared
+    /// by all clones of the storage.
+    pub fn negative_cache(&self) -> Arc<NegativeCache> {
+        self.negative_cache.clone()
+    }
+
+    /// add_zone_change_hook register a hook fired when a zone is loaded or reloaded,
+    /// the hooks are shared by all clones of the storage.
+    pub fn add_zone_change_hook<F>(&self, hook: F)
+    where
+        F: Fn(&DNSName) + Send + Sync + 'static,
+    {
+        self.zone_hooks.0.write().unwrap().push(Box::new(hook));
+    }
+
+    /// set_fault_injector install the injector on the lookups of the storage and all its
+    /// clones, None remove it.
+    #[cfg(feature = "testing")]
+    pub fn set_fault_injector(&self, injector: Option<Arc<FaultInjector>>) {
+        *self.faults.write().unwrap() = injector;
+    }
+
+    /// inject_fault apply the next fault of the installed injector to a lookup, an error
+    /// fault is returned as StorageError::InjectedFault and a delay blocks the caller.
+    #[cfg(feature = "testing")]
+    pub(crate) fn inject_fault(&self) -> Result<(), StorageError> {
+        let fault = match self.faults.read().unwrap().as_ref() {
+            Some(injector) => injector.next_fault(),
+            None => return Ok(()),
+        };
+        match fault {
+            Some(Fault::Error) => return Err(StorageError::InjectedFault),
+            Some(Fault::Delay(delay)) => std::thread::sleep(delay),
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// notify_zone_changed fire all zone change hooks with the zone apex.
+    pub fn notify_zone_changed(&self, apex: &DNSName) {
+        self.negative_cache.invalidate();
+        for hook in self.zone_hooks.0.read().unwrap().iter() {
+            hook(apex);
         }
     }
 
@@ -47,14 +314,134 @@ impl SafeRBTreeStorage {
         &mut self,
         file: &str,
         default_origin: Option<String>,
+    ) -> Result<Arc<RwLock<SafeRBTreeNode>>, OtterError> {
+        self.update_zone_with_option(file, default_origin, &ZoneOption::default())
+    }
+
+    /// update_zone_with_option load the zone file and apply the zone options to each record.
+    pub fn update_zone_with_option(
+        &mut self,
+        file: &str,
+        default_origin: Option<String>,
+        option: &ZoneOption,
+    ) -> Result<Arc<RwLock<SafeRBTreeNode>>, OtterError> {
+        self.update_zone_with_progress(file, default_origin, &mut LoadOption::new(option.clone()))
+    }
+
+    /// update_zone_with_progress load the zone file record by record, abort when the zone
+    /// exceeds the max_records or max_memory limit and report the progress to the callback.
+    pub fn update_zone_with_progress(
+        &mut self,
+        file: &str,
+        default_origin: Option<String>,
+        opts: &mut LoadOption,
     ) -> Result<Arc<RwLock<SafeRBTreeNode>>, OtterError> {
         let parser = ZoneFileParser::new(file)?;
-        let reader = ZoneReader::new(parser, default_origin);
+        self.load_zone_lines(file, parser, default_origin, opts)
+    }
+
+    /// new_zone_from_str build the storage from the zone text, used by tests and tools
+    /// which keep the zone data in memory.
+    pub fn new_zone_from_str(
+        text: &str,
+        default_origin: Option<String>,
+    ) -> Result<SafeRBTreeStorage, OtterError> {
+        let mut zone = SafeRBTreeStorage::new(SafeRBTreeNode::new_root());
+        zone.update_zone_from_str(text, default_origin, &ZoneOption::default())?;
+        Ok(zone)
+    }
+
+    /// update_zone_from_str load the zone text like update_zone_with_option.
+    pub fn update_zone_from_str(
+        &mut self,
+        text: &str,
+        default_origin: Option<String>,
+        option: &ZoneOption,
+    ) -> Result<Arc<RwLock<SafeRBTreeNode>>, OtterError> {
+        self.load_zone_lines(
+            "<text>",
+            ZoneTextReader::new(text),
+            default_origin,
+            &mut LoadOption::new(option.clone()),
+        )
+    }
+
+    // load_zone_lines load the zone from the lines of a zone file or text into a detached
+    // tree and publish it when all records are loaded, the readers never see a half loaded
+    // zone. the error is returned with the source and the line of the last record read.
+    fn load_zone_lines<T>(
+        &mut self,
+        file: &str,
+        lines: T,
+        default_origin: Option<String>,
+        opts: &mut LoadOption,
+    ) -> Result<Arc<RwLock<SafeRBTreeNode>>, OtterError>
+    where
+        T: ZoneLines,
+    {
+        let mut reader =
+            ZoneReader::new(lines, default_origin).with_default_ttl(opts.zone_option.default_ttl);
+        let mut staged = SafeRBTreeStorage::default();
+        let apex = staged
+            .load_zone_records(file, &mut reader, opts)
+            .map_err(|err| match reader.line() {
+                0 => err.with_file(file),
+                line => err.with_file(file).with_line(line),
+            })?
+            .read()
+            .unwrap()
+            .get_name();
+        self.swap_zone(&mut staged, &apex)
+    }
+
+    /// load_zone_records load the zone from the records, the first one must be the apex soa
+    /// and the others must be at or below the apex. the source is only used in the error
+    /// messages.
+    pub(crate) fn load_zone_records<T>(
+        &mut self,
+        file: &str,
+        records: T,
+        opts: &mut LoadOption,
+    ) -> Result<Arc<RwLock<SafeRBTreeNode>>, OtterError>
+    where
+        T: Iterator<Item = Result<ResourceRecord, DNSProtoErr>>,
+    {
+        let option = opts.zone_option.clone();
         let mut first_rr = None;
         let mut start_point = None;
-        for item in reader {
+        let mut progress = LoadProgress::default();
+        for item in records {
             match item {
-                Ok(rr) => {
+                Ok(mut rr) => {
+                    progress.records += 1;
+                    progress.memory += estimate_record_memory(&rr);
+                    if let Some(max_records) = option.max_records {
+                        if progress.records > max_records {
+                            return Err(OtterError::StorageError(
+                                StorageError::ZoneLoadLimitExceeded(
+                                    file.to_owned(),
+                                    "max_records".to_owned(),
+                                    max_records,
+                                ),
+                            ));
+                        }
+                    }
+                    if let Some(max_memory) = option.max_memory {
+                        if progress.memory > max_memory {
+                            return Err(OtterError::StorageError(
+                                StorageError::ZoneLoadLimitExceeded(
+                                    file.to_owned(),
+                                    "max_memory".to_owned(),
+                                    max_memory,
+                                ),
+                            ));
+                        }
+                    }
+                    opts.report(&progress, false);
+                    option.clamp_ttl(&mut rr);
+                    check_ptr_owner(&rr, option.check_names)?;
+                    check_target_hostname(&rr, option.check_names)?;
+                    option.check_label_depth(&rr)?;
                     // insert rr record to zone node.
                     if first_rr.is_none() {
                         if rr.get_type() != DNSType::SOA {
@@ -63,92 +450,514 @@ impl SafeRBTreeStorage {
                             ));
                         }
                         first_rr = Some(rr.clone());
+                        self.add_zone_apex(rr.get_dname());
                         start_point = Some(self.insert_rr(rr)?);
                     } else {
                         if rr.get_type() == DNSType::SOA {
                             return Err(OtterError::StorageError(StorageError::TooManySOARecords));
                         }
+                        let apex = first_rr.as_ref().map(|soa| soa.get_dname());
+                        if rr.get_type() == DNSType::CNAME && apex == Some(rr.get_dname()) {
+                            return Err(OtterError::StorageError(StorageError::ApexCNAMEError(
+                                rr.get_dname().to_string(),
+                            )));
+                        }
+                        if let Some(apex) = apex.filter(|apex| !rr.get_dname().is_part_of(apex)) {
+                            return Err(OtterError::StorageError(StorageError::ZoneOutOfArea(
+                                rr.get_dname().to_string(),
+                                apex.to_string(),
+                            )));
+                        }
                         self.insert_rr(rr)?;
                     }
                 }
-                Err(err) => return Err(OtterError::DNSProtoError(err)),
+                Err(err) => return Err(err.into()),
             }
         }
         if first_rr.is_none() || start_point.is_none() {
             return Err(OtterError::StorageError(StorageError::SOAResourceError));
         }
-        Ok(start_point.unwrap())
+        opts.report(&progress, true);
+        let start_point = start_point.unwrap();
+        let apex = start_point.read().unwrap().get_name();
+        if option.verify_zonemd != ZonemdVerify::Off {
+            if let Err(err) = verify_zonemd(self, &apex) {
+                if option.verify_zonemd == ZonemdVerify::Reject {
+                    return Err(OtterError::StorageError(err));
+                }
+                warn!("{}", err);
+            }
+        }
+        if option.spf_to_txt {
+            let count = self.synthesize_spf_txt(&apex)?;
+            if count > 0 {
+                warn!(
+                    "zone {} has {} deprecated spf records, synthesized the txt records",
+                    apex.to_string(),
+                    count
+                );
+            }
+        }
+        self.notify_zone_changed(&apex);
+        Ok(start_point)
+    }
+
+    /// synthesize_spf_txt add a txt record with the same data for each spf record of the
+    /// zone, unless the owner already has it. return the number of synthesized records.
+    fn synthesize_spf_txt(&mut self, apex: &DNSName) -> Result<usize, OtterError> {
+        let mut synthesized = vec![];
+        for node in self.zone_nodes(apex) {
+            let node = node.read().unwrap();
+            let spf_rrset = match node.find_rrset(DNSType::SPF) {
+                Ok(rrset) => rrset,
+                Err(_) => continue,
+            };
+            let existing: Vec<DnsTypeTXT> = match node.find_rrset(DNSType::TXT) {
+                Ok(rrset) => rrset
+                    .read()
+                    .unwrap()
+                    .content()
+                    .iter()
+                    .filter_map(|rr| rr.get_data().as_ref())
+                    .filter_map(|data| data.as_any().downcast_ref::<DnsTypeTXT>())
+                    .cloned()
+                    .collect(),
+                Err(_) => vec![],
+            };
+            for rr in spf_rrset.read().unwrap().content().iter() {
+                let txt = match rr
+                    .get_data()
+                    .as_ref()
+                    .and_then(|data| data.as_any().downcast_ref::<DnsTypeSPF>())
+                {
+                    Some(spf) => spf.txt().clone(),
+                    None => continue,
+                };
+                if existing.contains(&txt) {
+                    continue;
+                }
+                let txt_rr = ResourceRecord::new(
+                    rr.get_dname().to_string().as_str(),
+                    DNSType::TXT,
+                    rr.get_class(),
+                    rr.get_ttl(),
+                    Some(Box::new(txt)),
+                )?;
+                synthesized.push(txt_rr);
+            }
+        }
+        let count = synthesized.len();
+        for rr in synthesized {
+            self.insert_rr(rr)?;
+        }
+        Ok(count)
+    }
+
+    fn add_zone_apex(&self, apex: &DNSName) {
+        let mut zone_apexes = self.zone_apexes.write().unwrap();
+        if !zone_apexes.contains(apex) {
+            zone_apexes.push(apex.clone());
+        }
+    }
+
+    /// find_zone return the closest loaded zone apex of the name,
+    /// or None if the name is not belong to any loaded zone.
+    pub fn find_zone(&self, name: &DNSName) -> Option<DNSName> {
+        let zone_apexes = self.zone_apexes.read().unwrap();
+        name.iter_suffixes()
+            .find(|suffix| zone_apexes.contains(suffix))
+    }
+
+    /// set_zone_hidden mark the zone as hidden or visible, a hidden zone is never listed or
+    /// transferred. the flag is kept by name, so it can be set before the zone is loaded
+    /// and is kept when the zone is reloaded or transferred.
+    pub fn set_zone_hidden(&self, apex: &DNSName, hidden: bool) {
+        let mut hidden_zones = self.hidden_zones.write().unwrap();
+        hidden_zones.retain(|zone| zone != apex);
+        if hidden {
+            hidden_zones.push(apex.clone());
+        }
+    }
+
+    /// hidden_zones return the apex names of the zones marked as hidden, loaded or not.
+    pub fn hidden_zones(&self) -> Vec<DNSName> {
+        self.hidden_zones.read().unwrap().clone()
+    }
+
+    /// is_zone_hidden return true when the zone is marked as hidden.
+    pub fn is_zone_hidden(&self, apex: &DNSName) -> bool {
+        self.hidden_zones.read().unwrap().contains(apex)
+    }
+
+    /// get_zone_soa return the soa data of the zone apex.
+    pub fn get_zone_soa(&self, apex: &DNSName) -> Result<DnsTypeSOA, StorageError> {
+        let rrset = self.find(apex)?.read().unwrap().find_rrset(DNSType::SOA)?;
+        let rrset = rrset.read().unwrap();
+        match rrset
+            .content()
+            .first()
+            .and_then(|rr| rr.get_data().as_ref())
+        {
+            Some(data) => match data.as_any().downcast_ref::<DnsTypeSOA>() {
+                Some(soa) => Ok(soa.clone()),
+                None => Err(StorageError::SOAResourceError),
+            },
+            None => Err(StorageError::SOAResourceError),
+        }
+    }
+
+    /// set_zone_soa replace the soa data of the zone apex.
+    pub fn set_zone_soa(&self, apex: &DNSName, soa: DnsTypeSOA) -> Result<(), StorageError> {
+        let rrset = self.find(apex)?.read().unwrap().find_rrset(DNSType::SOA)?;
+        let mut rrset = rrset.write().unwrap();
+        match rrset.content_mut().first_mut() {
+            Some(rr) => {
+                rr.set_data(Some(Box::new(soa)));
+                self.negative_cache.invalidate();
+                Ok(())
+            }
+            None => Err(StorageError::SOAResourceError),
+        }
+    }
+
+    /// reload_zone load the zone file into a new tree, check the serial against the served
+    /// zone with the serial policy, then replace the served zone data.
+    pub fn reload_zone(
+        &mut self,
+        file: &str,
+        default_origin: Option<String>,
+        option: &ZoneOption,
+    ) -> Result<Arc<RwLock<SafeRBTreeNode>>, OtterError> {
+        let mut reloaded = SafeRBTreeStorage::default();
+        let apex = reloaded
+            .update_zone_with_option(file, default_origin, option)?
+            .read()
+            .unwrap()
+            .get_name();
+        if self.find_zone(&apex) == Some(apex.clone()) {
+            let served = self.get_zone_soa(&apex)?.serial();
+            let mut soa = reloaded.get_zone_soa(&apex)?;
+            if !serial_gt(soa.serial(), served) {
+                let now = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map(|duration| duration.as_secs())
+                    .unwrap_or(0);
+                match bump_serial(option.serial_policy, served, now) {
+                    Some(serial) => {
+                        warn!(
+                            "zone {} serial {} not great than served serial {}, rewrite to {}",
+                            apex.to_string(),
+                            soa.serial(),
+                            served,
+                            serial
+                        );
+                        soa.set_serial(serial);
+                        reloaded.set_zone_soa(&apex, soa)?;
+                    }
+                    None => {
+                        return Err(OtterError::StorageError(StorageError::SerialNotIncreased(
+                            apex.to_string(),
+                            soa.serial(),
+                            served,
+                        )))
+                    }
+                }
+            }
+        }
+        self.swap_zone(&mut reloaded, &apex)
+    }
+
+    /// replace_zone build the zone from the records transferred from master in a new tree,
+    /// then replace the served zone data. the first record must be the apex soa, the served
+    /// zone is kept as is when any record fail to load. the records are tagged as transferred.
+    /// the rrsig records are verified before the replacement when verify_dnssec is set.
+    pub fn replace_zone(
+        &mut self,
+        records: Vec<ResourceRecord>,
+        option: &ZoneOption,
+    ) -> Result<Arc<RwLock<SafeRBTreeNode>>, OtterError> {
+        let mut replaced = SafeRBTreeStorage::default();
+        let mut apex = None;
+        for mut rr in records {
+            rr.set_provenance(Provenance::Transfer);
+            option.clamp_ttl(&mut rr);
+            check_target_hostname(&rr, option.check_names)?;
+            option.check_label_depth(&rr)?;
+            match (&apex, rr.get_type()) {
+                (None, DNSType::SOA) => {
+                    apex = Some(rr.get_dname().clone());
+                    replaced.add_zone_apex(rr.get_dname());
+                }
+                (None, _) => return Err(StorageError::NotStartWithSOARecord.into()),
+                (Some(_), DNSType::SOA) => return Err(StorageError::TooManySOARecords.into()),
+                _ => {}
+            }
+            replaced.insert_rr(rr)?;
+        }
+        let apex = apex.ok_or(StorageError::SOAResourceError)?;
+        if option.verify_dnssec {
+            let now = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|duration| duration.as_secs())
+                .unwrap_or(0);
+            match verify_dnssec(&replaced, &apex, now as u32, option.verify_dnssec_sample) {
+                Ok(verified) => debug!(
+                    "zone {} verified {} rrsig records",
+                    apex.to_string(),
+                    verified
+                ),
+                Err(err) if option.verify_dnssec_strict => return Err(err.into()),
+                Err(err) => warn!("{}", err),
+            }
+        }
+        self.swap_zone(&mut replaced, &apex)
+    }
+
+    /// swap_zone replace the served zone data of the apex with the data of source under the
+    /// write lock of the apex node, the zone is published after the swap so readers see
+    /// either no zone or the whole zone. the zones below the apex served from other sources
+    /// are moved into the source tree and kept, the DS rrset at a zone apex always comes
+    /// from the parent zone when it is served.
+    pub(crate) fn swap_zone(
+        &mut self,
+        source: &mut SafeRBTreeStorage,
+        apex: &DNSName,
+    ) -> Result<Arc<RwLock<SafeRBTreeNode>>, OtterError> {
+        let source_apex = source.find(apex)?;
+        let target = self.find_or_insert(apex)?;
+        self.graft_child_zones(source, apex)?;
+        let parent_served = self
+            .zones()
+            .iter()
+            .any(|zone| zone != apex && apex.is_part_of(zone));
+        {
+            let source = source_apex.read().unwrap();
+            let mut target_node = target.write().unwrap();
+            let rr_sets = source.rr_sets.clone();
+            if parent_served {
+                rr_sets.remove(&DNSType::DS);
+                if let Some(ds) = target_node.rr_sets.get(&DNSType::DS) {
+                    rr_sets.insert(DNSType::DS, ds.value().clone());
+                }
+            }
+            target_node.auth_data = source.auth_data;
+            target_node.rr_sets = rr_sets;
+            target_node.subtree = source.subtree.clone();
+        }
+        for child in target.read().unwrap().subtree.read().unwrap().values() {
+            child.write().unwrap().parent = Some(Arc::downgrade(&target));
+        }
+        self.add_zone_apex(apex);
+        self.notify_zone_changed(apex);
+        Ok(target)
+    }
+
+    // graft_child_zones move the served zones below the apex into the source tree, they are
+    // kept when the apex is swapped. the DS rrset of a child apex is replaced by the one of
+    // the source as it is the parent side data.
+    fn graft_child_zones(
+        &self,
+        source: &mut SafeRBTreeStorage,
+        apex: &DNSName,
+    ) -> Result<(), StorageError> {
+        let zones = self.zones();
+        let below = |zone: &DNSName, parent: &DNSName| zone != parent && zone.is_part_of(parent);
+        for child in zones.iter().filter(|zone| below(zone, apex)) {
+            // a zone below another child zone is moved with it
+            if zones
+                .iter()
+                .any(|zone| below(zone, apex) && below(child, zone))
+            {
+                continue;
+            }
+            let served = match self.find_or_none(child) {
+                Some(node) => node,
+                None => continue,
+            };
+            let parent_name = match child.parent() {
+                Some(name) => name,
+                None => continue,
+            };
+            let parent = source.find_or_insert(&parent_name)?;
+            let staged = child_node(&parent, &child.labels[0]);
+            {
+                let mut served_node = served.write().unwrap();
+                let ds = staged.and_then(|node| {
+                    node.read()
+                        .unwrap()
+                        .rr_sets
+                        .get(&DNSType::DS)
+                        .map(|ds| ds.value().clone())
+                });
+                match ds {
+                    Some(ds) => {
+                        served_node.rr_sets.insert(DNSType::DS, ds);
+                    }
+                    None => {
+                        served_node.rr_sets.remove(&DNSType::DS);
+                    }
+                }
+                // nodes right below the root have no parent, like find_or_insert
+                served_node.parent = match parent_name.label_count() {
+                    0 => None,
+                    _ => Some(Arc::downgrade(&parent)),
+                };
+            }
+            parent
+                .read()
+                .unwrap()
+                .subtree
+                .write()
+                .unwrap()
+                .insert(child.labels[0].clone(), served);
+        }
+        Ok(())
     }
 
     /// locate the dns name node from top zone root node. if the dns name is not found in this zone
     /// create a sub node based the label.
     /// should valid if the name is below to the zone data.
+    /// the empty names created on the way are kept in one node with a multi label edge, the
+    /// edge is split when a name branches from it, the returned node never has an edge.
     pub fn find_or_insert(
         &mut self,
         name: &DNSName,
     ) -> Result<Arc<RwLock<SafeRBTreeNode>>, StorageError> {
-        let current_name = &self.domain_tree.read().unwrap().get_name();
-        // domain not belong to this zone
-        if !name.is_part_of(current_name) {
-            return Err(StorageError::ZoneOutOfArea(
+        if name.label_count() > MAX_LABEL_DEPTH {
+            return Err(StorageError::LabelDepthExceeded(
                 name.to_string(),
-                current_name.to_string(),
+                MAX_LABEL_DEPTH,
             ));
         }
-        let mut labels_count = name.label_count();
-        if labels_count == 0 {
-            return Ok(self.domain_tree.clone());
+        // domain not belong to this zone, the zone name is only built for the error
+        if !self.domain_tree.read().unwrap().contains(name) {
+            return Err(StorageError::ZoneOutOfArea(
+                name.to_string(),
+                self.domain_tree.read().unwrap().get_name().to_string(),
+            ));
         }
-        let mut parent_node = None;
+        let depth = self.domain_tree.read().unwrap().depth();
+        // the labels below the tree top, the nearest one first
+        let labels: Vec<Label> = name.labels[..name.label_count() - depth]
+            .iter()
+            .rev()
+            .cloned()
+            .collect();
+        let mut rest = &labels[..];
         let mut current = self.domain_tree.clone();
-
-        for label in name.labels.iter().rev() {
-            labels_count -= 1;
-
+        while !rest.is_empty() {
             let result = current
                 .read()
                 .unwrap()
                 .subtree
                 .read()
                 .unwrap()
-                .get(&label.clone())
+                .get(&rest[0])
                 .cloned();
-
-            if let Some(node) = result {
-                if labels_count == 0 {
+            let node = match result {
+                Some(node) => node,
+                None => {
+                    let node = self.insert_path(&current, rest, name.label_count());
+                    self.negative_cache.invalidate();
                     return Ok(node);
                 }
-                parent_node = Some(node.clone());
-                current = node;
-                continue;
+            };
+            let (matched, spanned) = {
+                let node = node.read().unwrap();
+                let path: Vec<&Label> = node.edge.iter().chain(Some(&node.label)).collect();
+                let matched = path
+                    .iter()
+                    .zip(rest.iter())
+                    .take_while(|(label, other)| **label == *other)
+                    .count();
+                (matched, path.len())
+            };
+            let node = match matched < spanned {
+                // the name ends or branches inside the edge
+                true => self.split_edge(&current, &node, matched),
+                false => node,
+            };
+            if matched == rest.len() {
+                if matched > 1 {
+                    self.split_edge(&current, &node, matched - 1);
+                }
+                return Ok(node);
             }
+            current = node;
+            rest = &rest[matched..];
+        }
+        Ok(current)
+    }
 
-            let node = SafeRBTreeNode::from_label(label.clone());
-            if let Some(parent) = parent_node {
-                (*node).write().unwrap().parent = Some(Arc::downgrade(&parent));
-            } else {
-                (*node).write().unwrap().parent = None
-            }
-            current
+    // insert_path create the nodes of the labels below the node, the nearest one first. the
+    // empty names above the last label are kept in one node.
+    fn insert_path(
+        &self,
+        node: &Arc<RwLock<SafeRBTreeNode>>,
+        labels: &[Label],
+        depth: usize,
+    ) -> Arc<RwLock<SafeRBTreeNode>> {
+        let (last, empty) = labels.split_last().unwrap();
+        let created = SafeRBTreeNode::from_label(last.clone(), depth);
+        let mut top = created.clone();
+        if let Some((label, edge)) = empty.split_last() {
+            let upper = SafeRBTreeNode::from_label(label.clone(), depth - 1);
+            upper.write().unwrap().edge = edge.to_vec();
+            created.write().unwrap().parent = Some(Arc::downgrade(&upper));
+            upper
                 .read()
                 .unwrap()
                 .subtree
                 .write()
                 .unwrap()
-                .insert(label.clone(), node.clone());
-            // not found in subtree
-            if labels_count == 0 {
-                // subtree exist but has not label node
-                // create a new label node
-                return Ok(node);
-            } else {
-                // create a path to next label, but if each label has a new rbtree will consume
-                // too much memory , so should build with a compressed way
-                current = node.clone();
-                parent_node = Some(node);
-            }
+                .insert(last.clone(), created.clone());
+            top = upper;
         }
-        Ok(current)
+        // nodes right below the tree top have no parent
+        if !Arc::ptr_eq(node, &self.domain_tree) {
+            top.write().unwrap().parent = Some(Arc::downgrade(node));
+        }
+        let key = top.read().unwrap().key().clone();
+        node.read()
+            .unwrap()
+            .subtree
+            .write()
+            .unwrap()
+            .insert(key, top);
+        created
+    }
+
+    // split_edge move the names spanned by the child of the node above the position of its
+    // edge into a new node and return it, the child keeps the names from the position.
+    fn split_edge(
+        &self,
+        node: &Arc<RwLock<SafeRBTreeNode>>,
+        child: &Arc<RwLock<SafeRBTreeNode>>,
+        at: usize,
+    ) -> Arc<RwLock<SafeRBTreeNode>> {
+        let mut lower = child.write().unwrap();
+        let depth = lower.depth() - (lower.edge.len() - at) - 1;
+        let upper = SafeRBTreeNode::from_label(lower.edge[at - 1].clone(), depth);
+        {
+            let mut upper_node = upper.write().unwrap();
+            upper_node.edge = lower.edge[..at - 1].to_vec();
+            upper_node.parent = lower.parent.clone();
+            lower.edge.drain(..at);
+            upper_node
+                .subtree
+                .write()
+                .unwrap()
+                .insert(lower.key().clone(), child.clone());
+        }
+        lower.parent = Some(Arc::downgrade(&upper));
+        let key = upper.read().unwrap().key().clone();
+        node.read()
+            .unwrap()
+            .subtree
+            .write()
+            .unwrap()
+            .insert(key, upper.clone());
+        upper
     }
 
     pub fn insert_rr(
@@ -158,6 +967,7 @@ impl SafeRBTreeStorage {
         let dname = rr.get_dname();
         let vnode = self.find_or_insert(dname)?;
         vnode.write().unwrap().add_rr(rr)?;
+        self.negative_cache.invalidate();
         Ok(vnode)
     }
     /// search will travel from top of tree down to the bottom.
@@ -170,57 +980,45 @@ impl SafeRBTreeStorage {
         let node = node.read().unwrap();
         let result = match node.rr_sets.get(dtype) {
             Some(rrset) => Ok(rrset.clone()),
-            None => Err(StorageError::DNSTypeNotFoundError(
-                dname.to_string(),
-                dtype.to_string(),
-            )),
+            None => Err(StorageError::DNSTypeNotFoundError(u16::from(*dtype))),
         };
         result
     }
 
     pub fn delete_rrset(&mut self, dtype: DNSType) -> Result<(), StorageError> {
-        let name = self.domain_tree.read().unwrap().get_name();
-        match self.domain_tree.write().unwrap().rr_sets.remove(&dtype) {
-            Some(_) => Ok(()),
-            None => Err(StorageError::DNSTypeNotFoundError(
-                name.to_string(),
-                dtype.to_string(),
-            )),
+        let removed = self.domain_tree.write().unwrap().rr_sets.remove(&dtype);
+        match removed {
+            Some(_) => {
+                self.negative_cache.invalidate();
+                Ok(())
+            }
+            None => Err(StorageError::DNSTypeNotFoundError(u16::from(dtype))),
         }
     }
 
     pub fn find_best(&self, name: &DNSName) -> Option<Arc<RwLock<SafeRBTreeNode>>> {
+        // the root name is answered only when the root zone is loaded
         let mut labels_count = name.label_count();
-        if labels_count == 0 {
-            if !self.domain_tree.read().unwrap().auth_data {
-                return None;
-            }
-            return Some(self.domain_tree.clone());
-        }
         let mut current = self.domain_tree.clone();
         for label in name.labels.iter().rev() {
             labels_count -= 1;
-            let temp = current.clone();
-            let subtree = temp.read().unwrap().subtree.clone();
-            let subtree = subtree.read().unwrap();
-            let result = subtree.get(&label.clone());
             /// subtree exist and has label node
-            if let Some(node) = result {
+            if let Some(node) = child_node(&current, label) {
                 if labels_count == 0 {
-                    if !node.read().unwrap().auth_data {
+                    if !self.is_authoritative(&node) {
                         return None;
                     }
-                    return Some(node.clone());
+                    return Some(node);
                 }
-                current = node.clone();
+                current = node;
                 continue;
             }
-            if !current.read().unwrap().auth_data {
+            if !self.is_authoritative(&current) {
                 return None;
             }
             return Some(current);
         }
-        if !self.domain_tree.read().unwrap().auth_data {
+        if !self.is_authoritative(&self.domain_tree) {
             return None;
         }
         Some(current)
@@ -236,6 +1034,12 @@ impl SafeRBTreeStorage {
             let subtree = SafeRBTreeStorage {
                 domain_tree: val.clone(),
                 fast_cache: DashMap::new(),
+                zone_apexes: self.zone_apexes.clone(),
+                hidden_zones: self.hidden_zones.clone(),
+                zone_hooks: self.zone_hooks.clone(),
+                negative_cache: self.negative_cache.clone(),
+                #[cfg(feature = "testing")]
+                faults: self.faults.clone(),
             };
             return subtree.find_smallest(stack);
         }
@@ -262,49 +1066,301 @@ impl SafeRBTreeStorage {
     }
 
     pub fn is_own_domain(&self, name: &DNSName) -> bool {
-        let zone = self.domain_tree.read().unwrap().get_name();
-        let (is_relative, _) = name.is_relative(&zone);
-        is_relative
+        self.domain_tree.read().unwrap().contains(name)
     }
 
     pub fn find(&self, name: &DNSName) -> Result<Arc<RwLock<SafeRBTreeNode>>, StorageError> {
+        #[cfg(feature = "testing")]
+        self.inject_fault()?;
+        // the root name is answered only when the root zone is loaded
         let mut labels_count = name.label_count();
-        if labels_count == 0 {
-            if !self.domain_tree.read().unwrap().auth_data {
-                return Err(StorageError::RefusedError);
-            }
-            return Ok(self.domain_tree.clone());
-        }
         let mut current = self.domain_tree.clone();
         for label in name.labels.iter().rev() {
             labels_count -= 1;
-            let temp = current.clone();
-            let subtree = temp.read().unwrap().subtree.clone();
             /// subtree exist and has label node
-            if let Some(node) = subtree.read().unwrap().get(&label.clone()) {
+            if let Some(node) = child_node(&current, label) {
                 if labels_count == 0 {
-                    if !node.read().unwrap().auth_data {
+                    if !self.is_authoritative(&node) {
                         return Err(StorageError::RefusedError);
                     }
-                    return Ok(node.clone());
+                    return Ok(node);
                 }
-                current = node.clone();
+                current = node;
                 continue;
             }
-            if let Some(node) = subtree.read().unwrap().get(&WILDCARD_LABEL) {
-                return Ok(node.clone());
+            if let Some(node) = child_node(&current, &WILDCARD_LABEL) {
+                return Ok(node);
             }
             /// not found in subtree
-            if !current.read().unwrap().auth_data {
+            if !self.is_authoritative(&current) {
                 return Err(StorageError::RefusedError);
             }
-            return Err(StorageError::DomainNotFoundError(name.to_string()));
+            return Err(StorageError::DomainNotFoundError);
         }
-        if !current.read().unwrap().auth_data {
+        if !self.is_authoritative(&current) {
             return Err(StorageError::RefusedError);
         }
         Ok(current)
     }
+
+    /// is_authoritative return true when the node has records or is an empty non-terminal
+    /// inside a loaded zone, which must be answered with nodata instead of nxdomain(rfc8020).
+    /// nodes above the zone apexes are not authoritative.
+    fn is_authoritative(&self, node: &Arc<RwLock<SafeRBTreeNode>>) -> bool {
+        let node = node.read().unwrap();
+        node.auth_data
+            || self
+                .zone_apexes
+                .read()
+                .unwrap()
+                .iter()
+                .any(|apex| node.is_part_of(apex))
+    }
+
+    /// zones return the apex names of all loaded zones.
+    pub fn zones(&self) -> Vec<DNSName> {
+        self.zone_apexes.read().unwrap().clone()
+    }
+
+    /// zone_nodes return all nodes of the zone, nodes of the zones below it are excluded.
+    /// the apex node of a zone below holds the records of both zones, so it is excluded as
+    /// well, for_each_rrset visit the delegation and glue at the cut.
+    pub fn zone_nodes(&self, apex: &DNSName) -> Vec<Arc<RwLock<SafeRBTreeNode>>> {
+        let zones = self.zones();
+        let mut nodes = vec![];
+        let mut pending = match self.find_or_none(apex) {
+            Some(node) => vec![node],
+            None => return nodes,
+        };
+        // the children are pushed in reverse so a name comes before the names below it and
+        // siblings come out sorted by label
+        while let Some(node) = pending.pop() {
+            let children: Vec<Arc<RwLock<SafeRBTreeNode>>> = node
+                .read()
+                .unwrap()
+                .subtree
+                .read()
+                .unwrap()
+                .values()
+                .rev()
+                .map(walk_view)
+                .collect();
+            for child in children.iter() {
+                let is_apex = {
+                    let child = child.read().unwrap();
+                    zones
+                        .iter()
+                        .any(|zone| zone.label_count() == child.depth() && child.is_part_of(zone))
+                };
+                if !is_apex {
+                    pending.push(child.clone());
+                }
+            }
+            nodes.push(node);
+        }
+        nodes
+    }
+
+    /// for_each_rrset visit all rrsets of the zone with their owner name in the canonical
+    /// order, the sets of a name are sorted by type. the name is kept in one buffer along the
+    /// walk instead of being rebuilt from the parents of each node, only the delegation and
+    /// glue of the zones below it are visited.
+    pub fn for_each_rrset<F>(&self, apex: &DNSName, mut visit: F)
+    where
+        F: FnMut(&DNSName, DNSType, &RRSet),
+    {
+        let node = match self.find_or_none(apex) {
+            Some(node) => node,
+            None => return,
+        };
+        let zones: Vec<DNSName> = self
+            .zones()
+            .into_iter()
+            .filter(|zone| zone != apex)
+            .collect();
+        let hidden = self.hidden_zones();
+        let mut name = apex.clone();
+        visit_rrsets(
+            &node,
+            &mut name,
+            (zones.as_slice(), hidden.as_slice()),
+            &mut visit,
+        );
+    }
+
+    /// transfer_records return all records of the zone bracketed by the apex SOA like an
+    /// axfr response in the canonical order, the signatures follow the records they cover.
+    pub fn transfer_records(&self, apex: &DNSName) -> Result<Vec<ResourceRecord>, StorageError> {
+        let soa = self.find(apex)?.read().unwrap().find_rrset(DNSType::SOA)?;
+        let soa = soa
+            .read()
+            .unwrap()
+            .content()
+            .first()
+            .cloned()
+            .ok_or(StorageError::SOAResourceError)?;
+        let mut records = vec![soa.clone()];
+        self.for_each_rrset(apex, |name, dtype, rrset| {
+            let records_of_set = rrset.ordered(RecordOrder::Canonical);
+            // the apex soa only brackets the transfer, its signatures stay in place
+            let skip = match dtype == DNSType::SOA && name == apex {
+                true => rrset.size(),
+                false => 0,
+            };
+            records.extend(records_of_set.into_iter().skip(skip).cloned());
+        });
+        records.push(soa);
+        Ok(records)
+    }
+
+    // find_or_none walk down the tree to the name node without auth_data check, an empty
+    // name inside an edge is returned as a detached copy.
+    pub(crate) fn find_or_none(&self, name: &DNSName) -> Option<Arc<RwLock<SafeRBTreeNode>>> {
+        let mut current = self.domain_tree.clone();
+        for label in name.labels.iter().rev() {
+            current = child_node(&current, label)?;
+        }
+        Some(current)
+    }
+
+    /// find_children return the direct child nodes of the name, the name itself can be
+    /// an empty non-terminal without any records.
+    pub fn find_children(&self, name: &DNSName) -> Vec<Arc<RwLock<SafeRBTreeNode>>> {
+        let current = match self.find_or_none(name) {
+            Some(node) => node,
+            None => return vec![],
+        };
+        let children = current
+            .read()
+            .unwrap()
+            .subtree
+            .read()
+            .unwrap()
+            .values()
+            .map(walk_view)
+            .collect();
+        children
+    }
+}
+
+// visit_rrsets visit the rrsets of the node with the name and walk down to its children,
+// the labels of a child are pushed to the front of the name and removed after the child
+// is done. the locks are released before the visit goes down, the empty names inside an
+// edge have no records and are passed over. the zones below are visited as cuts, the
+// hidden ones are passed over with their delegation.
+fn visit_rrsets<F>(
+    node: &Arc<RwLock<SafeRBTreeNode>>,
+    name: &mut DNSName,
+    (zones, hidden): (&[DNSName], &[DNSName]),
+    visit: &mut F,
+) where
+    F: FnMut(&DNSName, DNSType, &RRSet),
+{
+    let (mut rr_sets, children) = {
+        let node = node.read().unwrap();
+        let rr_sets: Vec<(DNSType, Arc<RwLock<RRSet>>)> = node
+            .rr_sets
+            .iter()
+            .map(|rrset| (*rrset.key(), rrset.value().clone()))
+            .collect();
+        let children: Vec<Arc<RwLock<SafeRBTreeNode>>> =
+            node.subtree.read().unwrap().values().cloned().collect();
+        (rr_sets, children)
+    };
+    rr_sets.sort_by_key(|(dtype, _)| u16::from(*dtype));
+    for (dtype, rrset) in rr_sets.iter() {
+        visit(name, *dtype, &rrset.read().unwrap());
+    }
+    for child in children.iter() {
+        let pushed = {
+            let child = child.read().unwrap();
+            for label in child.edge.iter().chain(std::iter::once(&child.label)) {
+                name.labels.insert(0, label.clone());
+            }
+            child.edge.len() + 1
+        };
+        match (hidden.contains(name), zones.contains(name)) {
+            // nothing of a hidden zone is visible from its parent
+            (true, _) => {}
+            (false, true) => visit_cut(child, name, visit),
+            (false, false) => visit_rrsets(child, name, (zones, hidden), visit),
+        }
+        name.labels.drain(..pushed);
+    }
+}
+
+// visit_cut visit the parent side data at the apex of a zone below, the NS and DS rrsets
+// with the signatures of the DS and the address of the name servers at or below the cut
+// as the glue. the records of the zone below are not visited.
+fn visit_cut<F>(cut: &Arc<RwLock<SafeRBTreeNode>>, name: &DNSName, visit: &mut F)
+where
+    F: FnMut(&DNSName, DNSType, &RRSet),
+{
+    let (ns, ds, rrsig) = {
+        let node = cut.read().unwrap();
+        let rrset = |dtype: DNSType| node.find_rrset(dtype).ok();
+        (
+            rrset(DNSType::NS),
+            rrset(DNSType::DS),
+            rrset(DNSType::RRSIG),
+        )
+    };
+    let ns = match ns {
+        Some(ns) => ns,
+        None => return,
+    };
+    let mut targets: Vec<DNSName> = ns
+        .read()
+        .unwrap()
+        .iter_as::<DnsTypeNS>()
+        .map(|ns| ns.name().clone())
+        .filter(|target| target.is_part_of(name))
+        .collect();
+    targets.sort_by(|left, right| left.canonical_cmp(right));
+    targets.dedup();
+    let glue = |target: &DNSName| -> Vec<(DNSType, Arc<RwLock<RRSet>>)> {
+        let below = target.label_count() - name.label_count();
+        let mut node = cut.clone();
+        for label in target.labels[..below].iter().rev() {
+            node = match child_node(&node, label) {
+                Some(node) => node,
+                None => return vec![],
+            };
+        }
+        let node = node.read().unwrap();
+        [DNSType::A, DNSType::AAAA]
+            .iter()
+            .filter_map(|dtype| Some((*dtype, node.find_rrset(*dtype).ok()?)))
+            .collect()
+    };
+    let mut rr_sets = vec![(DNSType::NS, ns)];
+    rr_sets.extend(ds.map(|ds| (DNSType::DS, ds)));
+    // the signatures of the other rrsets at the cut belong to the zone below
+    if let Some(rrsig) = rrsig {
+        let mut signatures = RRSet::default();
+        for rr in rrsig.read().unwrap().signatures().iter().filter(|rr| {
+            rr.rdata_as::<DnsTypeRRSIG>().map_or(false, |rrsig| {
+                rrsig.type_covered() == u16::from(DNSType::DS)
+            })
+        }) {
+            signatures.add(rr.clone());
+        }
+        if !signatures.signatures().is_empty() {
+            rr_sets.push((DNSType::RRSIG, Arc::new(RwLock::new(signatures))));
+        }
+    }
+    if targets.first() == Some(name) {
+        rr_sets.extend(glue(name));
+    }
+    rr_sets.sort_by_key(|(dtype, _)| u16::from(*dtype));
+    for (dtype, rrset) in rr_sets.iter() {
+        visit(name, *dtype, &rrset.read().unwrap());
+    }
+    for target in targets.iter().filter(|target| *target != name) {
+        for (dtype, rrset) in glue(target).iter() {
+            visit(target, *dtype, &rrset.read().unwrap());
+        }
+    }
 }
 
 #[cfg(test)]
@@ -312,6 +1368,8 @@ mod test {
     use super::*;
     use crate::safe_rbtree::SafeRBTreeNode;
     use dnsproto::dnsname::DNSName;
+    use dnsproto::meta::DNSClass;
+    use dnsproto::qtype::DnsTypeA;
     use otterlib::errors::StorageError;
 
     fn get_example_zone() -> SafeRBTreeStorage {
@@ -342,7 +1400,7 @@ mod test {
         let test_domain = &DNSName::new("not_exist.example.com.", None).unwrap();
         assert_eq!(
             zone.find(test_domain).unwrap_err(),
-            StorageError::DomainNotFoundError("not_exist.example.com.".to_string())
+            StorageError::DomainNotFoundError
         );
         // out of zone
         let test_domain = &DNSName::new("outofzone.com.", None).unwrap();
@@ -352,6 +1410,275 @@ mod test {
         );
     }
 
+    #[test]
+    fn test_find_empty_non_terminal() {
+        let mut zone = get_example_zone();
+        let rr = ResourceRecord::new(
+            "_25._tcp.example.com.",
+            DNSType::TXT,
+            DNSClass::IN,
+            3600,
+            None,
+        )
+        .unwrap();
+        zone.insert_rr(rr).unwrap();
+        let rr = ResourceRecord::new(
+            "*.wild.example.com.",
+            DNSType::TXT,
+            DNSClass::IN,
+            3600,
+            None,
+        )
+        .unwrap();
+        zone.insert_rr(rr).unwrap();
+        // empty non-terminals exist but have no records
+        for name in ["_tcp.example.com.", "wild.example.com."].iter() {
+            let name = DNSName::new(name, None).unwrap();
+            let node = zone.find(&name).unwrap();
+            assert_eq!(node.read().unwrap().get_name(), name);
+            assert_eq!(
+                zone.search_rrset(&name, &DNSType::NS).unwrap_err(),
+                StorageError::DNSTypeNotFoundError(u16::from(DNSType::NS))
+            );
+            assert!(zone.find_best(&name).is_some());
+        }
+        // no node on the path below the empty non-terminal
+        let name = DNSName::new("_udp._tcp.example.com.", None).unwrap();
+        assert_eq!(
+            zone.find(&name).unwrap_err(),
+            StorageError::DomainNotFoundError
+        );
+        // node above the zone apex is not authoritative
+        let name = DNSName::new("com.", None).unwrap();
+        assert_eq!(zone.find(&name).unwrap_err(), StorageError::RefusedError);
+        assert!(zone.find_best(&name).is_none());
+    }
+
+    #[test]
+    fn test_node_depth_and_containment() {
+        let mut zone = get_example_zone();
+        let apex = DNSName::new("example.com.", None).unwrap();
+        let mail = DNSName::new("mail.example.com.", None).unwrap();
+        let node = zone.find(&mail).unwrap();
+        let node = node.read().unwrap();
+        assert_eq!(node.depth(), 3);
+        assert!(node.is_part_of(&apex));
+        assert!(node.is_part_of(&mail));
+        assert!(node.is_part_of(&DNSName::new(".", None).unwrap()));
+        assert!(!node.is_part_of(&DNSName::new("mail2.example.com.", None).unwrap()));
+        assert!(!node.is_part_of(&DNSName::new("example.net.", None).unwrap()));
+        assert!(node.contains(&DNSName::new("a.mail.example.com.", None).unwrap()));
+        assert!(!node.contains(&apex));
+        assert!(!node.contains(&DNSName::new("a.mail2.example.com.", None).unwrap()));
+
+        assert!(zone.is_own_domain(&mail));
+        let rr = ResourceRecord::new(
+            "a.b.c.example.com.",
+            DNSType::A,
+            DNSClass::IN,
+            3600,
+            Some(Box::new(DnsTypeA::new("192.0.2.1").unwrap())),
+        )
+        .unwrap();
+        let node = zone.insert_rr(rr).unwrap();
+        assert_eq!(node.read().unwrap().depth(), 5);
+        let ent = zone
+            .find(&DNSName::new("c.example.com.", None).unwrap())
+            .unwrap();
+        assert_eq!(ent.read().unwrap().depth(), 3);
+    }
+
+    // node_count count the nodes kept in the tree, the copies made when walking the edges
+    // are not counted.
+    fn node_count(node: &Arc<RwLock<SafeRBTreeNode>>) -> usize {
+        let children: Vec<Arc<RwLock<SafeRBTreeNode>>> = node
+            .read()
+            .unwrap()
+            .subtree
+            .read()
+            .unwrap()
+            .values()
+            .cloned()
+            .collect();
+        1 + children.iter().map(node_count).sum::<usize>()
+    }
+
+    #[test]
+    fn test_find_or_insert_compressed_edge() {
+        let mut zone = get_example_zone();
+        let count = node_count(&zone.domain_tree);
+        let name = |name: &str| DNSName::new(name, None).unwrap();
+        // the four empty names above the node are kept in one node
+        let node = zone
+            .find_or_insert(&name("a.b.c.d.e.example.com."))
+            .unwrap();
+        assert_eq!(node.read().unwrap().depth(), 7);
+        assert_eq!(
+            node.read().unwrap().get_name(),
+            name("a.b.c.d.e.example.com.")
+        );
+        assert_eq!(node_count(&zone.domain_tree), count + 2);
+        for ent in [
+            "e.example.com.",
+            "c.d.e.example.com.",
+            "b.c.d.e.example.com.",
+        ]
+        .iter()
+        {
+            let found = zone.find(&name(ent)).unwrap();
+            let found = found.read().unwrap();
+            assert_eq!(found.get_name(), name(ent));
+            assert_eq!(found.depth(), name(ent).label_count());
+            assert!(found.is_part_of(&name("e.example.com.")));
+            assert!(found.contains(&name("a.b.c.d.e.example.com.")));
+        }
+        assert_eq!(
+            zone.find(&name("x.c.d.e.example.com.")).unwrap_err(),
+            StorageError::DomainNotFoundError
+        );
+        assert_eq!(zone.find_children(&name("c.d.e.example.com.")).len(), 1);
+
+        // a branch splits the edge at the name it branches from
+        let branch = zone.find_or_insert(&name("x.c.d.e.example.com.")).unwrap();
+        assert_eq!(node_count(&zone.domain_tree), count + 4);
+        assert_eq!(
+            branch.read().unwrap().get_name(),
+            name("x.c.d.e.example.com.")
+        );
+        assert_eq!(zone.find_children(&name("c.d.e.example.com.")).len(), 2);
+        // a name inside the edge splits it and the node has no edge
+        let inside = zone.find_or_insert(&name("d.e.example.com.")).unwrap();
+        assert!(inside.read().unwrap().edge.is_empty());
+        assert_eq!(inside.read().unwrap().depth(), 4);
+        assert_eq!(node_count(&zone.domain_tree), count + 6);
+        for (existing, depth) in [("a.b.c.d.e.example.com.", 7), ("x.c.d.e.example.com.", 6)].iter()
+        {
+            let node = zone.find_or_insert(&name(existing)).unwrap();
+            assert_eq!(node.read().unwrap().depth(), *depth);
+            assert_eq!(node.read().unwrap().get_name(), name(existing));
+        }
+        assert_eq!(node_count(&zone.domain_tree), count + 6);
+        assert!(Arc::ptr_eq(
+            &zone
+                .find_or_insert(&name("a.b.c.d.e.example.com."))
+                .unwrap(),
+            &node
+        ));
+    }
+
+    #[test]
+    fn test_find_or_insert_label_depth() {
+        let mut zone = get_example_zone();
+        let count = node_count(&zone.domain_tree);
+        let mut deep = DNSName::new("example.com.", None).unwrap();
+        for _ in 0..120 {
+            deep.labels.insert(0, Label::from_str("a").unwrap());
+        }
+        // a chain of one node for each label would have 120 nodes
+        let node = zone.find_or_insert(&deep).unwrap();
+        assert_eq!(node.read().unwrap().depth(), 122);
+        assert_eq!(node.read().unwrap().get_name(), deep);
+        assert_eq!(node_count(&zone.domain_tree), count + 2);
+
+        for _ in 0..6 {
+            deep.labels.insert(0, Label::from_str("a").unwrap());
+        }
+        assert_eq!(deep.label_count(), MAX_LABEL_DEPTH + 1);
+        assert_eq!(
+            zone.find_or_insert(&deep).unwrap_err(),
+            StorageError::LabelDepthExceeded(deep.to_string(), MAX_LABEL_DEPTH)
+        );
+        assert_eq!(node_count(&zone.domain_tree), count + 2);
+    }
+
+    #[test]
+    fn test_update_zone_with_max_label_depth() {
+        let owner = format!("{}example.com.", "a.".repeat(70));
+        let text = format!(
+            "$ORIGIN example.com.\n\
+             @ 3600 IN SOA ns1 admin 1 7200 3600 1209600 300\n\
+             @ 3600 IN NS ns1\n\
+             {} 3600 IN TXT \"deep\"\n",
+            owner
+        );
+        let mut storage = SafeRBTreeStorage::default();
+        let err = storage
+            .update_zone_from_str(&text, None, &ZoneOption::default())
+            .unwrap_err();
+        match err.into_kind() {
+            OtterError::StorageError(StorageError::LabelDepthExceeded(name, limit)) => {
+                assert_eq!(name, owner);
+                assert_eq!(limit, DEFAULT_MAX_LABEL_DEPTH);
+            }
+            other => panic!("{:?}", other),
+        }
+        let option = ZoneOption {
+            max_label_depth: Some(80),
+            ..Default::default()
+        };
+        storage.update_zone_from_str(&text, None, &option).unwrap();
+        let owner = DNSName::new(&owner, None).unwrap();
+        assert!(storage.search_rrset(&owner, &DNSType::TXT).is_ok());
+        // the limit is never above the protocol limit
+        let option = ZoneOption {
+            max_label_depth: Some(200),
+            ..Default::default()
+        };
+        let rr =
+            ResourceRecord::new("a.example.com.", DNSType::TXT, DNSClass::IN, 3600, None).unwrap();
+        assert!(option.check_label_depth(&rr).is_ok());
+        let mut deep = rr.get_dname().clone();
+        while deep.label_count() <= MAX_LABEL_DEPTH {
+            deep.labels.insert(0, Label::from_str("a").unwrap());
+        }
+        let mut rr = rr;
+        rr.set_dname(deep.clone());
+        assert_eq!(
+            option.check_label_depth(&rr).unwrap_err(),
+            StorageError::LabelDepthExceeded(deep.to_string(), MAX_LABEL_DEPTH)
+        );
+    }
+
+    #[test]
+    fn test_find_or_insert_out_of_area() {
+        let zone = get_example_zone();
+        let apex = DNSName::new("example.com.", None).unwrap();
+        let mut subtree = SafeRBTreeStorage {
+            domain_tree: zone.find(&apex).unwrap(),
+            fast_cache: DashMap::new(),
+            zone_apexes: zone.zone_apexes.clone(),
+            hidden_zones: zone.hidden_zones.clone(),
+            zone_hooks: ZoneChangeHooks::default(),
+            negative_cache: zone.negative_cache.clone(),
+            #[cfg(feature = "testing")]
+            faults: zone.faults.clone(),
+        };
+        let www = DNSName::new("www.example.com.", None).unwrap();
+        assert_eq!(
+            subtree
+                .find_or_insert(&apex)
+                .unwrap()
+                .read()
+                .unwrap()
+                .depth(),
+            2
+        );
+        assert_eq!(
+            subtree
+                .find_or_insert(&www)
+                .unwrap()
+                .read()
+                .unwrap()
+                .depth(),
+            3
+        );
+        let name = DNSName::new("www.example.net.", None).unwrap();
+        assert_eq!(
+            subtree.find_or_insert(&name).unwrap_err(),
+            StorageError::ZoneOutOfArea(name.to_string(), apex.to_string())
+        );
+    }
+
     #[test]
     fn test_find_best_zone() {
         let zone = get_example_zone();
@@ -387,4 +1714,586 @@ mod test {
         let find_result = zone.find_best(&DNSName::new(".", None).unwrap());
         assert_eq!(find_result.is_some(), false);
     }
+
+    #[test]
+    fn test_find_children() {
+        let zone = SafeRBTreeStorage::new_zone_from_file("./test/catalog.zone", None).unwrap();
+        let name = DNSName::new("zones.catalog.example.", None).unwrap();
+        let mut children: Vec<String> = zone
+            .find_children(&name)
+            .iter()
+            .map(|node| node.read().unwrap().get_name().to_string())
+            .collect();
+        children.sort();
+        assert_eq!(
+            children,
+            vec![
+                "5960775ba382e7a4e09263fc06e7c00569b6a05c.zones.catalog.example.",
+                "b2f1d4a8.zones.catalog.example.",
+                "c7e1a2b3.zones.catalog.example.",
+            ]
+        );
+        let name = DNSName::new("notexist.catalog.example.", None).unwrap();
+        assert!(zone.find_children(&name).is_empty());
+    }
+
+    #[test]
+    fn test_zone_nodes() {
+        let mut zone = SafeRBTreeStorage::new_zone_from_file("./test/example.zone", None).unwrap();
+        zone.update_zone("./test/signed.zone", None).unwrap();
+        let apex = DNSName::new("example.com.", None).unwrap();
+        let names: Vec<String> = zone
+            .zone_nodes(&apex)
+            .iter()
+            .map(|node| node.read().unwrap().get_name().to_string())
+            .collect();
+        assert_eq!(names.len(), 7);
+        assert!(names.contains(&"mail3.example.com.".to_string()));
+        let apex = DNSName::new("signed.example.", None).unwrap();
+        assert_eq!(zone.zone_nodes(&apex).len(), 2);
+        assert_eq!(zone.zones().len(), 2);
+    }
+
+    #[test]
+    fn test_for_each_rrset() {
+        let zone = SafeRBTreeStorage::new_zone_from_file("./test/root.zone", None).unwrap();
+        let apex = DNSName::new(".", None).unwrap();
+        let mut expected = 0;
+        for node in zone.zone_nodes(&apex) {
+            let node = node.read().unwrap();
+            for rrset in node.rr_sets.iter() {
+                let rrset = rrset.value().read().unwrap();
+                expected += rrset.size() + rrset.signatures().len();
+            }
+        }
+        let (mut count, mut last): (usize, Option<DNSName>) = (0, None);
+        zone.for_each_rrset(&apex, |name, dtype, rrset| {
+            for rr in rrset.content().iter() {
+                assert_eq!(rr.get_dname(), name);
+                assert_eq!(rr.get_type(), dtype);
+            }
+            if let Some(last) = last.as_ref() {
+                assert_ne!(name.canonical_cmp(last), std::cmp::Ordering::Less);
+            }
+            last = Some(name.clone());
+            count += rrset.size() + rrset.signatures().len();
+        });
+        assert!(expected > 0);
+        assert_eq!(count, expected);
+
+        // the names inside a compressed edge are restored and the zones below are skipped
+        let mut zone = SafeRBTreeStorage::new_zone_from_str(
+            "$ORIGIN sub.example.com.\n\
+             @ 300 IN SOA ns admin 1 7200 3600 1209600 300\n\
+             www 300 IN A 192.0.2.2\n",
+            None,
+        )
+        .unwrap();
+        zone.update_zone_from_str(
+            "$ORIGIN example.com.\n\
+             @ 300 IN SOA ns admin 1 7200 3600 1209600 300\n\
+             a.b.c.www 300 IN A 192.0.2.1\n\
+             www 300 IN A 192.0.2.1\n",
+            None,
+            &ZoneOption::default(),
+        )
+        .unwrap();
+        let apex = DNSName::new("example.com.", None).unwrap();
+        let mut names = vec![];
+        zone.for_each_rrset(&apex, |name, dtype, _| {
+            names.push(format!("{} {}", name.to_string(), dtype))
+        });
+        assert_eq!(
+            names,
+            vec![
+                "example.com. SOA",
+                "www.example.com. A",
+                "a.b.c.www.example.com. A"
+            ]
+        );
+        assert_eq!(zone.transfer_records(&apex).unwrap().len(), 4);
+    }
+
+    #[test]
+    fn test_update_zone_with_ttl_clamp() {
+        let mut zone = SafeRBTreeStorage::default();
+        let option = ZoneOption {
+            min_ttl: Some(7200),
+            ..Default::default()
+        };
+        zone.update_zone_with_option("./test/example.zone", None, &option)
+            .unwrap();
+        let name = DNSName::new("ns.example.com.", None).unwrap();
+        let rrset = zone.search_rrset(&name, &DNSType::A).unwrap();
+        assert_eq!(rrset.read().unwrap().content()[0].get_ttl(), 7200);
+
+        let mut zone = SafeRBTreeStorage::default();
+        let option = ZoneOption {
+            max_ttl: Some(1800),
+            ..Default::default()
+        };
+        zone.update_zone_with_option("./test/example.zone", None, &option)
+            .unwrap();
+        let name = DNSName::new("example.com.", None).unwrap();
+        let rrset = zone.search_rrset(&name, &DNSType::SOA).unwrap();
+        assert_eq!(rrset.read().unwrap().content()[0].get_ttl(), 1800);
+    }
+
+    #[test]
+    fn test_update_zone_with_check_names() {
+        let file = "./test/check-names.zone";
+        let mut zone = SafeRBTreeStorage::default();
+        zone.update_zone(file, None).unwrap();
+        // underscore owner names are always allowed
+        let dmarc = DNSName::new("_dmarc.check.example.", None).unwrap();
+        assert!(zone.search_rrset(&dmarc, &DNSType::TXT).is_ok());
+
+        let option = ZoneOption {
+            check_names: CheckNames::Warn,
+            ..Default::default()
+        };
+        let mut zone = SafeRBTreeStorage::default();
+        zone.update_zone_with_option(file, None, &option).unwrap();
+
+        let option = ZoneOption {
+            check_names: CheckNames::Fail,
+            ..Default::default()
+        };
+        let mut zone = SafeRBTreeStorage::default();
+        match zone
+            .update_zone_with_option(file, None, &option)
+            .map_err(OtterError::into_kind)
+        {
+            Err(OtterError::StorageError(StorageError::InvalidHostName(owner, rtype, target))) => {
+                assert_eq!(owner, "check.example.");
+                assert_eq!(rtype, "MX");
+                assert_eq!(target, "mail_server.check.example.");
+            }
+            _ => panic!("expect invalid host name error"),
+        }
+    }
+
+    #[test]
+    fn test_update_zone_with_spf_to_txt() {
+        let apex = DNSName::new("spf.example.", None).unwrap();
+        let mail = DNSName::new("mail.spf.example.", None).unwrap();
+        // spf records are kept as is by default
+        let mut zone = SafeRBTreeStorage::default();
+        zone.update_zone("./test/spf.zone", None).unwrap();
+        let rrset = zone.search_rrset(&mail, &DNSType::SPF).unwrap();
+        assert_eq!(
+            rrset.read().unwrap().content()[0]
+                .get_data()
+                .as_ref()
+                .unwrap()
+                .to_string(),
+            "\"v=spf1 a\" \" -all\""
+        );
+        assert!(zone.search_rrset(&mail, &DNSType::TXT).is_err());
+        let rrset = zone.search_rrset(&apex, &DNSType::TXT).unwrap();
+        assert_eq!(rrset.read().unwrap().size(), 1);
+
+        let mut zone = SafeRBTreeStorage::default();
+        let option = ZoneOption {
+            spf_to_txt: true,
+            ..Default::default()
+        };
+        zone.update_zone_with_option("./test/spf.zone", None, &option)
+            .unwrap();
+        assert!(zone.search_rrset(&mail, &DNSType::SPF).is_ok());
+        let rrset = zone.search_rrset(&mail, &DNSType::TXT).unwrap();
+        let rrset = rrset.read().unwrap();
+        assert_eq!(rrset.size(), 1);
+        assert_eq!(rrset.content()[0].get_ttl(), 3600);
+        assert_eq!(
+            rrset.content()[0].get_data().as_ref().unwrap().to_string(),
+            "\"v=spf1 a\" \" -all\""
+        );
+        // the apex already has the same txt record
+        let rrset = zone.search_rrset(&apex, &DNSType::TXT).unwrap();
+        assert_eq!(rrset.read().unwrap().size(), 1);
+    }
+
+    #[test]
+    fn test_update_zone_with_default_ttl() {
+        let mut zone = SafeRBTreeStorage::default();
+        let err = zone.update_zone("./test/nottl.zone", None).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "file ./test/nottl.zone line 2: default domain ttl is not set"
+        );
+        assert_eq!(err.context().and_then(|context| context.line), Some(2));
+
+        let mut zone = SafeRBTreeStorage::default();
+        let option = ZoneOption {
+            default_ttl: Some(300),
+            ..Default::default()
+        };
+        zone.update_zone_with_option("./test/nottl.zone", None, &option)
+            .unwrap();
+        let name = DNSName::new("ns.example.org.", None).unwrap();
+        let rrset = zone.search_rrset(&name, &DNSType::A).unwrap();
+        assert_eq!(rrset.read().unwrap().content()[0].get_ttl(), 300);
+    }
+
+    #[test]
+    fn test_zone_change_hook() {
+        let changed = Arc::new(RwLock::new(vec![]));
+        let mut zone = SafeRBTreeStorage::default();
+        let hook_changed = changed.clone();
+        zone.clone()
+            .add_zone_change_hook(move |apex| hook_changed.write().unwrap().push(apex.clone()));
+        zone.update_zone("./test/example.zone", None).unwrap();
+        let option = ZoneOption {
+            serial_policy: SerialPolicy::Increment,
+            ..Default::default()
+        };
+        zone.reload_zone("./test/example.zone", None, &option)
+            .unwrap();
+        let apex = DNSName::new("example.com.", None).unwrap();
+        assert_eq!(*changed.read().unwrap(), vec![apex.clone(), apex]);
+    }
+
+    #[test]
+    fn test_update_zone_concurrent_reads() {
+        use crate::lookup::LookupResult;
+        use std::sync::atomic::{AtomicBool, Ordering};
+
+        let file = std::env::temp_dir().join(format!("otter_slow_{}.zone", std::process::id()));
+        let mut text = String::from(
+            "$ORIGIN slow.example.\n\
+             @ 300 IN SOA ns admin 1 7200 3600 1209600 300\n\
+             @ 300 IN NS ns\n\
+             ns 300 IN A 192.0.2.1\n",
+        );
+        for index in 0..2000 {
+            text.push_str(&format!("host{} 300 IN A 192.0.2.2\n", index));
+        }
+        std::fs::write(&file, text).unwrap();
+        let file = file.to_str().unwrap().to_owned();
+
+        let mut zone = SafeRBTreeStorage::default();
+        let loaded = Arc::new(AtomicBool::new(false));
+        let reader = {
+            let (zone, loaded) = (zone.clone(), loaded.clone());
+            std::thread::spawn(move || {
+                let apex = DNSName::new("slow.example.", None).unwrap();
+                let last = DNSName::new("host1999.slow.example.", None).unwrap();
+                let (mut absent, mut complete) = (0, 0);
+                loop {
+                    let done = loaded.load(Ordering::SeqCst);
+                    match zone.lookup_uncached(&last, DNSType::A) {
+                        LookupResult::OutOfZone => absent += 1,
+                        LookupResult::Answer(_) => {
+                            assert!(matches!(
+                                zone.lookup_uncached(&apex, DNSType::NS),
+                                LookupResult::Answer(_)
+                            ));
+                            complete += 1;
+                        }
+                        other => panic!("half loaded zone: {:?}", other),
+                    }
+                    if done {
+                        return (absent, complete);
+                    }
+                }
+            })
+        };
+        // slow down the load so the reader runs while the records are inserted
+        let mut option = LoadOption::new(ZoneOption::default())
+            .with_progress(100, |_: &LoadProgress| {
+                std::thread::sleep(std::time::Duration::from_millis(1))
+            });
+        zone.update_zone_with_progress(&file, None, &mut option)
+            .unwrap();
+        loaded.store(true, Ordering::SeqCst);
+        let (absent, complete) = reader.join().unwrap();
+        std::fs::remove_file(&file).unwrap();
+        assert!(absent > 0);
+        assert!(complete > 0);
+    }
+
+    #[test]
+    fn test_zone_hidden() {
+        let mut zone = SafeRBTreeStorage::default();
+        let sub = DNSName::new("sub.example.com.", None).unwrap();
+        let apex = DNSName::new("example.com.", None).unwrap();
+        // the flag is set before the zone is loaded
+        zone.set_zone_hidden(&sub, true);
+        zone.update_zone_from_str(
+            "$ORIGIN sub.example.com.\n\
+             @ 300 IN SOA ns admin 1 7200 3600 1209600 300\n\
+             @ 300 IN NS ns\n\
+             ns 300 IN A 192.0.2.53\n",
+            None,
+            &ZoneOption::default(),
+        )
+        .unwrap();
+        zone.update_zone_from_str(
+            "$ORIGIN example.com.\n\
+             @ 300 IN SOA ns admin 1 7200 3600 1209600 300\n\
+             www 300 IN A 192.0.2.1\n\
+             sub 300 IN NS ns.sub\n\
+             ns.sub 300 IN A 192.0.2.53\n",
+            None,
+            &ZoneOption::default(),
+        )
+        .unwrap();
+        assert!(zone.is_zone_hidden(&sub));
+        assert!(!zone.is_zone_hidden(&apex));
+        assert!(zone.clone().is_zone_hidden(&sub));
+        // the delegation and glue of the hidden zone are not in the parent transfer
+        let records = zone.transfer_records(&apex).unwrap();
+        assert!(records.iter().all(|rr| !rr.get_dname().is_part_of(&sub)));
+        assert_eq!(records.len(), 3);
+        assert_eq!(zone.hidden_zones(), vec![sub.clone()]);
+        zone.set_zone_hidden(&sub, false);
+        assert!(!zone.is_zone_hidden(&sub));
+    }
+
+    #[test]
+    fn test_update_zone_keeps_child_zones() {
+        let parent = "$ORIGIN example.com.\n\
+                      @ 300 IN SOA ns admin 1 7200 3600 1209600 300\n\
+                      www 300 IN A 192.0.2.1\n\
+                      sub 300 IN NS ns.sub\n\
+                      sub 300 IN DS 1657 8 2 9D6BAE62219231C99FAA479716B6E4619330CE8206670AEA6C1673A055DC3AF2\n\
+                      ns.sub 300 IN A 192.0.2.53\n";
+        let child = "$ORIGIN sub.example.com.\n\
+                     @ 300 IN SOA ns admin 1 7200 3600 1209600 300\n\
+                     @ 300 IN NS ns\n\
+                     ns 300 IN A 192.0.2.53\n\
+                     www 300 IN A 192.0.2.2\n";
+        let option = ZoneOption::default();
+        let sub = DNSName::new("sub.example.com.", None).unwrap();
+        let www = DNSName::new("www.sub.example.com.", None).unwrap();
+        // the child zone is loaded first, then the parent zone and its reload
+        let mut zone = SafeRBTreeStorage::new_zone_from_str(child, None).unwrap();
+        for _ in 0..2 {
+            zone.update_zone_from_str(parent, None, &option).unwrap();
+            assert_eq!(zone.zones().len(), 2);
+            assert!(zone.search_rrset(&www, &DNSType::A).is_ok());
+            assert!(zone.search_rrset(&sub, &DNSType::SOA).is_ok());
+            assert!(zone.search_rrset(&sub, &DNSType::DS).is_ok());
+            assert_eq!(zone.find(&www).unwrap().read().unwrap().get_name(), www);
+        }
+        // the DS rrset belongs to the parent zone, the child zone keeps it
+        zone.update_zone_from_str(child, None, &option).unwrap();
+        assert!(zone.search_rrset(&sub, &DNSType::DS).is_ok());
+        let unsigned = parent
+            .lines()
+            .filter(|line| !line.contains(" DS "))
+            .collect::<Vec<_>>();
+        zone.update_zone_from_str(&unsigned.join("\n"), None, &option)
+            .unwrap();
+        assert!(zone.search_rrset(&sub, &DNSType::DS).is_err());
+        assert!(zone.search_rrset(&www, &DNSType::A).is_ok());
+    }
+
+    #[test]
+    fn test_update_zone_with_progress() {
+        let mut reports = vec![];
+        let mut zone = SafeRBTreeStorage::default();
+        let mut option = LoadOption::new(ZoneOption::default())
+            .with_progress(5, |progress: &LoadProgress| reports.push(*progress));
+        zone.update_zone_with_progress("./test/example.zone", None, &mut option)
+            .unwrap();
+        drop(option);
+        let total = reports.last().unwrap().records;
+        assert!(total > 5);
+        for (index, progress) in reports.iter().enumerate().take(reports.len() - 1) {
+            assert_eq!(progress.records, (index + 1) * 5);
+        }
+        assert!(reports.windows(2).all(|w| w[0].memory < w[1].memory));
+
+        let mut zone = SafeRBTreeStorage::default();
+        let option = ZoneOption {
+            max_records: Some(total),
+            ..Default::default()
+        };
+        assert!(zone
+            .update_zone_with_option("./test/example.zone", None, &option)
+            .is_ok());
+
+        let mut zone = SafeRBTreeStorage::default();
+        let option = ZoneOption {
+            max_records: Some(total - 1),
+            ..Default::default()
+        };
+        match zone
+            .update_zone_with_option("./test/example.zone", None, &option)
+            .map_err(OtterError::into_kind)
+        {
+            Err(OtterError::StorageError(StorageError::ZoneLoadLimitExceeded(_, limit, max))) => {
+                assert_eq!(limit, "max_records");
+                assert_eq!(max, total - 1);
+            }
+            _ => assert!(false, "expect max_records limit error"),
+        }
+
+        let mut zone = SafeRBTreeStorage::default();
+        let option = ZoneOption {
+            max_memory: Some(1024),
+            ..Default::default()
+        };
+        match zone
+            .update_zone_with_option("./test/example.zone", None, &option)
+            .map_err(OtterError::into_kind)
+        {
+            Err(OtterError::StorageError(StorageError::ZoneLoadLimitExceeded(_, limit, _))) => {
+                assert_eq!(limit, "max_memory")
+            }
+            _ => assert!(false, "expect max_memory limit error"),
+        }
+    }
+
+    #[test]
+    fn test_check_ptr_owner() {
+        let valid = vec![
+            "1.2.0.192.in-addr.arpa.",
+            "1.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.1.0.0.0.0.0.0.0.8.b.d.0.1.0.0.2.ip6.arpa.",
+            // names out of the reverse zones are not checked
+            "zone1.zones.catalog.example.",
+            "_http._tcp.example.com.",
+        ];
+        for name in valid {
+            let rr = ResourceRecord::new(name, DNSType::PTR, DNSClass::IN, 3600, None).unwrap();
+            assert!(check_ptr_owner(&rr, CheckNames::Fail).is_ok());
+        }
+        let invalid = vec![
+            "2.0.192.in-addr.arpa.",
+            "300.2.0.192.in-addr.arpa.",
+            "10.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.1.0.0.0.0.0.0.0.8.b.d.0.1.0.0.2.ip6.arpa.",
+        ];
+        for name in invalid {
+            let rr = ResourceRecord::new(name, DNSType::PTR, DNSClass::IN, 3600, None).unwrap();
+            assert!(check_ptr_owner(&rr, CheckNames::Fail).is_err());
+            assert!(check_ptr_owner(&rr, CheckNames::Warn).is_ok());
+            assert!(check_ptr_owner(&rr, CheckNames::Ignore).is_ok());
+        }
+        let rr =
+            ResourceRecord::new("www.example.com.", DNSType::A, DNSClass::IN, 3600, None).unwrap();
+        assert!(check_ptr_owner(&rr, CheckNames::Fail).is_ok());
+    }
+
+    #[test]
+    fn test_new_zone_from_str() {
+        let storage = SafeRBTreeStorage::new_zone_from_str(
+            "example.org. 300 IN SOA ns.example.org. admin.example.org. 1 7200 3600 1209600 300\n\
+             www.example.org. 300 IN A 192.0.2.1\n",
+            None,
+        )
+        .unwrap();
+        let apex = DNSName::new("example.org.", None).unwrap();
+        assert_eq!(storage.zones(), vec![apex.clone()]);
+        assert_eq!(storage.get_zone_soa(&apex).unwrap().serial(), 1);
+        let www = DNSName::new("www.example.org.", None).unwrap();
+        assert!(storage.clone().search_rrset(&www, &DNSType::A).is_ok());
+        assert!(
+            SafeRBTreeStorage::new_zone_from_str("www.example.org. 300 IN A 192.0.2.1", None)
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_apex_cname() {
+        let result = SafeRBTreeStorage::new_zone_from_str(
+            "example.org. 300 IN SOA ns.example.org. admin.example.org. 1 7200 3600 1209600 300\n\
+             example.org. 300 IN CNAME www.example.net.\n",
+            None,
+        );
+        let err = result.unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "file <text> line 2: CNAME RR is not allowed at the zone apex example.org."
+        );
+        match err.into_kind() {
+            OtterError::StorageError(StorageError::ApexCNAMEError(apex)) => {
+                assert_eq!(apex, "example.org.")
+            }
+            other => panic!("{:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_find_zone() {
+        let zone = get_example_zone();
+        let apex = DNSName::new("example.com.", None).unwrap();
+        assert_eq!(zone.find_zone(&apex), Some(apex.clone()));
+        assert_eq!(
+            zone.find_zone(&DNSName::new("www.example.com.", None).unwrap()),
+            Some(apex)
+        );
+        assert_eq!(
+            zone.find_zone(&DNSName::new("example.org.", None).unwrap()),
+            None
+        );
+        assert_eq!(zone.find_zone(&DNSName::new("com.", None).unwrap()), None);
+    }
+
+    #[test]
+    fn test_get_zone_soa() {
+        let zone = get_example_zone();
+        let soa = zone
+            .get_zone_soa(&DNSName::new("example.com.", None).unwrap())
+            .unwrap();
+        assert_eq!(soa.serial(), 2020091025);
+        assert_eq!(soa.expire(), 1209600);
+        assert!(zone
+            .get_zone_soa(&DNSName::new("ns.example.com.", None).unwrap())
+            .is_err());
+    }
+
+    #[test]
+    fn test_reload_zone_with_serial_policy() {
+        let mut zone = get_example_zone();
+        let apex = DNSName::new("example.com.", None).unwrap();
+        let result = zone.reload_zone("./test/example.zone", None, &ZoneOption::default());
+        assert_eq!(result.is_err(), true);
+        assert_eq!(zone.get_zone_soa(&apex).unwrap().serial(), 2020091025);
+
+        let option = ZoneOption {
+            serial_policy: SerialPolicy::Increment,
+            ..Default::default()
+        };
+        zone.reload_zone("./test/example.zone", None, &option)
+            .unwrap();
+        assert_eq!(zone.get_zone_soa(&apex).unwrap().serial(), 2020091026);
+        // records still served after reload
+        let name = DNSName::new("ns.example.com.", None).unwrap();
+        assert_eq!(zone.search_rrset(&name, &DNSType::A).is_ok(), true);
+        let node = zone.find(&name).unwrap();
+        assert_eq!(node.read().unwrap().find_soa().is_ok(), true);
+    }
+
+    #[test]
+    fn test_replace_zone() {
+        let mut zone = get_example_zone();
+        let apex = DNSName::new("example.com.", None).unwrap();
+        let name = DNSName::new("ns.example.com.", None).unwrap();
+        let mut records = zone.transfer_records(&apex).unwrap();
+        records.pop();
+        // the served zone is kept when the records fail to load
+        let mut bad = records.clone();
+        bad.push(records[0].clone());
+        assert!(zone.replace_zone(bad, &ZoneOption::default()).is_err());
+        assert!(zone
+            .replace_zone(records[1..].to_vec(), &ZoneOption::default())
+            .is_err());
+        assert!(zone.search_rrset(&name, &DNSType::A).is_ok());
+
+        assert!(records.iter().all(|rr| rr.provenance() == Provenance::File));
+        let records = records
+            .into_iter()
+            .filter(|rr| !(rr.get_dname() == &name && rr.get_type() == DNSType::A))
+            .collect();
+        zone.replace_zone(records, &ZoneOption::default()).unwrap();
+        assert!(zone.search_rrset(&name, &DNSType::A).is_err());
+        assert_eq!(zone.get_zone_soa(&apex).unwrap().serial(), 2020091025);
+        assert!(zone
+            .transfer_records(&apex)
+            .unwrap()
+            .iter()
+            .all(|rr| rr.provenance() == Provenance::Transfer));
+    }
 }
@@ -93,7 +93,7 @@ impl UnSafeRBTreeStorage {
             let subtree = temp
                 .subtree
                 .get_or_insert(Rc::new(RefCell::new(RBTree::new())));
-            let result = subtree.borrow().get(&label.clone()).cloned();
+            let result = subtree.borrow().get(label).cloned();
 
             if let Some(node) = result {
                 if labels_count == 0 {
@@ -143,22 +143,15 @@ impl UnSafeRBTreeStorage {
         let node = node.borrow();
         let result = match node.rr_sets.get(&dtype) {
             Some(rrset) => Ok(rrset.clone()),
-            None => Err(StorageError::DNSTypeNotFoundError(
-                dname.to_string(),
-                dtype.to_string(),
-            )),
+            None => Err(StorageError::DNSTypeNotFoundError(u16::from(dtype))),
         };
         result
     }
 
     pub fn delete_rrset(&mut self, dtype: DNSType) -> Result<Rc<RefCell<RRSet>>, StorageError> {
-        let name = self.0.borrow().get_name();
         match self.0.borrow_mut().rr_sets.remove(&dtype) {
             Some(rrset) => Ok(rrset),
-            None => Err(StorageError::DNSTypeNotFoundError(
-                name.to_string(),
-                dtype.to_string(),
-            )),
+            None => Err(StorageError::DNSTypeNotFoundError(u16::from(dtype))),
         }
     }
 
@@ -175,7 +168,7 @@ impl UnSafeRBTreeStorage {
             }
             let temp = current.clone();
             let subtree = temp.borrow().subtree.as_ref().unwrap().clone();
-            let result = subtree.borrow().get(&label.clone()).cloned();
+            let result = subtree.borrow().get(label).cloned();
             /// subtree exist and has label node
             if let Some(node) = result {
                 if labels_count == 0 {
@@ -216,10 +209,10 @@ impl UnSafeRBTreeStorage {
 
             let subtree = temp.borrow_mut().subtree.take();
             if subtree.is_none() {
-                return Err(StorageError::DomainNotFoundError(name.to_string()));
+                return Err(StorageError::DomainNotFoundError);
             }
             let subtree = subtree.unwrap().clone();
-            let result = subtree.borrow().get(&label.clone()).cloned();
+            let result = subtree.borrow().get(label).cloned();
             /// subtree exist and has label node
             if let Some(node) = result {
                 temp.borrow_mut().subtree = Some(subtree);
@@ -236,7 +229,7 @@ impl UnSafeRBTreeStorage {
                 return Ok(node);
             }
             /// not found in subtree
-            return Err(StorageError::DomainNotFoundError(name.to_string()));
+            return Err(StorageError::DomainNotFoundError);
         }
         Ok(current)
     }
@@ -382,10 +375,7 @@ impl RBTreeNode {
     pub fn find_rrset(&self, dtype: DNSType) -> Result<Rc<RefCell<RRSet>>, StorageError> {
         match self.rr_sets.get(&dtype) {
             Some(rrset) => Ok(rrset.clone()),
-            None => Err(StorageError::DNSTypeNotFoundError(
-                self.get_name().to_string(),
-                dtype.to_string(),
-            )),
+            None => Err(StorageError::DNSTypeNotFoundError(u16::from(dtype))),
         }
     }
 
@@ -719,7 +709,7 @@ mod storage {
         assert_eq!(soa.content()[0].get_dname().to_string(), ".");
         assert_eq!(
             soa.content()[0].get_data().as_ref().unwrap().to_string(),
-            "a.root-servers.net. nstld.verisign-grs.com. ( 2021033102 1800 900 604800 86400 )"
+            "a.root-servers.net. nstld.verisign-grs.com. 2021033102 1800 900 604800 86400"
         );
         assert_eq!(
             soa.content()[0]
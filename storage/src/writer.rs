@@ -0,0 +1,223 @@
+use crate::storage::SafeRBTreeStorage;
+use dnsproto::dnsname::DNSName;
+use dnsproto::meta::{DNSType, Provenance, ResourceRecord};
+use otterlib::errors::{OtterError, StorageError};
+use std::fs::File;
+use std::io::{BufRead, BufReader, ErrorKind, Write};
+
+// the first line of the zone file written after a zone transfer
+const TRANSFER_HEADER: &str = "; otter transfer";
+// the first line of the journal file holding the records added by dynamic updates
+const JOURNAL_HEADER: &str = "; otter journal";
+
+/// TransferHeader is the serial and the refresh time(unix seconds) of a zone written
+/// after a zone transfer, kept in the comment line at the top of the zone file.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TransferHeader {
+    pub serial: u32,
+    pub refreshed: u64,
+}
+
+impl TransferHeader {
+    fn to_line(self) -> String {
+        format!(
+            "{} serial {} refreshed {}",
+            TRANSFER_HEADER, self.serial, self.refreshed
+        )
+    }
+
+    fn from_line(line: &str) -> Option<TransferHeader> {
+        let fields: Vec<&str> = line
+            .strip_prefix(TRANSFER_HEADER)?
+            .split_whitespace()
+            .collect();
+        match fields.as_slice() {
+            ["serial", serial, "refreshed", refreshed] => Some(TransferHeader {
+                serial: serial.parse().ok()?,
+                refreshed: refreshed.parse().ok()?,
+            }),
+            _ => None,
+        }
+    }
+}
+
+/// write_zone_file write all records of the zone to the file in the zone file format with
+/// the transfer header, the file is written to a temporary file first and then renamed so
+/// a failed write never leaves a truncated zone file.
+pub fn write_zone_file(
+    storage: &SafeRBTreeStorage,
+    apex: &DNSName,
+    path: &str,
+    refreshed: u64,
+) -> Result<(), OtterError> {
+    write_zone(storage, apex, path, refreshed, false)
+}
+
+/// write_zone_file_with_journal write the zone like write_zone_file, but the records added
+/// by dynamic updates go to the journal file next to it. the journal is removed when the
+/// zone has no dynamic records.
+pub fn write_zone_file_with_journal(
+    storage: &SafeRBTreeStorage,
+    apex: &DNSName,
+    path: &str,
+    refreshed: u64,
+) -> Result<(), OtterError> {
+    write_zone(storage, apex, path, refreshed, true)
+}
+
+/// journal_path return the path of the journal file of the zone file.
+pub fn journal_path(path: &str) -> String {
+    format!("{}.jnl", path)
+}
+
+fn write_zone(
+    storage: &SafeRBTreeStorage,
+    apex: &DNSName,
+    path: &str,
+    refreshed: u64,
+    journal: bool,
+) -> Result<(), OtterError> {
+    let mut records = storage.transfer_records(apex)?;
+    // the closing soa of the transfer
+    records.pop();
+    let header = TransferHeader {
+        serial: storage.get_zone_soa(apex)?.serial(),
+        refreshed,
+    };
+    // the soa always stays in the zone file even if an update changed it
+    let (dynamic, records): (Vec<ResourceRecord>, Vec<ResourceRecord>) =
+        records.into_iter().partition(|rr| {
+            journal && rr.provenance() == Provenance::Update && rr.get_type() != DNSType::SOA
+        });
+    write_records(path, &header.to_line(), &records)?;
+    if !journal {
+        return Ok(());
+    }
+    let journal = journal_path(path);
+    if dynamic.is_empty() {
+        return match std::fs::remove_file(&journal) {
+            Err(err) if err.kind() != ErrorKind::NotFound => {
+                Err(StorageError::ZoneWriteError(journal, err.to_string()).into())
+            }
+            _ => Ok(()),
+        };
+    }
+    let journal_header = format!("{} serial {}", JOURNAL_HEADER, header.serial);
+    write_records(&journal, &journal_header, &dynamic)
+}
+
+// write_records write the header line and the records to a temporary file and then rename it.
+fn write_records(path: &str, header: &str, records: &[ResourceRecord]) -> Result<(), OtterError> {
+    let io_error =
+        |err: std::io::Error| StorageError::ZoneWriteError(path.to_owned(), err.to_string());
+    let temp_path = format!("{}.tmp", path);
+    {
+        let mut file = File::create(&temp_path).map_err(io_error)?;
+        writeln!(file, "{}", header).map_err(io_error)?;
+        for rr in records.iter() {
+            writeln!(file, "{}", rr).map_err(io_error)?;
+        }
+        file.sync_all().map_err(io_error)?;
+    }
+    std::fs::rename(&temp_path, path).map_err(io_error)?;
+    Ok(())
+}
+
+/// read_transfer_header return the transfer header of the zone file, or None if the file
+/// is not written after a zone transfer.
+pub fn read_transfer_header(path: &str) -> Option<TransferHeader> {
+    let file = File::open(path).ok()?;
+    let line = BufReader::new(file).lines().next()?.ok()?;
+    TransferHeader::from_line(line.as_str())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use dnsproto::meta::DNSClass;
+    use dnsproto::qtype::DnsTypeTXT;
+
+    #[test]
+    fn test_write_zone_file() {
+        let storage = SafeRBTreeStorage::new_zone_from_file("./test/example.zone", None).unwrap();
+        let apex = DNSName::new("example.com.", None).unwrap();
+        let path = std::env::temp_dir().join("otter_write_zone_file.zone");
+        let path = path.to_str().unwrap();
+        write_zone_file(&storage, &apex, path, 1600000000).unwrap();
+        assert_eq!(
+            read_transfer_header(path),
+            Some(TransferHeader {
+                serial: 2020091025,
+                refreshed: 1600000000,
+            })
+        );
+        let records = |storage: &SafeRBTreeStorage| {
+            let mut records: Vec<String> = storage
+                .transfer_records(&apex)
+                .unwrap()
+                .iter()
+                .map(|rr| rr.to_string())
+                .collect();
+            records.sort();
+            records
+        };
+        let written = SafeRBTreeStorage::new_zone_from_file(path, None).unwrap();
+        assert_eq!(records(&written), records(&storage));
+        std::fs::remove_file(path).unwrap();
+
+        assert_eq!(read_transfer_header("./test/example.zone"), None);
+        assert_eq!(
+            TransferHeader::from_line("; otter transfer serial 1 refreshed x"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_write_zone_file_with_journal() {
+        let mut storage =
+            SafeRBTreeStorage::new_zone_from_file("./test/example.zone", None).unwrap();
+        let apex = DNSName::new("example.com.", None).unwrap();
+        let path = std::env::temp_dir().join("otter_write_zone_journal.zone");
+        let path = path.to_str().unwrap();
+        let journal = journal_path(path);
+        let mut rr = ResourceRecord::new(
+            "dyn.example.com.",
+            DNSType::TXT,
+            DNSClass::IN,
+            300,
+            Some(Box::new(DnsTypeTXT::new("added by update").unwrap())),
+        )
+        .unwrap();
+        rr.set_provenance(Provenance::Update);
+        storage.insert_rr(rr.clone()).unwrap();
+
+        // without journal the dynamic records stay in the zone file
+        write_zone_file(&storage, &apex, path, 1600000000).unwrap();
+        let mut written = SafeRBTreeStorage::new_zone_from_file(path, None).unwrap();
+        assert!(written.search_rrset(rr.get_dname(), &DNSType::TXT).is_ok());
+        assert!(!std::path::Path::new(&journal).exists());
+
+        write_zone_file_with_journal(&storage, &apex, path, 1600000000).unwrap();
+        let mut written = SafeRBTreeStorage::new_zone_from_file(path, None).unwrap();
+        assert!(written.search_rrset(rr.get_dname(), &DNSType::TXT).is_err());
+        assert!(written.search_rrset(&apex, &DNSType::SOA).is_ok());
+        let lines: Vec<String> = std::fs::read_to_string(&journal)
+            .unwrap()
+            .lines()
+            .map(|line| line.to_owned())
+            .collect();
+        assert_eq!(
+            lines,
+            vec![
+                "; otter journal serial 2020091025".to_owned(),
+                rr.to_string()
+            ]
+        );
+
+        // the journal is removed when no dynamic record is left
+        let storage = SafeRBTreeStorage::new_zone_from_file("./test/example.zone", None).unwrap();
+        write_zone_file_with_journal(&storage, &apex, path, 1600000000).unwrap();
+        assert!(!std::path::Path::new(&journal).exists());
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,201 @@
+use crate::storage::SafeRBTreeStorage;
+use dnsproto::dnsname::DNSName;
+use dnsproto::meta::{DNSType, ResourceRecord};
+use dnsproto::qtype::{
+    DnsTypeRRSIG, DnsTypeZONEMD, ZONEMD_HASH_SHA384, ZONEMD_HASH_SHA512, ZONEMD_SCHEME_SIMPLE,
+};
+use otterlib::errors::StorageError;
+use ring::digest;
+use std::collections::HashMap;
+
+fn digest_error(apex: &DNSName, reason: &str) -> StorageError {
+    StorageError::ZoneDigestError(apex.to_string(), reason.to_owned())
+}
+
+// is_apex_zonemd return true for the apex ZONEMD records and the signatures covering them,
+// which are excluded from the zone digest.
+fn is_apex_zonemd(rr: &ResourceRecord) -> bool {
+    match rr.get_type() {
+        DNSType::ZONEMD => true,
+        DNSType::RRSIG => rr
+            .get_data()
+            .as_ref()
+            .and_then(|data| data.as_any().downcast_ref::<DnsTypeRRSIG>())
+            .map(|rrsig| rrsig.type_covered() == u16::from(DNSType::ZONEMD))
+            .unwrap_or(false),
+        _ => false,
+    }
+}
+
+/// zone_digest calculate the SIMPLE scheme digest(rfc8976 section 3.3) of the zone: the
+/// canonical wire format of all records are hashed in canonical order, the apex ZONEMD
+/// records and their signatures are excluded.
+pub fn zone_digest(
+    storage: &SafeRBTreeStorage,
+    apex: &DNSName,
+    hash_algorithm: u8,
+) -> Result<Vec<u8>, StorageError> {
+    let algorithm = match hash_algorithm {
+        ZONEMD_HASH_SHA384 => &digest::SHA384,
+        ZONEMD_HASH_SHA512 => &digest::SHA512,
+        _ => return Err(digest_error(apex, "unsupported hash algorithm")),
+    };
+    // owner, type, canonical wire format and the rdata offset in it
+    let mut records: Vec<(DNSName, u16, Vec<u8>, usize)> = vec![];
+    let mut error = None;
+    // the delegations and glue of the zones below are part of the zone
+    storage.for_each_rrset(apex, |name, _, rrset| {
+        let at_apex = name == apex;
+        for rr in rrset.content().iter().chain(rrset.signatures().iter()) {
+            if at_apex && is_apex_zonemd(rr) {
+                continue;
+            }
+            match rr.encode_canonical() {
+                Ok(wire) => {
+                    let offset = name.to_binary(None).len() + 10;
+                    records.push((name.clone(), u16::from(rr.get_type()), wire, offset));
+                }
+                Err(err) => {
+                    error.get_or_insert_with(|| err.to_string());
+                }
+            }
+        }
+    });
+    if let Some(err) = error {
+        return Err(digest_error(apex, &err));
+    }
+    records.sort_by(|left, right| {
+        left.0
+            .canonical_cmp(&right.0)
+            .then(left.1.cmp(&right.1))
+            .then_with(|| left.2[left.3..].cmp(&right.2[right.3..]))
+    });
+    records.dedup_by(|left, right| left.2 == right.2);
+    let mut context = digest::Context::new(algorithm);
+    for (_, _, wire, _) in records.iter() {
+        context.update(wire.as_slice());
+    }
+    Ok(context.finish().as_ref().to_vec())
+}
+
+/// verify_zonemd verify the zone with the apex ZONEMD records(rfc8976 section 4), it
+/// succeeds when one record with the SIMPLE scheme and a supported hash algorithm has
+/// the zone soa serial and the same digest as calculated.
+pub fn verify_zonemd(storage: &SafeRBTreeStorage, apex: &DNSName) -> Result<(), StorageError> {
+    let serial = storage.get_zone_soa(apex)?.serial();
+    let rrset = storage
+        .find(apex)?
+        .read()
+        .unwrap()
+        .find_rrset(DNSType::ZONEMD)
+        .map_err(|_| digest_error(apex, "no ZONEMD record at apex"))?;
+    let zonemds: Vec<DnsTypeZONEMD> = rrset
+        .read()
+        .unwrap()
+        .content()
+        .iter()
+        .filter_map(|rr| {
+            rr.get_data()
+                .as_ref()
+                .and_then(|data| data.as_any().downcast_ref::<DnsTypeZONEMD>())
+                .cloned()
+        })
+        .collect();
+    let mut digests: HashMap<u8, Vec<u8>> = HashMap::new();
+    let mut reason = "no ZONEMD record with supported scheme and hash algorithm";
+    for zonemd in zonemds.iter() {
+        if zonemd.scheme() != ZONEMD_SCHEME_SIMPLE
+            || (zonemd.hash_algorithm() != ZONEMD_HASH_SHA384
+                && zonemd.hash_algorithm() != ZONEMD_HASH_SHA512)
+        {
+            continue;
+        }
+        let duplicated = zonemds
+            .iter()
+            .filter(|other| {
+                other.scheme() == zonemd.scheme()
+                    && other.hash_algorithm() == zonemd.hash_algorithm()
+            })
+            .count()
+            > 1;
+        if duplicated {
+            return Err(digest_error(
+                apex,
+                "multiple ZONEMD records with the same scheme and hash algorithm",
+            ));
+        }
+        if zonemd.serial() != serial {
+            reason = "ZONEMD serial does not match the soa serial";
+            continue;
+        }
+        if !digests.contains_key(&zonemd.hash_algorithm()) {
+            let digest = zone_digest(storage, apex, zonemd.hash_algorithm())?;
+            digests.insert(zonemd.hash_algorithm(), digest);
+        }
+        if digests[&zonemd.hash_algorithm()].as_slice() == zonemd.digest() {
+            return Ok(());
+        }
+        reason = "zone digest mismatch";
+    }
+    Err(digest_error(apex, reason))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::storage::ZoneOption;
+    use otterlib::setting::ZonemdVerify;
+
+    const DIGEST: &str = "c68090d90a7aed716bc459f9340e3d7c1370d4d24b7e2fc3a1ddc0b9a87153b9a9713b3c9ae5cc27777f98b8e730044c";
+
+    #[test]
+    fn test_zone_digest() {
+        let storage = SafeRBTreeStorage::new_zone_from_file("./test/zonemd.zone", None).unwrap();
+        let apex = DNSName::new("example.", None).unwrap();
+        let digest = zone_digest(&storage, &apex, ZONEMD_HASH_SHA384).unwrap();
+        let digest: String = digest.iter().map(|byte| format!("{:02x}", byte)).collect();
+        assert_eq!(digest, DIGEST);
+        assert_eq!(
+            zone_digest(&storage, &apex, ZONEMD_HASH_SHA512)
+                .unwrap()
+                .len(),
+            64
+        );
+        assert!(zone_digest(&storage, &apex, 0).is_err());
+        assert!(verify_zonemd(&storage, &apex).is_ok());
+
+        let storage =
+            SafeRBTreeStorage::new_zone_from_file("./test/zonemd-bad.zone", None).unwrap();
+        assert_eq!(
+            verify_zonemd(&storage, &apex),
+            Err(StorageError::ZoneDigestError(
+                "example.".to_owned(),
+                "zone digest mismatch".to_owned()
+            ))
+        );
+        let storage = SafeRBTreeStorage::new_zone_from_file("./test/example.zone", None).unwrap();
+        let apex = DNSName::new("example.com.", None).unwrap();
+        assert!(verify_zonemd(&storage, &apex).is_err(), "no zonemd record");
+    }
+
+    #[test]
+    fn test_load_zone_verify_zonemd() {
+        let mut option = ZoneOption {
+            verify_zonemd: ZonemdVerify::Reject,
+            ..Default::default()
+        };
+        let mut storage = SafeRBTreeStorage::default();
+        assert!(storage
+            .update_zone_with_option("./test/zonemd.zone", None, &option)
+            .is_ok());
+        let mut storage = SafeRBTreeStorage::default();
+        assert!(storage
+            .update_zone_with_option("./test/zonemd-bad.zone", None, &option)
+            .is_err());
+        option.verify_zonemd = ZonemdVerify::Warn;
+        let mut storage = SafeRBTreeStorage::default();
+        assert!(storage
+            .update_zone_with_option("./test/zonemd-bad.zone", None, &option)
+            .is_ok());
+    }
+}
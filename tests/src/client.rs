@@ -0,0 +1,55 @@
+use std::io;
+use std::net::SocketAddr;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpStream, UdpSocket};
+
+/// QUERY_TIMEOUT is how long the fake client wait for a response.
+pub const QUERY_TIMEOUT: Duration = Duration::from_secs(2);
+
+fn timed_out() -> io::Error {
+    io::Error::new(io::ErrorKind::TimedOut, "no response from server")
+}
+
+/// send_udp_query send the raw query to the server and return the raw response,
+/// the error kind is TimedOut when the server does not answer in time.
+pub async fn send_udp_query(server: SocketAddr, query: &[u8]) -> io::Result<Vec<u8>> {
+    send_udp_query_with_timeout(server, query, QUERY_TIMEOUT).await
+}
+
+pub async fn send_udp_query_with_timeout(
+    server: SocketAddr,
+    query: &[u8],
+    timeout: Duration,
+) -> io::Result<Vec<u8>> {
+    let local = match server {
+        SocketAddr::V4(_) => "127.0.0.1:0",
+        SocketAddr::V6(_) => "[::1]:0",
+    };
+    let socket = UdpSocket::bind(local).await?;
+    socket.send_to(query, server).await?;
+    let mut buffer = vec![0u8; 65535];
+    let (size, _) = tokio::time::timeout(timeout, socket.recv_from(&mut buffer))
+        .await
+        .map_err(|_| timed_out())??;
+    buffer.truncate(size);
+    Ok(buffer)
+}
+
+/// send_tcp_query send the raw query with the length prefix to the server and return the
+/// raw response without the prefix.
+pub async fn send_tcp_query(server: SocketAddr, query: &[u8]) -> io::Result<Vec<u8>> {
+    let exchange = async {
+        let mut stream = TcpStream::connect(server).await?;
+        let mut framed = (query.len() as u16).to_be_bytes().to_vec();
+        framed.extend_from_slice(query);
+        stream.write_all(&framed).await?;
+        let size = stream.read_u16().await?;
+        let mut response = vec![0u8; size as usize];
+        stream.read_exact(&mut response).await?;
+        Ok(response)
+    };
+    tokio::time::timeout(QUERY_TIMEOUT, exchange)
+        .await
+        .map_err(|_| timed_out())?
+}
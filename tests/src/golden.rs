@@ -0,0 +1,69 @@
+use dnsproto::message::Message;
+use std::path::PathBuf;
+
+/// UPDATE_GOLDEN is the environment variable to rewrite the golden files with the
+/// current responses instead of comparing them.
+pub const UPDATE_GOLDEN: &str = "OTTER_UPDATE_GOLDEN";
+
+/// render_response render the response as text without the message id, records of each
+/// section are sorted so the output does not depend on the storage order.
+pub fn render_response(response: &Message) -> String {
+    let header = &response.header;
+    let mut lines = vec![format!(
+        ";; opcode: {:?}, rcode: {:?}, aa: {}, tc: {}",
+        header.op_code(),
+        header.rcode(),
+        header.aa(),
+        header.tc()
+    )];
+    lines.push(";; question".to_string());
+    if let Ok((name, qtype)) = response.query_name_and_type() {
+        lines.push(format!("{} {}", name, qtype));
+    }
+    let sections = [
+        ("answer", response.answers()),
+        ("authority", response.authorities()),
+        ("additional", response.additionals()),
+    ];
+    for (section, records) in sections.iter() {
+        lines.push(format!(";; {}", section));
+        let mut records: Vec<String> = records
+            .iter()
+            .map(|rr| {
+                rr.to_string()
+                    .split_whitespace()
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            })
+            .collect();
+        records.sort();
+        lines.extend(records);
+    }
+    lines.join("\n") + "\n"
+}
+
+fn golden_path(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("golden")
+        .join(format!("{}.txt", name))
+}
+
+/// assert_golden compare the raw response with the golden file tests/golden/<name>.txt.
+pub fn assert_golden(name: &str, response: &[u8]) {
+    let response = Message::parse_dns_message(response).expect("valid response");
+    let rendered = render_response(&response);
+    let path = golden_path(name);
+    if std::env::var_os(UPDATE_GOLDEN).is_some() {
+        std::fs::write(&path, &rendered).expect("write golden file");
+        return;
+    }
+    let golden = std::fs::read_to_string(&path).unwrap_or_else(|err| {
+        panic!(
+            "read golden file {}: {}, run with {}=1 to create it",
+            path.display(),
+            err,
+            UPDATE_GOLDEN
+        )
+    });
+    assert_eq!(rendered, golden, "response differ from {}", path.display());
+}
@@ -0,0 +1,83 @@
+use crate::zone::write_zone_file;
+use otterlib::setting::{ExSetting, Settings, ZoneSetting};
+use server::OtterServer;
+use std::net::SocketAddr;
+use std::sync::mpsc;
+use storage::storage::SafeRBTreeStorage;
+
+/// TestServer is an OtterServer listening on 127.0.0.1 with port 0, it runs in its own
+/// thread and runtime until the test process exits.
+#[derive(Debug, Clone, Copy)]
+pub struct TestServer {
+    pub udp: SocketAddr,
+    pub tcp: SocketAddr,
+}
+
+impl TestServer {
+    /// start run the server with the setting, the listen addresses are replaced.
+    pub fn start(settings: Settings) -> TestServer {
+        TestServer::start_on(settings, "127.0.0.1:0")
+    }
+
+    /// start_on run the server listening on the address like [::1]:0 instead of 127.0.0.1.
+    pub fn start_on(settings: Settings, listen: &str) -> TestServer {
+        TestServer::spawn(settings, listen, None)
+    }
+
+    /// with_storage run the server serving the pre-built storage instead of the zone files
+    /// of the setting, the clones kept by the test share the zone data with the server.
+    pub fn with_storage(settings: Settings, storage: SafeRBTreeStorage) -> TestServer {
+        TestServer::spawn(settings, "127.0.0.1:0", Some(storage))
+    }
+
+    fn spawn(
+        mut settings: Settings,
+        listen: &str,
+        storage: Option<SafeRBTreeStorage>,
+    ) -> TestServer {
+        settings.server.listen = vec![listen.to_string()];
+        settings.server.listen_udp = None;
+        settings.server.listen_tcp = None;
+        settings.server.allow_root = true;
+        let (sender, receiver) = mpsc::channel();
+        std::thread::spawn(move || {
+            let runtime = tokio::runtime::Runtime::new().expect("test runtime");
+            runtime.block_on(async move {
+                let extension = ExSetting {
+                    tcp_workers: 1,
+                    udp_workers: 1,
+                };
+                let mut server = match storage {
+                    Some(storage) => OtterServer::with_storage(settings, storage),
+                    None => OtterServer::new(settings),
+                };
+                server
+                    .init_network(&extension)
+                    .await
+                    .expect("bind test listeners");
+                sender.send(server.local_addrs()).unwrap();
+                if let Err(err) = server.run(&extension).await {
+                    eprintln!("test server exit: {}", err);
+                }
+            })
+        });
+        let (tcp, udp) = receiver.recv().expect("test server started");
+        TestServer {
+            udp: udp[0],
+            tcp: tcp[0],
+        }
+    }
+
+    /// with_zones start the server with the zones written from the (domain, zone text) list.
+    pub fn with_zones(zones: &[(&str, &str)]) -> TestServer {
+        let mut settings = Settings::default();
+        for (domain, text) in zones.iter() {
+            settings.zone.push(ZoneSetting {
+                domain: domain.to_string(),
+                file: write_zone_file(text).to_string_lossy().into_owned(),
+                ..Default::default()
+            });
+        }
+        TestServer::start(settings)
+    }
+}
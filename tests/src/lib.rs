@@ -1,3 +1,9 @@
+pub mod client;
+pub mod golden;
+pub mod harness;
+pub mod packet;
+pub mod zone;
+
 use otterlib::setting::{Settings, ZoneSetting};
 use server::OtterServer;
 
@@ -5,11 +11,9 @@ pub fn create_dns_server() -> OtterServer {
     let mut settings = Settings::default();
     settings.server.listen = vec!["127.0.0.1:0".to_string()];
     let zone = ZoneSetting {
-        domain: "abc.com.".to_string(),
-        file: "example.com.zone".to_string(),
-        master: None,
-        notify: None,
-        acl: None,
+        domain: "example.com.".to_string(),
+        file: zone::fixture_path("example.zone"),
+        ..Default::default()
     };
     settings.zone.push(zone);
     OtterServer::new(settings)
@@ -0,0 +1,218 @@
+use dnsproto::dnsname::DNSName;
+use dnsproto::meta::{DNSClass, DNSType};
+
+const OPT_TYPE: u16 = 41;
+
+/// PacketBuilder build raw query bytes, every header field and counter can be set to
+/// any value so malformed packets can be crafted as well.
+#[derive(Debug, Clone)]
+pub struct PacketBuilder {
+    id: u16,
+    flags: u16,
+    counts: Option<[u16; 4]>,
+    questions: Vec<Vec<u8>>,
+    edns: Option<Edns>,
+    trailing: Vec<u8>,
+}
+
+#[derive(Debug, Clone)]
+struct Edns {
+    payload: u16,
+    version: u8,
+    do_bit: bool,
+    options: Vec<u8>,
+}
+
+impl Default for PacketBuilder {
+    fn default() -> Self {
+        PacketBuilder {
+            id: 0,
+            // standard query with rd set
+            flags: 0x0100,
+            counts: None,
+            questions: vec![],
+            edns: None,
+            trailing: vec![],
+        }
+    }
+}
+
+impl PacketBuilder {
+    pub fn new() -> PacketBuilder {
+        PacketBuilder::default()
+    }
+
+    /// query return a builder with one IN question.
+    pub fn query(name: &str, qtype: DNSType) -> PacketBuilder {
+        PacketBuilder::new().question(name, qtype, DNSClass::IN)
+    }
+
+    pub fn id(mut self, id: u16) -> Self {
+        self.id = id;
+        self
+    }
+
+    /// flags set the second 16 bits of the header: qr, opcode, aa, tc, rd, ra, z, ad, cd
+    /// and rcode.
+    pub fn flags(mut self, flags: u16) -> Self {
+        self.flags = flags;
+        self
+    }
+
+    pub fn qr(self, qr: bool) -> Self {
+        self.flag(0x8000, qr)
+    }
+
+    pub fn rd(self, rd: bool) -> Self {
+        self.flag(0x0100, rd)
+    }
+
+    pub fn cd(self, cd: bool) -> Self {
+        self.flag(0x0010, cd)
+    }
+
+    pub fn opcode(mut self, opcode: u8) -> Self {
+        self.flags = (self.flags & !0x7800) | ((opcode as u16 & 0x0f) << 11);
+        self
+    }
+
+    fn flag(mut self, mask: u16, value: bool) -> Self {
+        if value {
+            self.flags |= mask;
+        } else {
+            self.flags &= !mask;
+        }
+        self
+    }
+
+    /// counts replace the question, answer, authority and additional counters computed
+    /// from the sections.
+    pub fn counts(mut self, counts: [u16; 4]) -> Self {
+        self.counts = Some(counts);
+        self
+    }
+
+    pub fn question(mut self, name: &str, qtype: DNSType, qclass: DNSClass) -> Self {
+        let mut question = DNSName::new(name, None)
+            .expect("valid query name")
+            .to_binary(None);
+        question.extend_from_slice(&u16::from(qtype).to_be_bytes());
+        question.extend_from_slice(&u16::from(qclass).to_be_bytes());
+        self.questions.push(question);
+        self
+    }
+
+    /// edns add the OPT record with the udp payload size.
+    pub fn edns(mut self, payload: u16) -> Self {
+        self.edns = Some(Edns {
+            payload,
+            version: 0,
+            do_bit: false,
+            options: vec![],
+        });
+        self
+    }
+
+    pub fn edns_version(mut self, version: u8) -> Self {
+        self.edns_mut().version = version;
+        self
+    }
+
+    pub fn do_bit(mut self) -> Self {
+        self.edns_mut().do_bit = true;
+        self
+    }
+
+    /// edns_option append an option to the OPT record, the data is not checked.
+    pub fn edns_option(mut self, code: u16, data: &[u8]) -> Self {
+        let options = &mut self.edns_mut().options;
+        options.extend_from_slice(&code.to_be_bytes());
+        options.extend_from_slice(&(data.len() as u16).to_be_bytes());
+        options.extend_from_slice(data);
+        self
+    }
+
+    fn edns_mut(&mut self) -> &mut Edns {
+        self.edns.get_or_insert(Edns {
+            payload: 1232,
+            version: 0,
+            do_bit: false,
+            options: vec![],
+        })
+    }
+
+    /// raw append the bytes at the end of the packet.
+    pub fn raw(mut self, bytes: &[u8]) -> Self {
+        self.trailing.extend_from_slice(bytes);
+        self
+    }
+
+    pub fn build(&self) -> Vec<u8> {
+        let counts = self.counts.unwrap_or([
+            self.questions.len() as u16,
+            0,
+            0,
+            self.edns.is_some() as u16,
+        ]);
+        let mut packet = vec![];
+        packet.extend_from_slice(&self.id.to_be_bytes());
+        packet.extend_from_slice(&self.flags.to_be_bytes());
+        for count in counts.iter() {
+            packet.extend_from_slice(&count.to_be_bytes());
+        }
+        for question in self.questions.iter() {
+            packet.extend_from_slice(question);
+        }
+        if let Some(edns) = &self.edns {
+            packet.push(0);
+            packet.extend_from_slice(&OPT_TYPE.to_be_bytes());
+            packet.extend_from_slice(&edns.payload.to_be_bytes());
+            packet.push(0);
+            packet.push(edns.version);
+            packet.extend_from_slice(&((edns.do_bit as u16) << 15).to_be_bytes());
+            packet.extend_from_slice(&(edns.options.len() as u16).to_be_bytes());
+            packet.extend_from_slice(&edns.options);
+        }
+        packet.extend_from_slice(&self.trailing);
+        packet
+    }
+
+    /// build_tcp return the packet with the two bytes length prefix.
+    pub fn build_tcp(&self) -> Vec<u8> {
+        let packet = self.build();
+        let mut framed = (packet.len() as u16).to_be_bytes().to_vec();
+        framed.extend_from_slice(&packet);
+        framed
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use dnsproto::message::Message;
+
+    #[test]
+    fn test_packet_builder() {
+        let packet = PacketBuilder::query("example.com.", DNSType::A)
+            .id(0xa4ac)
+            .edns(4096)
+            .do_bit()
+            .edns_option(10, &[1, 2, 3, 4, 5, 6, 7, 8])
+            .build();
+        let message = Message::parse_dns_message(&packet).unwrap();
+        assert_eq!(message.header.id(), 0xa4ac);
+        let (name, qtype) = message.query_name_and_type().unwrap();
+        assert_eq!(name.to_string(), "example.com.");
+        assert_eq!(*qtype, DNSType::A);
+        let edns = message.edns().unwrap();
+        assert_eq!(edns.cookie(), Some(&[1u8, 2, 3, 4, 5, 6, 7, 8][..]));
+
+        // header only
+        assert_eq!(
+            PacketBuilder::new().id(0xa4ac).build(),
+            vec![0xa4, 0xac, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00]
+        );
+        let tcp = PacketBuilder::new().opcode(5).rd(false).build_tcp();
+        assert_eq!(&tcp[..6], &[0x00, 0x0c, 0x00, 0x00, 0x28, 0x00][..]);
+    }
+}
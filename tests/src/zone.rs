@@ -0,0 +1,43 @@
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use storage::storage::SafeRBTreeStorage;
+
+static ZONE_FILE_INDEX: AtomicUsize = AtomicUsize::new(0);
+
+/// EXAMPLE_ZONE is the example.com. zone used by most tests, the same data as
+/// storage/test/example.zone without the comments.
+pub const EXAMPLE_ZONE: &str = "$ORIGIN example.com.
+$TTL 3600
+example.com.  IN  SOA   ns.example.com. username.example.com. ( 2020091025 7200 3600 1209600 3600 )
+example.com.  IN  NS    ns
+example.com.  IN  NS    ns.somewhere.example.
+example.com.  IN  MX    10 mail.example.com.
+example.com.  IN  A     192.0.2.1
+              IN  AAAA  2001:db8:10::1
+ns            IN  A     192.0.2.2
+              IN  AAAA  2001:db8:10::2
+www           IN  CNAME example.com.
+mail          IN  A     192.0.2.3
+";
+
+/// zone_from_str build the storage from the zone text, panic if the text is not valid.
+pub fn zone_from_str(text: &str) -> SafeRBTreeStorage {
+    SafeRBTreeStorage::new_zone_from_str(text, None).expect("valid zone text")
+}
+
+/// write_zone_file write the zone text to a new file in the temp directory and return
+/// the path, used when the server load the zone from the setting.
+pub fn write_zone_file(text: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!(
+        "otter_test_{}_{}.zone",
+        std::process::id(),
+        ZONE_FILE_INDEX.fetch_add(1, Ordering::SeqCst)
+    ));
+    std::fs::write(&path, text).expect("write zone file");
+    path
+}
+
+/// fixture_path return the path of a zone file in storage/test.
+pub fn fixture_path(name: &str) -> String {
+    format!("{}/../storage/test/{}", env!("CARGO_MANIFEST_DIR"), name)
+}
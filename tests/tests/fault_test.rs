@@ -0,0 +1,226 @@
+use dnsproto::dnsname::DNSName;
+use dnsproto::message::Message;
+use dnsproto::meta::{DNSType, RCode};
+use otter_tests::client::send_udp_query;
+use otter_tests::harness::TestServer;
+use otter_tests::packet::PacketBuilder;
+use otter_tests::zone::{zone_from_str, EXAMPLE_ZONE};
+use otterlib::errors::{NetworkError, OtterError, TransferError};
+use otterlib::fault::{Fault, FaultInjector};
+use otterlib::setting::Settings;
+use server::dnsclient::{self, QueryOption};
+use server::transfer::{transfer_in, Master, MasterList, TransferLimits};
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+
+async fn query_rcode(server: &TestServer, name: &str) -> RCode {
+    let query = PacketBuilder::query(name, DNSType::A).build();
+    let response = send_udp_query(server.udp, &query).await.unwrap();
+    Message::parse_dns_message(&response)
+        .unwrap()
+        .header
+        .rcode()
+}
+
+#[tokio::test]
+async fn query_deadline_with_slow_storage() {
+    let mut settings = Settings::default();
+    settings.server.query_timeout = Some(100);
+    let storage = zone_from_str(EXAMPLE_ZONE);
+    let server = TestServer::with_storage(settings, storage.clone());
+    assert_eq!(
+        query_rcode(&server, "mail.example.com.").await,
+        RCode::NoError
+    );
+
+    let injector = Arc::new(FaultInjector::new());
+    injector.add(Fault::Delay(Duration::from_millis(300)), 100);
+    storage.set_fault_injector(Some(injector.clone()));
+    assert_eq!(
+        query_rcode(&server, "mail.example.com.").await,
+        RCode::ServerFailure
+    );
+    assert!(injector.injected() > 0);
+
+    storage.set_fault_injector(None);
+    assert_eq!(
+        query_rcode(&server, "ns.example.com.").await,
+        RCode::NoError
+    );
+}
+
+#[tokio::test]
+async fn udp_loop_survives_storage_errors() {
+    let storage = zone_from_str(EXAMPLE_ZONE);
+    let server = TestServer::with_storage(Settings::default(), storage.clone());
+    let injector = Arc::new(FaultInjector::new());
+    injector.add(Fault::Error, 100);
+    storage.set_fault_injector(Some(injector.clone()));
+    for _ in 0..3 {
+        assert_eq!(
+            query_rcode(&server, "mail.example.com.").await,
+            RCode::ServerFailure
+        );
+    }
+
+    // every query is still answered when half of the lookups fail
+    injector.clear();
+    injector.add(Fault::Error, 50);
+    let mut rcodes = vec![];
+    for _ in 0..10 {
+        rcodes.push(query_rcode(&server, "mail.example.com.").await);
+    }
+    assert!(rcodes.contains(&RCode::ServerFailure));
+    assert!(rcodes.contains(&RCode::NoError));
+
+    storage.set_fault_injector(None);
+    assert_eq!(
+        query_rcode(&server, "mail.example.com.").await,
+        RCode::NoError
+    );
+}
+
+/// scripted_master answer every axfr request with the example zone in one message, the
+/// response is encoded once and only the id is copied from each request.
+async fn scripted_master() -> SocketAddr {
+    let storage = zone_from_str(EXAMPLE_ZONE);
+    let apex = DNSName::new("example.com.", None).unwrap();
+    // the records and messages are not Send, keep only the encoded response in the task
+    let response = {
+        let mut message = Message::new_transfer_message(&Message::new_axfr_query(&apex));
+        for rr in storage.transfer_records(&apex).unwrap() {
+            message.append_answer(rr);
+        }
+        message.encode(true).unwrap()
+    };
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let master = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        while let Ok((mut stream, _)) = listener.accept().await {
+            let size = stream.read_u16().await.unwrap();
+            let mut request = vec![0; size as usize];
+            stream.read_exact(&mut request).await.unwrap();
+            let mut response = response.clone();
+            response[..2].copy_from_slice(&request[..2]);
+            stream.write_u16(response.len() as u16).await.unwrap();
+            let _ = stream.write_all(&response).await;
+        }
+    });
+    master
+}
+
+fn master(id: &str, addr: SocketAddr) -> Master {
+    Master {
+        id: id.to_string(),
+        addr,
+        key: None,
+        source: None,
+    }
+}
+
+/// refresh try the candidate masters at now like the transfer scheduler, the id of the
+/// master which succeeded is returned.
+async fn refresh(masters: &mut MasterList, now: u64) -> Option<String> {
+    let zone = DNSName::new("example.com.", None).unwrap();
+    let option = QueryOption {
+        timeout: Duration::from_millis(200),
+        ..Default::default()
+    };
+    for index in masters.candidates(now) {
+        let master = masters.get(index).unwrap().clone();
+        match transfer_in(master.addr, &zone, &option, TransferLimits::default()).await {
+            Ok(_) => {
+                masters.mark_success(index);
+                return Some(master.id);
+            }
+            Err(_) => masters.mark_failure(index, now),
+        }
+    }
+    None
+}
+
+#[tokio::test]
+async fn transfer_retry_with_backoff() {
+    let (primary, backup) = (scripted_master().await, scripted_master().await);
+    let injector = Arc::new(FaultInjector::new());
+    injector.add(Fault::Drop, 100);
+    dnsclient::set_fault_injector(primary, Some(injector.clone()));
+    let mut masters = MasterList::new(vec![master("primary", primary), master("backup", backup)]);
+
+    // the dropped transfer timeouts and the backup master is used
+    assert_eq!(refresh(&mut masters, 1000).await.as_deref(), Some("backup"));
+    assert_eq!(masters.preferred().unwrap().id, "backup");
+    // the primary is skipped until the first backoff(10 seconds) lapses
+    assert_eq!(masters.candidates(1005), vec![1]);
+    assert_eq!(masters.candidates(1010), vec![1, 0]);
+
+    // the backup fails too, nothing is tried until the backoff lapses
+    dnsclient::set_fault_injector(backup, Some(injector.clone()));
+    assert_eq!(refresh(&mut masters, 1010).await, None);
+    assert!(masters.candidates(1019).is_empty());
+    // the second failure of the primary doubles its backoff
+    assert_eq!(masters.candidates(1020), vec![1]);
+    assert_eq!(masters.candidates(1030), vec![1, 0]);
+
+    dnsclient::set_fault_injector(primary, None);
+    assert_eq!(
+        refresh(&mut masters, 1030).await.as_deref(),
+        Some("primary")
+    );
+    assert_eq!(masters.preferred().unwrap().id, "primary");
+    dnsclient::set_fault_injector(backup, None);
+}
+
+#[tokio::test]
+async fn transfer_with_damaged_responses() {
+    let remote = scripted_master().await;
+    let zone = DNSName::new("example.com.", None).unwrap();
+    let option = QueryOption {
+        timeout: Duration::from_millis(200),
+        ..Default::default()
+    };
+    let injector = Arc::new(FaultInjector::new());
+    dnsclient::set_fault_injector(remote, Some(injector.clone()));
+
+    injector.add_times(Fault::Drop, 1);
+    match transfer_in(remote, &zone, &option, TransferLimits::default()).await {
+        Err(OtterError::NetworkError(NetworkError::QueryTimeout(addr))) => {
+            assert_eq!(addr, remote.to_string())
+        }
+        other => panic!("expect timeout, got {:?}", other.map(|m| m.len())),
+    }
+
+    injector.add_times(Fault::Corrupt, 1);
+    assert!(
+        transfer_in(remote, &zone, &option, TransferLimits::default())
+            .await
+            .is_err()
+    );
+
+    // the closing soa is received twice
+    injector.add_times(Fault::Duplicate, 1);
+    match transfer_in(remote, &zone, &option, TransferLimits::default()).await {
+        Err(OtterError::TransferError(err)) => assert_eq!(
+            err,
+            TransferError::SOAMismatch(
+                "example.com.".to_string(),
+                "records after the closing soa".to_string()
+            )
+        ),
+        other => panic!("expect rejected, got {:?}", other.map(|m| m.len())),
+    }
+
+    injector.add_times(Fault::Delay(Duration::from_millis(50)), 1);
+    assert_eq!(
+        transfer_in(remote, &zone, &option, TransferLimits::default())
+            .await
+            .unwrap()
+            .len(),
+        1
+    );
+    assert_eq!(injector.injected(), 4);
+    dnsclient::set_fault_injector(remote, None);
+}
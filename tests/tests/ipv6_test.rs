@@ -0,0 +1,117 @@
+use dnsproto::dnsname::DNSName;
+use dnsproto::message::Message;
+use dnsproto::meta::{DNSType, RCode, ResourceRecord};
+use otter_tests::client::{send_tcp_query, send_udp_query};
+use otter_tests::harness::TestServer;
+use otter_tests::packet::PacketBuilder;
+use otter_tests::zone::write_zone_file;
+use otterlib::setting::{Settings, ZoneSetting, ACL};
+
+const V6_ZONE: &str = "$ORIGIN v6.example.
+$TTL 3600
+@             IN  SOA   ns1 hostmaster ( 2020091025 7200 3600 1209600 3600 )
+@             IN  NS    ns1
+ns1           IN  AAAA  2001:db8::53
+www           IN  AAAA  2001:db8::80
+sub           IN  NS    ns.sub
+ns.sub        IN  AAAA  2001:db8:1::53
+";
+
+/// ipv6_available check the loopback ::1 can be bound, some ci containers have no ipv6.
+fn ipv6_available() -> bool {
+    std::net::UdpSocket::bind("[::1]:0").is_ok()
+}
+
+fn v6_settings(acl: Option<&str>) -> Settings {
+    let mut settings = Settings::default();
+    let mut zone = ZoneSetting {
+        domain: "v6.example.".to_string(),
+        file: write_zone_file(V6_ZONE).to_string_lossy().into_owned(),
+        ..Default::default()
+    };
+    if let Some(address) = acl {
+        settings.acl.push(ACL {
+            id: "v6_clients".to_string(),
+            address: address.to_string(),
+            action: "query".to_string(),
+            key: None,
+        });
+        zone.allow_query = Some(vec!["v6_clients".to_string()]);
+    }
+    settings.zone.push(zone);
+    settings
+}
+
+fn names(records: &[&ResourceRecord]) -> Vec<DNSName> {
+    records.iter().map(|rr| rr.get_dname().clone()).collect()
+}
+
+#[tokio::test]
+async fn aaaa_only_zone_over_ipv6() {
+    if !ipv6_available() {
+        eprintln!("skip aaaa_only_zone_over_ipv6: ::1 is not available");
+        return;
+    }
+    let server = TestServer::start_on(v6_settings(None), "[::1]:0");
+    assert!(server.udp.is_ipv6());
+    assert!(server.tcp.is_ipv6());
+
+    let query = PacketBuilder::query("www.v6.example.", DNSType::AAAA).build();
+    for response in vec![
+        send_udp_query(server.udp, &query).await.unwrap(),
+        send_tcp_query(server.tcp, &query).await.unwrap(),
+    ] {
+        let response = Message::parse_dns_message(&response).unwrap();
+        assert_eq!(response.header.rcode(), RCode::NoError);
+        let answers = response.answers();
+        assert_eq!(answers.len(), 1);
+        assert_eq!(answers[0].get_type(), DNSType::AAAA);
+    }
+
+    // no A record in the zone, the answer is empty but the name exists
+    let query = PacketBuilder::query("www.v6.example.", DNSType::A).build();
+    let response = send_udp_query(server.udp, &query).await.unwrap();
+    let response = Message::parse_dns_message(&response).unwrap();
+    assert_eq!(response.header.rcode(), RCode::NoError);
+    assert!(response.answers().is_empty());
+
+    // the referral carry the AAAA glue of the delegated name server
+    let query = PacketBuilder::query("www.sub.v6.example.", DNSType::AAAA).build();
+    let response = send_udp_query(server.udp, &query).await.unwrap();
+    let response = Message::parse_dns_message(&response).unwrap();
+    assert!(response.answers().is_empty());
+    assert_eq!(
+        names(&response.authorities()),
+        vec![DNSName::new("sub.v6.example.", None).unwrap()]
+    );
+    let glue: Vec<_> = response
+        .additionals()
+        .into_iter()
+        .filter(|rr| rr.get_type() == DNSType::AAAA)
+        .collect();
+    assert_eq!(
+        names(&glue),
+        vec![DNSName::new("ns.sub.v6.example.", None).unwrap()]
+    );
+}
+
+#[tokio::test]
+async fn ipv6_acl_prefix() {
+    if !ipv6_available() {
+        eprintln!("skip ipv6_acl_prefix: ::1 is not available");
+        return;
+    }
+    let query = PacketBuilder::query("www.v6.example.", DNSType::AAAA).build();
+
+    // ::1 is out of the documentation prefix
+    let server = TestServer::start_on(v6_settings(Some("2001:db8::/32")), "[::1]:0");
+    let response = send_udp_query(server.udp, &query).await.unwrap();
+    let response = Message::parse_dns_message(&response).unwrap();
+    assert_eq!(response.header.rcode(), RCode::Refused);
+
+    let server = TestServer::start_on(v6_settings(Some("::1/128")), "[::1]:0");
+    let response = send_udp_query(server.udp, &query).await.unwrap();
+    let response = Message::parse_dns_message(&response).unwrap();
+    assert_eq!(response.header.rcode(), RCode::NoError);
+    assert_eq!(response.answers().len(), 1);
+}
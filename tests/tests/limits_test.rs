@@ -0,0 +1,120 @@
+use dnsproto::message::Message;
+use dnsproto::meta::{DNSType, RCode};
+use otter_tests::client::{send_tcp_query, send_udp_query, send_udp_query_with_timeout};
+use otter_tests::harness::TestServer;
+use otter_tests::packet::PacketBuilder;
+use otter_tests::zone::{zone_from_str, EXAMPLE_ZONE};
+use otterlib::fault::{Fault, FaultInjector};
+use otterlib::setting::Settings;
+use std::io;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+fn limited_server(max_tcp_clients: Option<usize>, per_ip: Option<usize>) -> TestServer {
+    let mut settings = Settings::default();
+    // idle connections are held open for the whole test
+    settings.server.query_timeout = Some(5000);
+    settings.server.max_tcp_clients = max_tcp_clients;
+    settings.server.max_tcp_clients_per_ip = per_ip;
+    TestServer::with_storage(settings, zone_from_str(EXAMPLE_ZONE))
+}
+
+/// assert_refused check the server closes the connection without reading a query.
+async fn assert_refused(stream: &mut TcpStream) {
+    let mut buffer = [0u8; 2];
+    match tokio::time::timeout(Duration::from_secs(1), stream.read(&mut buffer)).await {
+        Ok(Ok(0)) | Ok(Err(_)) => {}
+        other => panic!("expect the connection closed, got {:?}", other),
+    }
+}
+
+async fn tcp_rcode(stream: &mut TcpStream) -> io::Result<RCode> {
+    let query = PacketBuilder::query("mail.example.com.", DNSType::A).build_tcp();
+    stream.write_all(&query).await?;
+    let size = stream.read_u16().await?;
+    let mut response = vec![0u8; size as usize];
+    stream.read_exact(&mut response).await?;
+    Ok(Message::parse_dns_message(&response)
+        .unwrap()
+        .header
+        .rcode())
+}
+
+/// connect_until_accepted retry the query until the closed connections release their
+/// slots in the server.
+async fn connect_until_accepted(server: &TestServer) -> RCode {
+    let query = PacketBuilder::query("mail.example.com.", DNSType::A).build();
+    for _ in 0..20 {
+        if let Ok(response) = send_tcp_query(server.tcp, &query).await {
+            return Message::parse_dns_message(&response)
+                .unwrap()
+                .header
+                .rcode();
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    panic!("tcp connection never accepted")
+}
+
+#[tokio::test]
+async fn tcp_clients_over_global_limit_are_refused() {
+    let limit = 3;
+    let server = limited_server(Some(limit), None);
+    let mut streams = vec![];
+    for _ in 0..limit {
+        streams.push(TcpStream::connect(server.tcp).await.unwrap());
+    }
+    let mut last = TcpStream::connect(server.tcp).await.unwrap();
+    assert_refused(&mut last).await;
+
+    // the connections within the limit are still served
+    assert_eq!(tcp_rcode(&mut streams[0]).await.unwrap(), RCode::NoError);
+    streams.clear();
+    assert_eq!(connect_until_accepted(&server).await, RCode::NoError);
+}
+
+#[tokio::test]
+async fn tcp_clients_over_per_ip_limit_are_refused() {
+    let server = limited_server(None, Some(1));
+    let mut first = TcpStream::connect(server.tcp).await.unwrap();
+    let mut second = TcpStream::connect(server.tcp).await.unwrap();
+    assert_refused(&mut second).await;
+    assert_eq!(tcp_rcode(&mut first).await.unwrap(), RCode::NoError);
+    drop(first);
+    assert_eq!(connect_until_accepted(&server).await, RCode::NoError);
+}
+
+#[tokio::test]
+async fn udp_queries_over_in_flight_ceiling_are_dropped() {
+    let mut settings = Settings::default();
+    settings.server.query_timeout = Some(1000);
+    settings.server.max_udp_in_flight = Some(1);
+    let storage = zone_from_str(EXAMPLE_ZONE);
+    let server = TestServer::with_storage(settings, storage.clone());
+    let injector = Arc::new(FaultInjector::new());
+    injector.add(Fault::Delay(Duration::from_millis(500)), 100);
+    storage.set_fault_injector(Some(injector));
+
+    let query = PacketBuilder::query("mail.example.com.", DNSType::A).build();
+    let slow = {
+        let query = query.clone();
+        tokio::spawn(async move { send_udp_query(server.udp, &query).await })
+    };
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    let dropped = send_udp_query_with_timeout(server.udp, &query, Duration::from_millis(300)).await;
+    assert_eq!(dropped.unwrap_err().kind(), io::ErrorKind::TimedOut);
+    // the query in process is answered, SERVFAIL when the delays pass the deadline
+    assert!(slow.await.unwrap().is_ok());
+
+    storage.set_fault_injector(None);
+    let response = send_udp_query(server.udp, &query).await.unwrap();
+    assert_eq!(
+        Message::parse_dns_message(&response)
+            .unwrap()
+            .header
+            .rcode(),
+        RCode::NoError
+    );
+}
@@ -0,0 +1,153 @@
+use dnsproto::dnsname::DNSName;
+use otter_tests::zone::fixture_path;
+use otterlib::setting::{Masters, ZoneSetting};
+use server::transfer::{load_secondary, Clock, TransferScheduler, ZoneFreshness};
+use server::zonestate::{state_path, ZoneState};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use storage::storage::{SafeRBTreeStorage, ZoneOption};
+use storage::writer::write_zone_file;
+
+const NOW: u64 = 1_600_000_000;
+
+#[derive(Debug)]
+struct TestClock(AtomicU64);
+
+impl Clock for TestClock {
+    fn now(&self) -> u64 {
+        self.0.load(Ordering::SeqCst)
+    }
+}
+
+fn secondary_setting(file: &str) -> ZoneSetting {
+    ZoneSetting {
+        domain: "example.com.".to_string(),
+        file: file.to_string(),
+        storage: Some(std::env::temp_dir().to_string_lossy().into_owned()),
+        master: Some(Masters::from("master")),
+        ..Default::default()
+    }
+}
+
+#[test]
+fn secondary_zone_restart() {
+    let apex = DNSName::new("example.com.", None).unwrap();
+    let zone = secondary_setting("otter_secondary_restart.zone");
+    let path = zone.storage_file();
+    let path = path.as_str();
+
+    // the zone was transferred 100 seconds before the restart
+    let transferred =
+        SafeRBTreeStorage::new_zone_from_file(&fixture_path("example.zone"), None).unwrap();
+    write_zone_file(&transferred, &apex, path, NOW - 100).unwrap();
+
+    let clock = Arc::new(TestClock(AtomicU64::new(NOW)));
+    let scheduler = TransferScheduler::new(clock.clone());
+    let mut storage = SafeRBTreeStorage::default();
+    assert!(load_secondary(
+        &mut storage,
+        &scheduler,
+        &zone,
+        &ZoneOption::default()
+    ));
+    assert_eq!(storage.get_zone_soa(&apex).unwrap().serial(), 2020091025);
+    // the refresh timer(7200 seconds) resumes from the transfer time
+    assert_eq!(scheduler.freshness(&apex), Some(ZoneFreshness::Fresh));
+    clock.0.store(NOW + 7100, Ordering::SeqCst);
+    assert_eq!(scheduler.freshness(&apex), Some(ZoneFreshness::NeedRefresh));
+
+    // a corrupted copy is not loaded and the zone is transferred at once
+    std::fs::write(path, "example.com. IN SOA broken\n").unwrap();
+    let scheduler = TransferScheduler::new(clock.clone());
+    let mut storage = SafeRBTreeStorage::default();
+    assert!(!load_secondary(
+        &mut storage,
+        &scheduler,
+        &zone,
+        &ZoneOption::default()
+    ));
+    assert_eq!(storage.find_zone(&apex), None);
+    assert_eq!(scheduler.zones_need_refresh(), vec![apex.clone()]);
+
+    // so is a missing copy
+    std::fs::remove_file(path).unwrap();
+    let scheduler = TransferScheduler::new(clock);
+    let mut storage = SafeRBTreeStorage::default();
+    assert!(!load_secondary(
+        &mut storage,
+        &scheduler,
+        &zone,
+        &ZoneOption::default()
+    ));
+    assert_eq!(scheduler.zones_need_refresh(), vec![apex]);
+}
+
+#[test]
+fn secondary_zone_restart_with_state() {
+    let apex = DNSName::new("example.com.", None).unwrap();
+    let zone = secondary_setting("otter_secondary_state.zone");
+    let path = zone.storage_file();
+    let path = path.as_str();
+    let transferred =
+        SafeRBTreeStorage::new_zone_from_file(&fixture_path("example.zone"), None).unwrap();
+    write_zone_file(&transferred, &apex, path, NOW - 100).unwrap();
+    let soa = transferred.get_zone_soa(&apex).unwrap();
+
+    // the master confirmed the serial 1000 seconds after the transfer and a later
+    // refresh failed, the zone file is not written again
+    let clock = Arc::new(TestClock(AtomicU64::new(NOW)));
+    let scheduler = TransferScheduler::new(clock.clone());
+    let mut storage = SafeRBTreeStorage::default();
+    assert!(load_secondary(
+        &mut storage,
+        &scheduler,
+        &zone,
+        &ZoneOption::default()
+    ));
+    clock.0.store(NOW + 1000, Ordering::SeqCst);
+    assert!(!scheduler.check_serial(&apex, &soa));
+    scheduler.set_master(&apex, "master2");
+    clock.0.store(NOW + 3000, Ordering::SeqCst);
+    scheduler.mark_failed(&apex);
+    scheduler.save_state(&apex, path);
+
+    // the restarted scheduler resumes the timers from the state instead of the transfer
+    clock.0.store(NOW + 7150, Ordering::SeqCst);
+    let scheduler = TransferScheduler::new(clock.clone());
+    let mut storage = SafeRBTreeStorage::default();
+    assert!(load_secondary(
+        &mut storage,
+        &scheduler,
+        &zone,
+        &ZoneOption::default()
+    ));
+    assert_eq!(scheduler.freshness(&apex), Some(ZoneFreshness::Fresh));
+    assert_eq!(
+        scheduler.zone_state(&apex),
+        Some(ZoneState {
+            serial: soa.serial(),
+            last_success: Some(NOW + 1000),
+            last_failure: Some(NOW + 3000),
+            master: Some("master2".to_string()),
+        })
+    );
+    clock.0.store(NOW + 8200, Ordering::SeqCst);
+    assert_eq!(scheduler.zones_need_refresh(), vec![apex.clone()]);
+
+    // a corrupted state is ignored and the timers resume from the transfer header
+    std::fs::write(state_path(path), "serial = broken\n").unwrap();
+    clock.0.store(NOW + 7150, Ordering::SeqCst);
+    let scheduler = TransferScheduler::new(clock);
+    let mut storage = SafeRBTreeStorage::default();
+    assert!(load_secondary(
+        &mut storage,
+        &scheduler,
+        &zone,
+        &ZoneOption::default()
+    ));
+    assert_eq!(scheduler.freshness(&apex), Some(ZoneFreshness::NeedRefresh));
+    assert_eq!(scheduler.zone_state(&apex).unwrap().master, None);
+
+    std::fs::remove_file(path).unwrap();
+    std::fs::remove_file(state_path(path)).unwrap();
+}
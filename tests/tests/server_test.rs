@@ -1 +1,74 @@
+use dnsproto::message::Message;
+use dnsproto::meta::{DNSType, RCode};
+use otter_tests::client::{send_tcp_query, send_udp_query, send_udp_query_with_timeout};
+use otter_tests::golden::assert_golden;
+use otter_tests::harness::TestServer;
+use otter_tests::packet::PacketBuilder;
+use otter_tests::zone::EXAMPLE_ZONE;
+use std::io::ErrorKind;
+use std::time::Duration;
 
+fn example_server() -> TestServer {
+    TestServer::with_zones(&[("example.com.", EXAMPLE_ZONE)])
+}
+
+#[tokio::test]
+async fn query_over_udp_and_tcp() {
+    let server = example_server();
+    let query = PacketBuilder::query("mail.example.com.", DNSType::A).build();
+    let response = send_udp_query(server.udp, &query).await.unwrap();
+    assert_golden("mail_example_com_a", &response);
+    let response = send_tcp_query(server.tcp, &query).await.unwrap();
+    assert_golden("mail_example_com_a", &response);
+}
+
+#[tokio::test]
+async fn query_without_question() {
+    let server = example_server();
+    // header only
+    let query = PacketBuilder::new().id(0xa4ac).build();
+    let response = send_udp_query(server.udp, &query).await.unwrap();
+    let response = Message::parse_dns_message(&response).unwrap();
+    assert_eq!(response.header.rcode(), RCode::FormatError);
+    assert_eq!(response.header.id(), 0xa4ac);
+
+    // cookie only query
+    let query = PacketBuilder::new()
+        .id(0xa4ac)
+        .edns(4096)
+        .edns_option(10, &[1, 2, 3, 4, 5, 6, 7, 8])
+        .build();
+    let response = send_udp_query(server.udp, &query).await.unwrap();
+    let response = Message::parse_dns_message(&response).unwrap();
+    assert_eq!(response.header.rcode(), RCode::NoError);
+    assert!(response.answers().is_empty());
+    assert_eq!(
+        response.edns().unwrap().cookie(),
+        Some(&[1u8, 2, 3, 4, 5, 6, 7, 8][..])
+    );
+}
+
+#[tokio::test]
+async fn query_with_excessive_records() {
+    let server = example_server();
+    // 30 bytes query claims 65535 answers
+    let query = PacketBuilder::query("com.", DNSType::A)
+        .id(0xa4ac)
+        .counts([1, 0xffff, 0, 0])
+        .raw(&[0xc0, 0x0c, 0x00, 0x01, 0x00, 0x01, 0x00, 0x00, 0x00]);
+    let response = send_udp_query(server.udp, &query.build()).await.unwrap();
+    let response = Message::parse_dns_message(&response).unwrap();
+    assert_eq!(response.header.rcode(), RCode::FormatError);
+    assert_eq!(response.header.id(), 0xa4ac);
+    assert!(response.answers().is_empty());
+
+    // a response packet is still dropped
+    let err = send_udp_query_with_timeout(
+        server.udp,
+        &query.qr(true).build(),
+        Duration::from_millis(300),
+    )
+    .await
+    .unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::TimedOut);
+}
@@ -0,0 +1,76 @@
+use dnsproto::dnsname::DNSName;
+use otter_tests::harness::TestServer;
+use otter_tests::zone::fixture_path;
+use otterlib::setting::{Remote, Settings, ZoneSetting, ACL};
+use server::dnsclient::QueryOption;
+use server::transfer::{query_soa, transfer_in, TransferLimits};
+use std::time::Duration;
+
+/// secondary_loopback_available check 127.0.0.2 can be bound, only linux route the whole
+/// 127.0.0.0/8 to the loopback interface by default.
+fn secondary_loopback_available() -> bool {
+    std::net::UdpSocket::bind("127.0.0.2:0").is_ok()
+}
+
+/// master_settings serve example.com. to queries and transfers from 127.0.0.2 only.
+fn master_settings() -> Settings {
+    let mut settings = Settings::default();
+    for action in ["query", "transfer"].iter() {
+        settings.acl.push(ACL {
+            id: format!("secondary_{}", action),
+            address: "127.0.0.2/32".to_string(),
+            action: action.to_string(),
+            key: None,
+        });
+    }
+    settings.zone.push(ZoneSetting {
+        domain: "example.com.".to_string(),
+        file: fixture_path("example.zone"),
+        allow_query: Some(vec!["secondary_query".to_string()]),
+        acl: Some(vec!["secondary_transfer".to_string()]),
+        ..Default::default()
+    });
+    settings
+}
+
+#[tokio::test]
+async fn transfer_from_source_address() {
+    if !secondary_loopback_available() {
+        eprintln!("skip transfer_from_source_address: 127.0.0.2 is not available");
+        return;
+    }
+    let master = TestServer::start(master_settings());
+    let remote = Remote {
+        id: "master".to_string(),
+        address: format!("127.0.0.1@{}", master.tcp.port()),
+        source_address: Some("127.0.0.2".to_string()),
+        ..Default::default()
+    };
+    assert_eq!(remote.socket_addr().unwrap(), master.tcp);
+    let zone = DNSName::new("example.com.", None).unwrap();
+    let option = QueryOption {
+        timeout: Duration::from_secs(2),
+        source: remote.source_addr(None).unwrap(),
+        ..Default::default()
+    };
+
+    // the master acl only see the query and the transfer from the source address
+    let soa = query_soa(master.udp, &zone, &option).await.unwrap();
+    assert_eq!(soa.serial(), 2020091025);
+    let messages = transfer_in(master.tcp, &zone, &option, TransferLimits::default())
+        .await
+        .unwrap();
+    assert!(!messages.is_empty());
+
+    // from 127.0.0.1 both are refused
+    let option = QueryOption {
+        source: None,
+        ..option
+    };
+    assert!(query_soa(master.udp, &zone, &option).await.is_err());
+    assert!(
+        transfer_in(master.tcp, &zone, &option, TransferLimits::default())
+            .await
+            .is_err()
+    );
+}